@@ -0,0 +1,231 @@
+//! Preferences loaded at startup, shared by the terminal REPL and the kernel
+//!
+//! Top-level keys are session options, with the names and values of `:set`, so the file,
+//! `--set` on the command line and `:set`/`%set` in a session all change them the same way.
+//! Tables hold what isn't about the session:
+//!
+//! ```toml
+//! plutus-version = "v2"
+//! trace-level = "verbose"
+//! max-depth = 4
+//! budget-cpu = 10_000_000_000
+//!
+//! [history]
+//! file = "~/notes/aiken-history"
+//! size = 500
+//!
+//! [kernel]
+//! progress-delay = 2.5
+//! max-output = 1_000_000
+//! error-format = "html"
+//! ```
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use serde::Deserialize;
+
+use crate::evaluator::{ReplError, ReplEvaluator};
+
+/// `$XDG_CONFIG_HOME/iaiken/config.toml` (`~/.config/...`) on Linux, and the configuration
+/// directory of other platforms
+pub fn default_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("iaiken").join("config.toml"))
+}
+
+/// `name=value`, an option given on the command line
+pub fn parse_option(option: &str) -> Result<(String, String), String> {
+    option
+        .split_once('=')
+        .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+        .ok_or_else(|| format!("expected OPTION=VALUE, got `{}`", option))
+}
+
+/// Contents of a configuration file. Everything is optional: what isn't set keeps its default.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Config {
+    /// Session options, by name, as given to `:set`
+    pub options: Vec<(String, String)>,
+    pub history: HistoryConfig,
+    pub kernel: KernelConfig,
+}
+
+/// The `[history]` table
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HistoryConfig {
+    /// Where inputs are saved between sessions. A leading `~` is the home directory.
+    pub file: Option<PathBuf>,
+    /// Number of inputs kept
+    pub size: Option<usize>,
+}
+
+/// The `[kernel]` table
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct KernelConfig {
+    /// Seconds a cell runs before the kernel shows how long it has been running
+    pub progress_delay: Option<f64>,
+    /// Bytes a cell prints at most, in its streams and its result
+    pub max_output: Option<usize>,
+    /// Messages queued for IOPub before cells wait for clients to catch up
+    pub iopub_queue: Option<usize>,
+    /// How error reports are sent to frontends
+    pub error_format: Option<ErrorFormat>,
+}
+
+/// How the kernel sends error reports. Colors are escape codes that some frontends render and
+/// others print as they are.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ErrorFormat {
+    /// A traceback with colors
+    #[default]
+    Ansi,
+    /// A traceback without escape codes
+    Plain,
+    /// A display of the report in HTML, with its colors, and a plain text fallback
+    Html,
+}
+
+impl KernelConfig {
+    /// The progress delay, if it's set and a valid duration
+    pub fn progress_delay(&self) -> Option<Duration> {
+        self.progress_delay
+            .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+    }
+}
+
+impl Config {
+    /// Read the file at `path`. A missing file is the default configuration.
+    pub fn load(path: &Path) -> Result<Self, ReplError> {
+        match fs::read_to_string(path) {
+            Ok(content) => Self::parse(&content).map_err(|message| ReplError::InvalidSetting {
+                message: format!("{}: {}", path.display(), message),
+            }),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    pub fn parse(content: &str) -> Result<Self, String> {
+        let table: toml::Table = content.parse().map_err(|e| format!("{e}"))?;
+        let mut config = Self::default();
+        for (key, value) in table {
+            match (key.as_str(), value) {
+                ("history", value) => {
+                    config.history = value.try_into().map_err(|e| format!("[history]: {e}"))?;
+                    config.history.file = config.history.file.map(|file| expand_home(&file));
+                }
+                ("kernel", value) => {
+                    config.kernel = value.try_into().map_err(|e| format!("[kernel]: {e}"))?
+                }
+                (_, toml::Value::String(value)) => config.options.push((key, value)),
+                (_, value @ (toml::Value::Integer(_) | toml::Value::Float(_))) => {
+                    config.options.push((key, value.to_string()))
+                }
+                // Switches, eg. `warnings = false`
+                (_, toml::Value::Boolean(enabled)) => {
+                    let value = if enabled { "on" } else { "off" };
+                    config.options.push((key, value.to_string()))
+                }
+                _ => return Err(format!("`{}` must be a string, a number or a boolean", key)),
+            }
+        }
+        Ok(config)
+    }
+
+    /// Set option `name`, replacing its value from the file if any, eg. for `--set`
+    pub fn set(&mut self, name: &str, value: &str) {
+        self.options.retain(|(option, _)| option != name);
+        self.options.push((name.to_string(), value.to_string()));
+    }
+
+    /// Set the options on `eval`. Invalid options are skipped, and returned.
+    pub fn apply(&self, eval: &mut ReplEvaluator) -> Vec<ReplError> {
+        self.options
+            .iter()
+            .filter_map(|(name, value)| eval.set_option(name, value).err())
+            .collect()
+    }
+}
+
+/// `path`, with a leading `~` replaced by the home directory
+fn expand_home(path: &Path) -> PathBuf {
+    match (path.strip_prefix("~"), dirs::home_dir()) {
+        (Ok(rest), Some(home)) => home.join(rest),
+        _ => path.to_path_buf(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{path::PathBuf, time::Duration};
+
+    use super::{Config, ErrorFormat, parse_option};
+    use crate::evaluator::ReplEvaluator;
+
+    #[test]
+    fn test_parse() {
+        let config = Config::parse(
+            "trace-level = \"verbose\"\nmax-depth = 4\nwarnings = false\n\n[history]\nfile = \"/tmp/history\"\nsize = 10\n\n[kernel]\nprogress-delay = 2.5\nmax-output = 100\nerror-format = \"plain\"\n",
+        )
+        .unwrap();
+        assert_eq!(
+            config.options,
+            vec![
+                ("max-depth".to_string(), "4".to_string()),
+                ("trace-level".to_string(), "verbose".to_string()),
+                ("warnings".to_string(), "off".to_string())
+            ]
+        );
+        assert_eq!(config.history.file, Some(PathBuf::from("/tmp/history")));
+        assert_eq!(config.history.size, Some(10));
+        assert_eq!(
+            config.kernel.progress_delay(),
+            Some(Duration::from_millis(2500))
+        );
+        assert_eq!(config.kernel.max_output, Some(100));
+        assert_eq!(config.kernel.error_format, Some(ErrorFormat::Plain));
+
+        let config = Config::parse("[kernel]\nprogress-delay = -1").unwrap();
+        assert_eq!(config.kernel.progress_delay(), None);
+
+        assert_eq!(Config::parse("").unwrap(), Config::default());
+        assert!(Config::parse("[history]\nlines = 3").is_err());
+        assert!(Config::parse("[kernel]\nerror-format = \"fancy\"").is_err());
+        assert!(Config::parse("max-depth = [1]").is_err());
+        assert!(Config::parse("max-depth =").is_err());
+    }
+
+    #[test]
+    fn test_parse_option() {
+        assert_eq!(
+            parse_option("trace-level = verbose"),
+            Ok(("trace-level".to_string(), "verbose".to_string()))
+        );
+        assert!(parse_option("verbose").is_err());
+    }
+
+    #[test]
+    fn test_load_and_apply() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("config.toml");
+        assert_eq!(Config::load(&path).unwrap(), Config::default());
+
+        std::fs::write(&path, "max-depth = 3\nmax-width = \"wide\"\n").unwrap();
+        let mut config = Config::load(&path).unwrap();
+        config.set("max-depth", "5");
+
+        let mut repl = ReplEvaluator::new();
+        let errors = config.apply(&mut repl);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(repl.display_settings().max_depth, 5);
+
+        std::fs::write(&path, "[kernel").unwrap();
+        assert!(Config::load(&path).is_err());
+    }
+}
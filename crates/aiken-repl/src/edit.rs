@@ -0,0 +1,32 @@
+//! Editing code in the user's editor
+
+use std::{env, fs, io, process::Command};
+
+/// Open `code` in `$VISUAL` or `$EDITOR` (vi if neither is set) and return it as saved
+pub fn in_editor(code: &str) -> io::Result<String> {
+    // The extension lets editors pick Aiken syntax highlighting
+    let file = tempfile::Builder::new()
+        .prefix("repl")
+        .suffix(".ak")
+        .tempfile()?;
+    fs::write(file.path(), code)?;
+
+    let editor = env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+    // Editors can come with arguments, eg. `code --wait`
+    let mut words = editor.split_whitespace();
+    let program = words.next().unwrap_or("vi");
+    let status = Command::new(program)
+        .args(words)
+        .arg(file.path())
+        .status()?;
+    if !status.success() {
+        return Err(io::Error::other(format!(
+            "`{}` exited with {}",
+            editor, status
+        )));
+    }
+
+    fs::read_to_string(file.path())
+}
@@ -0,0 +1,364 @@
+//! Decoding of Cardano addresses, given in bech32 or as the hex of their bytes
+//!
+//! Addresses are laid out as in CIP-19: a header byte whose high nibble is the kind of address
+//! and low nibble the network, then the payment and stake parts. Byron addresses are not bech32
+//! and aren't supported.
+
+use std::fmt;
+
+use super::ReplError;
+
+const CHARSET: &str = "qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+
+/// Generators of the bech32 checksum (BIP-173)
+const GENERATORS: [u32; 5] = [0x3b6a57b2, 0x26508e6d, 0x1ea119fa, 0x3d4233dd, 0x2a1462b3];
+
+/// Size of key and script hashes
+const HASH_SIZE: usize = 28;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Credential {
+    Key(Vec<u8>),
+    Script(Vec<u8>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StakeReference {
+    Inline(Credential),
+    Pointer {
+        slot_number: u64,
+        transaction_index: u64,
+        certificate_index: u64,
+    },
+}
+
+/// Parts of a decoded address
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AddressInfo {
+    /// 1 for mainnet, 0 for the testnets
+    pub network: u8,
+    /// None for stake addresses
+    pub payment: Option<Credential>,
+    pub stake: Option<StakeReference>,
+}
+
+impl AddressInfo {
+    /// The address as Aiken code, with the constructors of `cardano/address`. Stake addresses are
+    /// their credential.
+    pub fn aiken_literal(&self) -> String {
+        match (&self.payment, &self.stake) {
+            (None, Some(StakeReference::Inline(credential))) => credential_literal(credential),
+            (payment, stake) => {
+                let stake = match stake {
+                    Some(StakeReference::Inline(credential)) => {
+                        format!("Some(Inline({}))", credential_literal(credential))
+                    }
+                    Some(StakeReference::Pointer {
+                        slot_number,
+                        transaction_index,
+                        certificate_index,
+                    }) => format!(
+                        "Some(Pointer {{ slot_number: {}, transaction_index: {}, certificate_index: {} }})",
+                        slot_number, transaction_index, certificate_index
+                    ),
+                    None => "None".to_string(),
+                };
+                format!(
+                    "Address {{ payment_credential: {}, stake_credential: {} }}",
+                    payment.as_ref().map(credential_literal).unwrap_or_default(),
+                    stake
+                )
+            }
+        }
+    }
+}
+
+impl fmt::Display for AddressInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let network = match self.network {
+            0 => "testnet".to_string(),
+            1 => "mainnet".to_string(),
+            n => format!("network {}", n),
+        };
+        writeln!(f, "Network: {}", network)?;
+        if let Some(payment) = &self.payment {
+            writeln!(f, "Payment: {}", describe(payment))?;
+        }
+        match &self.stake {
+            Some(StakeReference::Inline(credential)) => {
+                writeln!(f, "Stake: {}", describe(credential))?
+            }
+            Some(StakeReference::Pointer {
+                slot_number,
+                transaction_index,
+                certificate_index,
+            }) => writeln!(
+                f,
+                "Stake: pointer to slot {}, transaction {}, certificate {}",
+                slot_number, transaction_index, certificate_index
+            )?,
+            None => writeln!(f, "Stake: none")?,
+        }
+        write!(f, "Aiken: {}", self.aiken_literal())
+    }
+}
+
+fn describe(credential: &Credential) -> String {
+    match credential {
+        Credential::Key(hash) => format!("key hash {}", hex::encode(hash)),
+        Credential::Script(hash) => format!("script hash {}", hex::encode(hash)),
+    }
+}
+
+fn credential_literal(credential: &Credential) -> String {
+    match credential {
+        Credential::Key(hash) => format!("VerificationKey(#\"{}\")", hex::encode(hash)),
+        Credential::Script(hash) => format!("Script(#\"{}\")", hex::encode(hash)),
+    }
+}
+
+/// Decode an address from bech32 (`addr1...`, `stake_test1...`) or from the hex of its bytes
+pub fn decode(address: &str) -> Result<AddressInfo, ReplError> {
+    parse(&bytes(address)?)
+}
+
+/// Raw bytes of an address given in bech32 or hex
+pub(crate) fn bytes(address: &str) -> Result<Vec<u8>, ReplError> {
+    let address = address.trim();
+    if !address.is_empty() && address.chars().all(|c| c.is_ascii_hexdigit()) {
+        hex::decode(address).map_err(|e| invalid(e.to_string()))
+    } else {
+        decode_bech32(address)
+    }
+}
+
+fn invalid(message: impl Into<String>) -> ReplError {
+    ReplError::InvalidAddress {
+        message: message.into(),
+    }
+}
+
+/// Bytes of a bech32 string, once its checksum is verified. Unlike BIP-173, there's no maximum
+/// length: base addresses are longer than 90 characters.
+fn decode_bech32(text: &str) -> Result<Vec<u8>, ReplError> {
+    if text.chars().any(|c| c.is_ascii_uppercase()) && text.chars().any(|c| c.is_ascii_lowercase())
+    {
+        return Err(invalid("mixed-case bech32 string"));
+    }
+    let text = text.to_ascii_lowercase();
+    let (hrp, data) = text
+        .rsplit_once('1')
+        .ok_or_else(|| invalid("not a bech32 string, there's no `1` separator"))?;
+
+    let words = data
+        .chars()
+        .map(|c| {
+            CHARSET
+                .find(c)
+                .map(|word| word as u8)
+                .ok_or_else(|| invalid(format!("`{}` is not a bech32 character", c)))
+        })
+        .collect::<Result<Vec<u8>, _>>()?;
+    if hrp.is_empty() || words.len() < 6 {
+        return Err(invalid("bech32 string too short"));
+    }
+
+    let mut values: Vec<u8> = hrp.bytes().map(|b| b >> 5).collect();
+    values.push(0);
+    values.extend(hrp.bytes().map(|b| b & 31));
+    values.extend(&words);
+    if polymod(&values) != 1 {
+        return Err(invalid("invalid bech32 checksum"));
+    }
+
+    // Regroup the words of 5 bits, leaving out the checksum, in bytes
+    let mut bytes = Vec::new();
+    let (mut acc, mut bits) = (0u32, 0);
+    for word in &words[..words.len() - 6] {
+        acc = ((acc << 5) | *word as u32) & 0xfff;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((acc >> bits) as u8);
+        }
+    }
+    if bits >= 5 || acc & ((1 << bits) - 1) != 0 {
+        return Err(invalid("invalid bech32 padding"));
+    }
+    Ok(bytes)
+}
+
+fn polymod(values: &[u8]) -> u32 {
+    let mut checksum: u32 = 1;
+    for value in values {
+        let top = checksum >> 25;
+        checksum = ((checksum & 0x1ffffff) << 5) ^ *value as u32;
+        for (i, generator) in GENERATORS.iter().enumerate() {
+            if (top >> i) & 1 == 1 {
+                checksum ^= generator;
+            }
+        }
+    }
+    checksum
+}
+
+fn parse(bytes: &[u8]) -> Result<AddressInfo, ReplError> {
+    let header = *bytes.first().ok_or_else(|| invalid("empty address"))?;
+    let kind = header >> 4;
+    let network = header & 0x0f;
+
+    // Kinds with odd numbers have a script as payment credential, and 2 and 3 a script as stake
+    let credential = |script: bool, start: usize| {
+        let hash = bytes
+            .get(start..start + HASH_SIZE)
+            .ok_or_else(|| invalid("address too short"))?
+            .to_vec();
+        Ok::<_, ReplError>(if script {
+            Credential::Script(hash)
+        } else {
+            Credential::Key(hash)
+        })
+    };
+    let expect_size = |size: usize| {
+        if bytes.len() == size {
+            Ok(())
+        } else {
+            Err(invalid(format!(
+                "expected {} bytes for this kind of address, got {}",
+                size,
+                bytes.len()
+            )))
+        }
+    };
+
+    let (payment, stake) = match kind {
+        0..=3 => {
+            expect_size(1 + 2 * HASH_SIZE)?;
+            (
+                Some(credential(kind & 1 == 1, 1)?),
+                Some(StakeReference::Inline(credential(
+                    kind & 2 == 2,
+                    1 + HASH_SIZE,
+                )?)),
+            )
+        }
+        4 | 5 => {
+            let payment = credential(kind & 1 == 1, 1)?;
+            let mut rest = &bytes[1 + HASH_SIZE..];
+            let slot_number = variable_natural(&mut rest)?;
+            let transaction_index = variable_natural(&mut rest)?;
+            let certificate_index = variable_natural(&mut rest)?;
+            if !rest.is_empty() {
+                return Err(invalid("trailing bytes after the pointer"));
+            }
+            (
+                Some(payment),
+                Some(StakeReference::Pointer {
+                    slot_number,
+                    transaction_index,
+                    certificate_index,
+                }),
+            )
+        }
+        6 | 7 => {
+            expect_size(1 + HASH_SIZE)?;
+            (Some(credential(kind & 1 == 1, 1)?), None)
+        }
+        14 | 15 => {
+            expect_size(1 + HASH_SIZE)?;
+            (
+                None,
+                Some(StakeReference::Inline(credential(kind & 1 == 1, 1)?)),
+            )
+        }
+        8 => return Err(invalid("Byron addresses are not supported")),
+        _ => return Err(invalid(format!("unknown address kind {}", kind))),
+    };
+
+    Ok(AddressInfo {
+        network,
+        payment,
+        stake,
+    })
+}
+
+/// Natural number of a pointer: groups of 7 bits, big-endian, with the high bit set on all the
+/// groups but the last
+fn variable_natural(bytes: &mut &[u8]) -> Result<u64, ReplError> {
+    let mut n: u64 = 0;
+    loop {
+        let (byte, rest) = bytes
+            .split_first()
+            .ok_or_else(|| invalid("pointer too short"))?;
+        *bytes = rest;
+        n = n
+            .checked_mul(128)
+            .ok_or_else(|| invalid("pointer out of range"))?
+            | (*byte & 0x7f) as u64;
+        if byte & 0x80 == 0 {
+            return Ok(n);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{AddressInfo, Credential, StakeReference, decode};
+
+    const PAYMENT: &str = "9493315cd92eb5d8c4304e67b7e16ae36d61d34502694657811a2c8e";
+    const STAKE: &str = "337b62cfff6403a06a3acbc34f8c46003c69fe79a3628cefa9c47251";
+
+    fn key(hash: &str) -> Credential {
+        Credential::Key(hex::decode(hash).unwrap())
+    }
+
+    // Test vectors of CIP-19
+    #[test]
+    fn test_decode() {
+        let base = decode("addr1qx2fxv2umyhttkxyxp8x0dlpdt3k6cwng5pxj3jhsydzer3n0d3vllmyqwsx5wktcd8cc3sq835lu7drv2xwl2wywfgse35a3x").unwrap();
+        assert_eq!(
+            base,
+            AddressInfo {
+                network: 1,
+                payment: Some(key(PAYMENT)),
+                stake: Some(StakeReference::Inline(key(STAKE))),
+            }
+        );
+        assert_eq!(
+            base.aiken_literal(),
+            format!(
+                "Address {{ payment_credential: VerificationKey(#\"{}\"), stake_credential: Some(Inline(VerificationKey(#\"{}\"))) }}",
+                PAYMENT, STAKE
+            )
+        );
+
+        let pointer =
+            decode("addr1gx2fxv2umyhttkxyxp8x0dlpdt3k6cwng5pxj3jhsydzer5pnz75xxcrzqf96k").unwrap();
+        assert_eq!(
+            pointer.stake,
+            Some(StakeReference::Pointer {
+                slot_number: 2498243,
+                transaction_index: 27,
+                certificate_index: 3,
+            })
+        );
+
+        let enterprise =
+            decode("addr1vx2fxv2umyhttkxyxp8x0dlpdt3k6cwng5pxj3jhsydzers66hrl8").unwrap();
+        assert_eq!(enterprise.payment, Some(key(PAYMENT)));
+        assert_eq!(enterprise.stake, None);
+
+        let reward = decode("stake1uyehkck0lajq8gr28t9uxnuvgcqrc6070x3k9r8048z8y5gh6ffgw").unwrap();
+        assert_eq!(reward.payment, None);
+        assert_eq!(
+            reward.aiken_literal(),
+            format!("VerificationKey(#\"{}\")", STAKE)
+        );
+
+        // The same bytes, in hex
+        assert_eq!(decode(&format!("61{}", PAYMENT)).unwrap(), enterprise);
+
+        assert!(decode("addr1vx2fxv2umyhttkxyxp8x0dlpdt3k6cwng5pxj3jhsydzers66hrl9").is_err());
+        assert!(decode("61").is_err());
+    }
+}
@@ -0,0 +1,258 @@
+//! Build artifacts shared by sessions
+//!
+//! Every session starts with an empty temp project, so without this each new REPL or kernel
+//! would compile its packages from scratch. Once a session has built its project, the `build`
+//! directory is saved under the user's cache directory, keyed by the Aiken version and the
+//! project's `aiken.toml` (the Plutus version and the packages), and later sessions with the
+//! same key start from a copy of it.
+
+use std::{
+    collections::hash_map::DefaultHasher,
+    fs,
+    hash::{Hash, Hasher},
+    io,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use super::gc;
+use crate::version::aiken_version;
+
+/// Saved artifacts are evicted, least recently used first, once they grow past this size
+pub const ARTIFACTS_LIMIT: u64 = 512 * 1024 * 1024;
+
+/// File touched whenever an entry is restored, to tell which entries were used last
+const LAST_USED: &str = ".last-used";
+
+/// Prefix of the directories entries are copied to before they're renamed into place
+const PARTIAL: &str = ".partial-";
+
+/// Partial entries older than this were left by a session that died while saving them
+const STALE_PARTIAL: Duration = Duration::from_secs(60 * 60);
+
+/// A directory of saved build artifacts
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArtifactCache {
+    dir: PathBuf,
+    limit: u64,
+}
+
+impl ArtifactCache {
+    pub fn new(dir: PathBuf, limit: u64) -> Self {
+        Self { dir, limit }
+    }
+
+    /// `$XDG_CACHE_HOME/iaiken` (`~/.cache/iaiken`) on Linux, and the cache directory of other
+    /// platforms
+    pub fn user() -> Option<Self> {
+        dirs::cache_dir().map(|dir| Self::new(dir.join("iaiken"), ARTIFACTS_LIMIT))
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Directory of the artifacts of projects described by `aiken_toml`
+    fn entry(&self, aiken_toml: &str) -> PathBuf {
+        let mut hasher = DefaultHasher::new();
+        aiken_toml.hash(&mut hasher);
+        self.dir
+            .join(format!("{}-{:016x}", aiken_version(), hasher.finish()))
+    }
+
+    /// Copy the saved artifacts of `aiken_toml` to `build_dir`, unless it already exists.
+    /// Returns whether anything was copied.
+    pub fn restore(&self, aiken_toml: &str, build_dir: &Path) -> io::Result<bool> {
+        let entry = self.entry(aiken_toml);
+        if build_dir.exists() || !entry.is_dir() {
+            return Ok(false);
+        }
+        copy_dir(&entry, build_dir)?;
+        let _ = fs::remove_file(build_dir.join(LAST_USED));
+        fs::write(entry.join(LAST_USED), "")?;
+        Ok(true)
+    }
+
+    /// Save `build_dir` as the artifacts of `aiken_toml`, unless some already are, then evict
+    /// the least recently used entries if the cache is over its limit
+    pub fn store(&self, aiken_toml: &str, build_dir: &Path) -> io::Result<()> {
+        let entry = self.entry(aiken_toml);
+        if entry.exists() || !build_dir.is_dir() {
+            return Ok(());
+        }
+
+        // Copied aside first, so other sessions never restore a partial entry. Each store has a
+        // directory of its own: evaluators of one process may store at the same time.
+        fs::create_dir_all(&self.dir)?;
+        let partial = tempfile::Builder::new()
+            .prefix(PARTIAL)
+            .tempdir_in(&self.dir)?;
+        copy_dir(build_dir, partial.path())?;
+        fs::write(partial.path().join(LAST_USED), "")?;
+        // Unless another session saved the same entry meanwhile, in which case the copy is
+        // removed when dropped
+        if fs::rename(partial.path(), &entry).is_ok() {
+            let _ = partial.keep();
+        }
+
+        self.sweep_partials(STALE_PARTIAL)?;
+        self.evict()?;
+        Ok(())
+    }
+
+    /// Remove the partial entries older than `age`, left by sessions that died while saving
+    /// them. Younger ones may still be in progress.
+    fn sweep_partials(&self, age: Duration) -> io::Result<()> {
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            let stale = fs::metadata(&path)
+                .and_then(|metadata| metadata.modified())
+                .is_ok_and(|modified| modified.elapsed().is_ok_and(|elapsed| elapsed >= age));
+            if is_partial(&path) && stale {
+                // Another session may be sweeping it too
+                let _ = fs::remove_dir_all(&path);
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove the least recently used entries until the cache fits its limit, returning the
+    /// number of bytes freed
+    pub fn evict(&self) -> io::Result<u64> {
+        let mut entries = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.is_dir() && !is_partial(&path) {
+                let last_used = fs::metadata(path.join(LAST_USED))
+                    .and_then(|metadata| metadata.modified())
+                    .unwrap_or(SystemTime::UNIX_EPOCH);
+                entries.push((last_used, gc::dir_size(&path)?, path));
+            }
+        }
+        entries.sort();
+
+        let mut size: u64 = entries.iter().map(|(_, size, _)| size).sum();
+        let mut freed = 0;
+        for (_, entry_size, path) in entries {
+            if size <= self.limit {
+                break;
+            }
+            fs::remove_dir_all(&path)?;
+            size -= entry_size;
+            freed += entry_size;
+        }
+        Ok(freed)
+    }
+
+    /// Remove all saved artifacts, returning the number of bytes freed
+    pub fn clear(&self) -> io::Result<u64> {
+        let size = gc::dir_size(&self.dir)?;
+        if self.dir.exists() {
+            fs::remove_dir_all(&self.dir)?;
+        }
+        Ok(size)
+    }
+}
+
+fn is_partial(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.starts_with(PARTIAL))
+}
+
+fn copy_dir(from: &Path, to: &Path) -> io::Result<()> {
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            fs::copy(entry.path(), target)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::{fs, thread, time::Duration};
+
+    use super::{ArtifactCache, STALE_PARTIAL};
+
+    #[test]
+    fn test_store_and_restore() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let cache = ArtifactCache::new(dir.path().join("cache"), 1024);
+        let build = dir.path().join("build");
+        fs::create_dir_all(build.join("packages")).unwrap();
+        fs::write(build.join("packages").join("packages.toml"), "[]").unwrap();
+
+        let restored = dir.path().join("restored");
+        assert!(!cache.restore("plutus = \"v3\"", &restored).unwrap());
+
+        cache.store("plutus = \"v3\"", &build).unwrap();
+        assert!(cache.restore("plutus = \"v3\"", &restored).unwrap());
+        assert_eq!(
+            fs::read_to_string(restored.join("packages").join("packages.toml")).unwrap(),
+            "[]"
+        );
+        // Existing artifacts are kept, and other projects have their own
+        assert!(!cache.restore("plutus = \"v3\"", &restored).unwrap());
+        assert!(
+            !cache
+                .restore("plutus = \"v2\"", &dir.path().join("v2"))
+                .unwrap()
+        );
+
+        assert_eq!(cache.clear().unwrap(), 2);
+        assert!(!cache.dir().exists());
+    }
+
+    #[test]
+    fn test_evict() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let cache = ArtifactCache::new(dir.path().join("cache"), 150);
+        let build = dir.path().join("build");
+        fs::create_dir_all(&build).unwrap();
+        fs::write(build.join("artifact"), "x".repeat(100)).unwrap();
+
+        cache.store("v1", &build).unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        cache.store("v2", &build).unwrap();
+
+        // Only the last entry fits
+        assert!(!cache.restore("v1", &dir.path().join("v1")).unwrap());
+        assert!(cache.restore("v2", &dir.path().join("v2")).unwrap());
+    }
+
+    #[test]
+    fn test_concurrent_stores() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let cache = ArtifactCache::new(dir.path().join("cache"), 1024 * 1024);
+        let build = dir.path().join("build");
+        fs::create_dir_all(build.join("packages")).unwrap();
+        fs::write(build.join("packages").join("packages.toml"), "[]").unwrap();
+
+        // Evaluators of one process, each on its own thread
+        thread::scope(|scope| {
+            for i in 0..4 {
+                let (cache, build) = (&cache, &build);
+                scope.spawn(move || cache.store(&format!("v{}", i), build).unwrap());
+            }
+        });
+        for i in 0..4 {
+            let restored = dir.path().join(format!("v{}", i));
+            assert!(cache.restore(&format!("v{}", i), &restored).unwrap());
+        }
+
+        // Partial entries are only swept once stale
+        let partial = cache.dir().join(".partial-dead");
+        fs::create_dir_all(&partial).unwrap();
+        cache.sweep_partials(STALE_PARTIAL).unwrap();
+        assert!(partial.exists());
+        thread::sleep(Duration::from_millis(20));
+        cache.sweep_partials(Duration::from_millis(10)).unwrap();
+        assert!(!partial.exists());
+    }
+}
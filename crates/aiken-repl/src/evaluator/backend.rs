@@ -0,0 +1,54 @@
+//! Backends that compile the definitions of a session and evaluate its code
+//!
+//! The session keeps the definitions, settings and history, and hands code to its backend: the
+//! aiken crates the REPL is built with, run in-process, or an installed `aiken` executable
+//! driven through its command line (see `compiler`), which frees the session from the version
+//! of the crates, eg. to try a nightly compiler.
+
+use super::{
+    EvaluationResult, ReplError, ReplEvaluator, looks_like_expression, split_trailing_expression,
+};
+use crate::version::aiken_version;
+
+/// What compiles the definitions of a session and evaluates its code
+pub trait EvalBackend {
+    /// Version of the compiler, eg. `1.1.19`
+    fn version(&self) -> String;
+
+    /// Type-check the definitions of the session, eg. before switching to this backend
+    fn check(&self, repl: &ReplEvaluator) -> Result<(), ReplError>;
+
+    /// Add the definitions of `code` to the session
+    fn define(&self, repl: &mut ReplEvaluator, code: &str) -> Result<EvaluationResult, ReplError>;
+
+    /// Evaluate `code`: an expression, definitions, or definitions followed by an expression
+    fn eval(&self, repl: &mut ReplEvaluator, code: &str) -> Result<EvaluationResult, ReplError>;
+}
+
+/// The aiken-lang and aiken-project crates the REPL is built with. Values come with their types
+/// and budgets, and results can be cached.
+pub struct InProcess;
+
+impl EvalBackend for InProcess {
+    fn version(&self) -> String {
+        aiken_version()
+    }
+
+    fn check(&self, repl: &ReplEvaluator) -> Result<(), ReplError> {
+        repl.create_temp_project(repl.context.source()).map(|_| ())
+    }
+
+    fn define(&self, repl: &mut ReplEvaluator, code: &str) -> Result<EvaluationResult, ReplError> {
+        repl.eval_definitions(code)
+    }
+
+    fn eval(&self, repl: &mut ReplEvaluator, code: &str) -> Result<EvaluationResult, ReplError> {
+        if looks_like_expression(code) {
+            repl.eval_cached_expression(code)
+        } else if let Some((definitions, expression)) = split_trailing_expression(code) {
+            repl.eval_mixed(definitions, expression)
+        } else {
+            repl.eval_definitions(code)
+        }
+    }
+}
@@ -0,0 +1,96 @@
+//! Serialization of compiled expressions, to see how big a script would be on-chain
+//!
+//! Programs are flat-encoded and wrapped in a CBOR byte string, like the `compiledCode` of a
+//! blueprint. Transaction builders such as cardano-cli expect that wrapped once more.
+
+use std::fmt;
+
+use uplc::ast::{DeBruijn, NamedDeBruijn, Program};
+
+use super::ReplError;
+
+/// Encodings of a compiled expression
+#[derive(Debug, Clone)]
+pub struct ScriptCbor {
+    /// Size of the flat-encoded program
+    pub flat_size: usize,
+    /// Flat bytes wrapped in a CBOR byte string
+    pub cbor: Vec<u8>,
+    /// `cbor` wrapped in another CBOR byte string, for on-chain submission
+    pub double_cbor: Vec<u8>,
+}
+
+impl fmt::Display for ScriptCbor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "CBOR: {}", hex::encode(&self.cbor))?;
+        writeln!(f, "Double CBOR: {}", hex::encode(&self.double_cbor))?;
+        write!(
+            f,
+            "Size: {} bytes (flat: {} bytes)",
+            self.cbor.len(),
+            self.flat_size
+        )
+    }
+}
+
+pub fn encode_program(program: Program<NamedDeBruijn>) -> Result<ScriptCbor, ReplError> {
+    // Names are only useful for debugging, scripts are serialized without them
+    let program: Program<DeBruijn> = program.into();
+    let flat = program
+        .to_flat()
+        .map_err(|err| ReplError::EvaluationFailed {
+            message: format!("Failed to encode program: {:?}", err),
+        })?;
+
+    let cbor = wrap_bytes(&flat);
+    let double_cbor = wrap_bytes(&cbor);
+    Ok(ScriptCbor {
+        flat_size: flat.len(),
+        cbor,
+        double_cbor,
+    })
+}
+
+/// Encode `bytes` as a definite-length CBOR byte string (major type 2)
+fn wrap_bytes(bytes: &[u8]) -> Vec<u8> {
+    const MAJOR_BYTES: u8 = 0x40;
+
+    let len = bytes.len();
+    let mut out = Vec::with_capacity(len + 9);
+    if len < 24 {
+        out.push(MAJOR_BYTES | len as u8);
+    } else if len <= u8::MAX as usize {
+        out.push(MAJOR_BYTES | 24);
+        out.push(len as u8);
+    } else if len <= u16::MAX as usize {
+        out.push(MAJOR_BYTES | 25);
+        out.extend_from_slice(&(len as u16).to_be_bytes());
+    } else if len <= u32::MAX as usize {
+        out.push(MAJOR_BYTES | 26);
+        out.extend_from_slice(&(len as u32).to_be_bytes());
+    } else {
+        out.push(MAJOR_BYTES | 27);
+        out.extend_from_slice(&(len as u64).to_be_bytes());
+    }
+    out.extend_from_slice(bytes);
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::wrap_bytes;
+
+    #[test]
+    fn test_wrap_bytes() {
+        assert_eq!(wrap_bytes(&[]), vec![0x40]);
+        assert_eq!(wrap_bytes(&[1, 2, 3]), vec![0x43, 1, 2, 3]);
+
+        let wrapped = wrap_bytes(&[0; 24]);
+        assert_eq!(&wrapped[..2], &[0x58, 24]);
+        assert_eq!(wrapped.len(), 26);
+
+        let wrapped = wrap_bytes(&[0; 300]);
+        assert_eq!(&wrapped[..3], &[0x59, 0x01, 0x2c]);
+        assert_eq!(wrapped.len(), 303);
+    }
+}
@@ -0,0 +1,383 @@
+//! Lookups of UTxOs on the chain, to try code against real outputs
+//!
+//! Providers are pluggable through [`ChainProvider`]. Blockfrost is the only one built in, behind
+//! the `blockfrost` feature: without it, the REPL never goes online.
+
+use std::{collections::BTreeMap, fmt};
+
+use super::{ReplError, address, simulate::Network};
+
+/// A source of UTxOs, eg. a Blockfrost project or a Kupo instance
+pub trait ChainProvider: Send {
+    /// Network the provider reads from
+    fn network(&self) -> Network;
+
+    /// Unspent outputs at an address given in bech32
+    fn utxos_at(&self, address: &str) -> Result<Vec<Utxo>, ReplError>;
+
+    /// The output at `index` of the transaction `tx_hash` (in hex), spent or not
+    fn utxo(&self, tx_hash: &str, index: u64) -> Result<Utxo, ReplError>;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Datum {
+    /// Hash of a datum given along the spending transaction
+    Hash(String),
+    /// CBOR hex of the datum
+    Inline(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReferenceScript {
+    /// Plutus version, from 1 to 3
+    pub plutus_version: u8,
+    /// CBOR hex of the script, as in the `compiledCode` of a blueprint
+    pub cbor: String,
+}
+
+/// An output created by a transaction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Utxo {
+    pub tx_hash: String,
+    pub output_index: u64,
+    /// Address in bech32
+    pub address: String,
+    pub lovelace: u64,
+    /// Quantities of the other assets, by policy id and asset name, in hex
+    pub assets: BTreeMap<String, BTreeMap<String, u64>>,
+    pub datum: Option<Datum>,
+    pub reference_script: Option<ReferenceScript>,
+}
+
+impl Utxo {
+    /// CBOR of the output reference: `[tx_hash, index]`
+    pub fn input_cbor(&self) -> Result<Vec<u8>, ReplError> {
+        let mut out = Vec::new();
+        write_head(&mut out, 4, 2);
+        write_bytes(&mut out, &decode_hex(&self.tx_hash)?);
+        write_head(&mut out, 0, self.output_index);
+        Ok(out)
+    }
+
+    /// CBOR of the output, in the map format of Babbage and later eras
+    pub fn output_cbor(&self) -> Result<Vec<u8>, ReplError> {
+        let fields = 2 + self.datum.is_some() as u64 + self.reference_script.is_some() as u64;
+        let mut out = Vec::new();
+        write_head(&mut out, 5, fields);
+
+        write_head(&mut out, 0, 0);
+        write_bytes(&mut out, &address::bytes(&self.address)?);
+
+        write_head(&mut out, 0, 1);
+        if self.assets.is_empty() {
+            write_head(&mut out, 0, self.lovelace);
+        } else {
+            write_head(&mut out, 4, 2);
+            write_head(&mut out, 0, self.lovelace);
+            write_head(&mut out, 5, self.assets.len() as u64);
+            for (policy, names) in &self.assets {
+                write_bytes(&mut out, &decode_hex(policy)?);
+                write_head(&mut out, 5, names.len() as u64);
+                for (name, quantity) in names {
+                    write_bytes(&mut out, &decode_hex(name)?);
+                    write_head(&mut out, 0, *quantity);
+                }
+            }
+        }
+
+        match &self.datum {
+            Some(Datum::Hash(hash)) => {
+                write_head(&mut out, 0, 2);
+                write_head(&mut out, 4, 2);
+                write_head(&mut out, 0, 0);
+                write_bytes(&mut out, &decode_hex(hash)?);
+            }
+            Some(Datum::Inline(cbor)) => {
+                write_head(&mut out, 0, 2);
+                write_head(&mut out, 4, 2);
+                write_head(&mut out, 0, 1);
+                write_embedded(&mut out, &decode_hex(cbor)?);
+            }
+            None => (),
+        }
+
+        if let Some(script) = &self.reference_script {
+            let mut encoded = Vec::new();
+            write_head(&mut encoded, 4, 2);
+            write_head(&mut encoded, 0, script.plutus_version as u64);
+            write_bytes(&mut encoded, &decode_hex(&script.cbor)?);
+            write_head(&mut out, 0, 3);
+            write_embedded(&mut out, &encoded);
+        }
+        Ok(out)
+    }
+}
+
+impl fmt::Display for Utxo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}#{}: {} lovelace",
+            self.tx_hash, self.output_index, self.lovelace
+        )?;
+        for (policy, names) in &self.assets {
+            for (name, quantity) in names {
+                write!(f, " + {} {}.{}", quantity, policy, name)?;
+            }
+        }
+        match &self.datum {
+            Some(Datum::Hash(hash)) => write!(f, "\n  datum hash: {}", hash)?,
+            Some(Datum::Inline(cbor)) => write!(f, "\n  inline datum: {}", cbor)?,
+            None => (),
+        }
+        if let Some(script) = &self.reference_script {
+            write!(
+                f,
+                "\n  reference script: Plutus V{}, {} bytes",
+                script.plutus_version,
+                script.cbor.len() / 2
+            )?;
+        }
+        Ok(())
+    }
+}
+
+fn decode_hex(text: &str) -> Result<Vec<u8>, ReplError> {
+    hex::decode(text).map_err(|e| ReplError::ChainFailed {
+        message: format!("invalid hex `{}`: {}", text, e),
+    })
+}
+
+fn write_head(out: &mut Vec<u8>, major: u8, value: u64) {
+    let major = major << 5;
+    match value {
+        0..=23 => out.push(major | value as u8),
+        24..=0xff => out.extend([major | 24, value as u8]),
+        0x100..=0xffff => {
+            out.push(major | 25);
+            out.extend((value as u16).to_be_bytes());
+        }
+        0x1_0000..=0xffff_ffff => {
+            out.push(major | 26);
+            out.extend((value as u32).to_be_bytes());
+        }
+        _ => {
+            out.push(major | 27);
+            out.extend(value.to_be_bytes());
+        }
+    }
+}
+
+fn write_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    write_head(out, 2, bytes.len() as u64);
+    out.extend(bytes);
+}
+
+/// CBOR embedded in a byte string, with tag 24
+fn write_embedded(out: &mut Vec<u8>, cbor: &[u8]) {
+    write_head(out, 6, 24);
+    write_bytes(out, cbor);
+}
+
+/// Create the provider given by `settings`, eg. the project id of a Blockfrost project
+#[cfg(feature = "blockfrost")]
+pub fn configure(settings: &str) -> Result<Box<dyn ChainProvider>, ReplError> {
+    Ok(Box::new(blockfrost::Blockfrost::new(settings)?))
+}
+
+#[cfg(not(feature = "blockfrost"))]
+pub fn configure(_settings: &str) -> Result<Box<dyn ChainProvider>, ReplError> {
+    Err(ReplError::ChainFailed {
+        message: "built without chain providers, rebuild with `--features blockfrost`".to_string(),
+    })
+}
+
+#[cfg(feature = "blockfrost")]
+mod blockfrost {
+    use std::collections::BTreeMap;
+
+    use serde_json::Value;
+
+    use super::{ChainProvider, Datum, ReferenceScript, Utxo};
+    use crate::evaluator::{ReplError, simulate::Network};
+
+    /// Outputs per page of `/addresses/{address}/utxos`
+    const PAGE_SIZE: usize = 100;
+
+    pub struct Blockfrost {
+        project_id: String,
+        network: Network,
+    }
+
+    impl Blockfrost {
+        /// Project ids start with the network they're for, eg. `preprodXXXX`
+        pub fn new(project_id: &str) -> Result<Self, ReplError> {
+            let project_id = project_id.trim();
+            let network = [
+                ("mainnet", Network::Mainnet),
+                ("preprod", Network::Preprod),
+                ("preview", Network::Preview),
+            ]
+            .into_iter()
+            .find(|(prefix, _)| project_id.starts_with(prefix))
+            .map(|(_, network)| network)
+            .ok_or_else(|| failed("expected a Blockfrost project id, eg. `preprodXXXX`"))?;
+            Ok(Self {
+                project_id: project_id.to_string(),
+                network,
+            })
+        }
+
+        fn get(&self, path: &str) -> Result<Value, ReplError> {
+            let url = format!(
+                "https://cardano-{}.blockfrost.io/api/v0{}",
+                self.network, path
+            );
+            let body = ureq::get(&url)
+                .set("project_id", &self.project_id)
+                .call()
+                .map_err(|e| failed(format!("GET {}: {}", path, e)))?
+                .into_string()
+                .map_err(|e| failed(e.to_string()))?;
+            serde_json::from_str(&body).map_err(|e| failed(e.to_string()))
+        }
+
+        fn output(&self, tx_hash: &str, output: &Value) -> Result<Utxo, ReplError> {
+            let text = |field: &str| output[field].as_str().map(str::to_string);
+
+            let mut lovelace = 0;
+            let mut assets: BTreeMap<String, BTreeMap<String, u64>> = BTreeMap::new();
+            for amount in output["amount"].as_array().into_iter().flatten() {
+                let (Some(unit), Some(quantity)) = (
+                    amount["unit"].as_str(),
+                    amount["quantity"].as_str().and_then(|q| q.parse().ok()),
+                ) else {
+                    return Err(failed(format!("unexpected amount {}", amount)));
+                };
+                if unit == "lovelace" {
+                    lovelace = quantity;
+                } else {
+                    // Units are the policy id, 28 bytes, followed by the asset name
+                    let (policy, name) = unit.split_at(unit.len().min(56));
+                    assets
+                        .entry(policy.to_string())
+                        .or_default()
+                        .insert(name.to_string(), quantity);
+                }
+            }
+
+            let datum = match (text("inline_datum"), text("data_hash")) {
+                (Some(cbor), _) => Some(Datum::Inline(cbor)),
+                (None, Some(hash)) => Some(Datum::Hash(hash)),
+                (None, None) => None,
+            };
+            let reference_script = text("reference_script_hash")
+                .map(|hash| self.script(&hash))
+                .transpose()?;
+
+            Ok(Utxo {
+                tx_hash: tx_hash.to_string(),
+                output_index: output["output_index"].as_u64().unwrap_or_default(),
+                address: text("address").unwrap_or_default(),
+                lovelace,
+                assets,
+                datum,
+                reference_script,
+            })
+        }
+
+        fn script(&self, hash: &str) -> Result<ReferenceScript, ReplError> {
+            let plutus_version = match self.get(&format!("/scripts/{}", hash))?["type"].as_str() {
+                Some("plutusV1") => 1,
+                Some("plutusV2") => 2,
+                Some("plutusV3") => 3,
+                _ => {
+                    return Err(failed(format!(
+                        "reference script {} isn't a Plutus script",
+                        hash
+                    )));
+                }
+            };
+            let cbor = self.get(&format!("/scripts/{}/cbor", hash))?["cbor"]
+                .as_str()
+                .map(str::to_string)
+                .ok_or_else(|| failed(format!("no CBOR for script {}", hash)))?;
+            Ok(ReferenceScript {
+                plutus_version,
+                cbor,
+            })
+        }
+    }
+
+    impl ChainProvider for Blockfrost {
+        fn network(&self) -> Network {
+            self.network
+        }
+
+        fn utxos_at(&self, address: &str) -> Result<Vec<Utxo>, ReplError> {
+            let mut utxos = Vec::new();
+            for page in 1.. {
+                let outputs = self.get(&format!("/addresses/{}/utxos?page={}", address, page))?;
+                let outputs = outputs.as_array().cloned().unwrap_or_default();
+                for output in &outputs {
+                    let tx_hash = output["tx_hash"].as_str().unwrap_or_default();
+                    utxos.push(self.output(tx_hash, output)?);
+                }
+                if outputs.len() < PAGE_SIZE {
+                    break;
+                }
+            }
+            Ok(utxos)
+        }
+
+        fn utxo(&self, tx_hash: &str, index: u64) -> Result<Utxo, ReplError> {
+            let tx = self.get(&format!("/txs/{}/utxos", tx_hash))?;
+            let output = tx["outputs"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .find(|output| output["output_index"].as_u64() == Some(index))
+                .ok_or_else(|| failed(format!("no output {}#{}", tx_hash, index)))?;
+            self.output(tx_hash, output)
+        }
+    }
+
+    fn failed(message: impl Into<String>) -> ReplError {
+        ReplError::ChainFailed {
+            message: message.into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use super::{Datum, Utxo};
+
+    #[test]
+    fn test_utxo_cbor() {
+        let utxo = Utxo {
+            tx_hash: "ab".repeat(32),
+            output_index: 1,
+            address: "addr1vx2fxv2umyhttkxyxp8x0dlpdt3k6cwng5pxj3jhsydzers66hrl8".to_string(),
+            lovelace: 2_000_000,
+            assets: BTreeMap::new(),
+            datum: Some(Datum::Inline("182a".to_string())),
+            reference_script: None,
+        };
+        assert_eq!(
+            hex::encode(utxo.input_cbor().unwrap()),
+            format!("825820{}01", "ab".repeat(32))
+        );
+        assert_eq!(
+            hex::encode(utxo.output_cbor().unwrap()),
+            concat!(
+                "a3",
+                "00581d619493315cd92eb5d8c4304e67b7e16ae36d61d34502694657811a2c8e",
+                "011a001e8480",
+                "028201d81842182a"
+            )
+        );
+    }
+}
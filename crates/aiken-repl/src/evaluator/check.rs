@@ -0,0 +1,97 @@
+//! Full verification of the session, as `aiken check` does for a project
+//!
+//! Evaluations stop at the first error and only show warnings they haven't shown before. A check
+//! goes over everything once more: all the errors, all the warnings, and the session's tests.
+
+use std::fmt;
+
+use super::report::TestOutcome;
+
+/// Everything a full check of the session found
+#[derive(Debug, Clone, Default)]
+pub struct CheckReport {
+    /// Number of definitions checked
+    pub definitions: usize,
+    /// Rendered errors, including failed tests
+    pub errors: Vec<String>,
+    /// Rendered warnings
+    pub warnings: Vec<String>,
+    /// Seed of the property tests, when there were some
+    pub seed: Option<u32>,
+    /// Property tests that gave labels, to show their coverage
+    pub labelled: Vec<TestOutcome>,
+}
+
+impl CheckReport {
+    pub fn passed(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+impl fmt::Display for CheckReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for diagnostic in self.errors.iter().chain(&self.warnings) {
+            writeln!(f, "{}", diagnostic.trim_end())?;
+        }
+        for test in &self.labelled {
+            writeln!(f, "{}", test)?;
+        }
+        let plural = |count: usize| if count == 1 { "" } else { "s" };
+        write!(
+            f,
+            "{} definition{} checked | {} error{} | {} warning{}",
+            self.definitions,
+            plural(self.definitions),
+            self.errors.len(),
+            plural(self.errors.len()),
+            self.warnings.len(),
+            plural(self.warnings.len())
+        )?;
+        match self.seed {
+            Some(seed) => write!(f, " | seed {}", seed),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{CheckReport, TestOutcome};
+
+    #[test]
+    fn test_report_display() {
+        let report = CheckReport {
+            definitions: 1,
+            errors: Vec::new(),
+            warnings: vec!["unused variable\n".to_string()],
+            seed: None,
+            labelled: Vec::new(),
+        };
+        assert!(report.passed());
+        assert_eq!(
+            format!("{}", report),
+            "unused variable\n1 definition checked | 0 errors | 1 warning"
+        );
+
+        let report = CheckReport {
+            definitions: 2,
+            errors: vec!["property failed\n".to_string()],
+            warnings: Vec::new(),
+            seed: Some(42),
+            labelled: vec![TestOutcome {
+                name: "sorts".to_string(),
+                passed: true,
+                budget: None,
+                iterations: Some(100),
+                traces: Vec::new(),
+                counterexample: None,
+                coverage: vec![("empty".to_string(), 5.0)],
+            }],
+        };
+        assert_eq!(
+            format!("{}", report),
+            "property failed\nPASS [after 100 tests] sorts\n      5.00% empty\n\
+             2 definitions checked | 1 error | 0 warnings | seed 42"
+        );
+    }
+}
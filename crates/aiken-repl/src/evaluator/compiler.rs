@@ -0,0 +1,396 @@
+//! Compilers a session evaluates with: the aiken-lang the REPL is built with, or a pinned
+//! `aiken` binary driven through its command line
+//!
+//! Aiken releases change the syntax, so a notebook written against one release may not compile
+//! with the next. Pinning a binary makes the session check and evaluate with that release, or
+//! with a nightly build, by running `aiken check` on a project of its own: definitions are
+//! type-checked as they are added, and an expression is evaluated in a test that traces its
+//! value.
+//!
+//! Only the built-in compiler knows the types of the values, so values of a pinned binary are
+//! shown as the compiler's traces show them, in CBOR diagnostic notation. Commands other than
+//! evaluating code and adding definitions (eg. `:check`, `:cbor`) use the built-in compiler.
+
+use std::{
+    env, fmt, fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use super::{
+    DefinitionInfo, DefinitionKey, EvaluationResult, Phase, Provenance, ReplError, ReplEvaluator,
+    backend::{EvalBackend, InProcess},
+    context, export, looks_like_expression, module_path, split_trailing_expression,
+};
+use crate::version::aiken_version;
+
+/// Name of the test an expression is evaluated in
+const EVAL_TEST: &str = "repl_eval";
+
+/// Label of the trace of the value, to find it in the report of `aiken check`
+const VALUE_LABEL: &str = "repl_value";
+
+/// Where definitions are compiled and expressions evaluated
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Compiler {
+    /// The aiken-lang the REPL is built with
+    #[default]
+    Builtin,
+    /// An `aiken` executable of another release
+    Binary(AikenBinary),
+}
+
+/// An `aiken` executable and the release it reports. As a backend, it checks and evaluates
+/// the session's code in a project of its own.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AikenBinary {
+    pub path: PathBuf,
+    /// Version without its leading `v` and commit, eg. `1.1.17`
+    pub version: String,
+}
+
+impl Compiler {
+    /// The compiler named by `spec`: `builtin` (or `in-process`), `aiken` for the one on the
+    /// `PATH`, a version (eg. `1.1.17`) or the path of an `aiken` executable. The built-in
+    /// compiler serves its own version.
+    pub fn select(spec: &str) -> Result<Self, ReplError> {
+        let spec = spec.trim().trim_start_matches('v');
+        if spec == "builtin" || spec == "in-process" || spec == aiken_version() {
+            return Ok(Compiler::Builtin);
+        }
+        AikenBinary::find(spec).map(Compiler::Binary)
+    }
+
+    /// Version of the compiler, eg. `1.1.19`
+    pub fn version(&self) -> String {
+        self.backend().version()
+    }
+
+    /// What evaluates the code of the session with this compiler
+    pub fn backend(&self) -> Box<dyn EvalBackend> {
+        match self {
+            Compiler::Builtin => Box::new(InProcess),
+            Compiler::Binary(binary) => Box::new(binary.clone()),
+        }
+    }
+
+    /// The compiler as the `backend` option is set to select it
+    pub fn option(&self) -> String {
+        match self {
+            Compiler::Builtin => "in-process".to_string(),
+            Compiler::Binary(binary) => binary.path.display().to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Compiler {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Compiler::Builtin => write!(f, "aiken {} (built in)", aiken_version()),
+            Compiler::Binary(binary) => {
+                write!(f, "aiken {} ({})", binary.version, binary.path.display())
+            }
+        }
+    }
+}
+
+impl AikenBinary {
+    /// The executable at `spec` if it's a path, the first `aiken` on the `PATH` for `aiken`, and
+    /// otherwise the `aiken-<version>` or `aiken` on the `PATH` that reports being version `spec`
+    pub fn find(spec: &str) -> Result<Self, ReplError> {
+        let path = Path::new(spec);
+        if path.components().count() > 1 || path.is_file() {
+            return Self::at(path.to_path_buf());
+        }
+
+        let names = [format!("aiken-{}", spec), format!("aiken-v{}", spec)]
+            .into_iter()
+            .chain([String::from("aiken")]);
+        let dirs: Vec<PathBuf> = env::var_os("PATH")
+            .map(|path| env::split_paths(&path).collect())
+            .unwrap_or_default();
+        names
+            .flat_map(|name| dirs.iter().map(move |dir| dir.join(&name)))
+            .filter_map(|path| Self::at(executable(path)).ok())
+            .find(|binary| spec == "aiken" || binary.version == spec)
+            .ok_or_else(|| ReplError::InvalidSetting {
+                message: format!(
+                    "no aiken {} found on the PATH, install it as `aiken-{}` or give the path of its executable",
+                    spec, spec
+                ),
+            })
+    }
+
+    /// The executable at `path`, with the version it reports
+    fn at(path: PathBuf) -> Result<Self, ReplError> {
+        let failed = |message: String| ReplError::InvalidSetting { message };
+        let output = Command::new(&path)
+            .arg("--version")
+            .output()
+            .map_err(|e| failed(format!("cannot run {}: {}", path.display(), e)))?;
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let version = parse_version(&stdout)
+            .ok_or_else(|| failed(format!("{} is not an aiken executable", path.display())))?;
+        Ok(Self { path, version })
+    }
+
+    /// Type-check `definitions` in the project of the session
+    fn check_project(&self, repl: &ReplEvaluator, definitions: &str) -> Result<(), ReplError> {
+        self.run_check(repl, definitions).map(|_| ())
+    }
+
+    /// Evaluate `expression` against `definitions` in the project of the session, returning
+    /// the value as the compiler traces it
+    fn trace_value(
+        &self,
+        repl: &ReplEvaluator,
+        definitions: &str,
+        expression: &str,
+    ) -> Result<String, ReplError> {
+        let module = format!(
+            "{}\n\ntest {}() {{\n  trace @\"{}\": {{\n{}\n}}\n  True\n}}\n",
+            definitions, EVAL_TEST, VALUE_LABEL, expression
+        );
+        let report = self.run_check(repl, &module)?;
+        traced_value(&report).ok_or_else(|| ReplError::EvaluationFailed {
+            message: format!(
+                "aiken {} didn't report the value of the expression:\n{}",
+                self.version,
+                report.trim()
+            ),
+        })
+    }
+
+    /// Write `module` to the project of the session, then run `aiken check` on it. Returns what
+    /// it printed.
+    fn run_check(&self, repl: &ReplEvaluator, module: &str) -> Result<String, ReplError> {
+        let project = Self::project(repl);
+        let lib = project.join("lib");
+        // The session's module may have moved since the last run
+        if lib.exists() {
+            fs::remove_dir_all(&lib)?;
+        }
+        fs::create_dir_all(&lib)?;
+        fs::write(
+            project.join("aiken.toml"),
+            format!(
+                "name = \"{}\"\nversion = \"0.0.0\"\nplutus = \"{}\"\n{}",
+                repl.names.project,
+                export::plutus(repl.plutus_version),
+                export::dependencies(module)
+            ),
+        )?;
+        let path = module_path(&lib, &repl.names.module);
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, module)?;
+
+        let output = Command::new(&self.path)
+            .arg("check")
+            .arg(&project)
+            .args(["--trace-level", "verbose"])
+            .env("NO_COLOR", "1")
+            .output()?;
+        let report = format!(
+            "{}{}",
+            String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&output.stderr)
+        );
+        if !output.status.success() {
+            return Err(ReplError::EvaluationFailed {
+                message: format!(
+                    "aiken {} rejected the code:\n{}",
+                    self.version,
+                    report.trim()
+                ),
+            });
+        }
+        Ok(report)
+    }
+}
+
+/// `path` with the extension executables have on the platform
+fn executable(path: PathBuf) -> PathBuf {
+    if cfg!(windows) {
+        // Not `with_extension`, which would take the `.17` of `aiken-1.1.17` for one
+        let mut path = path.into_os_string();
+        path.push(".exe");
+        PathBuf::from(path)
+    } else {
+        path
+    }
+}
+
+/// The version in the output of `aiken --version`, eg. `1.1.17` in `aiken v1.1.17+c3ad52b`
+fn parse_version(output: &str) -> Option<String> {
+    let version = output.strip_prefix("aiken")?.split_whitespace().next()?;
+    let version = version.trim_start_matches('v');
+    let version = version.split('+').next()?;
+    version
+        .starts_with(|c: char| c.is_ascii_digit())
+        .then(|| version.to_string())
+}
+
+/// The value traced with `VALUE_LABEL` in the report of `aiken check`
+fn traced_value(report: &str) -> Option<String> {
+    let label = format!("{}: ", VALUE_LABEL);
+    report.lines().find_map(|line| {
+        let start = line.find(&label)? + label.len();
+        Some(line[start..].trim().to_string())
+    })
+}
+
+impl AikenBinary {
+    /// Project of the binary, next to the one of the built-in compiler
+    fn project(repl: &ReplEvaluator) -> PathBuf {
+        repl.temp_dir.path().join("pinned")
+    }
+
+    fn eval_expression(
+        &self,
+        repl: &ReplEvaluator,
+        code: &str,
+    ) -> Result<EvaluationResult, ReplError> {
+        let value = self.trace_value(repl, repl.context.source(), code)?;
+        Ok(EvaluationResult::Traced {
+            value,
+            compiler: self.version.clone(),
+        })
+    }
+}
+
+impl EvalBackend for AikenBinary {
+    fn version(&self) -> String {
+        self.version.clone()
+    }
+
+    fn check(&self, repl: &ReplEvaluator) -> Result<(), ReplError> {
+        self.check_project(repl, repl.context.source())
+    }
+
+    /// Add `code` to the definitions once the binary type-checked it
+    fn define(&self, repl: &mut ReplEvaluator, code: &str) -> Result<EvaluationResult, ReplError> {
+        // Definitions are split by the built-in parser, the binary explains code it can't read
+        let added = context::split(code).or_else(|error| {
+            self.check_project(repl, &format!("{}\n\n{}", repl.context.source(), code))?;
+            Err(error)
+        })?;
+        repl.check_imports(&added)?;
+        if let Some(key) = context::duplicate(&added) {
+            return Err(ReplError::DuplicateDefinition {
+                definition: key.to_string(),
+            });
+        }
+
+        let mut context = repl.context.clone();
+        context.add(
+            added.clone(),
+            &Provenance::new(repl.origin.as_deref(), code),
+        );
+        self.check_project(repl, context.source())?;
+        repl.context = context;
+        repl.definitions_changed(false);
+
+        let mut defined = added.into_iter().filter_map(|(key, _)| match key {
+            DefinitionKey::Definition(kind, name) => Some((name, kind)),
+            _ => None,
+        });
+        Ok(match (defined.next(), defined.next()) {
+            (None, _) => EvaluationResult::NoResult,
+            (Some((name, kind)), None) => EvaluationResult::Definition {
+                name,
+                kind,
+                tipo: None,
+            },
+            (Some(first), Some(second)) => EvaluationResult::Definitions(
+                [first, second]
+                    .into_iter()
+                    .chain(defined)
+                    .map(|(name, kind)| DefinitionInfo {
+                        name,
+                        kind,
+                        tipo: String::new(),
+                        value: None,
+                        origin: None,
+                    })
+                    .collect(),
+            ),
+        })
+    }
+
+    fn eval(&self, repl: &mut ReplEvaluator, code: &str) -> Result<EvaluationResult, ReplError> {
+        // The binary's phases can't be told apart from here
+        repl.progress.clock.enter(Phase::Typecheck);
+        if looks_like_expression(code) {
+            return self.eval_expression(repl, code);
+        }
+        match split_trailing_expression(code) {
+            Some((definitions, expression)) => {
+                let definitions = self.define(repl, definitions)?;
+                let result = self.eval_expression(repl, expression)?;
+                Ok(EvaluationResult::Mixed {
+                    definitions: Box::new(definitions),
+                    result: Box::new(result),
+                })
+            }
+            None => self.define(repl, code),
+        }
+    }
+}
+
+impl ReplEvaluator {
+    /// Compiler the session evaluates with
+    pub fn compiler(&self) -> &Compiler {
+        &self.compiler
+    }
+
+    /// Evaluate with `compiler` from now on. The definitions of the session are checked with it
+    /// first, and the session keeps its compiler if they don't compile.
+    pub fn set_compiler(&mut self, compiler: Compiler) -> Result<(), ReplError> {
+        compiler.backend().check(self)?;
+        self.compiler = compiler;
+        // Cached results were computed by the other compiler
+        if let Some(cache) = &mut self.cache {
+            cache.clear();
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Compiler, parse_version, traced_value};
+
+    #[test]
+    fn test_parse_version() {
+        assert_eq!(
+            parse_version("aiken v1.1.17+c3ad52b\n"),
+            Some("1.1.17".to_string())
+        );
+        assert_eq!(
+            parse_version("aiken 1.0.29-alpha"),
+            Some("1.0.29-alpha".to_string())
+        );
+        assert_eq!(parse_version("cargo 1.88.0"), None);
+        assert_eq!(parse_version("aiken --help"), None);
+    }
+
+    #[test]
+    fn test_traced_value() {
+        let report = "    Testing ...\n\n    ┍━ repl ━━━━━━━━━━━━━━━━━━━\n    │ PASS [mem: 1.23 K, cpu: 456.78 K] repl_eval\n    │ · with traces\n    │ | repl_value: [1, 2, 3]\n    ┕━━━━━━━━━━━━━━━━━━━━━━━━━━ 1 tests | 1 passed | 0 failed\n";
+        assert_eq!(traced_value(report), Some("[1, 2, 3]".to_string()));
+        assert_eq!(traced_value("    │ PASS repl_eval"), None);
+    }
+
+    #[test]
+    fn test_select_builtin() {
+        assert_eq!(Compiler::select("builtin").unwrap(), Compiler::Builtin);
+        let version = Compiler::Builtin.version();
+        assert_eq!(
+            Compiler::select(&format!("v{}", version)).unwrap(),
+            Compiler::Builtin
+        );
+        assert!(Compiler::select("0.0.0-missing").is_err());
+    }
+}
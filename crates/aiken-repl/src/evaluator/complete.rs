@@ -0,0 +1,175 @@
+//! Completion of names at the cursor, for line editors and notebooks
+//!
+//! Candidates are read from the typed ASTs of the checked modules, like the docs lookup:
+//! definitions of the session, module paths after `use`, and members of modules after `list.`.
+
+use aiken_lang::ast::{Definition, TypedDefinition};
+use aiken_project::module::CheckedModule;
+
+/// Keywords of the language, completed along with the session's definitions
+pub const KEYWORDS: &[&str] = &[
+    "and",
+    "as",
+    "bench",
+    "const",
+    "else",
+    "expect",
+    "fail",
+    "fn",
+    "if",
+    "is",
+    "let",
+    "opaque",
+    "or",
+    "pub",
+    "test",
+    "todo",
+    "trace",
+    "type",
+    "use",
+    "validator",
+    "via",
+    "when",
+];
+
+/// Names that can be completed in the current context
+#[derive(Debug, Clone, Default)]
+pub struct CompletionCandidates {
+    /// Definitions and constructors of the session
+    pub definitions: Vec<String>,
+    /// Modules that can be imported, with their public definitions and constructors
+    pub modules: Vec<(String, Vec<String>)>,
+}
+
+/// Completions of the word ending at the cursor
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Completions {
+    /// Byte offset where the completed word starts
+    pub start: usize,
+    pub matches: Vec<String>,
+}
+
+impl CompletionCandidates {
+    pub fn new(modules: &[CheckedModule], session_module: &str) -> Self {
+        let mut candidates = Self::default();
+        for module in modules {
+            if module.name == session_module {
+                candidates.definitions = module
+                    .ast
+                    .definitions()
+                    .flat_map(|d| names(d, false))
+                    .collect();
+            } else {
+                let members = module
+                    .ast
+                    .definitions()
+                    .flat_map(|d| names(d, true))
+                    .collect();
+                candidates.modules.push((module.name.clone(), members));
+            }
+        }
+        candidates.modules.sort();
+        candidates
+    }
+
+    /// Complete the word of `line` that ends at byte offset `cursor`
+    pub fn complete(&self, line: &str, cursor: usize) -> Completions {
+        let before = &line[..cursor];
+        let start = before
+            .rfind(|c: char| !(c.is_alphanumeric() || c == '_' || c == '/' || c == '.'))
+            .map(|i| i + 1)
+            .unwrap_or(0);
+        let word = &before[start..];
+
+        let mut matches: Vec<String> = if before[..start].trim() == "use" {
+            // Module paths of imports
+            self.modules
+                .iter()
+                .map(|(name, _)| name.clone())
+                .filter(|name| name.starts_with(word))
+                .collect()
+        } else if let Some((module, prefix)) = word.rsplit_once('.') {
+            // Members of a module, referred to by the last segment of its path
+            self.modules
+                .iter()
+                .filter(|(name, _)| name == module || name.ends_with(&format!("/{}", module)))
+                .flat_map(|(_, members)| members)
+                .filter(|member| member.starts_with(prefix))
+                .map(|member| format!("{}.{}", module, member))
+                .collect()
+        } else if word.is_empty() {
+            Vec::new()
+        } else {
+            self.definitions
+                .iter()
+                .map(String::as_str)
+                .chain(KEYWORDS.iter().copied())
+                .filter(|name| name.starts_with(word))
+                .map(str::to_string)
+                .collect()
+        };
+        matches.sort();
+        matches.dedup();
+
+        Completions { start, matches }
+    }
+}
+
+/// Names a definition brings in scope: its own and those of its constructors
+fn names(def: &TypedDefinition, public_only: bool) -> Vec<String> {
+    match def {
+        Definition::Fn(f) if f.public || !public_only => vec![f.name.clone()],
+        Definition::ModuleConstant(c) if c.public || !public_only => vec![c.name.clone()],
+        Definition::TypeAlias(a) if a.public || !public_only => vec![a.alias.clone()],
+        Definition::DataType(d) if d.public || !public_only => {
+            let mut names = vec![d.name.clone()];
+            if !(d.opaque && public_only) {
+                names.extend(d.constructors.iter().map(|c| c.name.clone()));
+            }
+            names
+        }
+        _ => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{CompletionCandidates, Completions};
+
+    fn candidates() -> CompletionCandidates {
+        CompletionCandidates {
+            definitions: vec!["double".to_string(), "Color".to_string(), "Red".to_string()],
+            modules: vec![
+                ("aiken/builtin".to_string(), vec!["add_integer".to_string()]),
+                (
+                    "aiken/collection/list".to_string(),
+                    vec!["filter".to_string(), "foldl".to_string(), "map".to_string()],
+                ),
+            ],
+        }
+    }
+
+    #[test]
+    fn test_complete() {
+        let candidates = candidates();
+        assert_eq!(
+            candidates.complete("double(2) + dou", 15),
+            Completions {
+                start: 12,
+                matches: vec!["double".to_string()]
+            }
+        );
+        assert_eq!(
+            candidates.complete("use aiken/c", 11).matches,
+            vec!["aiken/collection/list"]
+        );
+        assert_eq!(
+            candidates.complete("list.f", 6).matches,
+            vec!["list.filter", "list.foldl"]
+        );
+
+        // Keywords are completed too
+        assert_eq!(candidates.complete("val", 3).matches, vec!["validator"]);
+        assert!(candidates.complete("1 + ", 4).matches.is_empty());
+    }
+}
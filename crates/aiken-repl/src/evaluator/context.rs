@@ -0,0 +1,563 @@
+//! The session's definitions, kept one by one rather than as a single string
+//!
+//! Inputs are split along the definitions the parser finds in them, so a definition can be
+//! replaced or forgotten on its own. Every change to the context is a generation: definitions
+//! remember the generation that added them, and removed ones the generation that removed them,
+//! so undoing the last generation puts the context back exactly as it was.
+//!
+//! Definitions also remember their provenance: the input they were written in, where it came
+//! from (eg. a notebook cell) and when.
+
+use std::{
+    fmt,
+    time::{Duration, SystemTime},
+};
+
+use aiken_lang::{
+    ast::{Definition, ModuleKind},
+    parser,
+};
+
+use super::{DefinitionKind, ReplError};
+
+/// What a definition defines. Defining the same thing again replaces the earlier definition.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum DefinitionKey {
+    Definition(DefinitionKind, String),
+    /// An import, by module path
+    Import(String),
+    Test(String),
+    Validator(String),
+    /// Anything else, by its source code
+    Other(String),
+}
+
+impl DefinitionKey {
+    /// Whether both keys define the same thing. Functions and constants share their names, so
+    /// a constant and a function called alike conflict.
+    pub fn conflicts(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Definition(kind, name), Self::Definition(other_kind, other_name)) => {
+                name == other_name
+                    && (kind == other_kind
+                        || (*kind != DefinitionKind::Type && *other_kind != DefinitionKind::Type))
+            }
+            _ => self == other,
+        }
+    }
+
+    /// Whether the definition is called `name`, or imports the module `name`
+    fn is_named(&self, name: &str) -> bool {
+        match self {
+            Self::Definition(_, n) | Self::Test(n) | Self::Validator(n) => n == name,
+            Self::Import(module) => module == name,
+            Self::Other(_) => false,
+        }
+    }
+}
+
+impl fmt::Display for DefinitionKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Definition(kind, name) => write!(f, "{} {}", kind.as_str(), name),
+            Self::Import(module) => write!(f, "use {}", module),
+            Self::Test(name) => write!(f, "test {}", name),
+            Self::Validator(name) => write!(f, "validator {}", name),
+            Self::Other(code) => write!(f, "{}", code.lines().next().unwrap_or_default()),
+        }
+    }
+}
+
+/// Where and when a definition was written
+#[derive(Debug, Clone, PartialEq)]
+pub struct Provenance {
+    /// Where the input came from, eg. `In[4]`, if known
+    pub origin: Option<String>,
+    pub defined_at: SystemTime,
+    /// The whole input, as it was written
+    pub input: String,
+}
+
+impl Provenance {
+    /// An input written now at `origin`
+    pub fn new(origin: Option<&str>, input: &str) -> Self {
+        Self {
+            origin: origin.map(str::to_string),
+            defined_at: SystemTime::now(),
+            input: input.to_string(),
+        }
+    }
+
+    /// `in In[4], 3 min ago`, as of `now`
+    pub fn describe(&self, now: SystemTime) -> String {
+        let elapsed = now.duration_since(self.defined_at).unwrap_or_default();
+        match &self.origin {
+            Some(origin) => format!("in {}, {}", origin, ago(elapsed)),
+            None => ago(elapsed),
+        }
+    }
+}
+
+impl fmt::Display for Provenance {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.describe(SystemTime::now()))
+    }
+}
+
+fn ago(elapsed: Duration) -> String {
+    let secs = elapsed.as_secs();
+    match secs {
+        0..60 => format!("{}s ago", secs),
+        60..3600 => format!("{} min ago", secs / 60),
+        _ => format!("{} h ago", secs / 3600),
+    }
+}
+
+/// A definition of the context
+#[derive(Debug, Clone)]
+pub(crate) struct StoredDefinition {
+    pub key: DefinitionKey,
+    pub code: String,
+    pub provenance: Provenance,
+    /// Generation that added the definition
+    generation: u64,
+}
+
+/// An input whose definitions were partly replaced by a later one: what it still defines no
+/// longer matches what it shows
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct StaleInput {
+    pub origin: String,
+    pub replaced: Vec<DefinitionKey>,
+    pub kept: Vec<DefinitionKey>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Context {
+    definitions: Vec<StoredDefinition>,
+    /// Definitions removed by a later generation, with that generation
+    removed: Vec<(u64, StoredDefinition)>,
+    generation: u64,
+    /// Source of the definitions, as written to the session module
+    source: String,
+}
+
+impl Context {
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &DefinitionKey> {
+        self.definitions.iter().map(|definition| &definition.key)
+    }
+
+    /// Definitions with the line of the source they start at, from 1, past their doc comments
+    pub fn lines(&self) -> impl Iterator<Item = (&DefinitionKey, usize)> {
+        let mut start = 1;
+        self.definitions.iter().map(move |definition| {
+            let comments = definition
+                .code
+                .lines()
+                .take_while(|line| line.trim_start().starts_with("//"))
+                .count();
+            let line = start + comments;
+            // Definitions are separated by a blank line
+            start += definition.code.lines().count() + 1;
+            (&definition.key, line)
+        })
+    }
+
+    /// The definition `key` would replace though it defines something else, eg. a constant
+    /// replaced by a function of the same name
+    pub fn shadowed_by(&self, key: &DefinitionKey) -> Option<&StoredDefinition> {
+        self.definitions
+            .iter()
+            .find(|definition| definition.key != *key && definition.key.conflicts(key))
+    }
+
+    /// Provenance of the definition called `name`
+    pub fn provenance(&self, name: &str) -> Option<&Provenance> {
+        self.definitions
+            .iter()
+            .find(|definition| {
+                !matches!(definition.key, DefinitionKey::Import(_)) && definition.key.is_named(name)
+            })
+            .map(|definition| &definition.provenance)
+    }
+
+    /// Inputs from other origins than `origin` whose definitions `keys` would partly replace
+    pub fn stale_inputs(&self, keys: &[DefinitionKey], origin: Option<&str>) -> Vec<StaleInput> {
+        let mut stale: Vec<StaleInput> = Vec::new();
+        for definition in &self.definitions {
+            let Some(input_origin) = definition.provenance.origin.as_deref() else {
+                continue;
+            };
+            if Some(input_origin) == origin
+                || matches!(
+                    definition.key,
+                    DefinitionKey::Import(_) | DefinitionKey::Other(_)
+                )
+            {
+                continue;
+            }
+            let index = match stale.iter().position(|input| input.origin == input_origin) {
+                Some(index) => index,
+                None => {
+                    stale.push(StaleInput {
+                        origin: input_origin.to_string(),
+                        replaced: Vec::new(),
+                        kept: Vec::new(),
+                    });
+                    stale.len() - 1
+                }
+            };
+            let input = &mut stale[index];
+            if keys.iter().any(|key| key.conflicts(&definition.key)) {
+                input.replaced.push(definition.key.clone());
+            } else {
+                input.kept.push(definition.key.clone());
+            }
+        }
+        stale.retain(|input| !input.replaced.is_empty() && !input.kept.is_empty());
+        stale
+    }
+
+    /// Add definitions written in `provenance`, replacing those that define the same things
+    pub fn add(&mut self, added: Vec<(DefinitionKey, String)>, provenance: &Provenance) {
+        let generation = self.next_generation();
+        let keys: Vec<DefinitionKey> = added.iter().map(|(key, _)| key.clone()).collect();
+        self.remove_where(generation, |definition| {
+            keys.iter().any(|key| key.conflicts(&definition.key))
+        });
+        self.add_to_generation(generation, added, provenance);
+    }
+
+    /// Replace all the definitions
+    pub fn replace(&mut self, added: Vec<(DefinitionKey, String)>, provenance: &Provenance) {
+        let generation = self.next_generation();
+        self.remove_where(generation, |_| true);
+        self.add_to_generation(generation, added, provenance);
+    }
+
+    /// Remove the definitions called `name` (or the import of module `name`), returning them
+    pub fn forget(&mut self, name: &str) -> Vec<DefinitionKey> {
+        if !self.keys().any(|key| key.is_named(name)) {
+            return Vec::new();
+        }
+        let generation = self.next_generation();
+        let forgotten = self.remove_where(generation, |definition| definition.key.is_named(name));
+        self.rebuild();
+        forgotten
+    }
+
+    /// Take back the last change, returning the definitions it had added or removed. None when
+    /// there's nothing left to undo.
+    pub fn undo(&mut self) -> Option<Vec<DefinitionKey>> {
+        if self.generation == 0 {
+            return None;
+        }
+        let generation = self.generation;
+        self.generation -= 1;
+
+        let mut changed = Vec::new();
+        self.definitions.retain(|definition| {
+            let added = definition.generation == generation;
+            if added {
+                changed.push(definition.key.clone());
+            }
+            !added
+        });
+        let (restored, removed) = std::mem::take(&mut self.removed)
+            .into_iter()
+            .partition(|(removed_by, _)| *removed_by == generation);
+        self.removed = removed;
+        for (_, definition) in restored {
+            if !changed.contains(&definition.key) {
+                changed.push(definition.key.clone());
+            }
+            self.definitions.push(definition);
+        }
+
+        // Definitions that were added together stay together, in the order they were written
+        self.definitions
+            .sort_by_key(|definition| definition.generation);
+        self.rebuild();
+        Some(changed)
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    fn next_generation(&mut self) -> u64 {
+        self.generation += 1;
+        self.generation
+    }
+
+    fn add_to_generation(
+        &mut self,
+        generation: u64,
+        added: Vec<(DefinitionKey, String)>,
+        provenance: &Provenance,
+    ) {
+        self.definitions
+            .extend(added.into_iter().map(|(key, code)| StoredDefinition {
+                key,
+                code,
+                provenance: provenance.clone(),
+                generation,
+            }));
+        self.rebuild();
+    }
+
+    fn remove_where(
+        &mut self,
+        generation: u64,
+        mut remove: impl FnMut(&StoredDefinition) -> bool,
+    ) -> Vec<DefinitionKey> {
+        let mut removed = Vec::new();
+        let mut kept = Vec::new();
+        for definition in std::mem::take(&mut self.definitions) {
+            if remove(&definition) {
+                removed.push(definition.key.clone());
+                self.removed.push((generation, definition));
+            } else {
+                kept.push(definition);
+            }
+        }
+        self.definitions = kept;
+        removed
+    }
+
+    fn rebuild(&mut self) {
+        self.source = self
+            .definitions
+            .iter()
+            .map(|definition| definition.code.as_str())
+            .collect::<Vec<_>>()
+            .join("\n\n");
+    }
+}
+
+/// Split code into its definitions. Doc comments go with the definition that follows them.
+pub(crate) fn split(code: &str) -> Result<Vec<(DefinitionKey, String)>, ReplError> {
+    let (module, _extra) =
+        parser::module(code, ModuleKind::Lib).map_err(|errors| ReplError::ParseFailed {
+            message: errors
+                .first()
+                .map(|e| e.to_string().trim().to_string())
+                .unwrap_or_default(),
+        })?;
+
+    let starts: Vec<usize> = module
+        .definitions
+        .iter()
+        .map(|definition| definition_start(code, definition.location().start))
+        .collect();
+
+    Ok(module
+        .definitions
+        .iter()
+        .enumerate()
+        .map(|(i, definition)| {
+            let end = starts.get(i + 1).copied().unwrap_or(code.len());
+            let source = code[starts[i]..end].trim().to_string();
+            let key = match definition {
+                Definition::Fn(f) => {
+                    DefinitionKey::Definition(DefinitionKind::Function, f.name.clone())
+                }
+                Definition::ModuleConstant(c) => {
+                    DefinitionKey::Definition(DefinitionKind::Constant, c.name.clone())
+                }
+                Definition::DataType(d) => {
+                    DefinitionKey::Definition(DefinitionKind::Type, d.name.clone())
+                }
+                Definition::TypeAlias(a) => {
+                    DefinitionKey::Definition(DefinitionKind::Type, a.alias.clone())
+                }
+                Definition::Use(u) => DefinitionKey::Import(u.module.join("/")),
+                Definition::Test(t) => DefinitionKey::Test(t.name.clone()),
+                Definition::Validator(v) => DefinitionKey::Validator(v.name.clone()),
+                _ => DefinitionKey::Other(source.clone()),
+            };
+            (key, source)
+        })
+        .collect())
+}
+
+/// A definition that code defines more than once, if any. Imports and other code can repeat.
+pub(crate) fn duplicate(definitions: &[(DefinitionKey, String)]) -> Option<&DefinitionKey> {
+    definitions.iter().enumerate().find_map(|(i, (key, _))| {
+        let named = !matches!(key, DefinitionKey::Import(_) | DefinitionKey::Other(_));
+        let repeated = definitions[i + 1..]
+            .iter()
+            .any(|(other, _)| other.conflicts(key));
+        (named && repeated).then_some(key)
+    })
+}
+
+/// Start of the line of a definition, or of the comments right above it
+fn definition_start(code: &str, offset: usize) -> usize {
+    let mut start = code[..offset].rfind('\n').map(|i| i + 1).unwrap_or(0);
+    while start > 0 {
+        let previous = code[..start - 1].rfind('\n').map(|i| i + 1).unwrap_or(0);
+        if !code[previous..start].trim_start().starts_with("//") {
+            break;
+        }
+        start = previous;
+    }
+    start
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, SystemTime};
+
+    use super::{Context, DefinitionKey, Provenance, duplicate, split};
+    use crate::evaluator::DefinitionKind;
+
+    fn function(name: &str) -> DefinitionKey {
+        DefinitionKey::Definition(DefinitionKind::Function, name.to_string())
+    }
+
+    fn unknown() -> Provenance {
+        Provenance::new(None, "")
+    }
+
+    #[test]
+    fn test_split() {
+        let definitions = split(
+            "use aiken/collection/list\n\n/// Doubles\npub fn double(x) {\n  x * 2\n}\npub const answer = 42",
+        )
+        .unwrap();
+        assert_eq!(
+            definitions,
+            vec![
+                (
+                    DefinitionKey::Import("aiken/collection/list".to_string()),
+                    "use aiken/collection/list".to_string()
+                ),
+                (
+                    function("double"),
+                    "/// Doubles\npub fn double(x) {\n  x * 2\n}".to_string()
+                ),
+                (
+                    DefinitionKey::Definition(DefinitionKind::Constant, "answer".to_string()),
+                    "pub const answer = 42".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_undo() {
+        let mut context = Context::default();
+        context.add(
+            vec![(function("f"), "fn f() { 1 }".to_string())],
+            &unknown(),
+        );
+        context.add(
+            vec![(function("g"), "fn g() { f() }".to_string())],
+            &unknown(),
+        );
+        context.add(
+            vec![(function("f"), "fn f() { 2 }".to_string())],
+            &unknown(),
+        );
+        assert_eq!(context.source(), "fn g() { f() }\n\nfn f() { 2 }");
+
+        // Undoing a redefinition brings the previous definition back
+        assert_eq!(context.undo(), Some(vec![function("f")]));
+        assert_eq!(context.source(), "fn f() { 1 }\n\nfn g() { f() }");
+
+        assert_eq!(context.forget("g"), vec![function("g")]);
+        assert_eq!(context.source(), "fn f() { 1 }");
+        assert!(context.forget("nope").is_empty());
+
+        assert_eq!(context.undo(), Some(vec![function("g")]));
+        assert_eq!(context.undo(), Some(vec![function("g")]));
+        assert_eq!(context.undo(), Some(vec![function("f")]));
+        assert_eq!(context.source(), "");
+        assert_eq!(context.undo(), None);
+    }
+
+    #[test]
+    fn test_conflicts() {
+        let constant =
+            |name: &str| DefinitionKey::Definition(DefinitionKind::Constant, name.to_string());
+        let mut context = Context::default();
+        context.add(
+            vec![(constant("x"), "const x = 1".to_string())],
+            &Provenance::new(Some("In[3]"), "const x = 1"),
+        );
+
+        // A function replaces the constant of the same name
+        let shadowed = context.shadowed_by(&function("x")).unwrap();
+        assert_eq!(shadowed.key, constant("x"));
+        assert_eq!(shadowed.provenance.origin.as_deref(), Some("In[3]"));
+        assert!(context.shadowed_by(&constant("x")).is_none());
+        context.add(
+            vec![(function("x"), "fn x() { 1 }".to_string())],
+            &unknown(),
+        );
+        assert_eq!(context.source(), "fn x() { 1 }");
+
+        let twice = split("fn f() { 1 }\nconst f = 2\nuse aiken/list\nuse aiken/list").unwrap();
+        assert_eq!(duplicate(&twice), Some(&function("f")));
+        assert_eq!(duplicate(&twice[1..]), None);
+    }
+
+    #[test]
+    fn test_provenance() {
+        let mut context = Context::default();
+        let cell = Provenance::new(Some("In[4]"), "fn f() { 1 }\nfn g() { 2 }");
+        context.add(
+            vec![
+                (function("f"), "fn f() { 1 }".to_string()),
+                (function("g"), "fn g() { 2 }".to_string()),
+            ],
+            &cell,
+        );
+        assert_eq!(context.provenance("g"), Some(&cell));
+        assert_eq!(context.provenance("h"), None);
+
+        let later = cell.defined_at + Duration::from_secs(180);
+        assert_eq!(cell.describe(later), "in In[4], 3 min ago");
+        assert_eq!(unknown().describe(SystemTime::now()), "0s ago");
+
+        // Redefining part of a cell leaves it stale, unless it's the same cell again
+        let stale = context.stale_inputs(&[function("f")], Some("In[5]"));
+        assert_eq!(stale.len(), 1);
+        assert_eq!(stale[0].origin, "In[4]");
+        assert_eq!(stale[0].replaced, vec![function("f")]);
+        assert_eq!(stale[0].kept, vec![function("g")]);
+        assert!(
+            context
+                .stale_inputs(&[function("f")], Some("In[4]"))
+                .is_empty()
+        );
+        assert!(
+            context
+                .stale_inputs(&[function("f"), function("g")], None)
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_lines() {
+        let mut context = Context::default();
+        context.add(
+            vec![
+                (function("f"), "fn f() {\n  1\n}".to_string()),
+                (function("g"), "/// Calls f\nfn g() { f() }".to_string()),
+            ],
+            &unknown(),
+        );
+        let lines: Vec<_> = context
+            .lines()
+            .map(|(key, line)| (key.clone(), line))
+            .collect();
+        assert_eq!(lines, vec![(function("f"), 1), (function("g"), 6)]);
+        assert_eq!(context.source().lines().nth(5), Some("fn g() { f() }"));
+    }
+}
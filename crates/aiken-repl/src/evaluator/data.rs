@@ -0,0 +1,191 @@
+//! Conversions between Plutus Data and its JSON and CBOR encodings
+//!
+//! JSON follows the detailed schema of cardano-cli, used for datums and redeemers:
+//! `{"constructor": 0, "fields": [...]}`, `{"int": 42}`, `{"bytes": "cafe"}`, `{"list": [...]}`
+//! and `{"map": [{"k": ..., "v": ...}]}`.
+
+use std::fmt;
+
+use num_bigint::BigInt;
+use serde_json::{Value, json};
+use uplc::{PlutusData, ast::Data, machine::value::from_pallas_bigint};
+
+use super::ReplError;
+
+/// A piece of data in its different notations
+#[derive(Debug, Clone)]
+pub struct DataConversion {
+    /// Plutus notation, eg. `Constr 0 [I 42]`
+    pub data: String,
+    pub cbor: String,
+    pub json: Value,
+    /// Aiken value of the data, when its type is known
+    pub value: Option<String>,
+}
+
+impl DataConversion {
+    pub fn new(data: &PlutusData, data_notation: String, value: Option<String>) -> Self {
+        Self {
+            data: data_notation,
+            cbor: uplc::plutus_data_to_bytes(data)
+                .map(hex::encode)
+                .unwrap_or_default(),
+            json: to_json(data),
+            value,
+        }
+    }
+}
+
+impl fmt::Display for DataConversion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(value) = &self.value {
+            writeln!(f, "Value: {}", value)?;
+        }
+        writeln!(f, "Data: {}", self.data)?;
+        writeln!(f, "CBOR: {}", self.cbor)?;
+        write!(f, "JSON: {}", self.json)
+    }
+}
+
+/// Index of a constructor. Tags 121-127 and 1280-1400 are compact encodings of the first 128
+/// indexes, others go with a general tag.
+pub fn constructor_index(tag: u64, any_constructor: Option<u64>) -> u64 {
+    match tag {
+        121..=127 => tag - 121,
+        1280..=1400 => tag - 1280 + 7,
+        _ => any_constructor.unwrap_or_default(),
+    }
+}
+
+/// Data from its JSON or, when `input` is a string of hex digits, from its CBOR
+pub fn parse(input: &str) -> Result<PlutusData, ReplError> {
+    let input = input.trim();
+    if !input.is_empty() && input.chars().all(|c| c.is_ascii_hexdigit()) {
+        let bytes = hex::decode(input).map_err(|e| invalid(e.to_string()))?;
+        return uplc::plutus_data(&bytes).map_err(|e| invalid(format!("invalid CBOR: {}", e)));
+    }
+    let json: Value = serde_json::from_str(input).map_err(|e| invalid(e.to_string()))?;
+    from_json(&json)
+}
+
+fn invalid(message: impl Into<String>) -> ReplError {
+    ReplError::InvalidData {
+        message: message.into(),
+    }
+}
+
+pub fn from_json(json: &Value) -> Result<PlutusData, ReplError> {
+    let Some(object) = json.as_object() else {
+        return Err(invalid(format!("expected an object, got `{}`", json)));
+    };
+    let items = |key: &str| {
+        object[key]
+            .as_array()
+            .ok_or_else(|| invalid(format!("`{}` must be a list", key)))
+    };
+
+    if let Some(index) = object.get("constructor") {
+        let index = index
+            .as_u64()
+            .ok_or_else(|| invalid("`constructor` must be a natural number"))?;
+        let fields = match object.get("fields") {
+            Some(_) => items("fields")?
+                .iter()
+                .map(from_json)
+                .collect::<Result<_, _>>()?,
+            None => Vec::new(),
+        };
+        Ok(Data::constr(index, fields))
+    } else if let Some(int) = object.get("int") {
+        // Numbers too big for 64 bits only survive parsing as text
+        let int = int
+            .as_i64()
+            .map(BigInt::from)
+            .or_else(|| int.as_u64().map(BigInt::from))
+            .or_else(|| int.as_str().and_then(|text| text.parse().ok()))
+            .ok_or_else(|| invalid(format!("`{}` is not an integer", int)))?;
+        Ok(Data::integer(int))
+    } else if let Some(bytes) = object.get("bytes") {
+        let bytes = bytes
+            .as_str()
+            .ok_or_else(|| invalid("`bytes` must be a hex string"))
+            .and_then(|text| hex::decode(text).map_err(|e| invalid(e.to_string())))?;
+        Ok(Data::bytestring(bytes))
+    } else if object.contains_key("list") {
+        Ok(Data::list(
+            items("list")?
+                .iter()
+                .map(from_json)
+                .collect::<Result<_, _>>()?,
+        ))
+    } else if object.contains_key("map") {
+        let entries = items("map")?
+            .iter()
+            .map(|entry| match (entry.get("k"), entry.get("v")) {
+                (Some(key), Some(value)) => Ok((from_json(key)?, from_json(value)?)),
+                _ => Err(invalid("entries of `map` must have a `k` and a `v`")),
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Data::map(entries))
+    } else {
+        Err(invalid(
+            "expected one of `constructor`, `int`, `bytes`, `list` or `map`",
+        ))
+    }
+}
+
+pub fn to_json(data: &PlutusData) -> Value {
+    match data {
+        PlutusData::BigInt(int) => {
+            let int = from_pallas_bigint(int);
+            match i64::try_from(&int) {
+                Ok(small) => json!({ "int": small }),
+                Err(_) => json!({ "int": int.to_string() }),
+            }
+        }
+        PlutusData::BoundedBytes(bytes) => json!({ "bytes": hex::encode(&bytes[..]) }),
+        PlutusData::Constr(constr) => json!({
+            "constructor": constructor_index(constr.tag, constr.any_constructor),
+            "fields": constr.fields.iter().map(to_json).collect::<Vec<_>>(),
+        }),
+        PlutusData::Map(entries) => json!({
+            "map": entries
+                .iter()
+                .map(|(key, value)| json!({ "k": to_json(key), "v": to_json(value) }))
+                .collect::<Vec<_>>(),
+        }),
+        PlutusData::Array(items) => json!({
+            "list": items.iter().map(to_json).collect::<Vec<_>>(),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::{from_json, parse, to_json};
+
+    #[test]
+    fn test_json_round_trip() {
+        let json = json!({
+            "constructor": 1,
+            "fields": [
+                { "int": -42 },
+                { "bytes": "cafe" },
+                { "list": [{ "int": 1 }, { "int": "123456789012345678901234567890" }] },
+                { "map": [{ "k": { "bytes": "" }, "v": { "constructor": 200, "fields": [] } }] }
+            ]
+        });
+        let data = from_json(&json).unwrap();
+        assert_eq!(to_json(&data), json);
+
+        // CBOR hex decodes to the same data
+        let cbor = hex::encode(uplc::plutus_data_to_bytes(&data).unwrap());
+        assert_eq!(parse(&cbor).unwrap(), data);
+
+        assert!(parse(r#"{"int": "nope"}"#).is_err());
+        assert!(parse(r#"{"map": [{"k": {"int": 1}}]}"#).is_err());
+        assert!(parse(r#"{"string": "text"}"#).is_err());
+    }
+}
@@ -0,0 +1,215 @@
+//! Rust values from the Plutus Data of results, for tools embedding the evaluator
+//!
+//! Values are decoded the way Aiken encodes them as data: integers and byte arrays as they are,
+//! lists and tuples as lists, `Pairs` as maps, and constructors by index, eg. `False` and `None`
+//! as `Constr 0 []` and `Constr 1 []`.
+
+use std::collections::BTreeMap;
+
+use num_bigint::BigInt;
+use uplc::{PlutusData, machine::value::from_pallas_bigint};
+
+use super::{ReplError, data::constructor_index};
+
+/// A value that can be decoded from Plutus Data
+pub trait FromData: Sized {
+    fn from_data(data: &PlutusData) -> Result<Self, ReplError>;
+}
+
+/// The bytes of a byte array. `Vec<u8>` decodes a list of integers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bytes(pub Vec<u8>);
+
+/// A constructor of any type, by its index
+#[derive(Debug, Clone, PartialEq)]
+pub struct Constr {
+    pub index: u64,
+    pub fields: Vec<PlutusData>,
+}
+
+impl Constr {
+    /// Decode field `i`
+    pub fn field<T: FromData>(&self, i: usize) -> Result<T, ReplError> {
+        let field = self
+            .fields
+            .get(i)
+            .ok_or_else(|| invalid(format!("constructor {} has no field {}", self.index, i)))?;
+        T::from_data(field)
+    }
+}
+
+fn invalid(message: impl Into<String>) -> ReplError {
+    ReplError::InvalidData {
+        message: message.into(),
+    }
+}
+
+fn expected(what: &str, data: &PlutusData) -> ReplError {
+    let got = match data {
+        PlutusData::BigInt(_) => "an integer",
+        PlutusData::BoundedBytes(_) => "a byte array",
+        PlutusData::Constr(_) => "a constructor",
+        PlutusData::Map(_) => "a map",
+        PlutusData::Array(_) => "a list",
+    };
+    invalid(format!("expected {}, got {}", what, got))
+}
+
+impl FromData for PlutusData {
+    fn from_data(data: &PlutusData) -> Result<Self, ReplError> {
+        Ok(data.clone())
+    }
+}
+
+impl FromData for BigInt {
+    fn from_data(data: &PlutusData) -> Result<Self, ReplError> {
+        match data {
+            PlutusData::BigInt(int) => Ok(from_pallas_bigint(int)),
+            _ => Err(expected("an integer", data)),
+        }
+    }
+}
+
+impl FromData for i64 {
+    fn from_data(data: &PlutusData) -> Result<Self, ReplError> {
+        let int = BigInt::from_data(data)?;
+        i64::try_from(&int).map_err(|_| invalid(format!("{} doesn't fit in an i64", int)))
+    }
+}
+
+impl FromData for u64 {
+    fn from_data(data: &PlutusData) -> Result<Self, ReplError> {
+        let int = BigInt::from_data(data)?;
+        u64::try_from(&int).map_err(|_| invalid(format!("{} doesn't fit in a u64", int)))
+    }
+}
+
+impl FromData for Bytes {
+    fn from_data(data: &PlutusData) -> Result<Self, ReplError> {
+        match data {
+            PlutusData::BoundedBytes(bytes) => Ok(Bytes(bytes.to_vec())),
+            _ => Err(expected("a byte array", data)),
+        }
+    }
+}
+
+/// Text, from a byte array of UTF-8
+impl FromData for String {
+    fn from_data(data: &PlutusData) -> Result<Self, ReplError> {
+        let Bytes(bytes) = Bytes::from_data(data)?;
+        String::from_utf8(bytes).map_err(|_| invalid("the byte array isn't UTF-8 text"))
+    }
+}
+
+impl FromData for Constr {
+    fn from_data(data: &PlutusData) -> Result<Self, ReplError> {
+        match data {
+            PlutusData::Constr(constr) => Ok(Constr {
+                index: constructor_index(constr.tag, constr.any_constructor),
+                fields: constr.fields.to_vec(),
+            }),
+            _ => Err(expected("a constructor", data)),
+        }
+    }
+}
+
+impl FromData for bool {
+    fn from_data(data: &PlutusData) -> Result<Self, ReplError> {
+        match Constr::from_data(data)? {
+            Constr { index: 0, fields } if fields.is_empty() => Ok(false),
+            Constr { index: 1, fields } if fields.is_empty() => Ok(true),
+            _ => Err(invalid("expected `True` or `False`")),
+        }
+    }
+}
+
+impl<T: FromData> FromData for Option<T> {
+    fn from_data(data: &PlutusData) -> Result<Self, ReplError> {
+        let constr = Constr::from_data(data)?;
+        match (constr.index, constr.fields.len()) {
+            (0, 1) => constr.field(0).map(Some),
+            (1, 0) => Ok(None),
+            _ => Err(invalid("expected `Some(value)` or `None`")),
+        }
+    }
+}
+
+impl<T: FromData> FromData for Vec<T> {
+    fn from_data(data: &PlutusData) -> Result<Self, ReplError> {
+        match data {
+            PlutusData::Array(items) => items.iter().map(T::from_data).collect(),
+            _ => Err(expected("a list", data)),
+        }
+    }
+}
+
+/// A tuple or a `Pair`, both lists of two items
+impl<A: FromData, B: FromData> FromData for (A, B) {
+    fn from_data(data: &PlutusData) -> Result<Self, ReplError> {
+        match data {
+            PlutusData::Array(items) if items.len() == 2 => {
+                Ok((A::from_data(&items[0])?, B::from_data(&items[1])?))
+            }
+            _ => Err(expected("a list of two items", data)),
+        }
+    }
+}
+
+/// `Pairs`, or any other map
+impl<K: FromData + Ord, V: FromData> FromData for BTreeMap<K, V> {
+    fn from_data(data: &PlutusData) -> Result<Self, ReplError> {
+        match data {
+            PlutusData::Map(entries) => entries
+                .iter()
+                .map(|(key, value)| Ok((K::from_data(key)?, V::from_data(value)?)))
+                .collect(),
+            _ => Err(expected("a map", data)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use uplc::ast::Data;
+
+    use super::{Bytes, Constr, FromData};
+
+    #[test]
+    fn test_from_data() {
+        assert_eq!(i64::from_data(&Data::integer((-42).into())).unwrap(), -42);
+        assert!(u64::from_data(&Data::integer((-42).into())).is_err());
+        assert!(i64::from_data(&Data::bytestring(vec![1])).is_err());
+
+        let bytes = Data::bytestring(b"hi".to_vec());
+        assert_eq!(Bytes::from_data(&bytes).unwrap(), Bytes(b"hi".to_vec()));
+        assert_eq!(String::from_data(&bytes).unwrap(), "hi");
+
+        assert!(bool::from_data(&Data::constr(1, vec![])).unwrap());
+        assert_eq!(
+            Option::<i64>::from_data(&Data::constr(0, vec![Data::integer(1.into())])).unwrap(),
+            Some(1)
+        );
+        assert_eq!(
+            Option::<i64>::from_data(&Data::constr(1, vec![])).unwrap(),
+            None
+        );
+
+        let list = Data::list(vec![Data::integer(1.into()), Data::bytestring(vec![])]);
+        assert!(Vec::<i64>::from_data(&list).is_err());
+        let (int, bytes) = <(i64, Bytes)>::from_data(&list).unwrap();
+        assert_eq!((int, bytes), (1, Bytes(vec![])));
+
+        let map = Data::map(vec![(Data::integer(2.into()), Data::constr(0, vec![]))]);
+        assert_eq!(
+            BTreeMap::<u64, bool>::from_data(&map).unwrap(),
+            BTreeMap::from([(2, false)])
+        );
+
+        let constr = Constr::from_data(&Data::constr(200, vec![Data::integer(7.into())])).unwrap();
+        assert_eq!(constr.index, 200);
+        assert_eq!(constr.field::<i64>(0).unwrap(), 7);
+        assert!(constr.field::<i64>(1).is_err());
+    }
+}
@@ -0,0 +1,410 @@
+//! Which definitions of the session refer to which others
+//!
+//! Like the docs lookup, this reads the typed AST of the session module: a function or constant
+//! the compiler resolved to the session module is a reference to its definition, and a type of
+//! the session module, or a constructor of it, a reference to the type. Local variables, even
+//! named like a definition of the session, aren't references.
+
+use std::{collections::HashMap, rc::Rc};
+
+use aiken_lang::{
+    ast::{Definition, TypedDefinition},
+    expr::TypedExpr,
+    tipo::{Type, TypeVar, ValueConstructorVariant},
+};
+use aiken_project::module::CheckedModule;
+
+/// A definition of the session, in the graph
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GraphNode {
+    pub name: String,
+    /// `function`, `constant`, `type`, `test` or `validator`
+    pub kind: &'static str,
+}
+
+/// Definitions of the session and their references to each other
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DependencyGraph {
+    /// In the order of the context
+    pub nodes: Vec<GraphNode>,
+    /// `(from, to)` when `nodes[from]` refers to `nodes[to]`, in the order of `nodes`
+    pub edges: Vec<(usize, usize)>,
+}
+
+impl DependencyGraph {
+    /// The graph of the definitions of the checked session module. Imports aren't part of it.
+    pub(crate) fn new(module: &CheckedModule) -> Self {
+        let definitions: Vec<(GraphNode, &TypedDefinition)> = module
+            .ast
+            .definitions()
+            .filter_map(|definition| {
+                let (name, kind) = match definition {
+                    Definition::Fn(f) => (&f.name, "function"),
+                    Definition::ModuleConstant(c) => (&c.name, "constant"),
+                    Definition::DataType(d) => (&d.name, "type"),
+                    Definition::TypeAlias(a) => (&a.alias, "type"),
+                    Definition::Test(t) => (&t.name, "test"),
+                    Definition::Validator(v) => (&v.name, "validator"),
+                    _ => return None,
+                };
+                let name = name.clone();
+                Some((GraphNode { name, kind }, definition))
+            })
+            .collect();
+
+        // Names other definitions can refer to, tests and validators having none
+        let mut scope = Scope {
+            module: &module.name,
+            values: HashMap::new(),
+            types: HashMap::new(),
+        };
+        for (i, (node, _)) in definitions.iter().enumerate() {
+            match node.kind {
+                "function" | "constant" => scope.values.insert(node.name.as_str(), i),
+                "type" => scope.types.insert(node.name.as_str(), i),
+                _ => None,
+            };
+        }
+
+        let mut edges = Vec::new();
+        for (from, (_, definition)) in definitions.iter().enumerate() {
+            let mut targets = Vec::new();
+            scope.definition(definition, &mut targets);
+            targets.retain(|to| *to != from);
+            targets.sort();
+            targets.dedup();
+            edges.extend(targets.into_iter().map(|to| (from, to)));
+        }
+
+        Self {
+            nodes: definitions.into_iter().map(|(node, _)| node).collect(),
+            edges,
+        }
+    }
+
+    fn index(&self, name: &str) -> Option<usize> {
+        self.nodes.iter().position(|node| node.name == name)
+    }
+
+    /// Names of the definitions `name` refers to
+    pub fn dependencies(&self, name: &str) -> Vec<&str> {
+        let Some(i) = self.index(name) else {
+            return Vec::new();
+        };
+        self.edges
+            .iter()
+            .filter(|(from, _)| *from == i)
+            .map(|(_, to)| self.nodes[*to].name.as_str())
+            .collect()
+    }
+
+    /// Names of the definitions that refer to `name`, directly or through others: those to check
+    /// again when it changes
+    pub fn dependents(&self, name: &str) -> Vec<&str> {
+        let Some(i) = self.index(name) else {
+            return Vec::new();
+        };
+        let mut reached = vec![false; self.nodes.len()];
+        let mut pending = vec![i];
+        while let Some(to) = pending.pop() {
+            for (from, _) in self.edges.iter().filter(|(_, t)| *t == to) {
+                if !reached[*from] && *from != i {
+                    reached[*from] = true;
+                    pending.push(*from);
+                }
+            }
+        }
+        self.nodes
+            .iter()
+            .zip(reached)
+            .filter(|(_, reached)| *reached)
+            .map(|(node, _)| node.name.as_str())
+            .collect()
+    }
+
+    /// The part of the graph around `name`: what it refers to, what refers to it, and the
+    /// references between them. None if the session has no definition called `name`.
+    pub fn around(&self, name: &str) -> Option<DependencyGraph> {
+        let i = self.index(name)?;
+        let mut kept = vec![false; self.nodes.len()];
+        kept[i] = true;
+        // What `name` refers to, then what refers to it, each followed through
+        for forward in [true, false] {
+            let mut pending = vec![i];
+            while let Some(node) = pending.pop() {
+                for (from, to) in &self.edges {
+                    let (at, next) = if forward { (*from, *to) } else { (*to, *from) };
+                    if at == node && !kept[next] {
+                        kept[next] = true;
+                        pending.push(next);
+                    }
+                }
+            }
+        }
+
+        let mut indices = vec![None; self.nodes.len()];
+        let mut nodes = Vec::new();
+        for (i, node) in self.nodes.iter().enumerate() {
+            if kept[i] {
+                indices[i] = Some(nodes.len());
+                nodes.push(node.clone());
+            }
+        }
+        let edges = self
+            .edges
+            .iter()
+            .filter_map(|(from, to)| Some((indices[*from]?, indices[*to]?)))
+            .collect();
+        Some(DependencyGraph { nodes, edges })
+    }
+
+    /// The graph in the DOT language of Graphviz, references pointing from the definition that
+    /// makes them
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph session {\n  rankdir=RL;\n");
+        for node in &self.nodes {
+            let shape = match node.kind {
+                "type" => "ellipse",
+                "constant" => "note",
+                "test" | "validator" => "hexagon",
+                _ => "box",
+            };
+            dot.push_str(&format!("  \"{}\" [shape={}];\n", node.name, shape));
+        }
+        for (from, to) in &self.edges {
+            dot.push_str(&format!(
+                "  \"{}\" -> \"{}\";\n",
+                self.nodes[*from].name, self.nodes[*to].name
+            ));
+        }
+        dot.push('}');
+        dot
+    }
+}
+
+/// Definitions of the session module, by the name references resolve to
+struct Scope<'a> {
+    module: &'a str,
+    values: HashMap<&'a str, usize>,
+    types: HashMap<&'a str, usize>,
+}
+
+impl Scope<'_> {
+    /// Push the definitions `definition` refers to on `targets`
+    fn definition(&self, definition: &TypedDefinition, targets: &mut Vec<usize>) {
+        match definition {
+            Definition::Fn(f) => {
+                for arg in &f.arguments {
+                    self.tipo(&arg.tipo, targets);
+                }
+                self.tipo(&f.return_type, targets);
+                self.expr(&f.body, targets);
+            }
+            Definition::ModuleConstant(c) => {
+                self.tipo(&c.tipo, targets);
+                self.expr(&c.value, targets);
+            }
+            Definition::DataType(d) => {
+                for constructor in &d.constructors {
+                    for arg in &constructor.arguments {
+                        self.tipo(&arg.tipo, targets);
+                    }
+                }
+            }
+            Definition::TypeAlias(a) => self.tipo(&a.tipo, targets),
+            Definition::Test(t) => {
+                for arg in &t.arguments {
+                    self.expr(&arg.via, targets);
+                }
+                self.expr(&t.body, targets);
+            }
+            Definition::Validator(v) => {
+                for param in &v.params {
+                    self.tipo(&param.tipo, targets);
+                }
+                for handler in v.handlers.iter().chain([&v.fallback]) {
+                    for arg in &handler.arguments {
+                        self.tipo(&arg.tipo, targets);
+                    }
+                    self.expr(&handler.body, targets);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Push the definitions `expr` refers to on `targets`: the functions and constants it
+    /// uses, and the types of its parts, constructors included
+    fn expr(&self, expr: &TypedExpr, targets: &mut Vec<usize>) {
+        self.tipo(&expr.tipo(), targets);
+        match expr {
+            TypedExpr::Var { constructor, .. } => match &constructor.variant {
+                ValueConstructorVariant::ModuleFn { name, module, .. }
+                | ValueConstructorVariant::ModuleConstant { name, module, .. }
+                    if module == self.module =>
+                {
+                    targets.extend(self.values.get(name.as_str()));
+                }
+                _ => {}
+            },
+            TypedExpr::Sequence { expressions, .. } | TypedExpr::Pipeline { expressions, .. } => {
+                for expr in expressions {
+                    self.expr(expr, targets);
+                }
+            }
+            TypedExpr::Fn { body, .. } => self.expr(body, targets),
+            TypedExpr::List { elements, tail, .. } => {
+                for expr in elements.iter().chain(tail.as_deref()) {
+                    self.expr(expr, targets);
+                }
+            }
+            TypedExpr::Call { fun, args, .. } => {
+                self.expr(fun, targets);
+                for arg in args {
+                    self.expr(&arg.value, targets);
+                }
+            }
+            TypedExpr::BinOp { left, right, .. } => {
+                self.expr(left, targets);
+                self.expr(right, targets);
+            }
+            TypedExpr::Assignment { value, .. } => self.expr(value, targets),
+            TypedExpr::Trace { then, text, .. } => {
+                self.expr(text, targets);
+                self.expr(then, targets);
+            }
+            TypedExpr::When {
+                subject, clauses, ..
+            } => {
+                self.expr(subject, targets);
+                for clause in clauses {
+                    self.expr(&clause.then, targets);
+                }
+            }
+            TypedExpr::If {
+                branches,
+                final_else,
+                ..
+            } => {
+                for branch in branches.iter() {
+                    self.expr(&branch.condition, targets);
+                    self.expr(&branch.body, targets);
+                }
+                self.expr(final_else, targets);
+            }
+            TypedExpr::RecordAccess { record, .. } => self.expr(record, targets),
+            TypedExpr::Tuple { elems, .. } => {
+                for expr in elems {
+                    self.expr(expr, targets);
+                }
+            }
+            TypedExpr::Pair { fst, snd, .. } => {
+                self.expr(fst, targets);
+                self.expr(snd, targets);
+            }
+            TypedExpr::TupleIndex { tuple, .. } => self.expr(tuple, targets),
+            TypedExpr::RecordUpdate { spread, args, .. } => {
+                self.expr(spread, targets);
+                for arg in args {
+                    self.expr(&arg.value, targets);
+                }
+            }
+            TypedExpr::UnOp { value, .. } => self.expr(value, targets),
+            _ => {}
+        }
+    }
+
+    /// Push the types of the session `tipo` is made of on `targets`, aliases included
+    fn tipo(&self, tipo: &Rc<Type>, targets: &mut Vec<usize>) {
+        let alias = match tipo.as_ref() {
+            Type::App { alias, .. }
+            | Type::Fn { alias, .. }
+            | Type::Tuple { alias, .. }
+            | Type::Pair { alias, .. }
+            | Type::Var { alias, .. } => alias,
+        };
+        if let Some(alias) = alias {
+            targets.extend(self.types.get(alias.alias.as_str()));
+        }
+        match tipo.as_ref() {
+            Type::App {
+                module, name, args, ..
+            } => {
+                if module == self.module {
+                    targets.extend(self.types.get(name.as_str()));
+                }
+                for arg in args {
+                    self.tipo(arg, targets);
+                }
+            }
+            Type::Fn { args, ret, .. } => {
+                for arg in args {
+                    self.tipo(arg, targets);
+                }
+                self.tipo(ret, targets);
+            }
+            Type::Tuple { elems, .. } => {
+                for elem in elems {
+                    self.tipo(elem, targets);
+                }
+            }
+            Type::Pair { fst, snd, .. } => {
+                self.tipo(fst, targets);
+                self.tipo(snd, targets);
+            }
+            Type::Var { tipo, .. } => {
+                if let TypeVar::Link { tipo } = &*tipo.borrow() {
+                    self.tipo(tipo, targets);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::evaluator::ReplEvaluator;
+
+    #[test]
+    fn test_dependency_graph() {
+        let mut repl = ReplEvaluator::new();
+        assert!(repl.dependency_graph().unwrap().nodes.is_empty());
+
+        for code in [
+            "pub type Color {\n  Red\n  Green\n}",
+            "pub fn double(x) {\n  x * 2\n}",
+            "pub fn paint(double: Int) {\n  if double > 0 { Red } else { Green }\n}",
+            "/// Twice double\npub fn quadruple(x) {\n  double(double(x))\n}",
+            "test quadruple_works() {\n  quadruple(1) == 4\n}",
+        ] {
+            assert!(repl.eval(code).is_ok(), "{}", code);
+        }
+        let graph = repl.dependency_graph().unwrap();
+
+        let names: Vec<&str> = graph.nodes.iter().map(|node| node.name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec!["Color", "double", "paint", "quadruple", "quadruple_works"]
+        );
+        assert_eq!(graph.nodes[0].kind, "type");
+        // The argument named `double` shadows the function, and the constructors refer to Color
+        assert_eq!(graph.dependencies("paint"), vec!["Color"]);
+        assert_eq!(graph.dependencies("quadruple"), vec!["double"]);
+        assert_eq!(
+            graph.dependents("double"),
+            vec!["quadruple", "quadruple_works"]
+        );
+        assert!(graph.dependents("quadruple_works").is_empty());
+
+        let around = graph.around("quadruple").unwrap();
+        let names: Vec<&str> = around.nodes.iter().map(|node| node.name.as_str()).collect();
+        assert_eq!(names, vec!["double", "quadruple", "quadruple_works"]);
+        assert_eq!(around.edges, vec![(1, 0), (2, 1)]);
+        assert!(graph.around("nothing").is_none());
+
+        let dot = around.to_dot();
+        assert!(dot.starts_with("digraph session {\n"));
+        assert!(dot.contains("  \"quadruple\" -> \"double\";\n"));
+        assert!(dot.contains("  \"quadruple_works\" [shape=hexagon];\n"));
+    }
+}
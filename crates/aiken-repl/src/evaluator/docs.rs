@@ -0,0 +1,127 @@
+//! Documentation lookup for definitions in the session and its dependencies
+//!
+//! Docs are read straight from the typed ASTs of the checked modules, so anything
+//! that type-checks (user definitions, stdlib functions, etc.) can be looked up.
+
+use std::fmt;
+
+use aiken_lang::{
+    ast::{Definition, TypedDefinition},
+    tipo::{Type, pretty::Printer},
+};
+use aiken_project::module::CheckedModule;
+
+use super::Provenance;
+
+/// Documentation and signature of a single definition
+#[derive(Debug, Clone)]
+pub struct DocEntry {
+    pub name: String,
+    pub module: String,
+    pub signature: String,
+    pub doc: Option<String>,
+    /// Where the definition was written, for the session's own
+    pub provenance: Option<Provenance>,
+}
+
+impl fmt::Display for DocEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}.{}", self.module, self.name)?;
+        write!(f, "  {}", self.signature)?;
+        if let Some(provenance) = &self.provenance {
+            write!(f, "\n  defined {}", provenance)?;
+        }
+        match &self.doc {
+            Some(doc) if !doc.trim().is_empty() => write!(f, "\n\n{}", doc.trim_end()),
+            _ => write!(f, "\n\n(no documentation)"),
+        }
+    }
+}
+
+/// Find the docs of `name` among the checked modules.
+///
+/// Qualified names (`list.map`) are matched against the last segment of the module
+/// name. Unqualified names are looked up in `session_module` first, then everywhere else.
+pub fn lookup(modules: &[CheckedModule], name: &str, session_module: &str) -> Option<DocEntry> {
+    match name.rsplit_once('.') {
+        Some((module, name)) => modules
+            .iter()
+            .filter(|m| m.name == module || m.name.ends_with(&format!("/{}", module)))
+            .find_map(|m| find_in_module(m, name)),
+        None => modules
+            .iter()
+            .find(|m| m.name == session_module)
+            .and_then(|m| find_in_module(m, name))
+            .or_else(|| {
+                modules
+                    .iter()
+                    .filter(|m| m.name != session_module)
+                    .find_map(|m| find_in_module(m, name))
+            }),
+    }
+}
+
+fn find_in_module(module: &CheckedModule, name: &str) -> Option<DocEntry> {
+    module
+        .ast
+        .definitions()
+        .find_map(|def| signature_of(def, name))
+        .map(|(signature, doc)| DocEntry {
+            name: name.to_string(),
+            module: module.name.clone(),
+            signature,
+            doc,
+            provenance: None,
+        })
+}
+
+/// Render the signature of a definition if it's called `name`
+fn signature_of(def: &TypedDefinition, name: &str) -> Option<(String, Option<String>)> {
+    let mut printer = Printer::new();
+    match def {
+        Definition::Fn(f) if f.name == name => {
+            let tipo = Type::function(
+                f.arguments.iter().map(|arg| arg.tipo.clone()).collect(),
+                f.return_type.clone(),
+            );
+            Some((
+                format!("{} : {}", f.name, printer.pretty_print(&tipo, 0)),
+                f.doc.clone(),
+            ))
+        }
+        Definition::ModuleConstant(c) if c.name == name => Some((
+            format!("{} : {}", c.name, printer.pretty_print(&c.tipo, 0)),
+            c.doc.clone(),
+        )),
+        Definition::DataType(d) if d.name == name => {
+            let constructors: Vec<_> = d.constructors.iter().map(|c| c.name.clone()).collect();
+            Some((
+                format!(
+                    "type {}{} {{ {} }}",
+                    d.name,
+                    type_parameters(&d.parameters),
+                    constructors.join(" | ")
+                ),
+                d.doc.clone(),
+            ))
+        }
+        Definition::TypeAlias(a) if a.alias == name => Some((
+            format!(
+                "type {}{} = {}",
+                a.alias,
+                type_parameters(&a.parameters),
+                printer.pretty_print(&a.tipo, 0)
+            ),
+            a.doc.clone(),
+        )),
+        _ => None,
+    }
+}
+
+fn type_parameters(parameters: &[String]) -> String {
+    if parameters.is_empty() {
+        String::new()
+    } else {
+        format!("<{}>", parameters.join(", "))
+    }
+}
@@ -0,0 +1,488 @@
+//! A local mock chain, to walk through lock and unlock flows without any network
+//!
+//! The ledger is reduced to lovelace, inline datums and signatures: no fees, minting or validity
+//! ranges. Owners are labels, eg. `alice`, and those naming a validator of the session are
+//! scripts. Owners with a key of the session (`:keygen`) have its hash as credential, others the
+//! label's bytes, padded to 28, so they're readable in traces.
+//!
+//! Transactions are written one clause per line (or separated with `;`):
+//!
+//! ```text
+//! spend #0 with Unlock
+//! pay alice 10ada
+//! pay hello_world 2.5ada datum Datum { owner: "alice" }
+//! sign bob
+//! ```
+//!
+//! Spending an output of a validator runs it on the script context of the transaction, as in
+//! Plutus V3. Outputs of keys get signed by their owner.
+
+use std::{collections::BTreeMap, fmt, rc::Rc};
+
+use num_bigint::BigInt;
+use uplc::{
+    PlutusData,
+    ast::{Constant, Data, Term},
+    machine::cost_model::ExBudget,
+};
+
+use super::{DisplaySettings, ReplError, ReplEvaluator, pretty};
+
+const LOVELACE_PER_ADA: u64 = 1_000_000;
+
+/// Size of key and script hashes
+const HASH_SIZE: usize = 28;
+
+/// Result of running a validator on a script context
+#[derive(Debug, Clone)]
+pub struct ValidatorRun {
+    pub budget: ExBudget,
+    pub traces: Vec<String>,
+    /// Why the validator failed, if it did
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Owner {
+    name: String,
+    script: bool,
+    /// Hash of the key or script
+    hash: Vec<u8>,
+}
+
+impl Owner {
+    /// Address with the owner's credential and no stake credential
+    fn address(&self) -> PlutusData {
+        let credential = Data::constr(
+            self.script as u64,
+            vec![Data::bytestring(self.hash.clone())],
+        );
+        Data::constr(0, vec![credential, none()])
+    }
+}
+
+/// Hash of the session's key named `name`, or else the name's bytes padded to a hash size
+fn credential_hash(eval: &ReplEvaluator, name: &str) -> Vec<u8> {
+    eval.key_hash(name).unwrap_or_else(|| {
+        let mut hash = name.as_bytes().to_vec();
+        hash.resize(HASH_SIZE, 0);
+        hash
+    })
+}
+
+#[derive(Debug, Clone)]
+struct Output {
+    owner: Owner,
+    lovelace: u64,
+    datum: Option<PlutusData>,
+}
+
+impl Output {
+    fn data(&self) -> PlutusData {
+        let datum = match &self.datum {
+            Some(datum) => Data::constr(2, vec![datum.clone()]),
+            None => Data::constr(0, vec![]),
+        };
+        Data::constr(
+            0,
+            vec![self.owner.address(), lovelace(self.lovelace), datum, none()],
+        )
+    }
+}
+
+/// An output reference: the number of the transaction that created it and its index
+type OutputRef = (u64, u64);
+
+fn output_ref_data((tx, index): OutputRef) -> PlutusData {
+    Data::constr(
+        0,
+        vec![
+            Data::bytestring(tx_id(tx)),
+            Data::integer(BigInt::from(index)),
+        ],
+    )
+}
+
+/// Ids of transactions are their number, on 32 bytes
+fn tx_id(tx: u64) -> Vec<u8> {
+    let mut id = vec![0; 24];
+    id.extend(tx.to_be_bytes());
+    id
+}
+
+fn none() -> PlutusData {
+    Data::constr(1, vec![])
+}
+
+fn lovelace(quantity: u64) -> PlutusData {
+    Data::map(vec![(
+        Data::bytestring(vec![]),
+        Data::map(vec![(
+            Data::bytestring(vec![]),
+            Data::integer(BigInt::from(quantity)),
+        )]),
+    )])
+}
+
+/// UTxOs of the mock chain, with the outputs they were created by
+#[derive(Debug, Clone, Default)]
+pub struct MockChain {
+    /// Unspent outputs, by the number they're shown with
+    utxos: BTreeMap<u64, (OutputRef, Output)>,
+    next_utxo: u64,
+    /// Transactions submitted so far, funding included
+    transactions: u64,
+}
+
+/// What a submitted transaction did
+#[derive(Debug, Clone)]
+pub struct Submission {
+    pub spent: Vec<u64>,
+    pub created: Vec<u64>,
+    /// Runs of the validators, by validator name
+    pub runs: Vec<(String, ValidatorRun)>,
+}
+
+impl fmt::Display for Submission {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (name, run) in &self.runs {
+            writeln!(
+                f,
+                "{}: mem {}, cpu {}",
+                name, run.budget.mem, run.budget.cpu
+            )?;
+            for trace in &run.traces {
+                writeln!(f, "  {}", trace)?;
+            }
+        }
+        let ids = |ids: &[u64]| {
+            ids.iter()
+                .map(|id| format!("#{}", id))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        write!(
+            f,
+            "Spent {}, created {}",
+            ids(&self.spent),
+            ids(&self.created)
+        )
+    }
+}
+
+/// A clause of a transaction, before its expressions are evaluated
+enum Clause<'a> {
+    Spend {
+        utxo: u64,
+        redeemer: Option<&'a str>,
+    },
+    Pay(&'a str),
+    Sign(&'a str),
+}
+
+impl MockChain {
+    /// Create an output from nothing, eg. `alice 100ada` or `hello_world 5ada datum 42`
+    pub fn fund(&mut self, eval: &mut ReplEvaluator, output: &str) -> Result<u64, ReplError> {
+        let output = parse_output(eval, output)?;
+        self.transactions += 1;
+        Ok(self.add_utxo((self.transactions, 0), output))
+    }
+
+    /// Validate a transaction and apply it to the UTxOs
+    pub fn submit(&mut self, eval: &mut ReplEvaluator, tx: &str) -> Result<Submission, ReplError> {
+        let clauses = tx
+            .split(['\n', ';'])
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(parse_clause)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut inputs = BTreeMap::new();
+        let mut outputs = Vec::new();
+        let mut signatories = Vec::new();
+        for clause in clauses {
+            match clause {
+                Clause::Spend { utxo, redeemer } => {
+                    let (output_ref, output) = self
+                        .utxos
+                        .get(&utxo)
+                        .ok_or_else(|| invalid(format!("no unspent output #{}", utxo)))?;
+                    let redeemer = match redeemer {
+                        Some(code) => eval.expression_data(code)?,
+                        None => Data::constr(0, vec![]),
+                    };
+                    if !output.owner.script && !signatories.contains(&output.owner.name) {
+                        signatories.push(output.owner.name.clone());
+                    }
+                    if inputs
+                        .insert(*output_ref, (utxo, output.clone(), redeemer))
+                        .is_some()
+                    {
+                        return Err(invalid(format!("#{} is spent twice", utxo)));
+                    }
+                }
+                Clause::Pay(output) => outputs.push(parse_output(eval, output)?),
+                Clause::Sign(name) => {
+                    if !signatories.iter().any(|signer| signer == name) {
+                        signatories.push(name.to_string());
+                    }
+                }
+            }
+        }
+
+        if inputs.is_empty() {
+            return Err(invalid("a transaction spends at least one output"));
+        }
+        let spent: u64 = inputs.values().map(|(_, output, _)| output.lovelace).sum();
+        let paid: u64 = outputs.iter().map(|output| output.lovelace).sum();
+        if spent != paid {
+            return Err(invalid(format!(
+                "inputs hold {} lovelace but outputs {}",
+                spent, paid
+            )));
+        }
+
+        let tx = self.transactions + 1;
+        let signatories = signatories
+            .iter()
+            .map(|name| credential_hash(eval, name))
+            .collect::<Vec<_>>();
+        let tx_info = self.tx_info(tx, &inputs, &outputs, &signatories);
+        let mut runs = Vec::new();
+        for (output_ref, (utxo, output, redeemer)) in &inputs {
+            if !output.owner.script {
+                continue;
+            }
+            let script_info = Data::constr(
+                1,
+                vec![
+                    output_ref_data(*output_ref),
+                    match &output.datum {
+                        Some(datum) => Data::constr(0, vec![datum.clone()]),
+                        None => none(),
+                    },
+                ],
+            );
+            let context = Data::constr(0, vec![tx_info.clone(), redeemer.clone(), script_info]);
+            let run = eval.run_validator(&output.owner.name, context)?;
+            if let Some(error) = &run.error {
+                let mut message = format!(
+                    "{} refused to spend #{}: {}",
+                    output.owner.name, utxo, error
+                );
+                for trace in &run.traces {
+                    message.push_str(&format!("\n  {}", trace));
+                }
+                return Err(invalid(message));
+            }
+            runs.push((output.owner.name.clone(), run));
+        }
+
+        self.transactions = tx;
+        let spent = inputs
+            .values()
+            .map(|(utxo, _, _)| *utxo)
+            .collect::<Vec<_>>();
+        for utxo in &spent {
+            self.utxos.remove(utxo);
+        }
+        let created = outputs
+            .into_iter()
+            .enumerate()
+            .map(|(index, output)| self.add_utxo((tx, index as u64), output))
+            .collect();
+        Ok(Submission {
+            spent,
+            created,
+            runs,
+        })
+    }
+
+    /// Unspent outputs, by the number they're shown with
+    pub fn utxos(&self) -> impl Iterator<Item = String> {
+        self.utxos.iter().map(|(id, (_, output))| {
+            let ada = output.lovelace as f64 / LOVELACE_PER_ADA as f64;
+            let owner = if output.owner.script {
+                format!("{} (script)", output.owner.name)
+            } else {
+                output.owner.name.clone()
+            };
+            let datum = output
+                .datum
+                .as_ref()
+                .map(|datum| {
+                    let term = Term::Constant(Rc::new(Constant::Data(datum.clone())));
+                    format!(
+                        ", datum {}",
+                        pretty::term(&term, &DisplaySettings::default())
+                    )
+                })
+                .unwrap_or_default();
+            format!("#{} {}: {} ada{}", id, owner, ada, datum)
+        })
+    }
+
+    fn add_utxo(&mut self, output_ref: OutputRef, output: Output) -> u64 {
+        let id = self.next_utxo;
+        self.next_utxo += 1;
+        self.utxos.insert(id, (output_ref, output));
+        id
+    }
+
+    /// Transaction info of Plutus V3. Inputs come sorted by their output reference.
+    fn tx_info(
+        &self,
+        tx: u64,
+        inputs: &BTreeMap<OutputRef, (u64, Output, PlutusData)>,
+        outputs: &[Output],
+        signatories: &[Vec<u8>],
+    ) -> PlutusData {
+        let inputs_data = inputs
+            .iter()
+            .map(|(output_ref, (_, output, _))| {
+                Data::constr(0, vec![output_ref_data(*output_ref), output.data()])
+            })
+            .collect();
+        let redeemers = inputs
+            .iter()
+            .filter(|(_, (_, output, _))| output.owner.script)
+            .map(|(output_ref, (_, _, redeemer))| {
+                (
+                    Data::constr(1, vec![output_ref_data(*output_ref)]),
+                    redeemer.clone(),
+                )
+            })
+            .collect();
+        let signatories = signatories.iter().cloned().map(Data::bytestring).collect();
+        let always = Data::constr(
+            0,
+            vec![
+                Data::constr(0, vec![Data::constr(0, vec![]), Data::constr(1, vec![])]),
+                Data::constr(0, vec![Data::constr(2, vec![]), Data::constr(1, vec![])]),
+            ],
+        );
+        Data::constr(
+            0,
+            vec![
+                Data::list(inputs_data),
+                Data::list(vec![]),
+                Data::list(outputs.iter().map(Output::data).collect()),
+                Data::integer(BigInt::from(0)),
+                Data::map(vec![]),
+                Data::list(vec![]),
+                Data::map(vec![]),
+                always,
+                Data::list(signatories),
+                Data::map(redeemers),
+                Data::map(vec![]),
+                Data::bytestring(tx_id(tx)),
+                Data::map(vec![]),
+                Data::list(vec![]),
+                none(),
+                none(),
+            ],
+        )
+    }
+}
+
+fn invalid(message: impl Into<String>) -> ReplError {
+    ReplError::TransactionRejected {
+        message: message.into(),
+    }
+}
+
+fn parse_clause(line: &str) -> Result<Clause<'_>, ReplError> {
+    let (keyword, rest) = line
+        .split_once(char::is_whitespace)
+        .map(|(keyword, rest)| (keyword, rest.trim()))
+        .unwrap_or((line, ""));
+    match keyword {
+        "spend" => {
+            let (utxo, redeemer) = match rest.split_once(" with ") {
+                Some((utxo, redeemer)) => (utxo.trim(), Some(redeemer.trim())),
+                None => (rest, None),
+            };
+            let utxo = utxo.trim_start_matches('#').parse().map_err(|_| {
+                invalid(format!(
+                    "expected `spend #<n> [with <redeemer>]`, got `{}`",
+                    line
+                ))
+            })?;
+            Ok(Clause::Spend { utxo, redeemer })
+        }
+        "pay" => Ok(Clause::Pay(rest)),
+        "sign" if !rest.is_empty() => Ok(Clause::Sign(rest)),
+        _ => Err(invalid(format!(
+            "expected `spend`, `pay` or `sign`, got `{}`",
+            line
+        ))),
+    }
+}
+
+/// An output given as `<owner> <amount> [datum <expr>]`
+fn parse_output(eval: &mut ReplEvaluator, output: &str) -> Result<Output, ReplError> {
+    let usage = || {
+        invalid(format!(
+            "expected `<owner> <amount> [datum <expr>]`, got `{}`",
+            output
+        ))
+    };
+    let mut words = output.trim().splitn(3, char::is_whitespace);
+    let (Some(name), Some(amount)) = (words.next(), words.next()) else {
+        return Err(usage());
+    };
+    let datum = match words.next().map(str::trim) {
+        Some(rest) => {
+            let code = rest.strip_prefix("datum").ok_or_else(usage)?;
+            Some(eval.expression_data(code.trim())?)
+        }
+        None => None,
+    };
+    Ok(Output {
+        owner: Owner {
+            name: name.to_string(),
+            script: eval.validator_names().any(|validator| validator == name),
+            hash: credential_hash(eval, name),
+        },
+        lovelace: parse_amount(amount).ok_or_else(usage)?,
+        datum,
+    })
+}
+
+/// Lovelace of `100ada`, `2.5ada` or `2000000` (in lovelace)
+fn parse_amount(amount: &str) -> Option<u64> {
+    match amount.strip_suffix("ada") {
+        Some(ada) => {
+            let (whole, fraction) = ada.split_once('.').unwrap_or((ada, ""));
+            if fraction.len() > 6 {
+                return None;
+            }
+            let fraction = format!("{:0<6}", fraction).parse::<u64>().ok()?;
+            whole
+                .parse::<u64>()
+                .ok()?
+                .checked_mul(LOVELACE_PER_ADA)?
+                .checked_add(fraction)
+        }
+        None => amount
+            .strip_suffix("lovelace")
+            .unwrap_or(amount)
+            .parse()
+            .ok(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::parse_amount;
+
+    #[test]
+    fn test_parse_amount() {
+        assert_eq!(parse_amount("100ada"), Some(100_000_000));
+        assert_eq!(parse_amount("2.5ada"), Some(2_500_000));
+        assert_eq!(parse_amount("1500000"), Some(1_500_000));
+        assert_eq!(parse_amount("42lovelace"), Some(42));
+        assert_eq!(parse_amount("0.0000001ada"), None);
+        assert_eq!(parse_amount("lots"), None);
+    }
+}
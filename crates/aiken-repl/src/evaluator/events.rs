@@ -0,0 +1,155 @@
+//! Compiler telemetry of the temp project, forwarded to whoever wants to show progress
+//!
+//! Resolving packages or compiling the stdlib for the first time can take a while. Instead of
+//! staying silent (or printing to a terminal the kernel doesn't have), events are turned into
+//! lines of text and sent down a channel.
+//!
+//! Results of tests are reported as events too, they're kept until taken with `take_tests`.
+//! The start of a compilation enters type-checking on the clock of the evaluation.
+
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex, mpsc::Sender},
+};
+
+use aiken_lang::{ast::UntypedExpr, format::Formatter, test_framework::TestResult};
+use aiken_project::telemetry::{CoverageMode, Event, EventListener};
+
+use super::{
+    reify::LINE_WIDTH,
+    report::TestOutcome,
+    timings::{Clock, Phase},
+};
+
+/// Receives one line of text per compiler event
+pub type ProgressTx = Sender<String>;
+
+/// Event listener of the temp project
+#[derive(Clone, Default)]
+pub struct Progress {
+    pub tx: Option<ProgressTx>,
+    /// What the label percentages of property tests are relative to
+    pub coverage: CoverageMode,
+    /// Outcomes of the tests run since they were last taken, shared by the clones
+    tests: Arc<Mutex<Vec<TestOutcome>>>,
+    /// Phases of the evaluation in progress, shared with the evaluator
+    pub(crate) clock: Clock,
+}
+
+impl Progress {
+    /// Outcomes of the tests run since the last call, in the order they were run
+    pub fn take_tests(&self) -> Vec<TestOutcome> {
+        self.tests
+            .lock()
+            .map(|mut tests| std::mem::take(&mut *tests))
+            .unwrap_or_default()
+    }
+}
+
+impl EventListener for Progress {
+    fn handle_event(&self, event: Event) {
+        if let Event::StartingCompilation { .. } = &event {
+            self.clock.enter(Phase::Typecheck);
+        }
+        if let Event::FinishedTests { tests, .. } = &event
+            && let Ok(mut outcomes) = self.tests.lock()
+        {
+            outcomes.extend(tests.iter().map(|test| outcome(test, self.coverage)));
+        }
+        if let Some(tx) = &self.tx
+            && let Some(line) = describe(&event)
+        {
+            // Nobody listening anymore is fine, progress is best effort
+            let _ = tx.send(line);
+        }
+    }
+}
+
+/// Text shown for an event, None for events that happen on every evaluation
+fn describe(event: &Event) -> Option<String> {
+    match event {
+        Event::WaitingForBuildDirLock => Some("Waiting for the build directory lock...".into()),
+        Event::ResolvingPackages { name } => Some(format!("Resolving packages of {}...", name)),
+        Event::ResolvingVersions => Some("Resolving dependency versions...".into()),
+        Event::PackagesDownloaded { count, .. } => Some(format!("Downloaded {} package(s)", count)),
+        Event::CollectingTests { .. } => Some("Collecting tests...".into()),
+        Event::RunningTests => Some("Running tests...".into()),
+        _ => None,
+    }
+}
+
+fn outcome(result: &TestResult<UntypedExpr, UntypedExpr>, mode: CoverageMode) -> TestOutcome {
+    let mut outcome = TestOutcome {
+        name: result.title().to_string(),
+        passed: result.is_success(),
+        budget: None,
+        iterations: None,
+        traces: result.traces().to_vec(),
+        counterexample: None,
+        coverage: Vec::new(),
+    };
+    match result {
+        TestResult::UnitTestResult(unit) => outcome.budget = Some(unit.spent_budget),
+        TestResult::PropertyTestResult(property) => {
+            outcome.iterations = Some(property.iterations);
+            outcome.coverage = coverage(&property.labels, property.iterations, mode);
+            // Shrunk by the framework, down to the smallest failing input it found
+            outcome.counterexample = property
+                .counterexample
+                .as_ref()
+                .ok()
+                .and_then(Option::as_ref)
+                .map(|expr| {
+                    Formatter::new()
+                        .expr(expr, false)
+                        .to_pretty_string(LINE_WIDTH)
+                });
+        }
+        TestResult::BenchmarkResult(_) => {}
+    }
+    outcome
+}
+
+/// Percentages of the labels of a property test, out of its runs or out of all the labels given
+fn coverage(
+    labels: &BTreeMap<String, usize>,
+    iterations: usize,
+    mode: CoverageMode,
+) -> Vec<(String, f64)> {
+    let total = match mode {
+        CoverageMode::RelativeToTests => iterations,
+        CoverageMode::RelativeToLabels => labels.values().sum(),
+    };
+    let mut coverage: Vec<(String, f64)> = labels
+        .iter()
+        .map(|(label, count)| {
+            let percent = 100.0 * *count as f64 / total.max(1) as f64;
+            (label.clone(), percent)
+        })
+        .collect();
+    coverage.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+    coverage
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use aiken_project::telemetry::CoverageMode;
+
+    use super::coverage;
+
+    #[test]
+    fn test_coverage() {
+        let labels = BTreeMap::from([("big".to_string(), 10), ("small".to_string(), 30)]);
+        assert_eq!(
+            coverage(&labels, 100, CoverageMode::RelativeToTests),
+            vec![("small".to_string(), 30.0), ("big".to_string(), 10.0)]
+        );
+        assert_eq!(
+            coverage(&labels, 100, CoverageMode::RelativeToLabels),
+            vec![("small".to_string(), 75.0), ("big".to_string(), 25.0)]
+        );
+        assert!(coverage(&BTreeMap::new(), 0, CoverageMode::RelativeToTests).is_empty());
+    }
+}
@@ -0,0 +1,157 @@
+//! Writing the session out as a module or as a new project, so that code prototyped in the REPL
+//! can move to a real project
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use aiken_lang::plutus_version::PlutusVersion;
+
+use super::{PRELUDE, PRELUDE_MODULE, ReplError, module_path};
+
+/// Owner of exported projects, in `aiken.toml`
+const OWNER: &str = "repl";
+
+/// The stdlib as a dependency in `aiken.toml`, in the version projects of this compiler start
+/// with. Resolving it takes the network the first time.
+pub(crate) const STDLIB: &str =
+    "\n[[dependencies]]\nname = \"aiken-lang/stdlib\"\nversion = \"v2.2.0\"\nsource = \"github\"\n";
+
+const GITIGNORE: &str =
+    "# Aiken compilation artifacts\nartifacts/\n# Aiken's project working directory\nbuild/\n";
+
+/// Write `code` as the module at `path`, which must not exist yet
+pub fn write_module(path: &Path, code: &str) -> Result<Vec<PathBuf>, ReplError> {
+    ensure_missing(path)?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, format!("{}\n", code))?;
+    Ok(vec![path.to_path_buf()])
+}
+
+/// Dependencies of a project with `code` in it, for `aiken.toml`: the stdlib if `code` imports
+/// one of its modules, nothing otherwise so that code without imports builds offline
+pub(crate) fn dependencies(code: &str) -> &'static str {
+    let uses_stdlib = code
+        .lines()
+        .filter_map(|line| line.strip_prefix("use "))
+        .any(|module| {
+            // `aiken/builtin` comes with the compiler
+            (module.starts_with("aiken/") && !module.starts_with("aiken/builtin"))
+                || module.starts_with("cardano/")
+        });
+    if uses_stdlib { STDLIB } else { "" }
+}
+
+/// Create a project in `dir`, laid out like `aiken new` does, with `code` as its module. The
+/// module goes in `validators/` when it has validators, and in `lib/` otherwise. The prelude
+/// comes along when the code imports it.
+pub fn write_project(
+    dir: &Path,
+    code: &str,
+    has_validators: bool,
+    uses_prelude: bool,
+    plutus_version: PlutusVersion,
+) -> Result<Vec<PathBuf>, ReplError> {
+    ensure_missing(dir)?;
+    let name = module_name(dir)?;
+
+    let aiken_toml = dir.join("aiken.toml");
+    let gitignore = dir.join(".gitignore");
+    let lib = dir.join("lib");
+    let validators = dir.join("validators");
+    let module = if has_validators { &validators } else { &lib }.join(format!("{}.ak", name));
+
+    fs::create_dir_all(&lib)?;
+    fs::create_dir_all(&validators)?;
+    fs::write(
+        &aiken_toml,
+        format!(
+            "name = \"{}/{}\"\nversion = \"0.0.0\"\nplutus = \"{}\"\n{}",
+            OWNER,
+            name,
+            plutus(plutus_version),
+            dependencies(code)
+        ),
+    )?;
+    fs::write(&gitignore, GITIGNORE)?;
+    fs::write(&module, format!("{}\n", code))?;
+    let mut files = vec![aiken_toml, gitignore, module];
+
+    if uses_prelude {
+        let prelude = module_path(&lib, PRELUDE_MODULE);
+        if let Some(prelude_dir) = prelude.parent() {
+            fs::create_dir_all(prelude_dir)?;
+        }
+        fs::write(&prelude, PRELUDE)?;
+        files.push(prelude);
+    }
+
+    Ok(files)
+}
+
+/// Exports never overwrite anything
+fn ensure_missing(path: &Path) -> Result<(), ReplError> {
+    if path.exists() {
+        return Err(ReplError::ExportFailed {
+            message: format!("{} already exists", path.display()),
+        });
+    }
+    Ok(())
+}
+
+/// Name of the project and its module, from the name of its directory: lowercase, with
+/// underscores for anything that can't be in a module name
+fn module_name(dir: &Path) -> Result<String, ReplError> {
+    let name: String = dir
+        .file_name()
+        .map(|name| name.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+
+    match name.chars().next() {
+        Some(c) if c.is_ascii_lowercase() => Ok(name),
+        _ => Err(ReplError::ExportFailed {
+            message: format!(
+                "{} doesn't make a module name, it should start with a letter",
+                dir.display()
+            ),
+        }),
+    }
+}
+
+pub(crate) fn plutus(version: PlutusVersion) -> &'static str {
+    match version {
+        PlutusVersion::V1 => "v1",
+        PlutusVersion::V2 => "v2",
+        PlutusVersion::V3 => "v3",
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use super::{STDLIB, dependencies, module_name};
+
+    #[test]
+    fn test_module_name() {
+        assert_eq!(
+            module_name(Path::new("out/My-Project")).unwrap(),
+            "my_project"
+        );
+        assert!(module_name(Path::new("out/42")).is_err());
+    }
+
+    #[test]
+    fn test_dependencies() {
+        assert_eq!(dependencies("pub fn f() { 1 }"), "");
+        assert_eq!(dependencies("use aiken/builtin\nuse repl/prelude"), "");
+        assert_eq!(dependencies("use aiken/collection/list.{map}"), STDLIB);
+        assert_eq!(dependencies("use cardano/transaction"), STDLIB);
+    }
+}
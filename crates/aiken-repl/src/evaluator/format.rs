@@ -0,0 +1,38 @@
+//! Code formatting using Aiken's own formatter
+
+use aiken_lang::{ast::ModuleKind, format, parser};
+
+use super::ReplError;
+
+/// Name of the function used to wrap expressions so the formatter accepts them
+const FORMAT_WRAPPER: &str = "repl_format";
+
+/// Format a piece of code made of module definitions
+pub fn format_module(code: &str) -> Result<String, ReplError> {
+    let (module, extra) =
+        parser::module(code, ModuleKind::Lib).map_err(|errors| ReplError::ParseFailed {
+            message: errors
+                .first()
+                .map(|e| e.to_string().trim().to_string())
+                .unwrap_or_default(),
+        })?;
+
+    let mut output = String::new();
+    format::pretty(&mut output, module, extra, code);
+    Ok(output.trim_end().to_string())
+}
+
+/// Format a standalone expression by wrapping it in a function and unwrapping the result
+pub fn format_expression(code: &str) -> Result<String, ReplError> {
+    let wrapped = format!("fn {}() {{\n{}\n}}", FORMAT_WRAPPER, code);
+    let formatted = format_module(&wrapped)?;
+
+    // Drop the wrapper's first and last lines and its indentation
+    let lines: Vec<_> = formatted.lines().collect();
+    let body = &lines[1..lines.len().saturating_sub(1).max(1)];
+    Ok(body
+        .iter()
+        .map(|line| line.strip_prefix("  ").unwrap_or(line))
+        .collect::<Vec<_>>()
+        .join("\n"))
+}
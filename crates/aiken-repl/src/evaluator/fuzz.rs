@@ -0,0 +1,134 @@
+//! Exploratory property tests: `:fuzz <fn> with <fuzzer> satisfying <property>`
+//!
+//! Writing a property test means naming it, picking a fuzzer and restating the call under test.
+//! Here the parts are given as they come to mind, and a test is synthesized from them: it draws
+//! an input from the fuzzer and checks `property(fn, input)`. Aiken's test framework runs it, so
+//! failures come back with a shrunk counterexample.
+
+use std::fmt;
+
+use super::ReplError;
+
+/// Runs of a property when not given with `--max-success`, as for `aiken check`
+pub const DEFAULT_MAX_SUCCESS: usize = 100;
+
+/// Arguments of `:fuzz <fn> with <fuzzer> satisfying <property> [--max-success <n>] [--seed <n>]`
+#[derive(Debug, PartialEq, Eq)]
+pub struct FuzzArgs<'a> {
+    /// The function under test
+    pub function: &'a str,
+    /// Expression of the fuzzer inputs are drawn from
+    pub fuzzer: &'a str,
+    /// Function of the function under test and an input, true when the input satisfies it
+    pub property: &'a str,
+    pub max_success: usize,
+    /// Seed of the run, a random one when None
+    pub seed: Option<u32>,
+}
+
+impl<'a> FuzzArgs<'a> {
+    pub fn parse(args: &'a str) -> Result<Self, ReplError> {
+        let usage = || ReplError::ParseFailed {
+            message:
+                "expected <fn> with <fuzzer> satisfying <property> [--max-success <n>] [--seed <n>]"
+                    .to_string(),
+        };
+
+        // Options come last, after the property
+        let (args, options) = match args.find(" --") {
+            Some(start) => args.split_at(start),
+            None => (args, ""),
+        };
+        let mut max_success = DEFAULT_MAX_SUCCESS;
+        let mut seed = None;
+        let mut options = options.split_whitespace();
+        while let Some(option) = options.next() {
+            let value = options.next().ok_or_else(usage)?;
+            match option {
+                "--max-success" => {
+                    max_success = value
+                        .parse()
+                        .ok()
+                        .filter(|runs| *runs > 0)
+                        .ok_or_else(usage)?
+                }
+                "--seed" => seed = Some(value.parse().map_err(|_| usage())?),
+                _ => return Err(usage()),
+            }
+        }
+
+        let (function, rest) = args.split_once(" with ").ok_or_else(usage)?;
+        let (fuzzer, property) = rest.rsplit_once(" satisfying ").ok_or_else(usage)?;
+        let (function, fuzzer, property) = (function.trim(), fuzzer.trim(), property.trim());
+        if function.is_empty() || fuzzer.is_empty() || property.is_empty() {
+            return Err(usage());
+        }
+        Ok(Self {
+            function,
+            fuzzer,
+            property,
+            max_success,
+            seed,
+        })
+    }
+
+    /// Source of the property test called `name`
+    pub fn test(&self, name: &str) -> String {
+        format!(
+            "test {}(input via {}) {{\n  {}({}, input)\n}}",
+            name, self.fuzzer, self.property, self.function
+        )
+    }
+}
+
+/// The test as it was asked for, without its options
+impl fmt::Display for FuzzArgs<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} with {} satisfying {}",
+            self.function, self.fuzzer, self.property
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{DEFAULT_MAX_SUCCESS, FuzzArgs};
+
+    #[test]
+    fn test_parse_fuzz_args() {
+        let args =
+            FuzzArgs::parse("add with fuzz.both(fuzz.int(), fuzz.int()) satisfying is_commutative")
+                .unwrap();
+        assert_eq!(
+            args,
+            FuzzArgs {
+                function: "add",
+                fuzzer: "fuzz.both(fuzz.int(), fuzz.int())",
+                property: "is_commutative",
+                max_success: DEFAULT_MAX_SUCCESS,
+                seed: None,
+            }
+        );
+        assert_eq!(
+            args.test("repl_fuzz_0"),
+            "test repl_fuzz_0(input via fuzz.both(fuzz.int(), fuzz.int())) {\n  is_commutative(add, input)\n}"
+        );
+
+        let args = FuzzArgs::parse(
+            "add with int_pair() satisfying is_commutative --seed 42 --max-success 500",
+        )
+        .unwrap();
+        assert_eq!((args.max_success, args.seed), (500, Some(42)));
+        assert_eq!(
+            args.to_string(),
+            "add with int_pair() satisfying is_commutative"
+        );
+
+        assert!(FuzzArgs::parse("add with int_pair()").is_err());
+        assert!(FuzzArgs::parse("add with int_pair() satisfying p --seed").is_err());
+        assert!(FuzzArgs::parse("add with int_pair() satisfying p --max-success 0").is_err());
+        assert!(FuzzArgs::parse(" with int_pair() satisfying p").is_err());
+    }
+}
@@ -0,0 +1,51 @@
+//! Cleanup of the build artifacts left behind by the temp project
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// Directory where `aiken` puts dependencies and compilation artifacts
+const BUILD_DIR: &str = "build";
+
+/// Build artifacts are removed after an evaluation once they grow past this size
+pub const BUILD_DIR_LIMIT: u64 = 64 * 1024 * 1024;
+
+/// Total size in bytes of the files under `path` (0 if it doesn't exist)
+pub fn dir_size(path: &Path) -> io::Result<u64> {
+    if !path.exists() {
+        return Ok(0);
+    }
+
+    let mut size = 0;
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        size += if metadata.is_dir() {
+            dir_size(&entry.path())?
+        } else {
+            metadata.len()
+        };
+    }
+    Ok(size)
+}
+
+/// Directory of the build artifacts of the project in `root`
+pub fn build_dir(root: &Path) -> PathBuf {
+    root.join(BUILD_DIR)
+}
+
+/// Size of the build artifacts of the project in `root`
+pub fn build_size(root: &Path) -> io::Result<u64> {
+    dir_size(&build_dir(root))
+}
+
+/// Remove the build artifacts of the project in `root`, returning the number of bytes freed
+pub fn remove_build_artifacts(root: &Path) -> io::Result<u64> {
+    let build_dir = build_dir(root);
+    let size = dir_size(&build_dir)?;
+    if build_dir.exists() {
+        fs::remove_dir_all(&build_dir)?;
+    }
+    Ok(size)
+}
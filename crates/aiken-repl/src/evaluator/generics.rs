@@ -0,0 +1,52 @@
+//! Expressions whose type still has type variables once inferred, like `[]` or `fn(x) { x }`
+//!
+//! The wrapper function of such an expression is polymorphic, and the code generator expects
+//! the types it compiles to be known. Generic values are represented as `Data` at runtime, so
+//! the wrapper is checked again with its type variables set to `Data` instead, which compiles to
+//! the same program and lets the value be displayed.
+
+use std::rc::Rc;
+
+use aiken_lang::tipo::{Type, TypeVar};
+
+/// Whether `tipo` has type variables
+pub fn has_type_variables(tipo: &Rc<Type>) -> bool {
+    match tipo.as_ref() {
+        Type::App { args, .. } => args.iter().any(has_type_variables),
+        Type::Fn { args, ret, .. } => {
+            args.iter().any(has_type_variables) || has_type_variables(ret)
+        }
+        Type::Tuple { elems, .. } => elems.iter().any(has_type_variables),
+        Type::Pair { fst, snd, .. } => has_type_variables(fst) || has_type_variables(snd),
+        Type::Var { tipo, .. } => match &*tipo.borrow() {
+            TypeVar::Link { tipo } => has_type_variables(tipo),
+            TypeVar::Unbound { .. } | TypeVar::Generic { .. } => true,
+        },
+    }
+}
+
+/// `tipo` with its type variables replaced by `Data`
+pub fn default_to_data(tipo: &Rc<Type>) -> Rc<Type> {
+    let mut defaulted = tipo.as_ref().clone();
+    match &mut defaulted {
+        Type::App { args, .. } => args.iter_mut().for_each(|arg| *arg = default_to_data(arg)),
+        Type::Fn { args, ret, .. } => {
+            args.iter_mut().for_each(|arg| *arg = default_to_data(arg));
+            *ret = default_to_data(ret);
+        }
+        Type::Tuple { elems, .. } => elems
+            .iter_mut()
+            .for_each(|elem| *elem = default_to_data(elem)),
+        Type::Pair { fst, snd, .. } => {
+            *fst = default_to_data(fst);
+            *snd = default_to_data(snd);
+        }
+        Type::Var { tipo, .. } => {
+            return match &*tipo.borrow() {
+                TypeVar::Link { tipo } => default_to_data(tipo),
+                TypeVar::Unbound { .. } | TypeVar::Generic { .. } => Type::data(),
+            };
+        }
+    }
+    Rc::new(defaulted)
+}
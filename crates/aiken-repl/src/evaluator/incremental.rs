@@ -0,0 +1,164 @@
+//! Evaluation of expressions without checking the definitions again
+//!
+//! Checking a project type-checks every module, the prelude and the session's definitions
+//! included, though most inputs are expressions evaluated against the same definitions as the
+//! input before. Once an expression has been evaluated against some definitions, its checked
+//! project is kept: the next expression is type-checked with the REPL module alone, against the
+//! types of the other modules, and compiled with the kept project's functions.
+//!
+//! Each evaluator keeps its project in a `Kept`, which goes with the evaluator: it's cleared
+//! when the session is reset or loses definitions, and dropped with the evaluator. Projects hold
+//! reference counted types and the evaluator must stay `Send`, so the project itself is stored
+//! on the thread evaluations run on, under the id of its `Kept`. An evaluator moved to another
+//! thread finds none there, and goes through a full check.
+
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use aiken_lang::{
+    IdGenerator,
+    ast::{Definition, ModuleKind, TraceLevel, Tracing, TypedFunction},
+    builtins, parser,
+    plutus_version::PlutusVersion,
+    tipo::{TypeInfo, error::Warning},
+};
+use aiken_project::Project;
+use miette::Diagnostic;
+
+use super::{events, names::Names};
+
+thread_local! {
+    /// Checked projects of the evaluators running on this thread, by the id of their `Kept`
+    static CHECKED: RefCell<HashMap<u64, Checked>> = RefCell::new(HashMap::new());
+}
+
+/// Ids of the `Kept`s of the process
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+/// What a kept project was checked with. Expressions are only type-checked on their own
+/// against a project with the same key.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Key {
+    pub definitions_hash: u64,
+    pub plutus_version: PlutusVersion,
+    pub trace_level: TraceLevel,
+    pub names: Names,
+}
+
+struct Checked {
+    key: Key,
+    project: Project<events::Progress>,
+    /// Types of the modules the REPL module can import, by module name
+    module_types: HashMap<String, TypeInfo>,
+}
+
+/// The checked project of one evaluator, if it kept one, and the ids of the types inferred in
+/// its session
+pub struct Kept {
+    id: u64,
+    /// Shared by the prelude types of the kept project and the expressions checked against it,
+    /// so their type variables never get the same id
+    id_gen: IdGenerator,
+}
+
+impl Default for Kept {
+    fn default() -> Self {
+        Self {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            id_gen: IdGenerator::new(),
+        }
+    }
+}
+
+impl Drop for Kept {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+impl Kept {
+    /// Keep `project`, checked with `key`, for the next expressions
+    pub fn keep(&self, key: Key, project: Project<events::Progress>) {
+        let mut module_types = HashMap::from([
+            ("aiken".to_string(), builtins::prelude(&self.id_gen)),
+            ("aiken/builtin".to_string(), builtins::plutus(&self.id_gen)),
+        ]);
+        for module in project.modules() {
+            if module.name != key.names.module {
+                module_types.insert(module.name.clone(), module.ast.type_info.clone());
+            }
+        }
+
+        let checked = Checked {
+            key,
+            project,
+            module_types,
+        };
+        CHECKED.with_borrow_mut(|kept| kept.insert(self.id, checked));
+    }
+
+    /// Forget the kept project, if any
+    pub fn clear(&self) {
+        // Nothing is left to forget once the thread is being torn down
+        let _ = CHECKED.try_with(|kept| kept.borrow_mut().remove(&self.id));
+    }
+
+    /// Type-check `module_code`, the REPL module with the wrapper function `eval_fn_name`,
+    /// against the project kept for `key`, then run `f` with the project and the typed wrapper.
+    ///
+    /// None when no project is kept for `key`, or when the module doesn't type-check or the
+    /// wrapper raises warnings: a full check reports them with the whole project. Warnings about
+    /// the definitions were reported when they were checked.
+    pub fn with_checked<R>(
+        &self,
+        key: &Key,
+        module_code: &str,
+        eval_fn_name: &str,
+        f: impl FnOnce(&mut Project<events::Progress>, &TypedFunction) -> R,
+    ) -> Option<R> {
+        CHECKED.with_borrow_mut(|kept| {
+            let checked = kept
+                .get_mut(&self.id)
+                .filter(|checked| checked.key == *key)?;
+
+            let (mut module, _extra) = parser::module(module_code, ModuleKind::Lib).ok()?;
+            module.name = key.names.module.clone();
+            let mut warnings = Vec::new();
+            let typed = module
+                .infer(
+                    &self.id_gen,
+                    ModuleKind::Lib,
+                    &key.names.project,
+                    &checked.module_types,
+                    Tracing::All(key.trace_level),
+                    &mut warnings,
+                    None,
+                )
+                .ok()?;
+
+            let eval_fn = typed
+                .definitions()
+                .find_map(|definition| match definition {
+                    Definition::Fn(function) if function.name == eval_fn_name => {
+                        Some(function.clone())
+                    }
+                    _ => None,
+                })?;
+            let about_wrapper = |warning: &Warning| {
+                warning
+                    .labels()
+                    .into_iter()
+                    .flatten()
+                    .any(|label| label.offset() >= eval_fn.location.start)
+            };
+            if warnings.iter().any(about_wrapper) {
+                return None;
+            }
+
+            Some(f(&mut checked.project, &eval_fn))
+        })
+    }
+}
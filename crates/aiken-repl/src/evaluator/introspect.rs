@@ -0,0 +1,104 @@
+//! Structured listing of the definitions in the session, for variable explorers and the like
+//!
+//! Like the docs lookup, this reads the typed AST of the session module, so every entry
+//! comes with the type the compiler inferred for it.
+
+use std::rc::Rc;
+
+use aiken_lang::{
+    ast::{Definition, TypedDefinition, TypedFunction},
+    tipo::{Type, pretty::Printer},
+};
+use aiken_project::module::CheckedModule;
+
+use super::DefinitionKind;
+
+/// A definition in the session
+#[derive(Debug, Clone)]
+pub struct DefinitionInfo {
+    pub name: String,
+    pub kind: DefinitionKind,
+    /// Type of a function or constant, or the shape of a type (`type Color { Red | Green }`)
+    pub tipo: String,
+    /// Value of a constant, None for functions and types
+    pub value: Option<String>,
+    /// Where the definition was written, eg. `In[4]`, if known
+    pub origin: Option<String>,
+}
+
+/// List the definitions of a checked module, in source order and without values
+pub fn definitions(module: &CheckedModule) -> Vec<DefinitionInfo> {
+    module.ast.definitions().filter_map(info_of).collect()
+}
+
+/// Type of the function or constant `name` of a checked module
+pub fn value_type(module: &CheckedModule, name: &str) -> Option<Rc<Type>> {
+    module.ast.definitions().find_map(|def| match def {
+        Definition::Fn(f) if f.name == name => Some(function_type(f)),
+        Definition::ModuleConstant(c) if c.name == name => Some(c.tipo.clone()),
+        _ => None,
+    })
+}
+
+fn function_type(f: &TypedFunction) -> Rc<Type> {
+    Type::function(
+        f.arguments.iter().map(|arg| arg.tipo.clone()).collect(),
+        f.return_type.clone(),
+    )
+}
+
+fn info_of(def: &TypedDefinition) -> Option<DefinitionInfo> {
+    let mut printer = Printer::new();
+    let (name, kind, tipo) = match def {
+        Definition::Fn(f) => (
+            f.name.clone(),
+            DefinitionKind::Function,
+            printer.pretty_print(&function_type(f), 0),
+        ),
+        Definition::ModuleConstant(c) => (
+            c.name.clone(),
+            DefinitionKind::Constant,
+            printer.pretty_print(&c.tipo, 0),
+        ),
+        Definition::DataType(d) => {
+            let constructors: Vec<_> = d.constructors.iter().map(|c| c.name.clone()).collect();
+            (
+                d.name.clone(),
+                DefinitionKind::Type,
+                format!(
+                    "type {}{} {{ {} }}",
+                    d.name,
+                    type_parameters(&d.parameters),
+                    constructors.join(" | ")
+                ),
+            )
+        }
+        Definition::TypeAlias(a) => (
+            a.alias.clone(),
+            DefinitionKind::Type,
+            format!(
+                "type {}{} = {}",
+                a.alias,
+                type_parameters(&a.parameters),
+                printer.pretty_print(&a.tipo, 0)
+            ),
+        ),
+        _ => return None,
+    };
+
+    Some(DefinitionInfo {
+        name,
+        kind,
+        tipo,
+        value: None,
+        origin: None,
+    })
+}
+
+fn type_parameters(parameters: &[String]) -> String {
+    if parameters.is_empty() {
+        String::new()
+    } else {
+        format!("<{}>", parameters.join(", "))
+    }
+}
@@ -0,0 +1,128 @@
+//! Ed25519 key pairs of the session, to demo signature and multisig validators end-to-end
+//!
+//! Verification keys are hashed with blake2b-224, as in the credentials of Cardano addresses.
+//! Signing keys never leave the evaluator.
+
+use std::fmt;
+
+use blake2::{Blake2b, Digest, digest::consts::U28};
+use ed25519_dalek::{Signer, SigningKey};
+
+use super::ReplError;
+
+/// An ed25519 key pair
+#[derive(Debug, Clone)]
+pub(crate) struct KeyPair {
+    signing_key: SigningKey,
+}
+
+impl KeyPair {
+    pub fn generate() -> Result<Self, ReplError> {
+        let mut seed = [0; 32];
+        getrandom::fill(&mut seed).map_err(|e| ReplError::InvalidKey {
+            message: format!("no randomness to generate a key: {}", e),
+        })?;
+        Ok(Self {
+            signing_key: SigningKey::from_bytes(&seed),
+        })
+    }
+
+    pub fn info(&self, name: &str) -> KeyInfo {
+        let verification_key = self.signing_key.verifying_key().to_bytes().to_vec();
+        KeyInfo {
+            name: name.to_string(),
+            key_hash: Blake2b::<U28>::digest(&verification_key).to_vec(),
+            verification_key,
+        }
+    }
+
+    pub fn sign(&self, message: &[u8]) -> Vec<u8> {
+        self.signing_key.sign(message).to_bytes().to_vec()
+    }
+}
+
+/// Public parts of a key pair
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyInfo {
+    pub name: String,
+    pub verification_key: Vec<u8>,
+    /// Hash of the verification key, as in credentials and extra signatories
+    pub key_hash: Vec<u8>,
+}
+
+impl KeyInfo {
+    /// Constants the key is available as in the session: `<name>_vk` and `<name>_vkh`
+    pub fn definitions(&self) -> String {
+        format!(
+            "pub const {}_vk: ByteArray = #\"{}\"\n\npub const {}_vkh: ByteArray = #\"{}\"",
+            self.name,
+            hex::encode(&self.verification_key),
+            self.name,
+            hex::encode(&self.key_hash)
+        )
+    }
+}
+
+impl fmt::Display for KeyInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{}_vk = #\"{}\"",
+            self.name,
+            hex::encode(&self.verification_key)
+        )?;
+        write!(
+            f,
+            "{}_vkh = #\"{}\"",
+            self.name,
+            hex::encode(&self.key_hash)
+        )
+    }
+}
+
+/// Key names become parts of constant names, so they follow Aiken's rules for those
+pub fn validate_name(name: &str) -> Result<(), ReplError> {
+    let valid = name.starts_with(|c: char| c.is_ascii_lowercase())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(ReplError::InvalidKey {
+            message: format!(
+                "`{}` can't name a key: use lowercase letters, digits and `_`",
+                name
+            ),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+
+    use super::{KeyPair, validate_name};
+
+    #[test]
+    fn test_key_pair() {
+        let pair = KeyPair::generate().unwrap();
+        let info = pair.info("alice");
+        assert_eq!(info.verification_key.len(), 32);
+        assert_eq!(info.key_hash.len(), 28);
+        assert!(
+            info.definitions()
+                .starts_with("pub const alice_vk: ByteArray = #\"")
+        );
+
+        let signature = pair.sign(b"cafe");
+        let key = VerifyingKey::from_bytes(&info.verification_key.try_into().unwrap()).unwrap();
+        let signature = Signature::from_bytes(&signature.try_into().unwrap());
+        assert!(key.verify(b"cafe", &signature).is_ok());
+        assert!(key.verify(b"decaf", &signature).is_err());
+
+        assert!(validate_name("bob_2").is_ok());
+        assert!(validate_name("Bob").is_err());
+        assert!(validate_name("2bob").is_err());
+    }
+}
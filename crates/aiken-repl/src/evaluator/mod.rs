@@ -6,30 +6,134 @@
 //! expressions and function definitions.
 
 use std::{
-    collections::HashSet,
+    borrow::Cow,
+    cell::RefCell,
+    collections::{BTreeMap, HashMap},
     fmt, fs,
+    hash::{DefaultHasher, Hash, Hasher},
+    panic::{AssertUnwindSafe, catch_unwind},
+    path::{Path, PathBuf},
     rc::Rc,
     sync::atomic::{AtomicU64, Ordering},
 };
 
+use crate::version::PLUTUS_VERSIONS;
 use aiken_lang::{
-    ast::{Definition, TraceLevel, Tracing},
+    ast::{Definition, ModuleKind, TraceLevel, Tracing, TypedFunction},
+    parser,
     plutus_version::PlutusVersion,
     tipo::pretty::Printer,
 };
 use aiken_project::{
-    Project,
-    config::ProjectConfig,
-    error::Error as ProjectError,
-    module::CheckedModule,
-    telemetry::{CoverageMode, EventListener},
+    Project, config::ProjectConfig, error::Error as ProjectError, module::CheckedModule,
+    telemetry::CoverageMode,
 };
-use miette::Diagnostic;
+use miette::{Diagnostic, NamedSource};
 use uplc::{
+    PlutusData,
     ast::{Constant, NamedDeBruijn, Program, Term},
-    machine::{cost_model::ExBudget, eval_result::EvalResult},
+    machine::eval_result::EvalResult,
 };
 
+mod address;
+mod artifacts;
+mod backend;
+mod cbor;
+mod chain;
+mod check;
+mod compiler;
+mod complete;
+mod context;
+mod data;
+mod decode;
+mod depgraph;
+mod docs;
+mod emulator;
+mod events;
+mod export;
+mod format;
+mod fuzz;
+mod gc;
+mod generics;
+mod incremental;
+mod introspect;
+mod keys;
+mod names;
+mod patterns;
+mod pretty;
+mod probe;
+mod profile;
+mod reify;
+mod report;
+mod simulate;
+mod sourcemap;
+mod timeit;
+mod timings;
+mod untyped;
+mod warnings;
+
+use context::DefinitionKey;
+
+pub use address::{AddressInfo, Credential, StakeReference};
+pub use artifacts::ArtifactCache;
+pub use backend::{EvalBackend, InProcess};
+pub use cbor::ScriptCbor;
+pub use chain::{ChainProvider, Datum, ReferenceScript, Utxo};
+pub use check::CheckReport;
+pub use compiler::{AikenBinary, Compiler};
+pub use complete::{CompletionCandidates, Completions, KEYWORDS};
+pub use context::Provenance;
+pub use data::DataConversion;
+pub use decode::{Bytes, Constr, FromData};
+pub use depgraph::{DependencyGraph, GraphNode};
+pub use docs::DocEntry;
+pub use emulator::{MockChain, Submission, ValidatorRun};
+pub use events::ProgressTx;
+pub use fuzz::{DEFAULT_MAX_SUCCESS, FuzzArgs};
+pub use introspect::DefinitionInfo;
+pub use keys::KeyInfo;
+pub use pretty::{ByteArrayDisplay, DisplaySettings};
+pub use probe::{ProbeMeasure, RedefinitionReport};
+pub use profile::{FlamegraphArgs, Frame, Profile};
+pub use report::{TestOutcome, TestReport};
+pub use simulate::{Network, RedeemerRun, SimulateArgs, Simulation};
+pub use sourcemap::{SourceMap, SourceMapping, UplcDump};
+pub use timeit::{Timing, format_duration};
+pub use timings::{Phase, Timings};
+pub use untyped::UplcResult;
+pub use uplc::machine::cost_model::ExBudget;
+
+/// Seed of property tests when outputs are deterministic and the session has no seed
+const DETERMINISTIC_SEED: u32 = 42;
+
+/// Name of the function expressions are wrapped in to tell them from definitions
+const PARSE_WRAPPER: &str = "repl_parse";
+
+/// Module of helpers to build ledger values, that the session can always import
+const PRELUDE_MODULE: &str = "repl/prelude";
+const PRELUDE: &str = include_str!("prelude.ak");
+
+/// Options that can be changed with `set_option`
+pub const OPTIONS: &[&str] = &[
+    "bytearray-display",
+    "max-depth",
+    "max-width",
+    "max-chars",
+    "plutus-version",
+    "trace-level",
+    "budget-cpu",
+    "budget-mem",
+    "seed",
+    "coverage-mode",
+    "warnings",
+    "cache",
+    "auto-format",
+    "deterministic",
+    "project-name",
+    "module-name",
+    "backend",
+];
+
 /// Errors that can occur during REPL evaluation
 #[derive(Debug, thiserror::Error, Diagnostic)]
 pub enum ReplError {
@@ -37,14 +141,89 @@ pub enum ReplError {
     #[diagnostic(transparent)]
     ProjectError(#[from] ProjectError),
 
+    /// An error in the session's module, shown against the session's code rather than the
+    /// module of the temp project
+    #[error("REPL evaluation failed")]
+    #[diagnostic(forward(error))]
+    SessionError {
+        error: ProjectError,
+        #[source_code]
+        session: NamedSource<String>,
+    },
+
     #[error("Failed to create temporary file: {0}")]
     TempFileError(#[from] std::io::Error),
 
+    #[error("Failed to parse input: {message}")]
+    ParseFailed { message: String },
+
     #[error("Evaluation produced no result")]
     NoResult,
 
     #[error("Expression evaluation failed: {message}")]
     EvaluationFailed { message: String },
+
+    #[error("Invalid setting: {message}")]
+    InvalidSetting { message: String },
+
+    #[error("No checkpoint named `{name}`")]
+    UnknownCheckpoint { name: String },
+
+    #[error("Export failed: {message}")]
+    ExportFailed { message: String },
+
+    #[error("Invalid address: {message}")]
+    InvalidAddress { message: String },
+
+    #[error("Invalid data: {message}")]
+    InvalidData { message: String },
+
+    #[error("Simulation failed: {message}")]
+    SimulationFailed { message: String },
+
+    #[error("Chain lookup failed: {message}")]
+    ChainFailed { message: String },
+
+    #[error("Transaction rejected: {message}")]
+    TransactionRejected { message: String },
+
+    #[error("Invalid key: {message}")]
+    InvalidKey { message: String },
+
+    #[error("`{module}` is the module the REPL compiles the session in, it can't be imported")]
+    #[diagnostic(help(
+        "The session's definitions are already in scope. To import a module of that name, set the `module-name` option to another name for the session's module."
+    ))]
+    ReservedModule { module: String },
+
+    #[error("{definition} is defined more than once in the same input")]
+    DuplicateDefinition { definition: String },
+
+    #[error(
+        "`when` in {definition} doesn't cover every case, missing:\n{}",
+        patterns::list(.missing)
+    )]
+    #[diagnostic(help("Add a clause for each missing pattern, or a catch-all `_ ->` clause"))]
+    NonExhaustive {
+        definition: String,
+        missing: Vec<String>,
+        #[diagnostic_source]
+        error: ProjectError,
+    },
+
+    #[error("The type of this expression, {tipo}, has type variables the compiler can't resolve")]
+    #[diagnostic(help("Annotate it with a `let`, eg. `let xs: List<Int> = []` then `xs`"))]
+    AmbiguousType {
+        tipo: String,
+        /// The type with its type variables set to `Data`
+        defaulted: String,
+    },
+
+    #[error("The compiler crashed on this input: {message}")]
+    #[diagnostic(help(
+        "This is a bug in Aiken or the REPL. The session's definitions are as they were before this input."
+    ))]
+    Panicked { message: String },
 }
 
 /// The result of evaluating Aiken code in the REPL
@@ -55,6 +234,10 @@ pub enum EvaluationResult {
         value: String,
         tipo: Rc<aiken_lang::tipo::Type>,
         uplc_result: Option<Constant>,
+        /// Cost of the evaluation
+        budget: ExBudget,
+        /// Messages of `trace` expressions, in order
+        traces: Vec<String>,
     },
     /// A definition was added (function, type, etc.)
     Definition {
@@ -62,23 +245,103 @@ pub enum EvaluationResult {
         kind: DefinitionKind,
         tipo: Option<Rc<aiken_lang::tipo::Type>>,
     },
+    /// A value served from the cache, with its type already printed
+    Cached {
+        value: String,
+        tipo: String,
+        budget: ExBudget,
+        traces: Vec<String>,
+    },
+    /// Nothing was defined or computed, but the compiler had warnings about it (eg. an unused
+    /// import). Rendered for display.
+    Warnings(Vec<String>),
+    /// Tests were run
+    TestReport(TestReport),
+    /// Several definitions were added, with their types
+    Definitions(Vec<DefinitionInfo>),
+    /// A value computed by a pinned `aiken` binary, as its traces show it: in CBOR diagnostic
+    /// notation, without its type
+    Traced { value: String, compiler: String },
+    /// Definitions were added, then the expression the code ends with was evaluated
+    Mixed {
+        definitions: Box<EvaluationResult>,
+        result: Box<EvaluationResult>,
+    },
     /// No result (e.g., import statement)
     NoResult,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum DefinitionKind {
     Function,
     Type,
     Constant,
 }
 
-/// Helper struct that tracks definition names to avoid conflicts
-#[derive(Debug, Default)]
-pub struct DefinitionNames {
-    pub functions: HashSet<String>,
-    pub constants: HashSet<String>,
-    pub types: HashSet<String>,
+impl DefinitionKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DefinitionKind::Function => "function",
+            DefinitionKind::Type => "type",
+            DefinitionKind::Constant => "constant",
+        }
+    }
+}
+
+/// What a result is, for tools that read results without parsing their text
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResultSummary {
+    /// `value`, `definitions`, `tests`, `warnings` or `none`
+    pub kind: &'static str,
+    /// Type of the value, when the compiler gave it
+    pub tipo: Option<String>,
+    /// Cost of evaluating the value, when it was evaluated in-process
+    pub budget: Option<ExBudget>,
+    /// Names of the definitions added
+    pub definitions: Vec<String>,
+}
+
+impl EvaluationResult {
+    pub fn summary(&self) -> ResultSummary {
+        let summary = |kind| ResultSummary {
+            kind,
+            tipo: None,
+            budget: None,
+            definitions: Vec::new(),
+        };
+        match self {
+            EvaluationResult::Value { tipo, budget, .. } => ResultSummary {
+                tipo: Some(Printer::new().pretty_print(tipo, 0)),
+                budget: Some(*budget),
+                ..summary("value")
+            },
+            EvaluationResult::Cached { tipo, budget, .. } => ResultSummary {
+                tipo: Some(tipo.clone()),
+                budget: Some(*budget),
+                ..summary("value")
+            },
+            EvaluationResult::Traced { .. } => summary("value"),
+            EvaluationResult::Definition { name, .. } => ResultSummary {
+                definitions: vec![name.clone()],
+                ..summary("definitions")
+            },
+            EvaluationResult::Definitions(definitions) => ResultSummary {
+                definitions: definitions.iter().map(|d| d.name.clone()).collect(),
+                ..summary("definitions")
+            },
+            EvaluationResult::Warnings(_) => summary("warnings"),
+            EvaluationResult::TestReport(_) => summary("tests"),
+            EvaluationResult::Mixed {
+                definitions,
+                result,
+            } => {
+                let mut summary = result.summary();
+                summary.definitions = definitions.summary().definitions;
+                summary
+            }
+            EvaluationResult::NoResult => summary("none"),
+        }
+    }
 }
 
 /// This is how we'll show the evaluation result in the repl
@@ -86,18 +349,22 @@ impl fmt::Display for EvaluationResult {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             // When printing a value, show both the value and the type
+            // Functions show their signature in place of a value
+            EvaluationResult::Value { value, tipo, .. } if reify::function(tipo).is_some() => {
+                write!(f, "{}", value)
+            }
             EvaluationResult::Value { value, tipo, .. } => {
                 let mut printer = Printer::new();
                 let type_str = printer.pretty_print(tipo, 0);
                 write!(f, "{} : {}", value, type_str)
             }
+            EvaluationResult::Cached { value, tipo, .. } => write!(f, "{} : {}", value, tipo),
+            EvaluationResult::Traced { value, compiler } => {
+                write!(f, "{} (aiken {})", value, compiler)
+            }
             // Provide some feedback when creating a definition
             EvaluationResult::Definition { name, kind, tipo } => {
-                let kind_str = match kind {
-                    DefinitionKind::Function => "function",
-                    DefinitionKind::Type => "type",
-                    DefinitionKind::Constant => "constant",
-                };
+                let kind_str = kind.as_str();
                 if let Some(t) = tipo {
                     let mut printer = Printer::new();
                     let type_str = printer.pretty_print(t, 0);
@@ -106,24 +373,120 @@ impl fmt::Display for EvaluationResult {
                     write!(f, "Defined {} {}", kind_str, name)
                 }
             }
+            EvaluationResult::Definitions(definitions) => {
+                let lines: Vec<String> = definitions
+                    .iter()
+                    .map(|definition| match definition.kind {
+                        // The shape of a type is its definition, left out like for single types
+                        DefinitionKind::Type => format!("Defined type {}", definition.name),
+                        kind if definition.tipo.is_empty() => {
+                            format!("Defined {} {}", kind.as_str(), definition.name)
+                        }
+                        kind => format!(
+                            "Defined {} {} : {}",
+                            kind.as_str(),
+                            definition.name,
+                            definition.tipo
+                        ),
+                    })
+                    .collect();
+                write!(f, "{}", lines.join("\n"))
+            }
+            EvaluationResult::Warnings(warnings) => write!(f, "{}", warnings.join("\n")),
+            EvaluationResult::TestReport(report) => write!(f, "{}", report),
+            EvaluationResult::Mixed {
+                definitions,
+                result,
+            } => match definitions.as_ref() {
+                EvaluationResult::NoResult => write!(f, "{}", result),
+                definitions => write!(f, "{}\n{}", definitions, result),
+            },
             EvaluationResult::NoResult => write!(f, ""),
         }
     }
 }
 
-struct NoEvent;
-impl EventListener for NoEvent {}
-
 /// REPL evaluator that maintains state using Aiken's Project infrastructure
 pub struct ReplEvaluator {
-    /// Temporary directory for REPL files
+    /// Temporary directory for REPL files, reused by every evaluation
     temp_dir: tempfile::TempDir,
+    /// Where build artifacts are shared with other sessions, if anywhere
+    artifacts: Option<ArtifactCache>,
     /// Current accumulated definitions
-    pub(crate) definitions: String,
+    pub(crate) context: context::Context,
     /// Counter for generating unique evaluation function names
     eval_counter: AtomicU64,
     /// Plutus version for evaluation
     plutus_version: PlutusVersion,
+    /// Traces kept in the generated code
+    trace_level: TraceLevel,
+    /// Budget an evaluation may spend before it fails
+    budget: ExBudget,
+    /// Seed of property tests, a new random one for every run when None
+    seed: Option<u32>,
+    /// Whether definitions get formatted before being added to the context
+    auto_format: bool,
+    /// Whether outputs are kept the same from one run of a notebook to the next: property tests
+    /// get a fixed seed, and neither ages nor paths of the temp project are shown
+    deterministic: bool,
+    /// Results of previously evaluated expressions, when caching is enabled. They are kept as
+    /// text: types and UPLC constants are reference counted, and the evaluator must stay `Send`.
+    pub(crate) cache: Option<HashMap<CacheKey, CachedValue>>,
+    /// Where compiler events (eg. resolving packages) are reported, if anywhere
+    progress: events::Progress,
+    /// Expression measured after every change to the definitions, if any
+    probe: Option<probe::Probe>,
+    /// How the probe moved with the last evaluation, when it redefined functions
+    redefinition: Option<RedefinitionReport>,
+    /// How values get displayed
+    display_settings: DisplaySettings,
+    /// Compiler warnings of the evaluations, collected while type-checking
+    warnings: RefCell<warnings::Warnings>,
+    /// Definitions and settings saved by name, kept across resets
+    checkpoints: BTreeMap<String, Checkpoint>,
+    /// Where UTxOs are looked up, if anywhere. Kept across resets.
+    chain: Option<Box<dyn ChainProvider>>,
+    /// Key pairs generated in the session, by name
+    keys: BTreeMap<String, keys::KeyPair>,
+    /// Where the inputs being evaluated come from, eg. `cell 3`, if known
+    origin: Option<String>,
+    /// What definitions are compiled and expressions evaluated with. Kept across resets.
+    compiler: Compiler,
+    /// Names of the temp project and of the session's module in it
+    names: names::Names,
+    /// Where the time of the last evaluation went, if there was one
+    timings: Option<Timings>,
+    /// Project checked for the last expression, to check the next ones against
+    incremental: incremental::Kept,
+}
+
+/// A saved state of the session, to roll back to
+#[derive(Debug, Clone)]
+pub(crate) struct Checkpoint {
+    context: context::Context,
+    auto_format: bool,
+    cache: bool,
+    warnings: bool,
+    /// Code of the probe. It's measured again on rollback.
+    probe: Option<String>,
+    display_settings: DisplaySettings,
+}
+
+/// A cached value and its printed type
+#[derive(Debug, Clone)]
+pub(crate) struct CachedValue {
+    value: String,
+    tipo: String,
+    budget: ExBudget,
+    traces: Vec<String>,
+}
+
+/// An expression evaluated against a given set of definitions. Aiken expressions are pure,
+/// so the same key always evaluates to the same result.
+#[derive(Debug, PartialEq, Eq, Hash)]
+pub(crate) struct CacheKey {
+    definitions_hash: u64,
+    code: String,
 }
 
 impl Default for ReplEvaluator {
@@ -144,505 +507,2796 @@ impl ReplEvaluator {
 
         Self {
             temp_dir,
-            definitions: String::new(),
+            artifacts: None,
+            context: context::Context::default(),
             eval_counter: AtomicU64::new(0),
             plutus_version,
+            trace_level: TraceLevel::Compact,
+            budget: ExBudget::max(),
+            seed: None,
+            auto_format: false,
+            deterministic: false,
+            cache: None,
+            progress: events::Progress::default(),
+            probe: None,
+            redefinition: None,
+            display_settings: DisplaySettings::default(),
+            warnings: RefCell::default(),
+            checkpoints: BTreeMap::new(),
+            chain: None,
+            keys: BTreeMap::new(),
+            origin: None,
+            compiler: Compiler::Builtin,
+            names: names::Names::default(),
+            timings: None,
+            incremental: incremental::Kept::default(),
         }
     }
 
     /// Reset the evaluator context
     pub fn reset(&mut self) {
-        self.definitions.clear();
+        self.context.clear();
         self.eval_counter.store(0, Ordering::Relaxed);
+        if let Some(cache) = &mut self.cache {
+            cache.clear();
+        }
+        self.probe = None;
+        self.redefinition = None;
+        self.warnings.get_mut().clear();
+        self.keys.clear();
+        // A new session, with types of its own
+        self.incremental = incremental::Kept::default();
+        if let Err(e) = self.gc() {
+            eprintln!("Failed to clean up build artifacts: {}", e);
+        }
     }
 
-    /// Get information about current context
-    pub fn context_info(&self) -> String {
-        if self.definitions.is_empty() {
-            "Empty context".to_string()
-        } else {
-            format!("{}", self.definitions)
-        }
+    /// Remove the build artifacts of the temp project, returning the number of bytes freed
+    pub fn gc(&self) -> Result<u64, ReplError> {
+        Ok(gc::remove_build_artifacts(self.temp_dir.path())?)
     }
 
-    /// Evaluate a piece of Aiken code
-    pub fn eval(&mut self, code: &str) -> Result<EvaluationResult, ReplError> {
-        // Determine if this is an expression or a module with definitions
-        let is_expression = looks_like_expression(code);
+    /// Share build artifacts with other sessions through `cache`: the temp project starts from
+    /// the saved ones, and saves its own once built if there are none yet
+    pub fn set_artifact_cache(&mut self, cache: Option<ArtifactCache>) {
+        self.artifacts = cache;
+    }
 
-        if is_expression {
-            self.eval_expression(code)
-        } else {
-            self.eval_definitions(code)
+    /// Remove the build artifacts shared with other sessions, returning the number of bytes
+    /// freed
+    pub fn clear_artifact_cache(&self) -> Result<u64, ReplError> {
+        match &self.artifacts {
+            Some(cache) => Ok(cache.clear()?),
+            None => Ok(0),
         }
     }
 
-    /// Evaluate expressions by wrapping them in a function
-    fn eval_expression(&mut self, code: &str) -> Result<EvaluationResult, ReplError> {
-        // Create unique evaluation function name
-        let eval_count = self.eval_counter.fetch_add(1, Ordering::Relaxed);
-        let eval_fn_name = format!("repl_eval_{}", eval_count);
-
-        // Wrap the expression in a function for evaluation
-        let wrapped_code = format!("pub fn {}() {{ {} }}", eval_fn_name, code);
-
-        // Create complete module with accumulated definitions
-        let module_code = format!("{}\n\n{}", self.definitions, wrapped_code);
-
-        // Create a well-typed temporary project
-        let mut project = self.create_temp_project(&module_code)?;
-
-        // Find the REPL module
-        let repl_module = project
-            .modules()
-            .into_iter()
-            .find(|m| m.name == "repl")
-            .ok_or_else(|| ReplError::EvaluationFailed {
-                message: "Could not find repl module".to_string(),
-            })?;
-
-        // Find the evaluation function
-        let eval_fn = repl_module
-            .ast
-            .definitions()
-            .find_map(|def| match def {
-                Definition::Fn(f) if f.name == eval_fn_name => Some(f.clone()),
-                _ => None,
-            })
-            .ok_or_else(|| ReplError::EvaluationFailed {
-                message: format!(
-                    "Could not find evaluation function {}. This should never happen.",
-                    eval_fn_name
-                ),
-            })?;
-
-        // Generate UPLC and evaluate
-        let eval_result = self.generate_and_eval(&mut project, repl_module, &eval_fn)?;
-
-        // Extract and format the result
-        match eval_result.result {
-            Ok(term) => {
-                let value_str = term_to_string(&term);
-                Ok(EvaluationResult::Value {
-                    value: value_str,
-                    tipo: eval_fn.return_type,
-                    uplc_result: self.extract_constant(&term),
-                })
-            }
-            Err(err) => Err(ReplError::EvaluationFailed {
-                message: format!("Evaluation failed: {:?}", err),
-            }),
+    /// Remove the whole temp project. The directory is created again by the next evaluation.
+    ///
+    /// The temp dir is also removed when the evaluator is dropped, but the kernel keeps its
+    /// evaluator in a static that never is, so it has to call this before exiting.
+    pub fn cleanup(&self) -> Result<(), ReplError> {
+        let path = self.temp_dir.path();
+        if path.exists() {
+            fs::remove_dir_all(path)?;
         }
+        Ok(())
     }
 
-    /// Evaluate code as module definitions
-    fn eval_definitions(&mut self, code: &str) -> Result<EvaluationResult, ReplError> {
-        // Get all definition names from the new code
-        let new_names = self.collect_definition_names(code);
+    /// Drop the build artifacts once they grow too big, so long sessions don't fill the disk
+    fn gc_if_needed(&self) {
+        let over_limit = gc::build_size(self.temp_dir.path())
+            .map(|size| size > gc::BUILD_DIR_LIMIT)
+            .unwrap_or(false);
+        if over_limit && let Err(e) = self.gc() {
+            eprintln!("Failed to clean up build artifacts: {}", e);
+        }
+    }
 
-        // Remove any existing definitions with the same names (allow re-defining)
-        self.remove_existing_definitions(&new_names);
+    /// Get information about current context
+    pub fn context_info(&self) -> String {
+        if self.context.source().is_empty() {
+            "Empty context".to_string()
+        } else {
+            self.context.source().to_string()
+        }
+    }
 
-        let new_definitions = format!("{}\n\n{}", self.definitions, code);
+    /// The accumulated definitions, as source code
+    pub fn definitions(&self) -> &str {
+        self.context.source()
+    }
 
-        // Type check project with the new definitions
-        let _project = self.create_temp_project(&new_definitions)?;
+    /// Where and when the definition `name` was written, if it's one of the session's
+    pub fn provenance(&self, name: &str) -> Option<&Provenance> {
+        self.context.provenance(name)
+    }
 
-        // Add the definitions to our accumulated state
-        self.definitions = new_definitions;
+    /// Replace all the definitions with `code`, eg. after editing them in an external editor.
+    /// The context is left untouched if the new code doesn't type-check.
+    pub fn replace_context(&mut self, code: &str) -> Result<(), ReplError> {
+        let code = normalize_newlines(code);
+        let code: &str = &code;
+        let mut context = self.context.clone();
+        context.replace(
+            self.split_definitions(code)?,
+            &Provenance::new(self.origin.as_deref(), code),
+        );
+        self.create_temp_project(context.source())?;
+        self.context = context;
+        self.redefinition = None;
+        self.definitions_changed(true);
+        Ok(())
+    }
 
-        // Extract what was actually defined for better feedback
-        let defined_items: Vec<_> = [
-            new_names
-                .functions
-                .iter()
-                .map(|n| (n.clone(), DefinitionKind::Function))
-                .collect::<Vec<_>>(),
-            new_names
-                .constants
-                .iter()
-                .map(|n| (n.clone(), DefinitionKind::Constant))
-                .collect::<Vec<_>>(),
-            new_names
-                .types
-                .iter()
-                .map(|n| (n.clone(), DefinitionKind::Type))
-                .collect::<Vec<_>>(),
-        ]
-        .concat();
+    /// Remove the definitions added by the last evaluation, bringing back those it replaced.
+    /// Returns what changed, or None when there's nothing left to undo.
+    pub fn undo(&mut self) -> Option<Vec<String>> {
+        let changed = self.context.undo()?;
+        self.redefinition = None;
+        self.definitions_changed(true);
+        Some(changed.iter().map(ToString::to_string).collect())
+    }
 
-        match defined_items.len() {
-            0 => Ok(EvaluationResult::NoResult),
-            1 => {
-                let (name, kind) = defined_items.into_iter().next().unwrap();
-                Ok(EvaluationResult::Definition {
-                    name,
-                    kind,
-                    tipo: None,
-                })
-            }
-            _ => {
-                let names: Vec<_> = defined_items.iter().map(|(name, _)| name.clone()).collect();
-                Ok(EvaluationResult::Definition {
-                    name: format!("Multiple definitions: {}", names.join(", ")),
-                    kind: DefinitionKind::Function, // Use as generic?
-                    tipo: None,
-                })
-            }
+    /// Remove the definitions called `name`, or the import of module `name`, returning what was
+    /// removed. The context is left untouched if the other definitions depend on them.
+    pub fn forget(&mut self, name: &str) -> Result<Vec<String>, ReplError> {
+        let mut context = self.context.clone();
+        let forgotten = context.forget(name);
+        if forgotten.is_empty() {
+            return Ok(Vec::new());
         }
+        self.create_temp_project(context.source())?;
+        self.context = context;
+        self.redefinition = None;
+        self.definitions_changed(true);
+        Ok(forgotten.iter().map(ToString::to_string).collect())
     }
 
-    /// Create a well-typed temporary project for compilation and evaluation
-    fn create_temp_project(&self, module_code: &str) -> Result<Project<NoEvent>, ReplError> {
-        // Create temporary aiken.toml
-        let aiken_toml = r#"
-                            name = "repl/temp"
-                            version = "0.0.0"
-                            plutus = "v3"
-                            "#;
+    /// Save the definitions and settings under `name`, replacing an earlier checkpoint of that
+    /// name
+    pub fn checkpoint(&mut self, name: &str) {
+        let checkpoint = Checkpoint {
+            context: self.context.clone(),
+            auto_format: self.auto_format,
+            cache: self.cache.is_some(),
+            warnings: self.warnings.get_mut().enabled,
+            probe: self.probe.as_ref().map(|probe| probe.code.clone()),
+            display_settings: self.display_settings,
+        };
+        self.checkpoints.insert(name.to_string(), checkpoint);
+    }
 
-        let aiken_toml_path = self.temp_dir.path().join("aiken.toml");
-        fs::write(&aiken_toml_path, aiken_toml)?;
+    /// Restore the definitions and settings saved under `name`. The checkpoint is kept, to roll
+    /// back to it again.
+    pub fn rollback(&mut self, name: &str) -> Result<(), ReplError> {
+        let checkpoint =
+            self.checkpoints
+                .get(name)
+                .cloned()
+                .ok_or_else(|| ReplError::UnknownCheckpoint {
+                    name: name.to_string(),
+                })?;
+
+        self.context = checkpoint.context;
+        self.auto_format = checkpoint.auto_format;
+        self.set_cache(checkpoint.cache);
+        self.set_warnings(checkpoint.warnings);
+        self.display_settings = checkpoint.display_settings;
+        self.probe = checkpoint
+            .probe
+            .map(|code| probe::Probe { code, last: None });
+        self.redefinition = None;
+        self.definitions_changed(false);
+        Ok(())
+    }
 
-        // Create lib directory
-        let lib_dir = self.temp_dir.path().join("lib");
-        fs::create_dir_all(&lib_dir)?;
+    /// Which definitions of the context refer to which others
+    pub fn dependency_graph(&self) -> Result<DependencyGraph, ReplError> {
+        if self.context.source().trim().is_empty() {
+            return Ok(DependencyGraph::default());
+        }
 
-        // Write module to lib/repl.ak
-        let module_path = lib_dir.join("repl.ak");
-        fs::write(&module_path, module_code)?;
+        let project = self.create_temp_project(self.context.source())?;
+        Ok(project
+            .modules()
+            .into_iter()
+            .find(|m| m.name == self.names.module)
+            .map(|m| DependencyGraph::new(&m))
+            .unwrap_or_default())
+    }
 
-        // Load project config
-        let config = ProjectConfig::load(self.temp_dir.path())?;
+    /// Names of the saved checkpoints, in alphabetical order
+    pub fn checkpoints(&self) -> impl Iterator<Item = &str> {
+        self.checkpoints.keys().map(String::as_str)
+    }
 
-        // Create and check project
-        let mut project = Project::new_with_config(
-            config,
-            self.temp_dir.path().to_path_buf(),
-            NoEvent, // Use `Terminal::default()` to print compiler feedback (eg. "resolving dependencies")
-        );
+    /// List the definitions in the current context with their types, and the values of constants
+    pub fn inspect(&mut self) -> Result<Vec<DefinitionInfo>, ReplError> {
+        if self.context.source().trim().is_empty() {
+            return Ok(Vec::new());
+        }
 
-        // Type-check the whole project
-        if let Err(errors) = project.check(
-            true,  // skip_tests
-            None,  // match_tests
-            false, // verbose
-            false, // exact_match
-            0,     // seed
-            100,   // property_max_success
-            CoverageMode::default(),
-            Tracing::All(TraceLevel::Compact),
-            None,  // env
-            false, // plain_numbers
-        ) {
-            // Convert the first error to our error type
-            if let Some(first_error) = errors.into_iter().next() {
-                return Err(ReplError::ProjectError(first_error));
+        let project = self.create_temp_project(self.context.source())?;
+        let mut definitions = project
+            .modules()
+            .into_iter()
+            .find(|m| m.name == self.names.module)
+            .map(|m| introspect::definitions(&m))
+            .unwrap_or_default();
+
+        // All constants are evaluated in a single pass, like user expressions
+        let constants: Vec<String> = definitions
+            .iter()
+            .filter(|def| def.kind == DefinitionKind::Constant)
+            .map(|def| def.name.clone())
+            .collect();
+        let constants: Vec<&str> = constants.iter().map(String::as_str).collect();
+        let mut values = self.eval_expressions(&constants).into_iter();
+        for def in definitions
+            .iter_mut()
+            .filter(|def| def.kind == DefinitionKind::Constant)
+        {
+            if let Some(Ok(EvaluationResult::Value { value, .. })) = values.next() {
+                def.value = Some(value);
             }
         }
+        for def in definitions.iter_mut() {
+            def.origin = self
+                .context
+                .provenance(&def.name)
+                .and_then(|provenance| provenance.origin.clone());
+        }
 
-        Ok(project)
+        Ok(definitions)
     }
 
-    /// Generate and evaluate UPLC
-    fn generate_and_eval(
-        &self,
-        project: &mut Project<NoEvent>,
-        repl_module: CheckedModule,
-        eval_fn: &aiken_lang::ast::TypedFunction,
-    ) -> Result<EvalResult, ReplError> {
-        // Init a new code generator
-        let mut generator = project.new_generator(Tracing::All(TraceLevel::Compact));
+    /// Enable or disable formatting definitions before adding them to the context
+    pub fn set_auto_format(&mut self, enabled: bool) {
+        self.auto_format = enabled;
+    }
 
-        // Generate UPLC for the function
-        let program = generator.generate_raw(&eval_fn.body, &[], &repl_module.name);
+    pub fn deterministic(&self) -> bool {
+        self.deterministic
+    }
 
-        // Convert to NamedDeBruijn
-        let named_program = Program::<NamedDeBruijn>::try_from(program).map_err(|err| {
-            ReplError::EvaluationFailed {
-                message: format!("Failed to convert to NamedDeBruijn: {:?}", err),
-            }
-        })?;
+    /// `text` without the paths of the temp project, which change with every session, for
+    /// deterministic outputs
+    pub fn without_paths(&self, text: &str) -> String {
+        text.replace(&self.temp_dir.path().display().to_string(), "<session>")
+    }
 
-        // Evaluate Program
-        let result = named_program.eval_version(ExBudget::max(), &self.plutus_version.into());
+    /// Report compiler events of the next evaluations as lines of text on `tx`, or stop reporting
+    /// them with None. Dropping the sender this way lets the receiving end finish.
+    pub fn set_progress(&mut self, tx: Option<ProgressTx>) {
+        self.progress.tx = tx;
+    }
 
-        Ok(result)
+    /// Seed of property tests, None when every run picks a random one
+    pub fn seed(&self) -> Option<u32> {
+        self.seed
     }
 
-    /// Collect new definition names
-    fn collect_definition_names(&self, code: &str) -> DefinitionNames {
-        let mut names = DefinitionNames::default();
+    pub fn display_settings(&self) -> &DisplaySettings {
+        &self.display_settings
+    }
 
-        for line in code.lines() {
-            let line = line.trim();
+    /// Every option with its value, one `name = value` per line
+    pub fn options(&self) -> String {
+        let trace_level = match self.trace_level {
+            TraceLevel::Silent => "silent",
+            TraceLevel::Compact => "compact",
+            TraceLevel::Verbose => "verbose",
+        };
+        let coverage_mode = match self.progress.coverage {
+            CoverageMode::RelativeToTests => "relative-to-tests",
+            CoverageMode::RelativeToLabels => "relative-to-labels",
+        };
+        format!(
+            "{}\nplutus-version = {}\ntrace-level = {}\nbudget-cpu = {}\nbudget-mem = {}\nseed = {}\ncoverage-mode = {}\nwarnings = {}\ncache = {}\nauto-format = {}\ndeterministic = {}\nproject-name = {}\nmodule-name = {}\nbackend = {}",
+            self.display_settings,
+            export::plutus(self.plutus_version),
+            trace_level,
+            self.budget.cpu,
+            self.budget.mem,
+            self.seed
+                .map_or_else(|| "random".to_string(), |seed| seed.to_string()),
+            coverage_mode,
+            on_off(self.warnings.borrow().enabled),
+            on_off(self.cache.is_some()),
+            on_off(self.auto_format),
+            on_off(self.deterministic),
+            self.names.project,
+            self.names.module,
+            self.compiler.option()
+        )
+    }
 
-            // Extract function names
-            if let Some(func_name) = extract_function_name(line) {
-                names.functions.insert(func_name);
+    /// Change an option by name, as in `:set bytearray-display utf8`
+    pub fn set_option(&mut self, name: &str, value: &str) -> Result<(), ReplError> {
+        let invalid = |message: String| ReplError::InvalidSetting { message };
+        let settings = &mut self.display_settings;
+        match name {
+            "bytearray-display" => settings.bytearray = value.parse().map_err(invalid)?,
+            "max-depth" => {
+                settings.max_depth = value.parse().map_err(|e| invalid(format!("{e}")))?
             }
-
-            // Extract constant names
-            if let Some(const_name) = extract_constant_name(line) {
-                names.constants.insert(const_name);
+            "max-width" => {
+                settings.max_width = value.parse().map_err(|e| invalid(format!("{e}")))?
             }
-
-            // Extract type names
-            if let Some(type_name) = extract_type_name(line) {
-                names.types.insert(type_name);
+            "max-chars" => {
+                settings.max_chars = value.parse().map_err(|e| invalid(format!("{e}")))?
             }
-        }
-
-        names
-    }
-
-    /// Remove existing definitions that would conflict with new ones (support interactive re-definition)
-    /// TODO: For now I manipulate the text, but could I modify the AST directly instead?
-    fn remove_existing_definitions(&mut self, new_names: &DefinitionNames) {
-        let lines: Vec<String> = self.definitions.lines().map(|s| s.to_string()).collect();
-        let mut filtered_lines = Vec::new();
-
-        let mut i = 0;
-        while i < lines.len() {
-            let line = &lines[i];
-            let trimmed = line.trim();
-
-            // Check if this line starts a definition that we want to replace
-            let should_remove = if let Some(func_name) = extract_function_name(trimmed) {
-                new_names.functions.contains(&func_name)
-            } else if let Some(const_name) = extract_constant_name(trimmed) {
-                new_names.constants.contains(&const_name)
-            } else if let Some(type_name) = extract_type_name(trimmed) {
-                new_names.types.contains(&type_name)
-            } else {
-                false
-            };
-
-            if should_remove {
-                // Skip this definition and any continuation lines
-                i += 1;
-                // Skip any lines that are part of the same definition (indented or containing braces)
-                while i < lines.len() {
-                    let next_line = lines[i].trim();
-                    // Stop skipping if we hit another top-level definition or empty line
-                    if !next_line.is_empty()
-                        && !next_line.starts_with(' ')
-                        && !next_line.starts_with('\t')
-                        && !next_line.starts_with('}')
-                        && (next_line.starts_with("pub ")
-                            || next_line.starts_with("const ")
-                            || next_line.starts_with("fn ")
-                            || next_line.starts_with("type ")
-                            || next_line.starts_with("use "))
-                    {
-                        break;
+            "plutus-version" => {
+                self.plutus_version = match value {
+                    "v1" => PlutusVersion::V1,
+                    "v2" => PlutusVersion::V2,
+                    "v3" => PlutusVersion::V3,
+                    _ => {
+                        return Err(invalid(format!(
+                            "expected one of: {}",
+                            PLUTUS_VERSIONS.join(", ")
+                        )));
+                    }
+                }
+            }
+            "trace-level" => {
+                self.trace_level = match value {
+                    "silent" => TraceLevel::Silent,
+                    "compact" => TraceLevel::Compact,
+                    "verbose" => TraceLevel::Verbose,
+                    _ => {
+                        return Err(invalid(
+                            "expected one of: silent, compact, verbose".to_string(),
+                        ));
+                    }
+                }
+            }
+            "budget-cpu" | "budget-mem" => {
+                let limit: i64 = value.parse().map_err(|e| invalid(format!("{e}")))?;
+                if limit <= 0 {
+                    return Err(invalid(format!("{} must be positive", name)));
+                }
+                if name == "budget-cpu" {
+                    self.budget.cpu = limit;
+                } else {
+                    self.budget.mem = limit;
+                }
+            }
+            "seed" => {
+                self.seed = match value {
+                    "random" => None,
+                    _ => Some(value.parse().map_err(|_| {
+                        invalid("expected `random` or a number up to 4294967295".to_string())
+                    })?),
+                }
+            }
+            "coverage-mode" => {
+                self.progress.coverage = match value {
+                    "relative-to-tests" => CoverageMode::RelativeToTests,
+                    "relative-to-labels" => CoverageMode::RelativeToLabels,
+                    _ => {
+                        return Err(invalid(
+                            "expected one of: relative-to-tests, relative-to-labels".to_string(),
+                        ));
                     }
-                    i += 1;
                 }
-            } else {
-                filtered_lines.push(line.clone());
-                i += 1;
+            }
+            "warnings" => self.set_warnings(switch(value)?),
+            "cache" => self.set_cache(switch(value)?),
+            "auto-format" => self.auto_format = switch(value)?,
+            "deterministic" => self.deterministic = switch(value)?,
+            "project-name" => self.names.set_project(value)?,
+            "module-name" => self.set_module_name(value)?,
+            "backend" => self.set_compiler(Compiler::select(value)?)?,
+            _ => {
+                return Err(invalid(format!(
+                    "unknown option `{}`, expected one of: {}",
+                    name,
+                    OPTIONS.join(", ")
+                )));
             }
         }
 
-        self.definitions = filtered_lines.join("\n");
+        // Cached results were displayed with the old settings
+        if let Some(cache) = &mut self.cache {
+            cache.clear();
+        }
+        Ok(())
     }
 
-    /// Extract a constant from a term if possible
-    fn extract_constant(&self, term: &Term<NamedDeBruijn>) -> Option<Constant> {
-        match term {
-            Term::Constant(c) => Some(c.as_ref().clone()),
-            _ => None,
+    /// Change several options, eg. for one cell. Returns their values before, to set them back
+    /// with this function again. When an option can't be set, those set before it are restored.
+    pub fn set_options(
+        &mut self,
+        options: &[(String, String)],
+    ) -> Result<Vec<(String, String)>, ReplError> {
+        let current = self.options();
+        let mut previous = Vec::new();
+        for (name, value) in options {
+            let before = current
+                .lines()
+                .find_map(|line| line.strip_prefix(name.as_str())?.strip_prefix(" = "));
+            if let Err(e) = self.set_option(name, value) {
+                let _ = self.set_options(&previous);
+                return Err(e);
+            }
+            if let Some(before) = before {
+                previous.push((name.clone(), before.to_string()));
+            }
         }
+        Ok(previous)
     }
-}
 
-/// Check if the code looks like an expression vs definitions
-fn looks_like_expression(code: &str) -> bool {
-    let trimmed = code.trim();
+    /// Move the session's definitions to module `name`, unless they import it
+    fn set_module_name(&mut self, name: &str) -> Result<(), ReplError> {
+        let mut names = self.names.clone();
+        names.set_module(name)?;
+        if let Some(module) = reserved_import(&names, self.context.keys()) {
+            return Err(ReplError::ReservedModule {
+                module: module.to_string(),
+            });
+        }
+        // The old module would be compiled along with the new one
+        let lib = self.temp_dir.path().join("lib");
+        let _ = fs::remove_file(module_path(&lib, &self.names.module));
+        self.names = names;
+        Ok(())
+    }
 
-    // Common definition keywords
-    let def_keywords = [
-        "fn ",
-        "pub fn",
-        "type ",
-        "pub type",
-        "const ",
-        "pub const",
-        "use ",
-        "import ",
-        "test ",
-        "validator",
-    ];
+    /// Tell where the inputs evaluated next come from, eg. `cell 3`, for the notes about what
+    /// they redefine
+    pub fn set_origin(&mut self, origin: Option<String>) {
+        self.origin = origin;
+    }
 
-    // If it starts with a definition keyword, it's not an expression
-    for keyword in &def_keywords {
-        if trimmed.starts_with(keyword) {
-            return false;
+    /// Enable or disable collecting compiler warnings. Disabling drops the pending ones.
+    pub fn set_warnings(&mut self, enabled: bool) {
+        let warnings = self.warnings.get_mut();
+        warnings.enabled = enabled;
+        if !enabled {
+            warnings.take();
         }
     }
 
-    // If it contains newlines and definition keywords, probably definitions
-    if trimmed.contains('\n') {
-        for keyword in &def_keywords {
-            if trimmed.contains(keyword) {
-                return false;
-            }
+    /// Compiler warnings raised since the last call, rendered for display. Each warning is only
+    /// reported once, even though every evaluation type-checks the whole context again.
+    pub fn take_warnings(&self) -> Vec<String> {
+        self.warnings.borrow_mut().take()
+    }
+
+    /// Enable or disable caching of expression results. Disabling drops the cached results.
+    pub fn set_cache(&mut self, enabled: bool) {
+        if !enabled {
+            self.cache = None;
+        } else if self.cache.is_none() {
+            self.cache = Some(HashMap::new());
         }
     }
 
-    true
-}
+    /// Measure `code` now and after every change to the definitions, so that redefining a
+    /// function reports how its script size and budget moved. The probe must be an expression
+    /// that evaluates against the current definitions.
+    pub fn set_probe(&mut self, code: &str) -> Result<ProbeMeasure, ReplError> {
+        let program = self.compile_expression(code, "Only expressions can be probes")?;
+        let measure = probe::measure(program, self.plutus_version)?;
+        self.probe = Some(probe::Probe {
+            code: code.to_string(),
+            last: Some(measure),
+        });
+        Ok(measure)
+    }
 
-/// Convert a UPLC term to a display string
-/// TODO: Isn't this already implemented in Aiken somewhere?
-fn term_to_string(term: &Term<NamedDeBruijn>) -> String {
-    match term {
-        Term::Constant(c) => match c.as_ref() {
-            Constant::Integer(i) => i.to_string(),
-            Constant::ByteString(bs) => format!("#{}", hex::encode(bs)),
-            Constant::String(s) => format!("\"{}\"", s),
-            Constant::Bool(b) => if *b { "True" } else { "False" }.to_string(),
-            Constant::Unit => "Void".to_string(),
-            Constant::ProtoList(_, items) => {
-                let item_strs: Vec<_> = items.iter().map(|item| format!("{:?}", item)).collect();
-                format!("[{}]", item_strs.join(", "))
-            }
-            Constant::ProtoPair(_, _, first, second) => {
-                format!("Pair({:?}, {:?})", first, second)
-            }
-            Constant::Data(d) => format!("{:?}", d),
-            _ => format!("{:?}", c),
-        },
-        _ => format!("{:?}", term),
+    /// Stop measuring the probe
+    pub fn clear_probe(&mut self) {
+        self.probe = None;
+        self.redefinition = None;
     }
-}
 
-fn extract_function_name(line: &str) -> Option<String> {
-    if line.starts_with("pub fn ") {
-        line.strip_prefix("pub fn ")
-            .and_then(|rest| rest.split('(').next())
-            .map(|name| name.trim().to_string())
-    } else if line.starts_with("fn ") {
-        line.strip_prefix("fn ")
-            .and_then(|rest| rest.split('(').next())
-            .map(|name| name.trim().to_string())
-    } else {
-        None
+    /// Probe measurements before and after the last evaluation, if it redefined functions
+    pub fn take_redefinition_report(&mut self) -> Option<RedefinitionReport> {
+        self.redefinition.take()
     }
-}
 
-fn extract_constant_name(line: &str) -> Option<String> {
-    if line.starts_with("pub const ") {
-        line.strip_prefix("pub const ")
-            .and_then(|rest| rest.split_whitespace().next())
-            .map(|name| name.trim().to_string())
-    } else if line.starts_with("const ") {
-        line.strip_prefix("const ")
-            .and_then(|rest| rest.split_whitespace().next())
-            .map(|name| name.trim().to_string())
-    } else {
-        None
+    /// Format a piece of code (definitions or a single expression) with Aiken's formatter
+    pub fn format(&self, code: &str) -> Result<String, ReplError> {
+        if looks_like_expression(code) {
+            format::format_expression(code)
+        } else {
+            format::format_module(code)
+        }
     }
-}
 
-fn extract_type_name(line: &str) -> Option<String> {
-    if line.starts_with("pub type ") {
-        line.strip_prefix("pub type ")
-            .and_then(|rest| rest.split_whitespace().next())
-            .map(|name| name.trim().to_string())
-    } else if line.starts_with("type ") {
-        line.strip_prefix("type ")
-            .and_then(|rest| rest.split_whitespace().next())
-            .map(|name| name.trim().to_string())
-    } else {
-        None
+    /// Decode a Cardano address given in bech32 or hex, eg. to paste it in code as an `Address`
+    pub fn decode_address(&self, address: &str) -> Result<AddressInfo, ReplError> {
+        address::decode(address)
     }
-}
 
-#[cfg(test)]
-mod test {
-    use crate::evaluator::{EvaluationResult, ReplEvaluator, looks_like_expression};
+    /// Convert data given as JSON (in the detailed schema of cardano-cli) or as CBOR hex. When
+    /// `input` starts with a type, eg. `Option<Int> {"constructor": 0, ...}`, the data is also
+    /// shown as an Aiken value of that type.
+    pub fn to_data(&self, input: &str) -> Result<DataConversion, ReplError> {
+        let input = input.trim();
+        let (annotation, encoded) = match input.find('{') {
+            Some(start) => input.split_at(start),
+            None => input
+                .rsplit_once(char::is_whitespace)
+                .unwrap_or(("", input)),
+        };
+        // JSON is often quoted for the shell, as in `'{"int": 42}'`
+        let data = data::parse(encoded.trim().trim_matches('\''))?;
+        let term = Term::Constant(Rc::new(Constant::Data(data.clone())));
+        let notation = pretty::term(&term, &self.display_settings);
+
+        let annotation = annotation.trim().trim_end_matches('\'').trim();
+        if annotation.is_empty() {
+            return Ok(DataConversion::new(&data, notation, None));
+        }
 
-    #[test]
-    fn test_simple_expression() {
+        // The type is resolved from a function returning it, defined along the context
+        let eval_count = self.eval_counter.fetch_add(1, Ordering::Relaxed);
+        let type_fn_name = format!("repl_eval_{}", eval_count);
+        let module_code = format!(
+            "{}\n\npub fn {}(value: {}) -> {} {{ value }}",
+            self.context.source(),
+            type_fn_name,
+            annotation,
+            annotation
+        );
+        let project = self.create_temp_project(&module_code)?;
+        let (_, type_fn) = find_eval_fn(&project, &self.names.module, &type_fn_name)?;
+        let value = reify::value(&project, &term, type_fn.return_type).ok_or_else(|| {
+            ReplError::InvalidData {
+                message: format!("the data is not a value of type {}", annotation),
+            }
+        })?;
+        Ok(DataConversion::new(&data, notation, Some(value)))
+    }
+
+    /// Evaluate an expression and convert its value to data, as it would be in a datum or a
+    /// redeemer
+    pub fn from_data(&mut self, code: &str) -> Result<DataConversion, ReplError> {
+        let (value, data) = self.eval_data(code)?;
+        Ok(DataConversion::new(&data, value, None))
+    }
+
+    /// Data of an expression's value, eg. to use it as a datum
+    pub fn expression_data(&mut self, code: &str) -> Result<PlutusData, ReplError> {
+        self.eval_data(code).map(|(_, data)| data)
+    }
+
+    /// Value of an expression as a Rust value, eg. `eval_typed::<Option<i64>>("list.head(xs)")`,
+    /// for tools embedding the evaluator
+    pub fn eval_typed<T: FromData>(&mut self, code: &str) -> Result<T, ReplError> {
+        T::from_data(&self.expression_data(code)?)
+    }
+
+    /// Data of an expression's value, with its notation
+    fn eval_data(&mut self, code: &str) -> Result<(String, PlutusData), ReplError> {
+        if !looks_like_expression(code) {
+            return Err(ReplError::ParseFailed {
+                message: "Only expressions can be converted to data".to_string(),
+            });
+        }
+        let wrapped = format!("{{\n  let repl_data: Data = {}\n  repl_data\n}}", code);
+        match self.eval_expression(&wrapped)? {
+            EvaluationResult::Value {
+                value,
+                uplc_result: Some(Constant::Data(data)),
+                ..
+            } => Ok((value, data)),
+            _ => Err(ReplError::EvaluationFailed {
+                message: "The expression didn't evaluate to data".to_string(),
+            }),
+        }
+    }
+
+    /// Generate a key pair named `name`, and define its verification key and hash as the
+    /// constants `<name>_vk` and `<name>_vkh`
+    pub fn keygen(&mut self, name: &str) -> Result<KeyInfo, ReplError> {
+        keys::validate_name(name)?;
+        if self.keys.contains_key(name) {
+            return Err(ReplError::InvalidKey {
+                message: format!("there's already a key named `{}`", name),
+            });
+        }
+        let pair = keys::KeyPair::generate()?;
+        let info = pair.info(name);
+        self.define(&info.definitions())?;
+        self.keys.insert(name.to_string(), pair);
+        Ok(info)
+    }
+
+    /// Sign the bytes an expression evaluates to with the key named `name`. Returns the
+    /// signature.
+    pub fn sign(&mut self, name: &str, code: &str) -> Result<Vec<u8>, ReplError> {
+        if !self.keys.contains_key(name) {
+            return Err(ReplError::InvalidKey {
+                message: format!("no key named `{}`", name),
+            });
+        }
+        let message = match self.eval_expression(code)? {
+            EvaluationResult::Value {
+                uplc_result: Some(Constant::ByteString(bytes)),
+                ..
+            } => bytes,
+            _ => {
+                return Err(ReplError::EvaluationFailed {
+                    message: "Only byte arrays can be signed".to_string(),
+                });
+            }
+        };
+        Ok(self.keys[name].sign(&message))
+    }
+
+    /// Public parts of the key pairs of the session
+    pub fn keys(&self) -> impl Iterator<Item = KeyInfo> {
+        self.keys.iter().map(|(name, pair)| pair.info(name))
+    }
+
+    /// Hash of the verification key named `name`, if there's one
+    pub fn key_hash(&self, name: &str) -> Option<Vec<u8>> {
+        self.keys.get(name).map(|pair| pair.info(name).key_hash)
+    }
+
+    /// Names of the validators of the context
+    pub fn validator_names(&self) -> impl Iterator<Item = &str> {
+        self.context.keys().filter_map(|key| match key {
+            DefinitionKey::Validator(name) => Some(name.as_str()),
+            _ => None,
+        })
+    }
+
+    /// Run the validator `name` on a script context, as the ledger would. Validators with
+    /// parameters can't be run.
+    pub fn run_validator(
+        &self,
+        name: &str,
+        script_context: PlutusData,
+    ) -> Result<ValidatorRun, ReplError> {
+        let mut project = self.create_temp_project(self.context.source())?;
+        let repl_module = project
+            .modules()
+            .into_iter()
+            .find(|m| m.name == self.names.module)
+            .ok_or_else(|| ReplError::EvaluationFailed {
+                message: "Could not find repl module".to_string(),
+            })?;
+        let validator = repl_module
+            .ast
+            .definitions()
+            .find_map(|def| match def {
+                Definition::Validator(v) if v.name == name => Some(v.clone()),
+                _ => None,
+            })
+            .ok_or_else(|| ReplError::EvaluationFailed {
+                message: format!("No validator named `{}`", name),
+            })?;
+        if !validator.params.is_empty() {
+            return Err(ReplError::EvaluationFailed {
+                message: format!("`{}` has parameters, which can't be applied", name),
+            });
+        }
+
+        let mut generator = project.new_generator(Tracing::All(self.trace_level));
+        let program = generator.generate(&validator, &repl_module.name);
+        let program = Program::<NamedDeBruijn>::try_from(program).map_err(|err| {
+            ReplError::EvaluationFailed {
+                message: format!("Failed to convert to NamedDeBruijn: {:?}", err),
+            }
+        })?;
+
+        let result = program
+            .apply_data(script_context)
+            .eval_version(self.budget, &self.plutus_version.into());
+        Ok(ValidatorRun {
+            budget: result.cost(),
+            traces: result.logs(),
+            error: result.result.err().map(|err| format!("{}", err)),
+        })
+    }
+
+    /// Run the scripts of a serialized transaction against the UTxOs it spends, with `args` as
+    /// `<tx> --inputs <utxos.json> [--network mainnet|preprod|preview]`. Reports the budget and
+    /// traces of every redeemer.
+    pub fn simulate(&self, args: &str) -> Result<Simulation, ReplError> {
+        simulate::simulate(&SimulateArgs::parse(args)?, self.chain.as_deref())
+    }
+
+    /// Look UTxOs up with `provider`, eg. to simulate transactions without a file of inputs
+    pub fn set_chain_provider(&mut self, provider: Box<dyn ChainProvider>) {
+        self.chain = Some(provider);
+    }
+
+    /// Set the chain provider from its settings: the project id of a Blockfrost project, when
+    /// built with the `blockfrost` feature
+    pub fn configure_chain(&mut self, settings: &str) -> Result<(), ReplError> {
+        self.chain = Some(chain::configure(settings)?);
+        Ok(())
+    }
+
+    /// Network of the chain provider, if there's one
+    pub fn chain_network(&self) -> Option<Network> {
+        self.chain.as_ref().map(|chain| chain.network())
+    }
+
+    /// Unspent outputs at an address given in bech32, from the chain provider
+    pub fn utxos(&self, address: &str) -> Result<Vec<Utxo>, ReplError> {
+        self.chain
+            .as_ref()
+            .ok_or_else(|| ReplError::ChainFailed {
+                message: "no chain provider is configured".to_string(),
+            })?
+            .utxos_at(address.trim())
+    }
+
+    /// Format the accumulated definitions
+    pub fn format_context(&self) -> Result<String, ReplError> {
+        format::format_module(self.context.source())
+    }
+
+    /// Write the formatted definitions to `path`: as a module when it ends with `.ak`, or else as a
+    /// new project in that directory. Returns the files written. Nothing gets overwritten.
+    pub fn export(&self, path: &Path) -> Result<Vec<PathBuf>, ReplError> {
+        if self.context.source().trim().is_empty() {
+            return Err(ReplError::ExportFailed {
+                message: "the context is empty".to_string(),
+            });
+        }
+        let code = self.format_context()?;
+
+        if path.extension().is_some_and(|extension| extension == "ak") {
+            export::write_module(path, &code)
+        } else {
+            let has_validators = self
+                .context
+                .keys()
+                .any(|key| matches!(key, DefinitionKey::Validator(_)));
+            let uses_prelude = self
+                .context
+                .keys()
+                .any(|key| *key == DefinitionKey::Import(PRELUDE_MODULE.to_string()));
+            export::write_project(
+                path,
+                &code,
+                has_validators,
+                uses_prelude,
+                self.plutus_version,
+            )
+        }
+    }
+
+    /// Look up the documentation and signature of a definition, either from the
+    /// session (`my_fn`) or from a module in scope (`list.map`)
+    pub fn doc(&self, name: &str) -> Result<Option<DocEntry>, ReplError> {
+        let name = name.trim();
+        let source = self.context.source();
+        let project = self.create_temp_project(source)?;
+        let mut entry = docs::lookup(&project.modules(), name, &self.names.module);
+        // A qualified name may be of a stdlib module the session doesn't import, which takes
+        // fetching the stdlib. Failing to is an error of this lookup only.
+        if entry.is_none() && name.contains('.') && export::dependencies(source).is_empty() {
+            let project =
+                self.write_temp_project(source, export::STDLIB)
+                    .and_then(|project| self.check_temp_project(project, source))
+                    .map_err(|e| {
+                        let cause = match e {
+                            ReplError::ProjectError(error)
+                            | ReplError::SessionError { error, .. } => error.to_string(),
+                            e => e.to_string(),
+                        };
+                        ReplError::EvaluationFailed {
+                            message: format!(
+                                "Failed to fetch the stdlib to look up `{}`: {}",
+                                name, cause
+                            ),
+                        }
+                    })?;
+            entry = docs::lookup(&project.modules(), name, &self.names.module);
+        }
+        if let Some(entry) = entry
+            .as_mut()
+            .filter(|entry| entry.module == self.names.module)
+        {
+            // How long ago the definition was written changes with every run
+            if !self.deterministic {
+                entry.provenance = self.context.provenance(&entry.name).cloned();
+            }
+        }
+        Ok(entry)
+    }
+
+    /// Names that can be completed against the current definitions: the session's own, modules
+    /// to import and their members
+    pub fn completion_candidates(&self) -> Result<CompletionCandidates, ReplError> {
+        let project = self.create_temp_project(self.context.source())?;
+        Ok(CompletionCandidates::new(
+            &project.modules(),
+            &self.names.module,
+        ))
+    }
+
+    /// Evaluate a piece of Aiken code
+    pub fn eval(&mut self, code: &str) -> Result<EvaluationResult, ReplError> {
+        let code = normalize_newlines(code);
+        let code: &str = &code;
+        self.redefinition = None;
+
+        self.progress.clock.start();
+        let backend = self.compiler.backend();
+        let result = self.guarded(|repl| backend.eval(repl, code));
+        self.timings = Some(self.progress.clock.stop());
+
+        self.gc_if_needed();
+
+        // Warnings are all there is to show about code that doesn't produce anything
+        match result {
+            Ok(EvaluationResult::NoResult) => {
+                let warnings = self.take_warnings();
+                if warnings.is_empty() {
+                    Ok(EvaluationResult::NoResult)
+                } else {
+                    Ok(EvaluationResult::Warnings(warnings))
+                }
+            }
+            result => result,
+        }
+    }
+
+    /// Phases of the last evaluation and how long each took
+    pub fn timings(&self) -> Option<&Timings> {
+        self.timings.as_ref()
+    }
+
+    /// Evaluate `code` like `eval`, showing its result whole whatever the display settings
+    pub fn eval_full(&mut self, code: &str) -> Result<EvaluationResult, ReplError> {
+        let settings = self.display_settings;
+        // Cached results were cut, and this one shouldn't be served to `eval` later
+        let cache = self.cache.take();
+        self.display_settings = settings.unlimited();
+        let result = self.eval(code);
+        self.display_settings = settings;
+        self.cache = cache;
+        result
+    }
+
+    /// Build the temp project and evaluate a trivial expression, so that the first evaluation
+    /// doesn't pay for setting them up. Nothing is added to the context.
+    pub fn warm_up(&mut self) -> Result<(), ReplError> {
+        self.eval_expression("Void")?;
+        Ok(())
+    }
+
+    /// Add the definitions of `code` to the context like `eval` does, without evaluating
+    /// anything. Returns false for expressions, which are left out, as is the expression that
+    /// ends code mixing both.
+    pub fn define(&mut self, code: &str) -> Result<bool, ReplError> {
+        let code = normalize_newlines(code);
+        let code: &str = &code;
+        if looks_like_expression(code) {
+            return Ok(false);
+        }
+        let definitions =
+            split_trailing_expression(code).map_or(code, |(definitions, _)| definitions);
+        self.redefinition = None;
+        let backend = self.compiler.backend();
+        self.guarded(|repl| backend.define(repl, definitions))?;
+        Ok(true)
+    }
+
+    /// Run `f`, turning a panic of the compiler into an error instead of losing the session.
+    /// Whatever `f` changed to the definitions before panicking is undone.
+    fn guarded<R>(
+        &mut self,
+        f: impl FnOnce(&mut Self) -> Result<R, ReplError>,
+    ) -> Result<R, ReplError> {
+        let context = self.context.clone();
+        let probe = self.probe.clone();
+        match catch_unwind(AssertUnwindSafe(|| f(self))) {
+            Ok(result) => result,
+            Err(payload) => {
+                self.context = context;
+                self.probe = probe;
+                self.redefinition = None;
+                if let Some(cache) = &mut self.cache {
+                    cache.clear();
+                }
+                let message = payload
+                    .downcast_ref::<&str>()
+                    .map(|message| message.to_string())
+                    .or_else(|| payload.downcast_ref::<String>().cloned())
+                    .unwrap_or_else(|| "unknown panic".to_string());
+                Err(ReplError::Panicked { message })
+            }
+        }
+    }
+
+    /// Add `definitions` to the context, then evaluate `expression` against them. The
+    /// definitions are kept even if the expression fails.
+    fn eval_mixed(
+        &mut self,
+        definitions: &str,
+        expression: &str,
+    ) -> Result<EvaluationResult, ReplError> {
+        let definitions = self.eval_definitions(definitions)?;
+        let result = self.eval_cached_expression(expression)?;
+        Ok(EvaluationResult::Mixed {
+            definitions: Box::new(definitions),
+            result: Box::new(result),
+        })
+    }
+
+    /// Evaluate an expression, reusing the previous result if it was already evaluated
+    /// against the same definitions
+    fn eval_cached_expression(&mut self, code: &str) -> Result<EvaluationResult, ReplError> {
+        if self.cache.is_none() {
+            return self.eval_expression(code);
+        }
+
+        let key = CacheKey {
+            definitions_hash: hash_definitions(self.context.source()),
+            code: code.trim().to_string(),
+        };
+        if let Some(cached) = self.cache.as_ref().and_then(|cache| cache.get(&key)) {
+            let CachedValue {
+                value,
+                tipo,
+                budget,
+                traces,
+            } = cached.clone();
+            return Ok(EvaluationResult::Cached {
+                value,
+                tipo,
+                budget,
+                traces,
+            });
+        }
+
+        let result = self.eval_expression(code)?;
+        if let (
+            Some(cache),
+            EvaluationResult::Value {
+                value,
+                tipo,
+                budget,
+                traces,
+                ..
+            },
+        ) = (&mut self.cache, &result)
+            // Functions aren't evaluated, there's nothing to save
+            && reify::function(tipo).is_none()
+        {
+            let cached = CachedValue {
+                value: value.clone(),
+                tipo: Printer::new().pretty_print(tipo, 0),
+                budget: *budget,
+                traces: traces.clone(),
+            };
+            cache.insert(key, cached);
+        }
+        Ok(result)
+    }
+
+    /// Evaluate expressions by wrapping them in a function. Expressions with type variables,
+    /// like `[]`, are evaluated with the variables set to `Data`, with a note about it.
+    fn eval_expression(&mut self, code: &str) -> Result<EvaluationResult, ReplError> {
+        match self.eval_annotated_expression(code, None) {
+            Err(ReplError::AmbiguousType { tipo, defaulted }) => {
+                let result = self
+                    .eval_annotated_expression(code, Some(&defaulted))
+                    .map_err(|err| match err {
+                        // Data doesn't fit, eg. a type of another module isn't in scope
+                        ReplError::ProjectError(_) | ReplError::SessionError { .. } => {
+                            ReplError::AmbiguousType {
+                                tipo: tipo.clone(),
+                                defaulted: defaulted.clone(),
+                            }
+                        }
+                        err => err,
+                    })?;
+                self.warnings.get_mut().note(format!(
+                    "note: {} is ambiguous, evaluated as {}",
+                    tipo, defaulted
+                ));
+                Ok(result)
+            }
+            result => result,
+        }
+    }
+
+    /// Evaluate an expression wrapped in a function returning `return_type`, or whatever type is
+    /// inferred when None
+    fn eval_annotated_expression(
+        &mut self,
+        code: &str,
+        return_type: Option<&str>,
+    ) -> Result<EvaluationResult, ReplError> {
+        // Create unique evaluation function name
+        let eval_count = self.eval_counter.fetch_add(1, Ordering::Relaxed);
+        let eval_fn_name = format!("repl_eval_{}", eval_count);
+
+        // Wrap the expression in a function for evaluation
+        let annotation = return_type
+            .map(|tipo| format!(" -> {}", tipo))
+            .unwrap_or_default();
+        let wrapped_code = format!("pub fn {}(){} {{ {} }}", eval_fn_name, annotation, code);
+
+        // Create complete module with accumulated definitions
+        let module_code = format!("{}\n\n{}", self.context.source(), wrapped_code);
+
+        // Definitions that were checked already only need the expression to be type-checked
+        let key = incremental::Key {
+            definitions_hash: hash_definitions(self.context.source()),
+            plutus_version: self.plutus_version,
+            trace_level: self.trace_level,
+            names: self.names.clone(),
+        };
+        self.progress.clock.enter(Phase::Typecheck);
+        let evaluated =
+            self.incremental
+                .with_checked(&key, &module_code, &eval_fn_name, |project, eval_fn| {
+                    self.eval_function(project, &self.names.module, eval_fn)
+                });
+        if let Some(result) = evaluated {
+            return result;
+        }
+
+        // Create a well-typed temporary project
+        let mut project = self.create_temp_project(&module_code)?;
+
+        let result = self.eval_wrapped(&mut project, &eval_fn_name);
+        self.incremental.keep(key, project);
+        result
+    }
+
+    /// Evaluate several expressions against the current context, without adding anything to it.
+    ///
+    /// All expressions are type-checked together in a single project, which is much faster than
+    /// one project per expression. If that fails because some of them don't type-check, each
+    /// expression is evaluated on its own so every one gets its own result or error.
+    pub fn eval_expressions(
+        &mut self,
+        expressions: &[&str],
+    ) -> Vec<Result<EvaluationResult, ReplError>> {
+        if expressions.is_empty() {
+            return Vec::new();
+        }
+
+        let eval_count = self.eval_counter.fetch_add(1, Ordering::Relaxed);
+        let fn_names: Vec<String> = (0..expressions.len())
+            .map(|i| format!("repl_eval_{}_{}", eval_count, i))
+            .collect();
+
+        let wrapped_code: Vec<String> = fn_names
+            .iter()
+            .zip(expressions)
+            .map(|(name, code)| format!("pub fn {}() {{ {} }}", name, code))
+            .collect();
+        let module_code = format!("{}\n\n{}", self.context.source(), wrapped_code.join("\n\n"));
+
+        match self.create_temp_project(&module_code) {
+            Ok(mut project) => fn_names
+                .iter()
+                .map(|name| self.eval_wrapped(&mut project, name))
+                .collect(),
+            Err(_) => expressions
+                .iter()
+                .map(|code| self.eval_expression(code))
+                .collect(),
+        }
+    }
+
+    /// Time the evaluation of an expression, `repeats` runs of `loops` evaluations each. The
+    /// expression is compiled once, so only the evaluation of its UPLC program is timed.
+    pub fn timeit(
+        &mut self,
+        code: &str,
+        loops: Option<u32>,
+        repeats: u32,
+    ) -> Result<Timing, ReplError> {
+        let program = self.compile_expression(code, "Only expressions can be timed")?;
+        timeit::time_program(&program, self.plutus_version, loops, repeats)
+    }
+
+    /// Profile where the budget of an expression goes, by named function and builtin
+    pub fn profile(&mut self, code: &str) -> Result<Profile, ReplError> {
+        let program = self.compile_expression(code, "Only expressions can be profiled")?;
+        let source_map = self.source_map(&program.term);
+        profile::profile_program(&program, self.plutus_version, &source_map)
+    }
+
+    /// Profile an expression for a flamegraph, writing its collapsed stacks to the output of
+    /// `args` if any
+    pub fn flamegraph(&mut self, args: &FlamegraphArgs) -> Result<Profile, ReplError> {
+        let profile = self.profile(args.code)?;
+        if let Some(output) = args.output {
+            profile.write_folded(Path::new(output))?;
+        }
+        Ok(profile)
+    }
+
+    /// Check the whole session as `aiken check` would: type-check every definition and run the
+    /// tests, reporting all the errors and warnings rather than the first error and new warnings
+    pub fn check(&mut self) -> Result<CheckReport, ReplError> {
+        let seed = self.test_seed(None)?;
+        let source = self.context.source();
+        let mut project = self.write_temp_project(source, export::dependencies(source))?;
+        self.progress.take_tests();
+        let checked = project.check(
+            false, // skip_tests
+            None,  // match_tests
+            false, // verbose
+            false, // exact_match
+            seed,
+            fuzz::DEFAULT_MAX_SUCCESS,
+            self.progress.coverage,
+            Tracing::All(TraceLevel::Verbose),
+            None,  // env
+            false, // plain_numbers
+        );
+
+        // The seed only matters to property tests
+        let tests = self.progress.take_tests();
+        let property_tests = tests.iter().any(|test| test.iterations.is_some());
+
+        Ok(CheckReport {
+            definitions: self.context.keys().count(),
+            seed: property_tests.then_some(seed),
+            labelled: tests
+                .into_iter()
+                .filter(|test| !test.coverage.is_empty())
+                .collect(),
+            errors: checked
+                .err()
+                .unwrap_or_default()
+                .iter()
+                .map(|error| warnings::render(error))
+                .collect(),
+            warnings: project
+                .warnings()
+                .iter()
+                .map(|warning| warnings::render(warning))
+                .collect(),
+        })
+    }
+
+    /// Run `args.function` against a property test synthesized from `args`, drawing inputs from
+    /// its fuzzer until `args.max_success` of them pass or one fails and is shrunk
+    pub fn fuzz(&mut self, args: &FuzzArgs) -> Result<TestReport, ReplError> {
+        let eval_count = self.eval_counter.fetch_add(1, Ordering::Relaxed);
+        let test_name = format!("repl_fuzz_{}", eval_count);
+        let module_code = format!("{}\n\n{}", self.context.source(), args.test(&test_name));
+        let seed = self.test_seed(args.seed)?;
+
+        let mut project =
+            self.write_temp_project(&module_code, export::dependencies(&module_code))?;
+        // Outcomes of earlier runs, if any, aren't ours
+        self.progress.take_tests();
+        let checked = project.check(
+            false,                                                          // skip_tests
+            Some(vec![format!("{}.{{{}}}", self.names.module, test_name)]), // match_tests
+            false,                                                          // verbose
+            true,                                                           // exact_match
+            seed,
+            args.max_success,
+            self.progress.coverage,
+            Tracing::All(self.trace_level),
+            None,  // env
+            false, // plain_numbers
+        );
+
+        let mut tests = self.progress.take_tests();
+        if tests.is_empty() {
+            // The test didn't run: the fuzzer or the property don't type-check
+            return Err(
+                match checked.err().and_then(|errors| errors.into_iter().next()) {
+                    Some(error) => self.session_error(error, &module_code),
+                    None => ReplError::EvaluationFailed {
+                        message: "The property test didn't run".to_string(),
+                    },
+                },
+            );
+        }
+        for test in &mut tests {
+            test.name = args.to_string();
+        }
+        Ok(TestReport {
+            tests,
+            seed: Some(seed),
+        })
+    }
+
+    /// Compile an expression to UPLC, with the definitions of the session its functions come from
+    pub fn compile_to_uplc(&mut self, code: &str) -> Result<UplcDump, ReplError> {
+        let program = self.compile_expression(code, "Only expressions can be compiled")?;
+        Ok(UplcDump {
+            source_map: self.source_map(&program.term),
+            program: program.to_pretty(),
+        })
+    }
+
+    /// Map the functions bound in `term` to the function definitions of the session
+    fn source_map(&self, term: &Term<NamedDeBruijn>) -> SourceMap {
+        let functions: Vec<(&str, usize)> = self
+            .context
+            .lines()
+            .filter_map(|(key, line)| match key {
+                DefinitionKey::Definition(DefinitionKind::Function, name) => {
+                    Some((name.as_str(), line))
+                }
+                _ => None,
+            })
+            .collect();
+        SourceMap::new(term, &self.names.module, &functions)
+    }
+
+    /// Compile an expression and serialize its UPLC program, to see its size as a script
+    pub fn cbor(&mut self, code: &str) -> Result<ScriptCbor, ReplError> {
+        let program = self.compile_expression(code, "Only expressions can be encoded")?;
+        cbor::encode_program(program)
+    }
+
+    /// Compile an expression to UPLC without evaluating it. `not_expression` is the error
+    /// message for code that isn't an expression.
+    fn compile_expression(
+        &mut self,
+        code: &str,
+        not_expression: &str,
+    ) -> Result<Program<NamedDeBruijn>, ReplError> {
+        if !looks_like_expression(code) {
+            return Err(ReplError::ParseFailed {
+                message: not_expression.to_string(),
+            });
+        }
+
+        let eval_count = self.eval_counter.fetch_add(1, Ordering::Relaxed);
+        let eval_fn_name = format!("repl_eval_{}", eval_count);
+        let wrapped_code = format!("pub fn {}() {{ {} }}", eval_fn_name, code);
+        let module_code = format!("{}\n\n{}", self.context.source(), wrapped_code);
+
+        let mut project = self.create_temp_project(&module_code)?;
+        let (repl_module, eval_fn) = find_eval_fn(&project, &self.names.module, &eval_fn_name)?;
+        self.generate_program(&mut project, &repl_module.name, &eval_fn)
+    }
+
+    /// Evaluate a program written in untyped Plutus Core, with the session's Plutus version.
+    /// Definitions of the session are not in scope.
+    pub fn eval_uplc(&self, source: &str) -> Result<UplcResult, ReplError> {
+        untyped::eval_program(source, self.plutus_version, &self.display_settings)
+    }
+
+    /// Evaluate the wrapper function `eval_fn_name` of an already checked project
+    fn eval_wrapped(
+        &self,
+        project: &mut Project<events::Progress>,
+        eval_fn_name: &str,
+    ) -> Result<EvaluationResult, ReplError> {
+        let (repl_module, eval_fn) = find_eval_fn(project, &self.names.module, eval_fn_name)?;
+        self.eval_function(project, &repl_module.name, &eval_fn)
+    }
+
+    /// Evaluate a typed wrapper function of `module_name`, against the functions of `project`
+    fn eval_function(
+        &self,
+        project: &mut Project<events::Progress>,
+        module_name: &str,
+        eval_fn: &TypedFunction,
+    ) -> Result<EvaluationResult, ReplError> {
+        // Functions have no value to show but their signature, and needn't be compiled
+        if let Some(signature) = reify::function(&eval_fn.return_type) {
+            return Ok(EvaluationResult::Value {
+                value: signature,
+                tipo: eval_fn.return_type.clone(),
+                uplc_result: None,
+                budget: ExBudget { mem: 0, cpu: 0 },
+                traces: Vec::new(),
+            });
+        }
+
+        // Generate UPLC and evaluate
+        let eval_result = self.generate_and_eval(project, module_name, eval_fn)?;
+
+        // Extract and format the result
+        let budget = eval_result.cost();
+        let traces = eval_result.logs();
+        match eval_result.result {
+            Ok(term) => {
+                // Show the value in Aiken syntax when its type allows, as UPLC otherwise. Byte
+                // arrays follow the display settings, eg. string literals are shown as text.
+                let is_bytes = matches!(
+                    &term,
+                    Term::Constant(c) if matches!(c.as_ref(), Constant::ByteString(_))
+                );
+                // Lists are cut before being decoded, giant ones would take long to format
+                let settings = &self.display_settings;
+                let reified = match &term {
+                    Term::Constant(c) if !is_bytes => {
+                        let (short, hidden) = pretty::shorten(c, settings.max_width);
+                        let short = Term::Constant(Rc::new(short));
+                        reify::value(project, &short, eval_fn.return_type.clone()).map(|value| {
+                            let value = pretty::truncate(value, settings.max_chars);
+                            match hidden {
+                                0 => value,
+                                _ => format!("{}\n{}", value, pretty::more_items(hidden)),
+                            }
+                        })
+                    }
+                    _ => None,
+                };
+                let value_str = reified.unwrap_or_else(|| pretty::term(&term, settings));
+                Ok(EvaluationResult::Value {
+                    value: value_str,
+                    tipo: eval_fn.return_type.clone(),
+                    uplc_result: self.extract_constant(&term),
+                    budget,
+                    traces,
+                })
+            }
+            Err(err) => Err(ReplError::EvaluationFailed {
+                message: format!("Evaluation failed: {:?}", err),
+            }),
+        }
+    }
+
+    /// Evaluate code as module definitions
+    fn eval_definitions(&mut self, code: &str) -> Result<EvaluationResult, ReplError> {
+        let provenance = Provenance::new(self.origin.as_deref(), code);
+        // Keep the context readable by storing formatted definitions
+        let formatted;
+        let code = if self.auto_format {
+            formatted = format::format_module(code)?;
+            formatted.as_str()
+        } else {
+            code
+        };
+
+        let added = self.split_definitions(code)?;
+        if let Some(key) = context::duplicate(&added) {
+            return Err(ReplError::DuplicateDefinition {
+                definition: key.to_string(),
+            });
+        }
+        let redefined = added.iter().any(|(key, _)| {
+            matches!(key, DefinitionKey::Definition(DefinitionKind::Function, _))
+                && self.context.keys().any(|existing| existing == key)
+        });
+        // Replacing a definition of another kind may well be a mistake
+        let notes: Vec<String> = added
+            .iter()
+            .filter_map(|(key, _)| Some((key, self.context.shadowed_by(key)?)))
+            .map(|(key, shadowed)| {
+                let origin = shadowed
+                    .provenance
+                    .origin
+                    .as_ref()
+                    .map(|origin| format!(" in {}", origin))
+                    .unwrap_or_default();
+                format!(
+                    "note: {} was previously defined{}; redefining it as {}",
+                    shadowed.key, origin, key
+                )
+            })
+            .collect();
+        // As are changes that leave an earlier input defining only part of what it showed
+        let keys: Vec<DefinitionKey> = added.iter().map(|(key, _)| key.clone()).collect();
+        let stale_notes = self
+            .context
+            .stale_inputs(&keys, self.origin.as_deref())
+            .into_iter()
+            .map(|stale| {
+                format!(
+                    "note: {} from {} redefined; {} still defines {}",
+                    join_keys(&stale.replaced),
+                    stale.origin,
+                    stale.origin,
+                    join_keys(&stale.kept)
+                )
+            });
+        let notes: Vec<String> = notes.into_iter().chain(stale_notes).collect();
+
+        // Type check the new definitions on top of the others, replacing those they redefine
+        let mut context = self.context.clone();
+        context.add(added.clone(), &provenance);
+        let project = self.create_temp_project(context.source())?;
+        self.context = context;
+        for note in notes {
+            self.warnings.get_mut().note(note);
+        }
+
+        self.definitions_changed(redefined);
+
+        // Extract what was actually defined for better feedback
+        let defined_items: Vec<_> = added
+            .into_iter()
+            .filter_map(|(key, _)| match key {
+                DefinitionKey::Definition(kind, name) => Some((name, kind)),
+                _ => None,
+            })
+            .collect();
+
+        // With the types the compiler inferred for them
+        let repl_module = project
+            .modules()
+            .into_iter()
+            .find(|m| m.name == self.names.module);
+        match defined_items.len() {
+            0 => Ok(EvaluationResult::NoResult),
+            1 => {
+                let (name, kind) = defined_items.into_iter().next().unwrap();
+                let tipo = repl_module
+                    .as_ref()
+                    .and_then(|module| introspect::value_type(module, &name));
+                Ok(EvaluationResult::Definition { name, kind, tipo })
+            }
+            _ => {
+                let infos = repl_module
+                    .map(|module| introspect::definitions(&module))
+                    .unwrap_or_default();
+                let definitions = defined_items
+                    .into_iter()
+                    .map(|(name, kind)| {
+                        infos
+                            .iter()
+                            .find(|info| info.name == name && info.kind == kind)
+                            .cloned()
+                            .unwrap_or(DefinitionInfo {
+                                name,
+                                kind,
+                                tipo: String::new(),
+                                value: None,
+                                origin: None,
+                            })
+                    })
+                    .collect();
+                Ok(EvaluationResult::Definitions(definitions))
+            }
+        }
+    }
+
+    /// Split code into definitions. Code that doesn't parse is reported by the compiler, which
+    /// points at the faulty code.
+    fn split_definitions(&self, code: &str) -> Result<Vec<(DefinitionKey, String)>, ReplError> {
+        let added = context::split(code).or_else(|error| {
+            self.create_temp_project(&format!("{}\n\n{}", self.context.source(), code))?;
+            Err(error)
+        })?;
+        self.check_imports(&added)?;
+        Ok(added)
+    }
+
+    /// Refuse imports of the session's own module
+    fn check_imports(&self, added: &[(DefinitionKey, String)]) -> Result<(), ReplError> {
+        match reserved_import(&self.names, added.iter().map(|(key, _)| key)) {
+            Some(module) => Err(ReplError::ReservedModule {
+                module: module.to_string(),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// `error` of the temp project, against the session's code when it's in the session's
+    /// module: its path in the temp project means nothing to users
+    fn session_error(&self, error: ProjectError, module_code: &str) -> ReplError {
+        let module = module_path(Path::new("lib"), &self.names.module);
+        match &error {
+            ProjectError::Type { path, .. } | ProjectError::Parse { path, .. }
+                if path.ends_with(&module) =>
+            {
+                ReplError::SessionError {
+                    error,
+                    session: NamedSource::new(names::SESSION_SOURCE, module_code.to_string()),
+                }
+            }
+            _ => ReplError::ProjectError(error),
+        }
+    }
+
+    /// Drop what was computed against the previous definitions
+    fn definitions_changed(&mut self, redefined: bool) {
+        // Cached results and the kept project were computed against the old definitions
+        if let Some(cache) = &mut self.cache {
+            cache.clear();
+        }
+        self.incremental.clear();
+        self.remeasure_probe(redefined);
+    }
+
+    /// Measure the probe against new definitions, keeping the previous measure to compare. The
+    /// report only matters when functions were redefined.
+    fn remeasure_probe(&mut self, report: bool) {
+        if let Some(mut probe) = self.probe.take() {
+            let after = self
+                .compile_expression(&probe.code, "Only expressions can be probes")
+                .and_then(|program| probe::measure(program, self.plutus_version))
+                .ok();
+            let before = std::mem::replace(&mut probe.last, after);
+            if report {
+                self.redefinition = Some(RedefinitionReport {
+                    probe: probe.code.clone(),
+                    before,
+                    after,
+                });
+            }
+            self.probe = Some(probe);
+        }
+    }
+
+    /// Create a well-typed temporary project for compilation and evaluation
+    fn create_temp_project(
+        &self,
+        module_code: &str,
+    ) -> Result<Project<events::Progress>, ReplError> {
+        let project = self.write_temp_project(module_code, export::dependencies(module_code))?;
+        self.check_temp_project(project, module_code)
+    }
+
+    /// Type-check the temporary project written with `module_code`
+    fn check_temp_project(
+        &self,
+        mut project: Project<events::Progress>,
+        module_code: &str,
+    ) -> Result<Project<events::Progress>, ReplError> {
+        // Type-check the whole project
+        let checked = project.check(
+            true,  // skip_tests
+            None,  // match_tests
+            false, // verbose
+            false, // exact_match
+            0,     // seed
+            100,   // property_max_success
+            CoverageMode::default(),
+            Tracing::All(self.trace_level),
+            None,  // env
+            false, // plain_numbers
+        );
+
+        // Code that doesn't type-check can still have warnings worth showing
+        self.warnings.borrow_mut().collect(project.warnings());
+
+        if let Err(errors) = checked {
+            // Missing patterns come first, they're worth pointing out on their own
+            let non_exhaustive = errors
+                .iter()
+                .position(|error| patterns::missing_patterns(error).is_some());
+            if let Some(index) = non_exhaustive {
+                let error = errors.into_iter().nth(index).unwrap();
+                let (missing, offset) = patterns::missing_patterns(&error).unwrap();
+                return Err(ReplError::NonExhaustive {
+                    definition: patterns::enclosing_definition(module_code, offset),
+                    missing: missing.to_vec(),
+                    error,
+                });
+            }
+            // Convert the first error to our error type
+            if let Some(first_error) = errors.into_iter().next() {
+                return Err(self.session_error(first_error, module_code));
+            }
+        }
+
+        if let Some(cache) = &self.artifacts {
+            let stored = fs::read_to_string(self.temp_dir.path().join("aiken.toml")).and_then(
+                |aiken_toml| cache.store(&aiken_toml, &gc::build_dir(self.temp_dir.path())),
+            );
+            if let Err(e) = stored {
+                eprintln!("Failed to save build artifacts: {}", e);
+            }
+        }
+
+        Ok(project)
+    }
+
+    /// Seed of the next property tests: `seed` if given, else the one of the session, else a
+    /// random one, unless outputs are deterministic
+    fn test_seed(&self, seed: Option<u32>) -> Result<u32, ReplError> {
+        match seed.or(self.seed) {
+            Some(seed) => Ok(seed),
+            None if self.deterministic => Ok(DETERMINISTIC_SEED),
+            None => getrandom::u32().map_err(|e| ReplError::EvaluationFailed {
+                message: format!("Failed to pick a seed: {}", e),
+            }),
+        }
+    }
+
+    /// Write `module_code` to the temporary project with `dependencies` in its `aiken.toml`,
+    /// without checking it
+    fn write_temp_project(
+        &self,
+        module_code: &str,
+        dependencies: &str,
+    ) -> Result<Project<events::Progress>, ReplError> {
+        // Create temporary aiken.toml
+        let aiken_toml = format!(
+            "name = \"{}\"\nversion = \"0.0.0\"\nplutus = \"{}\"\n{}",
+            self.names.project,
+            export::plutus(self.plutus_version),
+            dependencies
+        );
+
+        // The directory may have been removed by `cleanup`
+        fs::create_dir_all(self.temp_dir.path())?;
+
+        let aiken_toml_path = self.temp_dir.path().join("aiken.toml");
+        fs::write(&aiken_toml_path, &aiken_toml)?;
+
+        // A new or collected project starts from the artifacts of earlier sessions
+        if let Some(cache) = &self.artifacts
+            && let Err(e) = cache.restore(&aiken_toml, &gc::build_dir(self.temp_dir.path()))
+        {
+            eprintln!("Failed to restore build artifacts: {}", e);
+        }
+
+        // Create lib directory
+        let lib_dir = self.temp_dir.path().join("lib");
+        fs::create_dir_all(&lib_dir)?;
+
+        // Write module to lib/repl.ak, next to the prelude in lib/repl/prelude.ak
+        fs::write(module_path(&lib_dir, &self.names.module), module_code)?;
+        let prelude = module_path(&lib_dir, PRELUDE_MODULE);
+        if let Some(prelude_dir) = prelude.parent() {
+            fs::create_dir_all(prelude_dir)?;
+        }
+        fs::write(prelude, PRELUDE)?;
+
+        // Load project config
+        let config = ProjectConfig::load(self.temp_dir.path())?;
+
+        Ok(Project::new_with_config(
+            config,
+            self.temp_dir.path().to_path_buf(),
+            self.progress.clone(),
+        ))
+    }
+
+    /// Generate and evaluate UPLC
+    fn generate_and_eval(
+        &self,
+        project: &mut Project<events::Progress>,
+        module_name: &str,
+        eval_fn: &aiken_lang::ast::TypedFunction,
+    ) -> Result<EvalResult, ReplError> {
+        let named_program = self.generate_program(project, module_name, eval_fn)?;
+
+        // Evaluate Program
+        self.progress.clock.enter(Phase::Eval);
+        let result = named_program.eval_version(self.budget, &self.plutus_version.into());
+
+        Ok(result)
+    }
+
+    /// Generate the UPLC program of a wrapper function
+    fn generate_program(
+        &self,
+        project: &mut Project<events::Progress>,
+        module_name: &str,
+        eval_fn: &aiken_lang::ast::TypedFunction,
+    ) -> Result<Program<NamedDeBruijn>, ReplError> {
+        // The code generator expects the types it compiles to be known
+        if generics::has_type_variables(&eval_fn.return_type) {
+            let mut printer = Printer::new();
+            return Err(ReplError::AmbiguousType {
+                tipo: printer.pretty_print(&eval_fn.return_type, 0),
+                defaulted: printer
+                    .pretty_print(&generics::default_to_data(&eval_fn.return_type), 0),
+            });
+        }
+
+        // Init a new code generator
+        self.progress.clock.enter(Phase::Codegen);
+        let mut generator = project.new_generator(Tracing::All(self.trace_level));
+
+        // Generate UPLC for the function
+        let program = generator.generate_raw(&eval_fn.body, &[], module_name);
+
+        // Convert to NamedDeBruijn
+        let named_program = Program::<NamedDeBruijn>::try_from(program).map_err(|err| {
+            ReplError::EvaluationFailed {
+                message: format!("Failed to convert to NamedDeBruijn: {:?}", err),
+            }
+        })?;
+
+        Ok(named_program)
+    }
+
+    /// Extract a constant from a term if possible
+    fn extract_constant(&self, term: &Term<NamedDeBruijn>) -> Option<Constant> {
+        match term {
+            Term::Constant(c) => Some(c.as_ref().clone()),
+            _ => None,
+        }
+    }
+}
+
+/// Value of an option that turns something `on` or `off`
+fn switch(value: &str) -> Result<bool, ReplError> {
+    match value {
+        "on" => Ok(true),
+        "off" => Ok(false),
+        _ => Err(ReplError::InvalidSetting {
+            message: "expected on or off".to_string(),
+        }),
+    }
+}
+
+fn on_off(enabled: bool) -> &'static str {
+    if enabled { "on" } else { "off" }
+}
+
+/// The first of `imports` that would import the session's module, if any
+fn reserved_import<'a>(
+    names: &names::Names,
+    imports: impl IntoIterator<Item = &'a DefinitionKey>,
+) -> Option<&'a str> {
+    imports.into_iter().find_map(|key| match key {
+        DefinitionKey::Import(module) if names.is_reserved(module) => Some(module.as_str()),
+        _ => None,
+    })
+}
+
+/// Find the wrapper function `eval_fn_name` in module `module` of a checked project
+fn find_eval_fn(
+    project: &Project<events::Progress>,
+    module: &str,
+    eval_fn_name: &str,
+) -> Result<(CheckedModule, TypedFunction), ReplError> {
+    // Find the REPL module
+    let repl_module = project
+        .modules()
+        .into_iter()
+        .find(|m| m.name == module)
+        .ok_or_else(|| ReplError::EvaluationFailed {
+            message: "Could not find repl module".to_string(),
+        })?;
+
+    // Find the evaluation function
+    let eval_fn = repl_module
+        .ast
+        .definitions()
+        .find_map(|def| match def {
+            Definition::Fn(f) if f.name == eval_fn_name => Some(f.clone()),
+            _ => None,
+        })
+        .ok_or_else(|| ReplError::EvaluationFailed {
+            message: format!(
+                "Could not find evaluation function {}. This should never happen.",
+                eval_fn_name
+            ),
+        })?;
+
+    Ok((repl_module, eval_fn))
+}
+
+/// `code` with Windows line endings replaced by `\n`. Definitions are tracked by their lines
+/// and offsets in the module, which counts a line end as one byte.
+fn normalize_newlines(code: &str) -> Cow<'_, str> {
+    if code.contains("\r\n") {
+        Cow::Owned(code.replace("\r\n", "\n"))
+    } else {
+        Cow::Borrowed(code)
+    }
+}
+
+/// Path of `module` (eg. `repl/prelude`) in the `lib` directory of a project, with the path
+/// separators of the platform
+fn module_path(lib: &Path, module: &str) -> PathBuf {
+    let mut path = module
+        .split('/')
+        .fold(lib.to_path_buf(), |path, part| path.join(part));
+    path.set_extension("ak");
+    path
+}
+
+/// Check if the code is an expression (or a sequence of them) rather than definitions, by
+/// parsing it as a module, then as the body of a function. Code that is neither is told apart
+/// by its first keyword, so it gets the errors of what it was meant to be.
+fn looks_like_expression(code: &str) -> bool {
+    if parses_as_module(code) {
+        return false;
+    }
+    if parses_as_expression(code) {
+        return true;
+    }
+
+    let def_keywords = [
+        "fn ",
+        "pub fn",
+        "type ",
+        "pub type",
+        "opaque type",
+        "pub opaque type",
+        "const ",
+        "pub const",
+        "use ",
+        "test ",
+        "validator",
+    ];
+    let trimmed = code.trim();
+    !def_keywords
+        .iter()
+        .any(|keyword| trimmed.starts_with(keyword))
+}
+
+fn parses_as_module(code: &str) -> bool {
+    parser::module(code, ModuleKind::Lib).is_ok()
+}
+
+fn parses_as_expression(code: &str) -> bool {
+    let wrapped = format!("fn {}() {{\n{}\n}}", PARSE_WRAPPER, code);
+    parser::module(&wrapped, ModuleKind::Lib).is_ok()
+}
+
+/// Split code made of definitions followed by an expression, eg. a function and a call to it,
+/// at the line where the expression starts. None unless both parts parse.
+fn split_trailing_expression(code: &str) -> Option<(&str, &str)> {
+    let line_starts: Vec<usize> = code.match_indices('\n').map(|(i, _)| i + 1).collect();
+    // Expressions are usually short, so their start is looked for from the end
+    line_starts.into_iter().rev().find_map(|start| {
+        let (definitions, expression) = code.split_at(start);
+        let parses = !definitions.trim().is_empty()
+            && !expression.trim().is_empty()
+            && parses_as_module(definitions)
+            && parses_as_expression(expression);
+        parses.then_some((definitions, expression))
+    })
+}
+
+/// Content hash of the accumulated definitions, used to key cached results
+fn hash_definitions(definitions: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    definitions.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// `function f, constant x`
+fn join_keys(keys: &[DefinitionKey]) -> String {
+    keys.iter()
+        .map(|key| key.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod test {
+    use std::{fs, path::Path};
+
+    use aiken_lang::tipo::pretty::Printer;
+
+    use crate::evaluator::{
+        ArtifactCache, DETERMINISTIC_SEED, DefinitionKind, DisplaySettings, EvaluationResult,
+        FuzzArgs, MockChain, ReplError, ReplEvaluator, artifacts, looks_like_expression,
+        module_path, split_trailing_expression,
+    };
+
+    #[test]
+    fn test_simple_expression() {
+        let mut repl = ReplEvaluator::new();
+
+        // Test simple boolean expression
+        let result = repl.eval("True");
+        assert!(result.is_ok());
+
+        if let Ok(EvaluationResult::Value { value, .. }) = result {
+            assert_eq!(value, "True");
+        } else {
+            panic!("Expected value result, got: {:?}", result);
+        }
+    }
+
+    #[test]
+    fn test_arithmetic_expression() {
+        let mut repl = ReplEvaluator::new();
+
+        // Test simple arithmetic
+        let result = repl.eval("1 + 2");
+        assert!(result.is_ok());
+
+        if let Ok(EvaluationResult::Value { value, .. }) = result {
+            assert_eq!(value, "3");
+        } else {
+            panic!("Expected value result, got: {:?}", result);
+        }
+    }
+
+    #[test]
+    fn test_aiken_values() {
+        let mut repl = ReplEvaluator::new();
+        let value = |repl: &mut ReplEvaluator, code: &str| match repl.eval(code) {
+            Ok(EvaluationResult::Value { value, .. }) => value,
+            other => panic!("Expected value result, got {:?}", other),
+        };
+
+        assert_eq!(value(&mut repl, "Some(42)"), "Some(42)");
+        assert_eq!(value(&mut repl, "[True, False]"), "[True, False]");
+
+        assert!(repl.eval("pub type Point { x: Int, y: Int }").is_ok());
+        assert_eq!(
+            value(&mut repl, "Point { x: 1, y: 2 }"),
+            "Point { x: 1, y: 2 }"
+        );
+    }
+
+    #[test]
+    fn test_set_option() {
+        let mut repl = ReplEvaluator::new();
+        let value = |repl: &mut ReplEvaluator| match repl.eval("\"hello\"") {
+            Ok(EvaluationResult::Value { value, .. }) => value,
+            other => panic!("Expected value result, got {:?}", other),
+        };
+
+        assert_eq!(value(&mut repl), "#68656c6c6f (\"hello\")");
+        assert!(repl.set_option("bytearray-display", "utf8").is_ok());
+        assert_eq!(value(&mut repl), "\"hello\"");
+        assert!(repl.set_option("bytearray-display", "hex").is_ok());
+        assert_eq!(value(&mut repl), "#68656c6c6f");
+
+        assert!(repl.set_option("max-depth", "3").is_ok());
+        assert_eq!(repl.display_settings().max_depth, 3);
+        assert!(repl.set_option("bytearray-display", "base64").is_err());
+        assert!(repl.set_option("max-width", "many").is_err());
+        assert!(repl.set_option("colour", "on").is_err());
+
+        assert!(repl.options().contains("\nseed = random\n"));
+        assert!(repl.set_option("seed", "1234").is_ok());
+        assert!(repl.options().contains("\nseed = 1234\n"));
+        assert!(repl.set_option("seed", "-1").is_err());
+        assert!(repl.set_option("seed", "random").is_ok());
+
+        assert!(
+            repl.set_option("coverage-mode", "relative-to-labels")
+                .is_ok()
+        );
+        assert!(
+            repl.options()
+                .contains("\ncoverage-mode = relative-to-labels\n")
+        );
+        assert!(repl.set_option("coverage-mode", "labels").is_err());
+
+        assert!(
+            repl.options()
+                .contains("\nwarnings = on\ncache = off\nauto-format = off\n")
+        );
+        assert!(repl.set_option("cache", "on").is_ok());
+        assert!(repl.set_option("auto-format", "on").is_ok());
+        assert!(repl.set_option("warnings", "off").is_ok());
+        assert!(
+            repl.options()
+                .contains("\nwarnings = off\ncache = on\nauto-format = on\n")
+        );
+        assert!(repl.set_option("cache", "yes").is_err());
+
+        assert!(repl.set_option("deterministic", "on").is_ok());
+        assert!(repl.options().contains("\ndeterministic = on\n"));
+        assert_eq!(repl.test_seed(None).ok(), Some(DETERMINISTIC_SEED));
+        let module = repl.temp_dir.path().join("lib").join("repl.ak");
+        assert_eq!(
+            repl.without_paths(&format!("in {}", module.display())),
+            format!(
+                "in {}",
+                Path::new("<session>").join("lib").join("repl.ak").display()
+            )
+        );
+        assert!(repl.set_option("deterministic", "off").is_ok());
+
+        let options = |options: &[(&str, &str)]| -> Vec<(String, String)> {
+            options
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect()
+        };
+        let previous = repl
+            .set_options(&options(&[("max-depth", "2"), ("trace-level", "verbose")]))
+            .unwrap();
+        assert!(repl.options().contains("\ntrace-level = verbose\n"));
+        assert!(repl.set_options(&previous).is_ok());
+        assert!(repl.options().contains("\ntrace-level = compact\n"));
+        // Nothing changes when one of them is invalid
+        assert!(
+            repl.set_options(&options(&[("max-depth", "2"), ("max-width", "many")]))
+                .is_err()
+        );
+        assert!(!repl.options().contains("\nmax-depth = 2\n"));
+
+        assert!(repl.options().ends_with("\nbackend = in-process"));
+        assert!(repl.set_option("backend", "builtin").is_ok());
+        assert!(repl.set_option("backend", "0.0.0-missing").is_err());
+    }
+
+    #[test]
+    fn test_module_names() {
+        let mut repl = ReplEvaluator::new();
+        // The session's own module can't be imported
+        assert!(matches!(
+            repl.eval("use repl"),
+            Err(ReplError::ReservedModule { module }) if module == "repl"
+        ));
+        assert!(matches!(
+            repl.eval("use repl/something"),
+            Err(ReplError::ReservedModule { .. })
+        ));
+        // Errors are shown against the session's code, not the temp project's module
+        match repl.eval("1 + True") {
+            Err(ReplError::SessionError { session, .. }) => assert_eq!(session.name(), "session"),
+            other => panic!("Expected an error of the session, got {:?}", other),
+        }
+
+        assert!(repl.eval("pub fn double(n: Int) -> Int { n * 2 }").is_ok());
+        assert!(repl.set_option("module-name", "scratch").is_ok());
+        assert!(repl.set_option("project-name", "me/scratch").is_ok());
+        assert!(
+            repl.options()
+                .contains("\nproject-name = me/scratch\nmodule-name = scratch\n")
+        );
+        match repl.eval("double(21)") {
+            Ok(EvaluationResult::Value { value, .. }) => assert_eq!(value, "42"),
+            other => panic!("Expected value result, got {:?}", other),
+        }
+        assert!(matches!(
+            repl.eval("use scratch"),
+            Err(ReplError::ReservedModule { .. })
+        ));
+        assert!(repl.eval("use repl/prelude").is_ok());
+        assert!(repl.set_option("module-name", "repl/prelude").is_err());
+        assert!(repl.set_option("module-name", "Scratch").is_err());
+    }
+
+    #[test]
+    fn test_output_limits() {
+        let mut repl = ReplEvaluator::new();
+        repl.set_cache(true);
+        let items: Vec<String> = (1..=40).map(|i| i.to_string()).collect();
+        let code = format!("[{}]", items.join(", "));
+        let value = |result| match result {
+            Ok(EvaluationResult::Value { value, .. } | EvaluationResult::Cached { value, .. }) => {
+                value
+            }
+            other => panic!("Expected value result, got {:?}", other),
+        };
+
+        let short = value(repl.eval(&code));
+        assert!(short.contains("32"), "{short}");
+        assert!(!short.contains("33"), "{short}");
+        assert!(short.ends_with("… 8 more items"), "{short}");
+
+        let full = value(repl.eval_full(&code));
+        assert!(
+            full.contains("40") && !full.contains("more items"),
+            "{full}"
+        );
+        // The full result isn't served from the cache afterwards
+        assert_eq!(value(repl.eval(&code)), short);
+
+        assert!(repl.set_option("max-chars", "5").is_ok());
+        assert!(value(repl.eval("\"hello world\"")).ends_with("more characters"));
+    }
+
+    #[test]
+    fn test_warm_up() {
+        let mut repl = ReplEvaluator::new();
+        assert!(repl.warm_up().is_ok());
+        assert_eq!(repl.context.source(), ReplEvaluator::new().context.source());
+        assert!(matches!(
+            repl.eval("1 + 2"),
+            Ok(EvaluationResult::Value { .. })
+        ));
+    }
+
+    #[test]
+    fn test_incremental_evaluation() {
+        let mut repl = ReplEvaluator::new();
+        assert!(repl.eval("pub fn double(n: Int) -> Int { n * 2 }").is_ok());
+        // The first expression checks the project, the next ones are checked on their own
+        for (code, expected) in [
+            ("double(2)", "4"),
+            ("double(21)", "42"),
+            ("[double(1)]", "[2]"),
+        ] {
+            match repl.eval(code) {
+                Ok(EvaluationResult::Value { value, .. }) => assert_eq!(value, expected),
+                other => panic!("Expected value result, got {:?}", other),
+            }
+        }
+        // Type errors are reported by a full check
+        assert!(matches!(
+            repl.eval("double(True)"),
+            Err(ReplError::SessionError { .. })
+        ));
+
+        // So are expressions against new definitions
+        assert!(
+            repl.eval("pub fn double(n: Int) -> Int { n + n + 1 }")
+                .is_ok()
+        );
+        assert!(matches!(
+            repl.eval("double(2)"),
+            Ok(EvaluationResult::Value { value, .. }) if value == "5"
+        ));
+    }
+
+    #[test]
+    fn test_evaluation_options() {
+        let mut repl = ReplEvaluator::new();
+        assert!(repl.set_option("plutus-version", "v2").is_ok());
+        assert!(repl.set_option("trace-level", "verbose").is_ok());
+        assert!(repl.set_option("budget-mem", "100").is_ok());
+        assert!(
+            repl.options()
+                .contains("plutus-version = v2\ntrace-level = verbose\n")
+        );
+        assert!(repl.options().ends_with("budget-mem = 100"));
+        // Doesn't fit in the budget
+        assert!(!matches!(
+            repl.eval("1 + 1"),
+            Ok(EvaluationResult::Value { .. })
+        ));
+
+        assert!(repl.set_option("plutus-version", "v4").is_err());
+        assert!(repl.set_option("trace-level", "all").is_err());
+        assert!(repl.set_option("budget-cpu", "0").is_err());
+    }
+
+    #[test]
+    fn test_expression_detection() {
+        // These should be detected as expressions
+        assert!(looks_like_expression("1 + 2"));
+        assert!(looks_like_expression("True"));
+        assert!(looks_like_expression("\"hello\""));
+
+        // These should be detected as definitions
+        assert!(!looks_like_expression("fn add(x, y) { x + y }"));
+        assert!(!looks_like_expression("pub const X = 42"));
+        assert!(!looks_like_expression("type Option<a> { Some(a) | None }"));
+
+        // Keywords in strings or names don't make definitions, and comments don't hide them
+        assert!(looks_like_expression(
+            "when x is {\n  None -> \"no type \"\n  Some(_) -> \"fn \"\n}"
+        ));
+        assert!(looks_like_expression("let use_count = 1\nuse_count + 1"));
+        assert!(!looks_like_expression("// Doubles\nfn double(n) { n * 2 }"));
+        assert!(!looks_like_expression("/// Answer\npub const answer = 42"));
+
+        // Code that doesn't parse goes by its first keyword
+        assert!(looks_like_expression("1 +"));
+        assert!(!looks_like_expression("fn broken("));
+    }
+
+    #[test]
+    fn test_mixed_code() {
+        assert_eq!(
+            split_trailing_expression("fn double(n) { n * 2 }\n\nlet x = double(3)\nx + 1"),
+            Some(("fn double(n) { n * 2 }\n\n", "let x = double(3)\nx + 1"))
+        );
+        assert_eq!(split_trailing_expression("fn double(n) { n * 2 }"), None);
+        assert_eq!(split_trailing_expression("1 + 2\nfn f() { 1 }"), None);
+
+        let mut repl = ReplEvaluator::new();
+        let result = repl.eval("pub fn double(n: Int) -> Int { n * 2 }\ndouble(21)");
+        match result {
+            Ok(mixed @ EvaluationResult::Mixed { .. }) => {
+                let summary = mixed.summary();
+                assert_eq!(summary.kind, "value");
+                assert_eq!(summary.tipo.as_deref(), Some("Int"));
+                assert!(summary.budget.is_some_and(|budget| budget.cpu > 0));
+                assert_eq!(summary.definitions, ["double"]);
+
+                let EvaluationResult::Mixed {
+                    definitions,
+                    result,
+                } = mixed
+                else {
+                    unreachable!()
+                };
+                assert!(matches!(*definitions, EvaluationResult::Definition { .. }));
+                assert!(result.to_string().starts_with("42 : Int"), "{result}");
+            }
+            other => panic!("Expected mixed result, got {:?}", other),
+        }
+        // The definitions stay
+        assert!(repl.eval("double(1)").is_ok());
+
+        // Only the definitions are added by `define`
+        assert_eq!(
+            repl.define("pub const three = 3\ndouble(three)").ok(),
+            Some(true)
+        );
+        assert!(repl.context.source().contains("three"));
+    }
+
+    #[test]
+    fn test_definition_addition() {
+        let mut repl = ReplEvaluator::new();
+
+        // Add a simple constant definition
+        let result = repl.eval("pub const my_const = 42");
+        assert!(result.is_ok());
+
+        // Should be able to use it in an expression
+        let result = repl.eval("my_const + 1");
+        assert!(result.is_ok());
+
+        if let Ok(EvaluationResult::Value { value, .. }) = result {
+            assert_eq!(value, "43");
+        } else {
+            panic!("Expected value result, got: {:?}", result);
+        }
+    }
+
+    #[test]
+    fn test_definition_types() {
+        let mut repl = ReplEvaluator::new();
+        let mut defined = |code: &str| repl.eval(code).unwrap().to_string();
+
+        assert_eq!(
+            defined("pub fn add(x: Int, y: Int) -> Int { x + y }"),
+            "Defined function add : fn(Int, Int) -> Int"
+        );
+        assert_eq!(
+            defined("pub const answer = 42"),
+            "Defined constant answer : Int"
+        );
+        assert_eq!(
+            defined("pub type Color {\n  Red\n  Green\n}\n\npub const red = Red"),
+            "Defined type Color\nDefined constant red : Color"
+        );
+    }
+
+    #[test]
+    fn test_conflicting_definitions() {
+        let mut repl = ReplEvaluator::new();
+        repl.set_origin(Some("In[3]".to_string()));
+        assert!(repl.eval("pub const foo = 1").is_ok());
+        repl.set_origin(None);
+
+        // A function replaces the constant, with a note about it
+        assert!(repl.eval("pub fn foo() { 2 }").is_ok());
+        assert_eq!(
+            repl.take_warnings(),
+            vec![
+                "note: constant foo was previously defined in In[3]; redefining it as function foo"
+            ]
+        );
+        assert!(matches!(
+            repl.eval("foo()"),
+            Ok(EvaluationResult::Value { value, .. }) if value == "2"
+        ));
+
+        assert!(matches!(
+            repl.eval("pub fn bar() { 1 }\n\npub fn bar() { 2 }"),
+            Err(ReplError::DuplicateDefinition { definition }) if definition == "function bar"
+        ));
+    }
+
+    #[test]
+    fn test_ambiguous_types() {
+        let mut repl = ReplEvaluator::new();
+
+        // Type variables default to Data, with a note
+        match repl.eval("[]") {
+            Ok(EvaluationResult::Value { value, tipo, .. }) => {
+                assert_eq!(value, "[]");
+                assert_eq!(Printer::new().pretty_print(&tipo, 0), "List<Data>");
+            }
+            other => panic!("Expected value result, got {:?}", other),
+        }
+        assert_eq!(
+            repl.take_warnings(),
+            vec!["note: List<a> is ambiguous, evaluated as List<Data>"]
+        );
+        assert!(repl.eval("None").is_ok());
+        assert_eq!(
+            repl.take_warnings(),
+            vec!["note: Option<a> is ambiguous, evaluated as Option<Data>"]
+        );
+
+        // Annotated expressions are left alone
+        assert!(repl.eval("let xs: List<Int> = []\nxs").is_ok());
+        assert!(repl.take_warnings().is_empty());
+
+        // Compiling without evaluating asks for an annotation
+        assert!(matches!(
+            repl.compile_to_uplc("[]"),
+            Err(ReplError::AmbiguousType { tipo, .. }) if tipo == "List<a>"
+        ));
+    }
+
+    #[test]
+    fn test_function_values() {
+        let mut repl = ReplEvaluator::new();
+        assert!(
+            repl.eval("pub fn add(x: Int, y: Int) -> Int { x + y }")
+                .is_ok()
+        );
+
+        let result = repl.eval("add").unwrap();
+        assert_eq!(result.to_string(), "<fn: (Int, Int) -> Int>");
+        // Generic functions needn't be made concrete
+        assert_eq!(
+            repl.eval("fn(x) { x }").unwrap().to_string(),
+            "<fn: (a) -> a>"
+        );
+        assert!(repl.take_warnings().is_empty());
+        assert_eq!(
+            repl.eval("add(1, _)").unwrap().to_string(),
+            "<fn: (Int) -> Int>"
+        );
+    }
+
+    #[test]
+    fn test_non_exhaustive_when() {
+        let mut repl = ReplEvaluator::new();
+        match repl.eval(
+            "pub fn describe(o: Option<Int>) -> Int {\n  when o is {\n    Some(n) -> n\n  }\n}",
+        ) {
+            Err(ReplError::NonExhaustive {
+                definition,
+                missing,
+                ..
+            }) => {
+                assert_eq!(definition, "function describe");
+                assert_eq!(missing, vec!["None"]);
+            }
+            other => panic!("Expected a non-exhaustive error, got {:?}", other),
+        }
+        assert!(repl.definitions().is_empty());
+
+        assert!(matches!(
+            repl.eval("when Some(1) is {\n  None -> 0\n}"),
+            Err(ReplError::NonExhaustive { definition, .. }) if definition == "the expression"
+        ));
+    }
+
+    #[test]
+    fn test_eval_typed() {
+        let mut repl = ReplEvaluator::new();
+        assert_eq!(repl.eval_typed::<i64>("1 + 2").unwrap(), 3);
+        assert_eq!(
+            repl.eval_typed::<Vec<Option<String>>>("[Some(\"a\"), None]")
+                .unwrap(),
+            vec![Some("a".to_string()), None]
+        );
+        assert_eq!(
+            repl.eval_typed::<(Bytes, bool)>("(#\"cafe\", True)")
+                .unwrap(),
+            (Bytes(vec![0xca, 0xfe]), true)
+        );
+
+        assert!(
+            repl.eval("pub type Shape {\n  Circle(Int)\n  Square { side: Int }\n}")
+                .is_ok()
+        );
+        let square = repl.eval_typed::<Constr>("Square { side: 4 }").unwrap();
+        assert_eq!(square.index, 1);
+        assert_eq!(square.field::<i64>(0).unwrap(), 4);
+
+        assert!(matches!(
+            repl.eval_typed::<bool>("42"),
+            Err(ReplError::InvalidData { .. })
+        ));
+    }
+
+    #[test]
+    fn test_stale_definitions() {
         let mut repl = ReplEvaluator::new();
+        repl.set_origin(Some("In[4]".to_string()));
+        assert!(repl.eval("pub fn f() { 1 }\n\npub fn g() { 2 }").is_ok());
+        assert_eq!(
+            repl.provenance("g").unwrap().origin.as_deref(),
+            Some("In[4]")
+        );
 
-        // Test simple boolean expression
-        let result = repl.eval("True");
-        assert!(result.is_ok());
+        // Running the same cell again leaves nothing stale
+        assert!(repl.eval("pub fn f() { 1 }\n\npub fn g() { 2 }").is_ok());
+        assert!(repl.take_warnings().is_empty());
 
-        if let Ok(EvaluationResult::Value { value, .. }) = result {
-            assert_eq!(value, "True");
-        } else {
-            panic!("Expected value result, got: {:?}", result);
-        }
+        repl.set_origin(Some("In[5]".to_string()));
+        assert!(repl.eval("pub fn f() { 3 }").is_ok());
+        assert_eq!(
+            repl.take_warnings(),
+            vec!["note: function f from In[4] redefined; In[4] still defines function g"]
+        );
+        assert_eq!(
+            repl.provenance("f").unwrap().origin.as_deref(),
+            Some("In[5]")
+        );
+        assert_eq!(repl.provenance("f").unwrap().input, "pub fn f() { 3 }");
     }
 
     #[test]
-    fn test_arithmetic_expression() {
+    fn test_function_definition_and_call() {
         let mut repl = ReplEvaluator::new();
 
-        // Test simple arithmetic
-        let result = repl.eval("1 + 2");
+        // Add a function definition
+        let result = repl.eval("pub fn add(x: Int, y: Int) -> Int { x + y }");
+        assert!(result.is_ok());
+
+        // Should be able to call it
+        let result = repl.eval("add(2, 3)");
         assert!(result.is_ok());
 
         if let Ok(EvaluationResult::Value { value, .. }) = result {
-            assert_eq!(value, "3");
+            assert_eq!(value, "5");
         } else {
             panic!("Expected value result, got: {:?}", result);
         }
     }
 
     #[test]
-    fn test_expression_detection() {
-        // These should be detected as expressions
-        assert!(looks_like_expression("1 + 2"));
-        assert!(looks_like_expression("True"));
-        assert!(looks_like_expression("\"hello\""));
+    fn test_inspect() {
+        let mut repl = ReplEvaluator::new();
+        assert!(repl.inspect().unwrap().is_empty());
 
-        // These should be detected as definitions
-        assert!(!looks_like_expression("fn add(x, y) { x + y }"));
-        assert!(!looks_like_expression("pub const X = 42"));
-        assert!(!looks_like_expression("type Option<a> { Some(a) | None }"));
+        assert!(repl.eval("pub const answer = 42").is_ok());
+        assert!(
+            repl.eval("pub fn add(x: Int, y: Int) -> Int { x + y }")
+                .is_ok()
+        );
+        assert!(repl.eval("pub type Color { Red Green }").is_ok());
+
+        let definitions = repl.inspect().unwrap();
+        assert_eq!(definitions.len(), 3);
+
+        let answer = definitions.iter().find(|d| d.name == "answer").unwrap();
+        assert_eq!(answer.kind, DefinitionKind::Constant);
+        assert_eq!(answer.tipo, "Int");
+        assert_eq!(answer.value.as_deref(), Some("42"));
+
+        let add = definitions.iter().find(|d| d.name == "add").unwrap();
+        assert_eq!(add.kind, DefinitionKind::Function);
+        assert_eq!(add.tipo, "fn(Int, Int) -> Int");
+        assert_eq!(add.value, None);
+
+        let color = definitions.iter().find(|d| d.name == "Color").unwrap();
+        assert_eq!(color.kind, DefinitionKind::Type);
+        assert_eq!(color.tipo, "type Color { Red | Green }");
+        assert_eq!(color.origin, None);
+
+        repl.set_origin(Some("In[7]".to_string()));
+        assert!(repl.eval("pub const question = 41").is_ok());
+        let definitions = repl.inspect().unwrap();
+        let question = definitions.iter().find(|d| d.name == "question").unwrap();
+        assert_eq!(question.origin.as_deref(), Some("In[7]"));
     }
 
     #[test]
-    fn test_definition_addition() {
+    fn test_timeit() {
         let mut repl = ReplEvaluator::new();
+        assert!(
+            repl.eval("pub fn add(x: Int, y: Int) -> Int { x + y }")
+                .is_ok()
+        );
 
-        // Add a simple constant definition
-        let result = repl.eval("pub const my_const = 42");
-        assert!(result.is_ok());
+        let timing = repl.timeit("add(1, 2)", Some(3), 2).unwrap();
+        assert_eq!(timing.loops, 3);
+        assert_eq!(timing.repeats, 2);
+        assert!(timing.budget.cpu > 0);
+        assert!(
+            format!("{}", timing).contains("per loop (mean ± std. dev. of 2 runs, 3 loops each)")
+        );
 
-        // Should be able to use it in an expression
-        let result = repl.eval("my_const + 1");
-        assert!(result.is_ok());
+        // Failing expressions are reported instead of timed
+        assert!(repl.timeit("fail", Some(1), 1).is_err());
+        assert!(repl.timeit("pub const x = 1", Some(1), 1).is_err());
+    }
 
-        if let Ok(EvaluationResult::Value { value, .. }) = result {
-            assert_eq!(value, "43");
-        } else {
-            panic!("Expected value result, got: {:?}", result);
+    #[test]
+    fn test_profile() {
+        let mut repl = ReplEvaluator::new();
+        assert!(
+            repl.eval("pub fn sum(n: Int) -> Int { if n == 0 { 0 } else { n + sum(n - 1) } }")
+                .is_ok()
+        );
+
+        let profile = repl.profile("sum(50)").unwrap();
+        assert!(profile.budget.cpu > 0);
+        let add = profile
+            .frames
+            .iter()
+            .find(|frame| frame.name == "addInteger")
+            .unwrap();
+        assert_eq!(add.calls, 50);
+        assert!(
+            profile
+                .frames
+                .iter()
+                .any(|frame| frame.name.contains("sum"))
+        );
+        let self_cpu: i64 = profile.frames.iter().map(|frame| frame.self_cpu).sum();
+        assert!(self_cpu <= profile.budget.cpu * 11 / 10);
+        assert!(format!("{}", profile).contains("addInteger"));
+        assert!(
+            profile
+                .folded()
+                .lines()
+                .all(|line| line.starts_with("(top level)"))
+        );
+        assert!(
+            profile
+                .stacks
+                .keys()
+                .any(|stack| stack.ends_with(";addInteger"))
+        );
+
+        assert!(repl.profile("fail").is_err());
+    }
+
+    #[test]
+    fn test_check() {
+        let mut repl = ReplEvaluator::new();
+        assert!(
+            repl.eval("pub fn double(x: Int) -> Int {\n  let unused = 1\n  x * 2\n}")
+                .is_ok()
+        );
+        let report = repl.check().unwrap();
+        assert!(report.passed());
+        assert_eq!(report.definitions, 1);
+        // Reported again, even though the evaluation already showed it
+        assert_eq!(report.warnings.len(), 1);
+
+        assert!(repl.eval("test doubles() { double(2) == 5 }").is_ok());
+        let report = repl.check().unwrap();
+        assert!(!report.passed());
+        assert!(format!("{}", report).ends_with("2 definitions checked | 1 error | 1 warning"));
+    }
+
+    #[test]
+    fn test_panics_keep_the_session() {
+        let mut repl = ReplEvaluator::new();
+        assert!(repl.eval("pub const x = 1").is_ok());
+
+        let result = repl.guarded(|repl| -> Result<(), ReplError> {
+            repl.eval_definitions("pub const y = 2")?;
+            panic!("codegen exploded")
+        });
+        assert!(matches!(
+            result,
+            Err(ReplError::Panicked { message }) if message == "codegen exploded"
+        ));
+
+        // The definitions from before the panic are left, without the ones added since
+        assert!(repl.eval("x").is_ok());
+        assert!(repl.eval("y").is_err());
+    }
+
+    #[test]
+    fn test_fuzz() {
+        let mut repl = ReplEvaluator::new();
+        assert!(
+            repl.eval("pub fn one_two(prng: PRNG) -> Option<(PRNG, (Int, Int))> {\n  Some((prng, (1, 2)))\n}")
+                .is_ok()
+        );
+        assert!(
+            repl.eval("pub fn commutes(f: fn(Int, Int) -> Int, input: (Int, Int)) -> Bool {\n  f(input.1st, input.2nd) == f(input.2nd, input.1st)\n}")
+                .is_ok()
+        );
+        assert!(
+            repl.eval("pub fn add(a: Int, b: Int) -> Int { a + b }")
+                .is_ok()
+        );
+        assert!(
+            repl.eval("pub fn sub(a: Int, b: Int) -> Int { a - b }")
+                .is_ok()
+        );
+
+        let args = FuzzArgs::parse("add with one_two satisfying commutes --seed 42").unwrap();
+        let report = repl.fuzz(&args).unwrap();
+        assert_eq!(report.passed(), 1);
+        assert_eq!(report.seed, Some(42));
+        assert_eq!(report.tests[0].name, "add with one_two satisfying commutes");
+
+        assert!(repl.set_option("seed", "7").is_ok());
+        let args = FuzzArgs::parse("sub with one_two satisfying commutes").unwrap();
+        let report = repl.fuzz(&args).unwrap();
+        assert_eq!(report.seed, Some(7));
+        assert_eq!(report.failed(), 1);
+        assert_eq!(report.tests[0].counterexample.as_deref(), Some("(1, 2)"));
+
+        // The session is left as it was
+        assert!(repl.check().unwrap().passed());
+        let args = FuzzArgs::parse("add with one_two satisfying missing").unwrap();
+        assert!(matches!(
+            repl.fuzz(&args),
+            Err(ReplError::SessionError { .. })
+        ));
+    }
+
+    #[test]
+    fn test_compile_to_uplc() {
+        let mut repl = ReplEvaluator::new();
+        assert!(repl.eval("pub const x = 1").is_ok());
+        assert!(
+            repl.eval("pub fn sum(n: Int) -> Int { if n == 0 { 0 } else { n + sum(n - 1) } }")
+                .is_ok()
+        );
+
+        let dump = repl.compile_to_uplc("sum(x)").unwrap();
+        assert!(dump.program.starts_with("(program"));
+        let mapping = &dump.source_map.mappings[0];
+        assert_eq!((mapping.definition.as_str(), mapping.line), ("sum", 3));
+        assert!(dump.annotated().contains("-- sum, line 3"));
+
+        assert!(repl.compile_to_uplc("pub const y = 2").is_err());
+    }
+
+    #[test]
+    fn test_redefinition_report() {
+        let mut repl = ReplEvaluator::new();
+        assert!(repl.eval("pub fn double(x: Int) -> Int { x + x }").is_ok());
+        let before = repl.set_probe("double(21)").unwrap();
+
+        // New definitions don't report anything
+        assert!(repl.eval("pub fn triple(x: Int) -> Int { 3 * x }").is_ok());
+        assert!(repl.take_redefinition_report().is_none());
+
+        assert!(repl.eval("pub fn double(x: Int) -> Int { 2 * x }").is_ok());
+        let report = repl.take_redefinition_report().unwrap();
+        assert_eq!(report.probe, "double(21)");
+        assert_eq!(report.before, Some(before));
+        assert!(report.after.is_some());
+        assert!(repl.take_redefinition_report().is_none());
+
+        // Without a probe there is nothing to compare
+        repl.clear_probe();
+        assert!(repl.eval("pub fn double(x: Int) -> Int { x * 2 }").is_ok());
+        assert!(repl.take_redefinition_report().is_none());
+    }
+
+    #[test]
+    fn test_warnings() {
+        let mut repl = ReplEvaluator::new();
+
+        assert!(repl.eval("fn unused() { 1 }").is_ok());
+        let warnings = repl.take_warnings();
+        assert_eq!(warnings.len(), 1);
+        assert!(repl.take_warnings().is_empty());
+
+        // Checking the same definitions again doesn't report them twice
+        assert!(repl.eval("1 + 2").is_ok());
+        assert!(repl.take_warnings().is_empty());
+
+        // Code that only has warnings returns them as its result
+        match repl.eval("use aiken/builtin") {
+            Ok(EvaluationResult::Warnings(warnings)) => assert_eq!(warnings.len(), 1),
+            other => panic!("Expected warnings, got {:?}", other),
         }
+        assert!(repl.take_warnings().is_empty());
+
+        repl.set_warnings(false);
+        assert!(repl.eval("fn unused_too() { 2 }").is_ok());
+        assert!(repl.take_warnings().is_empty());
     }
 
     #[test]
-    fn test_function_definition_and_call() {
+    fn test_cbor() {
         let mut repl = ReplEvaluator::new();
 
-        // Add a function definition
-        let result = repl.eval("pub fn add(x: Int, y: Int) -> Int { x + y }");
+        let script = repl.cbor("1 + 2").unwrap();
+        assert_eq!(script.cbor.len(), script.flat_size + 1);
+        assert_eq!(script.double_cbor[1..], script.cbor[..]);
+        assert!(format!("{}", script).starts_with("CBOR: "));
+
+        assert!(repl.cbor("pub const x = 1").is_err());
+    }
+
+    #[test]
+    fn test_eval_uplc() {
+        let repl = ReplEvaluator::new();
+
+        let result = repl
+            .eval_uplc(
+                "(program 1.0.0 [ [ (builtin addInteger) (con integer 1) ] (con integer 2) ])",
+            )
+            .unwrap();
+        assert_eq!(result.value, "3");
+        assert!(result.budget.cpu > 0);
+        assert!(format!("{}", result).ends_with(&format!(
+            "Budget: mem {}, cpu {}",
+            result.budget.mem, result.budget.cpu
+        )));
+
+        // Syntax errors and failing programs are reported
+        assert!(matches!(
+            repl.eval_uplc("(program 1.0.0"),
+            Err(ReplError::ParseFailed { .. })
+        ));
+        assert!(matches!(
+            repl.eval_uplc("(program 1.0.0 (error))"),
+            Err(ReplError::EvaluationFailed { .. })
+        ));
+    }
+
+    #[test]
+    fn test_doc_lookup() {
+        let mut repl = ReplEvaluator::new();
+
+        let result = repl.eval("/// Doubles a number\npub fn double(x: Int) -> Int { x * 2 }");
         assert!(result.is_ok());
 
-        // Should be able to call it
-        let result = repl.eval("add(2, 3)");
+        let entry = repl
+            .doc("double")
+            .unwrap()
+            .expect("Expected docs for double");
+        assert_eq!(entry.signature, "double : fn(Int) -> Int");
+        assert_eq!(
+            entry.doc.as_deref().map(str::trim),
+            Some("Doubles a number")
+        );
+        assert!(entry.to_string().contains("\n  defined 0s ago"));
+
+        // Unknown names yield no entry rather than an error
+        assert!(repl.doc("does_not_exist").unwrap().is_none());
+
+        // Functions of the stdlib, by the last segment of their module, fetched for the lookup
+        // since the session doesn't import it
+        let entry = repl
+            .doc("list.map")
+            .unwrap()
+            .expect("Expected docs for list.map");
+        assert_eq!(entry.module, "aiken/collection/list");
+        assert_eq!(entry.signature, "map : fn(List<a>, fn(a) -> b) -> List<b>");
+        assert!(
+            entry
+                .doc
+                .unwrap()
+                .starts_with("Apply a function to each element of a list")
+        );
+        assert!(entry.provenance.is_none());
+    }
+
+    #[test]
+    fn test_completion_candidates() {
+        let mut repl = ReplEvaluator::new();
+        assert!(repl.eval("pub fn double(x: Int) -> Int { x * 2 }").is_ok());
+        assert!(repl.eval("pub type Color { Red Green }").is_ok());
+
+        let candidates = repl.completion_candidates().unwrap();
+        assert_eq!(
+            candidates.complete("do", 2).matches,
+            vec!["double".to_string()]
+        );
+        assert_eq!(
+            candidates.complete("Gr", 2).matches,
+            vec!["Green".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_format() {
+        let repl = ReplEvaluator::new();
+
+        let formatted = repl.format("pub fn add(x: Int,y: Int)->Int{x+y}").unwrap();
+        assert_eq!(formatted, "pub fn add(x: Int, y: Int) -> Int {\n  x + y\n}");
+
+        let formatted = repl.format("1+2").unwrap();
+        assert_eq!(formatted, "1 + 2");
+    }
+
+    #[test]
+    fn test_auto_format_definitions() {
+        let mut repl = ReplEvaluator::new();
+        repl.set_auto_format(true);
+
+        let result = repl.eval("pub fn add(x: Int,y: Int)->Int{x+y}");
         assert!(result.is_ok());
+        assert!(
+            repl.definitions()
+                .contains("pub fn add(x: Int, y: Int) -> Int {")
+        );
+    }
 
-        if let Ok(EvaluationResult::Value { value, .. }) = result {
-            assert_eq!(value, "5");
-        } else {
-            panic!("Expected value result, got: {:?}", result);
-        }
+    #[test]
+    fn test_windows_line_endings() {
+        let mut repl = ReplEvaluator::new();
+        assert!(
+            repl.eval("/// Doubles\r\npub fn double(x: Int) -> Int {\r\n  x * 2\r\n}\r\n")
+                .is_ok()
+        );
+        assert!(!repl.definitions().contains('\r'));
+        let result = repl.eval("double(21)").unwrap();
+        assert!(format!("{}", result).contains("42"));
+
+        let lib = std::path::Path::new("lib");
+        assert_eq!(
+            module_path(lib, "repl/prelude"),
+            lib.join("repl").join("prelude.ak")
+        );
     }
 
     #[test]
@@ -651,17 +3305,367 @@ mod test {
 
         // Add some definitions
         let _result = repl.eval("pub const my_const = 42");
-        assert!(!repl.definitions.is_empty());
+        assert!(!repl.definitions().is_empty());
 
         // Reset should clear everything
         repl.reset();
-        assert!(repl.definitions.is_empty());
+        assert!(repl.definitions().is_empty());
 
         // Should no longer be able to use the constant
         let result = repl.eval("my_const");
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_reset_forgets_checked_project() {
+        let mut repl = ReplEvaluator::new();
+        assert!(repl.eval("pub const x = 1").is_ok());
+        // Keeps the checked project for the next expressions
+        assert!(repl.eval("x + 1").is_ok());
+
+        repl.reset();
+        assert!(matches!(
+            repl.eval("x"),
+            Err(ReplError::SessionError { .. })
+        ));
+    }
+
+    #[test]
+    fn test_undo_and_forget() {
+        let mut repl = ReplEvaluator::new();
+        assert!(repl.eval("pub fn double(x: Int) -> Int { x * 2 }").is_ok());
+        assert!(
+            repl.eval("pub fn quadruple(x: Int) -> Int { double(double(x)) }")
+                .is_ok()
+        );
+
+        // Other definitions still use it
+        assert!(repl.forget("double").is_err());
+        assert!(repl.definitions().contains("pub fn double"));
+
+        assert!(repl.eval("pub fn double(x: Int) -> Int { x + x }").is_ok());
+        assert_eq!(repl.undo(), Some(vec!["function double".to_string()]));
+        assert!(repl.definitions().contains("x * 2"));
+
+        assert_eq!(
+            repl.forget("quadruple").unwrap(),
+            vec!["function quadruple".to_string()]
+        );
+        assert!(repl.eval("quadruple(1)").is_err());
+        assert!(repl.forget("quadruple").unwrap().is_empty());
+
+        assert!(repl.undo().is_some());
+        assert!(repl.eval("quadruple(1)").is_ok());
+    }
+
+    #[test]
+    fn test_define() {
+        let mut repl = ReplEvaluator::new();
+        assert!(repl.define("pub const answer = 42").unwrap());
+        assert!(!repl.define("answer + 1").unwrap());
+        assert!(repl.define("pub const answer = \"oops\" + 1").is_err());
+        assert_eq!(repl.definitions(), "pub const answer = 42");
+    }
+
+    #[test]
+    fn test_prelude() {
+        let mut repl = ReplEvaluator::new();
+        assert!(repl.eval("use repl/prelude").is_ok());
+
+        // Base address test vector of CIP-19
+        let address = "addr1qx2fxv2umyhttkxyxp8x0dlpdt3k6cwng5pxj3jhsydzer3n0d3vllmyqwsx5wktcd8cc3sq835lu7drv2xwl2wywfgse35a3x";
+        let payment = "9493315cd92eb5d8c4304e67b7e16ae36d61d34502694657811a2c8e";
+        let stake = "337b62cfff6403a06a3acbc34f8c46003c69fe79a3628cefa9c47251";
+
+        let checks = [
+            r#"prelude.policy_id("00fF") == #"00ff""#.to_string(),
+            r#"prelude.key_address("00").payment_credential == prelude.VerificationKey(#"00")"#
+                .to_string(),
+            format!(
+                r#"prelude.address("{}") == prelude.Address {{
+                     payment_credential: prelude.VerificationKey(#"{}"),
+                     stake_credential: Some(prelude.Inline(prelude.VerificationKey(#"{}"))),
+                   }}"#,
+                address, payment, stake
+            ),
+            r#"(prelude.from_lovelace(1) |> prelude.add(#"01", "A", 2) |> prelude.add(#"01", "A", 3))
+                 == prelude.Value { inner: [Pair(#"", [Pair(#"", 1)]), Pair(#"01", [Pair("A", 5)])] }"#
+                .to_string(),
+        ];
+        for check in &checks {
+            match repl.eval(check) {
+                Ok(EvaluationResult::Value { value, .. }) => {
+                    assert_eq!(value, "True", "{}", check)
+                }
+                other => panic!("{}: {:?}", check, other),
+            }
+        }
+        assert!(repl.eval(r#"prelude.from_hex("0")"#).is_err());
+    }
+
+    #[test]
+    fn test_data_conversions() {
+        let mut repl = ReplEvaluator::new();
+        assert!(
+            repl.eval("pub type Datum { owner: ByteArray, deadline: Int }")
+                .is_ok()
+        );
+
+        let datum = r#"{"constructor": 0, "fields": [{"bytes": "cafe"}, {"int": 42}]}"#;
+        let converted = repl.to_data(&format!("Datum {}", datum)).unwrap();
+        assert_eq!(
+            converted.value.as_deref(),
+            Some("Datum { owner: #\"cafe\", deadline: 42 }")
+        );
+        assert_eq!(converted.cbor, "d8799f42cafe182aff");
+
+        // Data that doesn't fit the type
+        assert!(repl.to_data(r#"Datum {"int": 1}"#).is_err());
+
+        let back = repl
+            .from_data("Datum { owner: #\"cafe\", deadline: 42 }")
+            .unwrap();
+        assert_eq!(
+            back.json,
+            serde_json::from_str::<serde_json::Value>(datum).unwrap()
+        );
+        assert_eq!(back.cbor, converted.cbor);
+    }
+
+    #[test]
+    fn test_keygen() {
+        let mut repl = ReplEvaluator::new();
+        let alice = repl.keygen("alice").unwrap();
+        assert!(repl.keygen("alice").is_err());
+        assert_eq!(repl.keys().collect::<Vec<_>>(), vec![alice.clone()]);
+        assert_eq!(repl.key_hash("alice"), Some(alice.key_hash));
+
+        // The verification key is a constant of the session, which checks the signature
+        let signature = repl.sign("alice", "#\"cafe\"").unwrap();
+        assert!(repl.eval("use aiken/builtin").is_ok());
+        let result = repl.eval(&format!(
+            "builtin.verify_ed25519_signature(alice_vk, #\"cafe\", #\"{}\")",
+            hex::encode(&signature)
+        ));
+        if let Ok(EvaluationResult::Value { value, .. }) = result {
+            assert_eq!(value, "True");
+        } else {
+            panic!("Expected value result, got: {:?}", result);
+        }
+
+        assert!(repl.sign("bob", "#\"cafe\"").is_err());
+        assert!(repl.sign("alice", "42").is_err());
+    }
+
+    #[test]
+    fn test_mock_chain() {
+        let mut repl = ReplEvaluator::new();
+        assert!(
+            repl.eval(
+                "validator vault {\n  spend(_datum: Option<Int>, redeemer: Int, _own_ref: Data, _self: Data) {\n    trace @\"opening\"\n    redeemer == 42\n  }\n}"
+            )
+            .is_ok()
+        );
+        assert_eq!(repl.validator_names().collect::<Vec<_>>(), vec!["vault"]);
+
+        let mut chain = MockChain::default();
+        let funds = chain.fund(&mut repl, "alice 100ada").unwrap();
+        let lock = chain
+            .submit(
+                &mut repl,
+                &format!("spend #{}; pay vault 20ada datum 1; pay alice 80ada", funds),
+            )
+            .unwrap();
+        assert_eq!(lock.created, vec![1, 2]);
+        assert!(lock.runs.is_empty());
+
+        // The validator refuses other redeemers, and the outputs must hold what's spent
+        assert!(
+            chain
+                .submit(&mut repl, "spend #1 with 41\npay bob 20ada")
+                .is_err()
+        );
+        assert!(chain.submit(&mut repl, "spend #2\npay bob 1ada").is_err());
+
+        let unlock = chain
+            .submit(&mut repl, "spend #1 with 42\npay bob 20ada")
+            .unwrap();
+        assert_eq!(unlock.runs.len(), 1);
+        assert_eq!(unlock.runs[0].1.traces, vec!["opening"]);
+        assert_eq!(
+            chain.utxos().collect::<Vec<_>>(),
+            vec!["#2 alice: 80 ada", "#3 bob: 20 ada"]
+        );
+    }
+
+    #[test]
+    fn test_checkpoint_and_rollback() {
+        let mut repl = ReplEvaluator::new();
+        assert!(repl.eval("pub fn double(x: Int) -> Int { x * 2 }").is_ok());
+        repl.checkpoint("start");
+
+        assert!(repl.eval("pub fn double(x: Int) -> Int { x + x }").is_ok());
+        assert!(repl.eval("pub const answer = 42").is_ok());
+        assert!(repl.set_option("max-depth", "2").is_ok());
+        assert!(matches!(
+            repl.rollback("nope"),
+            Err(ReplError::UnknownCheckpoint { .. })
+        ));
+
+        // Settings come back along with the definitions, and the checkpoint outlives a reset
+        repl.reset();
+        assert!(repl.rollback("start").is_ok());
+        assert!(repl.definitions().contains("x * 2"));
+        assert!(repl.eval("answer").is_err());
+        assert_eq!(repl.display_settings(), &DisplaySettings::default());
+        assert_eq!(repl.checkpoints().collect::<Vec<_>>(), vec!["start"]);
+    }
+
+    #[test]
+    fn test_export() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let mut repl = ReplEvaluator::new();
+        assert!(repl.export(&dir.path().join("empty")).is_err());
+        assert!(repl.eval("pub fn double(x: Int)->Int { x*2 }").is_ok());
+
+        let module = dir.path().join("double.ak");
+        assert!(repl.export(&module).is_ok());
+        assert_eq!(
+            fs::read_to_string(&module).unwrap(),
+            "pub fn double(x: Int) -> Int {\n  x * 2\n}\n"
+        );
+        // Existing files are left alone
+        assert!(repl.export(&module).is_err());
+
+        let project = dir.path().join("my-project");
+        let files = repl.export(&project).unwrap();
+        assert!(files.contains(&project.join("lib").join("my_project.ak")));
+        let aiken_toml = fs::read_to_string(project.join("aiken.toml")).unwrap();
+        assert!(aiken_toml.contains("name = \"repl/my_project\""));
+        // Without imports of the stdlib, the project builds offline
+        assert!(!aiken_toml.contains("name = \"aiken-lang/stdlib\""));
+    }
+
+    #[test]
+    fn test_replace_context() {
+        let mut repl = ReplEvaluator::new();
+        assert!(repl.eval("pub const my_const = 42").is_ok());
+
+        // Code that doesn't type-check leaves the context alone
+        assert!(
+            repl.replace_context("pub const my_const = \"oops\" + 1")
+                .is_err()
+        );
+        assert!(repl.definitions().contains("pub const my_const = 42"));
+
+        assert!(repl.replace_context("pub const other = 1").is_ok());
+        assert_eq!(repl.definitions(), "pub const other = 1");
+        assert!(repl.eval("my_const").is_err());
+        assert!(repl.eval("other").is_ok());
+    }
+
+    #[test]
+    fn test_cache() {
+        let mut repl = ReplEvaluator::new();
+        repl.set_cache(true);
+
+        let result = repl.eval("pub const my_const = 42");
+        assert!(result.is_ok());
+
+        // Re-running the same expression is served from the cache
+        let first = repl.eval("my_const + 1").unwrap();
+        let second = repl.eval("my_const + 1").unwrap();
+        assert_eq!(format!("{}", first), format!("{}", second));
+        assert_eq!(repl.cache.as_ref().map(|cache| cache.len()), Some(1));
+
+        // Changing the definitions invalidates it
+        let result = repl.eval("pub const my_const = 1");
+        assert!(result.is_ok());
+        assert_eq!(repl.cache.as_ref().map(|cache| cache.len()), Some(0));
+        if let Ok(EvaluationResult::Value { value, .. }) = repl.eval("my_const + 1") {
+            assert_eq!(value, "2");
+        } else {
+            panic!("Expected value result");
+        }
+
+        repl.set_cache(false);
+        assert!(repl.cache.is_none());
+    }
+
+    #[test]
+    fn test_evaluator_is_send() {
+        // The kernel shares the evaluator between threads behind a mutex
+        fn assert_send<T: Send>() {}
+        assert_send::<ReplEvaluator>();
+    }
+
+    #[test]
+    fn test_eval_expressions() {
+        let mut repl = ReplEvaluator::new();
+
+        let result = repl.eval("pub const my_const = 42");
+        assert!(result.is_ok());
+
+        let results = repl.eval_expressions(&["my_const + 1", "True"]);
+        let values: Vec<String> = results
+            .into_iter()
+            .map(|result| match result {
+                Ok(EvaluationResult::Value { value, .. }) => value,
+                other => panic!("Expected value result, got: {:?}", other),
+            })
+            .collect();
+        assert_eq!(values, ["43", "True"]);
+
+        // A bad expression only fails on its own
+        let results = repl.eval_expressions(&["my_const + 1", "does_not_exist"]);
+        assert!(results[0].is_ok());
+        assert!(results[1].is_err());
+
+        // Nothing was added to the context
+        assert!(!repl.definitions().contains("repl_eval"));
+    }
+
+    #[test]
+    fn test_gc_and_cleanup() {
+        let mut repl = ReplEvaluator::new();
+
+        let result = repl.eval("pub const my_const = 42");
+        assert!(result.is_ok());
+
+        // Removing the build artifacts keeps the session going
+        assert!(repl.gc().is_ok());
+        assert!(!repl.temp_dir.path().join("build").exists());
+        let result = repl.eval("my_const");
+        assert!(result.is_ok());
+
+        // So does removing the whole project
+        assert!(repl.cleanup().is_ok());
+        assert!(!repl.temp_dir.path().exists());
+        let result = repl.eval("my_const + 1");
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_artifact_cache() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let cache = ArtifactCache::new(dir.path().join("iaiken"), artifacts::ARTIFACTS_LIMIT);
+
+        let mut first = ReplEvaluator::new();
+        first.set_artifact_cache(Some(cache.clone()));
+        assert!(first.eval("1 + 1").is_ok());
+        assert_eq!(fs::read_dir(cache.dir()).unwrap().count(), 1);
+
+        // A new session starts from the saved artifacts
+        let mut second = ReplEvaluator::new();
+        second.set_artifact_cache(Some(cache));
+        assert!(second.warm_up().is_ok());
+        assert!(second.temp_dir.path().join("build").exists());
+
+        assert!(second.clear_artifact_cache().is_ok());
+        assert!(!dir.path().join("iaiken").exists());
+        assert!(second.eval("2 + 2").is_ok());
+    }
+
     #[test]
     fn test_redefinition_support() {
         let mut repl = ReplEvaluator::new();
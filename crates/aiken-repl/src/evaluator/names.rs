@@ -0,0 +1,117 @@
+//! Names of the temp project and of the module holding the session's definitions
+//!
+//! They're made up, and `repl` is a name users may want for themselves: both can be changed with
+//! the `project-name` and `module-name` options. Imports of the session's module are refused
+//! rather than left to fail as a cyclic or unknown import, and diagnostics show the session's
+//! code under a name of its own instead of the path of the module in the temp project.
+
+use super::{PRELUDE_MODULE, ReplError};
+
+/// Name the session's code goes by in diagnostics
+pub(crate) const SESSION_SOURCE: &str = "session";
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct Names {
+    /// Name of the temp project, as a package: `owner/name`
+    pub project: String,
+    /// Module of the session's definitions, eg. `repl` for `lib/repl.ak`
+    pub module: String,
+}
+
+impl Default for Names {
+    fn default() -> Self {
+        Self {
+            project: "repl/temp".to_string(),
+            module: "repl".to_string(),
+        }
+    }
+}
+
+impl Names {
+    pub fn set_project(&mut self, name: &str) -> Result<(), ReplError> {
+        let valid = |part: &str| {
+            !part.is_empty()
+                && part
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '-')
+        };
+        match name.split_once('/') {
+            Some((owner, package)) if valid(owner) && valid(package) => {
+                self.project = name.to_string();
+                Ok(())
+            }
+            _ => Err(ReplError::InvalidSetting {
+                message: format!(
+                    "`{}` isn't a package name, expected `owner/name` in lowercase",
+                    name
+                ),
+            }),
+        }
+    }
+
+    pub fn set_module(&mut self, name: &str) -> Result<(), ReplError> {
+        let invalid = |message: String| Err(ReplError::InvalidSetting { message });
+        let valid = |part: &str| {
+            part.starts_with(|c: char| c.is_ascii_lowercase())
+                && part
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+        };
+        if !name.split('/').all(valid) {
+            return invalid(format!(
+                "`{}` isn't a module name, expected lowercase words separated by `/`",
+                name
+            ));
+        }
+        if name == "aiken" || name.starts_with("aiken/") {
+            return invalid(format!("`{}` is among the compiler's own modules", name));
+        }
+        if name == PRELUDE_MODULE {
+            return invalid(format!("`{}` is the module of the prelude", name));
+        }
+        self.module = name.to_string();
+        Ok(())
+    }
+
+    /// Whether importing `module` would import the session's module, or one under it that can't
+    /// exist. The prelude is under the default module, but a module of its own.
+    pub fn is_reserved(&self, module: &str) -> bool {
+        module != PRELUDE_MODULE
+            && module
+                .strip_prefix(&self.module)
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Names;
+
+    #[test]
+    fn test_set_names() {
+        let mut names = Names::default();
+        assert!(names.set_module("session/scratch").is_ok());
+        assert_eq!(names.module, "session/scratch");
+        for invalid in ["", "Session", "1st", "a//b", "a-b", "aiken", "aiken/list"] {
+            assert!(names.set_module(invalid).is_err(), "{invalid}");
+        }
+        assert!(names.set_module("repl/prelude").is_err());
+        assert_eq!(names.module, "session/scratch");
+
+        assert!(names.set_project("me/scratch-pad").is_ok());
+        assert_eq!(names.project, "me/scratch-pad");
+        for invalid in ["temp", "me/", "/temp", "Me/temp", "me/te/mp"] {
+            assert!(names.set_project(invalid).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn test_is_reserved() {
+        let names = Names::default();
+        assert!(names.is_reserved("repl"));
+        assert!(names.is_reserved("repl/something"));
+        assert!(!names.is_reserved("repl/prelude"));
+        assert!(!names.is_reserved("replay"));
+        assert!(!names.is_reserved("aiken/collection/list"));
+    }
+}
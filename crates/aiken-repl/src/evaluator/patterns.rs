@@ -0,0 +1,86 @@
+//! Pattern matches that don't cover every case
+//!
+//! The compiler reports them like any other type error, pointing into the temp module. They're
+//! the errors newcomers run into most, so they're picked out of the errors of a check and shown
+//! first, with the definition they're in and the patterns left to cover.
+
+use aiken_lang::tipo::error::Error as TypeError;
+use aiken_project::error::Error as ProjectError;
+use miette::Diagnostic;
+
+/// The patterns a `when` of `error` leaves out, and where it is in the module, if it's about one
+pub fn missing_patterns(error: &ProjectError) -> Option<(&[String], usize)> {
+    let ProjectError::Type { error, .. } = error else {
+        return None;
+    };
+    match error {
+        TypeError::NotExhaustivePatternMatch { unmatched, .. } => {
+            let offset = error.labels()?.next()?.offset();
+            Some((unmatched.as_slice(), offset))
+        }
+        _ => None,
+    }
+}
+
+/// `function describe`, the definition of `module_code` at `offset`, or `the expression` for the
+/// wrapper of an evaluated expression
+pub fn enclosing_definition(module_code: &str, offset: usize) -> String {
+    let before = &module_code[..offset.min(module_code.len())];
+    let definition = before
+        .lines()
+        .rev()
+        .filter(|line| !line.starts_with(char::is_whitespace))
+        .find_map(definition_name);
+    match definition {
+        Some((_, name)) if name.starts_with("repl_eval_") => "the expression".to_string(),
+        Some((kind, name)) => format!("{} {}", kind, name),
+        None => "the expression".to_string(),
+    }
+}
+
+/// Kind and name of the definition a top-level line starts
+fn definition_name(line: &str) -> Option<(&'static str, &str)> {
+    let line = line.strip_prefix("pub ").unwrap_or(line);
+    let (kind, rest) = [
+        ("function", "fn "),
+        ("constant", "const "),
+        ("test", "test "),
+        ("validator", "validator "),
+    ]
+    .into_iter()
+    .find_map(|(kind, keyword)| Some((kind, line.strip_prefix(keyword)?)))?;
+    let end = rest
+        .find(|c: char| !c.is_alphanumeric() && c != '_')
+        .unwrap_or(rest.len());
+    (end > 0).then(|| (kind, &rest[..end]))
+}
+
+/// The missing patterns, one per line
+pub fn list(missing: &[String]) -> String {
+    missing
+        .iter()
+        .map(|pattern| format!("  {}", pattern))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod test {
+    use super::enclosing_definition;
+
+    #[test]
+    fn test_enclosing_definition() {
+        let code = "/// Doc\npub fn describe(o) {\n  when o is {\n    Some(n) -> n\n  }\n}\n\nconst x = 1\n\npub fn repl_eval_3() { when x is { 1 -> 1 } }";
+        let offset = |needle: &str| code.find(needle).unwrap();
+        assert_eq!(
+            enclosing_definition(code, offset("when o")),
+            "function describe"
+        );
+        assert_eq!(enclosing_definition(code, offset("= 1")), "constant x");
+        assert_eq!(
+            enclosing_definition(code, offset("when x")),
+            "the expression"
+        );
+        assert_eq!(enclosing_definition(code, 0), "the expression");
+    }
+}
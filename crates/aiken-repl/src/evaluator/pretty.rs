@@ -0,0 +1,394 @@
+//! Display of UPLC results
+//!
+//! Constants are printed recursively, lists and pairs with Aiken syntax and data with the usual
+//! Plutus notation (`Constr 0 [I 1, B #ab]`). `DisplaySettings` cut off big structures and
+//! choose how byte arrays are shown.
+
+use std::{fmt, rc::Rc, str::FromStr};
+
+use uplc::{
+    PlutusData,
+    ast::{Constant, Data, NamedDeBruijn, Term},
+    machine::value::from_pallas_bigint,
+};
+
+/// How values are displayed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DisplaySettings {
+    /// Values nested deeper than this are shown as `…`
+    pub max_depth: usize,
+    /// Lists, maps and constructor fields show at most this many items
+    pub max_width: usize,
+    /// Values longer than this many characters are cut
+    pub max_chars: usize,
+    pub bytearray: ByteArrayDisplay,
+}
+
+impl Default for DisplaySettings {
+    fn default() -> Self {
+        Self {
+            max_depth: 8,
+            max_width: 32,
+            max_chars: 20_000,
+            bytearray: ByteArrayDisplay::Both,
+        }
+    }
+}
+
+impl DisplaySettings {
+    /// The same settings without limits, to show a value whole
+    pub fn unlimited(self) -> Self {
+        Self {
+            max_depth: usize::MAX,
+            max_width: usize::MAX,
+            max_chars: usize::MAX,
+            ..self
+        }
+    }
+}
+
+/// Settings as `name = value` lines, with the names `set_option` takes
+impl fmt::Display for DisplaySettings {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "bytearray-display = {}", self.bytearray)?;
+        writeln!(f, "max-depth = {}", self.max_depth)?;
+        writeln!(f, "max-width = {}", self.max_width)?;
+        write!(f, "max-chars = {}", self.max_chars)
+    }
+}
+
+/// How byte arrays are shown. String literals are byte arrays too, so showing them as text when
+/// they are valid UTF-8 is much friendlier than hex alone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ByteArrayDisplay {
+    /// `#68656c6c6f`
+    Hex,
+    /// `"hello"`, or hex when the bytes aren't valid UTF-8
+    Utf8,
+    /// `#68656c6c6f ("hello")`, the text only when the bytes are valid UTF-8
+    Both,
+}
+
+impl FromStr for ByteArrayDisplay {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "hex" => Ok(Self::Hex),
+            "utf8" => Ok(Self::Utf8),
+            "both" => Ok(Self::Both),
+            _ => Err(format!("expected hex, utf8 or both, got `{}`", s)),
+        }
+    }
+}
+
+impl fmt::Display for ByteArrayDisplay {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Hex => write!(f, "hex"),
+            Self::Utf8 => write!(f, "utf8"),
+            Self::Both => write!(f, "both"),
+        }
+    }
+}
+
+/// Display a UPLC term. Only constants have a readable form.
+pub fn term(term: &Term<NamedDeBruijn>, settings: &DisplaySettings) -> String {
+    let text = match term {
+        Term::Constant(c) => constant(c, settings, 0),
+        _ => format!("{:?}", term),
+    };
+    truncate(text, settings.max_chars)
+}
+
+/// `text` cut after `max_chars` characters, saying how many were left out
+pub fn truncate(text: String, max_chars: usize) -> String {
+    let length = text.chars().count();
+    if length <= max_chars {
+        return text;
+    }
+    let shown: String = text.chars().take(max_chars).collect();
+    format!("{}… {} more characters", shown, count(length - max_chars))
+}
+
+/// `c` with its lists and maps cut to `max_width` items, and the number of items left out.
+/// Values shown in Aiken syntax are decoded whole, so they're cut before.
+pub fn shorten(c: &Constant, max_width: usize) -> (Constant, usize) {
+    let mut hidden = 0;
+    let c = shorten_constant(c, max_width, &mut hidden);
+    (c, hidden)
+}
+
+fn shorten_constant(c: &Constant, max_width: usize, hidden: &mut usize) -> Constant {
+    match c {
+        Constant::ProtoList(tipo, items) => {
+            *hidden += items.len().saturating_sub(max_width);
+            let items = items
+                .iter()
+                .take(max_width)
+                .map(|item| shorten_constant(item, max_width, hidden))
+                .collect();
+            Constant::ProtoList(tipo.clone(), items)
+        }
+        Constant::ProtoPair(first_type, second_type, first, second) => Constant::ProtoPair(
+            first_type.clone(),
+            second_type.clone(),
+            Rc::new(shorten_constant(first, max_width, hidden)),
+            Rc::new(shorten_constant(second, max_width, hidden)),
+        ),
+        Constant::Data(d) => Constant::Data(shorten_data(d, max_width, hidden)),
+        _ => c.clone(),
+    }
+}
+
+/// Constructor fields are kept whole, the value wouldn't match its type without them
+fn shorten_data(d: &PlutusData, max_width: usize, hidden: &mut usize) -> PlutusData {
+    match d {
+        PlutusData::Constr(constr) => Data::constr(
+            super::data::constructor_index(constr.tag, constr.any_constructor),
+            constr
+                .fields
+                .iter()
+                .map(|field| shorten_data(field, max_width, hidden))
+                .collect(),
+        ),
+        PlutusData::Map(entries) => {
+            *hidden += entries.len().saturating_sub(max_width);
+            Data::map(
+                entries
+                    .iter()
+                    .take(max_width)
+                    .map(|(key, value)| {
+                        (
+                            shorten_data(key, max_width, hidden),
+                            shorten_data(value, max_width, hidden),
+                        )
+                    })
+                    .collect(),
+            )
+        }
+        PlutusData::Array(items) => {
+            *hidden += items.len().saturating_sub(max_width);
+            Data::list(
+                items
+                    .iter()
+                    .take(max_width)
+                    .map(|item| shorten_data(item, max_width, hidden))
+                    .collect(),
+            )
+        }
+        _ => d.clone(),
+    }
+}
+
+/// `… 99,900 more items`
+pub fn more_items(hidden: usize) -> String {
+    format!("… {} more items", count(hidden))
+}
+
+/// `n` with thousands separated by commas, eg. `99,900`
+fn count(n: usize) -> String {
+    let digits = n.to_string();
+    let mut grouped = String::new();
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i) % 3 == 0 {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    grouped
+}
+
+fn constant(c: &Constant, settings: &DisplaySettings, depth: usize) -> String {
+    match c {
+        Constant::Integer(i) => i.to_string(),
+        Constant::ByteString(bs) => bytes(bs, settings.bytearray),
+        Constant::String(s) => format!("{:?}", s),
+        Constant::Bool(b) => if *b { "True" } else { "False" }.to_string(),
+        Constant::Unit => "Void".to_string(),
+        Constant::Data(d) => data(d, settings, depth),
+        _ if depth >= settings.max_depth => "…".to_string(),
+        Constant::ProtoList(_, items) => format!(
+            "[{}]",
+            join(items.iter(), settings, |item| constant(
+                item,
+                settings,
+                depth + 1
+            ))
+        ),
+        Constant::ProtoPair(_, _, first, second) => format!(
+            "Pair({}, {})",
+            constant(first, settings, depth + 1),
+            constant(second, settings, depth + 1)
+        ),
+        _ => c.to_pretty(),
+    }
+}
+
+fn data(d: &PlutusData, settings: &DisplaySettings, depth: usize) -> String {
+    match d {
+        PlutusData::BigInt(i) => format!("I {}", from_pallas_bigint(i)),
+        PlutusData::BoundedBytes(bs) => format!("B {}", bytes(&bs[..], settings.bytearray)),
+        _ if depth >= settings.max_depth => "…".to_string(),
+        PlutusData::Constr(constr) => {
+            let index = super::data::constructor_index(constr.tag, constr.any_constructor);
+            format!(
+                "Constr {} [{}]",
+                index,
+                join(constr.fields.iter(), settings, |field| data(
+                    field,
+                    settings,
+                    depth + 1
+                ))
+            )
+        }
+        PlutusData::Map(entries) => format!(
+            "Map [{}]",
+            join(entries.iter(), settings, |(key, value)| format!(
+                "({}, {})",
+                data(key, settings, depth + 1),
+                data(value, settings, depth + 1)
+            ))
+        ),
+        PlutusData::Array(items) => format!(
+            "List [{}]",
+            join(items.iter(), settings, |item| data(
+                item,
+                settings,
+                depth + 1
+            ))
+        ),
+    }
+}
+
+fn bytes(bs: &[u8], display: ByteArrayDisplay) -> String {
+    let hex = format!("#{}", hex::encode(bs));
+    match (display, std::str::from_utf8(bs)) {
+        (ByteArrayDisplay::Utf8, Ok(text)) => format!("{:?}", text),
+        (ByteArrayDisplay::Both, Ok(text)) if !bs.is_empty() => format!("{} ({:?})", hex, text),
+        _ => hex,
+    }
+}
+
+/// Display at most `max_width` items, separated by commas
+fn join<T>(
+    items: impl ExactSizeIterator<Item = T>,
+    settings: &DisplaySettings,
+    show: impl Fn(T) -> String,
+) -> String {
+    let hidden = items.len().saturating_sub(settings.max_width);
+    let mut shown: Vec<String> = items.take(settings.max_width).map(show).collect();
+    if hidden > 0 {
+        shown.push(more_items(hidden));
+    }
+    shown.join(", ")
+}
+
+#[cfg(test)]
+mod test {
+    use std::rc::Rc;
+
+    use uplc::ast::{Constant, Data, Type};
+
+    use super::{ByteArrayDisplay, DisplaySettings, bytes, constant, count, shorten, truncate};
+
+    fn integers(values: &[i64]) -> Constant {
+        Constant::ProtoList(
+            Type::Integer,
+            values
+                .iter()
+                .map(|i| Constant::Integer((*i).into()))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_compound_constants() {
+        let limits = DisplaySettings::default();
+        assert_eq!(constant(&integers(&[1, 2, 3]), &limits, 0), "[1, 2, 3]");
+
+        let pair = Constant::ProtoPair(
+            Type::Integer,
+            Type::String,
+            Rc::new(Constant::Integer(1.into())),
+            Rc::new(Constant::String("one".to_string())),
+        );
+        assert_eq!(constant(&pair, &limits, 0), "Pair(1, \"one\")");
+    }
+
+    #[test]
+    fn test_limits() {
+        let limits = DisplaySettings {
+            max_depth: 1,
+            max_width: 2,
+            ..Default::default()
+        };
+        assert_eq!(
+            constant(&integers(&[1, 2, 3, 4]), &limits, 0),
+            "[1, 2, … 2 more items]"
+        );
+
+        let nested = Constant::ProtoList(Type::List(Rc::new(Type::Integer)), vec![integers(&[1])]);
+        assert_eq!(constant(&nested, &limits, 0), "[…]");
+
+        let unlimited = limits.unlimited();
+        assert_eq!(
+            constant(&integers(&[1, 2, 3, 4]), &unlimited, 0),
+            "[1, 2, 3, 4]"
+        );
+        assert_eq!(unlimited.bytearray, limits.bytearray);
+    }
+
+    #[test]
+    fn test_shorten() {
+        let nested = Constant::ProtoList(
+            Type::List(Rc::new(Type::Integer)),
+            vec![integers(&[1, 2, 3]), integers(&[4]), integers(&[5])],
+        );
+        let (short, hidden) = shorten(&nested, 2);
+        let limits = DisplaySettings::default();
+        assert_eq!(constant(&short, &limits, 0), "[[1, 2], [4]]");
+        assert_eq!(hidden, 2);
+
+        let data = Constant::Data(Data::constr(
+            0,
+            vec![Data::list(
+                (0..5).map(|i| Data::integer(i.into())).collect(),
+            )],
+        ));
+        let (short, hidden) = shorten(&data, 3);
+        assert_eq!(
+            constant(&short, &limits, 0),
+            "Constr 0 [List [I 0, I 1, I 2]]"
+        );
+        assert_eq!(hidden, 2);
+    }
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("short".to_string(), 10), "short");
+        assert_eq!(
+            truncate("é".repeat(12), 10),
+            format!("{}… 2 more characters", "é".repeat(10))
+        );
+        assert_eq!(count(7), "7");
+        assert_eq!(count(99_900), "99,900");
+        assert_eq!(count(1_000_000), "1,000,000");
+    }
+
+    #[test]
+    fn test_bytearray_display() {
+        assert_eq!(bytes(b"hello", ByteArrayDisplay::Hex), "#68656c6c6f");
+        assert_eq!(bytes(b"hello", ByteArrayDisplay::Utf8), "\"hello\"");
+        assert_eq!(
+            bytes(b"hello", ByteArrayDisplay::Both),
+            "#68656c6c6f (\"hello\")"
+        );
+
+        // Bytes that aren't text are always shown as hex
+        assert_eq!(bytes(&[0xff, 0x00], ByteArrayDisplay::Utf8), "#ff00");
+        assert_eq!(bytes(&[0xff, 0x00], ByteArrayDisplay::Both), "#ff00");
+        assert_eq!("both".parse(), Ok(ByteArrayDisplay::Both));
+        assert!("base64".parse::<ByteArrayDisplay>().is_err());
+    }
+}
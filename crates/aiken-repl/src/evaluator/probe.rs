@@ -0,0 +1,141 @@
+//! Comparison of a probe expression across redefinitions
+//!
+//! With a probe set (eg. `validate(sample_datum)`), the expression is compiled and measured
+//! after every change to the definitions. The last measurement is kept, so redefining a function
+//! can report how the script size and the budget of the probe moved.
+
+use std::fmt;
+
+use aiken_lang::plutus_version::PlutusVersion;
+use uplc::{
+    ast::{NamedDeBruijn, Program},
+    machine::cost_model::ExBudget,
+};
+
+use super::{ReplError, cbor};
+
+/// Size and cost of the probe against one generation of definitions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProbeMeasure {
+    /// Size of the serialized script, in bytes
+    pub size: usize,
+    pub budget: ExBudget,
+}
+
+impl fmt::Display for ProbeMeasure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "size {} bytes, mem {}, cpu {}",
+            self.size, self.budget.mem, self.budget.cpu
+        )
+    }
+}
+
+/// Probe expression and its measurement against the current definitions
+#[derive(Debug, Clone)]
+pub(crate) struct Probe {
+    pub code: String,
+    /// None when the probe doesn't compile or fails against the current definitions
+    pub last: Option<ProbeMeasure>,
+}
+
+/// Probe measurements before and after redefining functions
+#[derive(Debug, Clone)]
+pub struct RedefinitionReport {
+    pub probe: String,
+    pub before: Option<ProbeMeasure>,
+    pub after: Option<ProbeMeasure>,
+}
+
+impl fmt::Display for RedefinitionReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let size = |m: ProbeMeasure| m.size as i64;
+        let mem = |m: ProbeMeasure| m.budget.mem;
+        let cpu = |m: ProbeMeasure| m.budget.cpu;
+
+        writeln!(f, "Probe `{}`:", self.probe)?;
+        writeln!(
+            f,
+            "{}",
+            row("size", self.before.map(size), self.after.map(size))
+        )?;
+        writeln!(
+            f,
+            "{}",
+            row("mem", self.before.map(mem), self.after.map(mem))
+        )?;
+        write!(
+            f,
+            "{}",
+            row("cpu", self.before.map(cpu), self.after.map(cpu))
+        )
+    }
+}
+
+/// `label before -> after (difference)`, with `failed` for missing measurements
+fn row(label: &str, before: Option<i64>, after: Option<i64>) -> String {
+    let show = |value: Option<i64>| value.map_or("failed".to_string(), |v| v.to_string());
+    let mut row = format!("  {:<5} {} -> {}", label, show(before), show(after));
+    if let (Some(before), Some(after)) = (before, after) {
+        row.push_str(&format!(" ({:+})", after - before));
+    }
+    row
+}
+
+/// Evaluate the compiled probe and serialize it
+pub fn measure(
+    program: Program<NamedDeBruijn>,
+    plutus_version: PlutusVersion,
+) -> Result<ProbeMeasure, ReplError> {
+    let result = program
+        .clone()
+        .eval_version(ExBudget::max(), &plutus_version.into());
+    let budget = result.cost();
+    if let Err(err) = result.result {
+        return Err(ReplError::EvaluationFailed {
+            message: format!("Evaluation failed: {:?}", err),
+        });
+    }
+
+    let size = cbor::encode_program(program)?.cbor.len();
+    Ok(ProbeMeasure { size, budget })
+}
+
+#[cfg(test)]
+mod test {
+    use uplc::machine::cost_model::ExBudget;
+
+    use super::{ProbeMeasure, RedefinitionReport};
+
+    #[test]
+    fn test_report_display() {
+        let report = RedefinitionReport {
+            probe: "add(1, 2)".to_string(),
+            before: Some(ProbeMeasure {
+                size: 20,
+                budget: ExBudget {
+                    mem: 500,
+                    cpu: 1000,
+                },
+            }),
+            after: Some(ProbeMeasure {
+                size: 16,
+                budget: ExBudget {
+                    mem: 600,
+                    cpu: 1000,
+                },
+            }),
+        };
+        assert_eq!(
+            format!("{}", report),
+            "Probe `add(1, 2)`:\n  size  20 -> 16 (-4)\n  mem   500 -> 600 (+100)\n  cpu   1000 -> 1000 (+0)"
+        );
+
+        let report = RedefinitionReport {
+            after: None,
+            ..report
+        };
+        assert!(format!("{}", report).contains("size  20 -> failed\n"));
+    }
+}
@@ -0,0 +1,432 @@
+//! Sampling profiler of the budget of an expression
+//!
+//! The UPLC machine can't be stepped from outside, so the program is instrumented instead: named
+//! functions and builtins log a marker when entered and left. Evaluating the instrumented program
+//! with a fraction of its budget stops it part way, and the markers logged so far give the call
+//! stack at that point. Sampling that over the whole budget tells where it goes, the same way a
+//! sampling profiler interrupts a process.
+//!
+//! Markers cost budget of their own. The cost of one marked call is measured once and taken off
+//! the estimates, but builtins also get a wrapper of a few machine steps, so cheap builtins look a
+//! little more expensive than they are.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt, fs,
+    path::Path,
+    rc::Rc,
+};
+
+use aiken_lang::plutus_version::PlutusVersion;
+use uplc::{
+    ast::{Constant, DeBruijn, NamedDeBruijn, Program, Term},
+    builtins::DefaultFunction,
+    machine::cost_model::ExBudget,
+};
+
+use super::{ReplError, SourceMap};
+
+/// Number of budget-limited evaluations to sample the call stack with
+const SAMPLES: i64 = 100;
+/// Frames shown in the table
+const ROWS: usize = 20;
+/// Prefix of the markers, so user traces can't be mistaken for them
+const MARKER: char = '\u{1}';
+/// Frame of the budget spent outside of any named function or builtin
+const TOP_LEVEL: &str = "(top level)";
+
+/// Budget spent in a named function or builtin
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    pub name: String,
+    /// Line of the definition the frame comes from, for functions of the session
+    pub line: Option<usize>,
+    pub calls: u64,
+    /// Estimated cpu spent in the frame itself, not in the frames it calls
+    pub self_cpu: i64,
+    /// Estimated cpu spent in the frame and the frames it calls
+    pub total_cpu: i64,
+}
+
+/// Where the budget of an expression goes, most expensive frames first
+#[derive(Debug, Clone)]
+pub struct Profile {
+    /// Budget of the expression without instrumentation
+    pub budget: ExBudget,
+    pub frames: Vec<Frame>,
+    /// Cpu of the sampled call stacks, by stack in the collapsed format of flamegraph tools:
+    /// frames from the outermost, separated by `;`
+    pub stacks: BTreeMap<String, i64>,
+}
+
+impl Profile {
+    /// Stacks in the collapsed format, one `<stack> <cpu>` per line, as read by flamegraph.pl
+    /// or inferno
+    pub fn folded(&self) -> String {
+        self.stacks
+            .iter()
+            .map(|(stack, cpu)| format!("{} {}\n", stack, cpu))
+            .collect()
+    }
+
+    pub fn write_folded(&self, path: &Path) -> Result<(), ReplError> {
+        fs::write(path, self.folded())?;
+        Ok(())
+    }
+}
+
+/// Arguments of `:flamegraph [-o <path>] <expr>`
+#[derive(Debug, PartialEq, Eq)]
+pub struct FlamegraphArgs<'a> {
+    /// File to write the collapsed stacks to
+    pub output: Option<&'a str>,
+    pub code: &'a str,
+}
+
+impl<'a> FlamegraphArgs<'a> {
+    pub fn parse(args: &'a str) -> Result<Self, ReplError> {
+        let usage = || ReplError::ParseFailed {
+            message: "expected [-o <path>] <expr>".to_string(),
+        };
+        let args = args.trim();
+        let (output, code) = match args.strip_prefix("-o ") {
+            Some(rest) => {
+                let (path, code) = rest
+                    .trim_start()
+                    .split_once(char::is_whitespace)
+                    .ok_or_else(usage)?;
+                (Some(path), code.trim())
+            }
+            None => (None, args),
+        };
+        if code.is_empty() {
+            return Err(usage());
+        }
+        Ok(Self { output, code })
+    }
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Budget: mem {}, cpu {} ({} samples)\n{:>12} {:>7} {:>7} {:>8}  name",
+            self.budget.mem, self.budget.cpu, SAMPLES, "self cpu", "self", "total", "calls"
+        )?;
+        let share = |cpu: i64| 100.0 * cpu as f64 / self.budget.cpu.max(1) as f64;
+        for frame in self.frames.iter().take(ROWS) {
+            write!(
+                f,
+                "\n{:>12} {:>6.1}% {:>6.1}% {:>8}  {}",
+                frame.self_cpu,
+                share(frame.self_cpu),
+                share(frame.total_cpu),
+                frame.calls,
+                frame.name
+            )?;
+            if let Some(line) = frame.line {
+                write!(f, " (line {})", line)?;
+            }
+        }
+        if self.frames.len() > ROWS {
+            write!(f, "\n... and {} more", self.frames.len() - ROWS)?;
+        }
+        Ok(())
+    }
+}
+
+/// Profile the budget of `program`
+pub fn profile_program(
+    program: &Program<NamedDeBruijn>,
+    plutus_version: PlutusVersion,
+    source_map: &SourceMap,
+) -> Result<Profile, ReplError> {
+    let budget = eval(program.clone(), plutus_version, ExBudget::max())?.0;
+    let instrumented = Program {
+        version: program.version,
+        term: instrument(&program.term),
+    };
+    let (instrumented_budget, logs) = eval(instrumented.clone(), plutus_version, ExBudget::max())?;
+    let overhead = marker_overhead(program.version, plutus_version)?;
+
+    // Calls and markers nested in them come from the complete run
+    let mut frames: HashMap<String, FrameCounts> = HashMap::new();
+    let mut stack: Vec<(String, usize)> = Vec::new();
+    let mut nested_calls = 0;
+    for marker in logs.iter().filter_map(|log| parse_marker(log)) {
+        match marker {
+            Marker::Enter(name) => {
+                frames.entry(name.to_string()).or_default().calls += 1;
+                stack.push((name.to_string(), nested_calls));
+                nested_calls += 1;
+            }
+            Marker::Exit => {
+                if let Some((name, before)) = stack.pop()
+                    && !stack.iter().any(|(outer, _)| *outer == name)
+                {
+                    // Recursive calls are already in the outermost one
+                    frames.entry(name).or_default().marked_calls += (nested_calls - before) as i64;
+                }
+            }
+        }
+    }
+
+    // Stacks at evenly spaced fractions of the budget
+    let sample_cpu = instrumented_budget.cpu / SAMPLES;
+    let mut top_level = 0;
+    let mut stacks: BTreeMap<String, i64> = BTreeMap::new();
+    for i in 0..SAMPLES {
+        let limit = ExBudget {
+            cpu: instrumented_budget.cpu * (2 * i + 1) / (2 * SAMPLES),
+            ..ExBudget::max()
+        };
+        let logs = instrumented
+            .clone()
+            .eval_version(limit, &plutus_version.into())
+            .logs();
+        let stack = call_stack(&logs);
+        let collapsed: Vec<&str> = std::iter::once(TOP_LEVEL)
+            .chain(stack.iter().copied())
+            .collect();
+        *stacks.entry(collapsed.join(";")).or_default() += sample_cpu;
+        match stack.last() {
+            Some(name) => frames.entry(name.to_string()).or_default().self_samples += 1,
+            None => top_level += 1,
+        }
+        let mut seen: Vec<&str> = Vec::new();
+        for name in stack {
+            if !seen.contains(&name) {
+                frames.entry(name.to_string()).or_default().total_samples += 1;
+                seen.push(name);
+            }
+        }
+    }
+
+    let mut frames: Vec<Frame> = frames
+        .into_iter()
+        .map(|(name, counts)| Frame {
+            line: source_map.get(&name).map(|mapping| mapping.line),
+            name,
+            calls: counts.calls,
+            self_cpu: (counts.self_samples * sample_cpu - counts.calls as i64 * overhead).max(0),
+            total_cpu: (counts.total_samples * sample_cpu - counts.marked_calls * overhead).max(0),
+        })
+        .collect();
+    let top_level_cpu = (top_level * sample_cpu).min(budget.cpu);
+    frames.push(Frame {
+        name: TOP_LEVEL.to_string(),
+        line: None,
+        calls: 1,
+        self_cpu: top_level_cpu,
+        total_cpu: budget.cpu,
+    });
+    frames.sort_by(|a, b| b.self_cpu.cmp(&a.self_cpu).then(a.name.cmp(&b.name)));
+
+    Ok(Profile {
+        budget,
+        frames,
+        stacks,
+    })
+}
+
+#[derive(Debug, Default)]
+struct FrameCounts {
+    calls: u64,
+    /// Marked calls within the outermost calls of the frame, including themselves
+    marked_calls: i64,
+    self_samples: i64,
+    total_samples: i64,
+}
+
+fn eval(
+    program: Program<NamedDeBruijn>,
+    plutus_version: PlutusVersion,
+    budget: ExBudget,
+) -> Result<(ExBudget, Vec<String>), ReplError> {
+    let result = program.eval_version(budget, &plutus_version.into());
+    let cost = result.cost();
+    let logs = result.logs();
+    match result.result {
+        Ok(_) => Ok((cost, logs)),
+        Err(err) => Err(ReplError::EvaluationFailed {
+            message: format!("Evaluation failed: {:?}", err),
+        }),
+    }
+}
+
+/// Cpu of the markers of one call
+fn marker_overhead(
+    version: (usize, usize, usize),
+    plutus_version: PlutusVersion,
+) -> Result<i64, ReplError> {
+    let unit = || Term::Constant(Rc::new(Constant::Unit));
+    let cost = |term| {
+        eval(Program { version, term }, plutus_version, ExBudget::max()).map(|(cost, _)| cost.cpu)
+    };
+    Ok(cost(marked("", unit()))? - cost(unit())?)
+}
+
+enum Marker<'a> {
+    Enter(&'a str),
+    Exit,
+}
+
+fn parse_marker(log: &str) -> Option<Marker<'_>> {
+    let marker = log.strip_prefix(MARKER)?;
+    marker
+        .strip_prefix('>')
+        .map(Marker::Enter)
+        .or_else(|| marker.starts_with('<').then_some(Marker::Exit))
+}
+
+/// Frames entered and not left yet by the end of `logs`, outermost first
+fn call_stack(logs: &[String]) -> Vec<&str> {
+    let mut stack = Vec::new();
+    for marker in logs.iter().filter_map(|log| parse_marker(log)) {
+        match marker {
+            Marker::Enter(name) => stack.push(name),
+            Marker::Exit => {
+                stack.pop();
+            }
+        }
+    }
+    stack
+}
+
+/// Mark the bodies of named functions (`[(lam f rest) (lam x ...)]`) and the calls of builtins
+fn instrument(term: &Term<NamedDeBruijn>) -> Term<NamedDeBruijn> {
+    match term {
+        Term::Apply { function, argument } => {
+            let argument = match (function.as_ref(), argument.as_ref()) {
+                (Term::Lambda { parameter_name, .. }, Term::Lambda { .. }) => {
+                    mark_function(&parameter_name.text, argument)
+                }
+                _ => instrument(argument),
+            };
+            Term::Apply {
+                function: Rc::new(instrument(function)),
+                argument: Rc::new(argument),
+            }
+        }
+        Term::Lambda {
+            parameter_name,
+            body,
+        } => Term::Lambda {
+            parameter_name: parameter_name.clone(),
+            body: Rc::new(instrument(body)),
+        },
+        Term::Delay(body) => Term::Delay(Rc::new(instrument(body))),
+        Term::Force(body) => Term::Force(Rc::new(instrument(body))),
+        Term::Constr { tag, fields } => Term::Constr {
+            tag: *tag,
+            fields: fields.iter().map(instrument).collect(),
+        },
+        Term::Case { constr, branches } => Term::Case {
+            constr: Rc::new(instrument(constr)),
+            branches: branches.iter().map(instrument).collect(),
+        },
+        Term::Builtin(fun) if *fun != DefaultFunction::Trace => mark_builtin(*fun),
+        _ => term.clone(),
+    }
+}
+
+/// Mark the body of a function, under all of its parameters
+fn mark_function(name: &str, term: &Term<NamedDeBruijn>) -> Term<NamedDeBruijn> {
+    match term {
+        Term::Lambda {
+            parameter_name,
+            body,
+        } => Term::Lambda {
+            parameter_name: parameter_name.clone(),
+            body: Rc::new(mark_function(name, body)),
+        },
+        _ => marked(name, instrument(term)),
+    }
+}
+
+/// A builtin behind a closed wrapper of the same arity and forces, so it can replace it anywhere:
+/// `(delay (lam a (lam b <marked [(force (builtin f)) a b]>)))`
+fn mark_builtin(fun: DefaultFunction) -> Term<NamedDeBruijn> {
+    let arity = fun.arity();
+    let mut builtin = Term::Builtin(fun);
+    for _ in 0..fun.force_count() {
+        builtin = Term::Force(Rc::new(builtin));
+    }
+    let mut call = builtin;
+    for i in 0..arity {
+        call = Term::Apply {
+            function: Rc::new(call),
+            argument: Rc::new(Term::Var(Rc::new(NamedDeBruijn {
+                text: format!("arg_{}", i),
+                index: DeBruijn::new(arity - i),
+            }))),
+        };
+    }
+
+    let mut wrapper = marked(&fun.to_string(), call);
+    for i in (0..arity).rev() {
+        wrapper = Term::Lambda {
+            parameter_name: Rc::new(NamedDeBruijn {
+                text: format!("arg_{}", i),
+                index: DeBruijn::new(0),
+            }),
+            body: Rc::new(wrapper),
+        };
+    }
+    for _ in 0..fun.force_count() {
+        wrapper = Term::Delay(Rc::new(wrapper));
+    }
+    wrapper
+}
+
+/// `term`, logging a marker before and after evaluating it. No variable is bound, so the
+/// indices in `term` stay the same: `[(trace "<name") (force [(trace ">name") (delay term)])]`
+fn marked(name: &str, term: Term<NamedDeBruijn>) -> Term<NamedDeBruijn> {
+    let enter = trace(format!("{}>{}", MARKER, name), Term::Delay(Rc::new(term)));
+    trace(format!("{}<{}", MARKER, name), Term::Force(Rc::new(enter)))
+}
+
+fn trace(message: String, term: Term<NamedDeBruijn>) -> Term<NamedDeBruijn> {
+    Term::Apply {
+        function: Rc::new(Term::Apply {
+            function: Rc::new(Term::Force(Rc::new(Term::Builtin(DefaultFunction::Trace)))),
+            argument: Rc::new(Term::Constant(Rc::new(Constant::String(message)))),
+        }),
+        argument: Rc::new(term),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{FlamegraphArgs, MARKER, call_stack};
+
+    #[test]
+    fn test_call_stack() {
+        let logs: Vec<String> = [">main", ">foo", "<foo", ">bar", ">addInteger"]
+            .iter()
+            .map(|marker| format!("{}{}", MARKER, marker))
+            .chain(["user trace".to_string()])
+            .collect();
+        assert_eq!(call_stack(&logs), vec!["main", "bar", "addInteger"]);
+    }
+
+    #[test]
+    fn test_parse_flamegraph_args() {
+        assert_eq!(
+            FlamegraphArgs::parse("sum(10)").unwrap(),
+            FlamegraphArgs {
+                output: None,
+                code: "sum(10)",
+            }
+        );
+        assert_eq!(
+            FlamegraphArgs::parse("-o sum.folded sum(10)").unwrap(),
+            FlamegraphArgs {
+                output: Some("sum.folded"),
+                code: "sum(10)",
+            }
+        );
+        assert!(FlamegraphArgs::parse("-o sum.folded").is_err());
+        assert!(FlamegraphArgs::parse("").is_err());
+    }
+}
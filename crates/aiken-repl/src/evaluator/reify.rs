@@ -0,0 +1,80 @@
+//! Display of results as Aiken values, using the type of the evaluated expression
+//!
+//! UPLC only knows about integers, bytes, lists, pairs and data, so `Some(42)` comes back as
+//! `Constr 0 [I 42]`. Aiken turns counterexamples of property tests back into Aiken expressions
+//! from their type; the same reification is used here on results.
+
+use std::rc::Rc;
+
+use aiken_lang::{
+    IdGenerator,
+    ast::{DataTypeKey, Definition},
+    builtins,
+    expr::UntypedExpr,
+    format::Formatter,
+    tipo::{Type, TypeVar, pretty::Printer},
+};
+use aiken_project::Project;
+use uplc::ast::{NamedDeBruijn, Term};
+
+use super::events;
+
+/// Width the formatter wraps values at
+pub const LINE_WIDTH: isize = 80;
+
+/// `<fn: (Int, Int) -> Int>`, what a result of type `tipo` shows when it's a function
+pub fn function(tipo: &Rc<Type>) -> Option<String> {
+    match tipo.as_ref() {
+        Type::Fn { args, ret, .. } => {
+            let mut printer = Printer::new();
+            let args: Vec<String> = args
+                .iter()
+                .map(|arg| printer.pretty_print(arg, 0))
+                .collect();
+            Some(format!(
+                "<fn: ({}) -> {}>",
+                args.join(", "),
+                printer.pretty_print(ret, 0)
+            ))
+        }
+        Type::Var { tipo, .. } => match &*tipo.borrow() {
+            TypeVar::Link { tipo } => function(tipo),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Aiken syntax of a result of type `tipo`, or None when it can't be decoded (eg. functions)
+pub fn value(
+    project: &Project<events::Progress>,
+    term: &Term<NamedDeBruijn>,
+    tipo: Rc<Type>,
+) -> Option<String> {
+    let Term::Constant(constant) = term else {
+        return None;
+    };
+
+    // Constructors of the prelude (Option, Ordering, ...) and of every module of the project
+    let mut data_types = builtins::prelude_data_types(&IdGenerator::new());
+    let modules = project.modules();
+    for module in &modules {
+        for definition in module.ast.definitions() {
+            if let Definition::DataType(data_type) = definition {
+                let key = DataTypeKey {
+                    module_name: module.name.clone(),
+                    defined_type: data_type.name.clone(),
+                };
+                data_types.insert(key, data_type.clone());
+            }
+        }
+    }
+    let data_types = data_types.iter().collect();
+
+    let expr = UntypedExpr::reify_constant(&data_types, constant.as_ref().clone(), tipo).ok()?;
+    Some(
+        Formatter::new()
+            .expr(&expr, false)
+            .to_pretty_string(LINE_WIDTH),
+    )
+}
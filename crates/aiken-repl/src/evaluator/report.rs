@@ -0,0 +1,124 @@
+//! Results of running tests of the session
+
+use std::fmt;
+
+use uplc::machine::cost_model::ExBudget;
+
+/// Outcome of a single test
+#[derive(Debug, Clone)]
+pub struct TestOutcome {
+    pub name: String,
+    pub passed: bool,
+    /// Cost of unit tests
+    pub budget: Option<ExBudget>,
+    /// Number of runs of property tests
+    pub iterations: Option<usize>,
+    /// Messages of the `trace` builtin, in order
+    pub traces: Vec<String>,
+    /// Smallest input found to fail a property test, in Aiken syntax
+    pub counterexample: Option<String>,
+    /// Labels of a property test, most frequent first, with the percentage of runs or of all
+    /// labels that gave them, depending on the coverage mode
+    pub coverage: Vec<(String, f64)>,
+}
+
+/// Outcomes of a test run, in the order tests were run
+#[derive(Debug, Clone, Default)]
+pub struct TestReport {
+    pub tests: Vec<TestOutcome>,
+    /// Seed of the property tests, to run them again with the same inputs
+    pub seed: Option<u32>,
+}
+
+impl TestReport {
+    pub fn passed(&self) -> usize {
+        self.tests.iter().filter(|test| test.passed).count()
+    }
+
+    pub fn failed(&self) -> usize {
+        self.tests.len() - self.passed()
+    }
+}
+
+impl fmt::Display for TestOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", if self.passed { "PASS" } else { "FAIL" })?;
+        if let Some(budget) = &self.budget {
+            write!(f, " [mem: {}, cpu: {}]", budget.mem, budget.cpu)?;
+        }
+        if let Some(iterations) = self.iterations {
+            write!(f, " [after {} tests]", iterations)?;
+        }
+        write!(f, " {}", self.name)?;
+        if let Some(counterexample) = &self.counterexample {
+            write!(f, "\n    counterexample: {}", counterexample)?;
+        }
+        for (label, percent) in &self.coverage {
+            write!(f, "\n    {:>6.2}% {}", percent, label)?;
+        }
+        for trace in &self.traces {
+            write!(f, "\n    ↳ {}", trace)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for TestReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for test in &self.tests {
+            writeln!(f, "{}", test)?;
+        }
+        write!(
+            f,
+            "{} test{} | {} passed | {} failed",
+            self.tests.len(),
+            if self.tests.len() == 1 { "" } else { "s" },
+            self.passed(),
+            self.failed()
+        )?;
+        match self.seed {
+            Some(seed) => write!(f, " | seed {}", seed),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use uplc::machine::cost_model::ExBudget;
+
+    use super::{TestOutcome, TestReport};
+
+    #[test]
+    fn test_report_display() {
+        let report = TestReport {
+            tests: vec![
+                TestOutcome {
+                    name: "adds".to_string(),
+                    passed: true,
+                    budget: Some(ExBudget { mem: 10, cpu: 20 }),
+                    iterations: None,
+                    traces: Vec::new(),
+                    counterexample: None,
+                    coverage: Vec::new(),
+                },
+                TestOutcome {
+                    name: "commutes".to_string(),
+                    passed: false,
+                    budget: None,
+                    iterations: Some(42),
+                    traces: vec!["a + b != b + a".to_string()],
+                    counterexample: Some("(0, 1)".to_string()),
+                    coverage: vec![("zero".to_string(), 12.5)],
+                },
+            ],
+            seed: Some(42),
+        };
+        assert_eq!(
+            format!("{}", report),
+            "PASS [mem: 10, cpu: 20] adds\n\
+             FAIL [after 42 tests] commutes\n    counterexample: (0, 1)\n     12.50% zero\n    ↳ a + b != b + a\n\
+             2 tests | 1 passed | 1 failed | seed 42"
+        );
+    }
+}
@@ -0,0 +1,389 @@
+//! Simulation of the scripts of a serialized transaction, to debug real failures
+//!
+//! Scripts run as the ledger would in phase two, with the UTxOs the transaction spends or
+//! references. They come from the transaction's witnesses and from reference inputs, so a
+//! validator of the session runs when the transaction was built with its compiled code.
+//!
+//! Without a file of UTxOs, the inputs of the transaction are looked up with the chain provider.
+
+use std::{fmt, fs, path::Path, str::FromStr};
+
+use uplc::{machine::cost_model::ExBudget, tx};
+
+use super::{ReplError, chain::ChainProvider};
+
+/// Pairs of the CBOR of an output reference and of its output, as the ledger resolves inputs
+type RawUtxos = Vec<(Vec<u8>, Vec<u8>)>;
+
+/// Slot configurations of the public networks, as (zero time, zero slot, slot length)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Network {
+    #[default]
+    Mainnet,
+    Preprod,
+    Preview,
+}
+
+impl Network {
+    fn slot_config(self) -> (u64, u64, u32) {
+        match self {
+            Self::Mainnet => (1596059091000, 4492800, 1000),
+            Self::Preprod => (1655769600000, 86400, 1000),
+            Self::Preview => (1666656000000, 0, 1000),
+        }
+    }
+}
+
+impl fmt::Display for Network {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Mainnet => write!(f, "mainnet"),
+            Self::Preprod => write!(f, "preprod"),
+            Self::Preview => write!(f, "preview"),
+        }
+    }
+}
+
+impl FromStr for Network {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "mainnet" => Ok(Self::Mainnet),
+            "preprod" => Ok(Self::Preprod),
+            "preview" => Ok(Self::Preview),
+            _ => Err(format!(
+                "unknown network `{}`, expected mainnet, preprod or preview",
+                s
+            )),
+        }
+    }
+}
+
+/// Arguments of `:simulate <tx> [--inputs <utxos.json>] [--network mainnet|preprod|preview]`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SimulateArgs<'a> {
+    pub tx: &'a str,
+    /// UTxOs of the transaction, or else they're looked up on the chain
+    pub inputs: Option<&'a str>,
+    /// Network of the transaction, by default the chain provider's or mainnet
+    pub network: Option<Network>,
+}
+
+impl<'a> SimulateArgs<'a> {
+    pub fn parse(args: &'a str) -> Result<Self, ReplError> {
+        let usage = || ReplError::SimulationFailed {
+            message: "expected <tx> [--inputs <utxos.json>] [--network mainnet|preprod|preview]"
+                .to_string(),
+        };
+        let (mut tx, mut inputs, mut network) = (None, None, None);
+        let mut words = args.split_whitespace();
+        while let Some(word) = words.next() {
+            match word {
+                "--inputs" => inputs = Some(words.next().ok_or_else(usage)?),
+                "--network" => {
+                    network = Some(
+                        words
+                            .next()
+                            .ok_or_else(usage)?
+                            .parse()
+                            .map_err(|message| ReplError::SimulationFailed { message })?,
+                    )
+                }
+                _ if tx.is_none() && !word.starts_with("--") => tx = Some(word),
+                _ => return Err(usage()),
+            }
+        }
+        Ok(Self {
+            tx: tx.ok_or_else(usage)?,
+            inputs,
+            network,
+        })
+    }
+}
+
+/// Budget and traces of a redeemer's script
+#[derive(Debug, Clone)]
+pub struct RedeemerRun {
+    /// Purpose and index of the redeemer, eg. `Spend[0]`
+    pub redeemer: String,
+    pub budget: ExBudget,
+    pub traces: Vec<String>,
+}
+
+/// Runs of every script of a transaction
+#[derive(Debug, Clone)]
+pub struct Simulation {
+    pub runs: Vec<RedeemerRun>,
+}
+
+impl fmt::Display for Simulation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.runs.is_empty() {
+            return write!(f, "No scripts to run");
+        }
+        let mut total = ExBudget { mem: 0, cpu: 0 };
+        for run in &self.runs {
+            writeln!(
+                f,
+                "{}: mem {}, cpu {}",
+                run.redeemer, run.budget.mem, run.budget.cpu
+            )?;
+            for trace in &run.traces {
+                writeln!(f, "  {}", trace)?;
+            }
+            total.mem += run.budget.mem;
+            total.cpu += run.budget.cpu;
+        }
+        write!(f, "Total: mem {}, cpu {}", total.mem, total.cpu)
+    }
+}
+
+/// Run the scripts of the transaction at `args.tx` (CBOR, as hex or raw bytes) against the
+/// UTxOs of `args.inputs`: a JSON list of `{"input": "<cbor hex>", "output": "<cbor hex>"}`.
+pub fn simulate(
+    args: &SimulateArgs,
+    chain: Option<&dyn ChainProvider>,
+) -> Result<Simulation, ReplError> {
+    let tx = read_cbor(Path::new(args.tx))?;
+    let utxos = match (args.inputs, chain) {
+        (Some(inputs), _) => read_utxos(Path::new(inputs))?,
+        (None, Some(chain)) => resolve_inputs(&tx, chain)?,
+        (None, None) => {
+            return Err(failed(
+                "give the UTxOs with --inputs, or configure a chain provider to look them up"
+                    .to_string(),
+            ));
+        }
+    };
+    let network = args
+        .network
+        .or(chain.map(|chain| chain.network()))
+        .unwrap_or_default();
+
+    let max = ExBudget::default();
+    let results = tx::eval_phase_two_raw(
+        &tx,
+        &utxos,
+        None,
+        (max.cpu as u64, max.mem as u64),
+        network.slot_config(),
+        true,
+        |_| (),
+    )
+    .map_err(|e| failed(format!("{}", e)))?;
+
+    Ok(Simulation {
+        runs: results
+            .into_iter()
+            .map(|(redeemer, result)| RedeemerRun {
+                redeemer: redeemer_pointer(&redeemer)
+                    .map(|(tag, index)| format!("{}[{}]", purpose(tag), index))
+                    .unwrap_or_else(|| "Redeemer".to_string()),
+                budget: result.cost(),
+                traces: result.logs(),
+            })
+            .collect(),
+    })
+}
+
+fn failed(message: String) -> ReplError {
+    ReplError::SimulationFailed { message }
+}
+
+/// Bytes of a CBOR file, written either as hex text or as raw bytes
+fn read_cbor(path: &Path) -> Result<Vec<u8>, ReplError> {
+    let bytes = fs::read(path).map_err(|e| failed(format!("{}: {}", path.display(), e)))?;
+    match std::str::from_utf8(&bytes).map(str::trim) {
+        Ok(text) if text.chars().all(|c| c.is_ascii_hexdigit()) => {
+            hex::decode(text).map_err(|e| failed(format!("{}: {}", path.display(), e)))
+        }
+        _ => Ok(bytes),
+    }
+}
+
+fn read_utxos(path: &Path) -> Result<RawUtxos, ReplError> {
+    let invalid = |message: String| failed(format!("{}: {}", path.display(), message));
+    let content = fs::read_to_string(path).map_err(|e| invalid(e.to_string()))?;
+    let json: serde_json::Value =
+        serde_json::from_str(&content).map_err(|e| invalid(e.to_string()))?;
+
+    json.as_array()
+        .ok_or_else(|| invalid("expected a list of UTxOs".to_string()))?
+        .iter()
+        .map(|utxo| {
+            let field = |name: &str| {
+                utxo[name]
+                    .as_str()
+                    .and_then(|text| hex::decode(text).ok())
+                    .ok_or_else(|| {
+                        invalid(format!("every UTxO needs the CBOR hex of its {}", name))
+                    })
+            };
+            Ok((field("input")?, field("output")?))
+        })
+        .collect()
+}
+
+/// UTxOs spent, used as collateral or referenced by the transaction, from the chain
+fn resolve_inputs(tx: &[u8], chain: &dyn ChainProvider) -> Result<RawUtxos, ReplError> {
+    let inputs = transaction_inputs(tx)
+        .ok_or_else(|| failed("couldn't read the inputs of the transaction".to_string()))?;
+    inputs
+        .iter()
+        .map(|(tx_hash, index)| {
+            let utxo = chain.utxo(&hex::encode(tx_hash), *index)?;
+            Ok((utxo.input_cbor()?, utxo.output_cbor()?))
+        })
+        .collect()
+}
+
+/// Output references of the inputs (field 0), collateral (13) and reference inputs (18) of a
+/// transaction's body
+fn transaction_inputs(tx: &[u8]) -> Option<Vec<(Vec<u8>, u64)>> {
+    let mut cbor = tx;
+    let (4, _) = cbor_head(&mut cbor)? else {
+        return None;
+    };
+    let (5, fields) = cbor_head(&mut cbor)? else {
+        return None;
+    };
+    let mut inputs = Vec::new();
+    for _ in 0..fields {
+        let (0, key) = cbor_head(&mut cbor)? else {
+            return None;
+        };
+        if !matches!(key, 0 | 13 | 18) {
+            cbor_skip(&mut cbor)?;
+            continue;
+        }
+        // Sets may be tagged with 258
+        let mut head = cbor_head(&mut cbor)?;
+        if head.0 == 6 {
+            head = cbor_head(&mut cbor)?;
+        }
+        let (4, count) = head else {
+            return None;
+        };
+        for _ in 0..count {
+            let ((4, 2), (2, size)) = (cbor_head(&mut cbor)?, cbor_head(&mut cbor)?) else {
+                return None;
+            };
+            let tx_hash = cbor.get(..size as usize)?.to_vec();
+            cbor = &cbor[size as usize..];
+            let (0, index) = cbor_head(&mut cbor)? else {
+                return None;
+            };
+            inputs.push((tx_hash, index));
+        }
+    }
+    Some(inputs)
+}
+
+fn purpose(tag: u64) -> &'static str {
+    match tag {
+        0 => "Spend",
+        1 => "Mint",
+        2 => "Publish",
+        3 => "Withdraw",
+        4 => "Vote",
+        5 => "Propose",
+        _ => "Redeemer",
+    }
+}
+
+/// Tag and index of an evaluated redeemer, encoded as `[tag, index, data, ex_units]`
+fn redeemer_pointer(mut cbor: &[u8]) -> Option<(u64, u64)> {
+    let ((4, 4), (0, tag), (0, index)) = (
+        cbor_head(&mut cbor)?,
+        cbor_head(&mut cbor)?,
+        cbor_head(&mut cbor)?,
+    ) else {
+        return None;
+    };
+    Some((tag, index))
+}
+
+/// Major type and argument of the next CBOR item. Indefinite lengths aren't supported.
+fn cbor_head(cbor: &mut &[u8]) -> Option<(u8, u64)> {
+    let (&first, rest) = cbor.split_first()?;
+    let size = match first & 0x1f {
+        n @ 0..=23 => {
+            *cbor = rest;
+            return Some((first >> 5, n as u64));
+        }
+        24 => 1,
+        25 => 2,
+        26 => 4,
+        27 => 8,
+        _ => return None,
+    };
+    let value = rest
+        .get(..size)?
+        .iter()
+        .fold(0u64, |value, byte| (value << 8) | *byte as u64);
+    *cbor = &rest[size..];
+    Some((first >> 5, value))
+}
+
+fn cbor_skip(cbor: &mut &[u8]) -> Option<()> {
+    let (major, argument) = cbor_head(cbor)?;
+    match major {
+        2 | 3 => *cbor = cbor.get(argument as usize..)?,
+        4 => (0..argument).try_for_each(|_| cbor_skip(cbor))?,
+        5 => (0..argument * 2).try_for_each(|_| cbor_skip(cbor))?,
+        6 => cbor_skip(cbor)?,
+        _ => (),
+    }
+    Some(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Network, SimulateArgs, redeemer_pointer, transaction_inputs};
+
+    #[test]
+    fn test_parse_args() {
+        assert_eq!(
+            SimulateArgs::parse("tx.cbor --inputs utxos.json --network preview").unwrap(),
+            SimulateArgs {
+                tx: "tx.cbor",
+                inputs: Some("utxos.json"),
+                network: Some(Network::Preview),
+            }
+        );
+        assert_eq!(SimulateArgs::parse("tx.cbor").unwrap().inputs, None);
+        assert!(SimulateArgs::parse("--inputs utxos.json").is_err());
+        assert!(SimulateArgs::parse("tx.cbor --inputs utxos.json --network moon").is_err());
+    }
+
+    #[test]
+    fn test_redeemer_pointer() {
+        // Mint redeemer at index 300, with its data and budget left out
+        assert_eq!(
+            redeemer_pointer(&[0x84, 0x01, 0x19, 0x01, 0x2c]),
+            Some((1, 300))
+        );
+        assert_eq!(redeemer_pointer(&[0x83, 0x01, 0x00]), None);
+    }
+
+    #[test]
+    fn test_transaction_inputs() {
+        let hash = "ab".repeat(32);
+        // A body with a fee (2), a set of two inputs (0) and a reference input (18)
+        let input = |index: &str| format!("825820{}{}", hash, index);
+        let tx = hex::decode(format!(
+            "84a3021a0002a00000d9010282{}{}1281{}a0f5f6",
+            input("00"),
+            input("01"),
+            input("03")
+        ))
+        .unwrap();
+        let hash = hex::decode(hash).unwrap();
+        assert_eq!(
+            transaction_inputs(&tx),
+            Some(vec![(hash.clone(), 0), (hash.clone(), 1), (hash, 3)])
+        );
+        assert_eq!(transaction_inputs(&[0x80]), None);
+    }
+}
@@ -0,0 +1,198 @@
+//! Mapping from generated UPLC back to the session's definitions
+//!
+//! Aiken's code generator doesn't keep the spans of the terms it generates, but it binds every
+//! function it hoists to a variable named after the module and the function:
+//! `[(lam repl_double rest) (lam x ...)]`. Those binders tell which definition, and so which
+//! line of the session, the terms under them come from.
+
+use std::fmt;
+
+use uplc::ast::{NamedDeBruijn, Term};
+
+/// A UPLC function bound to a definition of the session
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceMapping {
+    /// Name of the variable the function is bound to
+    pub binder: String,
+    pub definition: String,
+    /// Line of the session's source the definition starts at, from 1
+    pub line: usize,
+}
+
+/// Functions of a program that come from definitions of the session
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SourceMap {
+    pub mappings: Vec<SourceMapping>,
+}
+
+impl SourceMap {
+    /// Map the functions bound in `term` to `functions`, the function definitions of the session
+    /// in `module` with their lines
+    pub fn new(term: &Term<NamedDeBruijn>, module: &str, functions: &[(&str, usize)]) -> Self {
+        let mut binders = Vec::new();
+        function_binders(term, &mut binders);
+
+        let mut mappings: Vec<SourceMapping> = Vec::new();
+        for binder in binders {
+            if mappings.iter().any(|mapping| mapping.binder == binder) {
+                continue;
+            }
+            // The longest name, so `double_all` isn't taken for a variant of `double`
+            let definition = functions
+                .iter()
+                .filter(|(name, _)| is_bound_to(&binder, module, name))
+                .max_by_key(|(name, _)| name.len());
+            if let Some((name, line)) = definition {
+                mappings.push(SourceMapping {
+                    binder,
+                    definition: name.to_string(),
+                    line: *line,
+                });
+            }
+        }
+        Self { mappings }
+    }
+
+    pub fn get(&self, binder: &str) -> Option<&SourceMapping> {
+        self.mappings
+            .iter()
+            .find(|mapping| mapping.binder == binder)
+    }
+}
+
+/// A compiled expression, printed as UPLC
+#[derive(Debug, Clone)]
+pub struct UplcDump {
+    pub program: String,
+    pub source_map: SourceMap,
+}
+
+impl UplcDump {
+    /// The program, with the definition and line functions come from after the line that binds
+    /// them
+    pub fn annotated(&self) -> String {
+        let mut annotated: Vec<&SourceMapping> = Vec::new();
+        self.program
+            .lines()
+            .map(|line| {
+                let mapping =
+                    self.source_map.mappings.iter().find(|mapping| {
+                        !annotated.contains(mapping) && binds(line, &mapping.binder)
+                    });
+                match mapping {
+                    Some(mapping) => {
+                        annotated.push(mapping);
+                        format!("{}  -- {}, line {}", line, mapping.definition, mapping.line)
+                    }
+                    None => line.to_string(),
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+impl fmt::Display for UplcDump {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.program)
+    }
+}
+
+/// Names of the variables functions are bound to, in `[(lam f rest) (lam x ...)]`
+fn function_binders(term: &Term<NamedDeBruijn>, binders: &mut Vec<String>) {
+    match term {
+        Term::Apply { function, argument } => {
+            if let (Term::Lambda { parameter_name, .. }, Term::Lambda { .. }) =
+                (function.as_ref(), argument.as_ref())
+            {
+                binders.push(parameter_name.text.clone());
+            }
+            function_binders(function, binders);
+            function_binders(argument, binders);
+        }
+        Term::Lambda { body, .. } | Term::Delay(body) | Term::Force(body) => {
+            function_binders(body, binders)
+        }
+        Term::Constr { fields, .. } => {
+            for field in fields {
+                function_binders(field, binders);
+            }
+        }
+        Term::Case { constr, branches } => {
+            function_binders(constr, binders);
+            for branch in branches {
+                function_binders(branch, binders);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Whether `binder` is the variable of function `name` of `module`: the name, after the
+/// module's, and before the suffix of a generic variant if any
+fn is_bound_to(binder: &str, module: &str, name: &str) -> bool {
+    let unqualified = binder
+        .strip_prefix(module)
+        .and_then(|rest| rest.strip_prefix(['_', '.', '/']))
+        .unwrap_or(binder);
+    unqualified == name
+        || unqualified
+            .strip_prefix(name)
+            .is_some_and(|variant| variant.starts_with('_'))
+}
+
+/// Whether a line of pretty-printed UPLC binds `binder`. Printed names may have the index of
+/// the variable as a suffix, eg. `repl_double_0`.
+fn binds(line: &str, binder: &str) -> bool {
+    line.match_indices("(lam ").any(|(start, _)| {
+        let name = line[start + 5..]
+            .split(|c: char| c.is_whitespace() || c == ')')
+            .next()
+            .unwrap_or_default();
+        name == binder
+            || name.strip_prefix(binder).is_some_and(|index| {
+                index
+                    .strip_prefix('_')
+                    .is_some_and(|digits| digits.chars().all(|c| c.is_ascii_digit()))
+            })
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::{SourceMap, SourceMapping, UplcDump, binds, is_bound_to};
+
+    #[test]
+    fn test_is_bound_to() {
+        assert!(is_bound_to("repl_double", "repl", "double"));
+        assert!(is_bound_to("double", "repl", "double"));
+        assert!(is_bound_to("repl_double_int", "repl", "double"));
+        assert!(!is_bound_to("repl_doubled", "repl", "double"));
+        assert!(!is_bound_to("other_double", "repl", "double"));
+        assert!(is_bound_to("session_double", "session", "double"));
+    }
+
+    #[test]
+    fn test_annotated() {
+        let dump = UplcDump {
+            program: "(program\n  1.1.0\n  [\n    (lam repl_double_0 [repl_double_0 (con integer 2)])\n    (lam x_1 [x_1 x_1])\n  ]\n)".to_string(),
+            source_map: SourceMap {
+                mappings: vec![SourceMapping {
+                    binder: "repl_double".to_string(),
+                    definition: "double".to_string(),
+                    line: 3,
+                }],
+            },
+        };
+        let annotated = dump.annotated();
+        assert!(
+            annotated.contains(
+                "(lam repl_double_0 [repl_double_0 (con integer 2)])  -- double, line 3\n"
+            )
+        );
+        assert_eq!(annotated.matches("--").count(), 1);
+
+        assert!(binds("(lam f (lam x x))", "f"));
+        assert!(!binds("(lam fx x)", "f"));
+    }
+}
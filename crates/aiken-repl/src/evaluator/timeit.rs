@@ -0,0 +1,151 @@
+//! Micro-timing of expressions
+//!
+//! The expression is compiled once and its UPLC program is evaluated over and over, so the
+//! timings are those of the evaluation alone, without type-checking or code generation.
+
+use std::{
+    fmt,
+    time::{Duration, Instant},
+};
+
+use aiken_lang::plutus_version::PlutusVersion;
+use uplc::{
+    ast::{NamedDeBruijn, Program},
+    machine::cost_model::ExBudget,
+};
+
+use super::ReplError;
+
+/// Without an explicit number of loops, use the first power of 10 that takes this long
+const MIN_RUN_TIME: Duration = Duration::from_millis(200);
+const MAX_LOOPS: u32 = 1_000_000;
+
+/// Timings of `repeats` runs of `loops` evaluations each
+#[derive(Debug, Clone)]
+pub struct Timing {
+    pub loops: u32,
+    pub repeats: u32,
+    /// Mean time of one evaluation over all runs
+    pub mean: Duration,
+    /// Standard deviation of the time of one evaluation between runs
+    pub stddev: Duration,
+    /// Budget of one evaluation, which is the same every time
+    pub budget: ExBudget,
+    /// Time of one evaluation in each run
+    pub runs: Vec<Duration>,
+}
+
+impl fmt::Display for Timing {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{} ± {} per loop (mean ± std. dev. of {} run{}, {} loop{} each)",
+            format_duration(self.mean),
+            format_duration(self.stddev),
+            self.repeats,
+            if self.repeats == 1 { "" } else { "s" },
+            self.loops,
+            if self.loops == 1 { "" } else { "s" },
+        )?;
+        write!(
+            f,
+            "Budget: mem {}, cpu {}",
+            self.budget.mem, self.budget.cpu
+        )
+    }
+}
+
+/// Evaluate `program` `repeats` times `loops` times. When `loops` is None, it is picked so a
+/// run takes at least `MIN_RUN_TIME`.
+pub fn time_program(
+    program: &Program<NamedDeBruijn>,
+    plutus_version: PlutusVersion,
+    loops: Option<u32>,
+    repeats: u32,
+) -> Result<Timing, ReplError> {
+    let run = |loops: u32| {
+        let start = Instant::now();
+        for _ in 0..loops {
+            let _ = program
+                .clone()
+                .eval_version(ExBudget::max(), &plutus_version.into());
+        }
+        start.elapsed()
+    };
+
+    // A first evaluation to fail early and get the budget
+    let result = program
+        .clone()
+        .eval_version(ExBudget::max(), &plutus_version.into());
+    let budget = result.cost();
+    if let Err(err) = result.result {
+        return Err(ReplError::EvaluationFailed {
+            message: format!("Evaluation failed: {:?}", err),
+        });
+    }
+
+    let loops = match loops {
+        Some(loops) => loops.max(1),
+        None => {
+            let mut loops = 1;
+            while loops < MAX_LOOPS && run(loops) < MIN_RUN_TIME {
+                loops *= 10;
+            }
+            loops
+        }
+    };
+
+    let repeats = repeats.max(1);
+    let per_loop: Vec<f64> = (0..repeats)
+        .map(|_| run(loops).as_secs_f64() / loops as f64)
+        .collect();
+    let mean = per_loop.iter().sum::<f64>() / per_loop.len() as f64;
+    let variance = per_loop.iter().map(|t| (t - mean).powi(2)).sum::<f64>() / per_loop.len() as f64;
+
+    Ok(Timing {
+        loops,
+        repeats,
+        mean: Duration::from_secs_f64(mean),
+        stddev: Duration::from_secs_f64(variance.sqrt()),
+        budget,
+        runs: per_loop.into_iter().map(Duration::from_secs_f64).collect(),
+    })
+}
+
+/// Format a duration with 3 significant digits and the largest unit it's at least 1 of
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs_f64();
+    let (value, unit) = if secs >= 1.0 {
+        (secs, "s")
+    } else if secs >= 1e-3 {
+        (secs * 1e3, "ms")
+    } else if secs >= 1e-6 {
+        (secs * 1e6, "µs")
+    } else {
+        (secs * 1e9, "ns")
+    };
+
+    if value >= 100.0 {
+        format!("{:.0} {}", value, unit)
+    } else if value >= 10.0 {
+        format!("{:.1} {}", value, unit)
+    } else {
+        format!("{:.2} {}", value, unit)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::format_duration;
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(Duration::from_secs(2)), "2.00 s");
+        assert_eq!(format_duration(Duration::from_micros(12_345)), "12.3 ms");
+        assert_eq!(format_duration(Duration::from_nanos(123_456)), "123 µs");
+        assert_eq!(format_duration(Duration::from_nanos(5)), "5.00 ns");
+        assert_eq!(format_duration(Duration::ZERO), "0.00 ns");
+    }
+}
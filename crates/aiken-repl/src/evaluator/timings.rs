@@ -0,0 +1,187 @@
+//! Where the time of an evaluation goes
+//!
+//! The clock is shared by the evaluator and the event listener of the temp project: the
+//! evaluator enters the phases it drives itself (parsing the input, generating code, running
+//! it), and the listener enters type-checking when the compiler starts. A phase lasts until the
+//! next one is entered, so the phases add up to the whole evaluation.
+
+use std::{
+    collections::BTreeMap,
+    fmt,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use super::format_duration;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Phase {
+    /// Reading the input and splitting its definitions, and writing the temp project
+    Parse,
+    /// Checking the project, which includes the compiler parsing its modules. With a pinned
+    /// `aiken` binary, its whole run.
+    Typecheck,
+    /// Generating the UPLC program of an expression
+    Codegen,
+    /// Running the program
+    Eval,
+}
+
+impl Phase {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Phase::Parse => "parse",
+            Phase::Typecheck => "typecheck",
+            Phase::Codegen => "codegen",
+            Phase::Eval => "eval",
+        }
+    }
+}
+
+/// Time spent in each phase of an evaluation, in the order of the phases. Phases entered more
+/// than once, eg. type-checking the definitions then the expression of a cell, are summed.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Timings {
+    pub phases: Vec<(Phase, Duration)>,
+}
+
+impl Timings {
+    pub fn total(&self) -> Duration {
+        self.phases.iter().map(|(_, duration)| *duration).sum()
+    }
+
+    /// Time spent running the program, the rest went into compiling it
+    pub fn eval(&self) -> Duration {
+        self.phases
+            .iter()
+            .filter(|(phase, _)| *phase == Phase::Eval)
+            .map(|(_, duration)| *duration)
+            .sum()
+    }
+}
+
+impl fmt::Display for Timings {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let total = self.total();
+        for (phase, duration) in &self.phases {
+            let percent = 100.0 * duration.as_secs_f64() / total.as_secs_f64().max(f64::EPSILON);
+            writeln!(
+                f,
+                "{:<10} {:>10} {:>4.0}%",
+                phase.as_str(),
+                format_duration(*duration),
+                percent
+            )?;
+        }
+        write!(
+            f,
+            "{:<10} {:>10} (compile {}, eval {})",
+            "total",
+            format_duration(total),
+            format_duration(total - self.eval()),
+            format_duration(self.eval())
+        )
+    }
+}
+
+/// Times the phases of the evaluation in progress, if any
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Clock(Arc<Mutex<Running>>);
+
+#[derive(Debug, Default)]
+struct Running {
+    /// Phase in progress and when it was entered, None when no evaluation is timed
+    current: Option<(Phase, Instant)>,
+    spent: BTreeMap<Phase, Duration>,
+}
+
+impl Running {
+    fn leave(&mut self) {
+        if let Some((phase, since)) = self.current.take() {
+            *self.spent.entry(phase).or_default() += since.elapsed();
+        }
+    }
+}
+
+impl Clock {
+    /// Start timing an evaluation, from its parsing
+    pub fn start(&self) {
+        if let Ok(mut running) = self.0.lock() {
+            running.spent.clear();
+            running.current = Some((Phase::Parse, Instant::now()));
+        }
+    }
+
+    /// Leave the phase in progress for `phase`. Nothing happens when no evaluation is timed, eg.
+    /// for `:check`.
+    pub fn enter(&self, phase: Phase) {
+        if let Ok(mut running) = self.0.lock()
+            && running.current.is_some()
+        {
+            running.leave();
+            running.current = Some((phase, Instant::now()));
+        }
+    }
+
+    /// Stop timing the evaluation, returning its phases
+    pub fn stop(&self) -> Timings {
+        let Ok(mut running) = self.0.lock() else {
+            return Timings::default();
+        };
+        running.leave();
+        Timings {
+            phases: std::mem::take(&mut running.spent).into_iter().collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::{Clock, Phase, Timings};
+
+    #[test]
+    fn test_clock() {
+        let clock = Clock::default();
+        // Not timing anything yet
+        clock.enter(Phase::Eval);
+        assert_eq!(clock.stop(), Timings::default());
+
+        clock.start();
+        clock.clone().enter(Phase::Typecheck);
+        clock.enter(Phase::Eval);
+        clock.enter(Phase::Typecheck);
+        let timings = clock.stop();
+        let phases: Vec<Phase> = timings.phases.iter().map(|(phase, _)| *phase).collect();
+        assert_eq!(phases, vec![Phase::Parse, Phase::Typecheck, Phase::Eval]);
+
+        // Stopped
+        clock.enter(Phase::Codegen);
+        assert_eq!(clock.stop(), Timings::default());
+    }
+
+    #[test]
+    fn test_display() {
+        let timings = Timings {
+            phases: vec![
+                (Phase::Parse, Duration::from_millis(25)),
+                (Phase::Typecheck, Duration::from_millis(60)),
+                (Phase::Eval, Duration::from_millis(15)),
+            ],
+        };
+        assert_eq!(timings.total(), Duration::from_millis(100));
+        assert_eq!(timings.eval(), Duration::from_millis(15));
+        assert_eq!(
+            timings.to_string(),
+            "parse         25.0 ms   25%\n\
+             typecheck     60.0 ms   60%\n\
+             eval          15.0 ms   15%\n\
+             total          100 ms (compile 85.0 ms, eval 15.0 ms)"
+        );
+        assert_eq!(
+            Timings::default().to_string(),
+            "total         0.00 ns (compile 0.00 ns, eval 0.00 ns)"
+        );
+    }
+}
@@ -0,0 +1,70 @@
+//! Evaluation of untyped Plutus Core written by hand
+//!
+//! The program doesn't go through the temp project: it is parsed by the uplc crate and evaluated
+//! as is, with the same Plutus version and budget as Aiken expressions.
+
+use std::fmt;
+
+use aiken_lang::plutus_version::PlutusVersion;
+use uplc::{
+    ast::{NamedDeBruijn, Program},
+    machine::cost_model::ExBudget,
+    parser,
+};
+
+use super::{
+    ReplError,
+    pretty::{self, DisplaySettings},
+};
+
+/// Result of a UPLC program and what it cost
+#[derive(Debug, Clone)]
+pub struct UplcResult {
+    pub value: String,
+    pub budget: ExBudget,
+    /// Messages of the `trace` builtin, in order
+    pub traces: Vec<String>,
+}
+
+impl fmt::Display for UplcResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for trace in &self.traces {
+            writeln!(f, "{}", trace)?;
+        }
+        writeln!(f, "{}", self.value)?;
+        write!(
+            f,
+            "Budget: mem {}, cpu {}",
+            self.budget.mem, self.budget.cpu
+        )
+    }
+}
+
+/// Parse a program such as `(program 1.1.0 (con integer 42))` and evaluate it
+pub fn eval_program(
+    source: &str,
+    plutus_version: PlutusVersion,
+    settings: &DisplaySettings,
+) -> Result<UplcResult, ReplError> {
+    let program = parser::program(source).map_err(|err| ReplError::ParseFailed {
+        message: format!("Invalid UPLC: {}", err),
+    })?;
+    let program =
+        Program::<NamedDeBruijn>::try_from(program).map_err(|err| ReplError::EvaluationFailed {
+            message: format!("Failed to convert to NamedDeBruijn: {:?}", err),
+        })?;
+
+    let result = program.eval_version(ExBudget::max(), &plutus_version.into());
+    let budget = result.cost();
+    let traces = result.logs();
+    match result.result {
+        Ok(term) => Ok(UplcResult {
+            value: pretty::term(&term, settings),
+            budget,
+            traces,
+        }),
+        Err(err) => Err(ReplError::EvaluationFailed {
+            message: format!("Evaluation failed: {:?}", err),
+        }),
+    }
+}
@@ -0,0 +1,80 @@
+//! Compiler warnings of the session's code, such as unused definitions or deprecated constructs
+//!
+//! Every evaluation type-checks the whole context again, so the same warnings come back each
+//! time. Only warnings that weren't reported yet are kept for the user to see.
+
+use std::collections::HashSet;
+
+use aiken_project::error::Warning;
+use miette::{Diagnostic, GraphicalReportHandler, GraphicalTheme};
+
+pub(crate) struct Warnings {
+    pub enabled: bool,
+    /// Rendered warnings not taken yet
+    pending: Vec<String>,
+    /// Keys of every warning seen since the last reset
+    reported: HashSet<String>,
+}
+
+impl Default for Warnings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            pending: Vec::new(),
+            reported: HashSet::new(),
+        }
+    }
+}
+
+impl Warnings {
+    /// Keep the warnings of a check pass that weren't reported before
+    pub fn collect(&mut self, warnings: Vec<Warning>) {
+        if !self.enabled {
+            return;
+        }
+        for warning in warnings {
+            if self.reported.insert(key(&warning)) {
+                self.pending.push(render(&warning));
+            }
+        }
+    }
+
+    /// Keep a note about the code that doesn't come from the compiler, eg. about what it
+    /// replaced
+    pub fn note(&mut self, note: String) {
+        if self.enabled {
+            self.pending.push(note);
+        }
+    }
+
+    pub fn take(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.pending)
+    }
+
+    /// Forget what was reported, for a fresh context
+    pub fn clear(&mut self) {
+        self.pending.clear();
+        self.reported.clear();
+    }
+}
+
+/// Identify a warning by its message and where it points. Definitions are at the start of the
+/// module, so their warnings keep the same offsets as long as they aren't redefined. The rendered
+/// report isn't stable: it shows the lines around the warning, which change as code is appended.
+fn key(warning: &Warning) -> String {
+    let mut key = warning.to_string();
+    for label in warning.labels().into_iter().flatten() {
+        key.push_str(&format!(" {}+{}", label.offset(), label.len()));
+    }
+    key
+}
+
+/// Render a warning, or any other diagnostic of the compiler, with its source snippet
+pub(crate) fn render(diagnostic: &dyn Diagnostic) -> String {
+    let handler = GraphicalReportHandler::new().with_theme(GraphicalTheme::default());
+    let mut output = String::new();
+    match handler.render_report(&mut output, diagnostic) {
+        Ok(_) => output,
+        Err(_) => format!("{}", diagnostic),
+    }
+}
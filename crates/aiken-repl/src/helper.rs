@@ -0,0 +1,236 @@
+//! Line editing of the terminal REPL: completion of names and commands, hints and highlighting
+
+use std::{borrow::Cow, cell::RefCell, rc::Rc};
+
+use aiken_repl::evaluator::{CompletionCandidates, Completions, KEYWORDS, OPTIONS, ReplEvaluator};
+use rustyline::{
+    Context, Helper,
+    completion::Completer,
+    highlight::{CmdKind, Highlighter},
+    hint::Hinter,
+    validate::Validator,
+};
+
+/// Special commands, completed after `:`
+const COMMANDS: &[&str] = &[
+    ":autoformat",
+    ":cache",
+    ":cbor",
+    ":chain",
+    ":check",
+    ":checkpoint",
+    ":context",
+    ":decode-address",
+    ":doc",
+    ":edit",
+    ":export",
+    ":flamegraph",
+    ":fmt",
+    ":forget",
+    ":from-data",
+    ":full-output",
+    ":fuzz",
+    ":gc",
+    ":help",
+    ":keygen",
+    ":keys",
+    ":probe",
+    ":profile",
+    ":quit",
+    ":reset",
+    ":rollback",
+    ":set",
+    ":show",
+    ":sign",
+    ":simulate",
+    ":to-data",
+    ":undo",
+    ":uplc",
+    ":utxos",
+    ":version",
+    ":warnings",
+];
+
+const KEYWORD: &str = "\x1b[1;35m";
+const STRING: &str = "\x1b[32m";
+const NUMBER: &str = "\x1b[33m";
+const COMMENT: &str = "\x1b[2m";
+const RESET: &str = "\x1b[0m";
+
+pub struct ReplHelper {
+    repl: Rc<RefCell<ReplEvaluator>>,
+    /// Candidates and the context they were read from. Reading them type-checks the context, which
+    /// is too slow to do on every key press.
+    candidates: RefCell<Option<(String, CompletionCandidates)>>,
+}
+
+impl ReplHelper {
+    pub fn new(repl: Rc<RefCell<ReplEvaluator>>) -> Self {
+        Self {
+            repl,
+            candidates: RefCell::new(None),
+        }
+    }
+
+    fn complete_line(&self, line: &str, pos: usize) -> Completions {
+        let before = &line[..pos];
+
+        // Commands, the options of `:set` and the checkpoints to roll back to
+        if !before.contains(char::is_whitespace) && before.starts_with(':') {
+            return Completions {
+                start: 0,
+                matches: matching(COMMANDS, before),
+            };
+        }
+        if let Some(option) = before.strip_prefix(":set ")
+            && !option.contains(char::is_whitespace)
+        {
+            return Completions {
+                start: ":set ".len(),
+                matches: matching(OPTIONS, option),
+            };
+        }
+
+        if let Some(name) = before.strip_prefix(":rollback ")
+            && !name.contains(char::is_whitespace)
+        {
+            let repl = self.repl.borrow();
+            let checkpoints: Vec<&str> = repl.checkpoints().collect();
+            return Completions {
+                start: ":rollback ".len(),
+                matches: matching(&checkpoints, name),
+            };
+        }
+
+        let context = self.repl.borrow().context_info();
+        let mut candidates = self.candidates.borrow_mut();
+        if candidates
+            .as_ref()
+            .is_none_or(|(cached, _)| *cached != context)
+        {
+            // Code that doesn't type-check has nothing to offer, try again next time
+            let Ok(fresh) = self.repl.borrow().completion_candidates() else {
+                return Completions::default();
+            };
+            *candidates = Some((context, fresh));
+        }
+        match candidates.as_ref() {
+            Some((_, candidates)) => candidates.complete(line, pos),
+            None => Completions::default(),
+        }
+    }
+}
+
+fn matching(names: &[&str], prefix: &str) -> Vec<String> {
+    names
+        .iter()
+        .filter(|name| name.starts_with(prefix))
+        .map(|name| name.to_string())
+        .collect()
+}
+
+impl Completer for ReplHelper {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        let completions = self.complete_line(line, pos);
+        Ok((completions.start, completions.matches))
+    }
+}
+
+impl Hinter for ReplHelper {
+    type Hint = String;
+
+    /// The rest of the name being typed, when only one name matches
+    fn hint(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> Option<String> {
+        if line.is_empty() || pos < line.len() {
+            return None;
+        }
+        let completions = self.complete_line(line, pos);
+        match completions.matches.as_slice() {
+            [only] => only
+                .strip_prefix(&line[completions.start..pos])
+                .filter(|rest| !rest.is_empty())
+                .map(str::to_string),
+            _ => None,
+        }
+    }
+}
+
+impl Highlighter for ReplHelper {
+    fn highlight<'l>(&self, line: &'l str, _pos: usize) -> Cow<'l, str> {
+        if line.starts_with(':') {
+            return Cow::Borrowed(line);
+        }
+        Cow::Owned(highlight(line))
+    }
+
+    fn highlight_hint<'h>(&self, hint: &'h str) -> Cow<'h, str> {
+        Cow::Owned(format!("{}{}{}", COMMENT, hint, RESET))
+    }
+
+    fn highlight_char(&self, _line: &str, _pos: usize, _kind: CmdKind) -> bool {
+        // Any edit can start or end a keyword, string or comment
+        true
+    }
+}
+
+impl Validator for ReplHelper {}
+
+impl Helper for ReplHelper {}
+
+/// Color keywords, strings, numbers and comments of Aiken code
+fn highlight(code: &str) -> String {
+    let mut output = String::with_capacity(code.len());
+    let mut rest = code;
+    while let Some(c) = rest.chars().next() {
+        let len = if rest.starts_with("//") {
+            let len = rest.find('\n').unwrap_or(rest.len());
+            paint(&mut output, COMMENT, &rest[..len]);
+            len
+        } else if c == '"' {
+            // Up to the closing quote, skipping escaped ones
+            let mut escaped = false;
+            let len = rest[1..]
+                .char_indices()
+                .find(|&(_, c)| {
+                    let closing = c == '"' && !escaped;
+                    escaped = c == '\\' && !escaped;
+                    closing
+                })
+                .map(|(i, _)| i + 2)
+                .unwrap_or(rest.len());
+            paint(&mut output, STRING, &rest[..len]);
+            len
+        } else if c.is_alphanumeric() || c == '_' {
+            let len = rest
+                .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+            let word = &rest[..len];
+            if KEYWORDS.contains(&word) {
+                paint(&mut output, KEYWORD, word);
+            } else if c.is_ascii_digit() {
+                paint(&mut output, NUMBER, word);
+            } else {
+                output.push_str(word);
+            }
+            len
+        } else {
+            output.push(c);
+            c.len_utf8()
+        };
+        rest = &rest[len..];
+    }
+    output
+}
+
+fn paint(output: &mut String, color: &str, text: &str) {
+    output.push_str(color);
+    output.push_str(text);
+    output.push_str(RESET);
+}
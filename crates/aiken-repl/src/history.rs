@@ -0,0 +1,94 @@
+//! Input history, shared by the terminal REPL and the kernel
+//!
+//! Entries are stored oldest first, one JSON string per line so that multi-line definitions
+//! survive. Adding an entry drops its earlier copies and the oldest entries past the maximum.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// Number of entries kept when no other maximum is given
+pub const DEFAULT_MAX_ENTRIES: usize = 1000;
+
+/// `$XDG_STATE_HOME/aiken-repl/history` (`~/.local/state/...`) on Linux, and the local data
+/// directory on other platforms
+pub fn default_path() -> Option<PathBuf> {
+    dirs::state_dir()
+        .or_else(dirs::data_local_dir)
+        .map(|dir| dir.join("aiken-repl").join("history"))
+}
+
+/// A history file
+#[derive(Debug, Clone)]
+pub struct History {
+    path: PathBuf,
+    max_entries: usize,
+}
+
+impl History {
+    pub fn new(path: PathBuf, max_entries: usize) -> Self {
+        Self { path, max_entries }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Entries, oldest first. A missing file is an empty history, and unreadable lines are
+    /// skipped.
+    pub fn load(&self) -> Vec<String> {
+        fs::read_to_string(&self.path)
+            .map(|content| {
+                content
+                    .lines()
+                    .filter_map(|line| serde_json::from_str(line).ok())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Add an entry as the most recent one
+    pub fn add(&self, entry: &str) -> io::Result<()> {
+        let mut entries = self.load();
+        entries.retain(|e| e != entry);
+        entries.push(entry.to_string());
+        let skip = entries.len().saturating_sub(self.max_entries);
+
+        let mut content = String::new();
+        for entry in &entries[skip..] {
+            content.push_str(&serde_json::to_string(entry)?);
+            content.push('\n');
+        }
+
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&self.path, content)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::History;
+
+    #[test]
+    fn test_history() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let history = History::new(dir.path().join("nested").join("history"), 3);
+        assert!(history.load().is_empty());
+
+        history.add("1 + 2").unwrap();
+        history.add("pub fn double(x) {\n  x * 2\n}").unwrap();
+        history.add("double(2)").unwrap();
+        assert_eq!(
+            history.load(),
+            vec!["1 + 2", "pub fn double(x) {\n  x * 2\n}", "double(2)"]
+        );
+
+        // Repeated entries move to the end, and the oldest ones go past the maximum
+        history.add("1 + 2").unwrap();
+        history.add("True").unwrap();
+        assert_eq!(history.load(), vec!["double(2)", "1 + 2", "True"]);
+    }
+}
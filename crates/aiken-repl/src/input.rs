@@ -0,0 +1,67 @@
+//! Splitting of user input into complete REPL inputs
+
+/// Whether `code` has delimiters left open, so more lines are needed before it can be evaluated.
+/// Delimiters in strings and comments don't count. Extra closing delimiters are left for the
+/// compiler to report.
+pub fn is_incomplete(code: &str) -> bool {
+    let mut depth: isize = 0;
+    let mut chars = code.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' => depth -= 1,
+            '"' => {
+                // Skip the string, which is left open if it has no closing quote
+                let mut closed = false;
+                while let Some(c) = chars.next() {
+                    match c {
+                        '\\' => {
+                            chars.next();
+                        }
+                        '"' => {
+                            closed = true;
+                            break;
+                        }
+                        _ => {}
+                    }
+                }
+                if !closed {
+                    return true;
+                }
+            }
+            '/' if chars.peek() == Some(&'/') => {
+                // Skip the comment, up to the end of the line
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    depth > 0
+}
+
+#[cfg(test)]
+mod test {
+    use super::is_incomplete;
+
+    #[test]
+    fn test_is_incomplete() {
+        assert!(!is_incomplete("1 + 2"));
+        assert!(!is_incomplete("pub fn add(x, y) { x + y }"));
+        assert!(is_incomplete("pub fn add(x, y) {"));
+        assert!(is_incomplete("pub fn add(x, y) {\n  [x,"));
+        assert!(!is_incomplete("pub fn add(x, y) {\n  x + y\n}"));
+
+        // Delimiters in strings and comments don't count
+        assert!(!is_incomplete("@\"{\""));
+        assert!(!is_incomplete("\"\\\"(\""));
+        assert!(!is_incomplete("1 // (\n"));
+        assert!(is_incomplete("@\"unterminated"));
+
+        // Too many closing delimiters is an error for the compiler
+        assert!(!is_incomplete("1 + 2)"));
+    }
+}
@@ -0,0 +1,94 @@
+//! `--json` output: one single-line JSON object per evaluation, so editor plugins and scripts
+//! can drive the REPL without parsing text meant for humans
+
+use aiken_lang::tipo::pretty::Printer;
+use aiken_repl::evaluator::{EvaluationResult, ReplError};
+use miette::{JSONReportHandler, ReportHandler};
+use serde_json::{Value, json};
+
+use crate::Rendered;
+
+/// `{value, type, kind, budget, traces, warnings, error}` of an evaluation. Fields that don't
+/// apply are null (or empty for lists).
+pub fn evaluation(
+    result: &Result<EvaluationResult, ReplError>,
+    mut warnings: Vec<String>,
+) -> Value {
+    let mut output = json!({
+        "value": null,
+        "type": null,
+        "kind": "none",
+        "budget": null,
+        "traces": [],
+        "warnings": [],
+        "error": null,
+    });
+
+    match result {
+        Ok(EvaluationResult::Value {
+            value,
+            tipo,
+            budget,
+            traces,
+            ..
+        }) => {
+            output["value"] = json!(value);
+            output["type"] = json!(Printer::new().pretty_print(tipo, 0));
+            output["kind"] = json!("value");
+            output["budget"] = json!({ "mem": budget.mem, "cpu": budget.cpu });
+            output["traces"] = json!(traces);
+        }
+        Ok(EvaluationResult::Cached {
+            value,
+            tipo,
+            budget,
+            traces,
+        }) => {
+            output["value"] = json!(value);
+            output["type"] = json!(tipo);
+            output["kind"] = json!("value");
+            output["budget"] = json!({ "mem": budget.mem, "cpu": budget.cpu });
+            output["traces"] = json!(traces);
+        }
+        Ok(EvaluationResult::Traced { value, compiler }) => {
+            output["value"] = json!(value);
+            output["kind"] = json!("value");
+            output["compiler"] = json!(compiler);
+        }
+        Ok(EvaluationResult::Definition { name, kind, tipo }) => {
+            output["value"] = json!(name);
+            output["type"] = json!(tipo.as_ref().map(|t| Printer::new().pretty_print(t, 0)));
+            output["kind"] = json!(kind.as_str());
+        }
+        Ok(EvaluationResult::Definitions(definitions)) => {
+            let names: Vec<&str> = definitions.iter().map(|d| d.name.as_str()).collect();
+            output["value"] = json!(names.join(", "));
+            output["kind"] = json!("definitions");
+        }
+        Ok(EvaluationResult::TestReport(report)) => {
+            output["value"] = json!(report.to_string());
+            output["kind"] = json!("tests");
+        }
+        Ok(EvaluationResult::Warnings(only_warnings)) => {
+            warnings.extend(only_warnings.iter().cloned());
+        }
+        // The expression the code ends with is its result
+        Ok(EvaluationResult::Mixed { result, .. }) => {
+            return evaluation(&Ok(result.as_ref().clone()), warnings);
+        }
+        Ok(EvaluationResult::NoResult) => {}
+        Err(err) => {
+            // The diagnostic as miette serializes it, with its labels and help
+            let rendered = format!(
+                "{:?}",
+                Rendered(&JSONReportHandler::new() as &dyn ReportHandler, err)
+            );
+            output["kind"] = json!("error");
+            output["error"] =
+                serde_json::from_str(&rendered).unwrap_or_else(|_| json!(err.to_string()));
+        }
+    }
+
+    output["warnings"] = json!(warnings);
+    output
+}
@@ -1 +1,5 @@
+pub mod config;
 pub mod evaluator;
+pub mod history;
+pub mod input;
+pub mod version;
@@ -1,32 +1,200 @@
-use aiken_repl::evaluator::{EvaluationResult, ReplError, ReplEvaluator};
-use rustyline::{DefaultEditor, error::ReadlineError};
+use std::{
+    cell::RefCell,
+    fmt, fs,
+    io::{self, IsTerminal, Read},
+    path::{Path, PathBuf},
+    process,
+    rc::Rc,
+};
+
+use aiken_repl::{
+    config,
+    evaluator::{
+        ArtifactCache, EvaluationResult, FlamegraphArgs, FuzzArgs, ReplError, ReplEvaluator,
+    },
+    history::{self, DEFAULT_MAX_ENTRIES, History},
+    input::is_incomplete,
+    version,
+};
+use clap::{Parser, ValueEnum};
+use miette::{
+    Diagnostic, JSONReportHandler, MietteHandlerOpts, NarratableReportHandler, ReportHandler,
+};
+use rustyline::{
+    Config, Editor, config::Configurer, error::ReadlineError, history::DefaultHistory,
+};
+
+use crate::helper::ReplHelper;
+
+mod edit;
+mod helper;
+mod json;
+
+type ReplEditor = Editor<ReplHelper, DefaultHistory>;
+
+#[derive(Parser)]
+#[command(name = "aiken-repl")]
+#[command(about = "Interactive REPL for the Aiken programming language")]
+struct Cli {
+    /// How errors are reported
+    #[arg(long = "error-format", value_enum, default_value_t = ErrorFormat::Fancy)]
+    error_format: ErrorFormat,
+    /// Print every evaluation as a single-line JSON object
+    /// {value, type, kind, budget, traces, warnings, error}
+    #[arg(long)]
+    json: bool,
+    /// Evaluate the given input and exit
+    #[arg(long, conflicts_with = "script")]
+    eval: Option<String>,
+    /// Run each line of a file as a REPL input and exit, failing on the first error.
+    /// Inputs are also read from stdin when it isn't a terminal.
+    #[arg(long)]
+    script: Option<PathBuf>,
+    /// File where inputs are saved between sessions [default: $XDG_STATE_HOME/aiken-repl/history]
+    #[arg(long = "history-file")]
+    history_file: Option<PathBuf>,
+    /// Number of inputs kept in the history file [default: 1000]
+    #[arg(long = "history-size")]
+    history_size: Option<usize>,
+    /// Print the versions of the REPL, Aiken and Plutus, and exit
+    #[arg(short = 'V', long)]
+    version: bool,
+    /// Configuration file [default: $XDG_CONFIG_HOME/iaiken/config.toml]
+    #[arg(long)]
+    config: Option<PathBuf>,
+    /// Set a session option, over the configuration file's (eg. `--set trace-level=verbose`)
+    #[arg(long = "set", value_name = "OPTION=VALUE", value_parser = config::parse_option)]
+    options: Vec<(String, String)>,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ErrorFormat {
+    /// Plain text, without colors or drawings
+    Plain,
+    /// Source snippets with colors, wrapped at the terminal's width
+    Fancy,
+    /// One JSON object per error, for scripts
+    Json,
+}
 
 fn main() {
-    println!("🎯 Aiken REPL");
-    println!(
-        "Evaluate Aiken expressions or definitions. Use :quit to exit and :help to view all commands"
-    );
-    println!();
+    let mut cli = Cli::parse();
+    if cli.version {
+        println!("{}", versions());
+        return;
+    }
+    let config = load_config(&mut cli);
+
+    // Inputs to run non-interactively, if any
+    let script = if let Some(code) = &cli.eval {
+        Some(code.clone())
+    } else if let Some(path) = &cli.script {
+        match fs::read_to_string(path) {
+            Ok(script) => Some(script),
+            Err(err) => {
+                eprintln!("❌ Cannot read {}: {}", path.display(), err);
+                process::exit(1);
+            }
+        }
+    } else if !io::stdin().is_terminal() {
+        let mut script = String::new();
+        if let Err(err) = io::stdin().read_to_string(&mut script) {
+            eprintln!("❌ Cannot read stdin: {}", err);
+            process::exit(1);
+        }
+        Some(script)
+    } else {
+        None
+    };
+
+    // Shared with the line editor, which completes names of the session
+    let mut evaluator = ReplEvaluator::new();
+    evaluator.set_artifact_cache(ArtifactCache::user());
+    for err in config.apply(&mut evaluator) {
+        eprintln!("⚠️  Ignoring option: {}", err);
+    }
+    let repl = Rc::new(RefCell::new(evaluator));
+    let succeeded = match script {
+        Some(script) => run_script(&mut repl.borrow_mut(), &script, &cli),
+        None => {
+            interactive(&repl, &cli);
+            true
+        }
+    };
+
+    let _ = repl.borrow().cleanup();
+    if !succeeded {
+        process::exit(1);
+    }
+}
+
+/// The configuration file with the options given on the command line, which also take the
+/// place of its history settings. An invalid file is reported and ignored.
+fn load_config(cli: &mut Cli) -> config::Config {
+    let path = cli.config.clone().or_else(config::default_path);
+    let mut config = match path.map(|path| config::Config::load(&path)) {
+        Some(Ok(config)) => config,
+        Some(Err(err)) => {
+            eprintln!("⚠️  Ignoring the configuration file: {}", err);
+            config::Config::default()
+        }
+        None => config::Config::default(),
+    };
+    for (name, value) in &cli.options {
+        config.set(name, value);
+    }
+    cli.history_file = cli.history_file.take().or(config.history.file.clone());
+    cli.history_size = cli.history_size.or(config.history.size);
+    config
+}
+
+fn versions() -> String {
+    version::report("aiken-repl", env!("CARGO_PKG_VERSION"))
+}
 
-    let mut repl = ReplEvaluator::new();
-    //let mut line_number = 1;
-    let mut rl = DefaultEditor::new().expect("Failed to create readline editor");
+/// What to do after an input
+#[derive(PartialEq, Eq)]
+enum Step {
+    Continue,
+    Quit,
+    /// The input was rejected: scripts stop here
+    Failed,
+}
+
+fn interactive(repl: &Rc<RefCell<ReplEvaluator>>, cli: &Cli) {
+    // In JSON mode, stdout only has evaluations
+    if !cli.json {
+        println!("🎯 Aiken REPL");
+        println!(
+            "Evaluate Aiken expressions or definitions. Use :quit to exit and :help to view all commands"
+        );
+        println!();
+    }
+
+    // Pasted text comes in whole, newlines included, instead of line by line
+    let config = Config::builder().bracketed_paste(true).build();
+    let mut rl = ReplEditor::with_config(config).expect("Failed to create readline editor");
+    rl.set_helper(Some(ReplHelper::new(repl.clone())));
+    let history_size = cli.history_size.unwrap_or(DEFAULT_MAX_ENTRIES);
+    let _ = rl.set_max_history_size(history_size);
+    let _ = rl.set_history_ignore_dups(true);
 
     // Load history if it exists
-    let _ = rl.load_history(".aiken_repl_history");
+    let history = cli
+        .history_file
+        .clone()
+        .or_else(history::default_path)
+        .map(|path| History::new(path, history_size));
+    for entry in history.iter().flat_map(History::load) {
+        rl.add_history_entry(entry).ok();
+    }
 
     loop {
-        // Create prompt
-        //let prompt = format!("[{}]> ", line_number);
-        let prompt = "λ> ";
-
-        // Read input with readline
-        let input = match rl.readline(&prompt) {
-            Ok(line) => line,
-            Err(ReadlineError::Interrupted) | Err(ReadlineError::Eof) => {
-                println!("Goodbye! 👋");
-                break;
-            }
+        let input = match read_input(&mut rl, cli.json) {
+            Ok(Some(input)) => input,
+            // Ctrl-C in the middle of a multi-line input discards it
+            Ok(None) => continue,
+            Err(ReadlineError::Interrupted) | Err(ReadlineError::Eof) => break,
             Err(err) => {
                 eprintln!("Error reading input: {}", err);
                 continue;
@@ -35,60 +203,621 @@ fn main() {
 
         let input = input.trim();
 
-        // Handle special commands
-        match input {
-            ":quit" | ":q" => {
-                println!("Goodbye! 👋");
-                break;
-            }
-            ":reset" => {
-                repl.reset();
-                println!("🗑️ Context reset");
-                //line_number = 1;
-                continue;
-            }
-            ":help" | ":h" => {
-                print_help();
-                continue;
+        // Add to history if not empty and not a command
+        if !input.is_empty() && !input.starts_with(':') {
+            rl.add_history_entry(input).ok();
+            if let Some(history) = &history
+                && let Err(err) = history.add(input)
+            {
+                eprintln!(
+                    "Failed to save history to {}: {}",
+                    history.path().display(),
+                    err
+                );
             }
-            ":context" | ":ctx" => {
-                println!("{}", repl.context_info());
-                continue;
+        }
+
+        if step(&mut repl.borrow_mut(), input, cli) == Step::Quit {
+            break;
+        }
+    }
+
+    if !cli.json {
+        println!("Goodbye! 👋");
+    }
+}
+
+/// Read an input, asking for more lines with a continuation prompt while delimiters are left
+/// open. None when the input is cancelled on a continuation line.
+fn read_input(rl: &mut ReplEditor, json: bool) -> Result<Option<String>, ReadlineError> {
+    let (prompt, continuation) = if json { ("", "") } else { ("λ> ", "…> ") };
+    let mut input = rl.readline(prompt)?;
+    while is_incomplete(&input) {
+        match rl.readline(continuation) {
+            Ok(line) => {
+                input.push('\n');
+                input.push_str(&line);
             }
-            "" => continue, // Empty line
-            _ => {}
+            Err(ReadlineError::Interrupted) => return Ok(None),
+            // Let the compiler report what's missing
+            Err(ReadlineError::Eof) => break,
+            Err(err) => return Err(err),
         }
+    }
+    Ok(Some(input))
+}
 
-        // Add to history if not empty and not a command
-        if !input.is_empty() && !input.starts_with(':') {
-            rl.add_history_entry(input).ok();
+/// Run every input of a script, stopping at the first error. Inputs span several lines while
+/// delimiters are left open. Empty lines and `//` comments are skipped.
+fn run_script(repl: &mut ReplEvaluator, script: &str, cli: &Cli) -> bool {
+    let mut lines = script.lines().enumerate();
+    while let Some((index, line)) = lines.next() {
+        let mut input = line.trim().to_string();
+        if input.is_empty() || input.starts_with("//") {
+            continue;
         }
+        // Inputs continue on the next lines while delimiters are left open
+        while is_incomplete(&input)
+            && let Some((_, line)) = lines.next()
+        {
+            input.push('\n');
+            input.push_str(line);
+        }
+        match step(repl, &input, cli) {
+            Step::Continue => {}
+            Step::Quit => break,
+            Step::Failed => {
+                eprintln!("❌ Stopped at line {}: {}", index + 1, line.trim());
+                return false;
+            }
+        }
+    }
+    true
+}
+
+/// Handle a special command or evaluate code
+fn step(repl: &mut ReplEvaluator, input: &str, cli: &Cli) -> Step {
+    let error_format = cli.error_format;
 
-        // Evaluate the input
-        match repl.eval(input) {
-            Ok(result) => {
-                match result {
-                    EvaluationResult::Value { .. } | EvaluationResult::Definition { .. } => {
-                        println!("{}", result);
+    // Split special commands from their arguments (eg. `:doc list.map`)
+    let (command, args) = match input.split_once(char::is_whitespace) {
+        Some((command, args)) if input.starts_with(':') => (command, args.trim()),
+        _ => (input, ""),
+    };
+
+    // Handle special commands
+    match command {
+        ":quit" | ":q" => return Step::Quit,
+        ":reset" => {
+            repl.reset();
+            println!("🗑️ Context reset");
+            return Step::Continue;
+        }
+        ":help" | ":h" => {
+            print_help();
+            return Step::Continue;
+        }
+        ":cache" => {
+            match args {
+                "on" => repl.set_cache(true),
+                "off" => repl.set_cache(false),
+                "clear" => {
+                    return match repl.clear_artifact_cache() {
+                        Ok(freed) => {
+                            println!("🧹 Freed {} KiB of shared build artifacts", freed / 1024);
+                            Step::Continue
+                        }
+                        Err(err) => report(&err, error_format),
+                    };
+                }
+                _ => return usage(":cache on|off|clear"),
+            }
+            println!("✓ Cache {}", args);
+            return Step::Continue;
+        }
+        ":gc" => {
+            return match repl.gc() {
+                Ok(freed) => {
+                    println!("🧹 Freed {} KiB of build artifacts", freed / 1024);
+                    Step::Continue
+                }
+                Err(err) => report(&err, error_format),
+            };
+        }
+        ":context" | ":ctx" => {
+            println!("{}", repl.context_info());
+            return Step::Continue;
+        }
+        ":doc" => return print_doc(repl, args, error_format),
+        ":edit" => return edit_context(repl, error_format),
+        ":undo" => {
+            match repl.undo() {
+                Some(changed) => println!("↩️ Undone: {}", changed.join(", ")),
+                None => println!("Nothing to undo"),
+            }
+            return Step::Continue;
+        }
+        ":forget" => {
+            if args.is_empty() {
+                return usage(":forget <name>");
+            }
+            return match repl.forget(args) {
+                Ok(forgotten) if forgotten.is_empty() => {
+                    eprintln!("❌ No definition named `{}` in scope", args);
+                    Step::Failed
+                }
+                Ok(forgotten) => {
+                    println!("🗑️ Forgot {}", forgotten.join(", "));
+                    Step::Continue
+                }
+                Err(err) => {
+                    let step = report(&err, error_format);
+                    eprintln!("Other definitions depend on `{}`, context unchanged", args);
+                    step
+                }
+            };
+        }
+        ":checkpoint" if args.is_empty() => {
+            let names: Vec<&str> = repl.checkpoints().collect();
+            if names.is_empty() {
+                println!("No checkpoints");
+            } else {
+                println!("{}", names.join("\n"));
+            }
+            return Step::Continue;
+        }
+        ":checkpoint" => {
+            repl.checkpoint(args);
+            println!("📌 Checkpoint `{}` saved", args);
+            return Step::Continue;
+        }
+        ":rollback" => {
+            if args.is_empty() {
+                return usage(":rollback <name>");
+            }
+            return match repl.rollback(args) {
+                Ok(()) => {
+                    println!("⏪ Rolled back to `{}`", args);
+                    Step::Continue
+                }
+                Err(err) => report(&err, error_format),
+            };
+        }
+        ":decode-address" => {
+            if args.is_empty() {
+                return usage(":decode-address <bech32|hex>");
+            }
+            return match repl.decode_address(args) {
+                Ok(address) => {
+                    println!("{}", address);
+                    Step::Continue
+                }
+                Err(err) => report(&err, error_format),
+            };
+        }
+        ":to-data" => {
+            if args.is_empty() {
+                return usage(":to-data [type] <json|cbor>");
+            }
+            return match repl.to_data(args) {
+                Ok(converted) => {
+                    println!("{}", converted);
+                    Step::Continue
+                }
+                Err(err) => report(&err, error_format),
+            };
+        }
+        ":from-data" => {
+            if args.is_empty() {
+                return usage(":from-data <expr>");
+            }
+            return match repl.from_data(args) {
+                Ok(converted) => {
+                    println!("{}", converted);
+                    Step::Continue
+                }
+                Err(err) => report(&err, error_format),
+            };
+        }
+        ":keygen" => {
+            if args.is_empty() {
+                return usage(":keygen <name>");
+            }
+            return match repl.keygen(args) {
+                Ok(key) => {
+                    println!("🔑 {}", key);
+                    Step::Continue
+                }
+                Err(err) => report(&err, error_format),
+            };
+        }
+        ":version" => {
+            println!("{}", versions());
+            return Step::Continue;
+        }
+        ":keys" => {
+            let keys = repl.keys().collect::<Vec<_>>();
+            if keys.is_empty() {
+                println!("No keys, generate one with :keygen <name>");
+            }
+            for key in keys {
+                println!("{}", key);
+            }
+            return Step::Continue;
+        }
+        ":sign" => {
+            let Some((name, code)) = args.split_once(char::is_whitespace) else {
+                return usage(":sign <name> <expr>");
+            };
+            return match repl.sign(name, code.trim()) {
+                Ok(signature) => {
+                    println!("#\"{}\"", hex::encode(signature));
+                    Step::Continue
+                }
+                Err(err) => report(&err, error_format),
+            };
+        }
+        ":simulate" => {
+            if args.is_empty() {
+                return usage(":simulate <tx> [--inputs <utxos.json>] [--network <name>]");
+            }
+            return match repl.simulate(args) {
+                Ok(simulation) => {
+                    println!("{}", simulation);
+                    Step::Continue
+                }
+                Err(err) => report(&err, error_format),
+            };
+        }
+        ":chain" => {
+            if let Some(settings) = args.strip_prefix("config") {
+                return match repl.configure_chain(settings) {
+                    Ok(()) => {
+                        println!(
+                            "🔗 Chain provider on {}",
+                            repl.chain_network().unwrap_or_default()
+                        );
+                        Step::Continue
                     }
-                    EvaluationResult::NoResult => {
-                        println!("✓ Ok");
+                    Err(err) => report(&err, error_format),
+                };
+            }
+            if !args.is_empty() {
+                return usage(":chain [config <project id>]");
+            }
+            match repl.chain_network() {
+                Some(network) => println!("Chain provider on {}", network),
+                None => println!("No chain provider"),
+            }
+            return Step::Continue;
+        }
+        ":utxos" => {
+            if args.is_empty() {
+                return usage(":utxos <address>");
+            }
+            return match repl.utxos(args) {
+                Ok(utxos) if utxos.is_empty() => {
+                    println!("No UTxOs at this address");
+                    Step::Continue
+                }
+                Ok(utxos) => {
+                    for utxo in utxos {
+                        println!("{}", utxo);
                     }
+                    Step::Continue
                 }
-                //line_number += 1;
+                Err(err) => report(&err, error_format),
+            };
+        }
+        ":export" => {
+            if args.is_empty() {
+                return usage(":export <file.ak|directory>");
             }
-            Err(err) => {
-                eprintln!("❌ Error: {}", err);
-                // Check if it's a diagnostic error and print it nicely
-                if let ReplError::ProjectError(project_err) = &err {
-                    eprintln!("{:?}", project_err);
+            return match repl.export(Path::new(args)) {
+                Ok(files) => {
+                    for file in files {
+                        println!("📦 Wrote {}", file.display());
+                    }
+                    Step::Continue
+                }
+                Err(err) => report(&err, error_format),
+            };
+        }
+        ":cbor" => {
+            if args.is_empty() {
+                return usage(":cbor <expr>");
+            }
+            return match repl.cbor(args) {
+                Ok(script) => {
+                    println!("{}", script);
+                    Step::Continue
+                }
+                Err(err) => report(&err, error_format),
+            };
+        }
+        ":profile" => {
+            if args.is_empty() {
+                return usage(":profile <expr>");
+            }
+            return match repl.profile(args) {
+                Ok(profile) => {
+                    println!("{}", profile);
+                    Step::Continue
+                }
+                Err(err) => report(&err, error_format),
+            };
+        }
+        ":full-output" => {
+            if args.is_empty() {
+                return usage(":full-output <expr>");
+            }
+            return match repl.eval_full(args) {
+                Ok(result) => {
+                    println!("{}", result);
+                    Step::Continue
+                }
+                Err(err) => report(&err, error_format),
+            };
+        }
+        ":check" => {
+            return match repl.check() {
+                Ok(report) => {
+                    println!("{}", report);
+                    if report.passed() {
+                        Step::Continue
+                    } else {
+                        Step::Failed
+                    }
+                }
+                Err(err) => report(&err, error_format),
+            };
+        }
+        ":fuzz" => {
+            let args = match FuzzArgs::parse(args) {
+                Ok(args) => args,
+                Err(_) => {
+                    return usage(
+                        ":fuzz <fn> with <fuzzer> satisfying <property> [--max-success <n>] [--seed <n>]",
+                    );
+                }
+            };
+            return match repl.fuzz(&args) {
+                Ok(report) => {
+                    println!("{}", report);
+                    if report.failed() == 0 {
+                        Step::Continue
+                    } else {
+                        Step::Failed
+                    }
+                }
+                Err(err) => report(&err, error_format),
+            };
+        }
+        ":uplc" => {
+            let (annotated, code) = match args.strip_prefix("--annotated") {
+                Some(code) => (true, code.trim()),
+                None => (false, args),
+            };
+            if code.is_empty() {
+                return usage(":uplc [--annotated] <expr>");
+            }
+            return match repl.compile_to_uplc(code) {
+                Ok(dump) if annotated => {
+                    println!("{}", dump.annotated());
+                    Step::Continue
+                }
+                Ok(dump) => {
+                    println!("{}", dump);
+                    Step::Continue
+                }
+                Err(err) => report(&err, error_format),
+            };
+        }
+        ":flamegraph" => {
+            let args = match FlamegraphArgs::parse(args) {
+                Ok(args) => args,
+                Err(_) => return usage(":flamegraph [-o <path>] <expr>"),
+            };
+            return match repl.flamegraph(&args) {
+                Ok(profile) => {
+                    match args.output {
+                        Some(output) => println!("🔥 Wrote {}", output),
+                        None => print!("{}", profile.folded()),
+                    }
+                    Step::Continue
+                }
+                Err(err) => report(&err, error_format),
+            };
+        }
+        ":set" => {
+            return match args.split_once(char::is_whitespace) {
+                Some((name, value)) => match repl.set_option(name, value.trim()) {
+                    Ok(()) => {
+                        println!("✓ {} = {}", name, value.trim());
+                        Step::Continue
+                    }
+                    Err(err) => report(&err, error_format),
+                },
+                None if args.is_empty() => {
+                    println!("{}", repl.options());
+                    Step::Continue
+                }
+                None => usage(":set <option> <value>"),
+            };
+        }
+        ":show" => {
+            if args != "settings" {
+                return usage(":show settings");
+            }
+            println!("{}", repl.options());
+            return Step::Continue;
+        }
+        ":warnings" => {
+            match args {
+                "on" => repl.set_warnings(true),
+                "off" => repl.set_warnings(false),
+                _ => return usage(":warnings on|off"),
+            }
+            println!("✓ Warnings {}", args);
+            return Step::Continue;
+        }
+        ":probe" => {
+            return match args {
+                "" => usage(":probe <expr>|off"),
+                "off" => {
+                    repl.clear_probe();
+                    println!("✓ Probe off");
+                    Step::Continue
+                }
+                code => match repl.set_probe(code) {
+                    Ok(measure) => {
+                        println!("✓ Probe set: {}", measure);
+                        Step::Continue
+                    }
+                    Err(err) => report(&err, error_format),
+                },
+            };
+        }
+        ":fmt" => {
+            // Format the given code, or the whole context if there's none
+            let formatted = if args.is_empty() {
+                repl.format_context()
+            } else {
+                repl.format(args)
+            };
+            return match formatted {
+                Ok(code) => {
+                    println!("{}", code);
+                    Step::Continue
                 }
+                Err(err) => report(&err, error_format),
+            };
+        }
+        ":autoformat" => {
+            match args {
+                "on" => repl.set_auto_format(true),
+                "off" => repl.set_auto_format(false),
+                _ => return usage(":autoformat on|off"),
             }
+            println!("✓ Auto-format {}", args);
+            return Step::Continue;
         }
+        "" => return Step::Continue, // Empty line
+        _ => {}
     }
 
-    // Save history before exiting
-    let _ = rl.save_history(".aiken_repl_history");
+    // Evaluate the input
+    let result = repl.eval(input);
+    if cli.json {
+        println!("{}", json::evaluation(&result, repl.take_warnings()));
+        return if result.is_ok() {
+            Step::Continue
+        } else {
+            Step::Failed
+        };
+    }
+    for warning in repl.take_warnings() {
+        eprintln!("{}", warning);
+    }
+    match result {
+        Ok(result) => {
+            match result {
+                EvaluationResult::Value { .. }
+                | EvaluationResult::Cached { .. }
+                | EvaluationResult::Traced { .. }
+                | EvaluationResult::Definition { .. }
+                | EvaluationResult::Definitions(_)
+                | EvaluationResult::TestReport(_)
+                | EvaluationResult::Mixed { .. } => {
+                    println!("{}", result);
+                }
+                EvaluationResult::Warnings(_) => {
+                    eprintln!("{}", result);
+                }
+                EvaluationResult::NoResult => {
+                    println!("✓ Ok");
+                }
+            }
+            if let Some(report) = repl.take_redefinition_report() {
+                println!("📏 {}", report);
+            }
+            Step::Continue
+        }
+        Err(err) => report(&err, error_format),
+    }
+}
+
+fn usage(usage: &str) -> Step {
+    eprintln!("Usage: {}", usage);
+    Step::Failed
+}
+
+/// Open the definitions in the user's editor, and replace the context with the saved code
+fn edit_context(repl: &mut ReplEvaluator, error_format: ErrorFormat) -> Step {
+    let edited = match edit::in_editor(repl.definitions()) {
+        Ok(edited) => edited,
+        Err(err) => {
+            eprintln!("❌ Cannot edit the context: {}", err);
+            return Step::Failed;
+        }
+    };
+    if edited.trim() == repl.definitions().trim() {
+        println!("✓ No changes");
+        return Step::Continue;
+    }
+
+    match repl.replace_context(&edited) {
+        Ok(()) => {
+            println!("✓ Context replaced");
+            if let Some(report) = repl.take_redefinition_report() {
+                println!("📏 {}", report);
+            }
+            Step::Continue
+        }
+        Err(err) => {
+            let step = report(&err, error_format);
+            eprintln!("Context unchanged");
+            step
+        }
+    }
+}
+
+fn print_doc(repl: &ReplEvaluator, name: &str, error_format: ErrorFormat) -> Step {
+    if name.is_empty() {
+        return usage(":doc <name>");
+    }
+    match repl.doc(name) {
+        Ok(Some(entry)) => println!("{}", entry),
+        Ok(None) => {
+            eprintln!("❌ No definition named `{}` in scope", name);
+            return Step::Failed;
+        }
+        Err(err) => return report(&err, error_format),
+    }
+    Step::Continue
+}
+
+/// Print an error with its source snippet, if it has one
+fn report(err: &ReplError, error_format: ErrorFormat) -> Step {
+    match error_format {
+        ErrorFormat::Fancy => {
+            // Detects the terminal's width and whether it supports colors
+            let handler = MietteHandlerOpts::new().build();
+            eprintln!("❌ {:?}", Rendered(&handler, err));
+        }
+        ErrorFormat::Plain => eprintln!("{:?}", Rendered(&NarratableReportHandler::new(), err)),
+        ErrorFormat::Json => eprintln!("{:?}", Rendered(&JSONReportHandler::new(), err)),
+    }
+    Step::Failed
+}
+
+/// A diagnostic rendered by a report handler
+struct Rendered<'a>(&'a dyn ReportHandler, &'a dyn Diagnostic);
+
+impl fmt::Debug for Rendered<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.debug(self.1, f)
+    }
 }
 
 fn print_help() {
@@ -99,6 +828,49 @@ fn print_help() {
     println!("  :quit, :q       - Exit the REPL");
     println!("  :reset          - Clear all definitions and restart");
     println!("  :context, :ctx  - Show current context info");
+    println!("  :gc             - Remove build artifacts of the temporary project");
+    println!("  :check          - Check every definition and run the tests, as `aiken check` does");
+    println!(
+        "  :fuzz <fn> with <fuzzer> satisfying <property> [--max-success <n>] [--seed <n>] - Check `property(fn, input)` on inputs drawn from `fuzzer`"
+    );
+    println!("  :doc <name>     - Show docs and signature (eg. `:doc my_fn`, `:doc list.map`)");
+    println!("  :cbor <expr>    - Show the CBOR hex and size of an expression compiled to UPLC");
+    println!(
+        "  :profile <expr> - Show which functions and builtins spend the budget of an expression"
+    );
+    println!(
+        "  :full-output <expr> - Show the result of an expression whole, ignoring max-width and max-chars"
+    );
+    println!("  :decode-address <address> - Show the parts of an address and its Aiken literal");
+    println!(
+        "  :to-data [type] <json|cbor> - Show data given as JSON or CBOR, as a value of `type`"
+    );
+    println!("  :from-data <expr> - Show the data of a value, as JSON and CBOR");
+    println!(
+        "  :simulate <tx> [--inputs <utxos.json>] - Run the scripts of a transaction, with their budgets and traces"
+    );
+    println!("  :edit           - Edit the current context in $VISUAL or $EDITOR");
+    println!("  :undo           - Take back the last change to the context");
+    println!("  :forget <name>  - Remove a definition (or an import) from the context");
+    println!("  :checkpoint [name] - Save the context and settings as `name`, or list checkpoints");
+    println!("  :rollback <name> - Restore the context and settings saved as `name`");
+    println!("  :keygen <name>  - Generate a key pair, defining `<name>_vk` and `<name>_vkh`");
+    println!("  :keys           - List the keys generated in the session");
+    println!("  :version        - Show the versions of the REPL, Aiken and Plutus");
+    println!("  :sign <name> <expr> - Sign the bytes of an expression with a key of the session");
+    println!("  :chain [config <project id>] - Show or set the Blockfrost project UTxOs come from");
+    println!("  :utxos <address> - List the UTxOs at an address, from the chain provider");
+    println!("  :export <path>  - Write the context to a module (`.ak`) or to a new project");
+    println!("  :fmt [code]     - Format the given code, or the current context");
+    println!("  :autoformat on|off - Format definitions before adding them to the context");
+    println!(
+        "  :probe <expr>|off - Compare size and budget of an expression when redefining functions"
+    );
+    println!("  :set [option value] - Show or change options (eg. `:set bytearray-display utf8`)");
+    println!("  :show settings  - Show every option with its value");
+    println!("  :warnings on|off - Show compiler warnings such as unused definitions");
+    println!("  :cache on|off   - Reuse results of expressions that were already evaluated");
+    println!("  :cache clear    - Remove the build artifacts shared by sessions");
     println!();
     println!("Examples:");
     println!("  True                          // Boolean literal");
@@ -0,0 +1,38 @@
+//! Versions of the REPL and of the Aiken compiler it's built with, for `--version` and the
+//! kernel's `%version`
+
+use aiken_project::config::compiler_version;
+
+/// Plutus versions the session can evaluate with, as named by `:set plutus-version`
+pub const PLUTUS_VERSIONS: &[&str] = &["v1", "v2", "v3"];
+
+/// Version of aiken-lang and aiken-project, eg. `1.1.19`. They're released together.
+pub fn aiken_version() -> String {
+    compiler_version(false).trim_start_matches('v').to_string()
+}
+
+/// `program` and its version, then the Aiken compiler with its commit and the Plutus versions,
+/// one per line
+pub fn report(program: &str, version: &str) -> String {
+    format!(
+        "{} {}\naiken-lang, aiken-project {}\nPlutus {}",
+        program,
+        version,
+        compiler_version(true),
+        PLUTUS_VERSIONS.join(", ")
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::{aiken_version, report};
+
+    #[test]
+    fn test_report() {
+        let report = report("aiken-repl", "0.0.1");
+        let lines: Vec<&str> = report.lines().collect();
+        assert_eq!(lines[0], "aiken-repl 0.0.1");
+        assert!(lines[1].starts_with(&format!("aiken-lang, aiken-project v{}", aiken_version())));
+        assert_eq!(lines[2], "Plutus v1, v2, v3");
+    }
+}
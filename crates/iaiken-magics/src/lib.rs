@@ -0,0 +1,272 @@
+//! Magic commands of the iaiken kernel
+//!
+//! A magic is a line (`%name args`) or a cell (`%%name args` followed by the cell body) that the
+//! kernel handles itself instead of evaluating as Aiken code. Each one implements [`Magic`],
+//! is registered with [`register!`] and is looked up by name in a [`Registry`]. The kernel's own
+//! magics are registered that way too.
+//!
+//! A plugin is a crate that registers its magics with [`register!`] and that the kernel is built
+//! with: add it to the dependencies of `iaiken` (behind a feature, to keep it optional) and link
+//! it with `use my_magics as _;`, as crates nothing refers to are left out of the binary.
+//!
+//! ```ignore
+//! use iaiken_magics::{Flavor, Invocation, KernelState, Magic, MimeBundle, text};
+//!
+//! struct Hello;
+//!
+//! impl Magic for Hello {
+//!     fn name(&self) -> &str {
+//!         "hello"
+//!     }
+//!
+//!     fn flavor(&self) -> Flavor {
+//!         Flavor::Line
+//!     }
+//!
+//!     fn help(&self) -> &str {
+//!         "`%hello [name]`: say hello"
+//!     }
+//!
+//!     fn execute(&self, _: &mut KernelState, call: &Invocation) -> Result<MimeBundle, String> {
+//!         Ok(text(format!("Hello {}", call.args)))
+//!     }
+//! }
+//!
+//! iaiken_magics::register!(Hello);
+//! ```
+
+use std::collections::BTreeMap;
+
+use aiken_repl::evaluator::{MockChain, ReplEvaluator};
+pub use jupyter_protocol::{MimeBundle, shell::execute::Payload};
+
+#[doc(hidden)]
+pub use inventory;
+
+/// Whether a magic is called on a line or on a whole cell
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Flavor {
+    /// `%name args`
+    Line,
+    /// `%%name args`, followed by the cell body
+    Cell,
+}
+
+impl Flavor {
+    pub fn prefix(&self) -> &'static str {
+        match self {
+            Flavor::Line => "%",
+            Flavor::Cell => "%%",
+        }
+    }
+}
+
+/// A call of a magic, as written in a cell
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Invocation<'a> {
+    pub flavor: Flavor,
+    pub name: &'a str,
+    /// The rest of the line, trimmed
+    pub args: &'a str,
+    /// The lines after the first, as they are, for cell magics. Empty for line magics.
+    pub body: &'a str,
+}
+
+impl<'a> Invocation<'a> {
+    /// Parse a cell as a call of a magic if it starts with `%`
+    pub fn parse(code: &'a str) -> Option<Self> {
+        if let Some(cell) = code.trim_start().strip_prefix("%%") {
+            let (first_line, body) = cell.split_once('\n').unwrap_or((cell, ""));
+            let (name, args) = split_name(first_line.trim());
+            return Some(Invocation {
+                flavor: Flavor::Cell,
+                name,
+                args,
+                body,
+            });
+        }
+
+        let (name, args) = split_name(code.trim().strip_prefix('%')?);
+        Some(Invocation {
+            flavor: Flavor::Line,
+            name,
+            args,
+            body: "",
+        })
+    }
+
+    /// The error for a magic no one registered
+    pub fn unknown(&self) -> String {
+        match self.flavor {
+            Flavor::Line => format!("Unknown magic: %{}", self.name),
+            Flavor::Cell => format!("Unknown cell magic: %%{}", self.name),
+        }
+    }
+}
+
+fn split_name(line: &str) -> (&str, &str) {
+    line.split_once(char::is_whitespace)
+        .map(|(name, args)| (name, args.trim()))
+        .unwrap_or((line, ""))
+}
+
+/// What magics run against: the session of the notebook and what the kernel keeps next to it
+pub struct KernelState<'a> {
+    pub eval: &'a mut ReplEvaluator,
+    pub chain: &'a mut Option<MockChain>,
+    /// Actions for the frontend, sent with the reply, eg. to show a result in the pager
+    pub payload: Vec<Payload>,
+}
+
+/// A magic command of the kernel
+pub trait Magic: Send + Sync {
+    /// Name it's called by, without the `%`
+    fn name(&self) -> &str;
+
+    fn flavor(&self) -> Flavor;
+
+    /// One line on how to call it and what it does, eg. ``"`%doc <name>`: show docs and
+    /// signature of a definition"``
+    fn help(&self) -> &str;
+
+    /// Whether `call` changes the definitions of the session, or how they compile. Such cells
+    /// are replayed by `%recover` and by `--export-notebook`.
+    fn defines(&self, _call: &Invocation) -> bool {
+        false
+    }
+
+    /// Run `call`, returning what to display. Errors are shown as the cell's error.
+    fn execute(&self, kernel: &mut KernelState, call: &Invocation) -> Result<MimeBundle, String>;
+}
+
+/// The magics of the kernel, by flavor and name
+#[derive(Default)]
+pub struct Registry {
+    magics: BTreeMap<(Flavor, String), Box<dyn Magic>>,
+}
+
+impl Registry {
+    /// Add `magic`, returning the one it replaces if its name was taken
+    pub fn register(&mut self, magic: Box<dyn Magic>) -> Option<Box<dyn Magic>> {
+        self.magics
+            .insert((magic.flavor(), magic.name().to_string()), magic)
+    }
+
+    pub fn get(&self, flavor: Flavor, name: &str) -> Option<&dyn Magic> {
+        self.magics
+            .get(&(flavor, name.to_string()))
+            .map(|magic| magic.as_ref())
+    }
+
+    /// The magic `call` is for, or the error to show for an unknown one
+    pub fn find(&self, call: &Invocation) -> Result<&dyn Magic, String> {
+        self.get(call.flavor, call.name)
+            .ok_or_else(|| call.unknown())
+    }
+
+    /// Every magic, line magics first, in the order of their names
+    pub fn iter(&self) -> impl Iterator<Item = &dyn Magic> {
+        self.magics.values().map(|magic| magic.as_ref())
+    }
+}
+
+/// A magic registered with [`register!`]
+pub struct Plugin(pub fn() -> Box<dyn Magic>);
+
+inventory::collect!(Plugin);
+
+/// Register a magic with the kernels built with the crate calling it
+#[macro_export]
+macro_rules! register {
+    ($magic:expr) => {
+        $crate::inventory::submit! {
+            $crate::Plugin(|| ::std::boxed::Box::new($magic))
+        }
+    };
+}
+
+/// Every magic registered with [`register!`], by the kernel and by the plugins it's built with
+pub fn registered() -> impl Iterator<Item = Box<dyn Magic>> {
+    inventory::iter::<Plugin>
+        .into_iter()
+        .map(|plugin| (plugin.0)())
+}
+
+/// A display of plain text
+pub fn text(text: impl Into<String>) -> MimeBundle {
+    let mut bundle = MimeBundle::new();
+    bundle.insert("text/plain".to_string(), text.into().into());
+    bundle
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Flavor, Invocation, KernelState, Magic, MimeBundle, Registry, registered, text};
+
+    struct Echo(Flavor);
+
+    impl Magic for Echo {
+        fn name(&self) -> &str {
+            "echo"
+        }
+
+        fn flavor(&self) -> Flavor {
+            self.0
+        }
+
+        fn help(&self) -> &str {
+            "`%echo <text>`: show the text"
+        }
+
+        fn execute(&self, _: &mut KernelState, call: &Invocation) -> Result<MimeBundle, String> {
+            Ok(text(call.args))
+        }
+    }
+
+    crate::register!(Echo(Flavor::Cell));
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            Invocation::parse("  %doc  add \n"),
+            Some(Invocation {
+                flavor: Flavor::Line,
+                name: "doc",
+                args: "add",
+                body: "",
+            })
+        );
+        assert_eq!(
+            Invocation::parse("%%timeit -n 10\nadd(1, 2)\n"),
+            Some(Invocation {
+                flavor: Flavor::Cell,
+                name: "timeit",
+                args: "-n 10",
+                body: "add(1, 2)\n",
+            })
+        );
+        assert_eq!(Invocation::parse("%gc").map(|call| call.args), Some(""));
+        assert_eq!(Invocation::parse("1 % 2"), None);
+    }
+
+    #[test]
+    fn test_registry() {
+        let mut registry = Registry::default();
+        assert!(registry.register(Box::new(Echo(Flavor::Line))).is_none());
+        assert!(registry.register(Box::new(Echo(Flavor::Line))).is_some());
+
+        let call = Invocation::parse("%echo hi").unwrap();
+        assert_eq!(registry.find(&call).map(|magic| magic.name()), Ok("echo"));
+        let call = Invocation::parse("%%echo\nhi").unwrap();
+        assert_eq!(
+            registry.find(&call).map(|magic| magic.name()),
+            Err("Unknown cell magic: %%echo".to_string())
+        );
+
+        for magic in registered() {
+            registry.register(magic);
+        }
+        assert!(registry.get(Flavor::Cell, "echo").is_some());
+        assert_eq!(registry.iter().count(), 2);
+    }
+}
@@ -0,0 +1,38 @@
+// CodeMirror mode for Aiken, loaded by the notebook frontend from the kernelspec resources.
+// DOCS: https://jupyter-client.readthedocs.io/en/latest/kernels.html#kernel-specs
+define(["codemirror/lib/codemirror", "codemirror/addon/mode/simple"], function (CodeMirror) {
+  "use strict";
+
+  var keywords =
+    "and as expect fn else if is let opaque or pub trace todo fail type use when validator const test bench via";
+
+  CodeMirror.defineSimpleMode("aiken", {
+    start: [
+      { regex: /\/\/.*/, token: "comment" },
+      { regex: /"(?:[^\\"]|\\.)*"/, token: "string" },
+      { regex: /#"(?:[^\\"]|\\.)*"/, token: "string" },
+      { regex: /#[0-9a-fA-F]*/, token: "number" },
+      { regex: /0x[0-9a-fA-F_]+|0b[01_]+|0o[0-7_]+|[0-9][0-9_]*/, token: "number" },
+      { regex: new RegExp("\\b(?:" + keywords.split(" ").join("|") + ")\\b"), token: "keyword" },
+      { regex: /\b(?:True|False)\b/, token: "atom" },
+      { regex: /\b[A-Z][A-Za-z0-9_]*\b/, token: "type" },
+      { regex: /\b[a-z_][a-z0-9_]*(?=\s*\()/, token: "def" },
+      { regex: /%%?[a-z_]+/, token: "meta", sol: true },
+      { regex: /->|\|>|[-+*\/%<>=!&|]+/, token: "operator" },
+      { regex: /[{[(]/, indent: true },
+      { regex: /[}\])]/, dedent: true },
+    ],
+    meta: {
+      lineComment: "//",
+      electricChars: "}])",
+    },
+  });
+
+  CodeMirror.defineMIME("text/x-aiken", "aiken");
+
+  return {
+    onload: function () {
+      console.info("Aiken CodeMirror mode loaded");
+    },
+  };
+});
@@ -1,18 +1,51 @@
-use crate::messages::ConnectionConfig;
+use aiken_repl::{config::Config, history::History};
 use control::control_loop;
 use heartbeat::heartbeat_loop;
+use iopub::iopub_loop;
+use jupyter_protocol::ConnectionConfig;
+use jupyter_protocol::iopub::{ExecutionState, kernel_status};
 use shell::shell_loop;
 use std::fs;
-use tokio::sync::mpsc::unbounded_channel;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::time::Duration;
+use supervisor::supervise;
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
 use tokio_util::sync::CancellationToken;
 use zeromq::Socket;
 
+use crate::eval::notebook;
+use crate::recovery::DefinitionLog;
+use crate::state::{KernelConfig, KernelState};
+
 mod control;
 mod heartbeat;
+mod http;
 mod iopub;
+mod router;
 mod shell;
+mod supervisor;
+#[cfg(test)]
+mod testing;
+
+/// How long a cell runs before its running time is shown, unless configured otherwise
+const DEFAULT_PROGRESS_DELAY: Duration = Duration::from_secs(1);
+
+/// Bytes a cell prints at most, unless configured otherwise
+const DEFAULT_MAX_OUTPUT: usize = 1024 * 1024;
 
-pub async fn run_kernel(connection_file: String) -> anyhow::Result<()> {
+/// Messages queued for IOPub before cells wait, unless configured otherwise
+const DEFAULT_IOPUB_QUEUE: usize = 1024;
+
+pub async fn run_kernel(
+    connection_file: String,
+    codemirror_mode: String,
+    history: Option<History>,
+    definition_log: Option<DefinitionLog>,
+    settings: Config,
+    warmup: bool,
+) -> anyhow::Result<()> {
     // 1. Read the connection file
     let config_data = fs::read_to_string(&connection_file).map_err(|e| {
         anyhow::anyhow!(
@@ -25,9 +58,6 @@ pub async fn run_kernel(connection_file: String) -> anyhow::Result<()> {
     // 2. Parse JSON into ConnectionConfig
     let config: ConnectionConfig = serde_json::from_str(&config_data)
         .map_err(|e| anyhow::anyhow!("Failed to parse connection file: {}", e))?;
-    // TODO: Why can't I just reference the original config and that's it?
-    let shell_config = config.clone();
-    let control_config = config.clone();
 
     // 3. Build ZMQ addresses
     println!("Kernel starting with config:");
@@ -37,7 +67,8 @@ pub async fn run_kernel(connection_file: String) -> anyhow::Result<()> {
     println!("  Stdin: {}", config.stdin_address());
     println!("  Heartbeat: {}", config.hb_address());
 
-    let (iopub_tx, mut iopub_rx) = unbounded_channel::<Vec<bytes::Bytes>>();
+    let iopub_queue = settings.kernel.iopub_queue.unwrap_or(DEFAULT_IOPUB_QUEUE);
+    let (iopub_tx, mut iopub_rx) = mpsc::channel::<Vec<bytes::Bytes>>(iopub_queue.max(1));
 
     // 4. Create ZMQ context and sockets
     let mut shell_socket = zeromq::RouterSocket::new();
@@ -55,69 +86,253 @@ pub async fn run_kernel(connection_file: String) -> anyhow::Result<()> {
 
     println!("All sockets bound successfully!");
 
-    // Initiate code execution count
-    let exec_count = std::sync::Arc::new(std::sync::atomic::AtomicU32::new(0));
+    // Session state shared by all handlers
+    let state = KernelState::new(kernel_config(
+        config.clone(),
+        codemirror_mode,
+        history,
+        definition_log,
+        settings,
+    ));
 
-    //Prepare cancelation tokens
-    let cancel = CancellationToken::new();
-    let cancel_iopub = cancel.clone();
-    let cancel_shell = cancel.clone();
-    let cancel_hb = cancel.clone();
-    let cancel_ctrl = cancel.clone();
-
-    let iopub_handle = tokio::spawn(async move {
-        loop {
-            tokio::select! {
-                _ = cancel_iopub.cancelled() => {
-                    println!("IOPub loop cancelled");
-                    break;
-                }
-                Some(frames) = iopub_rx.recv() => {
-                    // frames are already multipart bytes
-                    let _ = crate::messages::wire::send_bytes(&mut iopub_socket, frames).await;
-                }
-                else => break,
+    // Announce ourselves on IOPub
+    let starting = kernel_status(
+        &config.key,
+        &config.signature_scheme,
+        &state.session,
+        ExecutionState::Starting,
+    )?;
+    let _ = iopub_tx.send(starting).await;
+
+    // Compile the prelude before the first cell needs it. Cells sent meanwhile wait for the
+    // warm-up on the evaluator, and the kernel reports idle once it's done.
+    tokio::spawn({
+        let (iopub_tx, state) = (iopub_tx.clone(), state.clone());
+        async move {
+            if warmup && let Some(Err(e)) = state.evaluator().run(|eval| eval.warm_up()).await {
+                eprintln!("Failed to warm up the evaluator: {}", e);
+            }
+            let idle = kernel_status(
+                &state.config.connection.key,
+                &state.config.connection.signature_scheme,
+                &state.session,
+                ExecutionState::Idle,
+            );
+            if let Ok(idle) = idle {
+                let _ = iopub_tx.send(idle).await;
             }
         }
     });
 
-    // Spawn shell handler
-    let shell_iopub_tx = iopub_tx.clone();
-    let shell_handle = tokio::spawn(async move {
-        shell_loop(
-            cancel_shell,
-            &mut shell_socket,
-            shell_iopub_tx,
-            &shell_config,
-            exec_count,
-        )
-        .await
-    });
+    //Prepare cancelation tokens
+    let cancel = CancellationToken::new();
 
-    // Spawn heartbeat handler
-    let heartbeat_handle =
-        tokio::spawn(async move { heartbeat_loop(cancel_hb, &mut hb_socket).await });
-
-    // Spawn control handler
-    let control_iopub_tx = iopub_tx.clone();
-    let control_handler = tokio::spawn(async move {
-        control_loop(
-            cancel,
-            cancel_ctrl,
-            &mut control_socket,
-            control_iopub_tx,
-            &control_config,
-        )
-        .await
-    });
+    // Each channel loop runs under a supervisor that rebinds its socket if it dies
+    let iopub_handle = tokio::spawn(supervise(
+        "IOPub",
+        cancel.clone(),
+        config.iopub_address(),
+        iopub_socket,
+        {
+            let (cancel, iopub_tx, state) = (cancel.clone(), iopub_tx.clone(), state.clone());
+            async move |socket: &mut zeromq::PubSocket| {
+                iopub_loop(cancel.clone(), socket, &mut iopub_rx, &iopub_tx, &state).await
+            }
+        },
+    ));
+
+    let shell_handle = tokio::spawn(supervise(
+        "Shell",
+        cancel.clone(),
+        config.shell_address(),
+        shell_socket,
+        {
+            let (cancel, iopub_tx, state) = (cancel.clone(), iopub_tx.clone(), state.clone());
+            async move |socket: &mut zeromq::RouterSocket| {
+                shell_loop(cancel.clone(), socket, iopub_tx.clone(), state.clone()).await
+            }
+        },
+    ));
+
+    let heartbeat_handle = tokio::spawn(supervise(
+        "Heartbeat",
+        cancel.clone(),
+        config.hb_address(),
+        hb_socket,
+        {
+            let cancel = cancel.clone();
+            async move |socket: &mut zeromq::RepSocket| heartbeat_loop(cancel.clone(), socket).await
+        },
+    ));
+
+    let control_handle = tokio::spawn(supervise(
+        "Control",
+        cancel.clone(),
+        config.control_address(),
+        control_socket,
+        {
+            let (cancel, iopub_tx, state) = (cancel.clone(), iopub_tx.clone(), state.clone());
+            async move |socket: &mut zeromq::RouterSocket| {
+                control_loop(cancel.clone(), socket, iopub_tx.clone(), state.clone()).await
+            }
+        },
+    ));
 
     // Wait for tasks (they should run until cancelled)
-    let _ = tokio::join!(
-        heartbeat_handle,
-        shell_handle,
-        control_handler,
-        iopub_handle
-    );
+    let _ = tokio::join!(heartbeat_handle, shell_handle, control_handle, iopub_handle);
+
+    // Don't leave the session's temp project behind
+    state.cleanup().await;
 
     Ok(())
 }
+
+/// `--run`: execute the code cells of `notebook` without sockets, in a session of its own that
+/// leaves the history and the definition logs alone. Returns the number of cells run.
+pub async fn run_notebook(
+    notebook: &Path,
+    codemirror_mode: String,
+    settings: Config,
+) -> anyhow::Result<usize> {
+    let state = KernelState::new(kernel_config(
+        unbound(),
+        codemirror_mode,
+        None,
+        None,
+        settings,
+    ));
+    let result = notebook::run_notebook(&state, notebook).await;
+    state.cleanup().await;
+    result
+}
+
+/// `--serve-http`: answer the JSON API of `http` on `address` until interrupted, for a session of
+/// its own
+pub async fn serve_http(
+    address: SocketAddr,
+    codemirror_mode: String,
+    history: Option<History>,
+    definition_log: Option<DefinitionLog>,
+    settings: Config,
+    warmup: bool,
+) -> anyhow::Result<()> {
+    // Cells can write files and run programs: don't let other machines in
+    if !address.ip().is_loopback() {
+        anyhow::bail!(
+            "{} isn't a loopback address, the JSON API only listens on those",
+            address
+        );
+    }
+    let listener = TcpListener::bind(address)
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to listen on {}: {}", address, e))?;
+    let token = http::new_token()?;
+    println!("Serving the JSON API on http://{}", listener.local_addr()?);
+    println!("Send `Authorization: Bearer {}` with each request", token);
+
+    let state = KernelState::new(kernel_config(
+        unbound(),
+        codemirror_mode,
+        history,
+        definition_log,
+        settings,
+    ));
+    if warmup && let Some(Err(e)) = state.evaluator().run(|eval| eval.warm_up()).await {
+        eprintln!("Failed to warm up the evaluator: {}", e);
+    }
+
+    let cancel = CancellationToken::new();
+    tokio::spawn({
+        let cancel = cancel.clone();
+        async move {
+            let _ = tokio::signal::ctrl_c().await;
+            cancel.cancel();
+        }
+    });
+    http::serve(listener, state.clone(), token, cancel).await;
+
+    // Don't leave the session's temp project behind
+    state.cleanup().await;
+    Ok(())
+}
+
+/// Connection of a session no frontend connects to, which is never bound
+fn unbound() -> ConnectionConfig {
+    ConnectionConfig {
+        transport: "tcp".to_string(),
+        ip: "127.0.0.1".to_string(),
+        signature_scheme: "hmac-sha256".to_string(),
+        key: String::new(),
+        control_port: 0,
+        shell_port: 0,
+        stdin_port: 0,
+        hb_port: 0,
+        iopub_port: 0,
+    }
+}
+
+/// Settings of a session, from the configuration and the command line
+fn kernel_config(
+    connection: ConnectionConfig,
+    codemirror_mode: String,
+    history: Option<History>,
+    definition_log: Option<DefinitionLog>,
+    settings: Config,
+) -> KernelConfig {
+    KernelConfig {
+        connection,
+        codemirror_mode,
+        history,
+        definition_log,
+        options: settings.options,
+        progress_delay: settings
+            .kernel
+            .progress_delay()
+            .unwrap_or(DEFAULT_PROGRESS_DELAY),
+        max_output: settings.kernel.max_output.unwrap_or(DEFAULT_MAX_OUTPUT),
+        error_format: settings.kernel.error_format.unwrap_or_default(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use aiken_repl::config::Config;
+    use serde_json::json;
+
+    use super::run_notebook;
+
+    #[tokio::test]
+    async fn test_run_notebook() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("demo.ipynb");
+        let notebook = json!({
+            "cells": [
+                { "cell_type": "markdown", "source": "# Demo" },
+                { "cell_type": "code", "source": "1 + 2", "outputs": [] },
+                { "cell_type": "code", "source": "", "outputs": [] },
+                { "cell_type": "code", "source": ["this_is_", "not_defined"], "outputs": [] },
+                { "cell_type": "code", "source": "1 + 2", "outputs": [] }
+            ],
+            "nbformat": 4,
+            "nbformat_minor": 5
+        });
+        std::fs::write(&path, notebook.to_string()).unwrap();
+
+        let error = run_notebook(&path, "aiken".to_string(), Config::default())
+            .await
+            .unwrap_err();
+        assert!(error.to_string().starts_with("Code cell 3 failed"));
+
+        // The notebook is written up to the cell that failed
+        let notebook: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        let cells = &notebook["cells"];
+        assert_eq!(cells[1]["execution_count"], 1);
+        assert_eq!(cells[1]["outputs"][0]["output_type"], "execute_result");
+        assert_eq!(cells[2]["outputs"], json!([]));
+        assert_eq!(cells[3]["execution_count"], 2);
+        assert_eq!(cells[3]["outputs"][0]["output_type"], "error");
+        assert_eq!(cells[3]["outputs"][0]["ename"], "AikenError");
+        assert!(cells[4]["execution_count"].is_null());
+    }
+}
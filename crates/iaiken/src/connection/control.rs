@@ -2,86 +2,233 @@ use tokio_util::sync::CancellationToken;
 use zeromq::RouterSocket;
 use zeromq::SocketRecv;
 
-use crate::messages::control::shutdown::{ShutdownReply, ShutdownRequest};
-use crate::messages::wire::{delim_index, send_bytes};
-use crate::messages::{ConnectionConfig, JupyterMessage, MessageHeader};
+use jupyter_protocol::control::debug::DebugRequest;
+use jupyter_protocol::control::shutdown::{ShutdownReply, ShutdownRequest};
+use jupyter_protocol::control::subshell::{
+    CreateSubshellReply, CreateSubshellRequest, DeleteSubshellReply, DeleteSubshellRequest,
+    ListSubshellReply, ListSubshellRequest,
+};
+use jupyter_protocol::iopub::ExecutionState;
+use jupyter_protocol::reply::{Reply, ReplyError};
+use jupyter_protocol::wire::send_bytes;
+use jupyter_protocol::{JupyterMessage, MessageHeader};
 
 use super::iopub::IopubTx;
+use super::router::{ControlHandler, Incoming, dispatch_control};
+use super::supervisor::LoopExit;
+use crate::state::SharedState;
+use debug::Debugger;
+
+mod debug;
+
+/// Handles requests on the control channel
+struct Control<'a> {
+    cancel: CancellationToken,
+    state: SharedState,
+    socket: &'a mut RouterSocket,
+    iopub_tx: IopubTx,
+    debugger: Debugger,
+}
+
+impl Control<'_> {
+    /// Send `content` as the reply to `incoming`, wrapped in busy/idle statuses
+    async fn reply<C: serde::Serialize>(&mut self, incoming: Incoming, msg_type: &str, content: C) {
+        let raw_msg = incoming.raw_msg.clone();
+        self.publish_status(&raw_msg, ExecutionState::Busy).await;
+        self.send_reply(incoming, msg_type, content).await;
+        self.publish_status(&raw_msg, ExecutionState::Idle).await;
+    }
+
+    async fn publish_status(
+        &self,
+        raw_msg: &JupyterMessage<serde_json::Value>,
+        state: ExecutionState,
+    ) {
+        let config = &self.state.config.connection;
+        if let Ok(frames) = raw_msg.to_iopub_status(&config.key, &config.signature_scheme, state) {
+            let _ = self.iopub_tx.send(frames).await;
+        }
+    }
+
+    /// Send `content` as the reply to `incoming`
+    async fn send_reply<C: serde::Serialize>(
+        &mut self,
+        incoming: Incoming,
+        msg_type: &str,
+        content: C,
+    ) {
+        let config = &self.state.config.connection;
+        let raw_msg = incoming.raw_msg;
+
+        let reply_msg = JupyterMessage {
+            header: MessageHeader::new(raw_msg.header.session.clone(), msg_type.to_string()),
+            parent_header: Some(raw_msg.header.clone()),
+            metadata: serde_json::Value::Object(serde_json::Map::new()),
+            content,
+        };
+        match reply_msg.to_envelope_multipart(
+            incoming.frames,
+            incoming.delim_index,
+            &config.key,
+            &config.signature_scheme,
+        ) {
+            Ok(bytes_frames) => {
+                if let Err(e) = send_bytes(self.socket, bytes_frames).await {
+                    eprintln!("Failed to send {msg_type}: {e}");
+                }
+            }
+            Err(e) => eprintln!("Failed to create {msg_type}: {e}"),
+        }
+    }
+}
+
+impl ControlHandler for Control<'_> {
+    async fn reject(&mut self, incoming: Incoming, error: ReplyError) {
+        eprintln!("Rejected {}: {}", incoming.msg_type(), error.evalue);
+        if let Some(msg_type) = incoming.reply_type() {
+            let reply = Reply::<serde_json::Value>::Error(error);
+            self.reply(incoming, &msg_type, reply).await;
+        }
+    }
+
+    async fn shutdown(&mut self, incoming: Incoming, request: ShutdownRequest) {
+        let config = &self.state.config.connection;
+        let raw_msg = incoming.raw_msg;
+
+        if let Ok(frames) =
+            raw_msg.to_iopub_status(&config.key, &config.signature_scheme, ExecutionState::Busy)
+        {
+            let _ = self.iopub_tx.send(frames).await;
+        }
+
+        // Build reply
+        let reply_header =
+            MessageHeader::new(raw_msg.header.session.clone(), "shutdown_reply".to_string());
+        let reply = ShutdownReply {
+            restart: request.restart,
+        };
+        let reply_msg = JupyterMessage {
+            header: reply_header,
+            parent_header: Some(raw_msg.header.clone()),
+            metadata: serde_json::Value::Object(serde_json::Map::new()),
+            content: reply,
+        };
+
+        // A restarting kernel starts over from a clean session
+        if request.restart {
+            self.state.reset().await;
+        }
+        // Reuse identity envelope to send reply
+        if let Ok(bytes_frames) = reply_msg.to_envelope_multipart(
+            incoming.frames,
+            incoming.delim_index,
+            &config.key,
+            &config.signature_scheme,
+        ) {
+            // Send reply then cancel
+            if let Err(e) = send_bytes(self.socket, bytes_frames).await {
+                eprintln!("Failed to send shutdown_reply: {e}");
+            }
+            self.cancel.cancel(); // Shutdown! (cancell all loops)
+            return;
+        }
+
+        if let Ok(frames) =
+            raw_msg.to_iopub_status(&config.key, &config.signature_scheme, ExecutionState::Idle)
+        {
+            let _ = self.iopub_tx.send(frames).await;
+        }
+    }
+
+    async fn debug(&mut self, incoming: Incoming, request: DebugRequest) {
+        let raw_msg = incoming.raw_msg.clone();
+        self.publish_status(&raw_msg, ExecutionState::Busy).await;
+
+        let (reply, events) = self.debugger.handle(&self.state, &request).await;
+        self.send_reply(incoming, "debug_reply", reply).await;
+
+        // Events follow the response they belong to, eg. `initialized` after `initialize`
+        let config = &self.state.config.connection;
+        for event in events {
+            match raw_msg.to_iopub_debug_event(&config.key, &config.signature_scheme, event) {
+                Ok(frames) => {
+                    let _ = self.iopub_tx.send(frames).await;
+                }
+                Err(e) => eprintln!("Failed to create debug_event: {e}"),
+            }
+        }
+
+        self.publish_status(&raw_msg, ExecutionState::Idle).await;
+    }
+
+    async fn create_subshell(&mut self, incoming: Incoming, _request: CreateSubshellRequest) {
+        let reply: Reply<CreateSubshellReply> = Reply::Ok(CreateSubshellReply {
+            subshell_id: self.state.create_subshell(),
+        });
+        self.reply(incoming, "create_subshell_reply", reply).await;
+    }
+
+    async fn delete_subshell(&mut self, incoming: Incoming, request: DeleteSubshellRequest) {
+        // Deleting removes the subshell's temp project, on the subshell's evaluator thread
+        let deleted = self.state.delete_subshell(&request.subshell_id).await;
+
+        let reply = if deleted {
+            Reply::Ok(DeleteSubshellReply {})
+        } else {
+            Reply::Error(ReplyError::new(
+                "SubshellNotFound",
+                format!("No subshell with id {}", request.subshell_id),
+            ))
+        };
+        self.reply(incoming, "delete_subshell_reply", reply).await;
+    }
+
+    async fn list_subshell(&mut self, incoming: Incoming, _request: ListSubshellRequest) {
+        let reply: Reply<ListSubshellReply> = Reply::Ok(ListSubshellReply {
+            subshell_id: self.state.list_subshells(),
+        });
+        self.reply(incoming, "list_subshell_reply", reply).await;
+    }
+}
 
 pub async fn control_loop(
     cancel: CancellationToken,
-    cancel_ctrl: CancellationToken,
     control_socket: &mut RouterSocket,
     iopub_tx: IopubTx,
-    config: &ConnectionConfig,
-) {
+    state: SharedState,
+) -> LoopExit {
+    let mut control = Control {
+        cancel: cancel.clone(),
+        state: state.clone(),
+        socket: control_socket,
+        iopub_tx,
+        debugger: Debugger::new(),
+    };
+
     loop {
         tokio::select! {
-            _ = cancel_ctrl.cancelled() => {
+            _ = cancel.cancelled() => {
                 println!("Control loop cancelled");
-                break;
+                return LoopExit::Cancelled;
             }
-            recv = control_socket.recv() => {
+            recv = control.socket.recv() => {
                 match recv {
                     Ok(message) => {
-                        let frames: Vec<Vec<u8>> = message.iter().map(|f| f.to_vec()).collect();
-                        let ix = match delim_index(&frames) {
-                            Ok(i) => i,
-                            Err(e) => { eprintln!("{e}"); continue; }
-                        };
-                        // Parse as ShutdownRequest
-                        if let Ok(raw_msg) = JupyterMessage::<serde_json::Value>::from_multipart(
-                            &frames, &config.key, &config.signature_scheme
-                        ) {
-                            match raw_msg.header.msg_type.as_str()  {
-                                "shutdown_request" => {
-
-                                 if let Ok(frames) = raw_msg.to_iopub_status(&config.key, &config.signature_scheme, "busy") {
-                                    let _ = iopub_tx.send(frames);
-                                }
-
-                                let req = JupyterMessage::<ShutdownRequest>::from_multipart(
-                                    &frames, &config.key, &config.signature_scheme
-                                ).ok();
-                                let restart = req.as_ref().map(|m| m.content.restart).unwrap_or(false);
-
-                                // Build reply
-                                let reply_header = MessageHeader::new(
-                                    raw_msg.header.session.clone(),
-                                    "shutdown_reply".to_string()
-                                );
-                                let reply = ShutdownReply { restart };
-                                let reply_msg = JupyterMessage {
-                                    header: reply_header,
-                                    parent_header: Some(raw_msg.header.clone()),
-                                    metadata: serde_json::Value::Object(serde_json::Map::new()),
-                                    content: reply,
-                                };
-                                // Reuse identity envelope to send reply
-                                if let Ok(bytes_frames) = reply_msg.to_envelope_multipart(
-                                    frames, ix, &config.key, &config.signature_scheme
-                                ) {
-                                    // Send reply then cancel
-                                    if let Err(e) = send_bytes(control_socket, bytes_frames).await {
-                                        eprintln!("Failed to send shutdown_reply: {e}");
-                                    }
-                                    cancel.cancel(); // Shutdown! (cancell all loops)
-                                    break;
-                                }
-
-
-                                if let Ok(frames) = raw_msg.to_iopub_status(&config.key, &config.signature_scheme, "idle") {
-                                    let _ = iopub_tx.send(frames);
+                        let incoming = match Incoming::parse(message, &state.config.connection) {
+                            Ok(incoming) => incoming,
+                            Err(malformed) => {
+                                eprintln!("{malformed}");
+                                let error = malformed.reply_error();
+                                if let Some(incoming) = malformed.incoming {
+                                    control.reject(*incoming, error).await;
                                 }
-                            },
-                            _ => {
-                                println!("\n\nUnhandled control message type: {}\n\n", raw_msg.header.msg_type);
-                                //Unhandled control message type: kernel_info_request
+                                continue;
                             }
-                            }
-                        }
+                        };
+
+                        dispatch_control(&mut control, incoming).await;
                     }
-                    Err(e) => { eprintln!("Control receive error: {e}"); break; }
+                    Err(e) => { eprintln!("Control receive error: {e}"); return LoopExit::Failed; }
                 }
             }
         }
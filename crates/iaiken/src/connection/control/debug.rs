@@ -0,0 +1,173 @@
+//! Debug Adapter Protocol (DAP) requests received as `debug_request` on the control channel.
+//!
+//! Aiken code runs to completion on the UPLC machine, so there is no stepping or breakpoints
+//! yet. This answers the requests frontends use to set up a debug session and inspect state,
+//! exposing the session's definitions and the results of previous cells as variables.
+
+use std::path::PathBuf;
+
+use jupyter_protocol::control::debug::{
+    DebugEvent, DebugInfoBody, DebugReply, DebugRequest, DumpCellArguments, DumpCellBody,
+    InspectVariablesBody, Variable,
+};
+
+use crate::eval::variables::session_variables;
+use crate::state::SharedState;
+
+/// Seed of the hash used to name dumped cells, the same one ipykernel uses
+const HASH_SEED: u32 = 0xc70f6907;
+const CELL_SUFFIX: &str = ".ak";
+
+pub struct Debugger {
+    /// Sequence number of the last response or event sent
+    seq: u64,
+    started: bool,
+    /// Where dumped cells are written to
+    cells_dir: PathBuf,
+}
+
+impl Debugger {
+    pub fn new() -> Self {
+        Self {
+            seq: 0,
+            started: false,
+            cells_dir: std::env::temp_dir().join(format!("iaiken-debug-{}", std::process::id())),
+        }
+    }
+
+    fn next_seq(&mut self) -> u64 {
+        self.seq += 1;
+        self.seq
+    }
+
+    /// Handle a request, returning the response and the events to publish on IOPub
+    pub async fn handle(
+        &mut self,
+        state: &SharedState,
+        request: &DebugRequest,
+    ) -> (DebugReply, Vec<DebugEvent>) {
+        let mut events = Vec::new();
+        let body = match request.command.as_str() {
+            "initialize" => {
+                let event_seq = self.next_seq();
+                events.push(DebugEvent::new(
+                    event_seq,
+                    "initialized",
+                    serde_json::json!({}),
+                ));
+                Ok(serde_json::json!({ "supportsConfigurationDoneRequest": true }))
+            }
+            "attach" => {
+                self.started = true;
+                Ok(serde_json::json!({}))
+            }
+            "configurationDone" => Ok(serde_json::json!({})),
+            "disconnect" => {
+                self.started = false;
+                Ok(serde_json::json!({}))
+            }
+            "debugInfo" => serde_json::to_value(self.debug_info()).map_err(|e| e.to_string()),
+            "dumpCell" => self.dump_cell(request),
+            "inspectVariables" => {
+                let variables = session_variables(state)
+                    .await
+                    .into_iter()
+                    .map(|variable| Variable {
+                        name: variable.name,
+                        value: variable.value,
+                        type_name: variable.type_name,
+                        variables_reference: 0,
+                    })
+                    .collect();
+                serde_json::to_value(InspectVariablesBody { variables }).map_err(|e| e.to_string())
+            }
+            other => Err(format!("Unsupported debug command: {other}")),
+        };
+
+        let seq = self.next_seq();
+        let reply = match body {
+            Ok(body) => DebugReply::success(seq, request, body),
+            Err(message) => DebugReply::failure(seq, request, message),
+        };
+        (reply, events)
+    }
+
+    fn debug_info(&self) -> DebugInfoBody {
+        DebugInfoBody {
+            is_started: self.started,
+            hash_method: "Murmur2".to_string(),
+            hash_seed: HASH_SEED as u64,
+            tmp_file_prefix: format!("{}{}", self.cells_dir.display(), std::path::MAIN_SEPARATOR),
+            tmp_file_suffix: CELL_SUFFIX.to_string(),
+            breakpoints: Vec::new(),
+            stopped_threads: Vec::new(),
+            rich_rendering: false,
+            exception_paths: Vec::new(),
+        }
+    }
+
+    /// Write the code of a cell to the file frontends expect it in
+    fn dump_cell(&self, request: &DebugRequest) -> Result<serde_json::Value, String> {
+        let arguments: DumpCellArguments =
+            serde_json::from_value(request.arguments.clone()).map_err(|e| e.to_string())?;
+
+        let source_path = self.cells_dir.join(format!(
+            "{}{}",
+            murmur2(arguments.code.as_bytes(), HASH_SEED),
+            CELL_SUFFIX
+        ));
+        std::fs::create_dir_all(&self.cells_dir).map_err(|e| e.to_string())?;
+        std::fs::write(&source_path, &arguments.code).map_err(|e| e.to_string())?;
+
+        let body = DumpCellBody {
+            source_path: source_path.display().to_string(),
+        };
+        serde_json::to_value(body).map_err(|e| e.to_string())
+    }
+}
+
+impl Drop for Debugger {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.cells_dir);
+    }
+}
+
+/// 32 bit MurmurHash2, used by frontends to find the file a cell was dumped to
+fn murmur2(data: &[u8], seed: u32) -> u32 {
+    const M: u32 = 0x5bd1e995;
+    let mut h = seed ^ data.len() as u32;
+
+    let mut chunks = data.chunks_exact(4);
+    for chunk in &mut chunks {
+        let mut k = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        k = k.wrapping_mul(M);
+        k ^= k >> 24;
+        k = k.wrapping_mul(M);
+        h = h.wrapping_mul(M) ^ k;
+    }
+
+    let rest = chunks.remainder();
+    if !rest.is_empty() {
+        let k = rest
+            .iter()
+            .enumerate()
+            .fold(0u32, |k, (i, byte)| k | (*byte as u32) << (8 * i));
+        h = (h ^ k).wrapping_mul(M);
+    }
+
+    h ^= h >> 13;
+    h = h.wrapping_mul(M);
+    h ^ (h >> 15)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{HASH_SEED, murmur2};
+
+    #[test]
+    fn test_murmur2_matches_ipykernel() {
+        assert_eq!(murmur2(b"", HASH_SEED), 3990065800);
+        assert_eq!(murmur2(b"1 + 2", HASH_SEED), 1148854745);
+        assert_eq!(murmur2(b"pub const x = 1", HASH_SEED), 1402970584);
+    }
+}
@@ -2,12 +2,14 @@ use tokio_util::sync::CancellationToken;
 use zeromq::RepSocket;
 use zeromq::{SocketRecv, SocketSend};
 
-pub async fn heartbeat_loop(cancel_hb: CancellationToken, hb_socket: &mut RepSocket) {
+use super::supervisor::LoopExit;
+
+pub async fn heartbeat_loop(cancel_hb: CancellationToken, hb_socket: &mut RepSocket) -> LoopExit {
     loop {
         tokio::select! {
             _ = cancel_hb.cancelled() => {
                   println!("Heartbeat loop cancelled");
-                    break;
+                  return LoopExit::Cancelled;
             }
             msg = hb_socket.recv() => {
                 match msg {
@@ -15,12 +17,12 @@ pub async fn heartbeat_loop(cancel_hb: CancellationToken, hb_socket: &mut RepSoc
                         // Echo message back
                         if let Err(e) = hb_socket.send(message).await {
                             eprintln!("Heartbeat send message error: {e}");
-                            break;
+                            return LoopExit::Failed;
                         }
                     }
                     Err(e) => {
                         eprintln!("Heartbeat receive message error: {e}");
-                        break;
+                        return LoopExit::Failed;
                     }
                 }
             }
@@ -0,0 +1,629 @@
+//! `--serve-http`: a small JSON API over HTTP, for frontends that don't speak the Jupyter protocol
+//! (eg. editor extensions and web UIs), on a session like the kernel's
+//!
+//! Every request is answered with a JSON object, and its connection closed:
+//!
+//! - `POST /eval` with `{"code": "...", "metadata": {...}}` runs a cell as an execute request
+//!   would, with options for it in `metadata.iaiken` (see `cell_options`)
+//! - `POST /reset` starts over with a fresh session
+//! - `GET /context` returns the definitions of the session
+//! - `POST /complete` with `{"code": "...", "cursor_pos": n}` completes the name before the
+//!   cursor, which is counted in characters and defaults to the end of the code, as for
+//!   `complete_request`
+//! - `POST /hover`, with the same request, returns the signature and docs of the name around the
+//!   cursor as the session sees it, as for `inspect_request`
+//! - `GET /variables` lists the definitions of the session with their types, and the results of
+//!   earlier cells, as variable explorers show them
+//!
+//! An editor showing the notebook can so complete and type names against what the kernel has
+//! defined, rather than against the files on disk.
+//!
+//! Cells can write files and run programs, so the server only listens on loopback addresses, and
+//! only answers requests:
+//!
+//! - with a `Host` that is a loopback address or `localhost`, which a page that got its name to
+//!   resolve to the loopback address doesn't send
+//! - without an `Origin`, which browsers send with the requests of web pages and tools don't
+//! - with `Authorization: Bearer <token>`, the token being printed when the server starts
+//! - with a `Content-Type` of `application/json` for `POST`s, which a page can't send without
+//!   asking first
+
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde::{Deserialize, de::DeserializeOwned};
+use serde_json::{Value, json};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_util::sync::CancellationToken;
+
+use super::shell::inspect::name_at;
+use crate::eval::variables::session_variables;
+use crate::eval::{CellResult, aiken_error, complete, execute_aiken_code};
+use crate::messages::{ansi, bundle_text, cell_options};
+use crate::state::SharedState;
+use aiken_repl::config::ErrorFormat;
+
+/// Bytes of a request body at most
+const MAX_BODY: usize = 1024 * 1024;
+
+/// Bytes of the request line, and of each header line, at most
+const MAX_LINE: usize = 8 * 1024;
+
+/// Header lines of a request at most
+const MAX_HEADERS: usize = 100;
+
+/// Time a client has to send its whole request
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Status and body of a response
+type Response = (u16, Value);
+
+struct Request {
+    method: String,
+    path: String,
+    /// Names in lowercase
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl Request {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+#[derive(Deserialize)]
+struct EvalRequest {
+    code: String,
+    #[serde(default)]
+    metadata: Value,
+}
+
+/// A position in some code, for `/complete` and `/hover`
+#[derive(Deserialize)]
+struct CursorRequest {
+    code: String,
+    cursor_pos: Option<usize>,
+}
+
+/// A random token for the requests to the server to carry, in hex
+pub fn new_token() -> anyhow::Result<String> {
+    let mut bytes = [0u8; 32];
+    getrandom::fill(&mut bytes).map_err(|e| anyhow::anyhow!("Failed to make a token: {}", e))?;
+    Ok(hex::encode(bytes))
+}
+
+/// Answer the requests of `listener` carrying `token` until `cancel`. Each connection is handled
+/// on a task of its own, and cells wait for each other on the evaluator.
+pub async fn serve(
+    listener: TcpListener,
+    state: SharedState,
+    token: String,
+    cancel: CancellationToken,
+) {
+    let token: Arc<str> = token.into();
+    loop {
+        tokio::select! {
+            _ = cancel.cancelled() => return,
+            accepted = listener.accept() => match accepted {
+                Ok((stream, _)) => {
+                    let state = state.clone();
+                    let token = token.clone();
+                    tokio::spawn(async move {
+                        if let Err(e) = handle_connection(stream, &state, &token).await {
+                            eprintln!("HTTP connection error: {e}");
+                        }
+                    });
+                }
+                Err(e) => eprintln!("Failed to accept an HTTP connection: {e}"),
+            }
+        }
+    }
+}
+
+async fn handle_connection(
+    mut stream: TcpStream,
+    state: &SharedState,
+    token: &str,
+) -> std::io::Result<()> {
+    let (reader, mut writer) = stream.split();
+    let mut reader = BufReader::new(reader);
+    let (status, body) = match tokio::time::timeout(READ_TIMEOUT, read_request(&mut reader)).await {
+        Ok(request) => match request? {
+            Ok(request) => match authorize(&request, token) {
+                Ok(()) => route(state, request).await,
+                Err(response) => response,
+            },
+            Err(response) => response,
+        },
+        Err(_) => (408, error("The request took too long to send".to_string())),
+    };
+    let body = body.to_string();
+    let response = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        reason(status),
+        body.len(),
+        body
+    );
+    writer.write_all(response.as_bytes()).await?;
+    writer.shutdown().await
+}
+
+/// Read a request, or the response to a malformed one
+async fn read_request<R: AsyncBufRead + Unpin>(
+    reader: &mut R,
+) -> std::io::Result<Result<Request, Response>> {
+    let mut line = String::new();
+    if !read_line(reader, &mut line).await? {
+        return Ok(Err((
+            431,
+            error("The request line is too long".to_string()),
+        )));
+    }
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return Ok(Err((400, error("Malformed request line".to_string()))));
+    };
+    let method = method.to_string();
+    // The query string is of no use to any route
+    let path = target.split('?').next().unwrap_or(target).to_string();
+
+    let mut headers = Vec::new();
+    loop {
+        line.clear();
+        if !read_line(reader, &mut line).await? {
+            return Ok(Err((431, error("A header is too long".to_string()))));
+        }
+        if line.trim().is_empty() {
+            break;
+        }
+        if headers.len() == MAX_HEADERS {
+            return Ok(Err((431, error("There are too many headers".to_string()))));
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            headers.push((name.trim().to_ascii_lowercase(), value.trim().to_string()));
+        }
+    }
+
+    let length = match headers.iter().find(|(name, _)| name == "content-length") {
+        Some((_, value)) => match value.parse::<usize>() {
+            Ok(n) => n,
+            Err(_) => return Ok(Err((400, error("Invalid Content-Length".to_string())))),
+        },
+        None => 0,
+    };
+    if length > MAX_BODY {
+        let message = format!("Requests are {} bytes at most", MAX_BODY);
+        return Ok(Err((413, error(message))));
+    }
+    // Grown as the body comes rather than to the length the client claims
+    let mut body = Vec::new();
+    (&mut *reader)
+        .take(length as u64)
+        .read_to_end(&mut body)
+        .await?;
+    if body.len() < length {
+        return Ok(Err((
+            400,
+            error("The body is shorter than its Content-Length".to_string()),
+        )));
+    }
+    Ok(Ok(Request {
+        method,
+        path,
+        headers,
+        body,
+    }))
+}
+
+/// Read a line of `MAX_LINE` bytes at most into `line`, false if it's longer. The end of the
+/// stream counts as an empty line.
+async fn read_line<R: AsyncBufRead + Unpin>(
+    reader: &mut R,
+    line: &mut String,
+) -> std::io::Result<bool> {
+    let read = (&mut *reader).take(MAX_LINE as u64).read_line(line).await?;
+    Ok(read < MAX_LINE || line.ends_with('\n'))
+}
+
+/// Whether `request` may be answered, or the response refusing it
+fn authorize(request: &Request, token: &str) -> Result<(), Response> {
+    if request.header("origin").is_some() {
+        let message = "Requests from web pages aren't answered".to_string();
+        return Err((403, error(message)));
+    }
+    if !request.header("host").is_some_and(is_loopback_host) {
+        let message = "The Host must be a loopback address or localhost".to_string();
+        return Err((403, error(message)));
+    }
+    let bearer = request
+        .header("authorization")
+        .and_then(|value| value.strip_prefix("Bearer "));
+    if !bearer.is_some_and(|bearer| same_token(bearer.trim(), token)) {
+        let message = "Missing or wrong token, see `Authorization: Bearer <token>`".to_string();
+        return Err((401, error(message)));
+    }
+    let json = request
+        .header("content-type")
+        .and_then(|value| value.split(';').next())
+        .is_some_and(|mime| mime.trim().eq_ignore_ascii_case("application/json"));
+    if request.method == "POST" && !json {
+        let message = "The Content-Type must be application/json".to_string();
+        return Err((415, error(message)));
+    }
+    Ok(())
+}
+
+/// Whether a `Host` header names a loopback address, with or without a port and brackets
+fn is_loopback_host(host: &str) -> bool {
+    // `127.0.0.1:8787` and `[::1]:8787`, or `::1` and `[::1]` without a port
+    let unbracketed = host
+        .strip_prefix('[')
+        .and_then(|host| host.strip_suffix(']'))
+        .unwrap_or(host);
+    let ip = host
+        .parse::<SocketAddr>()
+        .map(|address| address.ip())
+        .or_else(|_| unbracketed.parse::<IpAddr>());
+    match ip {
+        Ok(ip) => ip.is_loopback(),
+        Err(_) => host
+            .split_once(':')
+            .map_or(host, |(name, _)| name)
+            .eq_ignore_ascii_case("localhost"),
+    }
+}
+
+/// Compare tokens in a time that doesn't depend on where they differ
+fn same_token(given: &str, token: &str) -> bool {
+    given.len() == token.len()
+        && given
+            .bytes()
+            .zip(token.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+async fn route(state: &SharedState, request: Request) -> Response {
+    match (request.method.as_str(), request.path.as_str()) {
+        ("POST", "/eval") => match parse(&request.body) {
+            Ok(eval_request) => eval(state, eval_request).await,
+            Err(response) => response,
+        },
+        ("POST", "/reset") => {
+            state.reset().await;
+            (200, json!({ "status": "ok" }))
+        }
+        ("GET", "/context") => {
+            let context = state
+                .evaluator()
+                .run(|eval| eval.definitions().to_string())
+                .await
+                .unwrap_or_default();
+            (200, json!({ "status": "ok", "context": context }))
+        }
+        ("POST", "/complete") => match parse(&request.body) {
+            Ok(complete_request) => completions(state, complete_request).await,
+            Err(response) => response,
+        },
+        ("POST", "/hover") => match parse(&request.body) {
+            Ok(hover_request) => hover(state, hover_request).await,
+            Err(response) => response,
+        },
+        ("GET", "/variables") => {
+            let variables = session_variables(state).await;
+            (200, json!({ "status": "ok", "variables": variables }))
+        }
+        (method, path) => (404, error(format!("No route for {} {}", method, path))),
+    }
+}
+
+/// Run a cell as `handle_execute_request` does, keeping it in the history
+async fn eval(state: &SharedState, request: EvalRequest) -> Response {
+    let execution_count = state.next_execution_count();
+    state.record_history(execution_count, &request.code);
+    let evaluator = state.evaluator();
+    let CellResult {
+        result,
+        warnings,
+        definitions,
+        metadata,
+        ..
+    } = match cell_options(&request.metadata) {
+        Ok(options) => {
+            execute_aiken_code(
+                &evaluator,
+                state,
+                &request.code,
+                execution_count,
+                options,
+                None,
+            )
+            .await
+        }
+        Err(e) => CellResult::error(e),
+    };
+
+    let format = state.config.error_format;
+    let warnings: Vec<String> = warnings
+        .iter()
+        .map(|warning| match format {
+            ErrorFormat::Ansi => warning.clone(),
+            ErrorFormat::Plain | ErrorFormat::Html => ansi::strip(warning),
+        })
+        .collect();
+    let reply = match result {
+        Ok(data) => {
+            state.log_definitions(&definitions);
+            let text = bundle_text(&data);
+            if !text.is_empty() {
+                state.record_output(execution_count, text);
+            }
+            json!({
+                "status": "ok",
+                "execution_count": execution_count,
+                "data": data,
+                "metadata": metadata,
+                "warnings": warnings,
+            })
+        }
+        Err(report) => {
+            let error = aiken_error(&report, format);
+            json!({
+                "status": "error",
+                "execution_count": execution_count,
+                "ename": error.ename,
+                "evalue": error.evalue,
+                "traceback": error.traceback,
+                "warnings": warnings,
+            })
+        }
+    };
+    (200, reply)
+}
+
+async fn completions(state: &SharedState, request: CursorRequest) -> Response {
+    let code = request.code;
+    let cursor = match request.cursor_pos {
+        Some(pos) => code.char_indices().nth(pos).map_or(code.len(), |(i, _)| i),
+        None => code.len(),
+    };
+    let completions = complete(&state.evaluator(), code.clone(), cursor).await;
+    let chars = |offset: usize| code[..offset].chars().count();
+    (
+        200,
+        json!({
+            "status": "ok",
+            "matches": completions.matches,
+            "cursor_start": chars(completions.start),
+            "cursor_end": chars(cursor),
+        }),
+    )
+}
+
+/// Docs of the name around the cursor, as `handle_inspect_request` finds them
+async fn hover(state: &SharedState, request: CursorRequest) -> Response {
+    let cursor_pos = request
+        .cursor_pos
+        .unwrap_or_else(|| request.code.chars().count());
+    let entry = match name_at(&request.code, cursor_pos) {
+        Some(name) => {
+            let name = name.to_string();
+            state
+                .evaluator()
+                .run(move |eval| eval.doc(&name).ok().flatten())
+                .await
+                .flatten()
+        }
+        None => None,
+    };
+    let reply = match entry {
+        Some(entry) => json!({
+            "status": "ok",
+            "found": true,
+            "name": entry.name,
+            "module": entry.module,
+            "signature": entry.signature,
+            "doc": entry.doc,
+            "defined": entry.provenance.as_ref().map(|provenance| provenance.to_string()),
+            "contents": entry.to_string(),
+        }),
+        None => json!({ "status": "ok", "found": false }),
+    };
+    (200, reply)
+}
+
+fn parse<T: DeserializeOwned>(body: &[u8]) -> Result<T, Response> {
+    serde_json::from_slice(body).map_err(|e| (400, error(format!("Invalid request: {}", e))))
+}
+
+fn error(message: String) -> Value {
+    json!({ "status": "error", "evalue": message })
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        408 => "Request Timeout",
+        413 => "Content Too Large",
+        415 => "Unsupported Media Type",
+        431 => "Request Header Fields Too Large",
+        _ => "Error",
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::{Value, json};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio_util::sync::CancellationToken;
+
+    use super::{is_loopback_host, serve};
+    use crate::connection::testing::state;
+
+    const TOKEN: &str = "secret";
+
+    /// Send a request with `headers` to the server at `address`, returning the status and body of
+    /// the response
+    async fn send(
+        address: std::net::SocketAddr,
+        method: &str,
+        path: &str,
+        headers: &str,
+        body: &str,
+    ) -> (u16, Value) {
+        let mut stream = TcpStream::connect(address).await.unwrap();
+        let request = format!(
+            "{} {} HTTP/1.1\r\n{}Content-Length: {}\r\n\r\n{}",
+            method,
+            path,
+            headers,
+            body.len(),
+            body
+        );
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        let status = head.split_whitespace().nth(1).unwrap().parse().unwrap();
+        (status, serde_json::from_str(body).unwrap())
+    }
+
+    /// Send a request as a client of the API would
+    async fn request(
+        address: std::net::SocketAddr,
+        method: &str,
+        path: &str,
+        body: Value,
+    ) -> (u16, Value) {
+        let headers = format!(
+            "Host: localhost\r\nAuthorization: Bearer {}\r\nContent-Type: application/json\r\n",
+            TOKEN
+        );
+        send(address, method, path, &headers, &body.to_string()).await
+    }
+
+    #[tokio::test]
+    async fn test_serve() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let cancel = CancellationToken::new();
+        tokio::spawn(serve(listener, state(), TOKEN.to_string(), cancel.clone()));
+
+        let (status, reply) = request(address, "POST", "/eval", json!({ "code": "1 + 2" })).await;
+        assert_eq!(status, 200);
+        assert_eq!(reply["status"], "ok");
+        assert_eq!(reply["execution_count"], 1);
+        assert!(reply["data"]["text/plain"].as_str().unwrap().contains('3'));
+
+        let (_, reply) = request(address, "POST", "/eval", json!({ "code": "nope" })).await;
+        assert_eq!(reply["status"], "error");
+        assert_eq!(reply["ename"], "AikenError");
+
+        let code = json!({ "code": "pub const x = 1" });
+        assert_eq!(
+            request(address, "POST", "/eval", code).await.1["status"],
+            "ok"
+        );
+        let (_, reply) = request(address, "GET", "/context", json!({})).await;
+        assert!(reply["context"].as_str().unwrap().contains("x = 1"));
+
+        let code = json!({ "code": "x + 1", "cursor_pos": 1 });
+        let (_, reply) = request(address, "POST", "/complete", code).await;
+        assert_eq!(reply["matches"], json!(["x"]));
+        assert_eq!(
+            (reply["cursor_start"].clone(), reply["cursor_end"].clone()),
+            (json!(0), json!(1))
+        );
+
+        let code = json!({ "code": "x + 1", "cursor_pos": 0 });
+        let (_, reply) = request(address, "POST", "/hover", code).await;
+        assert_eq!(reply["found"], true);
+        assert_eq!(reply["signature"], "x : Int");
+        let (_, reply) = request(address, "POST", "/hover", json!({ "code": "y" })).await;
+        assert_eq!(reply["found"], false);
+        let (_, reply) = request(address, "GET", "/variables", json!({})).await;
+        assert_eq!(reply["variables"][0]["name"], "x");
+        assert_eq!(reply["variables"][0]["type"], "Int");
+
+        let (_, reply) = request(address, "POST", "/reset", json!({})).await;
+        assert_eq!(reply["status"], "ok");
+        let (_, reply) = request(address, "GET", "/context", json!({})).await;
+        assert_eq!(reply["context"], "");
+
+        let (status, _) = request(address, "POST", "/eval", json!({ "source": "1" })).await;
+        assert_eq!(status, 400);
+        let (status, reply) = request(address, "GET", "/nowhere", json!({})).await;
+        assert_eq!(status, 404);
+        assert_eq!(reply["evalue"], "No route for GET /nowhere");
+
+        // What a web page open in a browser can send
+        let auth = format!("Authorization: Bearer {}\r\n", TOKEN);
+        let code = r#"{"code": "1"}"#;
+        let cross_origin = format!(
+            "Host: 127.0.0.1\r\nOrigin: https://example.com\r\n{}Content-Type: text/plain\r\n",
+            auth
+        );
+        let (status, _) = send(address, "POST", "/eval", &cross_origin, code).await;
+        assert_eq!(status, 403);
+        let json = "Content-Type: application/json\r\n";
+        let rebound = format!("Host: example.com:8787\r\n{}{}", auth, json);
+        let (status, _) = send(address, "POST", "/eval", &rebound, code).await;
+        assert_eq!(status, 403);
+        let untokened = format!("Host: localhost\r\n{}", json);
+        let (status, _) = send(address, "POST", "/eval", &untokened, code).await;
+        assert_eq!(status, 401);
+        let text = format!("Host: localhost\r\n{}Content-Type: text/plain\r\n", auth);
+        let (status, _) = send(address, "POST", "/eval", &text, code).await;
+        assert_eq!(status, 415);
+
+        let huge = format!(
+            "Host: localhost\r\n{}{}Content-Length: 1000000000\r\n",
+            auth, json
+        );
+        let (status, _) = send(address, "POST", "/eval", &huge, "").await;
+        assert_eq!(status, 413);
+        let long = format!("Host: localhost\r\nX-Long: {}\r\n", "a".repeat(10_000));
+        let (status, _) = send(address, "GET", "/context", &long, "").await;
+        assert_eq!(status, 431);
+
+        cancel.cancel();
+    }
+
+    #[test]
+    fn test_is_loopback_host() {
+        for host in [
+            "localhost",
+            "LocalHost:8787",
+            "127.0.0.1",
+            "127.0.0.1:80",
+            "[::1]:8787",
+            "[::1]",
+            "::1",
+        ] {
+            assert!(is_loopback_host(host), "{}", host);
+        }
+        for host in [
+            "example.com",
+            "localhost.example.com",
+            "192.168.1.2:8787",
+            "[::2]",
+            "::2",
+            "[::1",
+        ] {
+            assert!(!is_loopback_host(host), "{}", host);
+        }
+    }
+}
@@ -1,3 +1,365 @@
-use tokio::sync::mpsc::UnboundedSender;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
-pub type IopubTx = UnboundedSender<Vec<bytes::Bytes>>;
+use futures::StreamExt;
+use futures::channel::mpsc::Receiver;
+use jupyter_protocol::iopub::iopub_welcome;
+use jupyter_protocol::{ConnectionConfig, JupyterMessage, MimeBundle};
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+use zeromq::{Socket, SocketEvent, SocketSend};
+
+use super::supervisor::LoopExit;
+use crate::state::{SharedState, lock};
+
+/// Queue of the frames to publish. It's bounded: senders wait while `iopub_loop` catches up,
+/// so a cell printing faster than clients read doesn't pile its output up in memory.
+pub type IopubTx = mpsc::Sender<Vec<bytes::Bytes>>;
+pub type IopubRx = mpsc::Receiver<Vec<bytes::Bytes>>;
+
+/// Stream text is held back until this much is pending, or until it's flushed, so a burst of
+/// short lines goes out as a few messages
+const COALESCE_BYTES: usize = 8 * 1024;
+
+/// Outputs of the cell a request is running, for handlers that report progress while they work.
+///
+/// Outputs shown with a display id can be replaced later, so progress updates in place instead
+/// of printing a new line per step.
+///
+/// A cell prints at most `max_output` bytes, in its streams and its result. What comes after is
+/// dropped, and a warning says so.
+#[derive(Clone)]
+pub struct CellOutput {
+    parent: JupyterMessage<serde_json::Value>,
+    key: String,
+    scheme: String,
+    iopub_tx: IopubTx,
+    max_output: usize,
+    written: Arc<Mutex<Written>>,
+}
+
+/// What a cell printed so far
+#[derive(Default)]
+struct Written {
+    bytes: usize,
+    /// Stream text not published yet, by stream name, in order
+    pending: Vec<(String, String)>,
+    /// Whether output was dropped, and whether the cell shows the warning about it yet
+    truncated: bool,
+    warned: bool,
+}
+
+impl Written {
+    /// Take what fits of `text` in the cell's output, None if it was already full
+    fn take<'a>(&mut self, text: &'a str, max_output: usize) -> Option<&'a str> {
+        if self.truncated {
+            return None;
+        }
+        let room = max_output.saturating_sub(self.bytes);
+        let text = if text.len() > room {
+            self.truncated = true;
+            let end = (0..=room)
+                .rev()
+                .find(|&end| text.is_char_boundary(end))
+                .unwrap_or(0);
+            &text[..end]
+        } else {
+            text
+        };
+        self.bytes += text.len();
+        Some(text)
+    }
+}
+
+impl CellOutput {
+    pub fn new(
+        config: &ConnectionConfig,
+        parent: &JupyterMessage<serde_json::Value>,
+        iopub_tx: &IopubTx,
+        max_output: usize,
+    ) -> Self {
+        Self {
+            parent: parent.clone(),
+            key: config.key.clone(),
+            scheme: config.signature_scheme.clone(),
+            iopub_tx: iopub_tx.clone(),
+            max_output,
+            written: Arc::new(Mutex::new(Written::default())),
+        }
+    }
+
+    /// Show `data` as a new output that can be updated through `display_id`
+    pub async fn display(&self, display_id: &str, data: MimeBundle) {
+        self.show(data, Some(display_id)).await;
+    }
+
+    /// Show `data` as a new output, that can be updated if it has a `display_id`
+    pub async fn show(&self, data: MimeBundle, display_id: Option<&str>) {
+        self.flush().await;
+        let metadata = serde_json::Value::Object(serde_json::Map::new());
+        self.publish(
+            "display_data",
+            self.parent
+                .to_iopub_display_data(&self.key, &self.scheme, data, metadata, display_id),
+        )
+        .await;
+    }
+
+    /// Replace the output shown with `display_id`
+    pub async fn update(&self, display_id: &str, data: MimeBundle) {
+        self.flush().await;
+        let metadata = serde_json::Value::Object(serde_json::Map::new());
+        self.publish(
+            "update_display_data",
+            self.parent.to_iopub_update_display_data(
+                &self.key,
+                &self.scheme,
+                data,
+                metadata,
+                display_id,
+            ),
+        )
+        .await;
+    }
+
+    /// Print `text` to the cell's `stdout` or `stderr`. It's published with the text printed
+    /// right after it, see `flush`.
+    pub async fn stream(&self, name: &str, text: &str) {
+        let (pending, full) = {
+            let mut written = lock(&self.written);
+            let Some(text) = written.take(text, self.max_output) else {
+                return;
+            };
+            match written.pending.last_mut() {
+                Some((last, pending)) if last == name => pending.push_str(text),
+                _ => written.pending.push((name.to_string(), text.to_string())),
+            }
+            let pending: usize = written.pending.iter().map(|(_, text)| text.len()).sum();
+            (pending, written.truncated)
+        };
+        if pending >= COALESCE_BYTES || full {
+            self.flush().await;
+        }
+    }
+
+    /// Publish the stream text held back, and the truncation warning once the cell's output is
+    /// full
+    pub async fn flush(&self) {
+        let (pending, warn) = {
+            let mut written = lock(&self.written);
+            let warn = written.truncated && !written.warned;
+            written.warned = written.truncated;
+            (std::mem::take(&mut written.pending), warn)
+        };
+        for (name, text) in pending {
+            self.publish(
+                "stream",
+                self.parent
+                    .to_iopub_stream(&self.key, &self.scheme, &name, &text),
+            )
+            .await;
+        }
+        if warn {
+            self.warn_truncated().await;
+        }
+    }
+
+    /// `data` with its text cut to what fits in the cell's output. The other representations
+    /// are dropped with it.
+    pub async fn limit(&self, data: MimeBundle) -> MimeBundle {
+        let (limited, truncated) = {
+            let mut written = lock(&self.written);
+            let text = data
+                .get("text/plain")
+                .and_then(|text| text.as_str())
+                .unwrap_or_default();
+            let Some(text) = written.take(text, self.max_output) else {
+                return MimeBundle::new();
+            };
+            let limited = if written.truncated {
+                let mut limited = MimeBundle::new();
+                limited.insert("text/plain".into(), text.into());
+                limited
+            } else {
+                data
+            };
+            (limited, written.truncated)
+        };
+        if truncated {
+            self.flush().await;
+        }
+        limited
+    }
+
+    async fn warn_truncated(&self) {
+        let text = format!(
+            "Output truncated: the cell printed more than {} bytes. Change the limit with `max-output` in the [kernel] table of the configuration file.",
+            self.max_output
+        );
+        self.publish(
+            "stream",
+            self.parent
+                .to_iopub_stream(&self.key, &self.scheme, "stderr", &format!("\n{text}\n")),
+        )
+        .await;
+    }
+
+    async fn publish(&self, msg_type: &str, frames: anyhow::Result<Vec<bytes::Bytes>>) {
+        match frames {
+            Ok(frames) => {
+                if let Err(e) = self.iopub_tx.send(frames).await {
+                    eprintln!("Failed to send {msg_type}: {e}");
+                }
+            }
+            Err(e) => eprintln!("Failed to create {msg_type}: {e}"),
+        }
+    }
+}
+
+/// Publish the frames queued by the other channels, until the socket fails to send them
+pub async fn iopub_loop<S: Socket + SocketSend>(
+    cancel_iopub: CancellationToken,
+    iopub_socket: &mut S,
+    iopub_rx: &mut IopubRx,
+    iopub_tx: &IopubTx,
+    state: &SharedState,
+) -> LoopExit {
+    // Welcomes are tied to this socket, stop them when it goes away
+    let cancel_welcome = cancel_iopub.child_token();
+    tokio::spawn(welcome_loop(
+        cancel_welcome.clone(),
+        iopub_socket.monitor(),
+        iopub_tx.clone(),
+        state.clone(),
+    ));
+    let _guard = cancel_welcome.drop_guard();
+
+    loop {
+        tokio::select! {
+            _ = cancel_iopub.cancelled() => {
+                println!("IOPub loop cancelled");
+                return LoopExit::Cancelled;
+            }
+            Some(frames) = iopub_rx.recv() => {
+                // frames are already multipart bytes
+                if let Err(e) = jupyter_protocol::wire::send_bytes(iopub_socket, frames).await {
+                    eprintln!("IOPub send error: {e}");
+                    return LoopExit::Failed;
+                }
+            }
+            else => return LoopExit::Cancelled,
+        }
+    }
+}
+
+/// Time we give a freshly connected client to send its subscription
+const SUBSCRIBE_GRACE: Duration = Duration::from_millis(100);
+
+/// Send `iopub_welcome` to clients connecting to IOPub.
+///
+/// Protocol 5.4 expects an XPUB socket that answers each subscription with a welcome message.
+/// `zeromq` only has a plain PUB socket and doesn't tell us about subscriptions, so we watch for
+/// new connections instead and publish the welcome once the client had time to subscribe.
+pub async fn welcome_loop(
+    cancel: CancellationToken,
+    mut events: Receiver<SocketEvent>,
+    iopub_tx: IopubTx,
+    state: SharedState,
+) {
+    loop {
+        tokio::select! {
+            _ = cancel.cancelled() => break,
+            event = events.next() => match event {
+                Some(SocketEvent::Accepted(_, _)) => {
+                    tokio::time::sleep(SUBSCRIBE_GRACE).await;
+                    let config = &state.config.connection;
+                    match iopub_welcome(&config.key, &config.signature_scheme, &state.session, "") {
+                        Ok(frames) => {
+                            let _ = iopub_tx.send(frames).await;
+                        }
+                        Err(e) => eprintln!("Failed to build iopub_welcome: {e}"),
+                    }
+                }
+                Some(_) => {}
+                None => break,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use jupyter_protocol::{JupyterMessage, MessageHeader};
+
+    use super::{COALESCE_BYTES, CellOutput, IopubRx};
+    use crate::connection::testing::{KEY, SCHEME, config};
+
+    fn cell_output(max_output: usize) -> (CellOutput, IopubRx) {
+        let config = config();
+        let parent = JupyterMessage {
+            header: MessageHeader::new("session".to_string(), "execute_request".to_string()),
+            parent_header: None,
+            metadata: serde_json::json!({}),
+            content: serde_json::json!({}),
+        };
+        let (tx, rx) = tokio::sync::mpsc::channel(16);
+        (CellOutput::new(&config, &parent, &tx, max_output), rx)
+    }
+
+    /// Stream name and text of the messages published so far
+    fn streams(rx: &mut IopubRx) -> Vec<(String, String)> {
+        let mut streams = Vec::new();
+        while let Ok(frames) = rx.try_recv() {
+            let frames: Vec<Vec<u8>> = frames.iter().map(|frame| frame.to_vec()).collect();
+            let msg =
+                JupyterMessage::<serde_json::Value>::from_multipart(&frames, KEY, SCHEME).unwrap();
+            assert_eq!(msg.header.msg_type, "stream");
+            streams.push((
+                msg.content["name"].as_str().unwrap().to_string(),
+                msg.content["text"].as_str().unwrap().to_string(),
+            ));
+        }
+        streams
+    }
+
+    #[tokio::test]
+    async fn test_coalesce() {
+        let (output, mut rx) = cell_output(1024 * 1024);
+        output.stream("stdout", "one\n").await;
+        output.stream("stdout", "two\n").await;
+        output.stream("stderr", "three\n").await;
+        assert!(streams(&mut rx).is_empty());
+
+        output.flush().await;
+        assert_eq!(
+            streams(&mut rx),
+            [
+                ("stdout".to_string(), "one\ntwo\n".to_string()),
+                ("stderr".to_string(), "three\n".to_string())
+            ]
+        );
+
+        // Published without waiting for a flush once enough is pending
+        output.stream("stdout", &"x".repeat(COALESCE_BYTES)).await;
+        assert_eq!(streams(&mut rx).len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_max_output() {
+        let (output, mut rx) = cell_output(10);
+        output.stream("stdout", "12345").await;
+        output.stream("stdout", "6789ab").await;
+        output.stream("stdout", "dropped").await;
+        output.flush().await;
+
+        let streams = streams(&mut rx);
+        assert_eq!(streams.len(), 2);
+        assert_eq!(streams[0].1, "123456789a");
+        assert_eq!(streams[1].0, "stderr");
+        assert!(streams[1].1.contains("Output truncated"));
+
+        let mut data = jupyter_protocol::MimeBundle::new();
+        data.insert("text/plain".into(), "result".into());
+        assert!(output.limit(data).await.is_empty());
+    }
+}
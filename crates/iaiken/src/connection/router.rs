@@ -0,0 +1,280 @@
+//! Typed routing of shell and control requests to their handlers.
+//!
+//! Incoming messages are parsed once into a `ShellRequest`/`ControlRequest` and dispatched to
+//! the matching method of a handler trait. Adding a message type means adding a variant to the
+//! request enum and a method (with a default "unhandled" implementation) to the handler trait.
+
+use jupyter_protocol::{
+    ConnectionConfig, JupyterMessage,
+    comm::{CommClose, CommMsg, CommOpen},
+    control::{
+        debug::DebugRequest,
+        interrupt::InterruptRequest,
+        shutdown::ShutdownRequest,
+        subshell::{CreateSubshellRequest, DeleteSubshellRequest, ListSubshellRequest},
+    },
+    reply::{ReplyError, reply_type},
+    request::{ControlRequest, ShellRequest},
+    shell::{
+        comm_info::CommInfoRequest, complete::CompleteRequest, execute::ExecuteRequest,
+        history::HistoryRequest, inspect::InspectRequest, is_complete::IsCompleteRequest,
+        kernel_info::KernelInfoRequest,
+    },
+    wire::{delim_index, verified_header},
+};
+
+/// A message received on a ROUTER socket, with the identity envelope needed to reply to it
+pub struct Incoming {
+    pub raw_msg: JupyterMessage<serde_json::Value>,
+    pub frames: Vec<Vec<u8>>,
+    pub delim_index: usize,
+}
+
+/// A message that failed to parse
+pub struct Malformed {
+    pub error: anyhow::Error,
+    /// The message with null content, when it's signed and its header is valid, so that it can
+    /// still be answered. Messages that fail the signature check are never answered.
+    pub incoming: Option<Box<Incoming>>,
+}
+
+impl Malformed {
+    /// Error replied to the message, if it can be answered
+    pub fn reply_error(&self) -> ReplyError {
+        ReplyError::new("InvalidMessage", self.error.to_string())
+    }
+}
+
+impl std::fmt::Display for Malformed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.incoming {
+            Some(incoming) => write!(
+                f,
+                "Malformed {} {} from session {}: {}",
+                incoming.msg_type(),
+                incoming.raw_msg.header.msg_id,
+                incoming.raw_msg.header.session,
+                self.error
+            ),
+            None => write!(f, "Malformed message: {}", self.error),
+        }
+    }
+}
+
+impl Incoming {
+    pub fn parse(
+        message: zeromq::ZmqMessage,
+        config: &ConnectionConfig,
+    ) -> Result<Self, Malformed> {
+        let frames: Vec<Vec<u8>> = message.iter().map(|frame| frame.to_vec()).collect();
+        let delim_index = delim_index(&frames).map_err(|error| Malformed {
+            error,
+            incoming: None,
+        })?;
+        let error = match JupyterMessage::<serde_json::Value>::from_multipart(
+            &frames,
+            &config.key,
+            &config.signature_scheme,
+        ) {
+            Ok(raw_msg) => {
+                return Ok(Self {
+                    raw_msg,
+                    frames,
+                    delim_index,
+                });
+            }
+            Err(e) => anyhow::anyhow!("Failed to parse message with {} frames: {e}", frames.len()),
+        };
+
+        let incoming = verified_header(&frames, &config.key, &config.signature_scheme)
+            .ok()
+            .map(|header| {
+                Box::new(Self {
+                    raw_msg: JupyterMessage {
+                        header,
+                        parent_header: None,
+                        metadata: serde_json::Value::Object(serde_json::Map::new()),
+                        content: serde_json::Value::Null,
+                    },
+                    frames,
+                    delim_index,
+                })
+            });
+        Err(Malformed { error, incoming })
+    }
+
+    pub fn msg_type(&self) -> &str {
+        &self.raw_msg.header.msg_type
+    }
+
+    /// Message type of the reply, None if the message isn't a request
+    pub fn reply_type(&self) -> Option<String> {
+        reply_type(self.msg_type())
+    }
+}
+
+/// Error replied to requests without a handler
+fn not_implemented(incoming: &Incoming) -> ReplyError {
+    ReplyError::new(
+        "NotImplemented",
+        format!("{} is not supported by this kernel", incoming.msg_type()),
+    )
+}
+
+/// Handlers for requests on the shell channel
+pub trait ShellHandler {
+    async fn kernel_info(&mut self, incoming: Incoming, _request: KernelInfoRequest) {
+        self.unhandled(incoming).await
+    }
+
+    async fn execute(&mut self, incoming: Incoming, _request: ExecuteRequest) {
+        self.unhandled(incoming).await
+    }
+
+    async fn complete(&mut self, incoming: Incoming, _request: CompleteRequest) {
+        self.unhandled(incoming).await
+    }
+
+    async fn inspect(&mut self, incoming: Incoming, _request: InspectRequest) {
+        self.unhandled(incoming).await
+    }
+
+    async fn is_complete(&mut self, incoming: Incoming, _request: IsCompleteRequest) {
+        self.unhandled(incoming).await
+    }
+
+    async fn history(&mut self, incoming: Incoming, _request: HistoryRequest) {
+        self.unhandled(incoming).await
+    }
+
+    async fn comm_info(&mut self, incoming: Incoming, _request: CommInfoRequest) {
+        self.unhandled(incoming).await
+    }
+
+    async fn comm_open(&mut self, incoming: Incoming, _request: CommOpen) {
+        self.unhandled(incoming).await
+    }
+
+    async fn comm_msg(&mut self, incoming: Incoming, _request: CommMsg) {
+        self.unhandled(incoming).await
+    }
+
+    async fn comm_close(&mut self, incoming: Incoming, _request: CommClose) {
+        self.unhandled(incoming).await
+    }
+
+    async fn unhandled(&mut self, incoming: Incoming) {
+        println!(
+            "\n\nUnhandled shell message type: {}\n\n",
+            incoming.msg_type()
+        );
+        let error = not_implemented(&incoming);
+        self.reject(incoming, error).await
+    }
+
+    /// Answer a request that can't be handled with an error reply. Messages that aren't
+    /// requests have nothing to answer.
+    async fn reject(&mut self, incoming: Incoming, error: ReplyError) {
+        eprintln!("Rejected {}: {}", incoming.msg_type(), error.evalue);
+    }
+}
+
+/// Handlers for requests on the control channel
+pub trait ControlHandler {
+    async fn shutdown(&mut self, incoming: Incoming, _request: ShutdownRequest) {
+        self.unhandled(incoming).await
+    }
+
+    async fn interrupt(&mut self, incoming: Incoming, _request: InterruptRequest) {
+        self.unhandled(incoming).await
+    }
+
+    async fn debug(&mut self, incoming: Incoming, _request: DebugRequest) {
+        self.unhandled(incoming).await
+    }
+
+    async fn kernel_info(&mut self, incoming: Incoming, _request: KernelInfoRequest) {
+        self.unhandled(incoming).await
+    }
+
+    async fn create_subshell(&mut self, incoming: Incoming, _request: CreateSubshellRequest) {
+        self.unhandled(incoming).await
+    }
+
+    async fn delete_subshell(&mut self, incoming: Incoming, _request: DeleteSubshellRequest) {
+        self.unhandled(incoming).await
+    }
+
+    async fn list_subshell(&mut self, incoming: Incoming, _request: ListSubshellRequest) {
+        self.unhandled(incoming).await
+    }
+
+    async fn unhandled(&mut self, incoming: Incoming) {
+        println!(
+            "\n\nUnhandled control message type: {}\n\n",
+            incoming.msg_type()
+        );
+        let error = not_implemented(&incoming);
+        self.reject(incoming, error).await
+    }
+
+    /// Answer a request that can't be handled with an error reply
+    async fn reject(&mut self, incoming: Incoming, error: ReplyError) {
+        eprintln!("Rejected {}: {}", incoming.msg_type(), error.evalue);
+    }
+}
+
+/// Parse a shell message into a typed request and route it to `handler`
+pub async fn dispatch_shell<H: ShellHandler>(handler: &mut H, incoming: Incoming) {
+    let content = incoming.raw_msg.content.clone();
+    let request = match ShellRequest::parse(incoming.msg_type(), content) {
+        Ok(request) => request,
+        Err(e) => {
+            let error = ReplyError::new(
+                "InvalidRequest",
+                format!("Failed to parse {} content: {e}", incoming.msg_type()),
+            );
+            return handler.reject(incoming, error).await;
+        }
+    };
+
+    match request {
+        ShellRequest::KernelInfo(req) => handler.kernel_info(incoming, req).await,
+        ShellRequest::Execute(req) => handler.execute(incoming, req).await,
+        ShellRequest::Complete(req) => handler.complete(incoming, req).await,
+        ShellRequest::Inspect(req) => handler.inspect(incoming, req).await,
+        ShellRequest::IsComplete(req) => handler.is_complete(incoming, req).await,
+        ShellRequest::History(req) => handler.history(incoming, req).await,
+        ShellRequest::CommInfo(req) => handler.comm_info(incoming, req).await,
+        ShellRequest::CommOpen(req) => handler.comm_open(incoming, req).await,
+        ShellRequest::CommMsg(req) => handler.comm_msg(incoming, req).await,
+        ShellRequest::CommClose(req) => handler.comm_close(incoming, req).await,
+        ShellRequest::Unknown(_) => handler.unhandled(incoming).await,
+    }
+}
+
+/// Parse a control message into a typed request and route it to `handler`
+pub async fn dispatch_control<H: ControlHandler>(handler: &mut H, incoming: Incoming) {
+    let content = incoming.raw_msg.content.clone();
+    let request = match ControlRequest::parse(incoming.msg_type(), content) {
+        Ok(request) => request,
+        Err(e) => {
+            let error = ReplyError::new(
+                "InvalidRequest",
+                format!("Failed to parse {} content: {e}", incoming.msg_type()),
+            );
+            return handler.reject(incoming, error).await;
+        }
+    };
+
+    match request {
+        ControlRequest::Shutdown(req) => handler.shutdown(incoming, req).await,
+        ControlRequest::Interrupt(req) => handler.interrupt(incoming, req).await,
+        ControlRequest::Debug(req) => handler.debug(incoming, req).await,
+        ControlRequest::KernelInfo(req) => handler.kernel_info(incoming, req).await,
+        ControlRequest::CreateSubshell(req) => handler.create_subshell(incoming, req).await,
+        ControlRequest::DeleteSubshell(req) => handler.delete_subshell(incoming, req).await,
+        ControlRequest::ListSubshell(req) => handler.list_subshell(incoming, req).await,
+        ControlRequest::Unknown(_) => handler.unhandled(incoming).await,
+    }
+}
@@ -1,98 +1,266 @@
-use std::sync::Arc;
-use std::sync::atomic::AtomicU32;
-use std::sync::atomic::Ordering;
+use std::collections::HashMap;
 
+use jupyter_protocol::comm::{CommClose, CommMsg, CommOpen};
+use jupyter_protocol::iopub::ExecutionState;
+use jupyter_protocol::reply::{Reply, ReplyError};
+use jupyter_protocol::shell::comm_info::CommInfoRequest;
+use jupyter_protocol::shell::execute::ExecuteRequest;
+use jupyter_protocol::shell::history::HistoryRequest;
+use jupyter_protocol::shell::inspect::InspectRequest;
+use jupyter_protocol::shell::kernel_info::KernelInfoRequest;
+use jupyter_protocol::wire::send_bytes;
+use jupyter_protocol::{JupyterMessage, MessageHeader};
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender, unbounded_channel};
 use tokio_util::sync::CancellationToken;
 use zeromq::RouterSocket;
 use zeromq::SocketRecv;
 
-use crate::messages::wire::delim_index;
-use crate::messages::{ConnectionConfig, JupyterMessage};
-
 use super::iopub::IopubTx;
+use super::router::{Incoming, ShellHandler, dispatch_shell};
+use super::supervisor::LoopExit;
+use crate::eval::worker::EvalWorker;
+use crate::state::SharedState;
 
+mod comm;
 mod execute;
+mod history;
+pub(super) mod inspect;
 mod kernel_info;
 
+/// Replies queued by the shell workers, sent on the shell socket by `shell_loop`
+pub type ReplyTx = UnboundedSender<Vec<bytes::Bytes>>;
+
+/// Handles requests on the shell channel, for the parent shell or one subshell
+#[derive(Clone)]
+struct Shell {
+    state: SharedState,
+    evaluator: EvalWorker,
+    reply_tx: ReplyTx,
+    iopub_tx: IopubTx,
+}
+
+impl ShellHandler for Shell {
+    async fn kernel_info(&mut self, incoming: Incoming, _request: KernelInfoRequest) {
+        kernel_info::handle_kernel_info_request(
+            &self.state.config.connection,
+            &self.reply_tx,
+            &self.iopub_tx,
+            incoming,
+            &self.state.config.codemirror_mode,
+        )
+        .await;
+    }
+
+    async fn execute(&mut self, incoming: Incoming, request: ExecuteRequest) {
+        execute::handle_execute_request(
+            &self.state,
+            &self.evaluator,
+            &self.reply_tx,
+            &self.iopub_tx,
+            incoming,
+            request,
+        )
+        .await;
+    }
+
+    async fn inspect(&mut self, incoming: Incoming, request: InspectRequest) {
+        inspect::handle_inspect_request(
+            &self.state,
+            &self.evaluator,
+            &self.reply_tx,
+            &self.iopub_tx,
+            incoming,
+            request,
+        )
+        .await;
+    }
+
+    async fn history(&mut self, incoming: Incoming, request: HistoryRequest) {
+        history::handle_history_request(
+            &self.state,
+            &self.reply_tx,
+            &self.iopub_tx,
+            incoming,
+            request,
+        )
+        .await;
+    }
+
+    async fn comm_info(&mut self, incoming: Incoming, request: CommInfoRequest) {
+        comm::handle_comm_info_request(
+            &self.state,
+            &self.reply_tx,
+            &self.iopub_tx,
+            incoming,
+            request,
+        )
+        .await;
+    }
+
+    async fn comm_open(&mut self, incoming: Incoming, request: CommOpen) {
+        comm::handle_comm_open(&self.state, &self.iopub_tx, incoming, request).await;
+    }
+
+    async fn comm_msg(&mut self, incoming: Incoming, request: CommMsg) {
+        comm::handle_comm_msg(&self.state, &self.iopub_tx, incoming, request).await;
+    }
+
+    async fn comm_close(&mut self, _incoming: Incoming, request: CommClose) {
+        comm::handle_comm_close(&self.state, request);
+    }
+
+    async fn reject(&mut self, incoming: Incoming, error: ReplyError) {
+        eprintln!("Rejected {}: {}", incoming.msg_type(), error.evalue);
+        let Some(msg_type) = incoming.reply_type() else {
+            return;
+        };
+        let config = &self.state.config.connection;
+        let Incoming {
+            raw_msg,
+            frames,
+            delim_index,
+        } = incoming;
+        let publish_status = async |execution_state| {
+            if let Ok(frames) =
+                raw_msg.to_iopub_status(&config.key, &config.signature_scheme, execution_state)
+            {
+                let _ = self.iopub_tx.send(frames).await;
+            }
+        };
+
+        publish_status(ExecutionState::Busy).await;
+        let reply_msg = JupyterMessage {
+            header: MessageHeader::new(raw_msg.header.session.clone(), msg_type.clone()),
+            parent_header: Some(raw_msg.header.clone()),
+            metadata: serde_json::Value::Object(serde_json::Map::new()),
+            content: Reply::<serde_json::Value>::Error(error),
+        };
+        match reply_msg.to_envelope_multipart(
+            frames,
+            delim_index,
+            &config.key,
+            &config.signature_scheme,
+        ) {
+            Ok(bytes_frames) => {
+                if let Err(e) = self.reply_tx.send(bytes_frames) {
+                    eprintln!("Failed to send {msg_type}: {e}");
+                }
+            }
+            Err(e) => eprintln!("Failed to create {msg_type}: {e}"),
+        }
+        publish_status(ExecutionState::Idle).await;
+    }
+}
+
+/// Requests handled in order, after the executions sent before them. The others don't depend on
+/// what's executing, and are answered right away even while a long cell runs.
+fn is_ordered(msg_type: &str) -> bool {
+    matches!(
+        msg_type,
+        "execute_request" | "comm_open" | "comm_msg" | "comm_close"
+    )
+}
+
+/// Run the ordered requests of one (sub)shell, one at a time
+fn spawn_worker(mut shell: Shell) -> UnboundedSender<Incoming> {
+    let (requests_tx, mut requests_rx): (_, UnboundedReceiver<Incoming>) = unbounded_channel();
+    tokio::spawn(async move {
+        while let Some(incoming) = requests_rx.recv().await {
+            dispatch_shell(&mut shell, incoming).await;
+        }
+    });
+    requests_tx
+}
+
+/// Receive shell messages and route them to the worker of their (sub)shell, so a long
+/// evaluation in one subshell doesn't block the others. Requests that aren't ordered are handled
+/// on their own task.
 pub async fn shell_loop(
     cancel_shell: CancellationToken,
     shell_socket: &mut RouterSocket,
     iopub_tx: IopubTx,
-    config: &ConnectionConfig,
-    exec_count: Arc<AtomicU32>,
-) {
+    state: SharedState,
+) -> LoopExit {
+    let (reply_tx, mut reply_rx) = unbounded_channel();
+    let new_shell = |evaluator: EvalWorker| Shell {
+        state: state.clone(),
+        evaluator,
+        reply_tx: reply_tx.clone(),
+        iopub_tx: iopub_tx.clone(),
+    };
+
+    let parent_shell = new_shell(state.evaluator());
+    let parent = spawn_worker(parent_shell.clone());
+    let mut subshells: HashMap<String, (Shell, UnboundedSender<Incoming>)> = HashMap::new();
+
     loop {
         tokio::select! {
             _ = cancel_shell.cancelled() => {
                 println!("Shell loop cancelled");
-                break;
+                return LoopExit::Cancelled;
             }
-        msg = shell_socket.recv() => {
-            match msg {
-            Ok(message) => {
-                // Try to parse as a generic message first to get the header
-                let frames: Vec<Vec<u8>> = message.iter().map(|frame| frame.to_vec()).collect();
-                let delim_index = match delim_index(&frames) {
-                    Ok(ix) => ix,
-                    Err(e) => {
-                        eprintln!("{e}");
-                        continue;
-                    }
-                };
-
-                if let Ok(raw_msg) = JupyterMessage::<serde_json::Value>::from_multipart(
-                    &frames,
-                    &config.key,
-                    &config.signature_scheme,
-                ) {
-                    println!("Received message type: {}", raw_msg.header.msg_type);
-
-                    // Route based on message type
-                    match raw_msg.header.msg_type.as_str() {
-                        "kernel_info_request" => {
-                            kernel_info::handle_kernel_info_request(
-                                &config,
-                                shell_socket,
-                                &iopub_tx,
-                                raw_msg,
-                                frames,
-                                delim_index,
-                            )
-                            .await;
-                        }
-                        "execute_request" => {
-                            // Increment execution counter and get the new value
-                            // The `Ordering` is probably too strict for this case.
-                            exec_count.fetch_add(1, Ordering::SeqCst);
-                            let n = exec_count.load(Ordering::SeqCst);
-
-                            execute::handle_execute_request(
-                                &config,
-                                shell_socket,
-                                &iopub_tx,
-                                raw_msg,
-                                frames,
-                                delim_index,
-                                n,
-                            )
-                            .await.unwrap();
-                        }
-                        _ => {
-                            println!("\n\nUnhandled shell message type: {}\n\n", raw_msg.header.msg_type);
-                            //TODO: Hanlde `history_request`?
+            Some(frames) = reply_rx.recv() => {
+                if let Err(e) = send_bytes(shell_socket, frames).await {
+                    eprintln!("Failed to send shell reply: {e}");
+                }
+            }
+            msg = shell_socket.recv() => {
+                match msg {
+                    Ok(message) => {
+                        let incoming = match Incoming::parse(message, &state.config.connection) {
+                            Ok(incoming) => incoming,
+                            Err(malformed) => {
+                                // Answered right away rather than by a worker, which may be
+                                // busy with a long evaluation
+                                eprintln!("{malformed}");
+                                let error = malformed.reply_error();
+                                if let Some(incoming) = malformed.incoming {
+                                    parent_shell.clone().reject(*incoming, error).await;
+                                }
+                                continue;
+                            }
+                        };
+                        println!("Received message type: {}", incoming.msg_type());
+
+                        // Forget the workers of deleted subshells
+                        subshells.retain(|id, _| state.subshell_evaluator(id).is_some());
+
+                        let (shell, worker) = match incoming.raw_msg.header.subshell_id.clone() {
+                            None => (&parent_shell, &parent),
+                            Some(id) => match state.subshell_evaluator(&id) {
+                                Some(evaluator) => {
+                                    let (shell, worker) =
+                                        subshells.entry(id).or_insert_with(|| {
+                                            let shell = new_shell(evaluator);
+                                            (shell.clone(), spawn_worker(shell))
+                                        });
+                                    (&*shell, &*worker)
+                                }
+                                None => {
+                                    let error = ReplyError::new(
+                                        "UnknownSubshell",
+                                        format!("No subshell with id {id}"),
+                                    );
+                                    parent_shell.clone().reject(incoming, error).await;
+                                    continue;
+                                }
+                            },
+                        };
+                        if is_ordered(incoming.msg_type()) {
+                            if worker.send(incoming).is_err() {
+                                eprintln!("Shell worker is gone");
+                            }
+                        } else {
+                            let mut shell = shell.clone();
+                            tokio::spawn(async move { dispatch_shell(&mut shell, incoming).await });
                         }
                     }
-                } else {
-                    println!("Failed to parse message with {} frames", frames.len());
+                    Err(e) => {
+                        eprintln!("Shell receive error: {e}");
+                        return LoopExit::Failed;
+                    }
                 }
             }
-            Err(e) => {
-                eprintln!("Shell receive error: {e}");
-                break;
-            }
-            }
-        }
-
         }
     }
 }
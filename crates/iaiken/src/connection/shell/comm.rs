@@ -0,0 +1,159 @@
+//! Comms opened by the frontend.
+//!
+//! The only target is the variable explorer: once a comm to `VARIABLES_TARGET` is open, the
+//! kernel sends it the session's variables (`{"method": "update", "variables": [...]}`) right
+//! away and after every execution. The frontend can ask for them with `{"method": "refresh"}`.
+
+use std::collections::HashMap;
+
+use crate::{
+    connection::{iopub::IopubTx, router::Incoming, shell::ReplyTx},
+    eval::variables::session_variables,
+    state::SharedState,
+};
+use jupyter_protocol::{
+    JupyterMessage, MessageHeader,
+    comm::{CommClose, CommMsg, CommOpen},
+    iopub::ExecutionState,
+    reply::Reply,
+    shell::comm_info::{CommInfo, CommInfoReply, CommInfoRequest},
+};
+
+/// Comm target of variable explorers
+pub const VARIABLES_TARGET: &str = "variable_inspector";
+
+pub async fn handle_comm_info_request(
+    state: &SharedState,
+    reply_tx: &ReplyTx,
+    iopub_tx: &IopubTx,
+    incoming: Incoming,
+    request: CommInfoRequest,
+) {
+    let config = &state.config.connection;
+    let Incoming {
+        raw_msg,
+        frames,
+        delim_index,
+    } = incoming;
+
+    publish_status(state, iopub_tx, &raw_msg, ExecutionState::Busy).await;
+
+    let comms: HashMap<String, CommInfo> = state
+        .comms(request.target_name.as_deref())
+        .into_iter()
+        .map(|(comm_id, target_name)| (comm_id, CommInfo { target_name }))
+        .collect();
+    let reply: Reply<CommInfoReply> = Reply::Ok(CommInfoReply { comms });
+
+    let reply_msg = JupyterMessage {
+        header: MessageHeader::new(
+            raw_msg.header.session.clone(),
+            "comm_info_reply".to_string(),
+        ),
+        parent_header: Some(raw_msg.header.clone()),
+        metadata: serde_json::Value::Object(serde_json::Map::new()),
+        content: reply,
+    };
+
+    if let Ok(bytes_frames) =
+        reply_msg.to_envelope_multipart(frames, delim_index, &config.key, &config.signature_scheme)
+        && let Err(e) = reply_tx.send(bytes_frames)
+    {
+        eprintln!("Failed to send comm_info_reply: {e}");
+    }
+
+    publish_status(state, iopub_tx, &raw_msg, ExecutionState::Idle).await;
+}
+
+pub async fn handle_comm_open(
+    state: &SharedState,
+    iopub_tx: &IopubTx,
+    incoming: Incoming,
+    request: CommOpen,
+) {
+    let config = &state.config.connection;
+    let raw_msg = incoming.raw_msg;
+
+    publish_status(state, iopub_tx, &raw_msg, ExecutionState::Busy).await;
+
+    if request.target_name == VARIABLES_TARGET {
+        state.open_comm(&request.comm_id, &request.target_name);
+        publish_variables(state, iopub_tx, &raw_msg).await;
+    } else {
+        // Tell the frontend there is nothing on this side of the comm
+        eprintln!("Unknown comm target: {}", request.target_name);
+        if let Ok(frames) =
+            raw_msg.to_iopub_comm_close(&config.key, &config.signature_scheme, &request.comm_id)
+        {
+            let _ = iopub_tx.send(frames).await;
+        }
+    }
+
+    publish_status(state, iopub_tx, &raw_msg, ExecutionState::Idle).await;
+}
+
+pub async fn handle_comm_msg(
+    state: &SharedState,
+    iopub_tx: &IopubTx,
+    incoming: Incoming,
+    request: CommMsg,
+) {
+    let raw_msg = incoming.raw_msg;
+    let is_variables = state
+        .comms(Some(VARIABLES_TARGET))
+        .contains_key(&request.comm_id);
+    if !is_variables {
+        eprintln!("Message for unknown comm {}", request.comm_id);
+        return;
+    }
+
+    publish_status(state, iopub_tx, &raw_msg, ExecutionState::Busy).await;
+    match request.data["method"].as_str() {
+        Some("refresh") => publish_variables(state, iopub_tx, &raw_msg).await,
+        other => eprintln!("Unknown variables comm method: {other:?}"),
+    }
+    publish_status(state, iopub_tx, &raw_msg, ExecutionState::Idle).await;
+}
+
+pub fn handle_comm_close(state: &SharedState, request: CommClose) {
+    state.close_comm(&request.comm_id);
+}
+
+/// Send the session's variables to every open variable explorer
+pub async fn publish_variables(
+    state: &SharedState,
+    iopub_tx: &IopubTx,
+    parent: &JupyterMessage<serde_json::Value>,
+) {
+    let comms = state.comms(Some(VARIABLES_TARGET));
+    if comms.is_empty() {
+        return;
+    }
+
+    let config = &state.config.connection;
+    let variables = session_variables(state).await;
+    let data = serde_json::json!({ "method": "update", "variables": variables });
+    for comm_id in comms.keys() {
+        match parent.to_iopub_comm_msg(&config.key, &config.signature_scheme, comm_id, data.clone())
+        {
+            Ok(frames) => {
+                let _ = iopub_tx.send(frames).await;
+            }
+            Err(e) => eprintln!("Failed to create comm_msg: {e}"),
+        }
+    }
+}
+
+async fn publish_status(
+    state: &SharedState,
+    iopub_tx: &IopubTx,
+    raw_msg: &JupyterMessage<serde_json::Value>,
+    execution_state: ExecutionState,
+) {
+    let config = &state.config.connection;
+    if let Ok(frames) =
+        raw_msg.to_iopub_status(&config.key, &config.signature_scheme, execution_state)
+    {
+        let _ = iopub_tx.send(frames).await;
+    }
+}
@@ -1,180 +1,575 @@
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
 use crate::{
-    connection::iopub::IopubTx,
-    eval::{evaluate_user_expressions, execute_aiken_code},
-    messages::{
-        ConnectionConfig, JupyterMessage, MessageHeader,
-        shell::execute::{ExecuteReply, ExecuteRequest},
-        wire::send_bytes,
+    connection::{
+        iopub::{CellOutput, IopubTx},
+        router::Incoming,
+        shell::{ReplyTx, comm::publish_variables},
     },
+    eval::worker::EvalWorker,
+    eval::{CellResult, aiken_error, evaluate_user_expressions, execute_aiken_code},
+    messages::{ansi, bundle_text, cell_options, error_bundle},
+    state::SharedState,
+};
+use aiken_repl::config::ErrorFormat;
+use jupyter_protocol::{
+    JupyterMessage, MessageHeader, MimeBundle,
+    iopub::ExecutionState,
+    reply::Reply,
+    shell::execute::{ExecuteError, ExecuteOk, ExecuteReply, ExecuteRequest},
 };
-use zeromq::RouterSocket;
+use std::collections::HashMap;
+use tokio_util::sync::CancellationToken;
 
 pub async fn handle_execute_request(
-    config: &ConnectionConfig,
-    shell_socket: &mut RouterSocket,
+    state: &SharedState,
+    evaluator: &EvalWorker,
+    reply_tx: &ReplyTx,
     iopub_tx: &IopubTx,
-    raw_msg: JupyterMessage<serde_json::Value>,
-    frames: Vec<Vec<u8>>,
-    delim_index: usize,
-    execution_count: u32,
-) -> anyhow::Result<()> {
+    incoming: Incoming,
+    request: ExecuteRequest,
+) {
     println!("Handling execute_request");
+    let config = &state.config.connection;
+    let Incoming {
+        raw_msg,
+        frames,
+        delim_index,
+    } = incoming;
+
+    // As the spec has it, only cells stored in the history are numbered, and silent cells never
+    // are. Others are sent the count of the last stored cell.
+    let store_history = request.store_history && !request.silent;
+    let execution_count = if store_history {
+        let n = state.next_execution_count();
+        state.record_history(n, &request.code);
+        n
+    } else {
+        state.execution_count()
+    };
+
+    println!("Executing code: {}", request.code);
+    let reply: ExecuteReply;
 
-    // Parse the execute request
-    if let Ok(exec_msg) = JupyterMessage::<ExecuteRequest>::from_multipart(
-        &frames,
-        &config.key,
-        &config.signature_scheme,
-    ) {
-        println!("Executing code: {}", exec_msg.content.code);
-        let request = &exec_msg.content;
-        let reply: ExecuteReply;
-
-        // Signal that the kernel is busy
-        if let Ok(msg) = raw_msg.to_iopub_status(&config.key, &config.signature_scheme, "busy") {
-            if let Err(e) = iopub_tx.send(msg) {
-                eprintln!("Failed to send busy status: {}", e);
+    // Signal that the kernel is busy
+    if let Ok(msg) =
+        raw_msg.to_iopub_status(&config.key, &config.signature_scheme, ExecutionState::Busy)
+        && let Err(e) = iopub_tx.send(msg).await
+    {
+        eprintln!("Failed to send busy status: {}", e);
+    }
+
+    // Send execute_input unless silent mode is enabled
+    if !request.silent {
+        if let Ok(msg) = raw_msg.to_iopub_execute_input(
+            &config.key,
+            &config.signature_scheme,
+            &request.code,
+            execution_count,
+        ) {
+            println!("Sending execute_input with count: {}", execution_count);
+            if let Err(e) = iopub_tx.send(msg).await {
+                eprintln!("Failed to send execute_input: {}", e);
             }
+        } else {
+            eprintln!("Failed to create execute_input message");
         }
+    }
 
-        // Send execute_input unless silent mode is enabled
-        if !request.silent {
-            if let Ok(msg) = raw_msg.to_iopub_execute_input(
-                &config.key,
-                &config.signature_scheme,
+    // Execute the main code. Unless silent, compiler events are printed in the cell and slow
+    // evaluations show how long they've been running. Neither is shown in deterministic mode,
+    // as they change from one run to the next.
+    let options = cell_options(&raw_msg.metadata);
+    let deterministic = match options.as_ref().ok().and_then(|options| {
+        options
+            .iter()
+            .find(|(name, _)| name == "deterministic")
+            .map(|(_, value)| value == "on")
+    }) {
+        Some(deterministic) => deterministic,
+        None => evaluator
+            .run(|eval| eval.deterministic())
+            .await
+            .unwrap_or(false),
+    };
+    let output = CellOutput::new(config, &raw_msg, iopub_tx, state.config.max_output);
+    let display_id = format!("progress-{}", raw_msg.header.msg_id);
+    let done = CancellationToken::new();
+    let (progress_tx, reporters) = if request.silent || deterministic {
+        (None, None)
+    } else {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let (output, done) = (output.clone(), done.clone());
+        let events = tokio::task::spawn_blocking({
+            let (output, done) = (output.clone(), done.clone());
+            let runtime = tokio::runtime::Handle::current();
+            move || runtime.block_on(forward_events(output, rx, done))
+        });
+        let progress = tokio::spawn(report_progress(
+            output,
+            display_id.clone(),
+            state.config.progress_delay,
+            done,
+        ));
+        (Some(tx), Some((events, progress)))
+    };
+
+    let start = Instant::now();
+    let CellResult {
+        result,
+        warnings,
+        definitions,
+        payload,
+        metadata,
+    } = match options {
+        Ok(options) => {
+            execute_aiken_code(
+                evaluator,
+                state,
                 &request.code,
                 execution_count,
-            ) {
-                println!("Sending execute_input with count: {}", execution_count);
-                if let Err(e) = iopub_tx.send(msg) {
-                    eprintln!("Failed to send execute_input: {}", e);
-                }
-            } else {
-                eprintln!("Failed to create execute_input message");
-            }
+                options,
+                progress_tx,
+            )
+            .await
         }
+        Err(e) => CellResult::error(e),
+    };
+    done.cancel();
+    if let Some((events, progress)) = reporters {
+        let _ = events.await;
+        // Clearing the outputs would also remove the compiler events, so replace the progress
+        // with how long the evaluation took instead
+        if progress.await.unwrap_or(false) {
+            let took = format!("Evaluated in {}s", start.elapsed().as_secs());
+            output.update(&display_id, plain_text(took)).await;
+        }
+    }
 
-        // Execute the main code
-        match execute_aiken_code(&request.code).await {
-            Ok(execution_result) => {
-                // Send execute_result unless silent mode is enabled.
-                // WARN: Here, we are using the execute_result message, which does the same as
-                // display_data, but provides the execution_count field for the frontend to
-                // display as Out[] counter. This looks different than other kernels, for example,
-                // IPython, but it is the "correct" way to do it as per the spec.
-                // If the Out[] counter gets annoying, we can change this to display_data.
-                // More info:
-                // - https://jupyter-client.readthedocs.io/en/stable/messaging.html#id6
-                // - https://jupyter-client.readthedocs.io/en/stable/messaging.html#display-data
-                // - https://discourse.jupyter.org/t/jupyter-messaging-display-data-vs-execute-result/21919
-                if !request.silent {
-                    if let Ok(msg) = raw_msg.to_iopub_execute_result(
-                        &config.key,
-                        &config.signature_scheme,
-                        execution_count,
-                        execution_result,
-                        serde_json::Value::Null,
-                    ) {
-                        if let Err(e) = iopub_tx.send(msg) {
-                            eprintln!("Failed to send execute_result: {}", e);
-                        }
-                    } else {
-                        eprintln!("Failed to create execute_result message");
-                    }
-                }
-
-                // Evaluate user expressions if provided
-                let user_expressions = if let serde_json::Value::Object(expr_map) =
-                    &request.user_expressions
-                {
-                    if !expr_map.is_empty() {
-                        // Convert JSON object to HashMap<String, String>
-                        let mut expressions = std::collections::HashMap::new();
-                        for (name, expr) in expr_map {
-                            if let serde_json::Value::String(expr_str) = expr {
-                                expressions.insert(name.clone(), expr_str.clone());
-                            }
-                        }
-
-                        if !expressions.is_empty() {
-                            let results = evaluate_user_expressions(&expressions).await;
-                            Some(serde_json::to_value(results).unwrap_or(serde_json::Value::Null))
-                        } else {
-                            None
-                        }
-                    } else {
-                        None
-                    }
-                } else {
-                    None
-                };
-
-                // Create successful execute reply
-                reply = ExecuteReply::Ok {
-                    execution_count,
-                    user_expressions,
-                };
-            }
-
-            Err(error) => {
-                // Extract error details for reply
-                let ename = "AikenError"; // Exception name
-                let evalue = error.lines().next().unwrap_or("").to_string(); // First line as exception value
-                let traceback: Vec<String> = error.lines().map(|line| line.to_string()).collect(); // Split into lines for proper traceback
+    // Warnings go to stderr, before the result or the error of the cell
+    if !request.silent {
+        for warning in &warnings {
+            let warning = match state.config.error_format {
+                ErrorFormat::Ansi => warning.clone(),
+                ErrorFormat::Plain | ErrorFormat::Html => ansi::strip(warning),
+            };
+            output.stream("stderr", &format!("{warning}\n")).await;
+        }
+        output.flush().await;
+    }
 
-                // Send error to IOPub
-                if let Ok(msg) = raw_msg.to_iopub_error(
+    match result {
+        Ok(execution_result) => {
+            // Send execute_result unless silent mode is enabled, or the cell has nothing to show
+            // besides its payload.
+            // WARN: Here, we are using the execute_result message, which does the same as
+            // display_data, but provides the execution_count field for the frontend to
+            // display as Out[] counter. This looks different than other kernels, for example,
+            // IPython, but it is the "correct" way to do it as per the spec.
+            // If the Out[] counter gets annoying, we can change this to display_data.
+            // More info:
+            // - https://jupyter-client.readthedocs.io/en/stable/messaging.html#id6
+            // - https://jupyter-client.readthedocs.io/en/stable/messaging.html#display-data
+            // - https://discourse.jupyter.org/t/jupyter-messaging-display-data-vs-execute-result/21919
+            if !request.silent && !execution_result.is_empty() {
+                if let Ok(msg) = raw_msg.to_iopub_execute_result(
                     &config.key,
                     &config.signature_scheme,
-                    ename,
-                    &evalue,
-                    &traceback,
+                    execution_count,
+                    output.limit(execution_result.clone()).await,
+                    metadata,
                 ) {
-                    if let Err(e) = iopub_tx.send(msg) {
-                        eprintln!("Failed to send error message: {}", e);
+                    if let Err(e) = iopub_tx.send(msg).await {
+                        eprintln!("Failed to send execute_result: {}", e);
                     }
                 } else {
-                    eprintln!("Failed to create error message");
+                    eprintln!("Failed to create execute_result message");
                 }
+            }
 
-                // Create error execute reply
-                reply = ExecuteReply::Error {
-                    execution_count,
-                    ename: ename.to_string(),
-                    evalue,
-                    traceback,
-                };
+            // Evaluate user expressions if provided
+            let expressions: HashMap<String, String> = match &request.user_expressions {
+                serde_json::Value::Object(expr_map) => expr_map
+                    .iter()
+                    .filter_map(|(name, expr)| Some((name.clone(), expr.as_str()?.to_string())))
+                    .collect(),
+                _ => HashMap::new(),
+            };
+            let user_expressions = if expressions.is_empty() {
+                None
+            } else {
+                Some(
+                    evaluate_user_expressions(evaluator, expressions, state.config.error_format)
+                        .await,
+                )
+            };
+
+            // Only the parent shell's definitions are recovered after a crash
+            if evaluator.same(&state.evaluator()) {
+                state.log_definitions(&definitions);
             }
+
+            // Keep the result around as `_N` for variable explorers
+            let text = bundle_text(&execution_result);
+            if store_history && !text.is_empty() {
+                state.record_output(execution_count, text);
+            }
+
+            // Create successful execute reply
+            reply = Reply::Ok(ExecuteOk {
+                execution_count,
+                user_expressions,
+                payload,
+            });
         }
 
-        // Build execute_reply
-        let reply_msg = JupyterMessage {
-            header: MessageHeader::new(raw_msg.header.session.clone(), "execute_reply".to_string()),
-            parent_header: Some(raw_msg.header.clone()),
-            metadata: serde_json::Value::Object(serde_json::Map::new()),
-            content: reply,
-        };
-
-        // Send execute_reply
-        if let Ok(byte_frames) = reply_msg.to_envelope_multipart(
-            frames,
-            delim_index,
-            &config.key,
-            &config.signature_scheme,
-        ) {
-            if let Err(e) = send_bytes(shell_socket, byte_frames).await {
-                eprintln!("Failed to send execute_reply: {}", e);
+        Err(report) => {
+            let format = state.config.error_format;
+            let mut error = aiken_error(&report, format);
+            // Frontends that render HTML show the report as a display, with its colors, so the
+            // error itself goes without traceback
+            if format == ErrorFormat::Html && !request.silent {
+                output.show(error_bundle(&report), None).await;
+                error.traceback.clear();
             }
-        } else {
-            eprintln!("Failed to create execute_reply message");
+
+            // Send error to IOPub
+            if let Ok(msg) = raw_msg.to_iopub_error(
+                &config.key,
+                &config.signature_scheme,
+                &error.ename,
+                &error.evalue,
+                &error.traceback,
+            ) {
+                if let Err(e) = iopub_tx.send(msg).await {
+                    eprintln!("Failed to send error message: {}", e);
+                }
+            } else {
+                eprintln!("Failed to create error message");
+            }
+
+            // Create error execute reply
+            reply = Reply::Error(ExecuteError {
+                execution_count,
+                error,
+            });
         }
+    }
 
-        // Announce kernel is back to idle
-        if let Ok(msg) = raw_msg.to_iopub_status(&config.key, &config.signature_scheme, "idle") {
-            if let Err(e) = iopub_tx.send(msg) {
-                eprintln!("Failed to send idle status: {}", e);
+    // Build execute_reply
+    let reply_msg = JupyterMessage {
+        header: MessageHeader::new(raw_msg.header.session.clone(), "execute_reply".to_string()),
+        parent_header: Some(raw_msg.header.clone()),
+        metadata: serde_json::Value::Object(serde_json::Map::new()),
+        content: reply,
+    };
+
+    // Send execute_reply
+    if let Ok(byte_frames) =
+        reply_msg.to_envelope_multipart(frames, delim_index, &config.key, &config.signature_scheme)
+    {
+        if let Err(e) = reply_tx.send(byte_frames) {
+            eprintln!("Failed to send execute_reply: {}", e);
+        }
+    } else {
+        eprintln!("Failed to create execute_reply message");
+    }
+
+    // Variable explorers show the parent shell's definitions, subshells don't change them
+    if evaluator.same(&state.evaluator()) {
+        publish_variables(state, iopub_tx, &raw_msg).await;
+    }
+
+    // Announce kernel is back to idle
+    if let Ok(msg) =
+        raw_msg.to_iopub_status(&config.key, &config.signature_scheme, ExecutionState::Idle)
+        && let Err(e) = iopub_tx.send(msg).await
+    {
+        eprintln!("Failed to send idle status: {}", e);
+    }
+}
+
+/// Show how long the cell has been running once it takes longer than `delay`, so slow
+/// evaluations (eg. compiling the stdlib for the first time) don't look frozen.
+/// Returns whether anything was shown.
+async fn report_progress(
+    output: CellOutput,
+    display_id: String,
+    delay: Duration,
+    done: CancellationToken,
+) -> bool {
+    let start = Instant::now();
+    tokio::select! {
+        _ = done.cancelled() => return false,
+        _ = tokio::time::sleep(delay) => {}
+    }
+
+    output.display(&display_id, running_for(start)).await;
+    loop {
+        tokio::select! {
+            _ = done.cancelled() => return true,
+            _ = tokio::time::sleep(Duration::from_secs(1)) => {
+                output.update(&display_id, running_for(start)).await;
             }
         }
     }
-    Ok(())
+}
+
+/// Print the compiler events of the evaluation (eg. resolving packages) in the cell, until
+/// `done` or until the evaluator drops its sender. Runs on a blocking thread, as it waits on
+/// the evaluator's channel.
+async fn forward_events(output: CellOutput, rx: Receiver<String>, done: CancellationToken) {
+    while !done.is_cancelled() {
+        match rx.recv_timeout(Duration::from_millis(100)) {
+            Ok(line) => output.stream("stdout", &format!("{line}\n")).await,
+            // Nothing new for a while, publish the lines held back
+            Err(RecvTimeoutError::Timeout) => output.flush().await,
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+    }
+    // Events sent right before the evaluation finished
+    for line in rx.try_iter() {
+        output.stream("stdout", &format!("{line}\n")).await;
+    }
+    output.flush().await;
+}
+
+fn running_for(start: Instant) -> MimeBundle {
+    plain_text(format!("Evaluating... ({}s)", start.elapsed().as_secs()))
+}
+
+fn plain_text(text: String) -> MimeBundle {
+    let mut data = MimeBundle::new();
+    data.insert("text/plain".into(), serde_json::Value::String(text));
+    data
+}
+
+#[cfg(test)]
+mod test {
+    use jupyter_protocol::shell::execute::ExecuteRequest;
+
+    use super::handle_execute_request;
+    use aiken_repl::config::ErrorFormat;
+
+    use crate::connection::testing::{
+        DELIMITER, IDENTITY, channels, decode, describe, incoming, state, state_with_errors,
+    };
+    use crate::state::SharedState;
+
+    /// Run `code`, returning the reply and the IOPub messages, in order
+    async fn execute(
+        state: &SharedState,
+        code: &str,
+        silent: bool,
+        store_history: bool,
+    ) -> (serde_json::Value, Vec<String>) {
+        execute_with_metadata(state, code, silent, store_history, serde_json::json!({})).await
+    }
+
+    async fn execute_with_metadata(
+        state: &SharedState,
+        code: &str,
+        silent: bool,
+        store_history: bool,
+        metadata: serde_json::Value,
+    ) -> (serde_json::Value, Vec<String>) {
+        let (reply_tx, iopub_tx, mut recorder) = channels();
+        let content = serde_json::json!({
+            "code": code,
+            "silent": silent,
+            "store_history": store_history,
+            "user_expressions": {},
+            "allow_stdin": false,
+            "stop_on_error": true,
+        });
+        let request: ExecuteRequest = serde_json::from_value(content.clone()).unwrap();
+        let mut incoming = incoming("execute_request", content);
+        incoming.raw_msg.metadata = metadata;
+        let evaluator = state.evaluator();
+        handle_execute_request(state, &evaluator, &reply_tx, &iopub_tx, incoming, request).await;
+
+        let replies = recorder.replies();
+        assert_eq!(replies.len(), 1);
+        assert_eq!(
+            (&replies[0][0][..], &replies[0][1][..]),
+            (IDENTITY, DELIMITER)
+        );
+        let reply = decode(&replies[0]);
+        assert_eq!(reply.header.msg_type, "execute_reply");
+        let iopub = describe(&recorder.iopub());
+        (reply.content, iopub)
+    }
+
+    /// Counts of the cells in the history
+    fn history_counts(state: &SharedState) -> Vec<u32> {
+        state
+            .history()
+            .iter()
+            .map(|entry| entry.execution_count)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_execute_frames() {
+        let state = state();
+
+        let (reply, iopub) = execute(&state, "1 + 2", false, true).await;
+        assert_eq!(reply["status"], "ok");
+        assert_eq!(reply["execution_count"], 1);
+        assert_eq!(
+            iopub,
+            [
+                "status: busy",
+                "execute_input",
+                "execute_result",
+                "status: idle"
+            ]
+        );
+
+        let (reply, iopub) = execute(&state, "this_is_not_defined", false, true).await;
+        assert_eq!(reply["status"], "error");
+        assert_eq!(reply["execution_count"], 2);
+        assert_eq!(
+            iopub,
+            ["status: busy", "execute_input", "error", "status: idle"]
+        );
+
+        // Neither the input nor the result are published
+        let (reply, iopub) = execute(&state, "1 + 2", true, true).await;
+        assert_eq!(reply["status"], "ok");
+        assert_eq!(iopub, ["status: busy", "status: idle"]);
+    }
+
+    #[tokio::test]
+    async fn test_execution_count() {
+        let state = state();
+
+        // Failed cells are numbered too
+        execute(&state, "1 + 2", false, true).await;
+        execute(&state, "this_is_not_defined", false, true).await;
+        assert_eq!(history_counts(&state), [1, 2]);
+
+        // Silent cells are never stored, even when asked to
+        let (reply, _) = execute(&state, "1 + 2", true, true).await;
+        assert_eq!(reply["execution_count"], 2);
+        let (reply, _) = execute(&state, "1 + 2", true, false).await;
+        assert_eq!(reply["execution_count"], 2);
+
+        // Cells not stored still show their input and result, under the last count
+        let (reply, iopub) = execute(&state, "1 + 2", false, false).await;
+        assert_eq!(reply["execution_count"], 2);
+        assert!(iopub.contains(&"execute_input".to_string()));
+        assert!(iopub.contains(&"execute_result".to_string()));
+        assert_eq!(history_counts(&state), [1, 2]);
+
+        let (reply, _) = execute(&state, "1 + 2", false, true).await;
+        assert_eq!(reply["execution_count"], 3);
+        assert_eq!(history_counts(&state), [1, 2, 3]);
+    }
+
+    #[tokio::test]
+    async fn test_error_formats() {
+        let state = state_with_errors(ErrorFormat::Html);
+        let (reply, iopub) = execute(&state, "this_is_not_defined", false, true).await;
+        assert_eq!(reply["status"], "error");
+        assert_eq!(reply["traceback"], serde_json::json!([]));
+        assert_eq!(
+            iopub,
+            [
+                "status: busy",
+                "execute_input",
+                "display_data",
+                "error",
+                "status: idle"
+            ]
+        );
+
+        let state = state_with_errors(ErrorFormat::Plain);
+        let (reply, iopub) = execute(&state, "this_is_not_defined", false, true).await;
+        let traceback = reply["traceback"].as_array().unwrap();
+        assert!(!traceback.is_empty());
+        assert!(
+            traceback
+                .iter()
+                .all(|line| !line.as_str().unwrap().contains('\x1b'))
+        );
+        assert_eq!(
+            iopub,
+            ["status: busy", "execute_input", "error", "status: idle"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_execute_metadata() {
+        let state = state();
+        let options = state.evaluator().run(|eval| eval.options()).await.unwrap();
+
+        let metadata = serde_json::json!({ "iaiken": { "max-depth": 2 } });
+        let (reply, _) = execute_with_metadata(&state, "1 + 2", false, true, metadata).await;
+        assert_eq!(reply["status"], "ok");
+        // The options are only changed for the cell
+        let after = state.evaluator().run(|eval| eval.options()).await.unwrap();
+        assert_eq!(after, options);
+
+        let metadata = serde_json::json!({ "iaiken": { "max-depth": "deep" } });
+        let (reply, iopub) = execute_with_metadata(&state, "1 + 2", false, true, metadata).await;
+        assert_eq!(reply["status"], "error");
+        assert!(iopub.contains(&"error".to_string()));
+
+        let metadata = serde_json::json!({ "iaiken": [] });
+        let (reply, _) = execute_with_metadata(&state, "1 + 2", false, true, metadata).await;
+        assert_eq!(
+            reply["evalue"],
+            "Invalid cell metadata: `iaiken` must be an object"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_execute_payload() {
+        let state = state();
+        let dir = tempfile::tempdir().unwrap();
+        let module = dir.path().join("demo.ak");
+        std::fs::write(&module, "pub const x = 1\n").unwrap();
+
+        // The cell is replaced with the file, and has no result of its own
+        let (reply, iopub) =
+            execute(&state, &format!("%load {}", module.display()), false, true).await;
+        assert_eq!(
+            reply["payload"],
+            serde_json::json!([
+                { "source": "set_next_input", "text": "pub const x = 1", "replace": true }
+            ])
+        );
+        assert_eq!(iopub, ["status: busy", "execute_input", "status: idle"]);
+
+        let (reply, _) = execute(&state, "%page 1 + 2", false, true).await;
+        assert_eq!(reply["payload"][0]["source"], "page");
+        assert_eq!(reply["payload"][0]["start"], 0);
+
+        let (reply, _) = execute(&state, "%load", false, true).await;
+        assert_eq!(reply["status"], "error");
+    }
+
+    #[tokio::test]
+    async fn test_execute_reset() {
+        let state = state();
+        let (reply, _) = execute(&state, "pub const x = 1", false, true).await;
+        assert_eq!(reply["status"], "ok");
+        let (reply, _) = execute(&state, "x + 1", false, true).await;
+        assert_eq!(reply["status"], "ok");
+
+        let (reply, iopub) = execute(&state, "%reset", false, true).await;
+        assert_eq!(reply["status"], "ok");
+        assert!(iopub.contains(&"execute_result".to_string()));
+        let (reply, _) = execute(&state, "x + 1", false, true).await;
+        assert_eq!(reply["status"], "error");
+        assert_eq!(reply["execution_count"], 4);
+
+        let (reply, _) = execute(&state, "%reset --hard", false, true).await;
+        assert_eq!(reply["status"], "ok");
+        let (reply, _) = execute(&state, "%reset --soft", false, true).await;
+        assert_eq!(reply["status"], "error");
+        assert_eq!(reply["execution_count"], 1);
+    }
 }
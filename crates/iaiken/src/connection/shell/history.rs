@@ -0,0 +1,178 @@
+use crate::{
+    connection::{iopub::IopubTx, router::Incoming, shell::ReplyTx},
+    state::SharedState,
+};
+use jupyter_protocol::{
+    JupyterMessage, MessageHeader,
+    iopub::ExecutionState,
+    reply::Reply,
+    shell::history::{HistoryReply, HistoryRequest},
+};
+
+/// Session number of the current session in history replies
+const CURRENT_SESSION: i64 = 0;
+
+/// Session number of the inputs of earlier sessions. Negative session numbers count back from
+/// the current one.
+const PREVIOUS_SESSION: i64 = -1;
+
+/// `(session, line, input)`, as in history replies
+type HistoryTuple = (i64, u32, String);
+
+pub async fn handle_history_request(
+    state: &SharedState,
+    reply_tx: &ReplyTx,
+    iopub_tx: &IopubTx,
+    incoming: Incoming,
+    request: HistoryRequest,
+) {
+    let config = &state.config.connection;
+    let Incoming {
+        raw_msg,
+        frames,
+        delim_index,
+    } = incoming;
+
+    if let Ok(frames) =
+        raw_msg.to_iopub_status(&config.key, &config.signature_scheme, ExecutionState::Busy)
+    {
+        let _ = iopub_tx.send(frames).await;
+    }
+
+    let reply: Reply<HistoryReply> = Reply::Ok(HistoryReply {
+        history: select_history(all_history(state), &request),
+    });
+
+    let reply_msg = JupyterMessage {
+        header: MessageHeader::new(raw_msg.header.session.clone(), "history_reply".to_string()),
+        parent_header: Some(raw_msg.header.clone()),
+        metadata: serde_json::Value::Object(serde_json::Map::new()),
+        content: reply,
+    };
+
+    if let Ok(bytes_frames) =
+        reply_msg.to_envelope_multipart(frames, delim_index, &config.key, &config.signature_scheme)
+        && let Err(e) = reply_tx.send(bytes_frames)
+    {
+        eprintln!("Failed to send history_reply: {e}");
+    }
+
+    if let Ok(frames) =
+        raw_msg.to_iopub_status(&config.key, &config.signature_scheme, ExecutionState::Idle)
+    {
+        let _ = iopub_tx.send(frames).await;
+    }
+}
+
+/// Pick the entries asked for by `request` among `(session, line, input)` tuples, oldest first.
+/// Earlier sessions all come from the history file, so `range` requests for sessions before the
+/// previous one get nothing.
+fn select_history(history: Vec<HistoryTuple>, request: &HistoryRequest) -> Vec<HistoryTuple> {
+    match request.hist_access_type.as_str() {
+        "tail" => {
+            let n = request.n.unwrap_or(history.len());
+            let skip = history.len().saturating_sub(n);
+            history.into_iter().skip(skip).collect()
+        }
+        "range" => {
+            let session = request.session.unwrap_or(CURRENT_SESSION);
+            let start = request.start.unwrap_or(0);
+            let stop = request.stop.unwrap_or(i64::MAX);
+            history
+                .into_iter()
+                .filter(|(s, line, _)| *s == session && (start..stop).contains(&i64::from(*line)))
+                .collect()
+        }
+        "search" => {
+            // Only `*` wildcards are supported, which covers what frontends send
+            let pattern = request.pattern.as_deref().unwrap_or("*");
+            let parts: Vec<&str> = pattern.split('*').filter(|p| !p.is_empty()).collect();
+            let mut matches: Vec<HistoryTuple> = history
+                .into_iter()
+                .filter(|(_, _, code)| parts.iter().all(|part| code.contains(part)))
+                .collect();
+            if request.unique {
+                // Keep the most recent copy of each input
+                let mut seen = std::collections::HashSet::new();
+                matches.reverse();
+                matches.retain(|(_, _, code)| seen.insert(code.clone()));
+                matches.reverse();
+            }
+            if let Some(n) = request.n {
+                let skip = matches.len().saturating_sub(n);
+                matches.drain(..skip);
+            }
+            matches
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// Inputs of earlier sessions, numbered by their position in the history file, followed by the
+/// cells of this one
+fn all_history(state: &SharedState) -> Vec<HistoryTuple> {
+    let previous = state
+        .previous_history()
+        .iter()
+        .enumerate()
+        .map(|(i, code)| (PREVIOUS_SESSION, i as u32 + 1, code.clone()));
+    let current = state
+        .history()
+        .into_iter()
+        .map(|entry| (CURRENT_SESSION, entry.execution_count, entry.code));
+    previous.chain(current).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use jupyter_protocol::shell::history::HistoryRequest;
+
+    use super::{CURRENT_SESSION, HistoryTuple, PREVIOUS_SESSION, select_history};
+
+    fn request(hist_access_type: &str) -> HistoryRequest {
+        HistoryRequest {
+            output: false,
+            raw: true,
+            hist_access_type: hist_access_type.to_string(),
+            session: None,
+            start: None,
+            stop: None,
+            n: None,
+            pattern: None,
+            unique: false,
+        }
+    }
+
+    fn history() -> Vec<HistoryTuple> {
+        vec![
+            (PREVIOUS_SESSION, 1, "1 + 2".to_string()),
+            (PREVIOUS_SESSION, 2, "double(2)".to_string()),
+            (CURRENT_SESSION, 1, "double(3)".to_string()),
+            (CURRENT_SESSION, 2, "1 + 2".to_string()),
+        ]
+    }
+
+    #[test]
+    fn test_select_history() {
+        let tail = HistoryRequest {
+            n: Some(3),
+            ..request("tail")
+        };
+        assert_eq!(select_history(history(), &tail), history()[1..]);
+
+        // Earlier sessions are reached with a negative session number
+        let range = HistoryRequest {
+            session: Some(PREVIOUS_SESSION),
+            start: Some(2),
+            ..request("range")
+        };
+        assert_eq!(select_history(history(), &range), history()[1..2]);
+
+        let search = HistoryRequest {
+            pattern: Some("1 *".to_string()),
+            unique: true,
+            ..request("search")
+        };
+        assert_eq!(select_history(history(), &search), history()[3..]);
+    }
+}
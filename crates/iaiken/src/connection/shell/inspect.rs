@@ -0,0 +1,106 @@
+use crate::{
+    connection::{iopub::IopubTx, router::Incoming, shell::ReplyTx},
+    eval::worker::EvalWorker,
+    state::SharedState,
+};
+use jupyter_protocol::{
+    JupyterMessage, MessageHeader, MimeBundle,
+    iopub::ExecutionState,
+    reply::Reply,
+    shell::inspect::{InspectReply, InspectRequest},
+};
+
+/// Answer with the docs of the name under the cursor: its signature, its documentation and, for
+/// the session's own definitions, the cell that defined it
+pub async fn handle_inspect_request(
+    state: &SharedState,
+    evaluator: &EvalWorker,
+    reply_tx: &ReplyTx,
+    iopub_tx: &IopubTx,
+    incoming: Incoming,
+    request: InspectRequest,
+) {
+    let config = &state.config.connection;
+    let Incoming {
+        raw_msg,
+        frames,
+        delim_index,
+    } = incoming;
+
+    if let Ok(frames) =
+        raw_msg.to_iopub_status(&config.key, &config.signature_scheme, ExecutionState::Busy)
+    {
+        let _ = iopub_tx.send(frames).await;
+    }
+
+    let mut data = MimeBundle::new();
+    if let Some(name) = name_at(&request.code, request.cursor_pos) {
+        let name = name.to_string();
+        let entry = evaluator
+            .run(move |eval| eval.doc(&name).ok().flatten())
+            .await
+            .flatten();
+        if let Some(entry) = entry {
+            data.insert("text/plain".to_string(), entry.to_string().into());
+        }
+    }
+
+    let reply: Reply<InspectReply> = Reply::Ok(InspectReply {
+        found: !data.is_empty(),
+        data,
+        metadata: serde_json::Value::Object(serde_json::Map::new()),
+    });
+
+    let reply_msg = JupyterMessage {
+        header: MessageHeader::new(raw_msg.header.session.clone(), "inspect_reply".to_string()),
+        parent_header: Some(raw_msg.header.clone()),
+        metadata: serde_json::Value::Object(serde_json::Map::new()),
+        content: reply,
+    };
+
+    if let Ok(bytes_frames) =
+        reply_msg.to_envelope_multipart(frames, delim_index, &config.key, &config.signature_scheme)
+        && let Err(e) = reply_tx.send(bytes_frames)
+    {
+        eprintln!("Failed to send inspect_reply: {e}");
+    }
+
+    if let Ok(frames) =
+        raw_msg.to_iopub_status(&config.key, &config.signature_scheme, ExecutionState::Idle)
+    {
+        let _ = iopub_tx.send(frames).await;
+    }
+}
+
+/// The name, possibly qualified (`list.map`), around `cursor_pos`, counted in characters
+pub(crate) fn name_at(code: &str, cursor_pos: usize) -> Option<&str> {
+    let is_name = |c: char| c.is_alphanumeric() || c == '_' || c == '.';
+    let cursor = code
+        .char_indices()
+        .nth(cursor_pos)
+        .map_or(code.len(), |(i, _)| i);
+    let start = code[..cursor]
+        .rfind(|c: char| !is_name(c))
+        .map_or(0, |i| i + 1);
+    let end = code[cursor..]
+        .find(|c: char| !is_name(c))
+        .map_or(code.len(), |i| cursor + i);
+    let name = code[start..end].trim_matches('.');
+    (!name.is_empty()).then_some(name)
+}
+
+#[cfg(test)]
+mod test {
+    use super::name_at;
+
+    #[test]
+    fn test_name_at() {
+        assert_eq!(name_at("double(2)", 3), Some("double"));
+        // Right after the name, as frontends send it
+        assert_eq!(name_at("double(2)", 6), Some("double"));
+        assert_eq!(name_at("list.map(xs, f)", 5), Some("list.map"));
+        assert_eq!(name_at("\"é\" == foo", 9), Some("foo"));
+        assert_eq!(name_at("1 + 2", 2), None);
+        assert_eq!(name_at("", 0), None);
+    }
+}
@@ -1,27 +1,27 @@
 use crate::{
-    connection::iopub::IopubTx,
-    messages::{
-        ConnectionConfig, JupyterMessage, MessageHeader, shell::kernel_info::KernelInfoReply,
-        wire::send_bytes,
-    },
+    connection::{iopub::IopubTx, router::Incoming, shell::ReplyTx},
+    messages::kernel_info::kernel_info_reply,
 };
-
-use zeromq::RouterSocket;
+use jupyter_protocol::{ConnectionConfig, JupyterMessage, MessageHeader, iopub::ExecutionState};
 
 pub async fn handle_kernel_info_request(
     config: &ConnectionConfig,
-    shell_socket: &mut RouterSocket,
+    reply_tx: &ReplyTx,
     iopub_tx: &IopubTx,
-    raw_msg: JupyterMessage<serde_json::Value>,
-    frames: Vec<Vec<u8>>,
-    delim_index: usize,
+    incoming: Incoming,
+    codemirror_mode: &str,
 ) {
+    let Incoming {
+        raw_msg,
+        frames,
+        delim_index,
+    } = incoming;
     println!(
         "Received kernel_info_request with raw_msg: {}",
         raw_msg.header.version
     );
     // Handle kernel info request
-    let reply = KernelInfoReply::new();
+    let reply = kernel_info_reply(codemirror_mode);
 
     // Build reply header
     let reply_header = MessageHeader::new(
@@ -29,8 +29,10 @@ pub async fn handle_kernel_info_request(
         "kernel_info_reply".to_string(),
     );
 
-    if let Ok(frames) = raw_msg.to_iopub_status(&config.key, &config.signature_scheme, "busy") {
-        let _ = iopub_tx.send(frames);
+    if let Ok(frames) =
+        raw_msg.to_iopub_status(&config.key, &config.signature_scheme, ExecutionState::Busy)
+    {
+        let _ = iopub_tx.send(frames).await;
     }
 
     // Create reply message
@@ -44,10 +46,56 @@ pub async fn handle_kernel_info_request(
     if let Ok(bytes_frames) =
         reply_msg.to_envelope_multipart(frames, delim_index, &config.key, &config.signature_scheme)
     {
-        send_bytes(shell_socket, bytes_frames).await.unwrap();
+        let _ = reply_tx.send(bytes_frames);
+    }
+
+    if let Ok(frames) =
+        raw_msg.to_iopub_status(&config.key, &config.signature_scheme, ExecutionState::Idle)
+    {
+        let _ = iopub_tx.send(frames).await;
     }
+}
+
+#[cfg(test)]
+mod test {
+    use jupyter_protocol::JupyterMessage;
+
+    use super::handle_kernel_info_request;
+    use crate::connection::testing::{
+        DELIMITER, IDENTITY, channels, config, decode, describe, incoming,
+    };
+
+    #[tokio::test]
+    async fn test_kernel_info_frames() {
+        let (reply_tx, iopub_tx, mut recorder) = channels();
+        let request = incoming("kernel_info_request", serde_json::json!({}));
+        let request_id = request.raw_msg.header.msg_id.clone();
+        handle_kernel_info_request(&config(), &reply_tx, &iopub_tx, request, "aiken").await;
+
+        let iopub = recorder.iopub();
+        assert_eq!(describe(&iopub), ["status: busy", "status: idle"]);
+        let parent_id = |msg: &JupyterMessage<serde_json::Value>| {
+            msg.parent_header
+                .as_ref()
+                .map(|parent| parent.msg_id.clone())
+        };
+        assert!(
+            iopub
+                .iter()
+                .all(|msg| parent_id(msg) == Some(request_id.clone()))
+        );
 
-    if let Ok(frames) = raw_msg.to_iopub_status(&config.key, &config.signature_scheme, "idle") {
-        let _ = iopub_tx.send(frames);
+        // Routed back to the client: its identity, the delimiter, the signature and the four
+        // parts of the message
+        let replies = recorder.replies();
+        assert_eq!(replies.len(), 1);
+        let frames = &replies[0];
+        assert_eq!(frames.len(), 7);
+        assert_eq!((&frames[0][..], &frames[1][..]), (IDENTITY, DELIMITER));
+        let reply = decode(frames);
+        assert_eq!(reply.header.msg_type, "kernel_info_reply");
+        assert_eq!(parent_id(&reply), Some(request_id));
+        assert_eq!(reply.content["status"], "ok");
+        assert_eq!(reply.content["language_info"]["codemirror_mode"], "aiken");
     }
 }
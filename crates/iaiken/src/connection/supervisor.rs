@@ -0,0 +1,187 @@
+//! Keeps the channel loops alive.
+//!
+//! Every loop returns a `LoopExit` instead of silently breaking. When a loop fails (usually
+//! because its socket errored), the supervisor closes the socket, binds a fresh one to the same
+//! address and restarts the loop. A loop that keeps failing brings the whole kernel down rather
+//! than leaving it half-dead.
+
+use std::time::{Duration, Instant};
+
+use tokio_util::sync::CancellationToken;
+use zeromq::Socket;
+
+/// How a channel loop ended
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LoopExit {
+    /// The kernel is shutting down
+    Cancelled,
+    /// The socket failed and the loop should be restarted
+    Failed,
+}
+
+/// Restarts allowed within `RESTART_WINDOW` before giving up
+const MAX_RESTARTS: usize = 5;
+const RESTART_WINDOW: Duration = Duration::from_secs(60);
+/// Pause between a failure and the next attempt, so we don't spin on a broken socket
+const RESTART_BACKOFF: Duration = Duration::from_millis(200);
+
+/// Recent failures of one loop
+struct RestartBudget {
+    failures: Vec<Instant>,
+}
+
+impl RestartBudget {
+    fn new() -> Self {
+        Self {
+            failures: Vec::new(),
+        }
+    }
+
+    /// Record a failure, returns false once the loop failed too often
+    fn record_failure(&mut self, now: Instant) -> bool {
+        self.failures
+            .retain(|failure| now.duration_since(*failure) < RESTART_WINDOW);
+        self.failures.push(now);
+        self.failures.len() <= MAX_RESTARTS
+    }
+}
+
+/// Run `run` on `socket`, rebinding the socket to `address` and restarting the loop whenever it
+/// fails. Cancels `cancel` (shutting down the kernel) after repeated failures.
+pub async fn supervise<S, F>(
+    name: &str,
+    cancel: CancellationToken,
+    address: String,
+    mut socket: S,
+    mut run: F,
+) where
+    S: Socket,
+    F: AsyncFnMut(&mut S) -> LoopExit,
+{
+    let mut budget = RestartBudget::new();
+
+    loop {
+        if run(&mut socket).await == LoopExit::Cancelled || cancel.is_cancelled() {
+            return;
+        }
+        eprintln!("{name} loop died, restarting it");
+
+        // Close the dead socket and bind a new one in its place
+        for e in socket.close().await {
+            eprintln!("Error closing {name} socket: {e}");
+        }
+        socket = loop {
+            if !budget.record_failure(Instant::now()) {
+                eprintln!(
+                    "{name} loop failed more than {MAX_RESTARTS} times in {}s, shutting down",
+                    RESTART_WINDOW.as_secs()
+                );
+                cancel.cancel();
+                return;
+            }
+            tokio::select! {
+                _ = cancel.cancelled() => return,
+                _ = tokio::time::sleep(RESTART_BACKOFF) => {}
+            }
+
+            let mut new_socket = S::new();
+            match new_socket.bind(&address).await {
+                Ok(_) => {
+                    println!("{name} socket rebound to {address}");
+                    break new_socket;
+                }
+                Err(e) => eprintln!("Failed to rebind {name} socket to {address}: {e}"),
+            }
+        };
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::connection::iopub::iopub_loop;
+    use crate::connection::testing::state;
+
+    #[test]
+    fn test_restart_budget() {
+        let mut budget = RestartBudget::new();
+        let start = Instant::now();
+        for _ in 0..MAX_RESTARTS {
+            assert!(budget.record_failure(start));
+        }
+        assert!(!budget.record_failure(start));
+
+        // Old failures don't count anymore
+        let later = start + RESTART_WINDOW;
+        assert!(budget.record_failure(later));
+    }
+
+    #[tokio::test]
+    async fn test_supervise_restarts_then_gives_up() {
+        let cancel = CancellationToken::new();
+        let mut runs = 0;
+        supervise(
+            "Test",
+            cancel.clone(),
+            "tcp://127.0.0.1:0".to_string(),
+            zeromq::RepSocket::new(),
+            async |_socket: &mut zeromq::RepSocket| {
+                runs += 1;
+                LoopExit::Failed
+            },
+        )
+        .await;
+
+        assert_eq!(runs, MAX_RESTARTS + 1);
+        assert!(cancel.is_cancelled());
+    }
+
+    #[tokio::test]
+    async fn test_supervise_restarts_failing_iopub() {
+        let cancel = CancellationToken::new();
+        let (iopub_tx, mut iopub_rx) = tokio::sync::mpsc::channel(16);
+        // A router fails to send to a client it doesn't know, as a dead IOPub socket would
+        for _ in 0..=MAX_RESTARTS {
+            let frames = vec![bytes::Bytes::from("nobody"), bytes::Bytes::from("frame")];
+            iopub_tx.send(frames).await.unwrap();
+        }
+        let state = state();
+        let mut runs = 0;
+        supervise(
+            "IOPub",
+            cancel.clone(),
+            "tcp://127.0.0.1:0".to_string(),
+            zeromq::RouterSocket::new(),
+            async |socket: &mut zeromq::RouterSocket| {
+                runs += 1;
+                iopub_loop(cancel.clone(), socket, &mut iopub_rx, &iopub_tx, &state).await
+            },
+        )
+        .await;
+
+        // Each frame failed to go out, and the loop was restarted for the next
+        assert_eq!(runs, MAX_RESTARTS + 1);
+        assert!(iopub_rx.is_empty());
+        assert!(cancel.is_cancelled());
+    }
+
+    #[tokio::test]
+    async fn test_supervise_stops_when_cancelled() {
+        let cancel = CancellationToken::new();
+        let mut runs = 0;
+        supervise(
+            "Test",
+            cancel.clone(),
+            "tcp://127.0.0.1:0".to_string(),
+            zeromq::RepSocket::new(),
+            async |_socket: &mut zeromq::RepSocket| {
+                runs += 1;
+                LoopExit::Cancelled
+            },
+        )
+        .await;
+
+        assert_eq!(runs, 1);
+        assert!(!cancel.is_cancelled());
+    }
+}
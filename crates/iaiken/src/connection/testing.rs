@@ -0,0 +1,126 @@
+//! In-memory ends of the shell and IOPub channels, to drive the handlers in tests without sockets
+//!
+//! Handlers queue the frames they send on channels, so recording what they'd put on the wire
+//! only takes keeping the receiving ends.
+
+use std::time::Duration;
+
+use aiken_repl::config::ErrorFormat;
+use jupyter_protocol::{ConnectionConfig, JupyterMessage, MessageHeader};
+use tokio::sync::mpsc::{UnboundedReceiver, unbounded_channel};
+
+use super::{
+    iopub::{IopubRx, IopubTx},
+    router::Incoming,
+    shell::ReplyTx,
+};
+use crate::state::{KernelConfig, KernelState, SharedState};
+
+pub const KEY: &str = "key";
+pub const SCHEME: &str = "hmac-sha256";
+/// Routing id of the client the requests come from, sent back first in the replies
+pub const IDENTITY: &[u8] = b"client";
+pub const DELIMITER: &[u8] = b"<IDS|MSG>";
+
+pub fn config() -> ConnectionConfig {
+    ConnectionConfig {
+        transport: "tcp".to_string(),
+        ip: "127.0.0.1".to_string(),
+        signature_scheme: SCHEME.to_string(),
+        key: KEY.to_string(),
+        control_port: 0,
+        shell_port: 0,
+        stdin_port: 0,
+        hb_port: 0,
+        iopub_port: 0,
+    }
+}
+
+/// A kernel session without history, definition log or options, that never shows running times
+pub fn state() -> SharedState {
+    state_with_errors(ErrorFormat::Ansi)
+}
+
+/// A kernel session like `state()`, that shows errors in `error_format`
+pub fn state_with_errors(error_format: ErrorFormat) -> SharedState {
+    KernelState::new(KernelConfig {
+        connection: config(),
+        codemirror_mode: "aiken".to_string(),
+        history: None,
+        definition_log: None,
+        options: Vec::new(),
+        progress_delay: Duration::from_secs(3600),
+        max_output: super::DEFAULT_MAX_OUTPUT,
+        error_format,
+    })
+}
+
+/// A signed request from `IDENTITY`, as the router hands it to the handlers
+pub fn incoming(msg_type: &str, content: serde_json::Value) -> Incoming {
+    let raw_msg = JupyterMessage {
+        header: MessageHeader::new("session".to_string(), msg_type.to_string()),
+        parent_header: None,
+        metadata: serde_json::json!({}),
+        content,
+    };
+    let frames = raw_msg
+        .to_envelope_multipart(vec![IDENTITY.to_vec(), DELIMITER.to_vec()], 1, KEY, SCHEME)
+        .unwrap()
+        .iter()
+        .map(|frame| frame.to_vec())
+        .collect();
+    Incoming {
+        raw_msg,
+        frames,
+        delim_index: 1,
+    }
+}
+
+/// Receiving ends of the replies and IOPub messages of the handlers
+pub struct Recorder {
+    reply_rx: UnboundedReceiver<Vec<bytes::Bytes>>,
+    iopub_rx: IopubRx,
+}
+
+pub fn channels() -> (ReplyTx, IopubTx, Recorder) {
+    let (reply_tx, reply_rx) = unbounded_channel();
+    let (iopub_tx, iopub_rx) = tokio::sync::mpsc::channel(64);
+    (reply_tx, iopub_tx, Recorder { reply_rx, iopub_rx })
+}
+
+impl Recorder {
+    /// Frames of the replies sent so far
+    pub fn replies(&mut self) -> Vec<Vec<Vec<u8>>> {
+        let mut replies = Vec::new();
+        while let Ok(frames) = self.reply_rx.try_recv() {
+            replies.push(frames.iter().map(|frame| frame.to_vec()).collect());
+        }
+        replies
+    }
+
+    /// IOPub messages published so far, checked against the key
+    pub fn iopub(&mut self) -> Vec<JupyterMessage<serde_json::Value>> {
+        let mut messages = Vec::new();
+        while let Ok(frames) = self.iopub_rx.try_recv() {
+            let frames: Vec<Vec<u8>> = frames.iter().map(|frame| frame.to_vec()).collect();
+            messages.push(decode(&frames));
+        }
+        messages
+    }
+}
+
+/// Check the signature of `frames` and read the message
+pub fn decode(frames: &[Vec<u8>]) -> JupyterMessage<serde_json::Value> {
+    JupyterMessage::from_multipart(frames, KEY, SCHEME).unwrap()
+}
+
+/// Type of each message, with the state of status messages, eg. `status: busy`
+pub fn describe(messages: &[JupyterMessage<serde_json::Value>]) -> Vec<String> {
+    messages
+        .iter()
+        .map(|msg| match msg.content["execution_state"].as_str() {
+            Some(state) => format!("{}: {}", msg.header.msg_type, state),
+            None => msg.header.msg_type.clone(),
+        })
+        .collect()
+}
@@ -0,0 +1,356 @@
+//! Magics for addresses, data, keys and transactions, on a chain provider or the mock chain
+
+use aiken_repl::evaluator::{MockChain, ReplEvaluator};
+use iaiken_magics::{Flavor, Invocation, KernelState, Magic, register};
+use jupyter_protocol::MimeBundle;
+
+use crate::eval::format_evaluation_error_in_task;
+use crate::messages::text_bundle;
+
+const CHAIN_USAGE: &str = "Usage: %chain [config <project id> | init | fund <owner> <amount> [datum <expr>] | utxos], or %%chain submit followed by the transaction";
+
+struct DecodeAddress;
+
+impl Magic for DecodeAddress {
+    fn name(&self) -> &str {
+        "decode_address"
+    }
+
+    fn flavor(&self) -> Flavor {
+        Flavor::Line
+    }
+
+    fn help(&self) -> &str {
+        "`%decode_address <bech32|hex>`: show the parts of an address and its Aiken literal"
+    }
+
+    fn execute(&self, kernel: &mut KernelState, call: &Invocation) -> Result<MimeBundle, String> {
+        if call.args.is_empty() {
+            return Err("Usage: %decode_address <bech32|hex>".to_string());
+        }
+        kernel
+            .eval
+            .decode_address(call.args)
+            .map(|address| text_bundle(address.to_string()))
+            .map_err(format_evaluation_error_in_task)
+    }
+}
+
+register!(DecodeAddress);
+
+struct ToData;
+
+impl Magic for ToData {
+    fn name(&self) -> &str {
+        "to_data"
+    }
+
+    fn flavor(&self) -> Flavor {
+        Flavor::Line
+    }
+
+    fn help(&self) -> &str {
+        "`%to_data [type] <json|cbor>`: show data given as JSON or CBOR, as a value of `type`"
+    }
+
+    fn execute(&self, kernel: &mut KernelState, call: &Invocation) -> Result<MimeBundle, String> {
+        if call.args.is_empty() {
+            return Err("Usage: %to_data [type] <json|cbor>".to_string());
+        }
+        kernel
+            .eval
+            .to_data(call.args)
+            .map(|converted| text_bundle(converted.to_string()))
+            .map_err(format_evaluation_error_in_task)
+    }
+}
+
+register!(ToData);
+
+struct FromData;
+
+impl Magic for FromData {
+    fn name(&self) -> &str {
+        "from_data"
+    }
+
+    fn flavor(&self) -> Flavor {
+        Flavor::Line
+    }
+
+    fn help(&self) -> &str {
+        "`%from_data <expr>`: show the data of a value, as JSON and CBOR"
+    }
+
+    fn execute(&self, kernel: &mut KernelState, call: &Invocation) -> Result<MimeBundle, String> {
+        if call.args.is_empty() {
+            return Err("Usage: %from_data <expr>".to_string());
+        }
+        kernel
+            .eval
+            .from_data(call.args)
+            .map(|converted| text_bundle(converted.to_string()))
+            .map_err(format_evaluation_error_in_task)
+    }
+}
+
+register!(FromData);
+
+struct Simulate;
+
+impl Magic for Simulate {
+    fn name(&self) -> &str {
+        "simulate"
+    }
+
+    fn flavor(&self) -> Flavor {
+        Flavor::Line
+    }
+
+    fn help(&self) -> &str {
+        "`%simulate <tx> [--inputs <utxos.json>] [--network <name>]`: run the scripts of a transaction, with their budgets and traces"
+    }
+
+    fn execute(&self, kernel: &mut KernelState, call: &Invocation) -> Result<MimeBundle, String> {
+        if call.args.is_empty() {
+            return Err(
+                "Usage: %simulate <tx> [--inputs <utxos.json>] [--network <name>]".to_string(),
+            );
+        }
+        kernel
+            .eval
+            .simulate(call.args)
+            .map(|simulation| text_bundle(simulation.to_string()))
+            .map_err(format_evaluation_error_in_task)
+    }
+}
+
+register!(Simulate);
+
+struct Keygen;
+
+impl Magic for Keygen {
+    fn name(&self) -> &str {
+        "keygen"
+    }
+
+    fn flavor(&self) -> Flavor {
+        Flavor::Line
+    }
+
+    fn help(&self) -> &str {
+        "`%keygen <name>`: generate a key pair, defining `<name>_vk` and `<name>_vkh`"
+    }
+
+    fn execute(&self, kernel: &mut KernelState, call: &Invocation) -> Result<MimeBundle, String> {
+        if call.args.is_empty() {
+            return Err("Usage: %keygen <name>".to_string());
+        }
+        kernel
+            .eval
+            .keygen(call.args)
+            .map(|key| text_bundle(key.to_string()))
+            .map_err(format_evaluation_error_in_task)
+    }
+}
+
+register!(Keygen);
+
+struct Keys;
+
+impl Magic for Keys {
+    fn name(&self) -> &str {
+        "keys"
+    }
+
+    fn flavor(&self) -> Flavor {
+        Flavor::Line
+    }
+
+    fn help(&self) -> &str {
+        "`%keys`: list the keys generated in the session"
+    }
+
+    fn execute(&self, kernel: &mut KernelState, _call: &Invocation) -> Result<MimeBundle, String> {
+        let keys: Vec<String> = kernel.eval.keys().map(|key| key.to_string()).collect();
+        Ok(text_bundle(if keys.is_empty() {
+            "No keys, generate one with %keygen <name>".to_string()
+        } else {
+            keys.join("\n")
+        }))
+    }
+}
+
+register!(Keys);
+
+struct Sign;
+
+impl Magic for Sign {
+    fn name(&self) -> &str {
+        "sign"
+    }
+
+    fn flavor(&self) -> Flavor {
+        Flavor::Line
+    }
+
+    fn help(&self) -> &str {
+        "`%sign <name> <expr>`: sign the bytes of an expression with a key of the session"
+    }
+
+    fn execute(&self, kernel: &mut KernelState, call: &Invocation) -> Result<MimeBundle, String> {
+        let Some((name, code)) = call.args.split_once(char::is_whitespace) else {
+            return Err("Usage: %sign <name> <expr>".to_string());
+        };
+        kernel
+            .eval
+            .sign(name, code.trim())
+            .map(|signature| text_bundle(format!("#\"{}\"", hex::encode(signature))))
+            .map_err(format_evaluation_error_in_task)
+    }
+}
+
+register!(Sign);
+
+/// The chain provider and the mock chain, if they're set up
+fn chain_status(eval: &ReplEvaluator, chain: &Option<MockChain>) -> String {
+    let provider = match eval.chain_network() {
+        Some(network) => format!("Chain provider on {}", network),
+        None => "No chain provider".to_string(),
+    };
+    let mock = match chain {
+        Some(chain) => format!("mock chain with {} UTxOs", chain.utxos().count()),
+        None => "no mock chain".to_string(),
+    };
+    format!("{}, {}", provider, mock)
+}
+
+fn started(chain: &mut Option<MockChain>) -> Result<&mut MockChain, String> {
+    chain
+        .as_mut()
+        .ok_or_else(|| "No mock chain, start one with %chain init".to_string())
+}
+
+/// Submit the transaction `tx` to the mock chain, one clause per line
+fn submit(kernel: &mut KernelState, tx: &str) -> Result<MimeBundle, String> {
+    if tx.is_empty() {
+        return Err(CHAIN_USAGE.to_string());
+    }
+    started(kernel.chain)?
+        .submit(kernel.eval, tx)
+        .map(|submission| text_bundle(submission.to_string()))
+        .map_err(format_evaluation_error_in_task)
+}
+
+struct Chain;
+
+impl Magic for Chain {
+    fn name(&self) -> &str {
+        "chain"
+    }
+
+    fn flavor(&self) -> Flavor {
+        Flavor::Line
+    }
+
+    fn help(&self) -> &str {
+        "`%chain [config <project id> | init | fund <owner> <amount> | utxos | submit <tx>]`: set the Blockfrost project UTxOs come from, or start the mock chain, fund owners, list its UTxOs and submit transactions to it"
+    }
+
+    fn execute(&self, kernel: &mut KernelState, call: &Invocation) -> Result<MimeBundle, String> {
+        let (command, rest) = call
+            .args
+            .split_once(char::is_whitespace)
+            .map(|(command, rest)| (command, rest.trim()))
+            .unwrap_or((call.args, ""));
+        let (eval, chain) = (&mut *kernel.eval, &mut *kernel.chain);
+        match command {
+            "" => Ok(text_bundle(chain_status(eval, chain))),
+            "config" => eval
+                .configure_chain(rest)
+                .map(|()| text_bundle(chain_status(eval, chain)))
+                .map_err(format_evaluation_error_in_task),
+            "init" => {
+                *chain = Some(MockChain::default());
+                Ok(text_bundle("Mock chain started, without UTxOs".to_string()))
+            }
+            "fund" => started(chain)?
+                .fund(eval, rest)
+                .map(|utxo| text_bundle(format!("Created #{}", utxo)))
+                .map_err(format_evaluation_error_in_task),
+            "utxos" => {
+                let utxos: Vec<String> = started(chain)?.utxos().collect();
+                Ok(text_bundle(if utxos.is_empty() {
+                    "No UTxOs".to_string()
+                } else {
+                    utxos.join("\n")
+                }))
+            }
+            // Clauses are separated with `;` on a line
+            "submit" => submit(kernel, rest),
+            _ => Err(CHAIN_USAGE.to_string()),
+        }
+    }
+}
+
+register!(Chain);
+
+struct ChainSubmit;
+
+impl Magic for ChainSubmit {
+    fn name(&self) -> &str {
+        "chain"
+    }
+
+    fn flavor(&self) -> Flavor {
+        Flavor::Cell
+    }
+
+    fn help(&self) -> &str {
+        "`%%chain submit`: submit the transaction of the cell body to the mock chain, one clause per line"
+    }
+
+    fn execute(&self, kernel: &mut KernelState, call: &Invocation) -> Result<MimeBundle, String> {
+        if call.args != "submit" {
+            return Err(CHAIN_USAGE.to_string());
+        }
+        submit(kernel, call.body.trim())
+    }
+}
+
+register!(ChainSubmit);
+
+struct Utxos;
+
+impl Magic for Utxos {
+    fn name(&self) -> &str {
+        "utxos"
+    }
+
+    fn flavor(&self) -> Flavor {
+        Flavor::Line
+    }
+
+    fn help(&self) -> &str {
+        "`%utxos <address>`: list the UTxOs at an address, from the chain provider"
+    }
+
+    fn execute(&self, kernel: &mut KernelState, call: &Invocation) -> Result<MimeBundle, String> {
+        if call.args.is_empty() {
+            return Err("Usage: %utxos <address>".to_string());
+        }
+        kernel
+            .eval
+            .utxos(call.args)
+            .map(|utxos| {
+                if utxos.is_empty() {
+                    return text_bundle("No UTxOs at this address".to_string());
+                }
+                let utxos: Vec<String> = utxos.iter().map(|utxo| utxo.to_string()).collect();
+                text_bundle(utxos.join("\n"))
+            })
+            .map_err(format_evaluation_error_in_task)
+    }
+}
+
+register!(Utxos);
@@ -0,0 +1,457 @@
+//! Magics reading and changing the definitions of the session
+
+use std::{fs, path::Path};
+
+use iaiken_magics::{Flavor, Invocation, KernelState, Magic, register};
+use jupyter_protocol::{MimeBundle, shell::execute::Payload};
+
+use crate::eval::format_evaluation_error_in_task;
+use crate::messages::{depgraph_bundle, text_bundle};
+
+struct Doc;
+
+impl Magic for Doc {
+    fn name(&self) -> &str {
+        "doc"
+    }
+
+    fn flavor(&self) -> Flavor {
+        Flavor::Line
+    }
+
+    fn help(&self) -> &str {
+        "`%doc <name>`: show docs and signature of a definition, of the session or of the stdlib"
+    }
+
+    fn execute(&self, kernel: &mut KernelState, call: &Invocation) -> Result<MimeBundle, String> {
+        if call.args.is_empty() {
+            return Err("Usage: %doc <name>".to_string());
+        }
+        match kernel.eval.doc(call.args) {
+            Ok(Some(entry)) => Ok(text_bundle(entry.to_string())),
+            Ok(None) => Err(format!("No definition named `{}` in scope", call.args)),
+            Err(e) => Err(format_evaluation_error_in_task(e)),
+        }
+    }
+}
+
+register!(Doc);
+
+struct Format;
+
+impl Magic for Format {
+    fn name(&self) -> &str {
+        "format"
+    }
+
+    fn flavor(&self) -> Flavor {
+        Flavor::Line
+    }
+
+    fn help(&self) -> &str {
+        "`%format [code]`: format the given code, or the whole context"
+    }
+
+    fn execute(&self, kernel: &mut KernelState, call: &Invocation) -> Result<MimeBundle, String> {
+        match call.args {
+            "" => kernel.eval.format_context(),
+            code => kernel.eval.format(code),
+        }
+        .map(text_bundle)
+        .map_err(format_evaluation_error_in_task)
+    }
+}
+
+register!(Format);
+
+struct AutoFormat;
+
+impl Magic for AutoFormat {
+    fn name(&self) -> &str {
+        "autoformat"
+    }
+
+    fn flavor(&self) -> Flavor {
+        Flavor::Line
+    }
+
+    fn help(&self) -> &str {
+        "`%autoformat on|off`: format definitions before storing them"
+    }
+
+    fn defines(&self, _call: &Invocation) -> bool {
+        true
+    }
+
+    fn execute(&self, kernel: &mut KernelState, call: &Invocation) -> Result<MimeBundle, String> {
+        match call.args {
+            "on" => kernel.eval.set_auto_format(true),
+            "off" => kernel.eval.set_auto_format(false),
+            _ => return Err("Usage: %autoformat on|off".to_string()),
+        }
+        Ok(text_bundle(format!("Auto-format {}", call.args)))
+    }
+}
+
+register!(AutoFormat);
+
+struct Edit;
+
+impl Magic for Edit {
+    fn name(&self) -> &str {
+        "edit"
+    }
+
+    fn flavor(&self) -> Flavor {
+        Flavor::Line
+    }
+
+    fn help(&self) -> &str {
+        "`%edit`: show the definitions of the context, to copy into a `%%edit` cell"
+    }
+
+    fn execute(&self, kernel: &mut KernelState, _call: &Invocation) -> Result<MimeBundle, String> {
+        let definitions = kernel.eval.definitions().trim();
+        Ok(text_bundle(if definitions.is_empty() {
+            "Empty context".to_string()
+        } else {
+            format!("%%edit\n{}", definitions)
+        }))
+    }
+}
+
+register!(Edit);
+
+struct ReplaceContext;
+
+impl Magic for ReplaceContext {
+    fn name(&self) -> &str {
+        "edit"
+    }
+
+    fn flavor(&self) -> Flavor {
+        Flavor::Cell
+    }
+
+    fn help(&self) -> &str {
+        "`%%edit`: replace the definitions of the context with the cell body"
+    }
+
+    fn defines(&self, _call: &Invocation) -> bool {
+        true
+    }
+
+    fn execute(&self, kernel: &mut KernelState, call: &Invocation) -> Result<MimeBundle, String> {
+        kernel
+            .eval
+            .replace_context(call.body)
+            .map(|()| text_bundle("Context replaced".to_string()))
+            .map_err(format_evaluation_error_in_task)
+    }
+}
+
+register!(ReplaceContext);
+
+struct Reset;
+
+impl Magic for Reset {
+    fn name(&self) -> &str {
+        "reset"
+    }
+
+    fn flavor(&self) -> Flavor {
+        Flavor::Line
+    }
+
+    fn help(&self) -> &str {
+        "`%reset [--hard]`: clear the definitions and cached results, and with `--hard` the execution count and the mock chain too"
+    }
+
+    fn defines(&self, _call: &Invocation) -> bool {
+        true
+    }
+
+    fn execute(&self, kernel: &mut KernelState, call: &Invocation) -> Result<MimeBundle, String> {
+        match call.args {
+            "" => {
+                kernel.eval.reset();
+                Ok(text_bundle("Definitions cleared".to_string()))
+            }
+            "--hard" => {
+                kernel.eval.reset();
+                // The kernel starts the execution count over once the cell is done
+                *kernel.chain = None;
+                Ok(text_bundle(
+                    "Session reset: definitions, cached results, execution count and mock chain cleared"
+                        .to_string(),
+                ))
+            }
+            _ => Err("Usage: %reset [--hard]".to_string()),
+        }
+    }
+}
+
+register!(Reset);
+
+struct Undo;
+
+impl Magic for Undo {
+    fn name(&self) -> &str {
+        "undo"
+    }
+
+    fn flavor(&self) -> Flavor {
+        Flavor::Line
+    }
+
+    fn help(&self) -> &str {
+        "`%undo`: take back the last change to the context"
+    }
+
+    fn defines(&self, _call: &Invocation) -> bool {
+        true
+    }
+
+    fn execute(&self, kernel: &mut KernelState, _call: &Invocation) -> Result<MimeBundle, String> {
+        Ok(text_bundle(match kernel.eval.undo() {
+            Some(changed) => format!("Undone: {}", changed.join(", ")),
+            None => "Nothing to undo".to_string(),
+        }))
+    }
+}
+
+register!(Undo);
+
+struct Forget;
+
+impl Magic for Forget {
+    fn name(&self) -> &str {
+        "forget"
+    }
+
+    fn flavor(&self) -> Flavor {
+        Flavor::Line
+    }
+
+    fn help(&self) -> &str {
+        "`%forget <name>`: remove a definition, or an import, from the context"
+    }
+
+    fn defines(&self, _call: &Invocation) -> bool {
+        true
+    }
+
+    fn execute(&self, kernel: &mut KernelState, call: &Invocation) -> Result<MimeBundle, String> {
+        let name = call.args;
+        if name.is_empty() {
+            return Err("Usage: %forget <name>".to_string());
+        }
+        match kernel.eval.forget(name) {
+            Ok(forgotten) if forgotten.is_empty() => {
+                Err(format!("No definition named `{}` in scope", name))
+            }
+            Ok(forgotten) => Ok(text_bundle(format!("Forgot {}", forgotten.join(", ")))),
+            Err(e) => Err(format!(
+                "{}\nOther definitions depend on `{}`, context unchanged",
+                format_evaluation_error_in_task(e),
+                name
+            )),
+        }
+    }
+}
+
+register!(Forget);
+
+struct Checkpoint;
+
+impl Magic for Checkpoint {
+    fn name(&self) -> &str {
+        "checkpoint"
+    }
+
+    fn flavor(&self) -> Flavor {
+        Flavor::Line
+    }
+
+    fn help(&self) -> &str {
+        "`%checkpoint [name]`: save the context and settings as `name`, or list the checkpoints"
+    }
+
+    fn defines(&self, _call: &Invocation) -> bool {
+        true
+    }
+
+    fn execute(&self, kernel: &mut KernelState, call: &Invocation) -> Result<MimeBundle, String> {
+        if call.args.is_empty() {
+            let names: Vec<&str> = kernel.eval.checkpoints().collect();
+            return Ok(text_bundle(if names.is_empty() {
+                "No checkpoints".to_string()
+            } else {
+                names.join("\n")
+            }));
+        }
+        kernel.eval.checkpoint(call.args);
+        Ok(text_bundle(format!("Checkpoint `{}` saved", call.args)))
+    }
+}
+
+register!(Checkpoint);
+
+struct Rollback;
+
+impl Magic for Rollback {
+    fn name(&self) -> &str {
+        "rollback"
+    }
+
+    fn flavor(&self) -> Flavor {
+        Flavor::Line
+    }
+
+    fn help(&self) -> &str {
+        "`%rollback <name>`: restore the context and settings saved as `name`"
+    }
+
+    fn defines(&self, _call: &Invocation) -> bool {
+        true
+    }
+
+    fn execute(&self, kernel: &mut KernelState, call: &Invocation) -> Result<MimeBundle, String> {
+        if call.args.is_empty() {
+            return Err("Usage: %rollback <name>".to_string());
+        }
+        kernel
+            .eval
+            .rollback(call.args)
+            .map(|()| text_bundle(format!("Rolled back to `{}`", call.args)))
+            .map_err(format_evaluation_error_in_task)
+    }
+}
+
+register!(Rollback);
+
+struct Recover;
+
+impl Magic for Recover {
+    fn name(&self) -> &str {
+        "recover"
+    }
+
+    fn flavor(&self) -> Flavor {
+        Flavor::Line
+    }
+
+    fn help(&self) -> &str {
+        "`%recover`: replay the cells that changed the definitions of the last session, to restore it after the kernel crashed or was restarted"
+    }
+
+    // The log of the last session belongs to the kernel, which replays it before magics run
+    fn execute(&self, _kernel: &mut KernelState, _call: &Invocation) -> Result<MimeBundle, String> {
+        Err("%recover is only available in a notebook".to_string())
+    }
+}
+
+register!(Recover);
+
+struct Export;
+
+impl Magic for Export {
+    fn name(&self) -> &str {
+        "export"
+    }
+
+    fn flavor(&self) -> Flavor {
+        Flavor::Line
+    }
+
+    fn help(&self) -> &str {
+        "`%export <path>`: write the context to a module (`.ak`) or to a new project"
+    }
+
+    fn execute(&self, kernel: &mut KernelState, call: &Invocation) -> Result<MimeBundle, String> {
+        if call.args.is_empty() {
+            return Err("Usage: %export <file.ak|directory>".to_string());
+        }
+        kernel
+            .eval
+            .export(Path::new(call.args))
+            .map(|files| {
+                let written: Vec<String> = files
+                    .iter()
+                    .map(|file| format!("Wrote {}", file.display()))
+                    .collect();
+                text_bundle(written.join("\n"))
+            })
+            .map_err(format_evaluation_error_in_task)
+    }
+}
+
+register!(Export);
+
+struct Load;
+
+impl Magic for Load {
+    fn name(&self) -> &str {
+        "load"
+    }
+
+    fn flavor(&self) -> Flavor {
+        Flavor::Line
+    }
+
+    fn help(&self) -> &str {
+        "`%load <file.ak>`: replace the cell with the content of a file"
+    }
+
+    // The file goes in the reply, leaving the cell without output
+    fn execute(&self, kernel: &mut KernelState, call: &Invocation) -> Result<MimeBundle, String> {
+        let path = call.args;
+        if path.is_empty() {
+            return Err("Usage: %load <file.ak>".to_string());
+        }
+        let text =
+            fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+        kernel.payload.push(Payload::SetNextInput {
+            text: text.trim_end().to_string(),
+            replace: true,
+        });
+        Ok(MimeBundle::new())
+    }
+}
+
+register!(Load);
+
+struct DepGraph;
+
+impl Magic for DepGraph {
+    fn name(&self) -> &str {
+        "depgraph"
+    }
+
+    fn flavor(&self) -> Flavor {
+        Flavor::Line
+    }
+
+    fn help(&self) -> &str {
+        "`%depgraph [name]`: show which definitions of the session refer to which others, or only those around a definition"
+    }
+
+    fn execute(&self, kernel: &mut KernelState, call: &Invocation) -> Result<MimeBundle, String> {
+        let graph = kernel
+            .eval
+            .dependency_graph()
+            .map_err(format_evaluation_error_in_task)?;
+        let graph = match call.args {
+            "" => graph,
+            name => graph
+                .around(name)
+                .ok_or_else(|| format!("No definition named `{}` in the session", name))?,
+        };
+        if graph.nodes.is_empty() {
+            return Ok(text_bundle("No definitions in the session".to_string()));
+        }
+        Ok(depgraph_bundle(&graph))
+    }
+}
+
+register!(DepGraph);
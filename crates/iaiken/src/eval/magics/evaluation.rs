@@ -0,0 +1,421 @@
+//! Magics evaluating code in other ways than a cell does: compiled, profiled, timed or tested
+
+use aiken_repl::evaluator::{EvaluationResult, FlamegraphArgs, FuzzArgs};
+use iaiken_magics::{Flavor, Invocation, KernelState, Magic, register};
+use jupyter_protocol::{MimeBundle, shell::execute::Payload};
+
+use crate::eval::format_evaluation_error_in_task;
+use crate::messages::{
+    flamegraph_bundle, profile_bundle, result_bundle, text_bundle, timing_bundle,
+};
+
+/// Runs of `%%timeit` when not given with `-r`
+const TIMEIT_REPEATS: u32 = 7;
+
+struct Cbor;
+
+impl Magic for Cbor {
+    fn name(&self) -> &str {
+        "cbor"
+    }
+
+    fn flavor(&self) -> Flavor {
+        Flavor::Line
+    }
+
+    fn help(&self) -> &str {
+        "`%cbor <expr>`: show the CBOR hex and size of an expression compiled to UPLC"
+    }
+
+    fn execute(&self, kernel: &mut KernelState, call: &Invocation) -> Result<MimeBundle, String> {
+        if call.args.is_empty() {
+            return Err("Usage: %cbor <expr>".to_string());
+        }
+        kernel
+            .eval
+            .cbor(call.args)
+            .map(|script| text_bundle(script.to_string()))
+            .map_err(format_evaluation_error_in_task)
+    }
+}
+
+register!(Cbor);
+
+struct Profile;
+
+impl Magic for Profile {
+    fn name(&self) -> &str {
+        "profile"
+    }
+
+    fn flavor(&self) -> Flavor {
+        Flavor::Line
+    }
+
+    fn help(&self) -> &str {
+        "`%profile <expr>`: show which functions and builtins spend the budget of an expression"
+    }
+
+    fn execute(&self, kernel: &mut KernelState, call: &Invocation) -> Result<MimeBundle, String> {
+        if call.args.is_empty() {
+            return Err("Usage: %profile <expr>".to_string());
+        }
+        kernel
+            .eval
+            .profile(call.args)
+            .map(|profile| profile_bundle(&profile))
+            .map_err(format_evaluation_error_in_task)
+    }
+}
+
+register!(Profile);
+
+struct Flamegraph;
+
+impl Magic for Flamegraph {
+    fn name(&self) -> &str {
+        "flamegraph"
+    }
+
+    fn flavor(&self) -> Flavor {
+        Flavor::Line
+    }
+
+    fn help(&self) -> &str {
+        "`%flamegraph [-o <path>] <expr>`: show the call stacks of an expression as a flamegraph, and write them in the collapsed format to `path`"
+    }
+
+    fn execute(&self, kernel: &mut KernelState, call: &Invocation) -> Result<MimeBundle, String> {
+        let args = FlamegraphArgs::parse(call.args)
+            .map_err(|_| "Usage: %flamegraph [-o <path>] <expr>".to_string())?;
+        let profile = kernel
+            .eval
+            .flamegraph(&args)
+            .map_err(format_evaluation_error_in_task)?;
+        // Where the stacks were written, or else the stacks themselves
+        let text = match args.output {
+            Some(output) => format!("Wrote {}", output),
+            None => profile.folded(),
+        };
+        Ok(flamegraph_bundle(&profile, text))
+    }
+}
+
+register!(Flamegraph);
+
+struct Check;
+
+impl Magic for Check {
+    fn name(&self) -> &str {
+        "check"
+    }
+
+    fn flavor(&self) -> Flavor {
+        Flavor::Line
+    }
+
+    fn help(&self) -> &str {
+        "`%check`: check the whole session as `aiken check` does, with all errors and warnings"
+    }
+
+    fn execute(&self, kernel: &mut KernelState, _call: &Invocation) -> Result<MimeBundle, String> {
+        kernel
+            .eval
+            .check()
+            .map(|report| text_bundle(report.to_string()))
+            .map_err(format_evaluation_error_in_task)
+    }
+}
+
+register!(Check);
+
+struct Fuzz;
+
+impl Magic for Fuzz {
+    fn name(&self) -> &str {
+        "fuzz"
+    }
+
+    fn flavor(&self) -> Flavor {
+        Flavor::Line
+    }
+
+    fn help(&self) -> &str {
+        "`%fuzz <fn> with <fuzzer> satisfying <property> [--max-success <n>] [--seed <n>]`: check `property(fn, input)` on inputs drawn from `fuzzer`, with a shrunk counterexample on failure"
+    }
+
+    fn execute(&self, kernel: &mut KernelState, call: &Invocation) -> Result<MimeBundle, String> {
+        let args = FuzzArgs::parse(call.args).map_err(|_| {
+            "Usage: %fuzz <fn> with <fuzzer> satisfying <property> [--max-success <n>] [--seed <n>]"
+                .to_string()
+        })?;
+        kernel
+            .eval
+            .fuzz(&args)
+            .map(|report| result_bundle(&EvaluationResult::TestReport(report)))
+            .map_err(format_evaluation_error_in_task)
+    }
+}
+
+register!(Fuzz);
+
+struct UplcDump;
+
+impl Magic for UplcDump {
+    fn name(&self) -> &str {
+        "uplc"
+    }
+
+    fn flavor(&self) -> Flavor {
+        Flavor::Line
+    }
+
+    fn help(&self) -> &str {
+        "`%uplc [--annotated] <expr>`: show the UPLC an expression compiles to, with the lines of the definitions its functions come from when annotated"
+    }
+
+    fn execute(&self, kernel: &mut KernelState, call: &Invocation) -> Result<MimeBundle, String> {
+        let (annotated, code) = match call.args.strip_prefix("--annotated") {
+            Some(code) => (true, code.trim()),
+            None => (false, call.args),
+        };
+        if code.is_empty() {
+            return Err("Usage: %uplc [--annotated] <expr>".to_string());
+        }
+        kernel
+            .eval
+            .compile_to_uplc(code)
+            .map(|dump| {
+                text_bundle(if annotated {
+                    dump.annotated()
+                } else {
+                    dump.to_string()
+                })
+            })
+            .map_err(format_evaluation_error_in_task)
+    }
+}
+
+register!(UplcDump);
+
+struct Uplc;
+
+impl Magic for Uplc {
+    fn name(&self) -> &str {
+        "uplc"
+    }
+
+    fn flavor(&self) -> Flavor {
+        Flavor::Cell
+    }
+
+    fn help(&self) -> &str {
+        "`%%uplc`: evaluate the cell body as an untyped Plutus Core program"
+    }
+
+    fn execute(&self, kernel: &mut KernelState, call: &Invocation) -> Result<MimeBundle, String> {
+        let program = call.body.trim();
+        if program.is_empty() {
+            return Err("%%uplc needs a program to evaluate".to_string());
+        }
+        kernel
+            .eval
+            .eval_uplc(program)
+            .map(|result| text_bundle(result.to_string()))
+            .map_err(format_evaluation_error_in_task)
+    }
+}
+
+register!(Uplc);
+
+struct Probe;
+
+impl Magic for Probe {
+    fn name(&self) -> &str {
+        "probe"
+    }
+
+    fn flavor(&self) -> Flavor {
+        Flavor::Line
+    }
+
+    fn help(&self) -> &str {
+        "`%probe <expr>|off`: compare size and budget of an expression when redefining functions"
+    }
+
+    fn execute(&self, kernel: &mut KernelState, call: &Invocation) -> Result<MimeBundle, String> {
+        match call.args {
+            "" => Err("Usage: %probe <expr>|off".to_string()),
+            "off" => {
+                kernel.eval.clear_probe();
+                Ok(text_bundle("Probe off".to_string()))
+            }
+            code => kernel
+                .eval
+                .set_probe(code)
+                .map(|measure| text_bundle(format!("Probe set: {}", measure)))
+                .map_err(format_evaluation_error_in_task),
+        }
+    }
+}
+
+register!(Probe);
+
+struct Timeit;
+
+impl Magic for Timeit {
+    fn name(&self) -> &str {
+        "timeit"
+    }
+
+    fn flavor(&self) -> Flavor {
+        Flavor::Cell
+    }
+
+    fn help(&self) -> &str {
+        "`%%timeit [-n loops] [-r runs]`: time the evaluation of the expression in the cell body"
+    }
+
+    fn execute(&self, kernel: &mut KernelState, call: &Invocation) -> Result<MimeBundle, String> {
+        let body = call.body.trim();
+        if body.is_empty() {
+            return Err("%%timeit needs an expression to time".to_string());
+        }
+        let (loops, repeats) = parse_timeit_args(call.args)?;
+        kernel
+            .eval
+            .timeit(body, loops, repeats)
+            .map(|timing| timing_bundle(&timing))
+            .map_err(format_evaluation_error_in_task)
+    }
+}
+
+register!(Timeit);
+
+/// Parse the `-n loops` and `-r runs` options of `%%timeit`
+fn parse_timeit_args(args: &str) -> Result<(Option<u32>, u32), String> {
+    let usage = || "Usage: %%timeit [-n loops] [-r runs]".to_string();
+    let mut loops = None;
+    let mut repeats = TIMEIT_REPEATS;
+
+    let mut args = args.split_whitespace();
+    while let Some(flag) = args.next() {
+        let value = args
+            .next()
+            .and_then(|value| value.parse::<u32>().ok())
+            .filter(|value| *value > 0)
+            .ok_or_else(usage)?;
+        match flag {
+            "-n" => loops = Some(value),
+            "-r" => repeats = value,
+            _ => return Err(usage()),
+        }
+    }
+    Ok((loops, repeats))
+}
+
+/// Evaluate without the display limits, to show a giant result whole
+fn full_output(kernel: &mut KernelState, code: &str) -> Result<MimeBundle, String> {
+    if code.is_empty() {
+        return Err(
+            "Usage: %full_output <code>, or %%full_output followed by the code".to_string(),
+        );
+    }
+    kernel
+        .eval
+        .eval_full(code)
+        .map(|result| result_bundle(&result))
+        .map_err(format_evaluation_error_in_task)
+}
+
+struct FullOutput;
+
+impl Magic for FullOutput {
+    fn name(&self) -> &str {
+        "full_output"
+    }
+
+    fn flavor(&self) -> Flavor {
+        Flavor::Line
+    }
+
+    fn help(&self) -> &str {
+        "`%full_output <code>`: evaluate without the display limits, to show a giant result whole"
+    }
+
+    fn execute(&self, kernel: &mut KernelState, call: &Invocation) -> Result<MimeBundle, String> {
+        full_output(kernel, call.args)
+    }
+}
+
+register!(FullOutput);
+
+struct FullOutputCell;
+
+impl Magic for FullOutputCell {
+    fn name(&self) -> &str {
+        "full_output"
+    }
+
+    fn flavor(&self) -> Flavor {
+        Flavor::Cell
+    }
+
+    fn help(&self) -> &str {
+        "`%%full_output`: evaluate the cell body without the display limits"
+    }
+
+    fn execute(&self, kernel: &mut KernelState, call: &Invocation) -> Result<MimeBundle, String> {
+        full_output(kernel, call.body.trim())
+    }
+}
+
+register!(FullOutputCell);
+
+struct Page;
+
+impl Magic for Page {
+    fn name(&self) -> &str {
+        "page"
+    }
+
+    fn flavor(&self) -> Flavor {
+        Flavor::Line
+    }
+
+    fn help(&self) -> &str {
+        "`%page <expr>`: show the whole result of an expression in the pager, out of the way of the notebook"
+    }
+
+    // The result goes in the reply, leaving the cell without output
+    fn execute(&self, kernel: &mut KernelState, call: &Invocation) -> Result<MimeBundle, String> {
+        if call.args.is_empty() {
+            return Err("Usage: %page <expr>".to_string());
+        }
+        let result = kernel
+            .eval
+            .eval_full(call.args)
+            .map_err(format_evaluation_error_in_task)?;
+        kernel.payload.push(Payload::Page {
+            data: result_bundle(&result),
+            start: 0,
+        });
+        Ok(MimeBundle::new())
+    }
+}
+
+register!(Page);
+
+#[cfg(test)]
+mod test {
+    use super::{TIMEIT_REPEATS, parse_timeit_args};
+
+    #[test]
+    fn test_parse_timeit_args() {
+        assert_eq!(parse_timeit_args(""), Ok((None, TIMEIT_REPEATS)));
+        assert_eq!(parse_timeit_args("-n 100 -r 3"), Ok((Some(100), 3)));
+        assert!(parse_timeit_args("-n").is_err());
+        assert!(parse_timeit_args("-n 0").is_err());
+        assert!(parse_timeit_args("-x 1").is_err());
+    }
+}
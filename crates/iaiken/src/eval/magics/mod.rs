@@ -0,0 +1,192 @@
+//! Line magics (`%name args`) and cell magics (`%%name args` followed by the cell body) that
+//! the kernel handles instead of evaluating as Aiken code
+//!
+//! Each magic of the kernel implements `iaiken_magics::Magic` and is registered with
+//! `iaiken_magics::register!`, as those of plugins are. Its help lives in its `help`, and whether
+//! `%recover` and `--export-notebook` replay it in its `defines`.
+
+use std::sync::LazyLock;
+
+use iaiken_magics::{Flavor, Magic, Registry};
+use jupyter_protocol::MimeBundle;
+
+use crate::messages::magics_bundle;
+
+mod chain;
+mod context;
+mod evaluation;
+mod settings;
+
+/// The magics of the kernel and of the plugins it's built with
+pub fn registry() -> &'static Registry {
+    static REGISTRY: LazyLock<Registry> = LazyLock::new(|| {
+        let mut registry = Registry::default();
+        for magic in iaiken_magics::registered() {
+            let name = format!("{}{}", magic.flavor().prefix(), magic.name());
+            if registry.register(magic).is_some() {
+                eprintln!("Magic {name} is registered more than once, keeping one of them");
+            }
+        }
+        registry
+    });
+    &REGISTRY
+}
+
+/// `%help [name]` and `%name?`: the magics called `name`, with the flavor of its `%` or `%%`
+/// prefix if it has one, or every magic when it's empty
+pub fn help(flavor: Option<Flavor>, name: &str) -> Result<MimeBundle, String> {
+    let (flavor, name) = match name.strip_prefix("%%") {
+        Some(name) => (Some(Flavor::Cell), name),
+        None => match name.strip_prefix('%') {
+            Some(name) => (Some(Flavor::Line), name),
+            None => (flavor, name),
+        },
+    };
+    let magics: Vec<&dyn Magic> = registry()
+        .iter()
+        .filter(|magic| {
+            name.is_empty()
+                || (magic.name() == name && flavor.is_none_or(|flavor| magic.flavor() == flavor))
+        })
+        .collect();
+    if magics.is_empty() {
+        return Err(format!("No magic named `{}`, see %lsmagic", name));
+    }
+    Ok(magics_bundle(&magics))
+}
+
+#[cfg(test)]
+mod test {
+    use aiken_repl::evaluator::ReplEvaluator;
+    use iaiken_magics::{Flavor, Invocation, KernelState};
+
+    use super::{help, registry};
+    use crate::messages::bundle_text;
+
+    /// Run `code` as a magic on a fresh session
+    fn execute(code: &str) -> Result<String, String> {
+        let call = Invocation::parse(code).unwrap();
+        let mut eval = ReplEvaluator::new();
+        let mut kernel = KernelState {
+            eval: &mut eval,
+            chain: &mut None,
+            payload: Vec::new(),
+        };
+        let magic = registry().find(&call)?;
+        magic
+            .execute(&mut kernel, &call)
+            .map(|bundle| bundle_text(&bundle).to_string())
+    }
+
+    #[test]
+    fn test_registry() {
+        let line = [
+            "doc",
+            "format",
+            "autoformat",
+            "gc",
+            "cache",
+            "set",
+            "show",
+            "seed",
+            "warnings",
+            "depgraph",
+            "deterministic",
+            "cbor",
+            "profile",
+            "flamegraph",
+            "check",
+            "fuzz",
+            "uplc",
+            "probe",
+            "edit",
+            "reset",
+            "undo",
+            "forget",
+            "checkpoint",
+            "rollback",
+            "recover",
+            "export",
+            "decode_address",
+            "to_data",
+            "from_data",
+            "simulate",
+            "keygen",
+            "keys",
+            "sign",
+            "chain",
+            "utxos",
+            "page",
+            "load",
+            "version",
+            "aiken_version",
+            "timings",
+            "lsmagic",
+            "help",
+            "full_output",
+        ];
+        let cell = ["timeit", "uplc", "edit", "chain", "full_output"];
+        for name in line {
+            assert!(registry().get(Flavor::Line, name).is_some(), "%{}", name);
+        }
+        for name in cell {
+            assert!(registry().get(Flavor::Cell, name).is_some(), "%%{}", name);
+        }
+        assert_eq!(registry().iter().count(), line.len() + cell.len());
+        assert_eq!(
+            registry().find(&Invocation::parse("%nope").unwrap()).err(),
+            Some("Unknown magic: %nope".to_string())
+        );
+
+        // Only the magics changing the definitions are replayed
+        let reset = Invocation::parse("%reset --hard").unwrap();
+        assert!(registry().find(&reset).unwrap().defines(&reset));
+        let edit = Invocation::parse("%%edit\npub const x = 1").unwrap();
+        assert!(registry().find(&edit).unwrap().defines(&edit));
+        let doc = Invocation::parse("%doc add").unwrap();
+        assert!(!registry().find(&doc).unwrap().defines(&doc));
+    }
+
+    #[test]
+    fn test_usage() {
+        assert_eq!(execute("%doc"), Err("Usage: %doc <name>".to_string()));
+        assert_eq!(
+            execute("%%timeit -n 10\n"),
+            Err("%%timeit needs an expression to time".to_string())
+        );
+        assert_eq!(
+            execute("%%timeit -x 1\n1 + 2"),
+            Err("Usage: %%timeit [-n loops] [-r runs]".to_string())
+        );
+        assert!(
+            execute("%%chain fund\n")
+                .unwrap_err()
+                .starts_with("Usage: %chain")
+        );
+        assert_eq!(
+            execute("%chain utxos"),
+            Err("No mock chain, start one with %chain init".to_string())
+        );
+        assert_eq!(
+            execute("%deterministic maybe"),
+            Err("Usage: %deterministic on|off".to_string())
+        );
+        assert_eq!(
+            execute("%recover"),
+            Err("%recover is only available in a notebook".to_string())
+        );
+    }
+
+    #[test]
+    fn test_help() {
+        let all = help(None, "").unwrap();
+        assert!(bundle_text(&all).contains("%%timeit [-n loops] [-r runs]"));
+        // The line and cell magics of the name, or one of them
+        let uplc = help(None, "uplc").unwrap();
+        assert_eq!(bundle_text(&uplc).matches("uplc").count(), 2);
+        let cell = help(Some(Flavor::Line), "%%uplc").unwrap();
+        assert!(bundle_text(&cell).contains("%%uplc"));
+        assert!(!bundle_text(&cell).contains("--annotated"));
+        assert!(help(None, "nope").is_err());
+    }
+}
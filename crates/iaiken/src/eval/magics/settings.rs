@@ -0,0 +1,346 @@
+//! Magics showing and changing how the session evaluates, and what the kernel is made of
+
+use aiken_repl::evaluator::Compiler;
+use iaiken_magics::{Flavor, Invocation, KernelState, Magic, register};
+use jupyter_protocol::MimeBundle;
+
+use super::help;
+use crate::eval::format_evaluation_error_in_task;
+use crate::messages::{kernel_info::version_report, text_bundle};
+
+struct Set;
+
+impl Magic for Set {
+    fn name(&self) -> &str {
+        "set"
+    }
+
+    fn flavor(&self) -> Flavor {
+        Flavor::Line
+    }
+
+    fn help(&self) -> &str {
+        "`%set [option value]`: show or change options"
+    }
+
+    fn execute(&self, kernel: &mut KernelState, call: &Invocation) -> Result<MimeBundle, String> {
+        if call.args.is_empty() {
+            return Ok(text_bundle(kernel.eval.options()));
+        }
+        let Some((name, value)) = call.args.split_once(char::is_whitespace) else {
+            return Err("Usage: %set <option> <value>".to_string());
+        };
+        kernel
+            .eval
+            .set_option(name, value.trim())
+            .map(|()| text_bundle(format!("{} = {}", name, value.trim())))
+            .map_err(format_evaluation_error_in_task)
+    }
+}
+
+register!(Set);
+
+struct Show;
+
+impl Magic for Show {
+    fn name(&self) -> &str {
+        "show"
+    }
+
+    fn flavor(&self) -> Flavor {
+        Flavor::Line
+    }
+
+    fn help(&self) -> &str {
+        "`%show settings`: show every option with its value"
+    }
+
+    fn execute(&self, kernel: &mut KernelState, call: &Invocation) -> Result<MimeBundle, String> {
+        match call.args {
+            "settings" => Ok(text_bundle(kernel.eval.options())),
+            _ => Err("Usage: %show settings".to_string()),
+        }
+    }
+}
+
+register!(Show);
+
+struct Seed;
+
+impl Magic for Seed {
+    fn name(&self) -> &str {
+        "seed"
+    }
+
+    fn flavor(&self) -> Flavor {
+        Flavor::Line
+    }
+
+    fn help(&self) -> &str {
+        "`%seed [<n>|random]`: show or set the seed of property tests, to run them again with the same inputs"
+    }
+
+    fn execute(&self, kernel: &mut KernelState, call: &Invocation) -> Result<MimeBundle, String> {
+        if call.args.is_empty() {
+            return Ok(text_bundle(match kernel.eval.seed() {
+                Some(seed) => format!("seed = {}", seed),
+                None => "seed = random".to_string(),
+            }));
+        }
+        kernel
+            .eval
+            .set_option("seed", call.args)
+            .map(|()| text_bundle(format!("seed = {}", call.args)))
+            .map_err(format_evaluation_error_in_task)
+    }
+}
+
+register!(Seed);
+
+struct Warnings;
+
+impl Magic for Warnings {
+    fn name(&self) -> &str {
+        "warnings"
+    }
+
+    fn flavor(&self) -> Flavor {
+        Flavor::Line
+    }
+
+    fn help(&self) -> &str {
+        "`%warnings on|off`: show compiler warnings of the cells"
+    }
+
+    fn execute(&self, kernel: &mut KernelState, call: &Invocation) -> Result<MimeBundle, String> {
+        match call.args {
+            "on" => kernel.eval.set_warnings(true),
+            "off" => kernel.eval.set_warnings(false),
+            _ => return Err("Usage: %warnings on|off".to_string()),
+        }
+        Ok(text_bundle(format!("Warnings {}", call.args)))
+    }
+}
+
+register!(Warnings);
+
+struct Deterministic;
+
+impl Magic for Deterministic {
+    fn name(&self) -> &str {
+        "deterministic"
+    }
+
+    fn flavor(&self) -> Flavor {
+        Flavor::Line
+    }
+
+    fn help(&self) -> &str {
+        "`%deterministic on|off`: leave timings, progress and temp paths out of the outputs, to validate notebooks against saved outputs"
+    }
+
+    fn execute(&self, kernel: &mut KernelState, call: &Invocation) -> Result<MimeBundle, String> {
+        let setting @ ("on" | "off") = call.args else {
+            return Err("Usage: %deterministic on|off".to_string());
+        };
+        kernel
+            .eval
+            .set_option("deterministic", setting)
+            .map(|()| text_bundle(format!("Deterministic output {}", setting)))
+            .map_err(format_evaluation_error_in_task)
+    }
+}
+
+register!(Deterministic);
+
+struct Cache;
+
+impl Magic for Cache {
+    fn name(&self) -> &str {
+        "cache"
+    }
+
+    fn flavor(&self) -> Flavor {
+        Flavor::Line
+    }
+
+    fn help(&self) -> &str {
+        "`%cache on|off|clear`: reuse results of re-run expressions, or remove the build artifacts shared by sessions"
+    }
+
+    fn execute(&self, kernel: &mut KernelState, call: &Invocation) -> Result<MimeBundle, String> {
+        match call.args {
+            "on" => kernel.eval.set_cache(true),
+            "off" => kernel.eval.set_cache(false),
+            "clear" => {
+                return kernel
+                    .eval
+                    .clear_artifact_cache()
+                    .map(|freed| {
+                        text_bundle(format!(
+                            "Freed {} KiB of shared build artifacts",
+                            freed / 1024
+                        ))
+                    })
+                    .map_err(format_evaluation_error_in_task);
+            }
+            _ => return Err("Usage: %cache on|off|clear".to_string()),
+        }
+        Ok(text_bundle(format!("Cache {}", call.args)))
+    }
+}
+
+register!(Cache);
+
+struct Gc;
+
+impl Magic for Gc {
+    fn name(&self) -> &str {
+        "gc"
+    }
+
+    fn flavor(&self) -> Flavor {
+        Flavor::Line
+    }
+
+    fn help(&self) -> &str {
+        "`%gc`: remove build artifacts of the temp project"
+    }
+
+    fn execute(&self, kernel: &mut KernelState, _call: &Invocation) -> Result<MimeBundle, String> {
+        kernel
+            .eval
+            .gc()
+            .map(|freed| text_bundle(format!("Freed {} KiB of build artifacts", freed / 1024)))
+            .map_err(format_evaluation_error_in_task)
+    }
+}
+
+register!(Gc);
+
+struct Version;
+
+impl Magic for Version {
+    fn name(&self) -> &str {
+        "version"
+    }
+
+    fn flavor(&self) -> Flavor {
+        Flavor::Line
+    }
+
+    fn help(&self) -> &str {
+        "`%version`: show the versions of the kernel, Aiken, Plutus and the Jupyter protocol"
+    }
+
+    fn execute(&self, _kernel: &mut KernelState, _call: &Invocation) -> Result<MimeBundle, String> {
+        Ok(text_bundle(version_report()))
+    }
+}
+
+register!(Version);
+
+struct AikenVersion;
+
+impl Magic for AikenVersion {
+    fn name(&self) -> &str {
+        "aiken_version"
+    }
+
+    fn flavor(&self) -> Flavor {
+        Flavor::Line
+    }
+
+    fn help(&self) -> &str {
+        "`%aiken_version [<version>|<path>|builtin]`: show the compiler the session evaluates with, or switch to a pinned `aiken` binary, or back to the built-in compiler"
+    }
+
+    fn defines(&self, _call: &Invocation) -> bool {
+        true
+    }
+
+    fn execute(&self, kernel: &mut KernelState, call: &Invocation) -> Result<MimeBundle, String> {
+        let eval = &mut *kernel.eval;
+        if call.args.is_empty() {
+            return Ok(text_bundle(eval.compiler().to_string()));
+        }
+        Compiler::select(call.args)
+            .and_then(|compiler| eval.set_compiler(compiler))
+            .map(|()| text_bundle(format!("Evaluating with {}", eval.compiler())))
+            .map_err(format_evaluation_error_in_task)
+    }
+}
+
+register!(AikenVersion);
+
+struct Timings;
+
+impl Magic for Timings {
+    fn name(&self) -> &str {
+        "timings"
+    }
+
+    fn flavor(&self) -> Flavor {
+        Flavor::Line
+    }
+
+    fn help(&self) -> &str {
+        "`%timings`: show where the time of the last cell of Aiken code went, from parsing to evaluation"
+    }
+
+    fn execute(&self, kernel: &mut KernelState, _call: &Invocation) -> Result<MimeBundle, String> {
+        Ok(text_bundle(
+            kernel
+                .eval
+                .timings()
+                .map(|timings| timings.to_string())
+                .unwrap_or_else(|| "No cell evaluated yet".to_string()),
+        ))
+    }
+}
+
+register!(Timings);
+
+struct Lsmagic;
+
+impl Magic for Lsmagic {
+    fn name(&self) -> &str {
+        "lsmagic"
+    }
+
+    fn flavor(&self) -> Flavor {
+        Flavor::Line
+    }
+
+    fn help(&self) -> &str {
+        "`%lsmagic`: list the magics, with how to call them and what they do"
+    }
+
+    fn execute(&self, _kernel: &mut KernelState, _call: &Invocation) -> Result<MimeBundle, String> {
+        help(None, "")
+    }
+}
+
+register!(Lsmagic);
+
+struct Help;
+
+impl Magic for Help {
+    fn name(&self) -> &str {
+        "help"
+    }
+
+    fn flavor(&self) -> Flavor {
+        Flavor::Line
+    }
+
+    fn help(&self) -> &str {
+        "`%help [name]`: show how to call a magic and what it does, as `%name?` does, or list them all"
+    }
+
+    fn execute(&self, _kernel: &mut KernelState, call: &Invocation) -> Result<MimeBundle, String> {
+        help(None, call.args)
+    }
+}
+
+register!(Help);
@@ -1,82 +1,262 @@
-use aiken_repl::evaluator::{ReplError, ReplEvaluator};
+use aiken_repl::{
+    config::ErrorFormat,
+    evaluator::{Completions, ProgressTx, ReplError, ReplEvaluator},
+};
+use iaiken_magics::{Flavor, Invocation, KernelState};
+use jupyter_protocol::{
+    MimeBundle,
+    reply::{Reply, ReplyError},
+    shell::execute::{Payload, UserExpressionResult, UserExpressionValue},
+};
 use miette::{GraphicalReportHandler, GraphicalTheme};
-use std::sync::{Mutex, OnceLock};
+use std::collections::HashMap;
 
-static EVALUATOR: OnceLock<Mutex<ReplEvaluator>> = OnceLock::new();
+use crate::messages::{ansi, result_bundle, result_metadata, text_bundle};
+use crate::state::{SharedState, lock};
+use worker::EvalWorker;
 
-pub async fn execute_aiken_code(code: &str) -> Result<String, String> {
+mod magics;
+pub mod notebook;
+pub mod variables;
+pub mod worker;
+
+/// Output of a cell
+pub struct CellResult {
+    pub result: Result<MimeBundle, String>,
+    /// Compiler warnings raised by the cell, rendered for display
+    pub warnings: Vec<String>,
+    /// Cells that changed the definitions, to log for `%recover`: the cell itself, or the cells
+    /// it recovered
+    pub definitions: Vec<String>,
+    /// Actions for the frontend, sent with the reply
+    pub payload: Vec<Payload>,
+    /// Metadata of the result, see `result_metadata`
+    pub metadata: serde_json::Value,
+}
+
+impl CellResult {
+    /// A cell that failed before it could run
+    pub fn error(message: impl Into<String>) -> Self {
+        Self {
+            result: Err(message.into()),
+            warnings: Vec::new(),
+            definitions: Vec::new(),
+            payload: Vec::new(),
+            metadata: serde_json::Value::Null,
+        }
+    }
+}
+
+/// Evaluate cell number `execution_count` with `options` changed for it (see `cell_options`).
+/// Compiler events (eg. resolving packages) are reported on `progress`.
+pub async fn execute_aiken_code(
+    evaluator: &EvalWorker,
+    state: &SharedState,
+    code: &str,
+    execution_count: u32,
+    options: Vec<(String, String)>,
+    progress: Option<ProgressTx>,
+) -> CellResult {
     println!("execute_aiken_code with code: {code}");
     let code = code.to_string();
+    let mock_chain = state.mock_chain();
+    let line_magic = Invocation::parse(&code).filter(|call| call.flavor == Flavor::Line);
+    let recover = line_magic.is_some_and(|call| call.name == "recover");
+    let hard_reset = line_magic.is_some_and(|call| call.name == "reset" && call.args == "--hard");
+    let previous = if recover {
+        state.previous_definitions().to_vec()
+    } else {
+        Vec::new()
+    };
 
-    // Eval code making sure I'm propagating all errors
-    let task_result = tokio::task::spawn_blocking(move || {
-        let evaluator = EVALUATOR.get_or_init(|| Mutex::new(ReplEvaluator::new()));
-
-        let mut eval = match evaluator.lock() {
-            Ok(eval) => eval,
-            Err(_) => return Err("Error: Failed to acquire evaluator lock".to_string()),
-        };
+    let cell_result = evaluator
+        .run(move |eval| {
+            let session_options = match eval.set_options(&options) {
+                Ok(session_options) => session_options,
+                Err(e) => return CellResult::error(format_evaluation_error_in_task(e)),
+            };
+            eval.set_progress(progress);
+            eval.set_origin(Some(format!("In[{}]", execution_count)));
+            let before = eval.definitions().to_string();
+            let mut definitions = Vec::new();
+            let mut payload = Vec::new();
+            let mut summary = None;
+            let mut timings = None;
+            // Magics are handled by the kernel instead of being evaluated as Aiken code
+            let mut result = match Invocation::parse(&code) {
+                // Replays the log of the last session, which the kernel keeps
+                Some(_) if recover => {
+                    notebook::recover(eval, &previous).map(|(report, replayed)| {
+                        definitions = replayed;
+                        text_bundle(report)
+                    })
+                }
+                Some(call) if call.name.ends_with('?') => {
+                    magics::help(Some(call.flavor), call.name.trim_end_matches('?'))
+                }
+                Some(call) => {
+                    let mut chain = lock(&mock_chain);
+                    let mut kernel = KernelState {
+                        eval: &mut *eval,
+                        chain: &mut chain,
+                        payload: Vec::new(),
+                    };
+                    let result = magics::registry().find(&call).and_then(|magic| {
+                        let result = magic.execute(&mut kernel, &call);
+                        if result.is_ok() && magic.defines(&call) {
+                            definitions.push(code.clone());
+                        }
+                        result
+                    });
+                    payload = kernel.payload;
+                    result
+                }
+                None => {
+                    let result = eval
+                        .eval(&code)
+                        .map(|r| {
+                            summary = Some(r.summary());
+                            match eval.take_redefinition_report() {
+                                Some(report) => text_bundle(format!("{}\n{}", r, report)),
+                                None => result_bundle(&r),
+                            }
+                        })
+                        .map_err(format_evaluation_error_in_task);
+                    timings = eval.timings().cloned();
+                    // Expressions leave the definitions as they were
+                    if result.is_ok() && eval.definitions() != before {
+                        definitions.push(code.clone());
+                    }
+                    result
+                }
+            };
+            eval.set_progress(None);
+            eval.set_origin(None);
+            let mut warnings = eval.take_warnings();
+            // Outputs that change from one run to the next are left out, and the paths of the
+            // temp project are hidden, so that errors read the same in every session
+            if eval.deterministic() {
+                timings = None;
+                result = match result {
+                    Ok(bundle) => Ok(without_paths(eval, bundle)),
+                    Err(e) => Err(eval.without_paths(&e)),
+                };
+                for warning in &mut warnings {
+                    *warning = eval.without_paths(warning);
+                }
+            }
+            let _ = eval.set_options(&session_options);
+            CellResult {
+                result,
+                warnings,
+                definitions,
+                payload,
+                metadata: result_metadata(
+                    summary.as_ref(),
+                    timings.as_ref(),
+                    &eval.compiler().version(),
+                ),
+            }
+        })
+        .await;
 
-        eval.eval(&code)
-            .map(|r| format!("{}", r))
-            .map_err(|e| format_evaluation_error_in_task(e))
-    })
-    .await;
+    let cell_result =
+        cell_result.unwrap_or_else(|| CellResult::error("Error: The evaluation panicked"));
+    // The evaluator and the mock chain were reset by the magic, the count is the kernel's
+    if hard_reset && cell_result.result.is_ok() {
+        state.reset_execution_count();
+    }
+    cell_result
+}
 
-    task_result.map_err(|e| format!("Error: Task panicked: {}", e))?
+/// `bundle` with the paths of the temp project hidden in its texts
+fn without_paths(eval: &ReplEvaluator, bundle: MimeBundle) -> MimeBundle {
+    bundle
+        .into_iter()
+        .map(|(mime, data)| match data {
+            serde_json::Value::String(text) => (mime, eval.without_paths(&text).into()),
+            data => (mime, data),
+        })
+        .collect()
 }
 
+/// Evaluate the `user_expressions` of an execute request, all in one pass
 pub async fn evaluate_user_expressions(
-    expressions: &std::collections::HashMap<String, String>,
-) -> std::collections::HashMap<String, serde_json::Value> {
+    evaluator: &EvalWorker,
+    expressions: HashMap<String, String>,
+    format: ErrorFormat,
+) -> HashMap<String, UserExpressionResult> {
     println!(
         "evaluate_user_expressions with expressions: {:?}",
         expressions
     );
-    let expressions = expressions.clone();
-    let mut results = std::collections::HashMap::new();
 
-    let task_result = tokio::task::spawn_blocking(move || {
-        let evaluator = EVALUATOR.get_or_init(|| Mutex::new(ReplEvaluator::new()));
+    let (names, codes): (Vec<String>, Vec<String>) = expressions.into_iter().unzip();
+    let count = names.len();
+    let results = evaluator
+        .run(move |eval| {
+            let codes: Vec<&str> = codes.iter().map(String::as_str).collect();
+            eval.eval_expressions(&codes)
+                .into_iter()
+                .map(|result| match result {
+                    Ok(result) => Reply::Ok(UserExpressionValue {
+                        data: text_bundle(format!("{}", result)),
+                        metadata: serde_json::Value::Object(serde_json::Map::new()),
+                    }),
+                    Err(e) => {
+                        Reply::Error(aiken_error(&format_evaluation_error_in_task(e), format))
+                    }
+                })
+                .collect()
+        })
+        .await
+        .unwrap_or_else(|| {
+            vec![Reply::Error(aiken_error("Error: The evaluation panicked", format)); count]
+        });
 
-        let mut eval = match evaluator.lock() {
-            Ok(eval) => eval,
-            Err(_) => return results,
-        };
+    names.into_iter().zip(results).collect()
+}
 
-        for (name, expr) in expressions {
-            match eval.eval(&expr) {
-                Ok(result) => {
-                    let display_result = format!("{}", result);
-                    let mut mime_bundle = serde_json::Map::new();
-                    mime_bundle.insert(
-                        "text/plain".to_string(),
-                        serde_json::Value::String(display_result),
-                    );
-                    results.insert(name, serde_json::Value::Object(mime_bundle));
-                }
-                Err(_) => {
-                    // On error, return an error message as text/plain
-                    let mut mime_bundle = serde_json::Map::new();
-                    mime_bundle.insert(
-                        "text/plain".to_string(),
-                        serde_json::Value::String("Error evaluating expression".to_string()),
-                    );
-                    results.insert(name, serde_json::Value::Object(mime_bundle));
-                }
+/// Completions of the word before byte offset `cursor` of `code`, against the definitions of the
+/// session. Names are completed within the line of the cursor, but `start` is an offset in `code`.
+pub async fn complete(evaluator: &EvalWorker, code: String, cursor: usize) -> Completions {
+    let line_start = code[..cursor].rfind('\n').map_or(0, |i| i + 1);
+    evaluator
+        .run(move |eval| {
+            // Code that doesn't type-check has nothing to offer
+            let Ok(candidates) = eval.completion_candidates() else {
+                return Completions::default();
+            };
+            let line_end = code[cursor..].find('\n').map_or(code.len(), |i| cursor + i);
+            let completions = candidates.complete(&code[line_start..line_end], cursor - line_start);
+            Completions {
+                start: line_start + completions.start,
+                matches: completions.matches,
             }
-        }
-
-        results
-    })
-    .await;
+        })
+        .await
+        .unwrap_or_default()
+}
 
-    task_result.unwrap_or_default()
+/// An error of the evaluator, with its first line as value and all its lines as traceback. The
+/// traceback keeps its colors in the ANSI format only.
+pub fn aiken_error(error: &str, format: ErrorFormat) -> ReplyError {
+    let plain = ansi::strip(error);
+    let traceback = match format {
+        ErrorFormat::Ansi => error,
+        ErrorFormat::Plain | ErrorFormat::Html => &plain,
+    };
+    ReplyError {
+        ename: "AikenError".to_string(),
+        evalue: plain.lines().next().unwrap_or("").to_string(),
+        traceback: traceback.lines().map(|line| line.to_string()).collect(),
+    }
 }
 
 fn format_evaluation_error_in_task(error: ReplError) -> String {
     // Create a graphical report handler with colors enabled
-    let handler = GraphicalReportHandler::new().with_theme(GraphicalTheme::default());
+    // Always with colors: they're stripped later for frontends that don't want them
+    let handler = GraphicalReportHandler::new().with_theme(GraphicalTheme::unicode());
 
     // Format the error using miette's rich diagnostic formatting
     // We need to format the error without creating a Report since ReplError
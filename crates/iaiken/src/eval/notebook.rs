@@ -0,0 +1,228 @@
+//! `--export-notebook`: the code cells of a notebook stitched into one checked module, so a
+//! notebook can be the source of truth of the code it defines, and `--run`: a notebook executed
+//! without a frontend, with its outputs written back, to check it in CI
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use aiken_repl::{config::ErrorFormat, evaluator::ReplEvaluator};
+use anyhow::{Context, anyhow};
+use iaiken_magics::{Invocation, KernelState};
+use serde_json::{Value, json};
+
+use super::{CellResult, aiken_error, execute_aiken_code, format_evaluation_error_in_task, magics};
+use crate::messages::{ansi, cell_options};
+use crate::state::SharedState;
+
+/// Run the code cells of `notebook` in order against a fresh evaluator, and write the resulting
+/// definitions to `output` (by default, the notebook's path with an `.ak` extension). Expressions
+/// are left out, and so are magics that don't change the definitions. Returns the path written.
+pub fn export_notebook(notebook: &Path, output: Option<PathBuf>) -> anyhow::Result<PathBuf> {
+    let content = fs::read_to_string(notebook)
+        .with_context(|| format!("Failed to read {}", notebook.display()))?;
+    let cells = code_cells(
+        &serde_json::from_str(&content)
+            .with_context(|| format!("{} is not a notebook", notebook.display()))?,
+    );
+
+    let mut eval = ReplEvaluator::new();
+    for (i, cell) in cells.iter().enumerate() {
+        define(&mut eval, cell)
+            .map_err(|error| anyhow!("Code cell {} failed:\n{}", i + 1, error))?;
+    }
+
+    let code = eval
+        .format_context()
+        .map_err(|error| anyhow!(format_evaluation_error_in_task(error)))?;
+    let output = output.unwrap_or_else(|| notebook.with_extension("ak"));
+    fs::write(&output, format!("{}\n", code))
+        .with_context(|| format!("Failed to write {}", output.display()))?;
+    Ok(output)
+}
+
+/// Sources of the code cells, in order. Notebooks store them either as a string or as a list of
+/// lines.
+fn code_cells(notebook: &Value) -> Vec<String> {
+    notebook["cells"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|cell| cell["cell_type"] == "code")
+        .map(source)
+        .collect()
+}
+
+fn source(cell: &Value) -> String {
+    match &cell["source"] {
+        Value::String(source) => source.clone(),
+        Value::Array(lines) => lines.iter().filter_map(Value::as_str).collect(),
+        _ => String::new(),
+    }
+}
+
+/// `--run`: execute the code cells of `notebook` in order in the session of `state`, as a
+/// frontend would, and write their outputs and execution counts back to it. Options in the
+/// `iaiken` object of a cell's metadata apply to that cell, as for execute requests. Stops at the
+/// first cell that fails, which is an error once the notebook is written. Returns the number of
+/// cells run.
+pub async fn run_notebook(state: &SharedState, notebook: &Path) -> anyhow::Result<usize> {
+    let content = fs::read_to_string(notebook)
+        .with_context(|| format!("Failed to read {}", notebook.display()))?;
+    let mut document: Value = serde_json::from_str(&content)
+        .with_context(|| format!("{} is not a notebook", notebook.display()))?;
+
+    let evaluator = state.evaluator();
+    let format = state.config.error_format;
+    let mut run = 0;
+    let mut failure = None;
+    let cells = document
+        .get_mut("cells")
+        .and_then(Value::as_array_mut)
+        .into_iter()
+        .flatten();
+    for (i, cell) in cells.filter(|cell| cell["cell_type"] == "code").enumerate() {
+        let code = source(cell);
+        if code.trim().is_empty() {
+            continue;
+        }
+        let execution_count = state.next_execution_count();
+        let CellResult {
+            result,
+            warnings,
+            metadata,
+            ..
+        } = match cell_options(&cell["metadata"]) {
+            Ok(options) => {
+                execute_aiken_code(&evaluator, state, &code, execution_count, options, None).await
+            }
+            Err(e) => CellResult::error(e),
+        };
+        run += 1;
+
+        let mut outputs: Vec<Value> = warnings
+            .iter()
+            .map(|warning| {
+                let warning = match format {
+                    ErrorFormat::Ansi => warning.clone(),
+                    ErrorFormat::Plain | ErrorFormat::Html => ansi::strip(warning),
+                };
+                json!({ "output_type": "stream", "name": "stderr", "text": format!("{warning}\n") })
+            })
+            .collect();
+        match result {
+            Ok(data) if data.is_empty() => {}
+            Ok(data) => outputs.push(json!({
+                "output_type": "execute_result",
+                "execution_count": execution_count,
+                "data": data,
+                "metadata": metadata,
+            })),
+            Err(report) => {
+                let error = aiken_error(&report, format);
+                outputs.push(json!({
+                    "output_type": "error",
+                    "ename": error.ename,
+                    "evalue": error.evalue,
+                    "traceback": error.traceback,
+                }));
+                failure = Some(format!(
+                    "Code cell {} failed:\n{}",
+                    i + 1,
+                    ansi::strip(&report)
+                ));
+            }
+        }
+        cell["execution_count"] = json!(execution_count);
+        cell["outputs"] = Value::Array(outputs);
+        if failure.is_some() {
+            break;
+        }
+    }
+
+    fs::write(
+        notebook,
+        format!("{}\n", serde_json::to_string_pretty(&document)?),
+    )
+    .with_context(|| format!("Failed to write {}", notebook.display()))?;
+    match failure {
+        Some(failure) => Err(anyhow!(failure)),
+        None => Ok(run),
+    }
+}
+
+fn define(eval: &mut ReplEvaluator, code: &str) -> Result<(), String> {
+    match Invocation::parse(code) {
+        Some(call) => match magics::registry().find(&call) {
+            Ok(magic) if magic.defines(&call) => {
+                let mut kernel = KernelState {
+                    eval,
+                    chain: &mut None,
+                    payload: Vec::new(),
+                };
+                magic.execute(&mut kernel, &call).map(|_| ())
+            }
+            _ => Ok(()),
+        },
+        None => eval
+            .define(code)
+            .map(|_| ())
+            .map_err(format_evaluation_error_in_task),
+    }
+}
+
+/// `%recover`: replay the definition cells of the last session, as `export_notebook` does. A
+/// cell that fails doesn't stop the others, as later ones may not depend on it. Returns the
+/// report to display and the cells replayed.
+pub fn recover(
+    eval: &mut ReplEvaluator,
+    cells: &[String],
+) -> Result<(String, Vec<String>), String> {
+    if cells.is_empty() {
+        return Err("No definitions to recover from the last session".to_string());
+    }
+
+    let mut replayed = Vec::new();
+    let mut failures = Vec::new();
+    for (i, cell) in cells.iter().enumerate() {
+        match define(eval, cell) {
+            Ok(()) => replayed.push(cell.clone()),
+            Err(error) => failures.push(format!("Cell {} failed:\n{}", i + 1, error)),
+        }
+    }
+
+    let mut report = format!(
+        "Recovered {} of {} cells of the last session",
+        replayed.len(),
+        cells.len()
+    );
+    for failure in &failures {
+        report.push_str("\n\n");
+        report.push_str(failure);
+    }
+    Ok((report, replayed))
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::code_cells;
+
+    #[test]
+    fn test_code_cells() {
+        let notebook = json!({
+            "cells": [
+                { "cell_type": "markdown", "source": "# Doubling" },
+                { "cell_type": "code", "source": ["pub fn double(x) {\n", "  x * 2\n", "}"] },
+                { "cell_type": "code", "source": "double(21)" }
+            ]
+        });
+        assert_eq!(
+            code_cells(&notebook),
+            vec!["pub fn double(x) {\n  x * 2\n}", "double(21)"]
+        );
+        assert!(code_cells(&json!({})).is_empty());
+    }
+}
@@ -0,0 +1,62 @@
+//! The session's definitions and results, as shown by variable explorers and the debugger
+
+use serde::Serialize;
+
+use crate::state::SharedState;
+
+/// A definition or a cell result of the parent shell
+#[derive(Serialize, Debug, Clone)]
+pub struct SessionVariable {
+    pub name: String,
+    pub kind: String, // "function", "constant", "type" or "result"
+    #[serde(rename = "type")]
+    pub type_name: String,
+    pub value: String, // Empty for functions and types
+    /// Cell that defined it or computed it, eg. `In[4]`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub origin: Option<String>,
+}
+
+/// Definitions of the parent shell, followed by the results of previous cells as `_N`
+pub async fn session_variables(state: &SharedState) -> Vec<SessionVariable> {
+    let mut variables = Vec::new();
+
+    let definitions = state.evaluator().run(|eval| eval.inspect()).await;
+    match definitions {
+        Some(Ok(definitions)) => {
+            variables.extend(definitions.into_iter().map(|def| SessionVariable {
+                name: def.name,
+                kind: def.kind.as_str().to_string(),
+                type_name: def.tipo,
+                value: def.value.unwrap_or_default(),
+                origin: def.origin,
+            }))
+        }
+        Some(Err(e)) => eprintln!("Failed to inspect definitions: {e}"),
+        None => eprintln!("Failed to inspect definitions: the evaluator panicked"),
+    }
+
+    for entry in state.history() {
+        // Cells adding definitions only print feedback, the definitions are listed above
+        let Some(output) = entry
+            .output
+            .filter(|output| !output.starts_with("Defined "))
+        else {
+            continue;
+        };
+        // Values are displayed as `value : Type`
+        let (value, type_name) = match output.rsplit_once(" : ") {
+            Some((value, type_name)) => (value.to_string(), type_name.to_string()),
+            None => (output, String::new()),
+        };
+        variables.push(SessionVariable {
+            name: format!("_{}", entry.execution_count),
+            kind: "result".to_string(),
+            type_name,
+            value,
+            origin: Some(format!("In[{}]", entry.execution_count)),
+        });
+    }
+
+    variables
+}
@@ -0,0 +1,78 @@
+//! The thread an evaluator lives on
+//!
+//! Evaluations block for seconds (the first one importing the stdlib fetches and compiles it),
+//! so each evaluator is owned by a thread of its own and handlers send it jobs over a channel.
+//! Jobs run one at a time, in the order they were sent, and waiting for one doesn't hold up the
+//! async runtime: the shell keeps answering other requests while a cell runs.
+
+use std::panic::{AssertUnwindSafe, catch_unwind};
+
+use aiken_repl::evaluator::ReplEvaluator;
+use tokio::sync::{mpsc, oneshot};
+
+type Job = Box<dyn FnOnce(&mut ReplEvaluator) + Send>;
+
+/// Handle to an evaluator thread. Clones share the evaluator, which stops with the last handle.
+#[derive(Clone)]
+pub struct EvalWorker {
+    jobs: mpsc::UnboundedSender<Job>,
+}
+
+impl EvalWorker {
+    pub fn spawn(mut eval: ReplEvaluator) -> Self {
+        let (jobs, mut rx) = mpsc::unbounded_channel::<Job>();
+        let spawned = std::thread::Builder::new()
+            .name("evaluator".to_string())
+            .spawn(move || {
+                while let Some(job) = rx.blocking_recv() {
+                    // A job that panics loses its result, the evaluator goes on with the next
+                    if catch_unwind(AssertUnwindSafe(|| job(&mut eval))).is_err() {
+                        eprintln!("Evaluator job panicked");
+                    }
+                }
+            });
+        if let Err(e) = spawned {
+            eprintln!("Failed to start evaluator thread: {e}");
+        }
+        Self { jobs }
+    }
+
+    /// Run `job` on the evaluator once the jobs sent before are done. None if it panicked, or if
+    /// the evaluator thread is gone.
+    pub async fn run<R, F>(&self, job: F) -> Option<R>
+    where
+        R: Send + 'static,
+        F: FnOnce(&mut ReplEvaluator) -> R + Send + 'static,
+    {
+        let (tx, rx) = oneshot::channel();
+        let job: Job = Box::new(move |eval| {
+            let _ = tx.send(job(eval));
+        });
+        self.jobs.send(job).ok()?;
+        rx.await.ok()
+    }
+
+    /// Whether both handles are to the same evaluator
+    pub fn same(&self, other: &EvalWorker) -> bool {
+        self.jobs.same_channel(&other.jobs)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use aiken_repl::evaluator::ReplEvaluator;
+
+    use super::EvalWorker;
+
+    #[tokio::test]
+    async fn test_jobs() {
+        let worker = EvalWorker::spawn(ReplEvaluator::new());
+        assert!(worker.same(&worker.clone()));
+        assert!(!worker.same(&EvalWorker::spawn(ReplEvaluator::new())));
+
+        assert_eq!(worker.run(|_| 42).await, Some(42));
+        // The evaluator outlives a job that panics
+        assert_eq!(worker.run(|_| -> u32 { panic!("boom") }).await, None);
+        assert_eq!(worker.run(|_| "still there").await, Some("still there"));
+    }
+}
@@ -1,7 +1,15 @@
-use std::{fs, path::PathBuf};
+use std::{
+    env,
+    ffi::OsString,
+    fs,
+    path::{Path, PathBuf},
+};
 
 use serde::{Deserialize, Serialize};
 
+// CodeMirror mode for Aiken, loaded by the notebook frontend from the kernelspec resources
+const KERNEL_JS: &str = include_str!("../assets/kernel.js");
+
 // Kernel specification for installation
 // DOCS: https://jupyter-client.readthedocs.io/en/latest/kernels.html#kernel-specs
 #[derive(Serialize, Deserialize, Debug)]
@@ -14,12 +22,14 @@ pub struct KernelSpec {
 }
 
 impl KernelSpec {
-    pub fn new(executable_path: &str) -> Self {
+    pub fn new(executable_path: &str, codemirror_mode: &str) -> Self {
         Self {
             argv: vec![
                 executable_path.to_string(),
                 "--connection-file".to_string(),
                 "{connection_file}".to_string(),
+                "--codemirror-mode".to_string(),
+                codemirror_mode.to_string(),
             ],
             display_name: "Aiken".to_string(),
             language: "aiken".to_string(),
@@ -28,41 +38,177 @@ impl KernelSpec {
     }
 }
 
-fn get_aiken_kernel_dir() -> anyhow::Result<PathBuf> {
-    let home_dir =
-        dirs::home_dir().ok_or_else(|| anyhow::anyhow!("Could not find home directory"))?;
+/// Jupyter's data directory, where user kernelspecs live in `kernels/`: `$JUPYTER_DATA_DIR`
+/// if set, and otherwise where Jupyter puts it on the platform `os`, from the environment
+/// variables `var` and the home directory
+fn jupyter_data_dir(
+    os: &str,
+    var: impl Fn(&str) -> Option<OsString>,
+    home: Option<PathBuf>,
+) -> Option<PathBuf> {
+    let var = |name| {
+        var(name)
+            .filter(|value| !value.is_empty())
+            .map(PathBuf::from)
+    };
+    if let Some(dir) = var("JUPYTER_DATA_DIR") {
+        return Some(dir);
+    }
+    match os {
+        "windows" => Some(var("APPDATA").or_else(dirs::data_dir)?.join("jupyter")),
+        "macos" => Some(home?.join("Library").join("Jupyter")),
+        _ => {
+            let data = match var("XDG_DATA_HOME") {
+                Some(data) => data,
+                None => home?.join(".local").join("share"),
+            };
+            Some(data.join("jupyter"))
+        }
+    }
+}
 
-    let kernels_dir = home_dir
-        .join(".local")
-        .join("share")
-        .join("jupyter")
-        .join("kernels")
-        .join("aiken");
+/// Data directories Jupyter looks for kernelspecs in, in its order: those of `$JUPYTER_PATH`,
+/// the user's, the environment's (`$CONDA_PREFIX` or `$VIRTUAL_ENV`) and the system's
+fn jupyter_path(
+    os: &str,
+    var: impl Fn(&str) -> Option<OsString>,
+    home: Option<PathBuf>,
+) -> Vec<PathBuf> {
+    let separator = if os == "windows" { ';' } else { ':' };
+    let mut dirs: Vec<PathBuf> = var("JUPYTER_PATH")
+        .map(|path| {
+            path.to_string_lossy()
+                .split(separator)
+                .filter(|dir| !dir.is_empty())
+                .map(PathBuf::from)
+                .collect()
+        })
+        .unwrap_or_default();
+    dirs.extend(jupyter_data_dir(os, &var, home));
+    for name in ["CONDA_PREFIX", "VIRTUAL_ENV"] {
+        if let Some(prefix) = var(name).filter(|prefix| !prefix.is_empty()) {
+            dirs.push(PathBuf::from(prefix).join("share").join("jupyter"));
+        }
+    }
+    match os {
+        "windows" => dirs.extend(
+            var("PROGRAMDATA")
+                .filter(|data| !data.is_empty())
+                .map(|data| PathBuf::from(data).join("jupyter")),
+        ),
+        _ => dirs.extend([
+            PathBuf::from("/usr/local/share/jupyter"),
+            PathBuf::from("/usr/share/jupyter"),
+        ]),
+    }
+    let mut seen = Vec::new();
+    dirs.retain(|dir| {
+        let new = !seen.contains(dir);
+        seen.push(dir.clone());
+        new
+    });
+    dirs
+}
 
-    Ok(kernels_dir)
+/// Aiken kernelspecs in `kernels_dir`: `aiken`, even when partly installed, and with `all` any
+/// other named after Aiken or whose kernel.json is for Aiken
+fn aiken_kernelspecs(kernels_dir: &Path, all: bool) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(kernels_dir) else {
+        return Vec::new();
+    };
+    let mut specs: Vec<PathBuf> = entries
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            let name = path.file_name()?.to_string_lossy().to_lowercase();
+            let is_aiken = name == "aiken"
+                || all
+                    && (name.starts_with("aiken")
+                        || fs::read(path.join("kernel.json"))
+                            .ok()
+                            .and_then(|contents| {
+                                serde_json::from_slice::<KernelSpec>(&contents).ok()
+                            })
+                            .is_some_and(|spec| spec.language.eq_ignore_ascii_case("aiken")));
+            (is_aiken && path.is_dir()).then_some(path)
+        })
+        .collect();
+    specs.sort();
+    specs
 }
 
-pub fn install_kernel() -> anyhow::Result<()> {
-    use std::fs;
+/// Point the Aiken kernelspecs at `executable` when theirs is gone, or is another copy of the
+/// kernel, eg. after an update. Returns the kernelspecs rewritten.
+pub fn repoint_kernelspecs(executable: &Path) -> Vec<PathBuf> {
+    let data_dirs = jupyter_path(env::consts::OS, |name| env::var_os(name), dirs::home_dir());
+    data_dirs
+        .iter()
+        .flat_map(|dir| aiken_kernelspecs(&dir.join("kernels"), true))
+        .filter(|spec| match repoint(spec, executable) {
+            Ok(rewritten) => rewritten,
+            Err(e) => {
+                eprintln!("Failed to update {}: {e}", spec.display());
+                false
+            }
+        })
+        .collect()
+}
 
-    println!("Installing Aiken kernell...");
+/// Point the kernelspec in `spec_dir` at `executable` if it should be. Whether it was.
+fn repoint(spec_dir: &Path, executable: &Path) -> anyhow::Result<bool> {
+    let path = spec_dir.join("kernel.json");
+    let Ok(contents) = fs::read(&path) else {
+        return Ok(false);
+    };
+    let mut spec: serde_json::Value = serde_json::from_slice(&contents)?;
+    let Some(current) = spec["argv"][0].as_str().map(PathBuf::from) else {
+        return Ok(false);
+    };
+    let same = |a: &Path, b: &Path| match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    };
+    let other_copy = current.file_name() == executable.file_name();
+    if same(&current, executable) || current.exists() && !other_copy {
+        return Ok(false);
+    }
+    spec["argv"][0] = executable.to_string_lossy().into();
+    fs::write(&path, serde_json::to_string_pretty(&spec)?)?;
+    Ok(true)
+}
 
-    // Get current executable path
-    let exe_path = std::env::current_exe()?.to_string_lossy().to_string();
+/// Directory of the kernelspec: `<prefix>/share/jupyter/kernels/aiken` when given a prefix, as
+/// `jupyter kernelspec install --prefix` does, and otherwise in the user's data directory
+fn get_aiken_kernel_dir(prefix: Option<&Path>) -> anyhow::Result<PathBuf> {
+    let data_dir = match prefix {
+        Some(prefix) => prefix.join("share").join("jupyter"),
+        None => jupyter_data_dir(env::consts::OS, |name| env::var_os(name), dirs::home_dir())
+            .ok_or_else(|| anyhow::anyhow!("Could not find the Jupyter data directory"))?,
+    };
+    Ok(data_dir.join("kernels").join("aiken"))
+}
+
+/// `kernel.json` for the current executable, eg. for packaging tools to install themselves
+pub fn kernelspec_json(codemirror_mode: &str) -> anyhow::Result<String> {
+    let exe_path = env::current_exe()?.to_string_lossy().to_string();
+    let spec = KernelSpec::new(&exe_path, codemirror_mode);
+    Ok(serde_json::to_string_pretty(&spec)?)
+}
+
+pub fn install_kernel(codemirror_mode: &str, prefix: Option<&Path>) -> anyhow::Result<()> {
+    println!("Installing Aiken kernel...");
 
     // Find Jupyter kernel directory
-    let kernel_dir = get_aiken_kernel_dir()?;
+    let kernel_dir = get_aiken_kernel_dir(prefix)?;
 
     // Create directory if it doesn't exist
     fs::create_dir_all(&kernel_dir)?;
 
-    // Create kernel spec
-    let spec = KernelSpec::new(&exe_path);
-
     // Write kernel.json
     let kernel_json_path = kernel_dir.join("kernel.json");
-    let spec_json = serde_json::to_string_pretty(&spec)?;
-    fs::write(&kernel_json_path, spec_json)?;
+    fs::write(&kernel_json_path, kernelspec_json(codemirror_mode)?)?;
+
+    // Write kernel.js so the notebook can highlight Aiken code
+    fs::write(kernel_dir.join("kernel.js"), KERNEL_JS)?;
 
     println!("Aiken kernel installed successfully!");
     println!("Kernel spec written to: {}", kernel_json_path.display());
@@ -70,24 +216,246 @@ pub fn install_kernel() -> anyhow::Result<()> {
     Ok(())
 }
 
-pub fn uninstall_kernel() -> anyhow::Result<()> {
+pub fn uninstall_kernel(prefix: Option<&Path>, all: bool) -> anyhow::Result<()> {
     println!("Uninstalling Aiken kernel...");
 
-    // Find Jupyter kernel directory and read file contents
-    let kernel_dir = get_aiken_kernel_dir()?;
-    let kernel_file_contents = fs::read(kernel_dir.join("kernel.json"))?;
+    // Look where Jupyter would, or only in the prefix when given one
+    let data_dirs = match prefix {
+        Some(prefix) => vec![prefix.join("share").join("jupyter")],
+        None => jupyter_path(env::consts::OS, |name| env::var_os(name), dirs::home_dir()),
+    };
+    let kernels_dirs: Vec<PathBuf> = data_dirs.iter().map(|dir| dir.join("kernels")).collect();
+    let mut found = kernels_dirs
+        .iter()
+        .flat_map(|dir| aiken_kernelspecs(dir, all));
 
-    println!("Deleting {}...", kernel_dir.to_string_lossy());
+    // Without `--all`, the kernelspec Jupyter would start, which shadows the others
+    let (specs, others): (Vec<PathBuf>, Vec<PathBuf>) = if all {
+        (found.collect(), Vec::new())
+    } else {
+        (found.next().into_iter().collect(), found.collect())
+    };
+    if specs.is_empty() {
+        println!("No Aiken kernel found in:");
+        for dir in &kernels_dirs {
+            println!("  {}", dir.display());
+        }
+        return Ok(());
+    }
 
-    std::fs::remove_dir_all(&kernel_dir)?;
+    let mut failed = 0;
+    for spec in &specs {
+        // kernel.json may be gone or broken after a partial install, the binary is then unknown
+        let executable = fs::read(spec.join("kernel.json"))
+            .ok()
+            .and_then(|contents| serde_json::from_slice::<KernelSpec>(&contents).ok())
+            .and_then(|spec| spec.argv.into_iter().next());
+        println!("Deleting {}...", spec.display());
+        match fs::remove_dir_all(spec) {
+            Ok(()) => {
+                // Show the user where this binary is located
+                if let Some(executable) = executable {
+                    println!("You can now delete the kernel binary in: {}", executable);
+                }
+            }
+            Err(e) => {
+                eprintln!("Failed to remove {}: {e}", spec.display());
+                failed += 1;
+            }
+        }
+    }
+    for other in &others {
+        println!(
+            "Another Aiken kernel is installed in {}, use --all to remove it too",
+            other.display()
+        );
+    }
 
+    if failed > 0 {
+        anyhow::bail!(
+            "{failed} of {} kernel specs could not be removed",
+            specs.len()
+        );
+    }
     println!("Aiken kernel uninstalled successfully!");
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        ffi::OsString,
+        path::{Path, PathBuf},
+    };
+
+    use super::{aiken_kernelspecs, jupyter_data_dir, jupyter_path, repoint};
+
+    fn env(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<OsString> + use<> {
+        let vars: Vec<(String, OsString)> = vars
+            .iter()
+            .map(|(name, value)| (name.to_string(), OsString::from(value)))
+            .collect();
+        move |name| {
+            vars.iter()
+                .find(|(var, _)| var == name)
+                .map(|(_, value)| value.clone())
+        }
+    }
+
+    #[test]
+    fn test_jupyter_data_dir() {
+        let home = || Some(PathBuf::from("home"));
+        let appdata = r"C:\Users\ada\AppData\Roaming";
+        assert_eq!(
+            jupyter_data_dir("windows", env(&[("APPDATA", appdata)]), home()),
+            Some(PathBuf::from(appdata).join("jupyter"))
+        );
+        assert_eq!(
+            jupyter_data_dir("macos", env(&[]), home()),
+            Some(PathBuf::from("home").join("Library").join("Jupyter"))
+        );
+        assert_eq!(
+            jupyter_data_dir("linux", env(&[("XDG_DATA_HOME", "")]), home()),
+            Some(
+                PathBuf::from("home")
+                    .join(".local")
+                    .join("share")
+                    .join("jupyter")
+            )
+        );
+        assert_eq!(
+            jupyter_data_dir("linux", env(&[("XDG_DATA_HOME", "data")]), None),
+            Some(PathBuf::from("data").join("jupyter"))
+        );
+        assert_eq!(jupyter_data_dir("linux", env(&[]), None), None);
 
-    // Show the user where this binary is located
-    let kernel_file_parsed: serde_json::Value = serde_json::from_slice(&kernel_file_contents)?;
-    if let Some(exe_path) = kernel_file_parsed.get("argv").and_then(|argv| argv.get(0)) {
-        println!("You can now delete the kernel binary in: {}", exe_path);
+        // Jupyter's own override wins everywhere
+        let custom = env(&[("JUPYTER_DATA_DIR", "custom"), ("APPDATA", appdata)]);
+        assert_eq!(
+            jupyter_data_dir("windows", custom, home()),
+            Some(PathBuf::from("custom"))
+        );
     }
 
-    Ok(())
+    #[test]
+    fn test_jupyter_path() {
+        let home = || Some(PathBuf::from("home"));
+        let vars = env(&[
+            ("JUPYTER_PATH", "first:second:"),
+            ("JUPYTER_DATA_DIR", "data"),
+            ("CONDA_PREFIX", "conda"),
+            ("VIRTUAL_ENV", ""),
+        ]);
+        assert_eq!(
+            jupyter_path("linux", vars, home()),
+            [
+                "first",
+                "second",
+                "data",
+                "conda/share/jupyter",
+                "/usr/local/share/jupyter",
+                "/usr/share/jupyter"
+            ]
+            .map(PathBuf::from)
+        );
+
+        let vars = env(&[
+            ("JUPYTER_PATH", r"C:\first;C:\second"),
+            ("APPDATA", "appdata"),
+            ("PROGRAMDATA", "programdata"),
+            ("VIRTUAL_ENV", "appdata"),
+        ]);
+        assert_eq!(
+            jupyter_path("windows", vars, home()),
+            vec![
+                PathBuf::from(r"C:\first"),
+                PathBuf::from(r"C:\second"),
+                PathBuf::from("appdata").join("jupyter"),
+                PathBuf::from("appdata").join("share").join("jupyter"),
+                PathBuf::from("programdata").join("jupyter"),
+            ]
+        );
+
+        // A directory given twice is only searched once
+        let vars = env(&[("JUPYTER_PATH", "/usr/share/jupyter")]);
+        assert_eq!(jupyter_path("linux", vars, None).len(), 2);
+    }
+
+    #[test]
+    fn test_aiken_kernelspecs() {
+        let kernels = tempfile::tempdir().unwrap();
+        let spec = |name: &str, json: Option<&str>| {
+            let dir = kernels.path().join(name);
+            std::fs::create_dir(&dir).unwrap();
+            if let Some(json) = json {
+                std::fs::write(dir.join("kernel.json"), json).unwrap();
+            }
+        };
+        // A partial install, without kernel.json
+        spec("aiken", None);
+        spec("aiken-plutus-v2", Some("not json"));
+        spec(
+            "plutus",
+            Some(r#"{"argv": ["iaiken"], "display_name": "Plutus", "language": "aiken"}"#),
+        );
+        spec(
+            "python3",
+            Some(r#"{"argv": ["python"], "display_name": "Python 3", "language": "python"}"#),
+        );
+
+        assert_eq!(
+            aiken_kernelspecs(kernels.path(), false),
+            vec![kernels.path().join("aiken")]
+        );
+        assert_eq!(
+            aiken_kernelspecs(kernels.path(), true),
+            ["aiken", "aiken-plutus-v2", "plutus"].map(|name| kernels.path().join(name))
+        );
+        assert!(aiken_kernelspecs(&kernels.path().join("missing"), true).is_empty());
+    }
+
+    #[test]
+    fn test_repoint() {
+        let dir = tempfile::tempdir().unwrap();
+        let executable = dir.path().join("bin").join("iaiken");
+        let other_copy = dir.path().join("old").join("iaiken");
+        let other_kernel = dir.path().join("bin").join("other-kernel");
+        for binary in [&executable, &other_copy, &other_kernel] {
+            std::fs::create_dir_all(binary.parent().unwrap()).unwrap();
+            std::fs::write(binary, "").unwrap();
+        }
+
+        let spec = |name: &str, argv0: &Path| {
+            let spec_dir = dir.path().join(name);
+            std::fs::create_dir(&spec_dir).unwrap();
+            let json = serde_json::json!({
+                "argv": [argv0, "--connection-file", "{connection_file}"],
+                "display_name": name,
+                "language": "aiken",
+                "metadata": {"kept": true},
+            });
+            std::fs::write(spec_dir.join("kernel.json"), json.to_string()).unwrap();
+            spec_dir
+        };
+        let read = |spec_dir: &Path| -> serde_json::Value {
+            serde_json::from_slice(&std::fs::read(spec_dir.join("kernel.json")).unwrap()).unwrap()
+        };
+
+        let moved = spec("moved", &dir.path().join("gone").join("iaiken"));
+        assert!(repoint(&moved, &executable).unwrap());
+        let json = read(&moved);
+        assert_eq!(json["argv"][0], executable.to_string_lossy().as_ref());
+        assert_eq!(json["argv"][2], "{connection_file}");
+        assert_eq!(json["metadata"]["kept"], true);
+
+        let copy = spec("copy", &other_copy);
+        assert!(repoint(&copy, &executable).unwrap());
+
+        // Up to date, or another kernel built on iaiken
+        assert!(!repoint(&spec("current", &executable), &executable).unwrap());
+        assert!(!repoint(&spec("other", &other_kernel), &executable).unwrap());
+        // Without kernel.json there is nothing to point
+        std::fs::create_dir(dir.path().join("partial")).unwrap();
+        assert!(!repoint(&dir.path().join("partial"), &executable).unwrap());
+    }
 }
@@ -1,10 +1,18 @@
 mod connection;
 mod eval;
 mod messages;
+mod recovery;
+mod state;
 
 mod install;
+mod update;
 
+use aiken_repl::{
+    config::{self, Config},
+    history::{self, DEFAULT_MAX_ENTRIES, History},
+};
 use clap::Parser;
+use recovery::DefinitionLog;
 
 #[derive(Parser)]
 #[command(name = "iaiken")]
@@ -21,18 +29,162 @@ pub struct Cli {
     /// Uninstall kernel specification
     #[arg(long)]
     pub uninstall: bool,
+
+    /// Uninstall every Aiken kernel specification found, in all of Jupyter's directories, rather
+    /// than only the one Jupyter would start
+    #[arg(long, requires = "uninstall")]
+    pub all: bool,
+
+    /// Install into, or uninstall from, `<prefix>/share/jupyter/kernels` rather than the user's
+    /// Jupyter directory, eg. a conda environment or a container image
+    #[arg(long)]
+    pub prefix: Option<std::path::PathBuf>,
+
+    /// Replace this binary with the one of the latest release, and point the kernel
+    /// specifications at it
+    #[arg(long)]
+    pub update: bool,
+
+    /// Print the kernel specification (`kernel.json`) to stdout, and exit
+    #[arg(long = "kernelspec-json")]
+    pub kernelspec_json: bool,
+
+    /// CodeMirror mode advertised to frontends for syntax highlighting.
+    /// `aiken` is provided by the installed kernel.js, use eg. `rust` for frontends that can't load it
+    #[arg(long = "codemirror-mode", default_value = "aiken")]
+    pub codemirror_mode: String,
+
+    /// File where executed cells are saved between sessions, shared with aiken-repl
+    /// [default: $XDG_STATE_HOME/aiken-repl/history]
+    #[arg(long = "history-file")]
+    pub history_file: Option<std::path::PathBuf>,
+
+    /// Number of cells kept in the history file [default: 1000]
+    #[arg(long = "history-size")]
+    pub history_size: Option<usize>,
+
+    /// Directory where the cells that change the definitions of each session are logged, for
+    /// `%recover` [default: $XDG_STATE_HOME/iaiken/sessions]
+    #[arg(long = "recovery-dir")]
+    pub recovery_dir: Option<std::path::PathBuf>,
+
+    /// Configuration file, shared with aiken-repl [default: $XDG_CONFIG_HOME/iaiken/config.toml]
+    #[arg(long)]
+    pub config: Option<std::path::PathBuf>,
+
+    /// Set a session option, over the configuration file's (eg. `--set trace-level=verbose`)
+    #[arg(long = "set", value_name = "OPTION=VALUE", value_parser = config::parse_option)]
+    pub options: Vec<(String, String)>,
+
+    /// Don't compile the prelude at startup. The first cell compiles it instead.
+    #[arg(long = "no-warmup")]
+    pub no_warmup: bool,
+
+    /// Print the versions of the kernel, Aiken, Plutus and the Jupyter protocol, and exit
+    #[arg(short = 'V', long)]
+    pub version: bool,
+
+    /// Write the definitions of a notebook's code cells to a module, and exit
+    #[arg(long = "export-notebook", value_name = "NOTEBOOK")]
+    pub export_notebook: Option<std::path::PathBuf>,
+
+    /// Module written by --export-notebook [default: the notebook's path, with `.ak`]
+    #[arg(long, requires = "export_notebook")]
+    pub output: Option<std::path::PathBuf>,
+
+    /// Serve a JSON API (eval, reset, context, complete) on this loopback address instead of the
+    /// Jupyter protocol, eg. `127.0.0.1:8787`, for editor extensions and web UIs. Requests carry the
+    /// token it prints.
+    #[arg(long = "serve-http", value_name = "ADDRESS")]
+    pub serve_http: Option<std::net::SocketAddr>,
+
+    /// Execute the code cells of a notebook in order, write their outputs back to it, and exit,
+    /// with an error at the first cell that fails
+    #[arg(long, value_name = "NOTEBOOK")]
+    pub run: Option<std::path::PathBuf>,
 }
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     let cli = Cli::parse();
+    if cli.version {
+        println!("{}", messages::kernel_info::version_report());
+        return Ok(());
+    }
+    if cli.update {
+        return update::update();
+    }
+    if cli.kernelspec_json {
+        println!("{}", install::kernelspec_json(&cli.codemirror_mode)?);
+        return Ok(());
+    }
+    if let Some(notebook) = cli.export_notebook {
+        let module = eval::notebook::export_notebook(&notebook, cli.output)?;
+        println!("Wrote {}", module.display());
+        return Ok(());
+    }
+
+    // An invalid configuration file shouldn't keep the kernel from starting
+    let mut config = match cli.config.or_else(config::default_path) {
+        Some(path) => Config::load(&path).unwrap_or_else(|e| {
+            eprintln!("Ignoring the configuration file: {}", e);
+            Config::default()
+        }),
+        None => Config::default(),
+    };
+    for (name, value) in &cli.options {
+        config.set(name, value);
+    }
+    if let Some(notebook) = cli.run {
+        let cells = connection::run_notebook(&notebook, cli.codemirror_mode, config).await?;
+        println!("Ran {} cells of {}", cells, notebook.display());
+        return Ok(());
+    }
+
+    let history_size = cli
+        .history_size
+        .or(config.history.size)
+        .unwrap_or(DEFAULT_MAX_ENTRIES);
+    let history = cli
+        .history_file
+        .or_else(|| config.history.file.clone())
+        .or_else(history::default_path)
+        .map(|path| History::new(path, history_size));
+    let definition_log = cli
+        .recovery_dir
+        .or_else(recovery::default_dir)
+        .map(DefinitionLog::new);
+
+    if let Some(address) = cli.serve_http {
+        return connection::serve_http(
+            address,
+            cli.codemirror_mode,
+            history,
+            definition_log,
+            config,
+            !cli.no_warmup,
+        )
+        .await;
+    }
 
     match (cli.connection_file, cli.install, cli.uninstall) {
-        (Some(file), false, false) => connection::run_kernel(file).await,
-        (None, true, false) => install::install_kernel(),
-        (None, false, true) => install::uninstall_kernel(),
+        (Some(file), false, false) => {
+            connection::run_kernel(
+                file,
+                cli.codemirror_mode,
+                history,
+                definition_log,
+                config,
+                !cli.no_warmup,
+            )
+            .await
+        }
+        (None, true, false) => install::install_kernel(&cli.codemirror_mode, cli.prefix.as_deref()),
+        (None, false, true) => install::uninstall_kernel(cli.prefix.as_deref(), cli.all),
         _ => {
-            eprintln!("Usage: iaiken --connection-file=<file> | --install | --uninstall");
+            eprintln!(
+                "Usage: iaiken --connection-file=<file> | --install [--codemirror-mode=<mode>] [--prefix=<path>] | --uninstall [--all] [--prefix=<path>] | --update | --kernelspec-json | --export-notebook=<notebook> [--output=<file.ak>] | --run=<notebook> | --serve-http=<address>"
+            );
             std::process::exit(1);
         }
     }
@@ -0,0 +1,207 @@
+//! Escape codes in the reports of the compiler
+//!
+//! Reports are rendered once, with colors. Frontends that don't render escape codes get the
+//! text without them, and those that render HTML get the colors as styled spans.
+
+use super::escape_html;
+
+/// Colors of the SGR codes 30 to 37, and 90 to 97 for the bright ones
+const PALETTE: [&str; 16] = [
+    "#000000", "#cd3131", "#0dbc79", "#e5e510", "#2472c8", "#bc3fbc", "#11a8cd", "#e5e5e5",
+    "#666666", "#f14c4c", "#23d18b", "#f5f543", "#3b8eea", "#d670d6", "#29b8db", "#ffffff",
+];
+
+/// A piece of text, or the parameters of an SGR (`ESC [ ... m`) sequence. Other escape
+/// sequences, like hyperlinks, are dropped.
+enum Token<'a> {
+    Text(&'a str),
+    Sgr(&'a str),
+}
+
+fn tokens(text: &str) -> impl Iterator<Item = Token<'_>> {
+    let mut rest = text;
+    std::iter::from_fn(move || {
+        while !rest.is_empty() {
+            let Some(escape) = rest.strip_prefix('\x1b') else {
+                let end = rest.find('\x1b').unwrap_or(rest.len());
+                let (text, after) = rest.split_at(end);
+                rest = after;
+                return Some(Token::Text(text));
+            };
+            if let Some(csi) = escape.strip_prefix('[') {
+                // Parameters, then a final byte in `@` to `~`
+                let end = csi.find(|c: char| ('@'..='~').contains(&c));
+                let Some(end) = end else {
+                    rest = "";
+                    break;
+                };
+                rest = &csi[end + 1..];
+                if csi[end..].starts_with('m') {
+                    return Some(Token::Sgr(&csi[..end]));
+                }
+            } else if let Some(osc) = escape.strip_prefix(']') {
+                // Ended by BEL or by `ESC \`
+                let end = osc.find(['\x07', '\x1b']).unwrap_or(osc.len());
+                let after = &osc[end..];
+                rest = after
+                    .strip_prefix('\x07')
+                    .or_else(|| after.strip_prefix("\x1b\\"))
+                    .unwrap_or(after);
+            } else {
+                // A lone escape and the character after it
+                let mut chars = escape.chars();
+                chars.next();
+                rest = chars.as_str();
+            }
+        }
+        None
+    })
+}
+
+/// `text` without escape codes
+pub fn strip(text: &str) -> String {
+    tokens(text)
+        .filter_map(|token| match token {
+            Token::Text(text) => Some(text),
+            Token::Sgr(_) => None,
+        })
+        .collect()
+}
+
+/// Style set by the SGR sequences so far
+#[derive(Default, Clone, PartialEq)]
+struct Style {
+    color: Option<String>,
+    bold: bool,
+    italic: bool,
+    underline: bool,
+}
+
+impl Style {
+    fn apply(&mut self, params: &str) {
+        let mut params = params.split(';').map(|p| p.parse::<u8>().unwrap_or(0));
+        while let Some(param) = params.next() {
+            match param {
+                0 => *self = Style::default(),
+                1 => self.bold = true,
+                3 => self.italic = true,
+                4 => self.underline = true,
+                22 => self.bold = false,
+                23 => self.italic = false,
+                24 => self.underline = false,
+                30..=37 => self.color = Some(PALETTE[(param - 30) as usize].to_string()),
+                90..=97 => self.color = Some(PALETTE[(param - 90 + 8) as usize].to_string()),
+                39 => self.color = None,
+                38 => match params.next() {
+                    Some(5) => self.color = params.next().map(indexed_color),
+                    Some(2) => {
+                        let (r, g, b) = (params.next(), params.next(), params.next());
+                        self.color = Some(format!(
+                            "rgb({}, {}, {})",
+                            r.unwrap_or(0),
+                            g.unwrap_or(0),
+                            b.unwrap_or(0)
+                        ));
+                    }
+                    _ => {}
+                },
+                // Backgrounds are left to the frontend's theme
+                48 => match params.next() {
+                    Some(5) => {
+                        params.next();
+                    }
+                    Some(2) => {
+                        params.nth(2);
+                    }
+                    _ => {}
+                },
+                _ => {}
+            }
+        }
+    }
+
+    fn css(&self) -> String {
+        let mut css = Vec::new();
+        if let Some(color) = &self.color {
+            css.push(format!("color: {}", color));
+        }
+        if self.bold {
+            css.push("font-weight: bold".to_string());
+        }
+        if self.italic {
+            css.push("font-style: italic".to_string());
+        }
+        if self.underline {
+            css.push("text-decoration: underline".to_string());
+        }
+        css.join("; ")
+    }
+}
+
+/// Color `n` of the 256 of `ESC [ 38 ; 5 ; n m`
+fn indexed_color(n: u8) -> String {
+    match n {
+        0..=15 => PALETTE[n as usize].to_string(),
+        16..=231 => {
+            let level = |c: u8| if c == 0 { 0 } else { 55 + c as u32 * 40 };
+            let n = n - 16;
+            format!(
+                "rgb({}, {}, {})",
+                level(n / 36),
+                level(n / 6 % 6),
+                level(n % 6)
+            )
+        }
+        232..=255 => {
+            let gray = 8 + (n - 232) as u32 * 10;
+            format!("rgb({}, {}, {})", gray, gray, gray)
+        }
+    }
+}
+
+/// `text` as HTML to put in a `<pre>`, with its colors as styled spans
+pub fn to_html(text: &str) -> String {
+    let mut html = String::new();
+    let mut style = Style::default();
+    for token in tokens(text) {
+        match token {
+            Token::Sgr(params) => style.apply(params),
+            Token::Text(text) if style == Style::default() => html.push_str(&escape_html(text)),
+            Token::Text(text) => html.push_str(&format!(
+                "<span style=\"{}\">{}</span>",
+                style.css(),
+                escape_html(text)
+            )),
+        }
+    }
+    html
+}
+
+#[cfg(test)]
+mod test {
+    use super::{strip, to_html};
+
+    #[test]
+    fn test_strip() {
+        let report = "\x1b[31m×\x1b[0m Unknown variable\n  \x1b]8;;https://aiken-lang.org\x1b\\help\x1b]8;;\x1b\\: \x1b[1;38;5;12mx\x1b[0m";
+        assert_eq!(strip(report), "× Unknown variable\n  help: x");
+        assert_eq!(strip("plain"), "plain");
+        assert_eq!(strip("cut short \x1b[3"), "cut short ");
+    }
+
+    #[test]
+    fn test_to_html() {
+        assert_eq!(
+            to_html("\x1b[31m×\x1b[0m a < b"),
+            "<span style=\"color: #cd3131\">×</span> a &lt; b"
+        );
+        assert_eq!(
+            to_html("\x1b[1;38;2;10;20;30mx\x1b[22my\x1b[39mz"),
+            "<span style=\"color: rgb(10, 20, 30); font-weight: bold\">x</span><span style=\"color: rgb(10, 20, 30)\">y</span>z"
+        );
+        assert_eq!(
+            to_html("\x1b[38;5;196m!\x1b[m"),
+            "<span style=\"color: rgb(255, 0, 0)\">!</span>"
+        );
+    }
+}
@@ -0,0 +1,332 @@
+//! SVG charts published as `image/svg+xml` next to the text of a result
+
+use std::collections::BTreeMap;
+
+use aiken_repl::evaluator::DependencyGraph;
+
+use super::escape_html;
+
+const WIDTH: f64 = 480.0;
+const BAR_HEIGHT: f64 = 22.0;
+const GAP: f64 = 6.0;
+/// Room for the labels of the bars, left of them
+const LABEL_WIDTH: f64 = 80.0;
+/// Room for the values, right of the bars
+const VALUE_WIDTH: f64 = 90.0;
+const TITLE_HEIGHT: f64 = 28.0;
+
+/// Horizontal bar chart of `bars`, each a label and a value, with `format` for the values.
+/// A `mean` draws a dashed line across the bars.
+pub fn bar_chart(
+    title: &str,
+    bars: &[(String, f64)],
+    mean: Option<f64>,
+    format: impl Fn(f64) -> String,
+) -> String {
+    let height = TITLE_HEIGHT + bars.len() as f64 * (BAR_HEIGHT + GAP) + GAP;
+    let max = bars
+        .iter()
+        .map(|(_, value)| *value)
+        .chain(mean)
+        .fold(0.0, f64::max);
+    let plot_width = WIDTH - LABEL_WIDTH - VALUE_WIDTH;
+    let scale = |value: f64| {
+        if max > 0.0 {
+            value / max * plot_width
+        } else {
+            0.0
+        }
+    };
+
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{WIDTH}\" height=\"{height}\" \
+         font-family=\"sans-serif\" font-size=\"12\">\
+         <text x=\"{}\" y=\"18\" font-weight=\"bold\">{}</text>",
+        GAP,
+        escape_html(title)
+    );
+    for (i, (label, value)) in bars.iter().enumerate() {
+        let y = TITLE_HEIGHT + i as f64 * (BAR_HEIGHT + GAP);
+        let text_y = y + BAR_HEIGHT / 2.0 + 4.0;
+        let width = scale(*value);
+        svg.push_str(&format!(
+            "<text x=\"{}\" y=\"{text_y}\" text-anchor=\"end\">{}</text>\
+             <rect x=\"{LABEL_WIDTH}\" y=\"{y}\" width=\"{width:.1}\" height=\"{BAR_HEIGHT}\" \
+             fill=\"#4c78a8\"/>\
+             <text x=\"{:.1}\" y=\"{text_y}\">{}</text>",
+            LABEL_WIDTH - GAP,
+            escape_html(label),
+            LABEL_WIDTH + width + GAP,
+            escape_html(&format(*value)),
+        ));
+    }
+    if let Some(mean) = mean {
+        let x = LABEL_WIDTH + scale(mean);
+        svg.push_str(&format!(
+            "<line x1=\"{x:.1}\" y1=\"{TITLE_HEIGHT}\" x2=\"{x:.1}\" y2=\"{}\" stroke=\"#e45756\" \
+             stroke-dasharray=\"4 3\"><title>mean {}</title></line>",
+            height - GAP,
+            escape_html(&format(mean)),
+        ));
+    }
+    svg.push_str("</svg>");
+    svg
+}
+
+const FLAME_WIDTH: f64 = 960.0;
+const FLAME_ROW: f64 = 18.0;
+/// Approximate width of a character of the labels, to cut them to their frame
+const CHAR_WIDTH: f64 = 7.0;
+
+/// Frame of a flamegraph, merging the stacks with the same frames up to it
+#[derive(Default)]
+struct FlameNode<'a> {
+    value: i64,
+    children: BTreeMap<&'a str, FlameNode<'a>>,
+}
+
+impl FlameNode<'_> {
+    fn depth(&self) -> usize {
+        self.children
+            .values()
+            .map(|child| child.depth() + 1)
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+/// Flamegraph of collapsed `stacks` (frames from the outermost, separated by `;`) by their
+/// value. Each frame is as wide as the value of the stacks it's part of, on top of its caller.
+pub fn flamegraph(title: &str, stacks: &BTreeMap<String, i64>) -> String {
+    let mut root = FlameNode::default();
+    for (stack, value) in stacks {
+        root.value += value;
+        let mut node = &mut root;
+        for frame in stack.split(';') {
+            node = node.children.entry(frame).or_default();
+            node.value += value;
+        }
+    }
+
+    let height = TITLE_HEIGHT + root.depth() as f64 * FLAME_ROW;
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{FLAME_WIDTH}\" height=\"{height}\" \
+         font-family=\"monospace\" font-size=\"11\">\
+         <text x=\"{}\" y=\"18\" font-family=\"sans-serif\" font-size=\"12\" \
+         font-weight=\"bold\">{}</text>",
+        GAP,
+        escape_html(title)
+    );
+    let scale = FLAME_WIDTH / root.value.max(1) as f64;
+    flame_frames(&mut svg, &root, 0.0, height - FLAME_ROW, scale, root.value);
+    svg.push_str("</svg>");
+    svg
+}
+
+/// Draw the children of `node` from `x`, on the row at `y`, and theirs above it
+fn flame_frames(svg: &mut String, node: &FlameNode, mut x: f64, y: f64, scale: f64, total: i64) {
+    for (name, child) in &node.children {
+        let width = child.value as f64 * scale;
+        let chars = (width / CHAR_WIDTH) as usize;
+        let label = if name.chars().count() <= chars {
+            name.to_string()
+        } else if chars > 2 {
+            format!("{}..", name.chars().take(chars - 2).collect::<String>())
+        } else {
+            String::new()
+        };
+        svg.push_str(&format!(
+            "<g><title>{} ({}, {:.1}%)</title>\
+             <rect x=\"{x:.1}\" y=\"{y}\" width=\"{width:.1}\" height=\"{}\" fill=\"{}\" \
+             stroke=\"white\" stroke-width=\"0.5\"/>\
+             <text x=\"{:.1}\" y=\"{}\">{}</text></g>",
+            escape_html(name),
+            child.value,
+            100.0 * child.value as f64 / total.max(1) as f64,
+            FLAME_ROW - 1.0,
+            flame_color(name),
+            x + 3.0,
+            y + FLAME_ROW - 5.0,
+            escape_html(&label),
+        ));
+        flame_frames(svg, child, x, y - FLAME_ROW, scale, total);
+        x += width;
+    }
+}
+
+/// Warm color of a frame, the same for every frame of that name
+fn flame_color(name: &str) -> String {
+    let hash = name.bytes().fold(0u32, |hash, byte| {
+        hash.wrapping_mul(31).wrapping_add(byte as u32)
+    });
+    format!("hsl({}, 80%, {}%)", hash % 50, 55 + hash % 15)
+}
+
+const NODE_HEIGHT: f64 = 24.0;
+/// Room between the columns of a dependency graph, for its edges
+const COLUMN_GAP: f64 = 60.0;
+
+/// Graph of the references between definitions, each drawn as an arrow from the definition that
+/// makes it. Definitions are in columns from the left: first those that refer to none of the
+/// others, then those that refer to the first ones only, and so on.
+pub fn dependency_graph(title: &str, graph: &DependencyGraph) -> String {
+    // Column of each definition: one past the furthest of those it refers to. Cycles stop
+    // growing it once it has gone past every column.
+    let count = graph.nodes.len();
+    let mut ranks = vec![0; count];
+    for _ in 0..count {
+        for (from, to) in &graph.edges {
+            ranks[*from] = ranks[*from].max((ranks[*to] + 1).min(count));
+        }
+    }
+    let mut columns: Vec<Vec<usize>> = Vec::new();
+    for (node, rank) in ranks.iter().enumerate() {
+        if columns.len() <= *rank {
+            columns.resize(rank + 1, Vec::new());
+        }
+        columns[*rank].push(node);
+    }
+    let columns: Vec<Vec<usize>> = columns
+        .into_iter()
+        .filter(|nodes| !nodes.is_empty())
+        .collect();
+
+    // Box of each definition: x, y and width
+    let mut boxes = vec![(0.0, 0.0, 0.0); count];
+    let mut x = GAP;
+    for nodes in &columns {
+        let width = nodes
+            .iter()
+            .map(|node| graph.nodes[*node].name.chars().count() as f64 * CHAR_WIDTH + 2.0 * GAP)
+            .fold(0.0, f64::max);
+        for (row, node) in nodes.iter().enumerate() {
+            boxes[*node] = (x, TITLE_HEIGHT + row as f64 * (NODE_HEIGHT + GAP), width);
+        }
+        x += width + COLUMN_GAP;
+    }
+    let width = (x - COLUMN_GAP + GAP).max(WIDTH);
+    let rows = columns.iter().map(Vec::len).max().unwrap_or(0);
+    let height = TITLE_HEIGHT + rows as f64 * (NODE_HEIGHT + GAP) + GAP;
+
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\" \
+         font-family=\"sans-serif\" font-size=\"12\">\
+         <defs><marker id=\"depgraph-arrow\" viewBox=\"0 0 10 10\" refX=\"10\" refY=\"5\" \
+         markerWidth=\"6\" markerHeight=\"6\" orient=\"auto\">\
+         <path d=\"M 0 0 L 10 5 L 0 10 z\" fill=\"#888\"/></marker></defs>\
+         <text x=\"{}\" y=\"18\" font-weight=\"bold\">{}</text>",
+        GAP,
+        escape_html(title)
+    );
+    for (from, to) in &graph.edges {
+        let (from_x, from_y, _) = boxes[*from];
+        let (to_x, to_y, to_width) = boxes[*to];
+        svg.push_str(&format!(
+            "<line x1=\"{from_x:.1}\" y1=\"{:.1}\" x2=\"{:.1}\" y2=\"{:.1}\" stroke=\"#888\" \
+             marker-end=\"url(#depgraph-arrow)\"/>",
+            from_y + NODE_HEIGHT / 2.0,
+            to_x + to_width,
+            to_y + NODE_HEIGHT / 2.0,
+        ));
+    }
+    for (node, (x, y, width)) in graph.nodes.iter().zip(&boxes) {
+        let color = match node.kind {
+            "type" => "#f58518",
+            "constant" => "#54a24b",
+            "test" | "validator" => "#b279a2",
+            _ => "#4c78a8",
+        };
+        svg.push_str(&format!(
+            "<g><title>{} {}</title>\
+             <rect x=\"{x:.1}\" y=\"{y}\" width=\"{width:.1}\" height=\"{NODE_HEIGHT}\" rx=\"4\" \
+             fill=\"{color}\"/>\
+             <text x=\"{:.1}\" y=\"{}\" fill=\"white\">{}</text></g>",
+            node.kind,
+            escape_html(&node.name),
+            x + GAP,
+            y + NODE_HEIGHT / 2.0 + 4.0,
+            escape_html(&node.name),
+        ));
+    }
+    svg.push_str("</svg>");
+    svg
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use aiken_repl::evaluator::{DependencyGraph, GraphNode};
+
+    use super::{bar_chart, dependency_graph, flamegraph};
+
+    #[test]
+    fn test_bar_chart() {
+        let bars = vec![("run 1".to_string(), 2.0), ("run <2>".to_string(), 4.0)];
+        let svg = bar_chart("Time", &bars, Some(3.0), |value| format!("{} s", value));
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\""));
+        assert!(svg.ends_with("</svg>"));
+        assert_eq!(svg.matches("<rect").count(), 2);
+        assert!(svg.contains("run &lt;2&gt;"));
+        assert!(svg.contains("mean 3 s"));
+        // The longest bar spans the whole plot
+        assert!(svg.contains("width=\"310.0\""));
+
+        let empty = bar_chart("Time", &[], None, |value| value.to_string());
+        assert!(!empty.contains("<rect"));
+    }
+
+    #[test]
+    fn test_flamegraph() {
+        let stacks = BTreeMap::from([
+            ("main;fold".to_string(), 30),
+            ("main".to_string(), 10),
+            ("other".to_string(), 60),
+        ]);
+        let svg = flamegraph("Cpu", &stacks);
+        assert!(svg.ends_with("</svg>"));
+        // `main` is merged, with `fold` on top of it
+        assert_eq!(svg.matches("<rect").count(), 3);
+        assert!(
+            svg.contains("<title>main (40, 40.0%)</title><rect x=\"0.0\" y=\"46\" width=\"384.0\"")
+        );
+        assert!(svg.contains("<title>fold (30, 30.0%)</title><rect x=\"0.0\" y=\"28\""));
+        assert!(svg.contains("<title>other (60, 60.0%)</title><rect x=\"384.0\""));
+    }
+
+    #[test]
+    fn test_dependency_graph() {
+        let node = |name: &str, kind| GraphNode {
+            name: name.to_string(),
+            kind,
+        };
+        let graph = DependencyGraph {
+            nodes: vec![
+                node("double", "function"),
+                node("quadruple", "function"),
+                node("Color", "type"),
+                node("works", "test"),
+            ],
+            edges: vec![(1, 0), (3, 1), (3, 2)],
+        };
+        let svg = dependency_graph("Dependencies", &graph);
+        assert!(svg.ends_with("</svg>"));
+        assert_eq!(svg.matches("<rect").count(), 4);
+        assert_eq!(svg.matches("<line").count(), 3);
+        // Definitions that refer to no other come first, then those that refer to them
+        assert!(svg.contains("<title>function double</title><rect x=\"6.0\" y=\"28\""));
+        assert!(svg.contains("<title>type Color</title><rect x=\"6.0\" y=\"58\""));
+        assert!(svg.contains("<title>function quadruple</title><rect x=\"120.0\" y=\"28\""));
+        assert!(svg.contains("<title>test works</title><rect x=\"255.0\" y=\"28\""));
+
+        // References that go around in a cycle still end up drawn
+        let cycle = DependencyGraph {
+            nodes: vec![node("a", "function"), node("b", "function")],
+            edges: vec![(0, 1), (1, 0)],
+        };
+        assert_eq!(
+            dependency_graph("Cycle", &cycle).matches("<line").count(),
+            2
+        );
+    }
+}
@@ -0,0 +1,51 @@
+use aiken_repl::version;
+use jupyter_protocol::{
+    PROTOCOL_VERSION,
+    reply::Reply,
+    shell::kernel_info::{HelpLink, KernelInfoReply, LanguageInfo},
+};
+
+pub const KI_LI_MIMETYPE: &str = "text/x-aiken";
+const KI_IMPLEMENTATION: &str = "aiken";
+const KI_IMPLEMENTATION_VERSION: &str = env!("CARGO_PKG_VERSION");
+const KI_DEBUGGER: bool = false;
+const KI_LI_NAME: &str = "aiken";
+const KI_LI_FILE_EXT: &str = ".ak";
+
+// DOCS: https://jupyter-client.readthedocs.io/en/latest/messaging.html#kernel-info
+pub fn kernel_info_reply(codemirror_mode: &str) -> Reply<KernelInfoReply> {
+    Reply::Ok(KernelInfoReply {
+        protocol_version: PROTOCOL_VERSION.to_string(),
+        implementation: KI_IMPLEMENTATION.to_string(),
+        implementation_version: KI_IMPLEMENTATION_VERSION.to_string(),
+        language_info: LanguageInfo {
+            name: KI_LI_NAME.to_string(),
+            version: version::aiken_version(),
+            mimetype: KI_LI_MIMETYPE.to_string(),
+            file_extension: KI_LI_FILE_EXT.to_string(),
+            pygments_lexer: Some(KI_LI_NAME.to_string()),
+            codemirror_mode: Some(codemirror_mode.to_string()),
+            nbconvert_exporter: "script".to_string(),
+        },
+        banner: format!(
+            "Aiken Kernel v{}\nCardano Smart Contract Language",
+            KI_IMPLEMENTATION_VERSION
+        ),
+        debugger: KI_DEBUGGER,
+        help_links: vec![HelpLink {
+            text: "Aiken Documentation".to_string(),
+            url: "https://aiken-lang.org/".to_string(),
+        }],
+        supported_features: Some(vec!["kernel subshells".to_string()]),
+    })
+}
+
+/// Versions of the kernel, of the Aiken compiler and of the protocols it speaks, for
+/// `--version` and `%version`
+pub fn version_report() -> String {
+    format!(
+        "{}\nJupyter protocol {}",
+        version::report("iaiken", KI_IMPLEMENTATION_VERSION),
+        PROTOCOL_VERSION
+    )
+}
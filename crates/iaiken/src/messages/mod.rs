@@ -1,80 +1,449 @@
-use serde::{Deserialize, Serialize};
-
-pub mod crypto;
-pub mod iopub;
-pub mod wire;
-pub mod shell {
-    pub mod execute;
-    pub mod kernel_info;
-}
-pub mod control {
-    pub mod shutdown;
-}
-
-// DOCS: https://jupyter-client.readthedocs.io/en/latest/messaging.html#message-header
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct MessageHeader {
-    pub msg_id: String,   // UUID for this message
-    pub session: String,  // Session UUID
-    pub username: String, // Usually "kernel"
-    pub date: String,     // ISO 8601 timestamp
-    pub msg_type: String, // "execute_request", "kernel_info_request", etc.
-    pub version: String,  // Protocol version
-}
-
-impl MessageHeader {
-    pub fn new(session: String, msg_type: String) -> Self {
-        MessageHeader {
-            msg_id: uuid::Uuid::new_v4().to_string(),
-            session,
-            username: "kernel".to_string(),
-            date: chrono::Utc::now().to_rfc3339(),
-            msg_type,
-            version: shell::kernel_info::PROTOCOL_VERSION.to_string(),
+//! Aiken specific message contents. The Jupyter protocol itself lives in the `jupyter-protocol` crate.
+
+use std::time::Duration;
+
+use aiken_repl::evaluator::{
+    DependencyGraph, EvaluationResult, Profile, ResultSummary, TestReport, Timing, Timings,
+    format_duration,
+};
+use iaiken_magics::{Flavor, Magic};
+use jupyter_protocol::MimeBundle;
+
+pub mod ansi;
+mod chart;
+pub mod kernel_info;
+
+/// MIME bundle with both text/plain and text/x-aiken representations of `text`
+pub fn text_bundle(text: String) -> MimeBundle {
+    let mut data = MimeBundle::new();
+    data.insert("text/plain".into(), serde_json::Value::String(text.clone()));
+    data.insert(
+        kernel_info::KI_LI_MIMETYPE.into(),
+        serde_json::Value::String(text),
+    );
+    data
+}
+
+/// MIME bundle of an error report: the text without its colors, and HTML with them
+pub fn error_bundle(report: &str) -> MimeBundle {
+    let mut data = MimeBundle::new();
+    data.insert(
+        "text/plain".into(),
+        serde_json::Value::String(ansi::strip(report)),
+    );
+    data.insert(
+        "text/html".into(),
+        serde_json::Value::String(format!("<pre>{}</pre>", ansi::to_html(report))),
+    );
+    data
+}
+
+/// MIME bundle of an evaluation result. Test reports also get an HTML table.
+pub fn result_bundle(result: &EvaluationResult) -> MimeBundle {
+    let mut data = text_bundle(format!("{}", result));
+    if let EvaluationResult::TestReport(report) = result {
+        data.insert(
+            "text/html".into(),
+            serde_json::Value::String(test_report_html(report)),
+        );
+    }
+    data
+}
+
+/// MIME bundle of a `%%timeit` result, with a chart of the time per loop of each run
+pub fn timing_bundle(timing: &Timing) -> MimeBundle {
+    let mut data = text_bundle(format!("{}", timing));
+    let runs: Vec<(String, f64)> = timing
+        .runs
+        .iter()
+        .enumerate()
+        .map(|(i, time)| (format!("run {}", i + 1), time.as_secs_f64()))
+        .collect();
+    let chart = chart::bar_chart(
+        &format!(
+            "Time per loop ({} loop{} per run)",
+            timing.loops,
+            if timing.loops == 1 { "" } else { "s" }
+        ),
+        &runs,
+        Some(timing.mean.as_secs_f64()),
+        |secs| format_duration(Duration::from_secs_f64(secs)),
+    );
+    insert_svg(&mut data, chart);
+    data
+}
+
+/// Metadata of an `execute_result`: the seconds each compiler phase of the cell took, and
+/// their total
+pub fn timings_metadata(timings: &Timings) -> serde_json::Value {
+    let mut seconds = serde_json::Map::new();
+    for (phase, duration) in &timings.phases {
+        seconds.insert(phase.as_str().into(), duration.as_secs_f64().into());
+    }
+    seconds.insert("total".into(), timings.total().as_secs_f64().into());
+    serde_json::json!({ "timings": seconds })
+}
+
+/// Metadata of an execute_result, for tools that read results without parsing their text: what
+/// the result is (`kind`, and `magic` for cells of magics), the `type` and `budget` of a value,
+/// the `definitions` added, the `aiken_version` that compiled it, and `timings`
+pub fn result_metadata(
+    summary: Option<&ResultSummary>,
+    timings: Option<&Timings>,
+    aiken_version: &str,
+) -> serde_json::Value {
+    let mut metadata = match timings {
+        Some(timings) => timings_metadata(timings),
+        None => serde_json::json!({}),
+    };
+    metadata["kind"] = summary.map_or("magic", |summary| summary.kind).into();
+    if let Some(tipo) = summary.and_then(|summary| summary.tipo.as_ref()) {
+        metadata["type"] = tipo.as_str().into();
+    }
+    if let Some(budget) = summary.and_then(|summary| summary.budget.as_ref()) {
+        metadata["budget"] = serde_json::json!({ "cpu": budget.cpu, "mem": budget.mem });
+    }
+    metadata["definitions"] = summary
+        .map(|summary| summary.definitions.clone())
+        .unwrap_or_default()
+        .into();
+    metadata["aiken_version"] = aiken_version.into();
+    metadata
+}
+
+/// Frames charted by `%profile`
+const PROFILE_CHART_FRAMES: usize = 10;
+
+/// MIME bundle of a `%profile` result, with a chart of the most expensive frames
+pub fn profile_bundle(profile: &Profile) -> MimeBundle {
+    let mut data = text_bundle(format!("{}", profile));
+    let frames: Vec<(String, f64)> = profile
+        .frames
+        .iter()
+        .take(PROFILE_CHART_FRAMES)
+        .map(|frame| (frame.name.clone(), frame.self_cpu as f64))
+        .collect();
+    let chart = chart::bar_chart("Self cpu", &frames, None, |cpu| format!("{:.0}", cpu));
+    insert_svg(&mut data, chart);
+    data
+}
+
+/// MIME bundle of a `%flamegraph` result: the flamegraph of its stacks under `text`
+pub fn flamegraph_bundle(profile: &Profile, text: String) -> MimeBundle {
+    let mut data = text_bundle(text);
+    let title = format!(
+        "Cpu by call stack (mem {}, cpu {})",
+        profile.budget.mem, profile.budget.cpu
+    );
+    insert_svg(&mut data, chart::flamegraph(&title, &profile.stacks));
+    data
+}
+
+/// MIME bundle of `%depgraph`: what each definition refers to, as text, as a drawing and in the
+/// DOT language of Graphviz
+pub fn depgraph_bundle(graph: &DependencyGraph) -> MimeBundle {
+    let text = graph
+        .nodes
+        .iter()
+        .map(|node| match graph.dependencies(&node.name).as_slice() {
+            [] => format!("{} {}", node.kind, node.name),
+            dependencies => format!("{} {} -> {}", node.kind, node.name, dependencies.join(", ")),
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    let mut data = text_bundle(text);
+    data.insert("text/vnd.graphviz".into(), graph.to_dot().into());
+    insert_svg(
+        &mut data,
+        chart::dependency_graph("Dependencies of the session", graph),
+    );
+    data
+}
+
+/// Add an SVG image to a bundle. Frontends only show its richest representation, so the HTML
+/// one has both the text and the image.
+pub fn insert_svg(data: &mut MimeBundle, svg: String) {
+    let html = format!("<pre>{}</pre>{}", escape_html(bundle_text(data)), svg);
+    data.insert("text/html".into(), serde_json::Value::String(html));
+    data.insert("image/svg+xml".into(), serde_json::Value::String(svg));
+}
+
+/// MIME bundle of `%lsmagic` and `%help`: the line magics then the cell magics, with how to call
+/// them and what they do, as text and as an HTML table
+pub fn magics_bundle(magics: &[&dyn Magic]) -> MimeBundle {
+    let mut text = Vec::new();
+    let mut html =
+        String::from("<table><thead><tr><th>Magic</th><th>Description</th></tr></thead><tbody>");
+    for flavor in [Flavor::Line, Flavor::Cell] {
+        let magics: Vec<_> = magics
+            .iter()
+            .filter(|magic| magic.flavor() == flavor)
+            .collect();
+        if magics.is_empty() {
+            continue;
+        }
+        text.push(
+            match flavor {
+                Flavor::Line => "Line magics:",
+                Flavor::Cell => "Cell magics:",
+            }
+            .to_string(),
+        );
+        for magic in magics {
+            let (usage, description) = magic_help(*magic);
+            text.push(format!("  {}\n      {}", usage, description));
+            html.push_str(&format!(
+                "<tr><td><code>{}</code></td><td style=\"text-align: left\">{}</td></tr>",
+                escape_html(&usage),
+                escape_html(&description)
+            ));
         }
     }
+    html.push_str("</tbody></table>");
+    let mut data = text_bundle(text.join("\n"));
+    data.insert("text/html".into(), serde_json::Value::String(html));
+    data
+}
+
+/// How to call a magic and what it does, from its help: `` `%doc <name>`: show docs `` gives
+/// `%doc <name>` and `Show docs`
+fn magic_help(magic: &dyn Magic) -> (String, String) {
+    let help = magic.help();
+    let (usage, description) = help
+        .strip_prefix('`')
+        .and_then(|help| help.split_once("`: "))
+        .unwrap_or(("", help));
+    let usage = match usage {
+        "" => format!("{}{}", magic.flavor().prefix(), magic.name()),
+        usage => usage.to_string(),
+    };
+    let mut chars = description.chars();
+    let description: String = chars
+        .next()
+        .map(|first| first.to_uppercase().chain(chars).collect())
+        .unwrap_or_default();
+    (usage, description.replace('`', ""))
 }
 
-// DOCS: https://jupyter-client.readthedocs.io/en/latest/messaging.html#general-message-format
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct JupyterMessage<T> {
-    pub header: MessageHeader,                // Header for this message
-    pub parent_header: Option<MessageHeader>, // Header of the parent message
-    pub metadata: serde_json::Value,          // Metadata for this message
-    pub content: T,                           // Content specific to the message type
+/// Options of a cell from the `iaiken` object in the metadata of its execute request, eg.
+/// `{"iaiken": {"budget": {"cpu": 1000000}, "trace": "verbose", "max-depth": 2}}`. Keys are
+/// the names of `%set`, or `budget` and `trace`, and values are given as in the config file.
+pub fn cell_options(metadata: &serde_json::Value) -> Result<Vec<(String, String)>, String> {
+    let Some(options) = metadata.get("iaiken") else {
+        return Ok(Vec::new());
+    };
+    let invalid = |message: String| format!("Invalid cell metadata: {}", message);
+    let options = options
+        .as_object()
+        .ok_or_else(|| invalid("`iaiken` must be an object".to_string()))?;
+
+    let mut cell = Vec::new();
+    for (name, value) in options {
+        match (name.as_str(), value) {
+            ("budget", serde_json::Value::Object(budget)) => {
+                for (unit, limit) in budget {
+                    let option = format!("budget-{}", unit);
+                    cell.push((
+                        option.clone(),
+                        option_value(&option, limit).map_err(invalid)?,
+                    ));
+                }
+            }
+            ("trace", value) => cell.push((
+                "trace-level".to_string(),
+                option_value(name, value).map_err(invalid)?,
+            )),
+            (name, value) => cell.push((
+                name.to_string(),
+                option_value(name, value).map_err(invalid)?,
+            )),
+        }
+    }
+    Ok(cell)
+}
+
+fn option_value(name: &str, value: &serde_json::Value) -> Result<String, String> {
+    match value {
+        serde_json::Value::String(value) => Ok(value.clone()),
+        serde_json::Value::Number(value) => Ok(value.to_string()),
+        serde_json::Value::Bool(true) => Ok("on".to_string()),
+        serde_json::Value::Bool(false) => Ok("off".to_string()),
+        _ => Err(format!(
+            "`{}` must be a string, a number or a boolean",
+            name
+        )),
+    }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct ConnectionConfig {
-    pub transport: String,        // Usually "tcp"
-    pub ip: String,               // Usually "127.0.0.1"
-    pub signature_scheme: String, // "hmac-sha256"
-    pub key: String,              // For HMAC signing
-    pub control_port: u16,
-    pub shell_port: u16,
-    pub stdin_port: u16,
-    pub hb_port: u16, // heartbeat
-    pub iopub_port: u16,
+/// The text/plain representation of a bundle
+pub fn bundle_text(data: &MimeBundle) -> &str {
+    data.get("text/plain")
+        .and_then(|text| text.as_str())
+        .unwrap_or_default()
 }
 
-impl ConnectionConfig {
-    pub fn shell_address(&self) -> String {
-        format!("{}://{}:{}", self.transport, self.ip, self.shell_port)
+fn test_report_html(report: &TestReport) -> String {
+    let mut html = String::from(
+        "<table><thead><tr><th>Test</th><th>Result</th><th>Mem</th><th>CPU</th><th>Runs</th></tr></thead><tbody>",
+    );
+    for test in &report.tests {
+        let (mem, cpu) = match &test.budget {
+            Some(budget) => (budget.mem.to_string(), budget.cpu.to_string()),
+            None => (String::new(), String::new()),
+        };
+        let runs = test.iterations.map(|n| n.to_string()).unwrap_or_default();
+        let traces: Vec<String> = test.traces.iter().map(|t| escape_html(t)).collect();
+        html.push_str(&format!(
+            "<tr><td>{}</td><td style=\"color: {}\">{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            escape_html(&test.name),
+            if test.passed { "green" } else { "red" },
+            if test.passed { "PASS" } else { "FAIL" },
+            mem,
+            cpu,
+            runs,
+        ));
+        if let Some(counterexample) = &test.counterexample {
+            html.push_str(&format!(
+                "<tr><td colspan=\"5\">Counterexample: <code>{}</code></td></tr>",
+                escape_html(counterexample)
+            ));
+        }
+        if !test.coverage.is_empty() {
+            let labels: Vec<String> = test
+                .coverage
+                .iter()
+                .map(|(label, percent)| format!("{:.2}% {}", percent, escape_html(label)))
+                .collect();
+            html.push_str(&format!(
+                "<tr><td colspan=\"5\">Coverage: {}</td></tr>",
+                labels.join(", ")
+            ));
+        }
+        if !traces.is_empty() {
+            html.push_str(&format!(
+                "<tr><td colspan=\"5\"><pre>{}</pre></td></tr>",
+                traces.join("\n")
+            ));
+        }
+    }
+    html.push_str(&format!(
+        "</tbody></table><p>{} passed, {} failed",
+        report.passed(),
+        report.failed()
+    ));
+    if let Some(seed) = report.seed {
+        html.push_str(&format!(" (seed {})", seed));
+    }
+    html.push_str("</p>");
+    html
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use aiken_repl::evaluator::{ExBudget, Phase, ResultSummary, Timings};
+
+    use super::{
+        bundle_text, cell_options, escape_html, insert_svg, result_metadata, text_bundle,
+        timings_metadata,
+    };
+
+    #[test]
+    fn test_timings_metadata() {
+        let timings = Timings {
+            phases: vec![
+                (Phase::Typecheck, Duration::from_millis(250)),
+                (Phase::Eval, Duration::from_millis(500)),
+            ],
+        };
+        assert_eq!(
+            timings_metadata(&timings),
+            serde_json::json!({
+                "timings": { "typecheck": 0.25, "eval": 0.5, "total": 0.75 }
+            })
+        );
     }
 
-    pub fn control_address(&self) -> String {
-        format!("{}://{}:{}", self.transport, self.ip, self.control_port)
+    #[test]
+    fn test_result_metadata() {
+        let summary = ResultSummary {
+            kind: "value",
+            tipo: Some("Int".to_string()),
+            budget: Some(ExBudget {
+                mem: 200,
+                cpu: 1000,
+            }),
+            definitions: vec!["double".to_string()],
+        };
+        assert_eq!(
+            result_metadata(Some(&summary), None, "1.1.19"),
+            serde_json::json!({
+                "kind": "value",
+                "type": "Int",
+                "budget": { "cpu": 1000, "mem": 200 },
+                "definitions": ["double"],
+                "aiken_version": "1.1.19",
+            })
+        );
+        assert_eq!(
+            result_metadata(None, None, "1.1.19"),
+            serde_json::json!({ "kind": "magic", "definitions": [], "aiken_version": "1.1.19" })
+        );
     }
 
-    pub fn stdin_address(&self) -> String {
-        format!("{}://{}:{}", self.transport, self.ip, self.stdin_port)
+    #[test]
+    fn test_cell_options() {
+        let options = |metadata| {
+            cell_options(&metadata).map(|options| {
+                options
+                    .iter()
+                    .map(|(name, value)| format!("{name}={value}"))
+                    .collect::<Vec<_>>()
+            })
+        };
+        assert_eq!(options(serde_json::json!({})), Ok(vec![]));
+        assert_eq!(
+            options(serde_json::json!({
+                "iaiken": {
+                    "budget": { "cpu": 1000, "mem": 200 },
+                    "trace": "verbose",
+                    "warnings": false,
+                },
+                "cellId": "4f2a",
+            })),
+            Ok(vec![
+                "budget-cpu=1000".to_string(),
+                "budget-mem=200".to_string(),
+                "trace-level=verbose".to_string(),
+                "warnings=off".to_string(),
+            ])
+        );
+        assert!(options(serde_json::json!({ "iaiken": "verbose" })).is_err());
+        assert!(options(serde_json::json!({ "iaiken": { "max-depth": [1] } })).is_err());
     }
 
-    pub fn hb_address(&self) -> String {
-        format!("{}://{}:{}", self.transport, self.ip, self.hb_port)
+    #[test]
+    fn test_escape_html() {
+        assert_eq!(
+            escape_html("a < b && \"c\""),
+            "a &lt; b &amp;&amp; &quot;c&quot;"
+        );
     }
 
-    pub fn iopub_address(&self) -> String {
-        format!("{}://{}:{}", self.transport, self.ip, self.iopub_port)
+    #[test]
+    fn test_insert_svg() {
+        let mut data = text_bundle("1 < 2".to_string());
+        insert_svg(&mut data, "<svg></svg>".to_string());
+        assert_eq!(bundle_text(&data), "1 < 2");
+        assert_eq!(data["image/svg+xml"], "<svg></svg>");
+        assert_eq!(data["text/html"], "<pre>1 &lt; 2</pre><svg></svg>");
     }
 }
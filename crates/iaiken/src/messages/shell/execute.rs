@@ -1,35 +0,0 @@
-use serde::{Deserialize, Serialize};
-
-// DOCS: https://jupyter-client.readthedocs.io/en/latest/messaging.html#execute
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct ExecuteRequest {
-    pub code: String,                        // Source code to be executed by the kernel
-    pub silent: bool,                        // If true, execute as quietly as possible
-    pub store_history: bool,                 // If true, store this execution in the history
-    pub user_expressions: serde_json::Value, // Mapping of names to expressions to evaluate after execution
-    pub allow_stdin: bool, // If true, code running in the kernel can prompt the user for input
-    pub stop_on_error: bool, // If true, aborts the execution queue if an exception is encountered.
-}
-
-#[derive(Serialize, Deserialize, Debug, Clone)]
-#[serde(rename_all = "lowercase")]
-pub enum Status {
-    Ok,
-    Error,
-}
-
-#[derive(Serialize, Deserialize, Debug, Clone)]
-#[serde(tag = "status", rename_all = "lowercase")]
-pub enum ExecuteReply {
-    Ok {
-        execution_count: u32,
-        #[serde(skip_serializing_if = "Option::is_none")]
-        user_expressions: Option<serde_json::Value>,
-    },
-    Error {
-        execution_count: u32,
-        ename: String,
-        evalue: String,
-        traceback: Vec<String>,
-    },
-}
@@ -0,0 +1,153 @@
+//! Definition log: the cells that changed the definitions of a session, in order, so that the
+//! session can be restored with `%recover` after the kernel crashed or was restarted
+//!
+//! Each session appends to its own file in the log directory, one JSON string per line as in
+//! the history file. A file is only created once the session defines something, so the most
+//! recently written file other than the session's own is the one worth recovering.
+
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use jupyter_protocol::new_session_id;
+
+/// Number of logs of earlier sessions kept in the log directory
+pub const KEPT_LOGS: usize = 10;
+
+/// `$XDG_STATE_HOME/iaiken/sessions` (`~/.local/state/...`) on Linux, and the local data
+/// directory on other platforms
+pub fn default_dir() -> Option<PathBuf> {
+    dirs::state_dir()
+        .or_else(dirs::data_local_dir)
+        .map(|dir| dir.join("iaiken").join("sessions"))
+}
+
+/// The definition log of a session
+#[derive(Debug, Clone)]
+pub struct DefinitionLog {
+    dir: PathBuf,
+    path: PathBuf,
+}
+
+impl DefinitionLog {
+    /// Log of a new session, in `dir`
+    pub fn new(dir: PathBuf) -> Self {
+        let path = dir.join(format!("{}.jsonl", new_session_id()));
+        Self { dir, path }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Add a cell after the ones logged so far
+    pub fn append(&self, cell: &str) -> io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(file, "{}", serde_json::to_string(cell)?)
+    }
+
+    /// Cells of the most recent log of another session, oldest first. Empty when there is none.
+    pub fn previous(&self) -> Vec<String> {
+        self.others()
+            .into_iter()
+            .next_back()
+            .map(|(path, _)| load(&path))
+            .unwrap_or_default()
+    }
+
+    /// Remove the logs of other sessions but the `keep` most recent ones
+    pub fn prune(&self, keep: usize) {
+        let others = self.others();
+        let excess = others.len().saturating_sub(keep);
+        for (path, _) in &others[..excess] {
+            if let Err(e) = fs::remove_file(path) {
+                eprintln!("Failed to remove {}: {e}", path.display());
+            }
+        }
+    }
+
+    /// Logs of other sessions, least recently written first
+    fn others(&self) -> Vec<(PathBuf, SystemTime)> {
+        let Ok(entries) = fs::read_dir(&self.dir) else {
+            return Vec::new();
+        };
+        let mut logs: Vec<(PathBuf, SystemTime)> = entries
+            .filter_map(|entry| {
+                let path = entry.ok()?.path();
+                let is_log = path.extension().is_some_and(|ext| ext == "jsonl");
+                let modified = fs::metadata(&path).and_then(|m| m.modified()).ok()?;
+                (is_log && path != self.path).then_some((path, modified))
+            })
+            .collect();
+        logs.sort_by_key(|(_, modified)| *modified);
+        logs
+    }
+}
+
+/// Cells of a log. Unreadable lines, such as one cut short by a crash, are skipped.
+fn load(path: &Path) -> Vec<String> {
+    fs::read_to_string(path)
+        .map(|content| {
+            content
+                .lines()
+                .filter_map(|line| serde_json::from_str(line).ok())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        fs,
+        time::{Duration, SystemTime},
+    };
+
+    use super::{DefinitionLog, load};
+
+    #[test]
+    fn test_definition_log() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let dir = dir.path().join("sessions");
+
+        let crashed = DefinitionLog::new(dir.clone());
+        crashed.append("pub fn double(x) {\n  x * 2\n}").unwrap();
+        crashed.append("%forget double").unwrap();
+        assert_eq!(
+            load(crashed.path()),
+            vec!["pub fn double(x) {\n  x * 2\n}", "%forget double"]
+        );
+
+        // An older session, and a line cut short when the kernel died
+        let older = DefinitionLog::new(dir.clone());
+        older.append("const x = 1").unwrap();
+        let file = fs::File::options().write(true).open(older.path()).unwrap();
+        file.set_modified(SystemTime::now() - Duration::from_secs(60))
+            .unwrap();
+        fs::write(
+            crashed.path(),
+            fs::read_to_string(crashed.path()).unwrap() + "\"const y",
+        )
+        .unwrap();
+
+        let current = DefinitionLog::new(dir.clone());
+        assert_eq!(
+            current.previous(),
+            vec!["pub fn double(x) {\n  x * 2\n}", "%forget double"]
+        );
+        // The session's own log is never the previous one
+        current.append("const z = 3").unwrap();
+        assert_eq!(current.previous().len(), 2);
+
+        current.prune(1);
+        assert!(!older.path().exists());
+        assert!(crashed.path().exists() && current.path().exists());
+    }
+}
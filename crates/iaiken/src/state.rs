@@ -0,0 +1,307 @@
+//! State of a kernel session.
+//!
+//! `KernelState` is created by `run_kernel` and shared (behind an `Arc`) by every channel
+//! handler. Everything that belongs to the session lives here rather than in statics, so a
+//! restart can reset it and several sessions could coexist in one process.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use aiken_repl::{
+    config::ErrorFormat,
+    evaluator::{ArtifactCache, MockChain, ReplEvaluator},
+    history::History,
+};
+use jupyter_protocol::{ConnectionConfig, new_session_id};
+
+use crate::eval::worker::EvalWorker;
+use crate::recovery::{DefinitionLog, KEPT_LOGS};
+
+/// Settings the kernel was started with
+pub struct KernelConfig {
+    pub connection: ConnectionConfig,
+    pub codemirror_mode: String,
+    /// Where executed cells are saved between sessions, if anywhere
+    pub history: Option<History>,
+    /// Where the cells that change the definitions are logged for `%recover`, if anywhere
+    pub definition_log: Option<DefinitionLog>,
+    /// Options every evaluator starts with, by name, from the configuration file and the
+    /// command line
+    pub options: Vec<(String, String)>,
+    /// How long a cell runs before its running time is shown
+    pub progress_delay: Duration,
+    /// Bytes a cell prints at most
+    pub max_output: usize,
+    /// How errors and warnings are shown
+    pub error_format: ErrorFormat,
+}
+
+impl KernelConfig {
+    /// A fresh evaluator, with the configured options
+    pub fn new_evaluator(&self) -> ReplEvaluator {
+        let mut eval = ReplEvaluator::new();
+        eval.set_artifact_cache(ArtifactCache::user());
+        for (name, value) in &self.options {
+            if let Err(e) = eval.set_option(name, value) {
+                eprintln!("Ignoring option {}: {}", name, e);
+            }
+        }
+        eval
+    }
+}
+
+/// A cell that was executed with `store_history`
+#[derive(Debug, Clone)]
+pub struct HistoryEntry {
+    pub execution_count: u32,
+    pub code: String,
+    /// Text of the result, if the cell evaluated to something
+    pub output: Option<String>,
+}
+
+/// The mock chain of `%chain init`, if it was started
+pub type SharedMockChain = Arc<Mutex<Option<MockChain>>>;
+
+pub struct KernelState {
+    pub config: KernelConfig,
+    /// Session id used for the messages the kernel sends on its own (eg. `starting` status)
+    pub session: String,
+    /// Evaluator of the parent shell
+    evaluator: EvalWorker,
+    /// Evaluators of the subshells created by the frontend, by subshell id. Each has its own
+    /// definitions.
+    subshells: Mutex<HashMap<String, EvalWorker>>,
+    subshell_counter: AtomicU32,
+    execution_count: AtomicU32,
+    history: Mutex<Vec<HistoryEntry>>,
+    /// Inputs of earlier sessions, as the history file was when the kernel started
+    previous_history: Vec<String>,
+    /// Definition cells of the last session, as its log was when the kernel started
+    previous_definitions: Vec<String>,
+    /// Comms opened by the frontend: target name by comm id
+    comms: Mutex<HashMap<String, String>>,
+    /// Ledger emulated for walkthroughs, shared by the shell and subshells
+    mock_chain: SharedMockChain,
+}
+
+pub type SharedState = Arc<KernelState>;
+
+/// Lock `mutex`, even if a thread panicked while holding it. What's behind the kernel's mutexes
+/// stays consistent between two statements, so one bad cell shouldn't leave it locked for good.
+pub fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|poisoned| {
+        mutex.clear_poison();
+        poisoned.into_inner()
+    })
+}
+
+impl KernelState {
+    pub fn new(config: KernelConfig) -> SharedState {
+        let previous_history = config
+            .history
+            .as_ref()
+            .map(History::load)
+            .unwrap_or_default();
+        let previous_definitions = config
+            .definition_log
+            .as_ref()
+            .map(|log| {
+                log.prune(KEPT_LOGS);
+                log.previous()
+            })
+            .unwrap_or_default();
+        let evaluator = EvalWorker::spawn(config.new_evaluator());
+        Arc::new(Self {
+            config,
+            previous_history,
+            previous_definitions,
+            session: new_session_id(),
+            evaluator,
+            subshells: Mutex::new(HashMap::new()),
+            subshell_counter: AtomicU32::new(0),
+            execution_count: AtomicU32::new(0),
+            history: Mutex::new(Vec::new()),
+            comms: Mutex::new(HashMap::new()),
+            mock_chain: Arc::new(Mutex::new(None)),
+        })
+    }
+
+    /// Evaluator of the parent shell
+    pub fn evaluator(&self) -> EvalWorker {
+        self.evaluator.clone()
+    }
+
+    pub fn mock_chain(&self) -> SharedMockChain {
+        self.mock_chain.clone()
+    }
+
+    /// Evaluator of a subshell, None if there is no such subshell
+    pub fn subshell_evaluator(&self, subshell_id: &str) -> Option<EvalWorker> {
+        lock(&self.subshells).get(subshell_id).cloned()
+    }
+
+    /// Create a subshell with a fresh evaluator and return its id
+    pub fn create_subshell(&self) -> String {
+        let n = self.subshell_counter.fetch_add(1, Ordering::SeqCst) + 1;
+        let subshell_id = format!("subshell-{n}");
+        lock(&self.subshells).insert(
+            subshell_id.clone(),
+            EvalWorker::spawn(self.config.new_evaluator()),
+        );
+        subshell_id
+    }
+
+    /// Delete a subshell, returns false if there was no such subshell
+    pub async fn delete_subshell(&self, subshell_id: &str) -> bool {
+        let removed = lock(&self.subshells).remove(subshell_id);
+        match removed {
+            Some(evaluator) => {
+                cleanup_evaluator(&evaluator).await;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn list_subshells(&self) -> Vec<String> {
+        let mut ids: Vec<String> = lock(&self.subshells).keys().cloned().collect();
+        ids.sort();
+        ids
+    }
+
+    /// Number of the last cell stored in the history
+    pub fn execution_count(&self) -> u32 {
+        self.execution_count.load(Ordering::SeqCst)
+    }
+
+    /// Increment the execution counter and return the new value
+    pub fn next_execution_count(&self) -> u32 {
+        self.execution_count.fetch_add(1, Ordering::SeqCst) + 1
+    }
+
+    /// Start the execution count over, so that the next cell is number 1 (`%reset --hard`)
+    pub fn reset_execution_count(&self) {
+        self.execution_count.store(0, Ordering::SeqCst);
+    }
+
+    pub fn record_history(&self, execution_count: u32, code: &str) {
+        lock(&self.history).push(HistoryEntry {
+            execution_count,
+            code: code.to_string(),
+            output: None,
+        });
+        if let Some(file) = &self.config.history
+            && let Err(e) = file.add(code)
+        {
+            eprintln!("Failed to save history to {}: {e}", file.path().display());
+        }
+    }
+
+    /// Attach the result of a cell to its history entry
+    pub fn record_output(&self, execution_count: u32, output: &str) {
+        if let Some(entry) = lock(&self.history)
+            .iter_mut()
+            .rev()
+            .find(|entry| entry.execution_count == execution_count)
+        {
+            entry.output = Some(output.to_string());
+        }
+    }
+
+    pub fn history(&self) -> Vec<HistoryEntry> {
+        lock(&self.history).clone()
+    }
+
+    pub fn previous_history(&self) -> &[String] {
+        &self.previous_history
+    }
+
+    /// Log cells of the parent shell that changed the definitions
+    pub fn log_definitions(&self, cells: &[String]) {
+        let Some(log) = &self.config.definition_log else {
+            return;
+        };
+        for cell in cells {
+            if let Err(e) = log.append(cell) {
+                eprintln!("Failed to log definitions to {}: {e}", log.path().display());
+                return;
+            }
+        }
+    }
+
+    pub fn previous_definitions(&self) -> &[String] {
+        &self.previous_definitions
+    }
+
+    pub fn open_comm(&self, comm_id: &str, target_name: &str) {
+        lock(&self.comms).insert(comm_id.to_string(), target_name.to_string());
+    }
+
+    pub fn close_comm(&self, comm_id: &str) {
+        lock(&self.comms).remove(comm_id);
+    }
+
+    /// Open comms (target name by comm id), only those of `target_name` if given
+    pub fn comms(&self, target_name: Option<&str>) -> HashMap<String, String> {
+        lock(&self.comms)
+            .iter()
+            .filter(|(_, target)| target_name.is_none_or(|name| name == target.as_str()))
+            .map(|(id, target)| (id.clone(), target.clone()))
+            .collect()
+    }
+
+    /// Start over with a fresh evaluator, counter and history (used when restarting)
+    pub async fn reset(&self) {
+        self.cleanup().await;
+        let fresh = self.config.new_evaluator();
+        self.evaluator.run(move |eval| *eval = fresh).await;
+        lock(&self.subshells).clear();
+        self.reset_execution_count();
+        lock(&self.history).clear();
+        lock(&self.comms).clear();
+        *lock(&self.mock_chain) = None;
+    }
+
+    /// Remove the temp projects of the shell and subshells before the kernel exits
+    pub async fn cleanup(&self) {
+        cleanup_evaluator(&self.evaluator).await;
+        let subshells: Vec<EvalWorker> = lock(&self.subshells).values().cloned().collect();
+        for evaluator in &subshells {
+            cleanup_evaluator(evaluator).await;
+        }
+    }
+}
+
+async fn cleanup_evaluator(evaluator: &EvalWorker) {
+    if let Some(Err(e)) = evaluator.run(|eval| eval.cleanup()).await {
+        eprintln!("Failed to remove temporary project: {e}");
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Mutex;
+
+    use super::lock;
+
+    #[test]
+    fn test_lock_after_panic() {
+        let mutex = Mutex::new(vec![1]);
+        let _ = std::thread::scope(|scope| {
+            scope
+                .spawn(|| {
+                    let mut numbers = lock(&mutex);
+                    numbers.push(2);
+                    panic!("bad cell while holding the lock");
+                })
+                .join()
+        });
+        assert!(mutex.is_poisoned());
+
+        lock(&mutex).push(3);
+        assert_eq!(*lock(&mutex), [1, 2, 3]);
+        assert!(!mutex.is_poisoned());
+    }
+}
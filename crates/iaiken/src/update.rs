@@ -0,0 +1,186 @@
+//! `iaiken --update`: replace the kernel binary with the one of the latest GitHub release
+//!
+//! Each release has a binary per platform, eg. `iaiken-x86_64-linux`, next to its SHA-256 in
+//! `iaiken-x86_64-linux.sha256`. The binary is only swapped in once its checksum matches, and
+//! the kernelspecs are then pointed at it.
+
+use std::{
+    env, fs,
+    io::{self, Read},
+    path::Path,
+};
+
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use crate::install;
+
+const LATEST_RELEASE: &str = "https://api.github.com/repos/rober-m/iaiken/releases/latest";
+
+#[derive(Deserialize)]
+struct Release {
+    tag_name: String,
+    assets: Vec<Asset>,
+}
+
+#[derive(Deserialize)]
+struct Asset {
+    name: String,
+    browser_download_url: String,
+}
+
+/// Name of the release asset with the binary for `os` and `arch`, eg. `iaiken-x86_64-linux`
+fn asset_name(os: &str, arch: &str) -> String {
+    let extension = if os == "windows" { ".exe" } else { "" };
+    format!("iaiken-{arch}-{os}{extension}")
+}
+
+/// Whether the version of the release tagged `tag`, eg. `v0.1.0`, is later than `current`
+fn is_newer(tag: &str, current: &str) -> bool {
+    let numbers = |version: &str| -> Vec<u64> {
+        let version = version.trim_start_matches('v');
+        // Pre-release and build suffixes are ignored
+        let version = version.split(['-', '+']).next().unwrap_or_default();
+        version
+            .split('.')
+            .map(|number| number.parse().unwrap_or(0))
+            .collect()
+    };
+    numbers(tag) > numbers(current)
+}
+
+/// The hex SHA-256 of a checksum file, alone or in `sha256sum`'s format
+fn parse_checksum(contents: &str) -> Option<String> {
+    let checksum = contents.split_whitespace().next()?;
+    (checksum.len() == 64 && checksum.chars().all(|c| c.is_ascii_hexdigit()))
+        .then(|| checksum.to_ascii_lowercase())
+}
+
+fn verify(binary: &[u8], checksum: &str) -> anyhow::Result<()> {
+    let actual = hex::encode(Sha256::digest(binary));
+    if actual != checksum {
+        anyhow::bail!("Checksum mismatch: expected {checksum}, downloaded {actual}");
+    }
+    Ok(())
+}
+
+fn get(url: &str) -> anyhow::Result<ureq::Response> {
+    let user_agent = concat!("iaiken/", env!("CARGO_PKG_VERSION"));
+    Ok(ureq::get(url).set("User-Agent", user_agent).call()?)
+}
+
+pub fn update() -> anyhow::Result<()> {
+    println!("Checking for updates...");
+    let release: Release = serde_json::from_str(&get(LATEST_RELEASE)?.into_string()?)?;
+    let current = env!("CARGO_PKG_VERSION");
+    if !is_newer(&release.tag_name, current) {
+        println!("iaiken {current} is up to date");
+        return Ok(());
+    }
+
+    let name = asset_name(env::consts::OS, env::consts::ARCH);
+    let asset = |name: &str| {
+        release
+            .assets
+            .iter()
+            .find(|asset| asset.name == name)
+            .ok_or_else(|| anyhow::anyhow!("Release {} has no {name}", release.tag_name))
+    };
+    let binary = asset(&name)?;
+    let checksum = asset(&format!("{name}.sha256"))?;
+
+    println!("Downloading iaiken {}...", release.tag_name);
+    let checksum = parse_checksum(&get(&checksum.browser_download_url)?.into_string()?)
+        .ok_or_else(|| anyhow::anyhow!("Could not read the checksum of {name}"))?;
+    let mut bytes = Vec::new();
+    get(&binary.browser_download_url)?
+        .into_reader()
+        .read_to_end(&mut bytes)?;
+    verify(&bytes, &checksum)?;
+
+    let executable = env::current_exe()?;
+    replace(&executable, &bytes)?;
+    println!(
+        "Updated {} from {current} to {}",
+        executable.display(),
+        release.tag_name
+    );
+
+    for spec in install::repoint_kernelspecs(&executable) {
+        println!("Kernel spec {} now starts the update", spec.display());
+    }
+    Ok(())
+}
+
+/// Swap `executable` for `binary`. The binary is written next to it first, so that a failed
+/// write leaves it untouched, and the running executable is moved aside rather than removed,
+/// which Windows doesn't allow.
+fn replace(executable: &Path, binary: &[u8]) -> io::Result<()> {
+    let new = executable.with_extension("new");
+    let old = executable.with_extension("old");
+    // Left behind on Windows by the previous update
+    let _ = fs::remove_file(&old);
+
+    fs::write(&new, binary)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&new, fs::Permissions::from_mode(0o755))?;
+    }
+    fs::rename(executable, &old)?;
+    if let Err(e) = fs::rename(&new, executable) {
+        let _ = fs::rename(&old, executable);
+        return Err(e);
+    }
+    let _ = fs::remove_file(&old);
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{asset_name, is_newer, parse_checksum, replace, verify};
+
+    #[test]
+    fn test_release_assets() {
+        assert_eq!(asset_name("linux", "x86_64"), "iaiken-x86_64-linux");
+        assert_eq!(asset_name("windows", "x86_64"), "iaiken-x86_64-windows.exe");
+
+        assert!(is_newer("v0.1.0", "0.0.1"));
+        assert!(is_newer("0.0.10", "0.0.9"));
+        assert!(is_newer("v1.0.0-rc.1", "0.9.0"));
+        assert!(!is_newer("v0.0.1", "0.0.1"));
+        assert!(!is_newer("v0.0.1", "0.1.0"));
+    }
+
+    #[test]
+    fn test_verify() {
+        // As `sha256sum` writes it, in either case
+        let checksum = "C1B3E7B5D9B1A8C55E4F7F0D7B8F6F0B6B1F4A0E5C9C3D2A1B0F9E8D7C6B5A4F";
+        let file = format!("{checksum}  iaiken-x86_64-linux\n");
+        assert_eq!(parse_checksum(&file), Some(checksum.to_ascii_lowercase()));
+        assert_eq!(parse_checksum("not a checksum"), None);
+        assert_eq!(parse_checksum(""), None);
+
+        let checksum = hex::encode(<sha2::Sha256 as sha2::Digest>::digest(b"iaiken"));
+        assert!(verify(b"iaiken", &checksum).is_ok());
+        assert!(verify(b"tampered", &checksum).is_err());
+    }
+
+    #[test]
+    fn test_replace() {
+        let dir = tempfile::tempdir().unwrap();
+        let executable = dir.path().join("iaiken");
+        std::fs::write(&executable, "old").unwrap();
+
+        replace(&executable, b"new").unwrap();
+        assert_eq!(std::fs::read(&executable).unwrap(), b"new");
+        // Nothing is left next to it
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&executable).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o755);
+        }
+    }
+}
@@ -0,0 +1,174 @@
+//! Kernelspec installation: run `iaiken --install` and `--uninstall` against a home directory of
+//! their own, and check the kernelspec lands where Jupyter looks for it on the platform, or in
+//! the prefix it's given.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// `iaiken` with `home` as the home directory, and as `%APPDATA%` its Windows counterpart
+fn iaiken(home: &Path) -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_iaiken"));
+    command
+        .env("HOME", home)
+        .env("USERPROFILE", home)
+        .env("APPDATA", home.join("AppData").join("Roaming"))
+        .env_remove("JUPYTER_DATA_DIR")
+        .env_remove("XDG_DATA_HOME");
+    command
+}
+
+/// Where Jupyter looks for the kernelspecs of the user
+fn kernels_dir(home: &Path) -> PathBuf {
+    let data_dir = if cfg!(windows) {
+        home.join("AppData").join("Roaming").join("jupyter")
+    } else if cfg!(target_os = "macos") {
+        home.join("Library").join("Jupyter")
+    } else {
+        home.join(".local").join("share").join("jupyter")
+    };
+    data_dir.join("kernels")
+}
+
+#[test]
+fn test_install_and_uninstall() {
+    let home = tempfile::tempdir().unwrap();
+    let kernel_dir = kernels_dir(home.path()).join("aiken");
+
+    let status = iaiken(home.path()).arg("--install").status().unwrap();
+    assert!(status.success());
+    let spec: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(kernel_dir.join("kernel.json")).unwrap()).unwrap();
+    assert_eq!(spec["language"], "aiken");
+    let executable = PathBuf::from(spec["argv"][0].as_str().unwrap());
+    assert_eq!(
+        executable.canonicalize().unwrap(),
+        Path::new(env!("CARGO_BIN_EXE_iaiken"))
+            .canonicalize()
+            .unwrap()
+    );
+    assert!(kernel_dir.join("kernel.js").exists());
+
+    let status = iaiken(home.path()).arg("--uninstall").status().unwrap();
+    assert!(status.success());
+    assert!(!kernel_dir.exists());
+}
+
+#[test]
+fn test_jupyter_data_dir_override() {
+    let home = tempfile::tempdir().unwrap();
+    let data_dir = home.path().join("custom data");
+
+    let status = iaiken(home.path())
+        .env("JUPYTER_DATA_DIR", &data_dir)
+        .arg("--install")
+        .status()
+        .unwrap();
+    assert!(status.success());
+    assert!(
+        data_dir
+            .join("kernels")
+            .join("aiken")
+            .join("kernel.json")
+            .exists()
+    );
+    assert!(!kernels_dir(home.path()).exists());
+}
+
+#[test]
+fn test_install_into_prefix() {
+    let home = tempfile::tempdir().unwrap();
+    let prefix = tempfile::tempdir().unwrap();
+    let kernel_dir = prefix
+        .path()
+        .join("share")
+        .join("jupyter")
+        .join("kernels")
+        .join("aiken");
+
+    let status = iaiken(home.path())
+        .arg("--install")
+        .arg("--prefix")
+        .arg(prefix.path())
+        .status()
+        .unwrap();
+    assert!(status.success());
+    assert!(kernel_dir.join("kernel.json").exists());
+    // Nothing is written to the home directory
+    assert_eq!(std::fs::read_dir(home.path()).unwrap().count(), 0);
+
+    let status = iaiken(home.path())
+        .arg("--uninstall")
+        .arg("--prefix")
+        .arg(prefix.path())
+        .status()
+        .unwrap();
+    assert!(status.success());
+    assert!(!kernel_dir.exists());
+}
+
+#[test]
+fn test_kernelspec_json() {
+    let home = tempfile::tempdir().unwrap();
+    let output = iaiken(home.path())
+        .arg("--kernelspec-json")
+        .arg("--codemirror-mode")
+        .arg("rust")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let spec: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(spec["display_name"], "Aiken");
+    assert_eq!(spec["argv"][1], "--connection-file");
+    assert_eq!(spec["argv"][4], "rust");
+    assert_eq!(std::fs::read_dir(home.path()).unwrap().count(), 0);
+}
+
+#[test]
+fn test_uninstall_partial_install() {
+    let home = tempfile::tempdir().unwrap();
+    let kernel_dir = kernels_dir(home.path()).join("aiken");
+    std::fs::create_dir_all(&kernel_dir).unwrap();
+    std::fs::write(kernel_dir.join("kernel.js"), "").unwrap();
+
+    let status = iaiken(home.path()).arg("--uninstall").status().unwrap();
+    assert!(status.success());
+    assert!(!kernel_dir.exists());
+
+    // Nothing left to remove is not an error
+    let output = iaiken(home.path())
+        .arg("--uninstall")
+        .arg("--prefix")
+        .arg(home.path())
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("No Aiken kernel found"));
+}
+
+#[test]
+fn test_uninstall_all() {
+    let home = tempfile::tempdir().unwrap();
+    let prefix = tempfile::tempdir().unwrap();
+    let kernels = prefix.path().join("share").join("jupyter").join("kernels");
+    let spec = |name: &str, language: &str| {
+        std::fs::create_dir_all(kernels.join(name)).unwrap();
+        let spec =
+            serde_json::json!({"argv": ["kernel"], "display_name": name, "language": language});
+        std::fs::write(kernels.join(name).join("kernel.json"), spec.to_string()).unwrap();
+    };
+    spec("aiken", "aiken");
+    spec("aiken-plutus-v2", "aiken");
+    spec("python3", "python");
+
+    let status = iaiken(home.path())
+        .arg("--uninstall")
+        .arg("--all")
+        .arg("--prefix")
+        .arg(prefix.path())
+        .status()
+        .unwrap();
+    assert!(status.success());
+    assert!(!kernels.join("aiken").exists());
+    assert!(!kernels.join("aiken-plutus-v2").exists());
+    assert!(kernels.join("python3").exists());
+}
@@ -0,0 +1,767 @@
+//! Protocol conformance tests: run the kernel binary against a generated connection file and
+//! talk to it over ZMQ like a Jupyter client would.
+
+use std::net::TcpListener;
+use std::process::{Child, Command, Stdio};
+use std::time::Duration;
+
+use jupyter_protocol::crypto::sign_message;
+use jupyter_protocol::wire::send_bytes;
+use jupyter_protocol::{ConnectionConfig, JupyterMessage, MessageHeader};
+use zeromq::{DealerSocket, Socket, SocketRecv, SubSocket};
+
+const KEY: &str = "a0436f6c-1916-498b-8eb9-e81ab9368e84";
+const SCHEME: &str = "hmac-sha256";
+const TIMEOUT: Duration = Duration::from_secs(120);
+
+type Message = JupyterMessage<serde_json::Value>;
+
+/// A minimal Jupyter client connected to a freshly started kernel
+struct Client {
+    kernel: Child,
+    shell: DealerSocket,
+    control: DealerSocket,
+    iopub: SubSocket,
+    session: String,
+    /// Subshell that shell requests are addressed to, None for the parent shell
+    subshell_id: Option<String>,
+    /// Types of the IOPub messages received while waiting for the subscription to go through
+    warmup: Vec<String>,
+    _connection_dir: tempfile::TempDir,
+}
+
+fn free_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0")
+        .and_then(|listener| listener.local_addr())
+        .map(|addr| addr.port())
+        .expect("Failed to find a free port")
+}
+
+impl Client {
+    async fn start() -> Self {
+        let config = ConnectionConfig {
+            transport: "tcp".to_string(),
+            ip: "127.0.0.1".to_string(),
+            signature_scheme: SCHEME.to_string(),
+            key: KEY.to_string(),
+            control_port: free_port(),
+            shell_port: free_port(),
+            stdin_port: free_port(),
+            hb_port: free_port(),
+            iopub_port: free_port(),
+        };
+
+        let connection_dir = tempfile::tempdir().unwrap();
+        let connection_file = connection_dir.path().join("connection.json");
+        std::fs::write(&connection_file, serde_json::to_string(&config).unwrap()).unwrap();
+
+        let kernel = Command::new(env!("CARGO_BIN_EXE_iaiken"))
+            .arg("--connection-file")
+            .arg(&connection_file)
+            .stdout(Stdio::null())
+            .spawn()
+            .expect("Failed to start kernel");
+
+        let mut client = Client {
+            kernel,
+            shell: DealerSocket::new(),
+            control: DealerSocket::new(),
+            iopub: SubSocket::new(),
+            session: "test-session".to_string(),
+            subshell_id: None,
+            warmup: Vec::new(),
+            _connection_dir: connection_dir,
+        };
+
+        // The kernel needs some time to bind its sockets
+        for _ in 0..100 {
+            if client.shell.connect(&config.shell_address()).await.is_ok() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        client
+            .control
+            .connect(&config.control_address())
+            .await
+            .unwrap();
+        client.iopub.connect(&config.iopub_address()).await.unwrap();
+        client.iopub.subscribe("").await.unwrap();
+
+        client.wait_for_iopub().await;
+        client
+    }
+
+    /// IOPub subscriptions take effect asynchronously: poke the kernel until status messages arrive
+    async fn wait_for_iopub(&mut self) {
+        for _ in 0..50 {
+            self.request(Channel::Shell, "kernel_info_request", serde_json::json!({}))
+                .await;
+            let window = Duration::from_millis(200);
+            // Drain whatever is left from the warm-up requests
+            while let Ok(msg) = tokio::time::timeout(window, recv_verified(&mut self.iopub)).await {
+                self.warmup.push(msg.header.msg_type);
+            }
+            if !self.warmup.is_empty() {
+                return;
+            }
+        }
+        panic!("Never received any IOPub message");
+    }
+
+    async fn send(
+        &mut self,
+        channel: Channel,
+        msg_type: &str,
+        content: serde_json::Value,
+    ) -> MessageHeader {
+        let mut header = MessageHeader::new(self.session.clone(), msg_type.to_string());
+        if let Channel::Shell = channel {
+            header.subshell_id = self.subshell_id.clone();
+        }
+        let msg = JupyterMessage {
+            header: header.clone(),
+            parent_header: None,
+            metadata: serde_json::json!({}),
+            content,
+        };
+        let frames = msg
+            .to_envelope_multipart(vec![b"<IDS|MSG>".to_vec()], 0, KEY, SCHEME)
+            .unwrap();
+        let socket = match channel {
+            Channel::Shell => &mut self.shell,
+            Channel::Control => &mut self.control,
+        };
+        send_bytes(socket, frames).await.unwrap();
+        header
+    }
+
+    /// Send a message of type `msg_type` with raw `content` bytes, signed with `key`
+    async fn send_raw(
+        &mut self,
+        channel: Channel,
+        msg_type: &str,
+        content: &[u8],
+        key: &str,
+    ) -> MessageHeader {
+        let header = MessageHeader::new(self.session.clone(), msg_type.to_string());
+        let header_bytes = serde_json::to_vec(&header).unwrap();
+        let signature = sign_message(key, SCHEME, &header_bytes, b"{}", b"{}", content).unwrap();
+        let frames: Vec<bytes::Bytes> = [
+            b"<IDS|MSG>".to_vec(),
+            signature.into_bytes(),
+            header_bytes,
+            b"{}".to_vec(),
+            b"{}".to_vec(),
+            content.to_vec(),
+        ]
+        .into_iter()
+        .map(Into::into)
+        .collect();
+        let socket = match channel {
+            Channel::Shell => &mut self.shell,
+            Channel::Control => &mut self.control,
+        };
+        send_bytes(socket, frames).await.unwrap();
+        header
+    }
+
+    /// Send a request and wait for its reply, checking signature and parent header
+    async fn request(
+        &mut self,
+        channel: Channel,
+        msg_type: &str,
+        content: serde_json::Value,
+    ) -> Message {
+        let header = self.send(channel, msg_type, content).await;
+        let socket = match channel {
+            Channel::Shell => &mut self.shell,
+            Channel::Control => &mut self.control,
+        };
+        let reply = recv_verified(socket).await;
+        let reply_type = msg_type.replace("_request", "_reply");
+        assert_eq!(reply.header.msg_type, reply_type);
+        assert_eq!(
+            reply.parent_header.as_ref().map(|p| &p.msg_id),
+            Some(&header.msg_id)
+        );
+        reply
+    }
+
+    /// Collect the IOPub messages caused by `parent` until the kernel goes back to idle
+    async fn iopub_until_idle(&mut self, parent: &MessageHeader) -> Vec<Message> {
+        let mut messages = Vec::new();
+        loop {
+            let msg = recv_verified(&mut self.iopub).await;
+            let is_child = msg
+                .parent_header
+                .as_ref()
+                .is_some_and(|p| p.msg_id == parent.msg_id);
+            if !is_child {
+                continue;
+            }
+            let idle = msg.header.msg_type == "status" && msg.content["execution_state"] == "idle";
+            messages.push(msg);
+            if idle {
+                return messages;
+            }
+        }
+    }
+
+    async fn execute(&mut self, code: &str) -> (Message, Vec<Message>) {
+        self.execute_with_expressions(code, serde_json::json!({}))
+            .await
+    }
+
+    async fn execute_with_expressions(
+        &mut self,
+        code: &str,
+        user_expressions: serde_json::Value,
+    ) -> (Message, Vec<Message>) {
+        let reply = self
+            .request(
+                Channel::Shell,
+                "execute_request",
+                serde_json::json!({
+                    "code": code,
+                    "silent": false,
+                    "store_history": true,
+                    "user_expressions": user_expressions,
+                    "allow_stdin": false,
+                    "stop_on_error": true,
+                }),
+            )
+            .await;
+        let parent = reply.parent_header.clone().unwrap();
+        let iopub = self.iopub_until_idle(&parent).await;
+        (reply, iopub)
+    }
+}
+
+impl Drop for Client {
+    fn drop(&mut self) {
+        let _ = self.kernel.kill();
+        let _ = self.kernel.wait();
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Channel {
+    Shell,
+    Control,
+}
+
+/// Receive a message and check that it is correctly signed
+async fn recv_verified<S: SocketRecv>(socket: &mut S) -> Message {
+    let message = tokio::time::timeout(TIMEOUT, socket.recv())
+        .await
+        .expect("Timed out waiting for the kernel")
+        .expect("Failed to receive message");
+    let frames: Vec<Vec<u8>> = message.iter().map(|f| f.to_vec()).collect();
+
+    let ix = frames.iter().position(|f| f == b"<IDS|MSG>").unwrap();
+    let expected = sign_message(
+        KEY,
+        SCHEME,
+        &frames[ix + 2],
+        &frames[ix + 3],
+        &frames[ix + 4],
+        &frames[ix + 5],
+    )
+    .unwrap();
+    assert_eq!(frames[ix + 1], expected.into_bytes(), "Bad signature");
+
+    JupyterMessage::from_multipart(&frames, KEY, SCHEME).unwrap()
+}
+
+fn msg_types(messages: &[Message]) -> Vec<&str> {
+    messages
+        .iter()
+        .map(|m| m.header.msg_type.as_str())
+        .collect()
+}
+
+#[tokio::test]
+async fn test_kernel_info() {
+    let mut client = Client::start().await;
+
+    let reply = client
+        .request(Channel::Shell, "kernel_info_request", serde_json::json!({}))
+        .await;
+    assert_eq!(reply.content["status"], "ok");
+    assert_eq!(reply.content["protocol_version"], "5.5");
+    assert_eq!(reply.content["language_info"]["name"], "aiken");
+
+    let iopub = client
+        .iopub_until_idle(reply.parent_header.as_ref().unwrap())
+        .await;
+    assert_eq!(msg_types(&iopub), ["status", "status"]);
+    assert_eq!(iopub[0].content["execution_state"], "busy");
+}
+
+#[tokio::test]
+async fn test_iopub_welcome() {
+    let client = Client::start().await;
+    assert!(
+        client.warmup.iter().any(|t| t == "iopub_welcome"),
+        "No iopub_welcome in {:?}",
+        client.warmup
+    );
+}
+
+#[tokio::test]
+async fn test_execute_ok() {
+    let mut client = Client::start().await;
+
+    let (reply, iopub) = client.execute("1 + 2").await;
+    assert_eq!(reply.content["status"], "ok");
+    assert_eq!(reply.content["execution_count"], 1);
+
+    assert_eq!(
+        msg_types(&iopub),
+        ["status", "execute_input", "execute_result", "status"]
+    );
+    assert_eq!(iopub[0].content["execution_state"], "busy");
+    assert_eq!(iopub[1].content["code"], "1 + 2");
+    assert_eq!(iopub[1].content["execution_count"], 1);
+    assert_eq!(iopub[2].content["data"]["text/plain"], "3 : Int");
+    assert_eq!(iopub[2].content["execution_count"], 1);
+    // What the result is, and where the time of the cell went, in seconds
+    let metadata = &iopub[2].content["metadata"];
+    assert_eq!(metadata["kind"], "value");
+    assert_eq!(metadata["type"], "Int");
+    assert!(metadata["budget"]["cpu"].is_i64());
+    assert!(metadata["aiken_version"].is_string());
+    assert!(metadata["timings"]["total"].is_f64());
+}
+
+#[tokio::test]
+async fn test_execution_count_increments() {
+    let mut client = Client::start().await;
+
+    let (reply, _) = client.execute("pub const x = 1").await;
+    assert_eq!(reply.content["execution_count"], 1);
+
+    let (reply, iopub) = client.execute("x + 1").await;
+    assert_eq!(reply.content["execution_count"], 2);
+    assert_eq!(iopub[2].content["data"]["text/plain"], "2 : Int");
+}
+
+#[tokio::test]
+async fn test_user_expressions() {
+    let mut client = Client::start().await;
+
+    let (reply, _) = client
+        .execute_with_expressions(
+            "pub const x = 1",
+            serde_json::json!({ "sum": "1 + 2", "bad": "this_is_not_defined" }),
+        )
+        .await;
+    assert_eq!(reply.content["status"], "ok");
+
+    let results = &reply.content["user_expressions"];
+    assert_eq!(results["sum"]["status"], "ok");
+    assert_eq!(results["sum"]["data"]["text/plain"], "3 : Int");
+    assert_eq!(results["bad"]["status"], "error");
+    assert_eq!(results["bad"]["ename"], "AikenError");
+}
+
+#[tokio::test]
+async fn test_history() {
+    let mut client = Client::start().await;
+
+    client.execute("pub const x = 1").await;
+    client.execute("x + 1").await;
+
+    let reply = client
+        .request(
+            Channel::Shell,
+            "history_request",
+            serde_json::json!({
+                "output": false,
+                "raw": true,
+                "hist_access_type": "tail",
+                "n": 1,
+            }),
+        )
+        .await;
+    assert_eq!(reply.content["status"], "ok");
+    assert_eq!(
+        reply.content["history"],
+        serde_json::json!([[0, 2, "x + 1"]])
+    );
+}
+
+#[tokio::test]
+async fn test_subshells() {
+    let mut client = Client::start().await;
+
+    let reply = client
+        .request(Channel::Shell, "kernel_info_request", serde_json::json!({}))
+        .await;
+    assert_eq!(
+        reply.content["supported_features"],
+        serde_json::json!(["kernel subshells"])
+    );
+
+    let reply = client
+        .request(
+            Channel::Control,
+            "create_subshell_request",
+            serde_json::json!({}),
+        )
+        .await;
+    assert_eq!(reply.content["status"], "ok");
+    let subshell_id = reply.content["subshell_id"].as_str().unwrap().to_string();
+
+    let reply = client
+        .request(
+            Channel::Control,
+            "list_subshell_request",
+            serde_json::json!({}),
+        )
+        .await;
+    assert_eq!(
+        reply.content["subshell_id"],
+        serde_json::json!([subshell_id])
+    );
+
+    // Definitions of the parent shell aren't visible in the subshell
+    client.execute("pub const x = 1").await;
+    client.subshell_id = Some(subshell_id.clone());
+    let (reply, _) = client.execute("x + 1").await;
+    assert_eq!(reply.content["status"], "error");
+    client.subshell_id = None;
+
+    let reply = client
+        .request(
+            Channel::Control,
+            "delete_subshell_request",
+            serde_json::json!({ "subshell_id": subshell_id }),
+        )
+        .await;
+    assert_eq!(reply.content["status"], "ok");
+
+    let reply = client
+        .request(
+            Channel::Control,
+            "list_subshell_request",
+            serde_json::json!({}),
+        )
+        .await;
+    assert_eq!(reply.content["subshell_id"], serde_json::json!([]));
+
+    // Requests to a subshell that's gone get an error reply rather than none
+    client.subshell_id = Some(subshell_id.clone());
+    let (reply, iopub) = client.execute("1 + 1").await;
+    assert_eq!(reply.content["status"], "error");
+    assert_eq!(reply.content["ename"], "UnknownSubshell");
+    assert!(
+        reply.content["evalue"]
+            .as_str()
+            .unwrap()
+            .contains(&subshell_id)
+    );
+    assert_eq!(msg_types(&iopub), ["status", "status"]);
+}
+
+#[tokio::test]
+async fn test_debug_request() {
+    let mut client = Client::start().await;
+
+    let reply = client
+        .request(
+            Channel::Control,
+            "debug_request",
+            serde_json::json!({ "seq": 1, "type": "request", "command": "initialize" }),
+        )
+        .await;
+    assert_eq!(reply.content["success"], true);
+    assert_eq!(reply.content["request_seq"], 1);
+
+    let iopub = client
+        .iopub_until_idle(reply.parent_header.as_ref().unwrap())
+        .await;
+    assert_eq!(msg_types(&iopub), ["status", "debug_event", "status"]);
+    assert_eq!(iopub[1].content["event"], "initialized");
+
+    let reply = client
+        .request(
+            Channel::Control,
+            "debug_request",
+            serde_json::json!({
+                "seq": 2,
+                "type": "request",
+                "command": "dumpCell",
+                "arguments": { "code": "1 + 2" },
+            }),
+        )
+        .await;
+    let source_path = reply.content["body"]["sourcePath"].as_str().unwrap();
+    assert_eq!(std::fs::read_to_string(source_path).unwrap(), "1 + 2");
+
+    client.execute("pub const x = 1").await;
+    client.execute("1 + 2").await;
+    let reply = client
+        .request(
+            Channel::Control,
+            "debug_request",
+            serde_json::json!({ "seq": 3, "type": "request", "command": "inspectVariables" }),
+        )
+        .await;
+    let variables = reply.content["body"]["variables"].as_array().unwrap();
+    let names: Vec<&str> = variables
+        .iter()
+        .map(|v| v["name"].as_str().unwrap())
+        .collect();
+    assert!(names.contains(&"x"), "No x in {names:?}");
+    let result = variables.iter().find(|v| v["name"] == "_2").unwrap();
+    assert_eq!(result["value"], "3");
+    assert_eq!(result["type"], "Int");
+
+    let reply = client
+        .request(
+            Channel::Control,
+            "debug_request",
+            serde_json::json!({ "seq": 4, "type": "request", "command": "stepIn" }),
+        )
+        .await;
+    assert_eq!(reply.content["success"], false);
+}
+
+#[tokio::test]
+async fn test_variables_comm() {
+    let mut client = Client::start().await;
+
+    let header = client
+        .send(
+            Channel::Shell,
+            "comm_open",
+            serde_json::json!({ "comm_id": "vars", "target_name": "variable_inspector", "data": {} }),
+        )
+        .await;
+    let iopub = client.iopub_until_idle(&header).await;
+    assert_eq!(msg_types(&iopub), ["status", "comm_msg", "status"]);
+    assert_eq!(iopub[1].content["data"]["variables"], serde_json::json!([]));
+
+    let reply = client
+        .request(Channel::Shell, "comm_info_request", serde_json::json!({}))
+        .await;
+    assert_eq!(
+        reply.content["comms"]["vars"]["target_name"],
+        "variable_inspector"
+    );
+
+    // Variables are sent again after every execution
+    client.execute("pub const x = 1").await;
+    let (_, iopub) = client.execute("1 + 2").await;
+    let update = iopub
+        .iter()
+        .find(|m| m.header.msg_type == "comm_msg")
+        .unwrap();
+    assert_eq!(update.content["comm_id"], "vars");
+    assert_eq!(
+        update.content["data"]["variables"],
+        serde_json::json!([
+            { "name": "x", "kind": "constant", "type": "Int", "value": "1", "origin": "In[1]" },
+            { "name": "_2", "kind": "result", "type": "Int", "value": "3", "origin": "In[2]" },
+        ])
+    );
+
+    // Comms to unknown targets are closed right away
+    let header = client
+        .send(
+            Channel::Shell,
+            "comm_open",
+            serde_json::json!({ "comm_id": "other", "target_name": "unknown", "data": {} }),
+        )
+        .await;
+    let iopub = client.iopub_until_idle(&header).await;
+    assert_eq!(msg_types(&iopub), ["status", "comm_close", "status"]);
+}
+
+#[tokio::test]
+async fn test_execute_error() {
+    let mut client = Client::start().await;
+
+    let (reply, iopub) = client.execute("this_is_not_defined").await;
+    assert_eq!(reply.content["status"], "error");
+    assert_eq!(reply.content["ename"], "AikenError");
+
+    assert_eq!(
+        msg_types(&iopub),
+        ["status", "execute_input", "error", "status"]
+    );
+    assert_eq!(iopub[2].content["ename"], "AikenError");
+}
+
+#[tokio::test]
+async fn test_kernel_info_while_executing() {
+    let mut client = Client::start().await;
+    let (reply, _) = client
+        .execute("fn fib(n: Int) -> Int { if n < 2 { n } else { fib(n - 1) + fib(n - 2) } }")
+        .await;
+    assert_eq!(reply.content["status"], "ok");
+
+    // The kernel_info_reply comes first, the cell is still running
+    let execute = client
+        .send(
+            Channel::Shell,
+            "execute_request",
+            serde_json::json!({
+                "code": "fib(20)",
+                "silent": false,
+                "store_history": true,
+                "user_expressions": {},
+                "allow_stdin": false,
+                "stop_on_error": true,
+            }),
+        )
+        .await;
+    let info = client
+        .request(Channel::Shell, "kernel_info_request", serde_json::json!({}))
+        .await;
+    assert_eq!(info.content["status"], "ok");
+
+    let reply = recv_verified(&mut client.shell).await;
+    assert_eq!(reply.header.msg_type, "execute_reply");
+    assert_eq!(
+        reply.parent_header.as_ref().map(|p| &p.msg_id),
+        Some(&execute.msg_id)
+    );
+    assert_eq!(reply.content["status"], "ok");
+
+    // The kernel_info_request has a busy and an idle of its own
+    let iopub = client
+        .iopub_until_idle(info.parent_header.as_ref().unwrap())
+        .await;
+    assert_eq!(msg_types(&iopub), ["status", "status"]);
+}
+
+#[tokio::test]
+async fn test_malformed_messages() {
+    let mut client = Client::start().await;
+
+    // Signed, but the content isn't JSON: answered with an error instead of leaving the client
+    // waiting
+    let header = client
+        .send_raw(Channel::Shell, "execute_request", b"{\"code\": ", KEY)
+        .await;
+    let reply = recv_verified(&mut client.shell).await;
+    assert_eq!(reply.header.msg_type, "execute_reply");
+    assert_eq!(reply.parent_header.as_ref().unwrap().msg_id, header.msg_id);
+    assert_eq!(reply.content["status"], "error");
+    assert_eq!(reply.content["ename"], "InvalidMessage");
+    let iopub = client.iopub_until_idle(&header).await;
+    assert_eq!(msg_types(&iopub), ["status", "status"]);
+
+    // Badly signed messages are dropped: the next reply is the kernel_info_reply
+    client
+        .send_raw(Channel::Shell, "execute_request", b"{}", "another-key")
+        .await;
+    let reply = client
+        .request(Channel::Shell, "kernel_info_request", serde_json::json!({}))
+        .await;
+    assert_eq!(reply.content["status"], "ok");
+
+    let header = client
+        .send_raw(Channel::Control, "kernel_info_request", b"[", KEY)
+        .await;
+    let reply = recv_verified(&mut client.control).await;
+    assert_eq!(reply.header.msg_type, "kernel_info_reply");
+    assert_eq!(reply.parent_header.as_ref().unwrap().msg_id, header.msg_id);
+    assert_eq!(reply.content["ename"], "InvalidMessage");
+}
+
+#[tokio::test]
+async fn test_inspect() {
+    let mut client = Client::start().await;
+
+    client.execute("pub const x = 1").await;
+    let reply = client
+        .request(
+            Channel::Shell,
+            "inspect_request",
+            serde_json::json!({ "code": "x + 1", "cursor_pos": 1, "detail_level": 0 }),
+        )
+        .await;
+    assert_eq!(reply.content["status"], "ok");
+    assert_eq!(reply.content["found"], true);
+    let text = reply.content["data"]["text/plain"].as_str().unwrap();
+    assert!(text.contains("defined in In[1]"), "{text}");
+
+    // Nothing to inspect between names
+    let reply = client
+        .request(
+            Channel::Shell,
+            "inspect_request",
+            serde_json::json!({ "code": "x + 1", "cursor_pos": 2 }),
+        )
+        .await;
+    assert_eq!(reply.content["found"], false);
+    assert_eq!(reply.content["data"], serde_json::json!({}));
+}
+
+#[tokio::test]
+async fn test_error_replies() {
+    let mut client = Client::start().await;
+
+    // A request without a handler gets an error reply instead of none
+    let reply = client
+        .request(
+            Channel::Shell,
+            "complete_request",
+            serde_json::json!({ "code": "li", "cursor_pos": 2 }),
+        )
+        .await;
+    assert_eq!(reply.content["status"], "error");
+    assert_eq!(reply.content["ename"], "NotImplemented");
+    let iopub = client
+        .iopub_until_idle(reply.parent_header.as_ref().unwrap())
+        .await;
+    assert_eq!(msg_types(&iopub), ["status", "status"]);
+
+    // So does a request that doesn't parse
+    let reply = client
+        .request(
+            Channel::Shell,
+            "history_request",
+            serde_json::json!({ "output": "yes" }),
+        )
+        .await;
+    assert_eq!(reply.content["status"], "error");
+    assert_eq!(reply.content["ename"], "InvalidRequest");
+
+    let reply = client
+        .request(
+            Channel::Control,
+            "delete_subshell_request",
+            serde_json::json!({ "subshell_id": "nope" }),
+        )
+        .await;
+    assert_eq!(reply.content["status"], "error");
+    assert_eq!(reply.content["ename"], "SubshellNotFound");
+}
+
+#[tokio::test]
+async fn test_shutdown() {
+    let mut client = Client::start().await;
+
+    let reply = client
+        .request(
+            Channel::Control,
+            "shutdown_request",
+            serde_json::json!({ "restart": false }),
+        )
+        .await;
+    assert_eq!(reply.content["restart"], false);
+
+    // The kernel process exits on its own after replying
+    for _ in 0..100 {
+        if client.kernel.try_wait().unwrap().is_some() {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("Kernel didn't exit after shutdown_request");
+}
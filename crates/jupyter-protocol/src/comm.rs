@@ -0,0 +1,26 @@
+use serde::{Deserialize, Serialize};
+
+// DOCS: https://jupyter-client.readthedocs.io/en/latest/messaging.html#opening-a-comm
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CommOpen {
+    pub comm_id: String,     // UUID of the comm
+    pub target_name: String, // Name of the target handling the comm on the other side
+    #[serde(default)]
+    pub data: serde_json::Value,
+}
+
+// DOCS: https://jupyter-client.readthedocs.io/en/latest/messaging.html#comm-messages
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CommMsg {
+    pub comm_id: String,
+    #[serde(default)]
+    pub data: serde_json::Value,
+}
+
+// DOCS: https://jupyter-client.readthedocs.io/en/latest/messaging.html#tearing-down-comms
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CommClose {
+    pub comm_id: String,
+    #[serde(default)]
+    pub data: serde_json::Value,
+}
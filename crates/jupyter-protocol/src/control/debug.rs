@@ -0,0 +1,120 @@
+use serde::{Deserialize, Serialize};
+
+// Contents of debug messages follow the Debug Adapter Protocol (DAP)
+// DOCS: https://jupyter-client.readthedocs.io/en/latest/messaging.html#debug-request
+// DOCS: https://microsoft.github.io/debug-adapter-protocol/specification
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DebugRequest {
+    pub seq: u64,
+    #[serde(rename = "type")]
+    pub kind: String, // Always "request"
+    pub command: String,
+    #[serde(default)]
+    pub arguments: serde_json::Value,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DebugReply {
+    pub seq: u64,
+    #[serde(rename = "type")]
+    pub kind: String, // Always "response"
+    pub request_seq: u64,
+    pub success: bool,
+    pub command: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    pub body: serde_json::Value,
+}
+
+// DOCS: https://jupyter-client.readthedocs.io/en/latest/messaging.html#additions-to-the-dap
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DebugEvent {
+    pub seq: u64,
+    #[serde(rename = "type")]
+    pub kind: String, // Always "event"
+    pub event: String,
+    pub body: serde_json::Value,
+}
+
+impl DebugReply {
+    /// Successful response to `request`
+    pub fn success(seq: u64, request: &DebugRequest, body: serde_json::Value) -> Self {
+        DebugReply {
+            seq,
+            kind: "response".to_string(),
+            request_seq: request.seq,
+            success: true,
+            command: request.command.clone(),
+            message: None,
+            body,
+        }
+    }
+
+    /// Failed response to `request`, `message` tells the frontend why
+    pub fn failure(seq: u64, request: &DebugRequest, message: String) -> Self {
+        DebugReply {
+            seq,
+            kind: "response".to_string(),
+            request_seq: request.seq,
+            success: false,
+            command: request.command.clone(),
+            message: Some(message),
+            body: serde_json::Value::Object(serde_json::Map::new()),
+        }
+    }
+}
+
+impl DebugEvent {
+    pub fn new(seq: u64, event: &str, body: serde_json::Value) -> Self {
+        DebugEvent {
+            seq,
+            kind: "event".to_string(),
+            event: event.to_string(),
+            body,
+        }
+    }
+}
+
+// DOCS: https://jupyter-client.readthedocs.io/en/latest/messaging.html#dumpcell
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DumpCellArguments {
+    pub code: String, // Source of the cell
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct DumpCellBody {
+    pub source_path: String, // File the cell was dumped to
+}
+
+// DOCS: https://jupyter-client.readthedocs.io/en/latest/messaging.html#debuginfo
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct DebugInfoBody {
+    pub is_started: bool,                    // Whether the debugger is started
+    pub hash_method: String,                 // Hash method used to name the dumped cells
+    pub hash_seed: u64,                      // Seed of the hash method
+    pub tmp_file_prefix: String,             // Prefix of the dumped cells' paths
+    pub tmp_file_suffix: String,             // Suffix of the dumped cells' paths
+    pub breakpoints: Vec<serde_json::Value>, // Breakpoints currently set, by source
+    pub stopped_threads: Vec<u64>,           // Threads stopped at a breakpoint
+    pub rich_rendering: bool,                // Whether richInspectVariables is supported
+    pub exception_paths: Vec<String>,        // Exception filters supported by the debugger
+}
+
+// DOCS: https://microsoft.github.io/debug-adapter-protocol/specification#Types_Variable
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Variable {
+    pub name: String,
+    pub value: String,
+    #[serde(rename = "type")]
+    pub type_name: String,
+    pub variables_reference: u64, // Non-zero if the variable has children that can be requested
+}
+
+// DOCS: https://jupyter-client.readthedocs.io/en/latest/messaging.html#inspectvariables
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct InspectVariablesBody {
+    pub variables: Vec<Variable>,
+}
@@ -0,0 +1,9 @@
+use serde::{Deserialize, Serialize};
+
+// DOCS: https://jupyter-client.readthedocs.io/en/latest/messaging.html#kernel-interrupt
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct InterruptRequest {}
+
+// DOCS: https://jupyter-client.readthedocs.io/en/latest/messaging.html#kernel-interrupt
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct InterruptReply {}
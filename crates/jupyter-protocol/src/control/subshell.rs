@@ -0,0 +1,29 @@
+use serde::{Deserialize, Serialize};
+
+// Kernel subshells, added in protocol 5.5 (JEP 91). Shell messages addressed to a subshell
+// carry its id in `MessageHeader::subshell_id`.
+// DOCS: https://jupyter.org/enhancement-proposals/91-kernel-subshells/kernel-subshells.html
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CreateSubshellRequest {}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CreateSubshellReply {
+    pub subshell_id: String, // Id of the new subshell
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DeleteSubshellRequest {
+    pub subshell_id: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DeleteSubshellReply {}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ListSubshellRequest {}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ListSubshellReply {
+    pub subshell_id: Vec<String>, // Ids of all subshells, not including the parent shell
+}
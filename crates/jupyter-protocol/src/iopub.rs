@@ -0,0 +1,366 @@
+use serde::{Deserialize, Serialize};
+
+use crate::comm::{CommClose, CommMsg};
+use crate::control::debug::DebugEvent;
+use crate::crypto::sign_message;
+use crate::{JupyterMessage, MessageHeader, MimeBundle};
+
+// DOCS: https://jupyter-client.readthedocs.io/en/latest/messaging.html#kernel-status
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExecutionState {
+    Busy,
+    Idle,
+    Starting,
+}
+
+// DOCS: https://jupyter-client.readthedocs.io/en/latest/messaging.html#kernel-status
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Status {
+    pub execution_state: ExecutionState,
+}
+
+// DOCS: https://jupyter-client.readthedocs.io/en/latest/messaging.html#streams-stdout-stderr-etc
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Stream {
+    pub name: String, // 'stdout' or 'stderr'
+    pub text: String,
+}
+
+// DOCS: https://jupyter-client.readthedocs.io/en/latest/messaging.html#display-data
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Transient {
+    pub display_id: String, // Identifier used to update an existing display
+}
+
+// DOCS: https://jupyter-client.readthedocs.io/en/latest/messaging.html#display-data
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DisplayData {
+    pub data: MimeBundle,
+    pub metadata: serde_json::Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transient: Option<Transient>,
+}
+
+// DOCS: https://jupyter-client.readthedocs.io/en/latest/messaging.html#code-inputs
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ExecuteInput {
+    pub code: String,
+    pub execution_count: u32,
+}
+
+// DOCS: https://jupyter-client.readthedocs.io/en/latest/messaging.html#id7
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ExecuteResult {
+    pub execution_count: u32,
+    pub data: MimeBundle,
+    pub metadata: serde_json::Value,
+}
+
+// DOCS: https://jupyter-client.readthedocs.io/en/latest/messaging.html#execution-errors
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Error {
+    pub ename: String,
+    pub evalue: String,
+    pub traceback: Vec<String>,
+}
+
+// DOCS: https://jupyter-client.readthedocs.io/en/latest/messaging.html#clear-output
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ClearOutput {
+    pub wait: bool, // Wait to clear the output until new output is available
+}
+
+// DOCS: https://jupyter-client.readthedocs.io/en/latest/messaging.html#welcome-message
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct IopubWelcome {
+    pub subscription: String, // Topic the client subscribed to, "" for everything
+}
+
+fn build_pub<C: Serialize>(
+    header: MessageHeader,
+    parent_header: Option<MessageHeader>,
+    metadata: serde_json::Value,
+    content: C,
+    key: &str,
+    scheme: &str,
+) -> anyhow::Result<Vec<bytes::Bytes>> {
+    let h = serde_json::to_vec(&header)?;
+    let p = serde_json::to_vec(&parent_header)?;
+    let m = serde_json::to_vec(&metadata)?;
+    let c = serde_json::to_vec(&content)?;
+    let sig = sign_message(key, scheme, &h, &p, &m, &c)?.into_bytes();
+    Ok(vec![b"<IDS|MSG>".to_vec(), sig, h, p, m, c]
+        .into_iter()
+        .map(Into::into)
+        .collect())
+}
+
+/// Build an IOPub message that isn't a reply to any request
+fn to_iopub_unparented<C: Serialize>(
+    key: &str,
+    scheme: &str,
+    session: &str,
+    msg_type: &str,
+    content: C,
+) -> anyhow::Result<Vec<bytes::Bytes>> {
+    let header = MessageHeader::new(session.to_string(), msg_type.to_string());
+    let metadata = serde_json::Value::Object(serde_json::Map::new());
+    build_pub(header, None, metadata, content, key, scheme)
+}
+
+// DOCS: https://jupyter-client.readthedocs.io/en/latest/messaging.html#kernel-status
+/// Status broadcast outside of any request, e.g. `starting` once the sockets are bound
+pub fn kernel_status(
+    key: &str,
+    scheme: &str,
+    session: &str,
+    execution_state: ExecutionState,
+) -> anyhow::Result<Vec<bytes::Bytes>> {
+    to_iopub_unparented(key, scheme, session, "status", Status { execution_state })
+}
+
+// DOCS: https://jupyter-client.readthedocs.io/en/latest/messaging.html#welcome-message
+pub fn iopub_welcome(
+    key: &str,
+    scheme: &str,
+    session: &str,
+    subscription: &str,
+) -> anyhow::Result<Vec<bytes::Bytes>> {
+    let content = IopubWelcome {
+        subscription: subscription.to_string(),
+    };
+    to_iopub_unparented(key, scheme, session, "iopub_welcome", content)
+}
+
+impl JupyterMessage<serde_json::Value> {
+    /// Build an IOPub message of `msg_type` with this message as parent
+    fn to_iopub<C: Serialize>(
+        &self,
+        key: &str,
+        scheme: &str,
+        msg_type: &str,
+        content: C,
+    ) -> anyhow::Result<Vec<bytes::Bytes>> {
+        let header = MessageHeader::new(self.header.session.clone(), msg_type.to_string());
+        let parent = Some(self.header.clone());
+        let metadata = serde_json::Value::Object(serde_json::Map::new());
+        build_pub(header, parent, metadata, content, key, scheme)
+    }
+
+    // DOCS: https://jupyter-client.readthedocs.io/en/latest/messaging.html#kernel-status
+    pub fn to_iopub_status(
+        &self,
+        key: &str,
+        scheme: &str,
+        execution_state: ExecutionState,
+    ) -> anyhow::Result<Vec<bytes::Bytes>> {
+        self.to_iopub(key, scheme, "status", Status { execution_state })
+    }
+
+    // DOCS: https://jupyter-client.readthedocs.io/en/latest/messaging.html#code-inputs
+    pub fn to_iopub_execute_input(
+        &self,
+        key: &str,
+        scheme: &str,
+        code: &str,
+        execution_count: u32,
+    ) -> anyhow::Result<Vec<bytes::Bytes>> {
+        let content = ExecuteInput {
+            code: code.to_string(),
+            execution_count,
+        };
+        self.to_iopub(key, scheme, "execute_input", content)
+    }
+
+    // DOCS: https://jupyter-client.readthedocs.io/en/latest/messaging.html#streams-stdout-stderr-etc
+    pub fn to_iopub_stream(
+        &self,
+        key: &str,
+        scheme: &str,
+        name: &str,
+        text: &str,
+    ) -> anyhow::Result<Vec<bytes::Bytes>> {
+        let content = Stream {
+            name: name.to_string(),
+            text: text.to_string(),
+        };
+        self.to_iopub(key, scheme, "stream", content)
+    }
+
+    // DOCS: https://jupyter-client.readthedocs.io/en/latest/messaging.html#id7
+    pub fn to_iopub_execute_result(
+        &self,
+        key: &str,
+        scheme: &str,
+        execution_count: u32,
+        data: MimeBundle,
+        metadata: serde_json::Value,
+    ) -> anyhow::Result<Vec<bytes::Bytes>> {
+        let content = ExecuteResult {
+            execution_count,
+            data,
+            metadata,
+        };
+        self.to_iopub(key, scheme, "execute_result", content)
+    }
+
+    // DOCS: https://jupyter-client.readthedocs.io/en/latest/messaging.html#display-data
+    /// With a `display_id`, the output can later be replaced with `update_display_data`
+    pub fn to_iopub_display_data(
+        &self,
+        key: &str,
+        scheme: &str,
+        data: MimeBundle,
+        metadata: serde_json::Value,
+        display_id: Option<&str>,
+    ) -> anyhow::Result<Vec<bytes::Bytes>> {
+        let content = DisplayData {
+            data,
+            metadata,
+            transient: display_id.map(|display_id| Transient {
+                display_id: display_id.to_string(),
+            }),
+        };
+        self.to_iopub(key, scheme, "display_data", content)
+    }
+
+    // DOCS: https://jupyter-client.readthedocs.io/en/latest/messaging.html#update-display-data
+    pub fn to_iopub_update_display_data(
+        &self,
+        key: &str,
+        scheme: &str,
+        data: MimeBundle,
+        metadata: serde_json::Value,
+        display_id: &str,
+    ) -> anyhow::Result<Vec<bytes::Bytes>> {
+        let content = DisplayData {
+            data,
+            metadata,
+            transient: Some(Transient {
+                display_id: display_id.to_string(),
+            }),
+        };
+        self.to_iopub(key, scheme, "update_display_data", content)
+    }
+
+    // DOCS: https://jupyter-client.readthedocs.io/en/latest/messaging.html#clear-output
+    pub fn to_iopub_clear_output(
+        &self,
+        key: &str,
+        scheme: &str,
+        wait: bool,
+    ) -> anyhow::Result<Vec<bytes::Bytes>> {
+        self.to_iopub(key, scheme, "clear_output", ClearOutput { wait })
+    }
+
+    // DOCS: https://jupyter-client.readthedocs.io/en/latest/messaging.html#execution-errors
+    pub fn to_iopub_error(
+        &self,
+        key: &str,
+        scheme: &str,
+        ename: &str,
+        evalue: &str,
+        traceback: &[String],
+    ) -> anyhow::Result<Vec<bytes::Bytes>> {
+        let content = Error {
+            ename: ename.to_string(),
+            evalue: evalue.to_string(),
+            traceback: traceback.to_vec(),
+        };
+        self.to_iopub(key, scheme, "error", content)
+    }
+
+    // DOCS: https://jupyter-client.readthedocs.io/en/latest/messaging.html#debug-event
+    pub fn to_iopub_debug_event(
+        &self,
+        key: &str,
+        scheme: &str,
+        event: DebugEvent,
+    ) -> anyhow::Result<Vec<bytes::Bytes>> {
+        self.to_iopub(key, scheme, "debug_event", event)
+    }
+
+    // DOCS: https://jupyter-client.readthedocs.io/en/latest/messaging.html#comm-messages
+    pub fn to_iopub_comm_msg(
+        &self,
+        key: &str,
+        scheme: &str,
+        comm_id: &str,
+        data: serde_json::Value,
+    ) -> anyhow::Result<Vec<bytes::Bytes>> {
+        let content = CommMsg {
+            comm_id: comm_id.to_string(),
+            data,
+        };
+        self.to_iopub(key, scheme, "comm_msg", content)
+    }
+
+    // DOCS: https://jupyter-client.readthedocs.io/en/latest/messaging.html#tearing-down-comms
+    pub fn to_iopub_comm_close(
+        &self,
+        key: &str,
+        scheme: &str,
+        comm_id: &str,
+    ) -> anyhow::Result<Vec<bytes::Bytes>> {
+        let content = CommClose {
+            comm_id: comm_id.to_string(),
+            data: serde_json::Value::Object(serde_json::Map::new()),
+        };
+        self.to_iopub(key, scheme, "comm_close", content)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{JupyterMessage, MessageHeader, MimeBundle};
+
+    const KEY: &str = "key";
+    const SCHEME: &str = "hmac-sha256";
+
+    fn parent() -> JupyterMessage<serde_json::Value> {
+        JupyterMessage {
+            header: MessageHeader::new("session".to_string(), "execute_request".to_string()),
+            parent_header: None,
+            metadata: serde_json::json!({}),
+            content: serde_json::json!({}),
+        }
+    }
+
+    fn parse(frames: Vec<bytes::Bytes>) -> JupyterMessage<serde_json::Value> {
+        let frames: Vec<Vec<u8>> = frames.iter().map(|frame| frame.to_vec()).collect();
+        JupyterMessage::from_multipart(&frames, KEY, SCHEME).unwrap()
+    }
+
+    #[test]
+    fn test_display_updates() {
+        let parent = parent();
+        let mut data = MimeBundle::new();
+        data.insert("text/plain".into(), "50%".into());
+
+        let msg = parse(
+            parent
+                .to_iopub_display_data(KEY, SCHEME, data.clone(), serde_json::json!({}), None)
+                .unwrap(),
+        );
+        assert_eq!(msg.header.msg_type, "display_data");
+        assert!(msg.content.get("transient").is_none());
+
+        let msg = parse(
+            parent
+                .to_iopub_update_display_data(KEY, SCHEME, data, serde_json::json!({}), "progress")
+                .unwrap(),
+        );
+        assert_eq!(msg.header.msg_type, "update_display_data");
+        assert_eq!(msg.content["transient"]["display_id"], "progress");
+        assert_eq!(msg.content["data"]["text/plain"], "50%");
+        assert_eq!(
+            msg.parent_header.map(|p| p.msg_id),
+            Some(parent.header.msg_id.clone())
+        );
+
+        let msg = parse(parent.to_iopub_clear_output(KEY, SCHEME, true).unwrap());
+        assert_eq!(msg.header.msg_type, "clear_output");
+        assert_eq!(msg.content["wait"], true);
+    }
+}
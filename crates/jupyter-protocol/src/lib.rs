@@ -0,0 +1,115 @@
+//! Jupyter messaging protocol for Rust kernels
+//!
+//! Message types, wire format (multipart ZMQ frames) and HMAC signing as described in
+//! the Jupyter messaging spec. Nothing in here is specific to a language.
+//!
+//! DOCS: https://jupyter-client.readthedocs.io/en/latest/messaging.html
+
+use serde::{Deserialize, Serialize};
+
+pub mod comm;
+pub mod crypto;
+pub mod iopub;
+pub mod reply;
+pub mod request;
+pub mod wire;
+pub mod shell {
+    pub mod comm_info;
+    pub mod complete;
+    pub mod execute;
+    pub mod history;
+    pub mod inspect;
+    pub mod is_complete;
+    pub mod kernel_info;
+}
+pub mod control {
+    pub mod debug;
+    pub mod interrupt;
+    pub mod shutdown;
+    pub mod subshell;
+}
+pub mod stdin {
+    pub mod input;
+}
+
+/// Version of the messaging protocol implemented by this crate
+pub const PROTOCOL_VERSION: &str = "5.5";
+
+/// A MIME bundle, mapping MIME types to their representation of the same data
+pub type MimeBundle = serde_json::Map<String, serde_json::Value>;
+
+/// A fresh session id, for messages the kernel sends on its own behalf
+pub fn new_session_id() -> String {
+    uuid::Uuid::new_v4().to_string()
+}
+
+// DOCS: https://jupyter-client.readthedocs.io/en/latest/messaging.html#message-header
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MessageHeader {
+    pub msg_id: String,   // UUID for this message
+    pub session: String,  // Session UUID
+    pub username: String, // Usually "kernel"
+    pub date: String,     // ISO 8601 timestamp
+    pub msg_type: String, // "execute_request", "kernel_info_request", etc.
+    pub version: String,  // Protocol version
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subshell_id: Option<String>, // Subshell a shell message is addressed to, None for the parent shell
+}
+
+impl MessageHeader {
+    pub fn new(session: String, msg_type: String) -> Self {
+        MessageHeader {
+            msg_id: uuid::Uuid::new_v4().to_string(),
+            session,
+            username: "kernel".to_string(),
+            date: chrono::Utc::now().to_rfc3339(),
+            msg_type,
+            version: PROTOCOL_VERSION.to_string(),
+            subshell_id: None,
+        }
+    }
+}
+
+// DOCS: https://jupyter-client.readthedocs.io/en/latest/messaging.html#general-message-format
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct JupyterMessage<T> {
+    pub header: MessageHeader,                // Header for this message
+    pub parent_header: Option<MessageHeader>, // Header of the parent message
+    pub metadata: serde_json::Value,          // Metadata for this message
+    pub content: T,                           // Content specific to the message type
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ConnectionConfig {
+    pub transport: String,        // Usually "tcp"
+    pub ip: String,               // Usually "127.0.0.1"
+    pub signature_scheme: String, // "hmac-sha256"
+    pub key: String,              // For HMAC signing
+    pub control_port: u16,
+    pub shell_port: u16,
+    pub stdin_port: u16,
+    pub hb_port: u16, // heartbeat
+    pub iopub_port: u16,
+}
+
+impl ConnectionConfig {
+    pub fn shell_address(&self) -> String {
+        format!("{}://{}:{}", self.transport, self.ip, self.shell_port)
+    }
+
+    pub fn control_address(&self) -> String {
+        format!("{}://{}:{}", self.transport, self.ip, self.control_port)
+    }
+
+    pub fn stdin_address(&self) -> String {
+        format!("{}://{}:{}", self.transport, self.ip, self.stdin_port)
+    }
+
+    pub fn hb_address(&self) -> String {
+        format!("{}://{}:{}", self.transport, self.ip, self.hb_port)
+    }
+
+    pub fn iopub_address(&self) -> String {
+        format!("{}://{}:{}", self.transport, self.ip, self.iopub_port)
+    }
+}
@@ -0,0 +1,142 @@
+//! Status of replies. Every reply has a `status`: `ok` with the content of its message type,
+//! `error` with the exception that kept the kernel from handling the request, or `aborted`
+//! when the request was dropped without being handled.
+
+use serde::{Deserialize, Serialize};
+
+// DOCS: https://jupyter-client.readthedocs.io/en/latest/messaging.html#request-reply
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "status", rename_all = "lowercase")]
+pub enum Reply<T, E = ReplyError> {
+    Ok(T),
+    Error(E),
+    Aborted,
+}
+
+// DOCS: https://jupyter-client.readthedocs.io/en/latest/messaging.html#request-reply
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ReplyError {
+    pub ename: String,          // Exception name, as a string
+    pub evalue: String,         // Exception value, as a string
+    pub traceback: Vec<String>, // Traceback frames, as strings
+}
+
+impl ReplyError {
+    /// An error without traceback
+    pub fn new(ename: impl Into<String>, evalue: impl Into<String>) -> Self {
+        Self {
+            ename: ename.into(),
+            evalue: evalue.into(),
+            traceback: Vec::new(),
+        }
+    }
+}
+
+/// Message type of the reply to a request of type `msg_type`, None if it isn't a request
+pub fn reply_type(msg_type: &str) -> Option<String> {
+    msg_type
+        .strip_suffix("_request")
+        .map(|name| format!("{name}_reply"))
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::{Reply, ReplyError, reply_type};
+    use crate::shell::{
+        execute::{ExecuteError, ExecuteOk, ExecuteReply, Payload},
+        history::HistoryReply,
+    };
+
+    #[test]
+    fn test_reply_status() {
+        let ok: Reply<HistoryReply> = Reply::Ok(HistoryReply {
+            history: vec![(0, 1, "1 + 2".to_string())],
+        });
+        assert_eq!(
+            serde_json::to_value(ok).unwrap(),
+            json!({ "status": "ok", "history": [[0, 1, "1 + 2"]] })
+        );
+
+        let error: Reply<HistoryReply> = Reply::Error(ReplyError::new("NotFound", "no history"));
+        assert_eq!(
+            serde_json::to_value(error).unwrap(),
+            json!({ "status": "error", "ename": "NotFound", "evalue": "no history", "traceback": [] })
+        );
+
+        let aborted: Reply<HistoryReply> = Reply::Aborted;
+        assert_eq!(
+            serde_json::to_value(aborted).unwrap(),
+            json!({ "status": "aborted" })
+        );
+    }
+
+    #[test]
+    fn test_execute_reply() {
+        let ok: ExecuteReply = Reply::Ok(ExecuteOk {
+            execution_count: 3,
+            user_expressions: None,
+            payload: Vec::new(),
+        });
+        assert_eq!(
+            serde_json::to_value(ok).unwrap(),
+            json!({ "status": "ok", "execution_count": 3 })
+        );
+
+        let ok: ExecuteReply = Reply::Ok(ExecuteOk {
+            execution_count: 5,
+            user_expressions: None,
+            payload: vec![Payload::SetNextInput {
+                text: "pub const x = 1".to_string(),
+                replace: true,
+            }],
+        });
+        let value = serde_json::to_value(&ok).unwrap();
+        assert_eq!(
+            value,
+            json!({
+                "status": "ok",
+                "execution_count": 5,
+                "payload": [
+                    { "source": "set_next_input", "text": "pub const x = 1", "replace": true }
+                ]
+            })
+        );
+        let parsed: ExecuteReply = serde_json::from_value(value).unwrap();
+        assert!(matches!(parsed, Reply::Ok(ExecuteOk { payload, .. }) if payload.len() == 1));
+
+        let error: ExecuteReply = Reply::Error(ExecuteError {
+            execution_count: 4,
+            error: ReplyError::new("AikenError", "Unknown variable"),
+        });
+        let value = serde_json::to_value(&error).unwrap();
+        assert_eq!(
+            value,
+            json!({
+                "status": "error",
+                "execution_count": 4,
+                "ename": "AikenError",
+                "evalue": "Unknown variable",
+                "traceback": []
+            })
+        );
+        let parsed: ExecuteReply = serde_json::from_value(value).unwrap();
+        assert!(matches!(
+            parsed,
+            Reply::Error(ExecuteError {
+                execution_count: 4,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_reply_type() {
+        assert_eq!(
+            reply_type("kernel_info_request").as_deref(),
+            Some("kernel_info_reply")
+        );
+        assert_eq!(reply_type("comm_msg"), None);
+    }
+}
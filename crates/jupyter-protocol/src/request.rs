@@ -0,0 +1,128 @@
+//! Typed requests, deserialized once from a message's `msg_type` and content so kernels can
+//! route them with an exhaustive `match` instead of comparing strings.
+
+use crate::comm::{CommClose, CommMsg, CommOpen};
+use crate::control::{
+    debug::DebugRequest,
+    interrupt::InterruptRequest,
+    shutdown::ShutdownRequest,
+    subshell::{CreateSubshellRequest, DeleteSubshellRequest, ListSubshellRequest},
+};
+use crate::shell::{
+    comm_info::CommInfoRequest, complete::CompleteRequest, execute::ExecuteRequest,
+    history::HistoryRequest, inspect::InspectRequest, is_complete::IsCompleteRequest,
+    kernel_info::KernelInfoRequest,
+};
+
+// DOCS: https://jupyter-client.readthedocs.io/en/latest/messaging.html#messages-on-the-shell-router-dealer-channel
+#[derive(Debug, Clone)]
+pub enum ShellRequest {
+    KernelInfo(KernelInfoRequest),
+    Execute(ExecuteRequest),
+    Complete(CompleteRequest),
+    Inspect(InspectRequest),
+    IsComplete(IsCompleteRequest),
+    History(HistoryRequest),
+    CommInfo(CommInfoRequest),
+    CommOpen(CommOpen),
+    CommMsg(CommMsg),
+    CommClose(CommClose),
+    /// A message type this crate doesn't know about
+    Unknown(String),
+}
+
+impl ShellRequest {
+    pub fn parse(msg_type: &str, content: serde_json::Value) -> anyhow::Result<Self> {
+        Ok(match msg_type {
+            "kernel_info_request" => Self::KernelInfo(serde_json::from_value(content)?),
+            "execute_request" => Self::Execute(serde_json::from_value(content)?),
+            "complete_request" => Self::Complete(serde_json::from_value(content)?),
+            "inspect_request" => Self::Inspect(serde_json::from_value(content)?),
+            "is_complete_request" => Self::IsComplete(serde_json::from_value(content)?),
+            "history_request" => Self::History(serde_json::from_value(content)?),
+            "comm_info_request" => Self::CommInfo(serde_json::from_value(content)?),
+            "comm_open" => Self::CommOpen(serde_json::from_value(content)?),
+            "comm_msg" => Self::CommMsg(serde_json::from_value(content)?),
+            "comm_close" => Self::CommClose(serde_json::from_value(content)?),
+            other => Self::Unknown(other.to_string()),
+        })
+    }
+}
+
+// DOCS: https://jupyter-client.readthedocs.io/en/latest/messaging.html#control
+#[derive(Debug, Clone)]
+pub enum ControlRequest {
+    Shutdown(ShutdownRequest),
+    Interrupt(InterruptRequest),
+    Debug(DebugRequest),
+    KernelInfo(KernelInfoRequest),
+    CreateSubshell(CreateSubshellRequest),
+    DeleteSubshell(DeleteSubshellRequest),
+    ListSubshell(ListSubshellRequest),
+    /// A message type this crate doesn't know about
+    Unknown(String),
+}
+
+impl ControlRequest {
+    pub fn parse(msg_type: &str, content: serde_json::Value) -> anyhow::Result<Self> {
+        Ok(match msg_type {
+            "shutdown_request" => Self::Shutdown(serde_json::from_value(content)?),
+            "interrupt_request" => Self::Interrupt(serde_json::from_value(content)?),
+            "debug_request" => Self::Debug(serde_json::from_value(content)?),
+            "kernel_info_request" => Self::KernelInfo(serde_json::from_value(content)?),
+            "create_subshell_request" => Self::CreateSubshell(serde_json::from_value(content)?),
+            "delete_subshell_request" => Self::DeleteSubshell(serde_json::from_value(content)?),
+            "list_subshell_request" => Self::ListSubshell(serde_json::from_value(content)?),
+            other => Self::Unknown(other.to_string()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ControlRequest, ShellRequest};
+
+    #[test]
+    fn test_parse_shell_requests() {
+        let request = ShellRequest::parse(
+            "execute_request",
+            serde_json::json!({
+                "code": "1 + 2",
+                "silent": false,
+                "store_history": true,
+                "user_expressions": {},
+                "allow_stdin": false,
+                "stop_on_error": true,
+            }),
+        );
+        assert!(matches!(request, Ok(ShellRequest::Execute(req)) if req.code == "1 + 2"));
+
+        let request = ShellRequest::parse("kernel_info_request", serde_json::json!({}));
+        assert!(matches!(request, Ok(ShellRequest::KernelInfo(_))));
+
+        let request = ShellRequest::parse("some_future_request", serde_json::json!({}));
+        assert!(matches!(request, Ok(ShellRequest::Unknown(t)) if t == "some_future_request"));
+
+        // Known types with invalid content are errors, not unknown messages
+        let request = ShellRequest::parse("execute_request", serde_json::json!({}));
+        assert!(request.is_err());
+    }
+
+    #[test]
+    fn test_parse_control_requests() {
+        let request =
+            ControlRequest::parse("shutdown_request", serde_json::json!({"restart": true}));
+        assert!(matches!(request, Ok(ControlRequest::Shutdown(req)) if req.restart));
+
+        let request = ControlRequest::parse("interrupt_request", serde_json::json!({}));
+        assert!(matches!(request, Ok(ControlRequest::Interrupt(_))));
+
+        let request = ControlRequest::parse(
+            "delete_subshell_request",
+            serde_json::json!({"subshell_id": "abc"}),
+        );
+        assert!(
+            matches!(request, Ok(ControlRequest::DeleteSubshell(req)) if req.subshell_id == "abc")
+        );
+    }
+}
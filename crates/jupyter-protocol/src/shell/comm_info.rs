@@ -0,0 +1,21 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+// DOCS: https://jupyter-client.readthedocs.io/en/latest/messaging.html#comm-info
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CommInfoRequest {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target_name: Option<String>, // Only return comms with this target name
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CommInfo {
+    pub target_name: String,
+}
+
+// DOCS: https://jupyter-client.readthedocs.io/en/latest/messaging.html#comm-info
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CommInfoReply {
+    pub comms: HashMap<String, CommInfo>, // Open comms by comm_id
+}
@@ -0,0 +1,17 @@
+use serde::{Deserialize, Serialize};
+
+// DOCS: https://jupyter-client.readthedocs.io/en/latest/messaging.html#completion
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CompleteRequest {
+    pub code: String,      // The code context in which completion is requested
+    pub cursor_pos: usize, // The cursor position within 'code' (in unicode characters) where completion is requested
+}
+
+// DOCS: https://jupyter-client.readthedocs.io/en/latest/messaging.html#completion
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CompleteReply {
+    pub matches: Vec<String>, // The list of all matches to the completion request
+    pub cursor_start: usize,  // Start of the range of text that should be replaced by the matches
+    pub cursor_end: usize,    // End of the range of text that should be replaced by the matches
+    pub metadata: serde_json::Value, // Information that frontend plugins might use for extra display information
+}
@@ -0,0 +1,60 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::MimeBundle;
+use crate::reply::{Reply, ReplyError};
+
+// DOCS: https://jupyter-client.readthedocs.io/en/latest/messaging.html#execute
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ExecuteRequest {
+    pub code: String,                        // Source code to be executed by the kernel
+    pub silent: bool,                        // If true, execute as quietly as possible
+    pub store_history: bool,                 // If true, store this execution in the history
+    pub user_expressions: serde_json::Value, // Mapping of names to expressions to evaluate after execution
+    pub allow_stdin: bool, // If true, code running in the kernel can prompt the user for input
+    pub stop_on_error: bool, // If true, aborts the execution queue if an exception is encountered.
+}
+
+/// Reply to an execute request, which has the execution count whatever its status
+pub type ExecuteReply = Reply<ExecuteOk, ExecuteError>;
+
+// DOCS: https://jupyter-client.readthedocs.io/en/latest/messaging.html#execution-results
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ExecuteOk {
+    pub execution_count: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_expressions: Option<HashMap<String, UserExpressionResult>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub payload: Vec<Payload>,
+}
+
+/// Something for the frontend to do besides showing the outputs of the cell. Payloads are
+/// deprecated by the spec in favour of display messages, but frontends still act on these.
+// DOCS: https://jupyter-client.readthedocs.io/en/latest/messaging.html#payloads-deprecated
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "source", rename_all = "snake_case")]
+pub enum Payload {
+    /// Show `data` in the pager, from line `start`
+    Page { data: MimeBundle, start: u32 },
+    /// Put `text` in the next cell, or in the cell that ran when `replace` is set
+    SetNextInput { text: String, replace: bool },
+}
+
+// DOCS: https://jupyter-client.readthedocs.io/en/latest/messaging.html#execution-errors
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ExecuteError {
+    pub execution_count: u32,
+    #[serde(flatten)]
+    pub error: ReplyError,
+}
+
+/// Result of one of the `user_expressions` of a request
+pub type UserExpressionResult = Reply<UserExpressionValue>;
+
+// DOCS: https://jupyter-client.readthedocs.io/en/latest/messaging.html#execution-results
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct UserExpressionValue {
+    pub data: MimeBundle, // Same as display_data
+    pub metadata: serde_json::Value,
+}
@@ -0,0 +1,27 @@
+use serde::{Deserialize, Serialize};
+
+// DOCS: https://jupyter-client.readthedocs.io/en/latest/messaging.html#history
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HistoryRequest {
+    pub output: bool, // If true, also return output history in the resulting dict
+    pub raw: bool,    // If true, return the raw input history, else the transformed input
+    pub hist_access_type: String, // 'range', 'tail' or 'search'
+    #[serde(default)]
+    pub session: Option<i64>, // For 'range': the session number, can be negative to count back
+    #[serde(default)]
+    pub start: Option<i64>, // For 'range': first line
+    #[serde(default)]
+    pub stop: Option<i64>, // For 'range': last line (exclusive)
+    #[serde(default)]
+    pub n: Option<usize>, // For 'tail' and 'search': get the last n cells
+    #[serde(default)]
+    pub pattern: Option<String>, // For 'search': glob pattern to filter input history
+    #[serde(default)]
+    pub unique: bool, // For 'search': if true, don't return duplicate entries
+}
+
+// DOCS: https://jupyter-client.readthedocs.io/en/latest/messaging.html#history
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HistoryReply {
+    pub history: Vec<(i64, u32, String)>, // A list of (session, line_number, input) tuples
+}
@@ -0,0 +1,20 @@
+use serde::{Deserialize, Serialize};
+
+use crate::MimeBundle;
+
+// DOCS: https://jupyter-client.readthedocs.io/en/latest/messaging.html#introspection
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct InspectRequest {
+    pub code: String,      // The code context in which introspection is requested
+    pub cursor_pos: usize, // The cursor position within 'code' (in unicode characters) where inspection is requested
+    #[serde(default)]
+    pub detail_level: u8, // The level of detail desired (0 or 1)
+}
+
+// DOCS: https://jupyter-client.readthedocs.io/en/latest/messaging.html#introspection
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct InspectReply {
+    pub found: bool,      // Whether an object was found
+    pub data: MimeBundle, // A MIME bundle describing the object
+    pub metadata: serde_json::Value,
+}
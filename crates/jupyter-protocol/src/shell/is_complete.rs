@@ -0,0 +1,24 @@
+use serde::{Deserialize, Serialize};
+
+// DOCS: https://jupyter-client.readthedocs.io/en/latest/messaging.html#code-completeness
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct IsCompleteRequest {
+    pub code: String, // The code entered so far as a multiline string
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum CompletenessStatus {
+    Complete,
+    Incomplete,
+    Invalid,
+    Unknown,
+}
+
+// DOCS: https://jupyter-client.readthedocs.io/en/latest/messaging.html#code-completeness
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct IsCompleteReply {
+    pub status: CompletenessStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub indent: Option<String>, // If status is 'incomplete', the characters to use to indent the next line
+}
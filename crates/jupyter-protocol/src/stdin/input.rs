@@ -0,0 +1,14 @@
+use serde::{Deserialize, Serialize};
+
+// DOCS: https://jupyter-client.readthedocs.io/en/latest/messaging.html#messages-on-the-stdin-router-dealer-channel
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct InputRequest {
+    pub prompt: String, // The text to show at the prompt
+    pub password: bool, // Is the request for a password? If so, the frontend shouldn't echo input
+}
+
+// DOCS: https://jupyter-client.readthedocs.io/en/latest/messaging.html#messages-on-the-stdin-router-dealer-channel
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct InputReply {
+    pub value: String, // The string entered by the user
+}
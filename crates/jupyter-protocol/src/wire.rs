@@ -0,0 +1,203 @@
+use super::{JupyterMessage, MessageHeader, crypto::sign_message};
+
+/// Send a multipart message on `socket`, failing if it can't be built or the socket fails
+pub async fn send_bytes<U: zeromq::Socket + zeromq::SocketSend>(
+    socket: &mut U,
+    bytes_frames: Vec<bytes::Bytes>,
+) -> anyhow::Result<()> {
+    let zmq_msg = zeromq::ZmqMessage::try_from(bytes_frames)
+        .map_err(|e| anyhow::anyhow!("Failed to create reply ZmqMessage: {e}"))?;
+    socket.send(zmq_msg).await?;
+    Ok(())
+}
+
+// Find the <IDS|MSG> delimiter to support variable identity envelope
+pub fn delim_index(frames: &[Vec<u8>]) -> anyhow::Result<usize> {
+    match frames.iter().position(|f| f.as_slice() == b"<IDS|MSG>") {
+        Some(index) => Ok(index),
+        None => Err(anyhow::anyhow!(
+            "Malformed message: missing <IDS|MSG> delimiter with {} frames",
+            frames.len()
+        )),
+    }
+}
+
+/// Header of a message, after checking the message's signature. The rest of the message
+/// doesn't have to parse, so a malformed request can still be answered.
+pub fn verified_header(
+    frames: &[Vec<u8>],
+    config_key: &str,
+    config_signature_scheme: &str,
+) -> anyhow::Result<MessageHeader> {
+    let delim_index = delim_index(frames)?;
+
+    if frames.len() < delim_index + 6 {
+        return Err(anyhow::anyhow!(
+            "Invalid message format: Only {} frames!",
+            frames.len()
+        ));
+    }
+
+    super::crypto::verify_incoming_hmac(frames, config_key, config_signature_scheme, delim_index)?;
+
+    let header = serde_json::from_slice(&frames[delim_index + 2])
+        .map_err(|e| anyhow::anyhow!("Invalid header: {e}"))?;
+    Ok(header)
+}
+
+impl<T: serde::de::DeserializeOwned> JupyterMessage<T> {
+    pub fn from_multipart(
+        frames: &[Vec<u8>],
+        config_key: &str,
+        config_signature_scheme: &str,
+    ) -> anyhow::Result<Self> {
+        let header = verified_header(frames, config_key, config_signature_scheme)?;
+        let delim_index = delim_index(frames)?;
+
+        let parent_bytes = &frames[delim_index + 3];
+        let metadata_bytes = &frames[delim_index + 4];
+        let content_bytes = &frames[delim_index + 5];
+
+        let parent_header: Option<MessageHeader> =
+            if parent_bytes.is_empty() || parent_bytes == b"{}" || parent_bytes == b"null" {
+                None
+            } else {
+                Some(
+                    serde_json::from_slice(parent_bytes)
+                        .map_err(|e| anyhow::anyhow!("Invalid parent header: {e}"))?,
+                )
+            };
+
+        let metadata: serde_json::Value = if metadata_bytes.is_empty() || metadata_bytes == b"{}" {
+            serde_json::Value::Object(serde_json::Map::new())
+        } else {
+            serde_json::from_slice(metadata_bytes)
+                .map_err(|e| anyhow::anyhow!("Invalid metadata: {e}"))?
+        };
+
+        let content: T = if content_bytes.is_empty() || content_bytes == b"{}" {
+            serde_json::from_str("{}")?
+        } else {
+            serde_json::from_slice(content_bytes)
+                .map_err(|e| anyhow::anyhow!("Invalid content: {e}"))?
+        };
+
+        Ok(JupyterMessage {
+            header,
+            parent_header,
+            metadata,
+            content,
+        })
+    }
+}
+
+impl<T: serde::Serialize> JupyterMessage<T> {
+    pub fn to_envelope_multipart(
+        &self,
+        frames: Vec<Vec<u8>>,
+        delim_index: usize,
+        key: &str,
+        scheme: &str,
+    ) -> anyhow::Result<Vec<bytes::Bytes>> {
+        // Serialize parts
+        let header_bytes = serde_json::to_vec(&self.header).unwrap();
+        let parent_header_bytes = serde_json::to_vec(&self.parent_header).unwrap();
+        let metadata_bytes = serde_json::to_vec(&self.metadata).unwrap();
+        let content_bytes = serde_json::to_vec(&self.content).unwrap();
+
+        // Compute HMAC
+        let sig = sign_message(
+            key,
+            scheme,
+            &header_bytes,
+            &parent_header_bytes,
+            &metadata_bytes,
+            &content_bytes,
+        )?
+        .into_bytes();
+
+        // Build outgoing frames
+        let mut out_frames: Vec<Vec<u8>> = Vec::with_capacity(delim_index + 6);
+        out_frames.extend_from_slice(&frames[..=delim_index]);
+        out_frames.push(sig);
+        out_frames.push(header_bytes);
+        out_frames.push(parent_header_bytes);
+        out_frames.push(metadata_bytes);
+        out_frames.push(content_bytes);
+
+        Ok(out_frames.into_iter().map(|frame| frame.into()).collect())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::verified_header;
+    use crate::shell::execute::ExecuteRequest;
+    use crate::{JupyterMessage, MessageHeader};
+
+    const KEY: &str = "a0436f6c-1916-498b-8eb9-e81ab9368e84";
+    const SCHEME: &str = "hmac-sha256";
+
+    fn execute_request() -> JupyterMessage<serde_json::Value> {
+        JupyterMessage {
+            header: MessageHeader::new("session".to_string(), "execute_request".to_string()),
+            parent_header: None,
+            metadata: serde_json::json!({}),
+            content: serde_json::json!({
+                "code": "1 + 2",
+                "silent": false,
+                "store_history": true,
+                "user_expressions": {},
+                "allow_stdin": false,
+                "stop_on_error": true,
+            }),
+        }
+    }
+
+    #[test]
+    fn test_multipart_roundtrip() {
+        let identity = vec![b"client-id".to_vec(), b"<IDS|MSG>".to_vec()];
+        let frames: Vec<Vec<u8>> = execute_request()
+            .to_envelope_multipart(identity, 1, KEY, SCHEME)
+            .unwrap()
+            .into_iter()
+            .map(|frame| frame.to_vec())
+            .collect();
+
+        assert_eq!(frames.len(), 7);
+        assert_eq!(frames[0], b"client-id");
+
+        let msg = JupyterMessage::<ExecuteRequest>::from_multipart(&frames, KEY, SCHEME).unwrap();
+        assert_eq!(msg.header.msg_type, "execute_request");
+        assert_eq!(msg.content.code, "1 + 2");
+        assert!(msg.content.store_history);
+    }
+
+    #[test]
+    fn test_malformed_message() {
+        let identity = vec![b"<IDS|MSG>".to_vec()];
+        let mut msg = execute_request();
+        msg.content = serde_json::json!({ "code": 3 });
+        let frames: Vec<Vec<u8>> = msg
+            .to_envelope_multipart(identity, 0, KEY, SCHEME)
+            .unwrap()
+            .into_iter()
+            .map(|frame| frame.to_vec())
+            .collect();
+
+        // Content of the wrong shape: the header can still be recovered to answer it
+        let error = JupyterMessage::<ExecuteRequest>::from_multipart(&frames, KEY, SCHEME)
+            .unwrap_err()
+            .to_string();
+        assert!(error.starts_with("Invalid content"), "{error}");
+        let header = verified_header(&frames, KEY, SCHEME).unwrap();
+        assert_eq!(header.msg_type, "execute_request");
+
+        // Signed with another key: nothing can be trusted
+        assert!(verified_header(&frames, "another-key", SCHEME).is_err());
+
+        // Missing the delimiter or frames
+        assert!(verified_header(&frames[1..], KEY, SCHEME).is_err());
+        assert!(verified_header(&frames[..4], KEY, SCHEME).is_err());
+    }
+}